@@ -0,0 +1,34 @@
+//! Cooperative cancellation of proving.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A token cancelling the proving stages it is passed to.
+///
+/// Cancellation is cooperative: the stages check the token between units of work, such as
+/// execution checkpoints, shards and recursion proofs, and return a `Cancelled` error once the work
+/// in flight is done, with their workers shut down and their channels dropped. Clones share the
+/// same state, so the caller can keep a clone and cancel it from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token that is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the stages the token was passed to.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -1,11 +1,16 @@
 use core::mem::take;
 
 use crate::{
+    cancel::CancellationToken,
     hook::{hookify, BoxedHook, HookEnv, HookRegistry},
+    journal::CoreJournal,
+    shard_memo::ShardProofStore,
+    spill::SpillCipher,
     subproof::SubproofVerifier,
 };
 use hashbrown::HashMap;
-use std::io::Write;
+use sp1_stark::{ProgressOpts, ProgressReporter};
+use std::{io::Write, sync::Arc};
 
 use sp1_primitives::consts::fd::LOWEST_ALLOWED_FD;
 
@@ -34,6 +39,36 @@ pub struct SP1Context<'a> {
 
     /// The IO options for the [`SP1Executor`].
     pub io_options: IoOptions<'a>,
+
+    /// Caller-supplied metadata attached to the tracing spans of the prover.
+    pub trace_metadata: TraceMetadata,
+
+    /// The cipher applied to checkpoints spilled to disk while proving.
+    ///
+    /// Note: `None` writes checkpoints in plaintext.
+    pub spill_cipher: Option<&'a dyn SpillCipher>,
+
+    /// The number of execution shards after which proving stops, producing a proof of a prefix
+    /// of the execution.
+    ///
+    /// Note: `None` proves the whole execution. Does nothing while executing.
+    pub shard_limit: Option<u32>,
+    /// The store of shard proofs reused for byte-identical shards, see [`ShardProofStore`].
+    ///
+    /// Note: `None` proves every shard. Does nothing while executing.
+    pub shard_proof_store: Option<&'a dyn ShardProofStore>,
+    /// The journal the core prover persists its progress to, see [`CoreJournal`].
+    ///
+    /// Note: `None` persists nothing. Does nothing while executing.
+    pub core_journal: Option<&'a CoreJournal>,
+    /// The token cancelling the core prover, see [`CancellationToken`].
+    ///
+    /// Note: `None` never cancels. Does nothing while executing.
+    pub cancellation: Option<CancellationToken>,
+    /// The reporter of the progress of the core prover, see [`ProgressReporter`].
+    ///
+    /// Note: Does nothing while executing.
+    pub progress: ProgressOpts,
 }
 
 impl Default for SP1Context<'_> {
@@ -51,6 +86,13 @@ pub struct SP1ContextBuilder<'a> {
     deferred_proof_verification: bool,
     calculate_gas: bool,
     io_options: IoOptions<'a>,
+    trace_metadata: TraceMetadata,
+    spill_cipher: Option<&'a dyn SpillCipher>,
+    shard_limit: Option<u32>,
+    shard_proof_store: Option<&'a dyn ShardProofStore>,
+    core_journal: Option<&'a CoreJournal>,
+    cancellation: Option<CancellationToken>,
+    progress: ProgressOpts,
 }
 
 impl Default for SP1ContextBuilder<'_> {
@@ -64,6 +106,13 @@ impl Default for SP1ContextBuilder<'_> {
             deferred_proof_verification: true,
             calculate_gas: true,
             io_options: IoOptions::default(),
+            trace_metadata: TraceMetadata::default(),
+            spill_cipher: None,
+            shard_limit: None,
+            shard_proof_store: None,
+            core_journal: None,
+            cancellation: None,
+            progress: ProgressOpts::default(),
         }
     }
 }
@@ -76,6 +125,42 @@ impl<'a> SP1Context<'a> {
     }
 }
 
+impl SP1Context<'static> {
+    /// Narrow the lifetime of a context that borrows nothing, e.g. to hand it to a prover that is
+    /// not `'static`.
+    #[must_use]
+    pub fn scoped<'a>(self) -> SP1Context<'a> {
+        let hook_registry = self.hook_registry.map(|registry| HookRegistry {
+            table: registry
+                .table
+                .into_iter()
+                .map(|(fd, hook)| (fd, hook as BoxedHook<'a>))
+                .collect(),
+        });
+        let IoOptions { stdout, stderr, public_values, output } = self.io_options;
+        SP1Context {
+            hook_registry,
+            subproof_verifier: self.subproof_verifier,
+            max_cycles: self.max_cycles,
+            deferred_proof_verification: self.deferred_proof_verification,
+            calculate_gas: self.calculate_gas,
+            io_options: IoOptions {
+                stdout: stdout.map(|w| w as &mut dyn IoWriter),
+                stderr: stderr.map(|w| w as &mut dyn IoWriter),
+                public_values: public_values.map(|w| w as &mut dyn IoWriter),
+                output: output.map(|w| w as &mut dyn IoWriter),
+            },
+            trace_metadata: self.trace_metadata,
+            spill_cipher: self.spill_cipher,
+            shard_limit: self.shard_limit,
+            shard_proof_store: self.shard_proof_store,
+            core_journal: self.core_journal,
+            cancellation: self.cancellation,
+            progress: self.progress,
+        }
+    }
+}
+
 impl<'a> SP1ContextBuilder<'a> {
     /// Create a new [`SP1ContextBuilder`].
     ///
@@ -126,6 +211,13 @@ impl<'a> SP1ContextBuilder<'a> {
             deferred_proof_verification,
             calculate_gas,
             io_options: take(&mut self.io_options),
+            trace_metadata: take(&mut self.trace_metadata),
+            spill_cipher: take(&mut self.spill_cipher),
+            shard_limit: take(&mut self.shard_limit),
+            shard_proof_store: take(&mut self.shard_proof_store),
+            core_journal: take(&mut self.core_journal),
+            cancellation: take(&mut self.cancellation),
+            progress: take(&mut self.progress),
         }
     }
 
@@ -176,6 +268,44 @@ impl<'a> SP1ContextBuilder<'a> {
         self
     }
 
+    /// Encrypt the checkpoints spilled to disk while proving with `spill_cipher`.
+    pub fn spill_cipher(&mut self, spill_cipher: &'a dyn SpillCipher) -> &mut Self {
+        self.spill_cipher = Some(spill_cipher);
+        self
+    }
+
+    /// Reuse the proofs in `shard_proof_store` for shards identical to ones already proven, and
+    /// store the proofs of the others.
+    pub fn shard_proof_store(&mut self, shard_proof_store: &'a dyn ShardProofStore) -> &mut Self {
+        self.shard_proof_store = Some(shard_proof_store);
+        self
+    }
+
+    /// Persist the progress of the core prover to `core_journal`, so that a job that crashed can
+    /// resume from it.
+    pub fn core_journal(&mut self, core_journal: &'a CoreJournal) -> &mut Self {
+        self.core_journal = Some(core_journal);
+        self
+    }
+
+    /// Stop proving with a `Cancelled` error once `cancellation` is cancelled.
+    pub fn cancellation(&mut self, cancellation: CancellationToken) -> &mut Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Report the progress of the core prover to `reporter`.
+    pub fn progress(&mut self, reporter: Arc<dyn ProgressReporter>) -> &mut Self {
+        self.progress = ProgressOpts::new(reporter);
+        self
+    }
+
+    /// Stop proving after `shard_limit` execution shards, producing an incomplete proof.
+    pub fn shard_limit(&mut self, shard_limit: u32) -> &mut Self {
+        self.shard_limit = Some(shard_limit);
+        self
+    }
+
     /// Set the maximum number of cpu cycles to use for execution.
     /// `report.total_instruction_count()` will be less than or equal to `max_cycles`.
     pub fn max_cycles(&mut self, max_cycles: u64) -> &mut Self {
@@ -200,6 +330,57 @@ impl<'a> SP1ContextBuilder<'a> {
         self.io_options.stderr = Some(writer);
         self
     }
+
+    /// Set the job id recorded on the tracing spans of the prover.
+    pub fn job_id(&mut self, job_id: impl Into<String>) -> &mut Self {
+        self.trace_metadata.job_id = Some(job_id.into());
+        self
+    }
+
+    /// Set the tenant id recorded on the tracing spans of the prover.
+    pub fn tenant_id(&mut self, tenant_id: impl Into<String>) -> &mut Self {
+        self.trace_metadata.tenant_id = Some(tenant_id.into());
+        self
+    }
+
+    /// Set the writer the public values are streamed to during execution.
+    pub fn public_values<W: IoWriter>(&mut self, writer: &'a mut W) -> &mut Self {
+        self.io_options.public_values = Some(writer);
+        self
+    }
+
+    /// Set the writer the output stream of the program is written to during execution.
+    pub fn output<W: IoWriter>(&mut self, writer: &'a mut W) -> &mut Self {
+        self.io_options.output = Some(writer);
+        self
+    }
+}
+
+/// Caller-supplied metadata identifying a proving job in logs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceMetadata {
+    /// The id of the job.
+    pub job_id: Option<String>,
+    /// The id of the tenant the job belongs to.
+    pub tenant_id: Option<String>,
+}
+
+impl TraceMetadata {
+    /// A span recording the metadata.
+    ///
+    /// Spans and events created while it is entered, including those of worker threads that
+    /// inherit the current span, are attributed to it. The span is disabled if no metadata is set.
+    #[must_use]
+    pub fn span(&self) -> tracing::Span {
+        if self.job_id.is_none() && self.tenant_id.is_none() {
+            return tracing::Span::none();
+        }
+        tracing::info_span!(
+            "job",
+            job_id = self.job_id.as_deref(),
+            tenant_id = self.tenant_id.as_deref()
+        )
+    }
 }
 
 /// The IO options for the [`SP1Executor`].
@@ -211,11 +392,19 @@ pub struct IoOptions<'a> {
     pub stdout: Option<&'a mut dyn IoWriter>,
     /// A writer to redirect `stderr` to.
     pub stderr: Option<&'a mut dyn IoWriter>,
+    /// A writer to stream the public values to as they are committed, instead of keeping them in
+    /// memory.
+    ///
+    /// Only used by execution: the prover needs the public values in memory.
+    pub public_values: Option<&'a mut dyn IoWriter>,
+    /// A writer to stream the output of the program to, see `sp1_zkvm::io::output`. Without one,
+    /// the output is discarded.
+    pub output: Option<&'a mut dyn IoWriter>,
 }
 
 impl Clone for IoOptions<'_> {
     fn clone(&self) -> Self {
-        IoOptions { stdout: None, stderr: None }
+        IoOptions { stdout: None, stderr: None, public_values: None, output: None }
     }
 }
 
@@ -228,7 +417,7 @@ impl<W: Write + Send> IoWriter for W {}
 
 #[cfg(test)]
 mod tests {
-    use crate::{subproof::NoOpSubproofVerifier, SP1Context};
+    use crate::{subproof::NoOpSubproofVerifier, SP1Context, TraceMetadata};
 
     #[test]
     fn defaults() {
@@ -268,4 +457,26 @@ mod tests {
             SP1Context::builder().subproof_verifier(&verifier).build();
         assert!(subproof_verifier.is_some());
     }
+
+    #[test]
+    fn trace_metadata() {
+        let SP1Context { trace_metadata, .. } = SP1Context::builder().build();
+        assert_eq!(trace_metadata, TraceMetadata::default());
+        assert!(trace_metadata.span().is_none());
+
+        let SP1Context { trace_metadata, .. } =
+            SP1Context::builder().job_id("job-42").tenant_id("acme").build();
+        assert_eq!(trace_metadata.job_id.as_deref(), Some("job-42"));
+        assert_eq!(trace_metadata.tenant_id.as_deref(), Some("acme"));
+    }
+
+    #[test]
+    fn scoped() {
+        let context: SP1Context<'static> =
+            SP1Context::builder().hook(30, |_, _| vec![]).max_cycles(10).job_id("job-42").build();
+        let SP1Context { hook_registry, max_cycles, trace_metadata, .. } = context.scoped();
+        assert!(hook_registry.unwrap().table.contains_key(&30));
+        assert_eq!(max_cycles, Some(10));
+        assert_eq!(trace_metadata.job_id.as_deref(), Some("job-42"));
+    }
 }
@@ -10,6 +10,13 @@ use eyre::OptionExt;
 use hashbrown::HashMap;
 use sp1_primitives::consts::{BABYBEAR_PRIME, MAXIMUM_MEMORY_SIZE, WORD_SIZE};
 
+use crate::GuestSymbols;
+
+/// The allocator entrypoints, in order of preference. The global allocator shims cover every
+/// allocator, while `sys_alloc_aligned` is only called by the bump allocator.
+const ALLOCATOR_SYMBOLS: [&[&str]; 2] =
+    [&["__rust_alloc", "__rust_alloc_zeroed"], &["sys_alloc_aligned"]];
+
 /// RISC-V 32IM ELF (Executable and Linkable Format) File.
 ///
 /// This file represents a binary in the ELF format, specifically the RISC-V 32IM architecture
@@ -29,6 +36,8 @@ pub(crate) struct Elf {
     pub(crate) pc_base: u32,
     /// The initial memory image, useful for global constants.
     pub(crate) memory_image: HashMap<u32, u32>,
+    /// The addresses of the guest symbols used to report memory usage.
+    pub(crate) guest_symbols: GuestSymbols,
 }
 
 impl Elf {
@@ -40,7 +49,13 @@ impl Elf {
         pc_base: u32,
         memory_image: HashMap<u32, u32>,
     ) -> Self {
-        Self { instructions, pc_start, pc_base, memory_image }
+        Self {
+            instructions,
+            pc_start,
+            pc_base,
+            memory_image,
+            guest_symbols: GuestSymbols { allocators: Vec::new(), heap_start: None },
+        }
     }
 
     /// Parse the ELF file into a vector of 32-bit encoded instructions and the first memory
@@ -199,6 +214,32 @@ impl Elf {
             eyre::bail!("base address is not found");
         }
 
-        Ok(Elf::new(instructions, entry, base_address.unwrap(), image))
+        let mut elf_file = Elf::new(instructions, entry, base_address.unwrap(), image);
+        elf_file.guest_symbols = guest_symbols(&elf);
+        Ok(elf_file)
+    }
+}
+
+/// Look up the allocator entrypoints and the start of the heap in the symbol table, if any.
+///
+/// Stripped ELFs have no symbol table, in which case heap usage is not reported.
+fn guest_symbols(elf: &ElfBytes<'_, LittleEndian>) -> GuestSymbols {
+    let Ok(Some((symbols, strings))) = elf.symbol_table() else {
+        return GuestSymbols::default();
+    };
+    let mut addresses = HashMap::new();
+    for symbol in symbols.iter() {
+        if let Ok(name) = strings.get(symbol.st_name as usize) {
+            if let Ok(address) = u32::try_from(symbol.st_value) {
+                addresses.insert(name, address);
+            }
+        }
     }
+
+    let allocators = ALLOCATOR_SYMBOLS
+        .iter()
+        .map(|names| names.iter().filter_map(|name| addresses.get(name).copied()).collect())
+        .find(|found: &Vec<u32>| !found.is_empty())
+        .unwrap_or_default();
+    GuestSymbols { allocators, heap_start: addresses.get("_end").copied() }
 }
@@ -0,0 +1,244 @@
+//! Exporting the event counts of executions in a columnar format.
+//!
+//! An [`EventCountTable`] flattens the [`RecordEstimator`] of many executions into one row per
+//! execution, shard and AIR with a nonzero number of events, so that the workload of the
+//! executions can be analyzed with dataframe tools. The table serializes with serde, and with the
+//! `parquet` feature it converts to an Arrow record batch and writes Parquet files.
+//!
+//! The columns are, in order:
+//!
+//! - `execution`: the id of the execution, as given to [`EventCountTable::push`].
+//! - `kind`: the kind of the shard, see [`ShardKind::as_str`].
+//! - `shard`: the index of the shard among the shards of its kind, and for precompile shards of the
+//!   same AIR.
+//! - `air`: the name of the AIR, see [`RiscvAirId::as_str`].
+//! - `events`: the estimated number of events.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{estimator::RecordEstimator, RiscvAirId};
+
+/// The version of the columns of an [`EventCountTable`].
+///
+/// This should be bumped whenever a column is added, removed or changes meaning.
+pub const EVENT_COUNT_TABLE_VERSION: u32 = 1;
+
+/// The kind of the shard a row of an [`EventCountTable`] counts the events of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShardKind {
+    /// A core shard.
+    Core,
+    /// A precompile shard, whose events are those of a single precompile AIR and its local
+    /// memory.
+    Precompile,
+    /// The global memory initialization and finalization of the whole program.
+    GlobalMemory,
+}
+
+impl ShardKind {
+    /// The name of the kind in the `kind` column.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Core => "core",
+            Self::Precompile => "precompile",
+            Self::GlobalMemory => "global_memory",
+        }
+    }
+}
+
+/// The event counts of executions, one row per execution, shard and AIR, see the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventCountTable {
+    /// The version of the columns, see [`EVENT_COUNT_TABLE_VERSION`].
+    pub version: u32,
+    /// The `execution` column.
+    pub execution: Vec<u64>,
+    /// The `kind` column.
+    pub kind: Vec<ShardKind>,
+    /// The `shard` column.
+    pub shard: Vec<u32>,
+    /// The `air` column.
+    pub air: Vec<RiscvAirId>,
+    /// The `events` column.
+    pub events: Vec<u64>,
+}
+
+impl EventCountTable {
+    /// Creates an empty table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { version: EVENT_COUNT_TABLE_VERSION, ..Self::default() }
+    }
+
+    /// The number of rows.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether the table has no rows.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Append the event counts of the execution estimated by `estimator`, with id `execution`.
+    ///
+    /// Precompile shards count their local memory events as events of
+    /// [`RiscvAirId::MemoryLocal`].
+    pub fn push(&mut self, execution: u64, estimator: &RecordEstimator) {
+        for (shard, counts) in estimator.core_records.iter().enumerate() {
+            for (air, &events) in counts {
+                self.push_row(execution, ShardKind::Core, shard, air, events);
+            }
+        }
+        for (air, shards) in &estimator.precompile_records {
+            for (shard, &(events, local_memory_events)) in shards.iter().enumerate() {
+                self.push_row(execution, ShardKind::Precompile, shard, air, events);
+                self.push_row(
+                    execution,
+                    ShardKind::Precompile,
+                    shard,
+                    RiscvAirId::MemoryLocal,
+                    local_memory_events,
+                );
+            }
+        }
+        self.push_row(
+            execution,
+            ShardKind::GlobalMemory,
+            0,
+            RiscvAirId::MemoryGlobalInit,
+            estimator.memory_global_init_events,
+        );
+        self.push_row(
+            execution,
+            ShardKind::GlobalMemory,
+            0,
+            RiscvAirId::MemoryGlobalFinalize,
+            estimator.memory_global_finalize_events,
+        );
+    }
+
+    fn push_row(
+        &mut self,
+        execution: u64,
+        kind: ShardKind,
+        shard: usize,
+        air: RiscvAirId,
+        events: u64,
+    ) {
+        if events == 0 {
+            return;
+        }
+        self.execution.push(execution);
+        self.kind.push(kind);
+        self.shard.push(shard as u32);
+        self.air.push(air);
+        self.events.push(events);
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_export {
+    use std::{io::Write, sync::Arc};
+
+    use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt32Array, UInt64Array};
+    use arrow_schema::{ArrowError, DataType, Field, Schema};
+    use parquet::{arrow::ArrowWriter, errors::ParquetError, file::properties::WriterProperties};
+
+    use super::{EventCountTable, EVENT_COUNT_TABLE_VERSION};
+
+    impl EventCountTable {
+        /// The Arrow schema of the table, with the version of the columns in its metadata.
+        #[must_use]
+        pub fn schema() -> Schema {
+            Schema::new(vec![
+                Field::new("execution", DataType::UInt64, false),
+                Field::new("kind", DataType::Utf8, false),
+                Field::new("shard", DataType::UInt32, false),
+                Field::new("air", DataType::Utf8, false),
+                Field::new("events", DataType::UInt64, false),
+            ])
+            .with_metadata([("version".to_string(), EVENT_COUNT_TABLE_VERSION.to_string())].into())
+        }
+
+        /// The table as an Arrow record batch with schema [`Self::schema`].
+        pub fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(UInt64Array::from(self.execution.clone())),
+                Arc::new(self.kind.iter().map(|kind| Some(kind.as_str())).collect::<StringArray>()),
+                Arc::new(UInt32Array::from(self.shard.clone())),
+                Arc::new(self.air.iter().map(|air| Some(air.as_str())).collect::<StringArray>()),
+                Arc::new(UInt64Array::from(self.events.clone())),
+            ];
+            RecordBatch::try_new(Arc::new(Self::schema()), columns)
+        }
+
+        /// Write the table to `writer` as a Parquet file.
+        pub fn write_parquet<W: Write + Send>(&self, writer: W) -> Result<(), ParquetError> {
+            let batch = self.to_record_batch()?;
+            let mut writer =
+                ArrowWriter::try_new(writer, batch.schema(), Some(WriterProperties::default()))?;
+            writer.write(&batch)?;
+            writer.close()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_count_table() {
+        let mut estimator = RecordEstimator::default();
+        let mut core = enum_map::EnumMap::default();
+        core[RiscvAirId::Cpu] = 10;
+        core[RiscvAirId::AddSub] = 4;
+        estimator.core_records = vec![core, enum_map::EnumMap::default()];
+        estimator.precompile_records[RiscvAirId::ShaCompress] = vec![(2, 0), (1, 3)];
+        estimator.memory_global_finalize_events = 5;
+
+        let mut table = EventCountTable::new();
+        table.push(7, &estimator);
+
+        // Rows without events are skipped.
+        let rows = (0..table.len())
+            .map(|i| (table.kind[i], table.shard[i], table.air[i], table.events[i]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                (ShardKind::Core, 0, RiscvAirId::Cpu, 10),
+                (ShardKind::Core, 0, RiscvAirId::AddSub, 4),
+                (ShardKind::Precompile, 0, RiscvAirId::ShaCompress, 2),
+                (ShardKind::Precompile, 1, RiscvAirId::ShaCompress, 1),
+                (ShardKind::Precompile, 1, RiscvAirId::MemoryLocal, 3),
+                (ShardKind::GlobalMemory, 0, RiscvAirId::MemoryGlobalFinalize, 5),
+            ]
+        );
+        assert!(table.execution.iter().all(|&execution| execution == 7));
+        assert_eq!(table.version, EVENT_COUNT_TABLE_VERSION);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_write_parquet() {
+        let mut estimator = RecordEstimator::default();
+        estimator.memory_global_init_events = 3;
+        estimator.memory_global_finalize_events = 5;
+        let mut table = EventCountTable::new();
+        table.push(1, &estimator);
+
+        let batch = table.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().metadata()["version"], EVENT_COUNT_TABLE_VERSION.to_string());
+        let mut bytes = Vec::new();
+        table.write_parquet(&mut bytes).unwrap();
+        assert!(bytes.starts_with(b"PAR1"));
+    }
+}
@@ -9,9 +9,13 @@ use crate::profiler::Profiler;
 use clap::ValueEnum;
 use enum_map::EnumMap;
 use hashbrown::HashMap;
+use p3_maybe_rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use sp1_primitives::consts::BABYBEAR_PRIME;
-use sp1_stark::{air::PublicValues, SP1CoreOpts};
+use sp1_stark::{
+    air::PublicValues, baby_bear_poseidon2::BabyBearPoseidon2, MachineVerificationError,
+    SP1CoreOpts,
+};
 use strum::IntoEnumIterator;
 use thiserror::Error;
 
@@ -32,7 +36,7 @@ use crate::{
     memory::{Entry, Memory},
     pad_rv32im_event_counts,
     record::{ExecutionRecord, MemoryAccessRecord},
-    report::ExecutionReport,
+    report::{ExecutionReport, STACK_TOP},
     state::{ExecutionState, ForkState},
     subproof::SubproofVerifier,
     syscalls::{default_syscall_map, Syscall, SyscallCode, SyscallContext},
@@ -120,6 +124,12 @@ pub struct Executor<'a> {
     /// The maximum number of shards to execute at once.
     pub shard_batch_size: u32,
 
+    /// The clock a shard must reach before a phase marker ends it, if phase markers are respected.
+    pub min_phase_shard_clk: Option<u32>,
+
+    /// Whether a phase marker asked to end the current shard.
+    pub phase_boundary: bool,
+
     /// The maximum number of cycles for a syscall.
     pub max_syscall_cycles: u32,
 
@@ -187,8 +197,16 @@ pub struct Executor<'a> {
     /// The options for the IO.
     pub io_options: IoOptions<'a>,
 
+    /// The input chunks, read once the input stream is exhausted. They are shared by the executors
+    /// of an execution rather than copied into their states, so checkpoints only keep their ptr.
+    pub input_chunks: Vec<Arc<Vec<u8>>>,
+
     /// Temporary event counts for the current shard. This is a field to reuse memory.
     event_counts: EnumMap<RiscvAirId, u64>,
+
+    /// The outcome of verifying the deferred proofs ahead of their syscalls, by index in the proof
+    /// stream, once the first of them is reached.
+    preverified_proofs: Option<Vec<Result<(), MachineVerificationError<BabyBearPoseidon2>>>>,
 }
 
 /// The different modes the executor can run in.
@@ -331,6 +349,8 @@ impl<'a> Executor<'a> {
             memory_accesses: MemoryAccessRecord::default(),
             shard_size: (opts.shard_size as u32) * 4,
             shard_batch_size: opts.shard_batch_size as u32,
+            min_phase_shard_clk: opts.min_phase_shard_size.map(|size| (size as u32) * 4),
+            phase_boundary: false,
             cycle_tracker: HashMap::new(),
             io_buf: HashMap::new(),
             #[cfg(feature = "profiling")]
@@ -360,6 +380,8 @@ impl<'a> Executor<'a> {
             lde_size_threshold: 0,
             event_counts: EnumMap::default(),
             io_options: context.io_options,
+            input_chunks: Vec::new(),
+            preverified_proofs: None,
         }
     }
 
@@ -383,6 +405,39 @@ impl<'a> Executor<'a> {
         HookEnv { runtime: self }
     }
 
+    /// Verify the deferred proof at `index` of the proof stream against the verification key hash
+    /// and committed value digest passed to its syscall.
+    ///
+    /// The first call verifies the parts of the remaining proofs that do not depend on the syscall
+    /// values in parallel, see [`SubproofVerifier::preverify_deferred_proof`].
+    pub(crate) fn verify_deferred_proof(
+        &mut self,
+        verifier: &dyn SubproofVerifier,
+        index: usize,
+        vk_hash: [u32; 8],
+        committed_value_digest: [u32; 8],
+    ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+        let proof_stream = &self.state.proof_stream;
+        let preverified =
+            self.preverified_proofs.get_or_insert_with(|| {
+                tracing::debug!("verifying {} deferred proofs", proof_stream.len() - index);
+                proof_stream
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, (proof, vk))| {
+                        if i < index {
+                            Ok(())
+                        } else {
+                            verifier.preverify_deferred_proof(proof, vk)
+                        }
+                    })
+                    .collect()
+            });
+        std::mem::replace(&mut preverified[index], Ok(()))?;
+        let (proof, vk) = &proof_stream[index];
+        verifier.check_deferred_proof(proof, vk, vk_hash, committed_value_digest)
+    }
+
     /// Recover runtime state from a program and existing execution state.
     #[must_use]
     pub fn recover(program: Program, state: ExecutionState, opts: SP1CoreOpts) -> Self {
@@ -1687,6 +1742,12 @@ impl<'a> Executor<'a> {
             }
             _ => unreachable!(),
         };
+        if self.print_report &&
+            !self.unconstrained &&
+            self.program.guest_symbols.allocators.contains(&next_pc)
+        {
+            self.report.memory_usage.heap_allocations += 1;
+        }
         (a, b, c, next_pc)
     }
 
@@ -1800,7 +1861,11 @@ impl<'a> Executor<'a> {
                 }
             }
 
-            if cpu_exit || !shape_match_found {
+            // End the shard at a phase marker, so that the shards of a phase do not depend on the
+            // phases before it.
+            let phase_exit = std::mem::take(&mut self.phase_boundary);
+
+            if cpu_exit || !shape_match_found || phase_exit {
                 self.bump_record();
                 self.state.current_shard += 1;
                 self.state.clk = 0;
@@ -2076,6 +2141,18 @@ impl<'a> Executor<'a> {
                     tracing::error!("failed to flush stderr override: {e}");
                 }
             }
+
+            if let Some(ref mut w) = self.io_options.public_values {
+                if let Err(e) = w.flush() {
+                    tracing::error!("failed to flush public values writer: {e}");
+                }
+            }
+
+            if let Some(ref mut w) = self.io_options.output {
+                if let Err(e) = w.flush() {
+                    tracing::error!("failed to flush output writer: {e}");
+                }
+            }
         }
 
         // Push the remaining execution record, if there are any CPU events.
@@ -2108,6 +2185,22 @@ impl<'a> Executor<'a> {
         Ok(done)
     }
 
+    /// Record the stack and heap watermarks from the touched memory addresses.
+    fn report_memory_usage(&mut self) {
+        let heap_start = self.program.guest_symbols.heap_start.or_else(|| {
+            self.program.memory_image.keys().max().map(|addr| addr + 4).filter(|&a| a > STACK_TOP)
+        });
+        let usage = &mut self.report.memory_usage;
+        usage.heap_start = heap_start;
+        for addr in self.state.memory.page_table.keys() {
+            if addr < STACK_TOP {
+                usage.stack_low = Some(usage.stack_low.map_or(addr, |low| low.min(addr)));
+            } else if heap_start.is_some_and(|start| addr >= start) {
+                usage.heap_high = Some(usage.heap_high.map_or(addr, |high| high.max(addr)));
+            }
+        }
+    }
+
     fn postprocess(&mut self) {
         // Flush remaining stdout/stderr
         for (fd, buf) in &self.io_buf {
@@ -2132,10 +2225,14 @@ impl<'a> Executor<'a> {
             );
         }
 
-        if !self.state.input_stream.is_empty() {
+        if self.next_input_len().is_some() {
             tracing::warn!("Not all input bytes were read.");
         }
 
+        if self.print_report {
+            self.report_memory_usage();
+        }
+
         if let Some(estimator) = &mut self.record_estimator {
             // Mirror the logic below.
             // Register 0 is always init and finalized, so we add 1
@@ -2348,15 +2445,27 @@ pub const fn align(addr: u32) -> u32 {
 #[cfg(test)]
 mod tests {
 
-    use sp1_stark::SP1CoreOpts;
-    use sp1_zkvm::syscalls::SHA_COMPRESS;
+    use std::{
+        collections::BTreeSet,
+        panic::{self, AssertUnwindSafe},
+        sync::Mutex,
+    };
+
+    use p3_baby_bear::BabyBear;
+    use p3_field::{AbstractField, PrimeField32};
+    use sp1_primitives::consts::fd::{FD_OUTPUT, FD_PHASE, FD_PUBLIC_VALUES};
+    use sp1_stark::{
+        baby_bear_poseidon2::BabyBearPoseidon2, MachineVerificationError, SP1CoreOpts,
+        StarkVerifyingKey,
+    };
+    use sp1_zkvm::syscalls::{SHA_COMPRESS, VERIFY_SP1_PROOF, WRITE};
 
     use crate::programs::tests::{
         fibonacci_program, panic_program, secp256r1_add_program, secp256r1_double_program,
         simple_memory_program, simple_program, ssz_withdrawals_program, u256xu2048_mul_program,
     };
 
-    use crate::{Register, SP1Context};
+    use crate::{subproof::SubproofVerifier, Register, SP1Context, SP1ReduceProof};
 
     use super::{Executor, Instruction, Opcode, Program};
 
@@ -3036,4 +3145,196 @@ mod tests {
         let mut runtime = Executor::new(program, SP1CoreOpts::default());
         runtime.run().unwrap();
     }
+
+    /// Records the order in which the parts of the deferred proofs are verified, telling the proofs
+    /// apart by the start pc of their verifying key.
+    struct RecordingVerifier {
+        calls: Mutex<Vec<(&'static str, u32)>>,
+        invalid: Option<u32>,
+    }
+
+    impl SubproofVerifier for RecordingVerifier {
+        fn verify_deferred_proof(
+            &self,
+            _: &SP1ReduceProof<BabyBearPoseidon2>,
+            _: &StarkVerifyingKey<BabyBearPoseidon2>,
+            _: [u32; 8],
+            _: [u32; 8],
+        ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+            unreachable!("the proofs are verified in two parts")
+        }
+
+        fn preverify_deferred_proof(
+            &self,
+            _: &SP1ReduceProof<BabyBearPoseidon2>,
+            vk: &StarkVerifyingKey<BabyBearPoseidon2>,
+        ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+            let index = vk.pc_start.as_canonical_u32();
+            self.calls.lock().unwrap().push(("preverify", index));
+            if self.invalid == Some(index) {
+                return Err(MachineVerificationError::InvalidPublicValues("invalid proof"));
+            }
+            Ok(())
+        }
+
+        fn check_deferred_proof(
+            &self,
+            _: &SP1ReduceProof<BabyBearPoseidon2>,
+            vk: &StarkVerifyingKey<BabyBearPoseidon2>,
+            _: [u32; 8],
+            _: [u32; 8],
+        ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+            self.calls.lock().unwrap().push(("check", vk.pc_start.as_canonical_u32()));
+            Ok(())
+        }
+    }
+
+    /// Run a program verifying `n` deferred proofs, then halting, with `verifier`.
+    fn run_verify_program(n: u32, verifier: &RecordingVerifier) -> Executor<'_> {
+        let verify = [
+            Instruction::new(Opcode::ADD, 5, 0, VERIFY_SP1_PROOF, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 1024, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 1024, false, true),
+            Instruction::new(Opcode::ECALL, 5, 10, 11, false, false),
+        ];
+        let halt = [
+            Instruction::new(Opcode::ADD, 5, 0, 0, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, 0, false, true),
+            Instruction::new(Opcode::ECALL, 5, 10, 11, false, false),
+        ];
+        let mut instructions = verify.repeat(n as usize);
+        instructions.extend(halt);
+        let program = Program::new(instructions, 0, 0);
+
+        let context = SP1Context::builder().subproof_verifier(verifier).build();
+        let mut runtime = Executor::with_context(program, SP1CoreOpts::default(), context);
+        let (proof, mut vk) = bincode::deserialize::<(
+            SP1ReduceProof<BabyBearPoseidon2>,
+            StarkVerifyingKey<BabyBearPoseidon2>,
+        )>(&[0; 1024])
+        .unwrap();
+        for i in 0..n {
+            vk.pc_start = BabyBear::from_canonical_u32(i);
+            runtime.write_proof(proof.clone(), vk.clone());
+        }
+        runtime.run_fast().unwrap();
+        runtime
+    }
+
+    #[test]
+    fn test_deferred_proofs_are_verified_ahead_of_their_syscalls() {
+        let verifier = RecordingVerifier { calls: Mutex::default(), invalid: None };
+        let runtime = run_verify_program(3, &verifier);
+        assert_eq!(runtime.state.proof_stream_ptr, 3);
+
+        // All the proofs are verified at the first syscall, and only checked at each syscall.
+        let calls = verifier.calls.lock().unwrap();
+        let (preverified, checked) = calls.split_at(3);
+        assert!(preverified.iter().all(|(call, _)| *call == "preverify"));
+        assert_eq!(preverified.iter().map(|(_, i)| *i).collect::<BTreeSet<_>>(), (0..3).collect());
+        assert_eq!(checked, [("check", 0), ("check", 1), ("check", 2)]);
+    }
+
+    #[test]
+    fn test_deferred_proof_failing_preverification_is_reported_at_its_syscall() {
+        let verifier = RecordingVerifier { calls: Mutex::default(), invalid: Some(1) };
+        let panic =
+            panic::catch_unwind(AssertUnwindSafe(|| drop(run_verify_program(3, &verifier))))
+                .expect_err("the invalid proof is rejected");
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("Failed to verify proof 1 "), "{message}");
+
+        // The proof before the invalid one was still checked at its own syscall.
+        let calls = verifier.calls.lock().unwrap();
+        assert_eq!(calls[3..], [("check", 0)]);
+    }
+
+    #[test]
+    fn test_public_values_writer() {
+        // Commit the word 42 at address 100 to the public values stream.
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 42, false, true),
+            Instruction::new(Opcode::ADD, 30, 0, 100, false, true),
+            Instruction::new(Opcode::SW, 29, 30, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, WRITE, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, FD_PUBLIC_VALUES, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 100, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 5, 10, 11, false, false),
+        ];
+        let program = Program::new(instructions, 0, 0);
+
+        let mut runtime = Executor::new(program.clone(), SP1CoreOpts::default());
+        runtime.run().unwrap();
+        assert_eq!(runtime.state.public_values_stream, 42u32.to_le_bytes());
+
+        let mut public_values = Vec::new();
+        let context = SP1Context::builder().public_values(&mut public_values).build();
+        let mut runtime = Executor::with_context(program, SP1CoreOpts::default(), context);
+        runtime.run().unwrap();
+        assert_eq!(runtime.state.public_values_stream, Vec::<u8>::new());
+        drop(runtime);
+        assert_eq!(public_values, 42u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_phase_marker_ends_shard() {
+        // Mark the start of the phase "ab" stored at address 100, between two additions.
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 0x6261, false, true),
+            Instruction::new(Opcode::ADD, 30, 0, 100, false, true),
+            Instruction::new(Opcode::SW, 29, 30, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, WRITE, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, FD_PHASE, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 100, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 2, false, true),
+            Instruction::new(Opcode::ECALL, 5, 10, 11, false, false),
+            Instruction::new(Opcode::ADD, 31, 0, 42, false, true),
+        ];
+        let program = Program::new(instructions, 0, 0);
+
+        // Without a minimum phase shard size, the marker is only reported.
+        let mut runtime = Executor::new(program.clone(), SP1CoreOpts::default());
+        runtime.run().unwrap();
+        assert_eq!(runtime.state.current_shard, 1);
+        assert_eq!(runtime.report.phases.len(), 1);
+        assert_eq!(runtime.report.phases[0].name, "ab");
+        assert!(!runtime.report.phases[0].boundary);
+
+        let opts = SP1CoreOpts { min_phase_shard_size: Some(4), ..SP1CoreOpts::default() };
+        let mut runtime = Executor::new(program, opts);
+        runtime.run().unwrap();
+        assert_eq!(runtime.state.current_shard, 2);
+        assert!(runtime.report.phases[0].boundary);
+        assert_eq!(runtime.register(Register::X31), 42);
+    }
+
+    #[test]
+    fn test_output_writer() {
+        // Write the word 42 at address 100 to the output stream.
+        let instructions = vec![
+            Instruction::new(Opcode::ADD, 29, 0, 42, false, true),
+            Instruction::new(Opcode::ADD, 30, 0, 100, false, true),
+            Instruction::new(Opcode::SW, 29, 30, 0, false, true),
+            Instruction::new(Opcode::ADD, 5, 0, WRITE, false, true),
+            Instruction::new(Opcode::ADD, 10, 0, FD_OUTPUT, false, true),
+            Instruction::new(Opcode::ADD, 11, 0, 100, false, true),
+            Instruction::new(Opcode::ADD, 12, 0, 4, false, true),
+            Instruction::new(Opcode::ECALL, 5, 10, 11, false, false),
+        ];
+        let program = Program::new(instructions, 0, 0);
+
+        // Without a writer, the output is discarded.
+        let mut runtime = Executor::new(program.clone(), SP1CoreOpts::default());
+        runtime.run().unwrap();
+        assert_eq!(runtime.state.public_values_stream, Vec::<u8>::new());
+
+        let mut output = Vec::new();
+        let context = SP1Context::builder().output(&mut output).build();
+        let mut runtime = Executor::with_context(program, SP1CoreOpts::default(), context);
+        runtime.run().unwrap();
+        assert_eq!(runtime.state.public_values_stream, Vec::<u8>::new());
+        drop(runtime);
+        assert_eq!(output, 42u32.to_le_bytes());
+    }
 }
@@ -1,10 +1,13 @@
-use std::io::Read;
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
 
 use serde::{de::DeserializeOwned, Serialize};
 use sp1_stark::{baby_bear_poseidon2::BabyBearPoseidon2, StarkVerifyingKey};
 
 use super::Executor;
-use crate::SP1ReduceProof;
+use crate::{PhaseMarker, SP1ReduceProof};
 
 impl Read for Executor<'_> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
@@ -33,6 +36,32 @@ impl Executor<'_> {
         }
     }
 
+    /// Set the input chunks, read once the input stream is exhausted.
+    ///
+    /// Unlike the input stream, the chunks are shared by the executors of an execution and are not
+    /// saved in its checkpoints, which makes them suited for huge inputs.
+    pub fn write_input_chunks(&mut self, chunks: Vec<Arc<Vec<u8>>>) {
+        self.input_chunks = chunks;
+    }
+
+    /// The length of the next input, if any.
+    pub(crate) fn next_input_len(&self) -> Option<usize> {
+        match self.state.input_stream.front() {
+            Some(input) => Some(input.len()),
+            None => self.input_chunks.get(self.state.input_chunks_ptr).map(|chunk| chunk.len()),
+        }
+    }
+
+    /// Take the next input, if any.
+    pub(crate) fn next_input(&mut self) -> Option<Vec<u8>> {
+        if let Some(input) = self.state.input_stream.pop_front() {
+            return Some(input);
+        }
+        let chunk = self.input_chunks.get(self.state.input_chunks_ptr)?.to_vec();
+        self.state.input_chunks_ptr += 1;
+        Some(chunk)
+    }
+
     /// Write a proof and verifying key to the proof stream.
     pub fn write_proof(
         &mut self,
@@ -42,6 +71,35 @@ impl Executor<'_> {
         self.state.proof_stream.push((proof, vk));
     }
 
+    /// Append bytes committed by the program to the public values stream.
+    ///
+    /// If a public values writer is set in the IO options, the bytes are written to it instead of
+    /// being kept in memory.
+    pub(crate) fn commit_public_values(&mut self, slice: &[u8]) {
+        match self.io_options.public_values {
+            Some(ref mut w) => w.write_all(slice).expect("failed to write public values"),
+            None => self.state.public_values_stream.extend_from_slice(slice),
+        }
+    }
+
+    /// Mark the start of a program phase, ending the current shard at the end of the cycle if it is
+    /// long enough. Markers in unconstrained blocks are ignored.
+    pub(crate) fn mark_phase(&mut self, name: &str) {
+        if self.unconstrained {
+            return;
+        }
+        let boundary = self.min_phase_shard_clk.is_some_and(|min| self.state.clk >= min);
+        self.phase_boundary = boundary;
+        if self.print_report {
+            self.report.phases.push(PhaseMarker {
+                name: name.to_string(),
+                shard: self.shard(),
+                clk: self.state.global_clk,
+                boundary,
+            });
+        }
+    }
+
     /// Read a serializable public values from the public values stream.
     pub fn read_public_values<T: DeserializeOwned>(&mut self) -> T {
         let result = bincode::deserialize_from::<_, T>(self);
@@ -0,0 +1,162 @@
+//! Persistence of the progress of core proving.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::spill::SpillCipher;
+
+/// A directory where the core prover persists its progress, so that a job that crashed resumes
+/// from its last proven checkpoint instead of executing the program from cycle zero.
+///
+/// For the latest execution checkpoints, the journal holds the whole execution state after the
+/// checkpoint, from which execution resumes, and the state of the prover after it, e.g. the
+/// precompile events deferred to later shards. It also holds the proof of every shard. See
+/// `sp1_core_machine::utils::latest_resume_point` for the checkpoint proving resumes after.
+///
+/// Entries are written atomically, so a crash leaves either the previous or the new version of an
+/// entry. The execution and prover states contain the memory of the program, and are sealed with
+/// the spill cipher of the context, if any.
+#[derive(Debug, Clone)]
+pub struct CoreJournal {
+    dir: PathBuf,
+}
+
+impl CoreJournal {
+    /// Open the journal in `dir`, creating the directory if needed.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// The directory of the journal.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write the entry `name`, sealing it with `cipher` if any.
+    pub fn write(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        cipher: Option<&dyn SpillCipher>,
+    ) -> io::Result<()> {
+        let sealed;
+        let bytes = match cipher {
+            Some(cipher) => {
+                sealed = cipher.seal(bytes)?;
+                &sealed
+            }
+            None => bytes,
+        };
+        let tmp = self.dir.join(format!(".{name}.tmp"));
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, self.dir.join(name))
+    }
+
+    /// Read the entry `name` written with the same cipher, if it exists.
+    pub fn read(
+        &self,
+        name: &str,
+        cipher: Option<&dyn SpillCipher>,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let bytes = match fs::read(self.dir.join(name)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match cipher {
+            Some(cipher) => cipher.open(&bytes).map(Some),
+            None => Ok(Some(bytes)),
+        }
+    }
+
+    /// Whether the entry `name` exists.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.dir.join(name).is_file()
+    }
+
+    /// The names of the entries.
+    pub fn entries(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Remove the entry `name`, if it exists.
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove every entry, e.g. once the job is done or to start a new one.
+    pub fn clear(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            fs::remove_file(entry?.path())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_entries() {
+        let dir = std::env::temp_dir().join(format!("sp1-core-journal-{}", std::process::id()));
+        let journal = CoreJournal::open(&dir).unwrap();
+        assert_eq!(journal.read("a", None).unwrap(), None);
+
+        journal.write("a", b"first", None).unwrap();
+        journal.write("a", b"second", None).unwrap();
+        journal.write("b", b"other", None).unwrap();
+        assert_eq!(journal.read("a", None).unwrap().as_deref(), Some(&b"second"[..]));
+        let mut entries = journal.entries().unwrap();
+        entries.sort();
+        assert_eq!(entries, ["a", "b"]);
+
+        journal.remove("a").unwrap();
+        journal.remove("a").unwrap();
+        assert!(!journal.contains("a"));
+        journal.clear().unwrap();
+        assert!(journal.entries().unwrap().is_empty());
+        fs::remove_dir(dir).unwrap();
+    }
+
+    /// A cipher that flips every bit, to tell sealed entries apart on disk.
+    struct FlipCipher;
+
+    impl SpillCipher for FlipCipher {
+        fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(plaintext.iter().map(|byte| !byte).collect())
+        }
+
+        fn open(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+            self.seal(ciphertext)
+        }
+    }
+
+    #[test]
+    fn test_journal_sealed_entries() {
+        let dir =
+            std::env::temp_dir().join(format!("sp1-core-journal-sealed-{}", std::process::id()));
+        let journal = CoreJournal::open(&dir).unwrap();
+
+        journal.write("a", b"memory", Some(&FlipCipher)).unwrap();
+        assert_ne!(fs::read(dir.join("a")).unwrap(), b"memory");
+        assert_eq!(journal.read("a", Some(&FlipCipher)).unwrap().as_deref(), Some(&b"memory"[..]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -21,16 +21,19 @@
 #![warn(missing_docs)]
 
 mod air;
+pub mod cancel;
 mod context;
 mod cost;
 mod dependencies;
 mod disassembler;
 pub mod estimator;
+pub mod event_counts;
 pub mod events;
 mod executor;
 mod hook;
 mod instruction;
 mod io;
+pub mod journal;
 mod memory;
 mod opcode;
 #[cfg(feature = "profiling")]
@@ -40,6 +43,8 @@ mod record;
 mod reduce;
 mod register;
 mod report;
+pub mod shard_memo;
+pub mod spill;
 mod state;
 pub mod subproof;
 pub mod syscalls;
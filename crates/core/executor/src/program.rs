@@ -5,7 +5,7 @@ use std::{fs::File, io::Read, str::FromStr};
 use crate::{
     disassembler::{transpile, Elf},
     instruction::Instruction,
-    RiscvAirId,
+    RiscvAirId, STACK_TOP,
 };
 use hashbrown::HashMap;
 use p3_field::{AbstractExtensionField, Field, PrimeField32};
@@ -20,6 +20,13 @@ use sp1_stark::{
     InteractionKind,
 };
 
+/// The address of the domain tag of a program, in the gap between the top of the stack and the
+/// start of the program.
+pub const DOMAIN_TAG_ADDR: u32 = STACK_TOP;
+
+/// The number of words of a domain tag.
+pub const DOMAIN_TAG_WORDS: usize = 8;
+
 /// A program that can be executed by the SP1 zkVM.
 ///
 /// Contains a series of instructions along with the initial memory image. It also contains the
@@ -36,6 +43,19 @@ pub struct Program {
     pub memory_image: HashMap<u32, u32>,
     /// The shape for the preprocessed tables.
     pub preprocessed_shape: Option<Shape<RiscvAirId>>,
+    /// The addresses of guest symbols used to report memory usage.
+    #[serde(default)]
+    pub guest_symbols: GuestSymbols,
+}
+
+/// The addresses of guest symbols the executor uses to report heap usage, if the ELF has a symbol
+/// table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestSymbols {
+    /// The entrypoints of the allocator, whose calls are counted as heap allocations.
+    pub allocators: Vec<u32>,
+    /// The start of the heap, i.e. the `_end` symbol placed after the program data.
+    pub heap_start: Option<u32>,
 }
 
 impl Program {
@@ -48,6 +68,7 @@ impl Program {
             pc_base,
             memory_image: HashMap::new(),
             preprocessed_shape: None,
+            guest_symbols: GuestSymbols::default(),
         }
     }
 
@@ -70,6 +91,7 @@ impl Program {
             pc_base: elf.pc_base,
             memory_image: elf.memory_image,
             preprocessed_shape: None,
+            guest_symbols: elf.guest_symbols,
         })
     }
 
@@ -84,6 +106,36 @@ impl Program {
         Program::from(&elf_code)
     }
 
+    /// Place a domain tag in the initial memory image of the program, at [`DOMAIN_TAG_ADDR`].
+    ///
+    /// The memory image is committed to by the verifying key of the program, so programs tagged
+    /// with different domains have different verifying keys, and their proofs do not verify
+    /// against each other's keys. The tag should be set before the preprocessed shape is fixed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the program already initializes the memory of the
+    /// tag with different values.
+    pub fn tag_domain(&mut self, tag: [u32; DOMAIN_TAG_WORDS]) -> eyre::Result<()> {
+        for (addr, word) in (DOMAIN_TAG_ADDR..).step_by(4).zip(tag) {
+            if self.memory_image.get(&addr).is_some_and(|&existing| existing != word) {
+                eyre::bail!("the program initializes the domain tag address {addr:#010x}");
+            }
+        }
+        self.memory_image.extend((DOMAIN_TAG_ADDR..).step_by(4).zip(tag));
+        Ok(())
+    }
+
+    /// The domain tag of the program, if it was tagged with [`Program::tag_domain`].
+    #[must_use]
+    pub fn domain_tag(&self) -> Option<[u32; DOMAIN_TAG_WORDS]> {
+        let mut tag = [0; DOMAIN_TAG_WORDS];
+        for (word, addr) in tag.iter_mut().zip((DOMAIN_TAG_ADDR..).step_by(4)) {
+            *word = *self.memory_image.get(&addr)?;
+        }
+        Some(tag)
+    }
+
     /// Custom logic for padding the trace to a power of two according to the proof shape.
     pub fn fixed_log2_rows<F: Field, A: MachineAir<F>>(&self, air: &A) -> Option<usize> {
         let id = RiscvAirId::from_str(&air.name()).unwrap();
@@ -1,15 +1,17 @@
 use std::{
+    collections::BTreeMap,
     fmt::{Display, Formatter, Result as FmtResult},
     ops::{Add, AddAssign},
 };
 
 use enum_map::{EnumArray, EnumMap};
 use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
 
 use crate::{events::generate_execution_report, syscalls::SyscallCode, Opcode};
 
 /// An execution report.
-#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExecutionReport {
     /// The opcode counts.
     pub opcode_counts: Box<EnumMap<Opcode, u64>>,
@@ -23,6 +25,105 @@ pub struct ExecutionReport {
     pub touched_memory_addresses: u64,
     /// The gas, if it was calculated.
     pub gas: Option<u64>,
+    /// The stack and heap usage of the guest.
+    #[serde(default)]
+    pub memory_usage: MemoryUsage,
+    /// The phase markers emitted by the guest, in execution order.
+    #[serde(default)]
+    pub phases: Vec<PhaseMarker>,
+    /// The labeled segments of the standard input of the execution.
+    #[serde(default)]
+    pub input_segments: Vec<InputSegment>,
+}
+
+/// A phase marker emitted by the guest with `sp1_zkvm::io::phase`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseMarker {
+    /// The name of the phase.
+    pub name: String,
+    /// The shard the marker was emitted in.
+    pub shard: u32,
+    /// The global clock at the marker.
+    pub clk: u64,
+    /// Whether the marker ended its shard.
+    pub boundary: bool,
+}
+
+/// Whether the inputs of a segment of the standard input are committed inputs or hints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputKind {
+    /// Inputs the program is expected to commit to, or to check against committed values.
+    Committed,
+    /// Unconstrained advice, which the program must check before relying on it, and which a
+    /// replay re-derives on the host.
+    Hint,
+}
+
+/// A labeled run of consecutive inputs of the standard input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputSegment {
+    /// The label of the segment.
+    pub label: String,
+    /// Whether the inputs are committed inputs or hints.
+    pub kind: InputKind,
+    /// The index of the first input of the segment in the standard input buffer.
+    pub start: usize,
+    /// The number of inputs of the segment.
+    pub len: usize,
+}
+
+/// The initial stack pointer of guest programs, set by the zkVM entrypoint.
+pub const STACK_TOP: u32 = 0x0020_0400;
+
+/// The stack and heap usage of a guest program.
+///
+/// The stack grows down from [`STACK_TOP`] and the heap grows up from the end of the program data,
+/// so the lowest touched address below the stack top and the highest touched address above the
+/// heap start are their watermarks.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// The lowest address touched on the stack.
+    pub stack_low: Option<u32>,
+    /// The start of the heap.
+    pub heap_start: Option<u32>,
+    /// The highest address touched on the heap.
+    pub heap_high: Option<u32>,
+    /// The number of calls to the allocator, if its symbols were found in the ELF.
+    pub heap_allocations: u64,
+}
+
+impl MemoryUsage {
+    /// The maximum depth of the stack, in bytes.
+    #[must_use]
+    pub fn stack_bytes(&self) -> u64 {
+        self.stack_low.map_or(0, |low| u64::from(STACK_TOP - low))
+    }
+
+    /// The maximum size of the heap, in bytes.
+    #[must_use]
+    pub fn heap_bytes(&self) -> u64 {
+        match (self.heap_start, self.heap_high) {
+            (Some(start), Some(high)) => u64::from(high) + 4 - u64::from(start),
+            _ => 0,
+        }
+    }
+
+    /// The range of touched addresses, from the bottom of the stack to the top of the heap.
+    #[must_use]
+    pub fn touched_range(&self) -> Option<(u32, u32)> {
+        let low = self.stack_low.or(self.heap_start)?;
+        let high = self.heap_high.map_or(STACK_TOP, |high| high + 4);
+        Some((low, high))
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.stack_low = self.stack_low.into_iter().chain(rhs.stack_low).min();
+        self.heap_start = self.heap_start.or(rhs.heap_start);
+        self.heap_high = self.heap_high.into_iter().chain(rhs.heap_high).max();
+        self.heap_allocations += rhs.heap_allocations;
+    }
 }
 
 impl ExecutionReport {
@@ -37,6 +138,250 @@ impl ExecutionReport {
     pub fn total_syscall_count(&self) -> u64 {
         self.syscall_counts.values().sum()
     }
+
+    /// Compare this report, taken as the baseline, with `other`.
+    #[must_use]
+    pub fn diff(&self, other: &ExecutionReport) -> ReportDiff {
+        let syscall_counts = self
+            .syscall_counts
+            .iter()
+            .map(|(code, &before)| (code, MetricDiff::new(before, other.syscall_counts[code])))
+            .filter(|(_, diff)| !diff.is_unchanged())
+            .collect();
+
+        let mut cycle_tracker = BTreeMap::new();
+        for (label, &before) in &self.cycle_tracker {
+            let after = other.cycle_tracker.get(label).copied().unwrap_or_default();
+            cycle_tracker.insert(label.clone(), MetricDiff::new(before, after));
+        }
+        for (label, &after) in &other.cycle_tracker {
+            cycle_tracker.entry(label.clone()).or_insert(MetricDiff::new(0, after));
+        }
+
+        ReportDiff {
+            instructions: MetricDiff::new(
+                self.total_instruction_count(),
+                other.total_instruction_count(),
+            ),
+            syscalls: MetricDiff::new(self.total_syscall_count(), other.total_syscall_count()),
+            gas: self.gas.zip(other.gas).map(|(before, after)| MetricDiff::new(before, after)),
+            touched_memory_addresses: MetricDiff::new(
+                self.touched_memory_addresses,
+                other.touched_memory_addresses,
+            ),
+            syscall_counts,
+            cycle_tracker,
+        }
+    }
+}
+
+/// The change of a metric between two execution reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricDiff {
+    /// The value in the baseline report.
+    pub before: u64,
+    /// The value in the new report.
+    pub after: u64,
+}
+
+impl MetricDiff {
+    /// Create a diff from the baseline and new values.
+    #[must_use]
+    pub const fn new(before: u64, after: u64) -> Self {
+        Self { before, after }
+    }
+
+    /// The absolute change.
+    #[must_use]
+    pub fn delta(&self) -> i128 {
+        i128::from(self.after) - i128::from(self.before)
+    }
+
+    /// The change relative to the baseline, in percent.
+    ///
+    /// A metric that goes up from zero has an infinite change.
+    #[must_use]
+    pub fn percent_change(&self) -> f64 {
+        if self.before == 0 {
+            return if self.after == 0 { 0.0 } else { f64::INFINITY };
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let percent = self.delta() as f64 * 100.0 / self.before as f64;
+        percent
+    }
+
+    /// Whether the metric is the same in both reports.
+    #[must_use]
+    pub fn is_unchanged(&self) -> bool {
+        self.before == self.after
+    }
+
+    /// Whether the metric went up by more than `max_percent` percent.
+    #[must_use]
+    pub fn exceeds(&self, max_percent: f64) -> bool {
+        self.percent_change() > max_percent
+    }
+}
+
+impl Display for MetricDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{} -> {} ({:+}, {:+.2}%)",
+            self.before,
+            self.after,
+            self.delta(),
+            self.percent_change()
+        )
+    }
+}
+
+/// The differences between two execution reports, see [`ExecutionReport::diff`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportDiff {
+    /// The total number of instructions, i.e. the cycle count.
+    pub instructions: MetricDiff,
+    /// The total number of syscalls.
+    pub syscalls: MetricDiff,
+    /// The gas, if both reports have it.
+    pub gas: Option<MetricDiff>,
+    /// The number of unique memory addresses touched.
+    pub touched_memory_addresses: MetricDiff,
+    /// The counts of the syscalls whose count changed.
+    pub syscall_counts: Vec<(SyscallCode, MetricDiff)>,
+    /// The cycle tracker counts, for the labels of either report.
+    pub cycle_tracker: BTreeMap<String, MetricDiff>,
+}
+
+impl ReportDiff {
+    /// Check the diff against `thresholds`, returning every metric that regressed beyond them.
+    pub fn check(&self, thresholds: &RegressionThresholds) -> Result<(), Vec<Regression>> {
+        let mut regressions = Vec::new();
+        let mut check = |metric: String, diff: MetricDiff, max_percent: Option<f64>| {
+            if let Some(max_percent) = max_percent.filter(|&max| diff.exceeds(max)) {
+                regressions.push(Regression { metric, diff, max_percent });
+            }
+        };
+
+        check("instructions".to_string(), self.instructions, thresholds.instructions);
+        check("syscalls".to_string(), self.syscalls, thresholds.syscalls);
+        if let Some(gas) = self.gas {
+            check("gas".to_string(), gas, thresholds.gas);
+        }
+        for (code, diff) in &self.syscall_counts {
+            check(format!("syscall {code}"), *diff, thresholds.syscall);
+        }
+        for (label, diff) in &self.cycle_tracker {
+            check(format!("cycle tracker {label}"), *diff, thresholds.cycle_tracker);
+        }
+
+        if regressions.is_empty() {
+            Ok(())
+        } else {
+            Err(regressions)
+        }
+    }
+}
+
+impl Display for ReportDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "syscalls: {}", self.syscalls)?;
+        if let Some(gas) = &self.gas {
+            writeln!(f, "gas: {gas}")?;
+        }
+        writeln!(f, "touched memory addresses: {}", self.touched_memory_addresses)?;
+        for (code, diff) in &self.syscall_counts {
+            writeln!(f, "  {code}: {diff}")?;
+        }
+        for (label, diff) in self.cycle_tracker.iter().filter(|(_, diff)| !diff.is_unchanged()) {
+            writeln!(f, "  {label}: {diff}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The largest increase allowed for each metric of a [`ReportDiff`], in percent.
+///
+/// Metrics without a threshold are not checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegressionThresholds {
+    /// The threshold for the total number of instructions.
+    pub instructions: Option<f64>,
+    /// The threshold for the total number of syscalls.
+    pub syscalls: Option<f64>,
+    /// The threshold for the gas.
+    pub gas: Option<f64>,
+    /// The threshold for the count of each syscall.
+    pub syscall: Option<f64>,
+    /// The threshold for each cycle tracker label.
+    pub cycle_tracker: Option<f64>,
+}
+
+impl RegressionThresholds {
+    /// Apply the same threshold to every metric.
+    #[must_use]
+    pub fn all(max_percent: f64) -> Self {
+        Self {
+            instructions: Some(max_percent),
+            syscalls: Some(max_percent),
+            gas: Some(max_percent),
+            syscall: Some(max_percent),
+            cycle_tracker: Some(max_percent),
+        }
+    }
+
+    /// Set the threshold for the total number of instructions.
+    #[must_use]
+    pub fn instructions(mut self, max_percent: f64) -> Self {
+        self.instructions = Some(max_percent);
+        self
+    }
+
+    /// Set the threshold for the total number of syscalls.
+    #[must_use]
+    pub fn syscalls(mut self, max_percent: f64) -> Self {
+        self.syscalls = Some(max_percent);
+        self
+    }
+
+    /// Set the threshold for the gas.
+    #[must_use]
+    pub fn gas(mut self, max_percent: f64) -> Self {
+        self.gas = Some(max_percent);
+        self
+    }
+
+    /// Set the threshold for the count of each syscall.
+    #[must_use]
+    pub fn syscall(mut self, max_percent: f64) -> Self {
+        self.syscall = Some(max_percent);
+        self
+    }
+
+    /// Set the threshold for each cycle tracker label.
+    #[must_use]
+    pub fn cycle_tracker(mut self, max_percent: f64) -> Self {
+        self.cycle_tracker = Some(max_percent);
+        self
+    }
+}
+
+/// A metric that went up by more than its threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Regression {
+    /// The name of the metric.
+    pub metric: String,
+    /// The change of the metric.
+    pub diff: MetricDiff,
+    /// The threshold it exceeded, in percent.
+    pub max_percent: f64,
+}
+
+impl Display for Regression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} regressed by more than {}%: {}", self.metric, self.max_percent, self.diff)
+    }
 }
 
 /// Combines two `HashMap`s together. If a key is in both maps, the values are added together.
@@ -55,6 +400,12 @@ impl AddAssign for ExecutionReport {
         counts_add_assign(&mut self.opcode_counts, *rhs.opcode_counts);
         counts_add_assign(&mut self.syscall_counts, *rhs.syscall_counts);
         self.touched_memory_addresses += rhs.touched_memory_addresses;
+        self.memory_usage += rhs.memory_usage;
+        self.phases.extend(rhs.phases);
+        self.phases.sort_by_key(|phase| phase.clk);
+        if self.input_segments.is_empty() {
+            self.input_segments = rhs.input_segments;
+        }
     }
 }
 
@@ -82,6 +433,94 @@ impl Display for ExecutionReport {
             writeln!(f, "  {line}")?;
         }
 
+        let usage = &self.memory_usage;
+        writeln!(f, "memory usage:")?;
+        writeln!(f, "  stack: {} bytes", usage.stack_bytes())?;
+        if usage.heap_start.is_some() {
+            writeln!(f, "  heap: {} bytes", usage.heap_bytes())?;
+        }
+        writeln!(f, "  heap allocations: {}", usage.heap_allocations)?;
+        if let Some((low, high)) = usage.touched_range() {
+            writeln!(f, "  touched range: {low:#010x}..{high:#010x}")?;
+        }
+
+        if !self.phases.is_empty() {
+            writeln!(f, "phases:")?;
+            for phase in &self.phases {
+                let boundary = if phase.boundary { ", shard boundary" } else { "" };
+                writeln!(
+                    f,
+                    "  {}: shard {}, clk {}{boundary}",
+                    phase.name, phase.shard, phase.clk
+                )?;
+            }
+        }
+
+        if !self.input_segments.is_empty() {
+            writeln!(f, "input segments:")?;
+            for segment in &self.input_segments {
+                let kind = match segment.kind {
+                    InputKind::Committed => "committed",
+                    InputKind::Hint => "hint",
+                };
+                writeln!(
+                    f,
+                    "  {} ({kind}): inputs {}..{}",
+                    segment.label,
+                    segment.start,
+                    segment.start + segment.len
+                )?;
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_diff_thresholds() {
+        let mut baseline = ExecutionReport::default();
+        baseline.opcode_counts[Opcode::ADD] = 100;
+        baseline.syscall_counts[SyscallCode::SHA_EXTEND] = 10;
+        baseline.gas = Some(1000);
+
+        let mut report = baseline.clone();
+        report.opcode_counts[Opcode::ADD] = 104;
+        report.syscall_counts[SyscallCode::SHA_EXTEND] = 20;
+        report.cycle_tracker.insert("loop".to_string(), 5);
+
+        let diff = baseline.diff(&report);
+        assert_eq!(diff.instructions, MetricDiff::new(100, 104));
+        assert_eq!(diff.syscall_counts, vec![(SyscallCode::SHA_EXTEND, MetricDiff::new(10, 20))]);
+        assert!(diff.gas.unwrap().is_unchanged());
+
+        assert!(diff.check(&RegressionThresholds::default().instructions(5.0)).is_ok());
+        let regressions = diff.check(&RegressionThresholds::all(5.0)).unwrap_err();
+        let metrics: Vec<_> = regressions.iter().map(|r| r.metric.as_str()).collect();
+        assert_eq!(metrics, ["syscalls", "syscall SHA_EXTEND", "cycle tracker loop"]);
+    }
+
+    #[test]
+    fn test_memory_usage_add() {
+        let mut usage = MemoryUsage {
+            stack_low: Some(STACK_TOP - 64),
+            heap_start: Some(0x0030_0000),
+            heap_high: Some(0x0030_0010),
+            heap_allocations: 2,
+        };
+        usage += MemoryUsage {
+            stack_low: Some(STACK_TOP - 32),
+            heap_start: Some(0x0030_0000),
+            heap_high: Some(0x0030_0100),
+            heap_allocations: 3,
+        };
+        assert_eq!(usage.stack_bytes(), 64);
+        assert_eq!(usage.heap_bytes(), 0x104);
+        assert_eq!(usage.heap_allocations, 5);
+        assert_eq!(usage.touched_range(), Some((STACK_TOP - 64, 0x0030_0104)));
+    }
+}
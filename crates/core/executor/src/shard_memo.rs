@@ -0,0 +1,72 @@
+//! Memoization of core shard proofs.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use hashbrown::HashMap;
+
+/// A store of serialized shard proofs, keyed by a digest of everything the proof depends on.
+///
+/// A shard proof is a deterministic function of the proving key, the public values and the traces
+/// of the shard, so a shard whose record is byte-identical to one already proven can reuse its
+/// proof. The core prover computes the key, see `sp1_core_machine::utils::shard_proof_key`, and
+/// checks that a reused proof matches the record before using it.
+///
+/// A store must only be shared between provers with the same STARK configuration, since the FRI
+/// parameters of the prover are not part of the key.
+pub trait ShardProofStore: Send + Sync {
+    /// The serialized proof stored under `key`, if any.
+    fn get(&self, key: &[u8; 32]) -> Option<Vec<u8>>;
+
+    /// Store the serialized proof of the shard with digest `key`.
+    fn put(&self, key: [u8; 32], proof: Vec<u8>);
+}
+
+/// A [`ShardProofStore`] keeping the proofs in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryShardProofStore {
+    proofs: Mutex<HashMap<[u8; 32], Vec<u8>>>,
+    hits: AtomicUsize,
+}
+
+impl InMemoryShardProofStore {
+    /// An empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of proofs stored.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.proofs.lock().unwrap().len()
+    }
+
+    /// Whether no proof is stored.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of lookups that found a proof.
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+impl ShardProofStore for InMemoryShardProofStore {
+    fn get(&self, key: &[u8; 32]) -> Option<Vec<u8>> {
+        let proof = self.proofs.lock().unwrap().get(key).cloned();
+        if proof.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        proof
+    }
+
+    fn put(&self, key: [u8; 32], proof: Vec<u8>) {
+        self.proofs.lock().unwrap().insert(key, proof);
+    }
+}
@@ -0,0 +1,15 @@
+//! Encryption of the execution state spilled to disk while proving.
+
+use std::io;
+
+/// A cipher applied to the checkpoints the prover writes to temporary files.
+///
+/// Checkpoints contain the memory of the program, and so its private inputs. This needs to be
+/// passed in rather than implemented here so that the key can be managed by the caller.
+pub trait SpillCipher: Sync + Send {
+    /// Encrypt and authenticate `plaintext`.
+    fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decrypt `ciphertext`, failing if it was not sealed with the same key.
+    fn open(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+}
@@ -1,7 +1,7 @@
 use std::{
     collections::VecDeque,
     fs::File,
-    io::{Seek, Write},
+    io::{self, Read, Seek, Write},
 };
 
 use hashbrown::HashMap;
@@ -12,6 +12,7 @@ use crate::{
     events::MemoryRecord,
     memory::Memory,
     record::{ExecutionRecord, MemoryAccessRecord},
+    spill::SpillCipher,
     syscalls::SyscallCode,
     ExecutorMode, SP1ReduceProof,
 };
@@ -45,6 +46,10 @@ pub struct ExecutionState {
     /// A stream of input values (global to the entire program).
     pub input_stream: VecDeque<Vec<u8>>,
 
+    /// A ptr to the current position in the input chunks of the executor, incremented when a
+    /// chunk is read after the input stream is exhausted.
+    pub input_chunks_ptr: usize,
+
     /// A stream of proofs (reduce vk, proof, verifying key) inputted to the program.
     pub proof_stream:
         Vec<(SP1ReduceProof<BabyBearPoseidon2>, StarkVerifyingKey<BabyBearPoseidon2>)>,
@@ -76,6 +81,7 @@ impl ExecutionState {
             memory: Memory::new_preallocated(),
             uninitialized_memory: Memory::new_preallocated(),
             input_stream: VecDeque::new(),
+            input_chunks_ptr: 0,
             public_values_stream: Vec::new(),
             public_values_stream_ptr: 0,
             proof_stream: Vec::new(),
@@ -116,4 +122,26 @@ impl ExecutionState {
         writer.seek(std::io::SeekFrom::Start(0))?;
         Ok(())
     }
+
+    /// Save the execution state to a file, encrypted with `cipher` if one is given.
+    pub fn save_with(&self, file: &mut File, cipher: Option<&dyn SpillCipher>) -> io::Result<()> {
+        let Some(cipher) = cipher else {
+            return self.save(file);
+        };
+        let bytes = bincode::serialize(self).map_err(io::Error::other)?;
+        file.write_all(&cipher.seal(&bytes)?)?;
+        file.seek(io::SeekFrom::Start(0))?;
+        Ok(())
+    }
+
+    /// Load an execution state written by [`ExecutionState::save_with`] with the same cipher.
+    pub fn load_with(file: &File, cipher: Option<&dyn SpillCipher>) -> io::Result<Self> {
+        let mut reader = io::BufReader::new(file);
+        let Some(cipher) = cipher else {
+            return bincode::deserialize_from(&mut reader).map_err(io::Error::other);
+        };
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        bincode::deserialize(&cipher.open(&bytes)?).map_err(io::Error::other)
+    }
 }
@@ -21,6 +21,32 @@ pub trait SubproofVerifier: Sync + Send {
         vk_hash: [u32; 8],
         committed_value_digest: [u32; 8],
     ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>>;
+
+    /// Verify the parts of a deferred proof that do not depend on the values passed to the
+    /// syscall, so that the deferred proofs of an execution can be verified in parallel before the
+    /// syscalls are reached.
+    ///
+    /// A proof verified this way is only checked with [`Self::check_deferred_proof`] at its
+    /// syscall. By default nothing is verified ahead of the syscall.
+    fn preverify_deferred_proof(
+        &self,
+        _proof: &SP1ReduceProof<BabyBearPoseidon2>,
+        _vk: &StarkVerifyingKey<BabyBearPoseidon2>,
+    ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+        Ok(())
+    }
+
+    /// Check a deferred proof verified with [`Self::preverify_deferred_proof`] against the values
+    /// passed to the syscall. By default the whole proof is verified.
+    fn check_deferred_proof(
+        &self,
+        proof: &SP1ReduceProof<BabyBearPoseidon2>,
+        vk: &StarkVerifyingKey<BabyBearPoseidon2>,
+        vk_hash: [u32; 8],
+        committed_value_digest: [u32; 8],
+    ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+        self.verify_deferred_proof(proof, vk, vk_hash, committed_value_digest)
+    }
 }
 
 /// A dummy verifier which does nothing.
@@ -13,7 +13,7 @@ impl Syscall for HintLenSyscall {
         // Note: If the user supplies an input > than length 2^32, then the length returned will be
         // truncated to 32-bits. Reading from the syscall will definitely fail in that case, as the
         // BabyBear field is < 2^32.
-        Some(ctx.rt.state.input_stream.front().map_or(u32::MAX, |data| data.len() as u32))
+        Some(ctx.rt.next_input_len().map_or(u32::MAX, |len| len as u32))
     }
 }
 
@@ -21,10 +21,9 @@ pub(crate) struct HintReadSyscall;
 
 impl Syscall for HintReadSyscall {
     fn execute(&self, ctx: &mut SyscallContext, _: SyscallCode, ptr: u32, len: u32) -> Option<u32> {
-        panic_if_input_exhausted(ctx);
-
-        // SAFETY: The input stream is not empty, as checked above, so the back is not None
-        let vec = unsafe { ctx.rt.state.input_stream.pop_front().unwrap_unchecked() };
+        let Some(vec) = ctx.rt.next_input() else {
+            panic!("hint input stream exhausted");
+        };
 
         assert!(!ctx.rt.unconstrained, "hint read should not be used in a unconstrained block");
         assert_eq!(vec.len() as u32, len, "hint input stream read length mismatch");
@@ -53,9 +52,3 @@ impl Syscall for HintReadSyscall {
         None
     }
 }
-
-fn panic_if_input_exhausted(ctx: &SyscallContext) {
-    if ctx.rt.state.input_stream.is_empty() {
-        panic!("hint input stream exhausted");
-    }
-}
@@ -30,13 +30,10 @@ impl Syscall for VerifySyscall {
             if proof_index >= rt.state.proof_stream.len() {
                 panic!("Not enough proofs were written to the runtime.");
             }
-            let (proof, proof_vk) = &rt.state.proof_stream[proof_index].clone();
-
             let vkey_bytes: [u32; 8] = vkey.try_into().unwrap();
             let pv_digest_bytes: [u32; 8] = pv_digest.try_into().unwrap();
             if let Some(verifier) = rt.subproof_verifier {
-                verifier
-                    .verify_deferred_proof(proof, proof_vk, vkey_bytes, pv_digest_bytes)
+                rt.verify_deferred_proof(verifier, proof_index, vkey_bytes, pv_digest_bytes)
                     .unwrap_or_else(|e| {
                         panic!(
                             "Failed to verify proof {proof_index} with digest {}: {}",
@@ -1,5 +1,5 @@
 use sp1_primitives::consts::{
-    fd::{FD_HINT, FD_PUBLIC_VALUES, LOWEST_ALLOWED_FD},
+    fd::{FD_HINT, FD_OUTPUT, FD_PHASE, FD_PUBLIC_VALUES, LOWEST_ALLOWED_FD},
     num_to_comma_separated,
 };
 
@@ -26,6 +26,12 @@ impl Syscall for WriteSyscall {
     /// If fd = 4:
     /// - Update the input stream.
     ///
+    /// If fd = `FD_PHASE`:
+    /// - Mark the start of a program phase, which may end the current shard.
+    ///
+    /// If fd = `FD_OUTPUT`:
+    /// - Write the bytes to the output writer, if any.
+    ///
     /// If the fd matches a hook in the hook registry, invoke the hook.
     ///
     /// Else, log a warning.
@@ -115,7 +121,7 @@ impl Syscall for WriteSyscall {
                 } else if fd == ED_DECOMPRESS {
                     crate::hook::deprecated_hooks::hook_ed_decompress(rt.hook_env(), slice)
                 } else if fd == PUBLIC_VALUES {
-                    rt.state.public_values_stream.extend_from_slice(slice);
+                    rt.commit_public_values(slice);
                     vec![]
                 } else if fd == INPUT {
                     rt.state.input_stream.push_front(slice.to_vec());
@@ -137,9 +143,18 @@ impl Syscall for WriteSyscall {
                 );
             }
         } else if fd == FD_PUBLIC_VALUES {
-            rt.state.public_values_stream.extend_from_slice(slice);
+            rt.commit_public_values(slice);
         } else if fd == FD_HINT {
             rt.state.input_stream.push_front(slice.to_vec());
+        } else if fd == FD_PHASE {
+            let name = String::from_utf8_lossy(slice);
+            rt.mark_phase(&name);
+        } else if fd == FD_OUTPUT {
+            if let Some(ref mut writer) = rt.io_options.output {
+                if let Err(e) = writer.write_all(slice) {
+                    tracing::error!("failed to write to the output writer: {e}");
+                }
+            }
         } else if let Some(mut hook) = rt.hook_registry.get(fd) {
             let res = hook.invoke_hook(rt.hook_env(), slice);
 
@@ -1,6 +1,10 @@
+use std::{fmt, sync::Arc};
+
+use hashbrown::HashMap;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use sp1_core_executor::SP1ReduceProof;
+use sp1_core_executor::{InputKind, InputSegment, SP1ReduceProof};
 use sp1_stark::{baby_bear_poseidon2::BabyBearPoseidon2, StarkVerifyingKey};
+use thiserror::Error;
 
 /// Standard input for the prover.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -10,17 +14,30 @@ pub struct SP1Stdin {
     pub buffer: Vec<Vec<u8>>,
     pub ptr: usize,
     pub proofs: Vec<(SP1ReduceProof<BabyBearPoseidon2>, StarkVerifyingKey<BabyBearPoseidon2>)>,
+    /// Input chunks, read after the buffer. They are shared by the executors proving the program
+    /// instead of being copied into each of them.
+    pub input_chunks: Vec<Arc<Vec<u8>>>,
+    /// The labeled segments of the buffer, in order. Inputs outside of any segment are committed
+    /// inputs.
+    #[serde(default)]
+    pub segments: Vec<InputSegment>,
 }
 
 impl SP1Stdin {
     /// Create a new `SP1Stdin`.
     pub const fn new() -> Self {
-        Self { buffer: Vec::new(), ptr: 0, proofs: Vec::new() }
+        Self {
+            buffer: Vec::new(),
+            ptr: 0,
+            proofs: Vec::new(),
+            input_chunks: Vec::new(),
+            segments: Vec::new(),
+        }
     }
 
     /// Create a `SP1Stdin` from a slice of bytes.
     pub fn from(data: &[u8]) -> Self {
-        Self { buffer: vec![data.to_vec()], ptr: 0, proofs: Vec::new() }
+        Self { buffer: vec![data.to_vec()], ..Self::default() }
     }
 
     /// Read a value from the buffer.
@@ -53,6 +70,121 @@ impl SP1Stdin {
         self.buffer.push(vec);
     }
 
+    /// Write a value to the buffer as an input of the segment `label`.
+    ///
+    /// Consecutive inputs written with the same label and kind form a single segment.
+    pub fn write_labeled<T: Serialize>(&mut self, label: &str, kind: InputKind, data: &T) {
+        self.write(data);
+        self.label_last_input(label, kind);
+    }
+
+    /// Write a slice of bytes to the buffer as an input of the segment `label`.
+    pub fn write_labeled_slice(&mut self, label: &str, kind: InputKind, slice: &[u8]) {
+        self.write_slice(slice);
+        self.label_last_input(label, kind);
+    }
+
+    /// Write a value to the buffer as a hint of the segment `label`, see
+    /// [`InputKind::Hint`].
+    pub fn write_hint<T: Serialize>(&mut self, label: &str, data: &T) {
+        self.write_labeled(label, InputKind::Hint, data);
+    }
+
+    fn label_last_input(&mut self, label: &str, kind: InputKind) {
+        let index = self.buffer.len() - 1;
+        match self.segments.last_mut() {
+            Some(segment)
+                if segment.label == label &&
+                    segment.kind == kind &&
+                    segment.start + segment.len == index =>
+            {
+                segment.len += 1;
+            }
+            _ => self.segments.push(InputSegment {
+                label: label.to_string(),
+                kind,
+                start: index,
+                len: 1,
+            }),
+        }
+    }
+
+    /// The kind of the input at `index` of the buffer.
+    #[must_use]
+    pub fn input_kind(&self, index: usize) -> InputKind {
+        self.segments
+            .iter()
+            .find(|segment| (segment.start..segment.start + segment.len).contains(&index))
+            .map_or(InputKind::Committed, |segment| segment.kind)
+    }
+
+    /// The standard input with every hint segment re-derived by the callback of `replayer`
+    /// registered for its label, from the committed inputs written before it.
+    ///
+    /// The committed inputs and the proofs are kept as is, so executing the program on the
+    /// replayed input checks that its hints can be derived on the host.
+    pub fn replay_hints(&self, replayer: &HintReplayer) -> Result<Self, HintReplayError> {
+        let mut buffer = Vec::with_capacity(self.buffer.len());
+        let mut segments_replayed = Vec::with_capacity(self.segments.len());
+        let mut committed = Vec::new();
+        let mut segments = self.segments.iter().peekable();
+        let mut index = 0;
+        while index < self.buffer.len() {
+            let Some(segment) = segments.next_if(|segment| segment.start == index) else {
+                committed.push(self.buffer[index].clone());
+                buffer.push(self.buffer[index].clone());
+                index += 1;
+                continue;
+            };
+            let inputs = match segment.kind {
+                InputKind::Committed => {
+                    let inputs = self.buffer[index..index + segment.len].to_vec();
+                    committed.extend(inputs.iter().cloned());
+                    inputs
+                }
+                InputKind::Hint => {
+                    let callback = replayer
+                        .callbacks
+                        .get(&segment.label)
+                        .ok_or_else(|| HintReplayError::MissingCallback(segment.label.clone()))?;
+                    callback(&committed)
+                }
+            };
+            segments_replayed.push(InputSegment {
+                label: segment.label.clone(),
+                kind: segment.kind,
+                start: buffer.len(),
+                len: inputs.len(),
+            });
+            buffer.extend(inputs);
+            index += segment.len;
+        }
+        Ok(Self {
+            buffer,
+            ptr: 0,
+            proofs: self.proofs.clone(),
+            input_chunks: self.input_chunks.clone(),
+            segments: segments_replayed,
+        })
+    }
+
+    /// Check that the callbacks of `replayer` re-derive every hint segment of the standard input,
+    /// see [`Self::replay_hints`].
+    pub fn check_hints(&self, replayer: &HintReplayer) -> Result<(), HintReplayError> {
+        let replayed = self.replay_hints(replayer)?;
+        for (recorded, segment) in self.segments.iter().zip(&replayed.segments) {
+            let inputs = |stdin: &Self, segment: &InputSegment| {
+                stdin.buffer[segment.start..segment.start + segment.len].to_vec()
+            };
+            if recorded.kind == InputKind::Hint &&
+                inputs(self, recorded) != inputs(&replayed, segment)
+            {
+                return Err(HintReplayError::Mismatch(recorded.label.clone()));
+            }
+        }
+        Ok(())
+    }
+
     pub fn write_proof(
         &mut self,
         proof: SP1ReduceProof<BabyBearPoseidon2>,
@@ -62,6 +194,48 @@ impl SP1Stdin {
     }
 }
 
+/// A host callback deriving the inputs of a hint segment from the committed inputs before it.
+pub type HintCallback = Arc<dyn Fn(&[Vec<u8>]) -> Vec<Vec<u8>> + Send + Sync>;
+
+/// The host callbacks re-deriving the hint segments of an [`SP1Stdin`], by label.
+#[derive(Clone, Default)]
+pub struct HintReplayer {
+    callbacks: HashMap<String, HintCallback>,
+}
+
+impl HintReplayer {
+    /// Create a replayer without callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Re-derive the hint segments labeled `label` with `callback`.
+    #[must_use]
+    pub fn with_callback(
+        mut self,
+        label: impl Into<String>,
+        callback: impl Fn(&[Vec<u8>]) -> Vec<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.callbacks.insert(label.into(), Arc::new(callback));
+        self
+    }
+}
+
+impl fmt::Debug for HintReplayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HintReplayer").field("labels", &self.callbacks.keys()).finish()
+    }
+}
+
+/// An error replaying the hints of an [`SP1Stdin`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum HintReplayError {
+    #[error("no callback is registered for the hint segment {0}")]
+    MissingCallback(String),
+    #[error("the replayed hints of the segment {0} differ from the recorded ones")]
+    Mismatch(String),
+}
+
 pub mod proof_serde {
     use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
     use sp1_stark::{MachineProof, StarkGenericConfig};
@@ -98,3 +272,39 @@ pub mod proof_serde {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_hints() {
+        // A committed input, two hints derived from it, and a labeled committed input.
+        let mut stdin = SP1Stdin::new();
+        stdin.write_slice(&[3]);
+        stdin.write_labeled_slice("square", InputKind::Hint, &[9]);
+        stdin.write_labeled_slice("square", InputKind::Hint, &[9]);
+        stdin.write_labeled_slice("nonce", InputKind::Committed, &[1]);
+        assert_eq!(stdin.segments.len(), 2);
+        assert_eq!((stdin.segments[0].start, stdin.segments[0].len), (1, 2));
+        assert_eq!(stdin.input_kind(0), InputKind::Committed);
+        assert_eq!(stdin.input_kind(2), InputKind::Hint);
+        assert_eq!(stdin.input_kind(3), InputKind::Committed);
+
+        let squares = |n: u8| {
+            HintReplayer::new().with_callback("square", move |committed: &[Vec<u8>]| {
+                vec![vec![committed[0][0] * n]; 2]
+            })
+        };
+        let replayed = stdin.replay_hints(&squares(3)).unwrap();
+        assert_eq!(replayed.buffer, stdin.buffer);
+        assert_eq!(replayed.segments, stdin.segments);
+        stdin.check_hints(&squares(3)).unwrap();
+
+        assert_eq!(stdin.check_hints(&squares(2)), Err(HintReplayError::Mismatch("square".into())));
+        assert_eq!(
+            stdin.check_hints(&HintReplayer::new()),
+            Err(HintReplayError::MissingCallback("square".into()))
+        );
+    }
+}
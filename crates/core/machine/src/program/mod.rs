@@ -180,7 +180,7 @@ mod tests {
     use p3_baby_bear::BabyBear;
 
     use p3_matrix::dense::RowMajorMatrix;
-    use sp1_core_executor::{ExecutionRecord, Instruction, Opcode, Program};
+    use sp1_core_executor::{ExecutionRecord, GuestSymbols, Instruction, Opcode, Program};
     use sp1_stark::air::MachineAir;
 
     use crate::program::ProgramChip;
@@ -203,6 +203,7 @@ mod tests {
                 pc_base: 0,
                 memory_image: HashMap::new(),
                 preprocessed_shape: None,
+                guest_symbols: GuestSymbols::default(),
             }),
             ..Default::default()
         };
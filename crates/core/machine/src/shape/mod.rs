@@ -10,7 +10,7 @@ use num::Integer;
 use p3_baby_bear::BabyBear;
 use p3_field::PrimeField32;
 use p3_util::log2_ceil_usize;
-use sp1_core_executor::{ExecutionRecord, Instruction, Opcode, Program, RiscvAirId};
+use sp1_core_executor::{rv32im_costs, ExecutionRecord, Instruction, Opcode, Program, RiscvAirId};
 use sp1_stark::{
     air::MachineAir,
     shape::{OrderedShape, Shape, ShapeCluster},
@@ -389,6 +389,22 @@ impl<F: PrimeField32> CoreShapeConfig<F> {
         max_core_shapes.collect()
     }
 
+    /// Estimate the memory taken by the records and traces of a shard of at most
+    /// `2^max_log_shard_size` cycles, from the largest of its maximal shapes.
+    #[must_use]
+    pub fn estimate_shard_memory(&self, max_log_shard_size: usize) -> usize {
+        let costs = rv32im_costs();
+        let max_cells = self
+            .maximal_core_shapes(max_log_shard_size)
+            .iter()
+            .map(|shape| shape.estimate_lde_size(&costs))
+            .max()
+            .unwrap_or_default();
+        // Every trace cell is a field element, and the records it is generated from take about as
+        // much again.
+        max_cells * 2 * std::mem::size_of::<F>()
+    }
+
     pub fn maximal_core_plus_precompile_shapes(
         &self,
         max_log_shard_size: usize,
@@ -449,6 +465,37 @@ impl<F: PrimeField32> CoreShapeConfig<F> {
             })
             .collect()
     }
+
+    /// A digest of the allowed shapes and their costs.
+    ///
+    /// Provers with the same digest fix the same preprocessed shapes, and hence produce the same
+    /// verifying keys for a program.
+    pub fn digest(&self) -> [u8; 32] {
+        fn sorted<K: Ord + Copy, V: Clone>(
+            iter: impl IntoIterator<Item = (K, V)>,
+        ) -> BTreeMap<K, V> {
+            iter.into_iter().collect()
+        }
+        fn cluster(cluster: &ShapeCluster<RiscvAirId>) -> BTreeMap<RiscvAirId, Vec<Option<usize>>> {
+            sorted(cluster.iter().map(|(k, v)| (*k, v.clone())))
+        }
+
+        let canonical = (
+            cluster(&self.partial_preprocessed_shapes),
+            self.partial_core_shapes
+                .iter()
+                .map(|(log_shard_size, clusters)| {
+                    (*log_shard_size, clusters.iter().map(cluster).collect::<Vec<_>>())
+                })
+                .collect::<BTreeMap<_, _>>(),
+            cluster(&self.partial_memory_shapes),
+            sorted(self.partial_precompile_shapes.iter().map(|(k, v)| (*k, v.clone()))),
+            self.partial_small_shapes.iter().map(cluster).collect::<Vec<_>>(),
+            sorted(self.costs.iter().map(|(k, v)| (*k, *v))),
+        );
+        let bytes = bincode::serialize(&canonical).expect("failed to serialize shape config");
+        sp1_primitives::io::blake3_hash(&bytes).try_into().unwrap()
+    }
 }
 
 impl<F: PrimeField32> Default for CoreShapeConfig<F> {
@@ -1,15 +1,23 @@
 pub mod concurrency;
 mod logger;
+mod profile;
 mod prove;
+mod resume;
+mod shard_memo;
 mod span;
+mod strict;
 mod test;
 pub mod uni_stark;
 
 pub use logger::*;
 use p3_field::Field;
+pub use profile::*;
 pub use prove::*;
+pub use resume::*;
+pub use shard_memo::*;
 use sp1_curves::params::Limbs;
 pub use span::*;
+pub use strict::*;
 pub use test::*;
 pub use uni_stark::*;
 
@@ -0,0 +1,221 @@
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use sp1_stark::{shape::OrderedShape, SP1CoreOpts};
+
+/// The number of slowest shards and recursion nodes kept by a [`ProvingProfile`].
+pub const PROFILE_TOP_N: usize = 5;
+
+/// The time spent in each stage of proving a shard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageTimings {
+    /// Executing the program of the shard, to generate its record.
+    pub execute: Duration,
+    /// Generating the dependencies and the traces of the record.
+    pub trace_gen: Duration,
+    /// Committing to the traces.
+    pub commit: Duration,
+    /// Computing the opening proof.
+    pub open: Duration,
+}
+
+impl StageTimings {
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.execute + self.trace_gen + self.commit + self.open
+    }
+}
+
+/// The stage timings of a core shard or a recursion node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardProfile {
+    /// The layer of the recursion tree, or zero for core shards.
+    pub layer: usize,
+    /// The index of the shard, or of the node in the order the recursion tree is proven.
+    pub index: usize,
+    pub shape: OrderedShape,
+    pub timings: StageTimings,
+}
+
+/// The distribution of the real heights of the traces of a chip across core shards.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceHeightHistogram {
+    /// The number of shards by the log2 of their real trace height, rounded up, i.e. by the
+    /// smallest shape height that fits them.
+    pub log_heights: BTreeMap<usize, usize>,
+    /// The largest real height.
+    pub max_height: usize,
+}
+
+impl TraceHeightHistogram {
+    pub fn record(&mut self, height: usize) {
+        *self.log_heights.entry(height.next_power_of_two().ilog2() as usize).or_default() += 1;
+        self.max_height = self.max_height.max(height);
+    }
+
+    pub fn merge(&mut self, other: &TraceHeightHistogram) {
+        for (&log_height, &count) in &other.log_heights {
+            *self.log_heights.entry(log_height).or_default() += count;
+        }
+        self.max_height = self.max_height.max(other.max_height);
+    }
+}
+
+/// The slowest core shards and recursion nodes of a proof, so that a performance regression
+/// points at the shapes responsible for it.
+///
+/// Only the [`PROFILE_TOP_N`] slowest of each are kept, slowest first, so profiling costs a
+/// handful of timers per shard whatever the size of the proof.
+///
+/// With [`SP1CoreOpts::trace_height_histograms`](sp1_stark::SP1CoreOpts), the real trace heights
+/// of every chip in every core shard are also collected, to tune shape configs from the heights
+/// programs actually reach.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvingProfile {
+    pub shards: Vec<ShardProfile>,
+    pub nodes: Vec<ShardProfile>,
+    /// The trace height histograms by chip, empty unless they are collected.
+    #[serde(default)]
+    pub trace_heights: BTreeMap<String, TraceHeightHistogram>,
+    /// The core options the core proof was generated with, if they were chosen among candidates
+    /// by the prover.
+    #[serde(default)]
+    pub selected_core_opts: Option<SP1CoreOpts>,
+}
+
+impl ProvingProfile {
+    pub fn record_shard(&mut self, shard: ShardProfile) {
+        Self::insert(&mut self.shards, shard);
+    }
+
+    pub fn record_node(&mut self, node: ShardProfile) {
+        Self::insert(&mut self.nodes, node);
+    }
+
+    /// Record the real trace heights of the chips of a core shard.
+    pub fn record_trace_heights(&mut self, heights: impl IntoIterator<Item = (String, usize)>) {
+        for (chip, height) in heights {
+            self.trace_heights.entry(chip).or_default().record(height);
+        }
+    }
+
+    /// Keep the slowest shards and nodes of both profiles, and add up their trace heights.
+    pub fn merge(&mut self, other: ProvingProfile) {
+        other.shards.into_iter().for_each(|shard| self.record_shard(shard));
+        other.nodes.into_iter().for_each(|node| self.record_node(node));
+        for (chip, histogram) in &other.trace_heights {
+            self.trace_heights.entry(chip.clone()).or_default().merge(histogram);
+        }
+        self.selected_core_opts = other.selected_core_opts.or(self.selected_core_opts);
+    }
+
+    fn insert(profiles: &mut Vec<ShardProfile>, profile: ShardProfile) {
+        let total = profile.timings.total();
+        let position = profiles.partition_point(|p| p.timings.total() >= total);
+        if position < PROFILE_TOP_N {
+            profiles.insert(position, profile);
+            profiles.truncate(PROFILE_TOP_N);
+        }
+    }
+
+    /// A compact table of the slowest shards and nodes, one per line, with the stage timings in
+    /// milliseconds and the three largest chips of their shapes, followed by the trace height
+    /// histograms, one chip per line.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (kind, profiles) in [("shard", &self.shards), ("node", &self.nodes)] {
+            for p in profiles {
+                let t = &p.timings;
+                let mut chips = p.shape.inner.clone();
+                chips.sort_by_key(|(_, log_height)| std::cmp::Reverse(*log_height));
+                let chips = chips
+                    .iter()
+                    .take(3)
+                    .map(|(name, log_height)| format!("{name}:{log_height}"))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                writeln!(
+                    summary,
+                    "{kind} {}/{}: total={}ms execute={}ms trace_gen={}ms commit={}ms open={}ms \
+                     shape=[{chips}]",
+                    p.layer,
+                    p.index,
+                    t.total().as_millis(),
+                    t.execute.as_millis(),
+                    t.trace_gen.as_millis(),
+                    t.commit.as_millis(),
+                    t.open.as_millis(),
+                )
+                .unwrap();
+            }
+        }
+        for (chip, histogram) in &self.trace_heights {
+            let buckets = histogram
+                .log_heights
+                .iter()
+                .map(|(log_height, count)| format!("2^{log_height}:{count}"))
+                .collect::<Vec<_>>()
+                .join(",");
+            writeln!(summary, "heights {chip}: max={} [{buckets}]", histogram.max_height).unwrap();
+        }
+        if let Some(opts) = &self.selected_core_opts {
+            writeln!(
+                summary,
+                "selected core opts: shard_size={} deferred_split_threshold={}",
+                opts.shard_size, opts.split_opts.deferred
+            )
+            .unwrap();
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_keeps_slowest() {
+        let mut profile = ProvingProfile::default();
+        for index in 0..10 {
+            profile.record_shard(ShardProfile {
+                layer: 0,
+                index,
+                shape: OrderedShape { inner: vec![("Cpu".to_string(), 20)] },
+                timings: StageTimings {
+                    open: Duration::from_millis(index as u64 * 7 % 10),
+                    ..StageTimings::default()
+                },
+            });
+        }
+        let indices = profile.shards.iter().map(|shard| shard.index).collect::<Vec<_>>();
+        assert_eq!(indices, vec![7, 4, 1, 8, 5]);
+        assert!(profile.nodes.is_empty());
+    }
+
+    #[test]
+    fn test_trace_height_histograms() {
+        let mut profile = ProvingProfile::default();
+        profile.record_trace_heights([("Cpu".to_string(), 1000), ("Add".to_string(), 1)]);
+        let mut other = ProvingProfile::default();
+        other.record_trace_heights([("Cpu".to_string(), 1024), ("Cpu".to_string(), 1025)]);
+        profile.merge(other);
+
+        let cpu = &profile.trace_heights["Cpu"];
+        assert_eq!(cpu.log_heights, BTreeMap::from([(10, 2), (11, 1)]));
+        assert_eq!(cpu.max_height, 1025);
+        assert_eq!(profile.trace_heights["Add"].log_heights, BTreeMap::from([(0, 1)]));
+    }
+
+    #[test]
+    fn test_merge_keeps_latest_selected_opts() {
+        let first = SP1CoreOpts { shard_size: 1 << 20, ..SP1CoreOpts::default() };
+        let second = SP1CoreOpts { shard_size: 1 << 19, ..SP1CoreOpts::default() };
+        let mut profile = ProvingProfile { selected_core_opts: Some(first), ..Default::default() };
+        profile.merge(ProvingProfile::default());
+        assert_eq!(profile.selected_core_opts, Some(first));
+        profile.merge(ProvingProfile { selected_core_opts: Some(second), ..Default::default() });
+        assert_eq!(profile.selected_core_opts, Some(second));
+    }
+}
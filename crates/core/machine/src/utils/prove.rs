@@ -1,10 +1,12 @@
 use p3_matrix::dense::RowMajorMatrix;
 use std::{
+    collections::BTreeMap,
     error::Error,
     fs::File,
     io::{self, Seek, SeekFrom},
     str::FromStr,
     sync::{
+        atomic::{AtomicUsize, Ordering},
         mpsc::{channel, sync_channel, Sender},
         Arc, Mutex,
     },
@@ -18,7 +20,7 @@ use crate::{
     utils::test::MaliciousTracePVGeneratorType,
 };
 use p3_maybe_rayon::prelude::*;
-use sp1_stark::{MachineProvingKey, StarkVerifyingKey};
+use sp1_stark::{FriParamsMismatch, MachineProvingKey, ProgressEvent, StarkVerifyingKey};
 use thiserror::Error;
 
 use p3_field::PrimeField32;
@@ -26,7 +28,11 @@ use sp1_stark::air::MachineAir;
 
 use crate::{
     io::SP1Stdin,
-    utils::{chunk_vec, concurrency::TurnBasedSync},
+    utils::{
+        chunk_vec, concurrency::TurnBasedSync, load_resume_point, reuse_shard_proof,
+        save_execution, save_prover_state, save_shard_proof, shard_proof_key, store_shard_proof,
+        ProvingProfile, ShardProfile, StageTimings, StrictModeError,
+    },
 };
 use sp1_core_executor::{
     estimator::RecordEstimator,
@@ -35,8 +41,8 @@ use sp1_core_executor::{
 };
 
 use sp1_core_executor::{
-    subproof::NoOpSubproofVerifier, ExecutionError, ExecutionRecord, ExecutionReport, Executor,
-    Program, SP1Context,
+    cancel::CancellationToken, spill::SpillCipher, subproof::NoOpSubproofVerifier, ExecutionError,
+    ExecutionRecord, ExecutionReport, Executor, Program, SP1Context,
 };
 use sp1_stark::{
     air::PublicValues, shape::OrderedShape, Com, MachineProof, MachineProver, MachineRecord,
@@ -64,9 +70,9 @@ where
 {
     let (proof_tx, proof_rx) = channel();
     let (shape_tx, shape_rx) = channel();
-    let (public_values, cycles) = prove_core_stream(
+    let (public_values, cycles, _) = prove_core_stream(
         prover,
-        pk,
+        || pk,
         program,
         stdin,
         opts,
@@ -85,10 +91,48 @@ where
     Ok((proof, public_values, cycles))
 }
 
+/// The real, unpadded trace heights of the chips of a shard with events.
+fn real_trace_heights(record: &ExecutionRecord) -> Vec<(String, usize)> {
+    let mut heights = BTreeMap::new();
+    let precompile_heights = record.precompile_heights().map(|(air, (height, _, _))| (air, height));
+    for (air, height) in
+        record.core_heights().into_iter().chain(record.memory_heights()).chain(precompile_heights)
+    {
+        let max: &mut usize = heights.entry(air).or_default();
+        *max = (*max).max(height);
+    }
+    heights
+        .into_iter()
+        .filter(|&(_, height)| height > 0)
+        .map(|(air, height)| (air.to_string(), height))
+        .collect()
+}
+
+/// A rough estimate of the memory taken by the records and traces of a shard per cycle, used when
+/// shapes are not fixed.
+const SHARD_BYTES_PER_CYCLE: usize = 1 << 10;
+
+/// Estimate the memory taken by the records and traces of a shard of `shard_size` cycles.
+pub fn estimate_shard_memory<F: PrimeField32>(
+    shape_config: Option<&CoreShapeConfig<F>>,
+    shard_size: usize,
+) -> usize {
+    match shape_config {
+        Some(config) => config.estimate_shard_memory(shard_size.ilog2() as usize),
+        None => shard_size.saturating_mul(SHARD_BYTES_PER_CYCLE),
+    }
+}
+
+/// Prove the execution of `program` and stream the shard proofs and shapes.
+///
+/// Returns the public values stream, the number of cycles and the profile of the slowest shards.
+///
+/// `pk` is only called once the first shard is ready to be proven, so the proving key may still be
+/// uploading to the device while the program executes.
 #[allow(clippy::too_many_arguments)]
-pub fn prove_core_stream<SC: StarkGenericConfig, P: MachineProver<SC, RiscvAir<SC::Val>>>(
+pub fn prove_core_stream<'a, SC: StarkGenericConfig, P: MachineProver<SC, RiscvAir<SC::Val>>>(
     prover: &P,
-    pk: &P::DeviceProvingKey,
+    pk: impl Fn() -> &'a P::DeviceProvingKey + Sync,
     program: Program,
     stdin: &SP1Stdin,
     opts: SP1CoreOpts,
@@ -98,7 +142,7 @@ pub fn prove_core_stream<SC: StarkGenericConfig, P: MachineProver<SC, RiscvAir<S
     shape_and_done_tx: Sender<(OrderedShape, bool)>,
     malicious_trace_pv_generator: Option<MaliciousTracePVGeneratorType<SC::Val, P>>, /* This is used for failure test cases that generate malicious traces and public values. */
     gas_calculator: Option<Box<dyn FnOnce(&RecordEstimator) -> Result<u64, Box<dyn Error>> + '_>>,
-) -> Result<(Vec<u8>, u64), SP1CoreProverError>
+) -> Result<(Vec<u8>, u64, ProvingProfile), SP1CoreProverError>
 where
     SC::Val: PrimeField32,
     SC::Challenger: 'static + Clone + Send,
@@ -107,15 +151,69 @@ where
     PcsProverData<SC>: Send + Sync,
 {
     // Setup the runtime.
+    let spill_cipher = context.spill_cipher;
+    let shard_proof_store = context.shard_proof_store;
+    let trace_height_histograms = opts.trace_height_histograms;
+    let shard_limit = context.shard_limit;
+    let journal = context.core_journal;
+    let cancellation = context.cancellation.clone();
+    let progress = context.progress.clone();
+    let cancelled = || cancellation.as_ref().is_some_and(CancellationToken::is_cancelled);
     let mut runtime = Box::new(Executor::with_context(program.clone(), opts, context));
+    let input_chunks = stdin.input_chunks.clone();
+    runtime.write_input_chunks(input_chunks.clone());
+
+    // Size the channels to the memory budget, if any. The shard memory is only estimated with a
+    // budget, since it enumerates the maximal shapes.
+    let shard_bytes =
+        opts.memory_budget.map_or(0, |_| estimate_shard_memory(shape_config, opts.shard_size));
+    let (checkpoints_channel_capacity, records_and_traces_channel_capacity) =
+        opts.channel_capacities(shard_bytes);
+    tracing::debug!(
+        "checkpoints channel capacity: {checkpoints_channel_capacity}, \
+        records and traces channel capacity: {records_and_traces_channel_capacity}"
+    );
+
     runtime.maximal_shapes = shape_config.map(|config| {
         config.maximal_core_shapes(opts.shard_size.ilog2() as usize).into_iter().collect()
     });
-    runtime.write_vecs(&stdin.buffer);
-    for proof in stdin.proofs.iter() {
-        let (proof, vk) = proof.clone();
-        runtime.write_proof(proof, vk);
-    }
+
+    // Resume after the latest checkpoint proven to the journal, if any, or start from the input.
+    let resume_point = journal
+        .map(|journal| load_resume_point::<SC>(journal, spill_cipher))
+        .transpose()
+        .map_err(SP1CoreProverError::IoError)?
+        .flatten();
+    let first_index = resume_point.as_ref().map_or(0, |point| point.index + 1);
+    let (initial_state, initial_deferred, resumed_proofs) = match resume_point {
+        Some(point) => {
+            tracing::info!(
+                "resuming core proving after checkpoint {} with {} shards proven",
+                point.index,
+                point.proofs.len()
+            );
+            runtime.state = point.execution;
+            (point.public_values, point.deferred, point.proofs)
+        }
+        None => {
+            runtime.write_vecs(&stdin.buffer);
+            for proof in stdin.proofs.iter() {
+                let (proof, vk) = proof.clone();
+                runtime.write_proof(proof, vk);
+            }
+            let deferred = ExecutionRecord::new(program.clone().into());
+            (PublicValues::<u32, u32>::default().reset(), deferred, Vec::new())
+        }
+    };
+
+    // The gas is estimated from the whole execution, which is not replayed when resuming.
+    let gas_calculator = gas_calculator.filter(|_| {
+        if first_index > 0 {
+            tracing::warn!("gas is not calculated when resuming core proving");
+        }
+        first_index == 0
+    });
+
     // Set the record estimator to collect data for gas calculation.
     if gas_calculator.is_some() {
         runtime.record_estimator = Some(Box::default());
@@ -131,13 +229,14 @@ where
     // Record the start of the process.
     let proving_start = Instant::now();
     let span = tracing::Span::current().clone();
+    let pk = &pk;
     std::thread::scope(move |s| {
         let _span = span.enter();
 
         // Spawn the checkpoint generator thread.
         let checkpoint_generator_span = tracing::Span::current().clone();
         let (checkpoints_tx, checkpoints_rx) =
-            sync_channel::<(usize, File, bool, u64)>(opts.checkpoints_channel_capacity);
+            sync_channel::<(usize, File, bool, u64)>(checkpoints_channel_capacity);
         let checkpoint_generator_handle: ScopedJoinHandle<Result<_, SP1CoreProverError>> =
             s.spawn(move || {
                 let _span = checkpoint_generator_span.enter();
@@ -148,16 +247,42 @@ where
                         let span = tracing::debug_span!("batch");
                         let _span = span.enter();
 
+                        // Stop executing once cancelled. Dropping the checkpoints channel shuts
+                        // down the workers.
+                        if cancelled() {
+                            break Err(SP1CoreProverError::Cancelled);
+                        }
+
+                        // Do not execute past the shard limit, if any.
+                        if let Some(limit) = shard_limit {
+                            let remaining = (limit + 1).saturating_sub(runtime.state.current_shard);
+                            runtime.shard_batch_size =
+                                remaining.min(opts.shard_batch_size as u32).max(1);
+                        }
+
                         // Execute the runtime until we reach a checkpoint.
                         let (checkpoint, _, done) = runtime
                             .execute_state(false)
                             .map_err(SP1CoreProverError::ExecutionError)?;
 
+                        // Persist the execution state, from which execution resumes after this
+                        // checkpoint.
+                        if let Some(journal) = journal.filter(|_| !done) {
+                            if let Err(e) = save_execution(
+                                journal,
+                                first_index + index,
+                                &runtime.state,
+                                spill_cipher,
+                            ) {
+                                tracing::warn!("failed to write the execution to the journal: {e}");
+                            }
+                        }
+
                         // Save the checkpoint to a temp file.
                         let mut checkpoint_file =
                             tempfile::tempfile().map_err(SP1CoreProverError::IoError)?;
                         checkpoint
-                            .save(&mut checkpoint_file)
+                            .save_with(&mut checkpoint_file, spill_cipher)
                             .map_err(SP1CoreProverError::IoError)?;
 
                         // Send the checkpoint.
@@ -165,8 +290,9 @@ where
                             .send((index, checkpoint_file, done, runtime.state.global_clk))
                             .unwrap();
 
-                        // If we've reached the final checkpoint, break out of the loop.
-                        if done {
+                        // If we've reached the final checkpoint or the shard limit, break out of
+                        // the loop.
+                        if done || shard_limit.is_some_and(|l| runtime.state.current_shard > l) {
                             break Ok(runtime);
                         }
 
@@ -176,23 +302,21 @@ where
                 })
             });
 
-        // Create the challenger and observe the verifying key.
-        let mut challenger = prover.config().challenger();
-        pk.observe_into(&mut challenger);
-
         // Spawn the phase 2 record generator thread.
         let p2_record_gen_sync = Arc::new(TurnBasedSync::new());
         let p2_trace_gen_sync = Arc::new(TurnBasedSync::new());
         let (p2_records_and_traces_tx, p2_records_and_traces_rx) =
-            sync_channel::<(Vec<ExecutionRecord>, Vec<Vec<(String, RowMajorMatrix<Val<SC>>)>>)>(
-                opts.records_and_traces_channel_capacity,
-            );
+            sync_channel::<(
+                Vec<ExecutionRecord>,
+                Vec<Vec<(String, RowMajorMatrix<Val<SC>>)>>,
+                Vec<StageTimings>,
+            )>(records_and_traces_channel_capacity);
         let p2_records_and_traces_tx = Arc::new(Mutex::new(p2_records_and_traces_tx));
 
         let shape_tx = Arc::new(Mutex::new(shape_and_done_tx));
         let report_aggregate = Arc::new(Mutex::new(ExecutionReport::default()));
-        let state = Arc::new(Mutex::new(PublicValues::<u32, u32>::default().reset()));
-        let deferred = Arc::new(Mutex::new(ExecutionRecord::new(program.clone().into())));
+        let state = Arc::new(Mutex::new(initial_state));
+        let deferred = Arc::new(Mutex::new(initial_deferred));
         let mut p2_record_and_trace_gen_handles = Vec::new();
         let checkpoints_rx = Arc::new(Mutex::new(checkpoints_rx));
         for _ in 0..opts.trace_gen_workers {
@@ -206,6 +330,7 @@ where
             let state = Arc::clone(&state);
             let deferred = Arc::clone(&deferred);
             let program = program.clone();
+            let input_chunks = input_chunks.clone();
             let span = tracing::Span::current().clone();
 
             #[cfg(feature = "debug")]
@@ -217,16 +342,32 @@ where
                     loop {
                         let received = { checkpoints_rx.lock().unwrap().recv() };
                         if let Ok((index, mut checkpoint, done, num_cycles)) = received {
+                            // Skip the checkpoint once cancelled, still taking the turns so that
+                            // the other workers are not blocked on them.
+                            if cancelled() {
+                                record_gen_sync.wait_for_turn(index);
+                                record_gen_sync.advance_turn();
+                                trace_gen_sync.wait_for_turn(index);
+                                trace_gen_sync.advance_turn();
+                                continue;
+                            }
+
+                            let execute_start = Instant::now();
                             let (mut records, report) = tracing::debug_span!("trace checkpoint")
                                 .in_scope(|| {
                                     trace_checkpoint::<SC>(
                                         program.clone(),
                                         &checkpoint,
+                                        &input_chunks,
+                                        spill_cipher,
                                         opts,
                                         shape_config,
                                     )
                                 });
 
+                            let execute = execute_start.elapsed();
+                            let trace_gen_start = Instant::now();
+
                             // Trace the checkpoint and reconstruct the execution records.
                             *report_aggregate.lock().unwrap() += report;
                             checkpoint
@@ -328,8 +469,8 @@ where
 
                             if shape_fixed_records.is_none() {
                                 // See if any deferred shards are ready to be committed to.
-                                let mut deferred = deferred.split(done, None, opts.split_opts);
-                                tracing::debug!("deferred {} records", deferred.len());
+                                let mut split = deferred.split(done, None, opts.split_opts);
+                                tracing::debug!("deferred {} records", split.len());
 
                                 // Update the public values & prover state for the shards which do
                                 // not contain "cpu events" before
@@ -337,7 +478,7 @@ where
                                 if !done {
                                     state.execution_shard += 1;
                                 }
-                                for record in deferred.iter_mut() {
+                                for record in split.iter_mut() {
                                     state.shard += 1;
                                     state.previous_init_addr_bits =
                                         record.public_values.previous_init_addr_bits;
@@ -350,7 +491,23 @@ where
                                     state.start_pc = state.next_pc;
                                     record.public_values = *state;
                                 }
-                                records.append(&mut deferred);
+                                records.append(&mut split);
+
+                                // Persist the state of the prover, from which proving resumes
+                                // after this checkpoint.
+                                if let Some(journal) = journal.filter(|_| !done) {
+                                    if let Err(e) = save_prover_state(
+                                        journal,
+                                        first_index + index,
+                                        &state,
+                                        &deferred,
+                                        spill_cipher,
+                                    ) {
+                                        tracing::warn!(
+                                            "failed to write the prover state to the journal: {e}"
+                                        );
+                                    }
+                                }
 
                                 // Generate the dependencies.
                                 tracing::debug_span!("generate dependencies", index).in_scope(
@@ -398,37 +555,57 @@ where
                             #[cfg(feature = "debug")]
                             all_records_tx.send(records.clone()).unwrap();
 
-                            let mut main_traces = Vec::new();
-                            if let Some(malicious_trace_pv_generator) = malicious_trace_pv_generator
-                            {
-                                tracing::info_span!("generate main traces", index).in_scope(|| {
-                                    main_traces = records
-                                        .par_iter_mut()
-                                        .map(|record| malicious_trace_pv_generator(prover, record))
-                                        .collect::<Vec<_>>();
-                                });
-                            } else {
+                            // The checkpoint is executed and its dependencies generated for all
+                            // of its shards at once, so their time is split evenly between them.
+                            let num_records = records.len().max(1) as u32;
+                            let shared_trace_gen = trace_gen_start.elapsed() / num_records;
+                            let timings_since = |start: Instant| StageTimings {
+                                execute: execute / num_records,
+                                trace_gen: shared_trace_gen + start.elapsed(),
+                                ..StageTimings::default()
+                            };
+
+                            let (main_traces, timings): (Vec<_>, Vec<_>) =
                                 tracing::info_span!("generate main traces", index).in_scope(|| {
-                                    main_traces = records
-                                        .par_iter()
-                                        .map(|record| prover.generate_traces(record))
-                                        .collect::<Vec<_>>();
+                                    if let Some(malicious_trace_pv_generator) =
+                                        malicious_trace_pv_generator
+                                    {
+                                        records
+                                            .par_iter_mut()
+                                            .map(|record| {
+                                                let start = Instant::now();
+                                                let traces =
+                                                    malicious_trace_pv_generator(prover, record);
+                                                (traces, timings_since(start))
+                                            })
+                                            .unzip()
+                                    } else {
+                                        records
+                                            .par_iter()
+                                            .map(|record| {
+                                                let start = Instant::now();
+                                                let traces = prover.generate_traces(record);
+                                                (traces, timings_since(start))
+                                            })
+                                            .unzip()
+                                    }
                                 });
-                            }
 
                             trace_gen_sync.wait_for_turn(index);
 
                             // Send the records to the phase 2 prover.
                             let chunked_records = chunk_vec(records, opts.shard_batch_size);
                             let chunked_main_traces = chunk_vec(main_traces, opts.shard_batch_size);
+                            let chunked_timings = chunk_vec(timings, opts.shard_batch_size);
                             chunked_records
                                 .into_iter()
                                 .zip(chunked_main_traces.into_iter())
-                                .for_each(|(records, main_traces)| {
+                                .zip(chunked_timings)
+                                .for_each(|((records, main_traces), timings)| {
                                     records_and_traces_tx
                                         .lock()
                                         .unwrap()
-                                        .send((records, main_traces))
+                                        .send((records, main_traces, timings))
                                         .unwrap();
                                 });
 
@@ -447,30 +624,92 @@ where
 
         // Spawn the phase 2 prover thread.
         let p2_prover_span = tracing::Span::current().clone();
+        let num_proven = Arc::new(AtomicUsize::new(resumed_proofs.len()));
+        let shard_num_proven = Arc::clone(&num_proven);
+        let shard_progress = progress.clone();
+        for proof in resumed_proofs {
+            proof_tx.send(proof).unwrap();
+        }
         let proof_tx = Arc::new(Mutex::new(proof_tx));
+        let profile = Arc::new(Mutex::new(ProvingProfile::default()));
+        let shard_profile = Arc::clone(&profile);
         let p2_prover_handle = s.spawn(move || {
             let _span = p2_prover_span.enter();
+
+            // Wait for the proving key, create the challenger and observe the verifying key.
+            let pk = pk();
+            let mut challenger = prover.config().challenger();
+            pk.observe_into(&mut challenger);
+
             tracing::debug_span!("phase 2 prover").in_scope(|| {
-                for (records, traces) in p2_records_and_traces_rx.into_iter() {
+                for (records, traces, timings) in p2_records_and_traces_rx.into_iter() {
+                    // Drain the remaining batches without proving them once cancelled.
+                    if cancelled() {
+                        continue;
+                    }
                     tracing::debug_span!("batch").in_scope(|| {
                         let span = tracing::Span::current().clone();
                         let proofs = records
                             .into_par_iter()
                             .zip(traces.into_par_iter())
-                            .map(|(record, main_traces)| {
+                            .zip(timings.into_par_iter())
+                            .map(|((record, main_traces), timings)| {
                                 let _span = span.enter();
 
                                 let shard = record.shard();
+                                if trace_height_histograms {
+                                    shard_profile
+                                        .lock()
+                                        .unwrap()
+                                        .record_trace_heights(real_trace_heights(&record));
+                                }
                                 let before = Instant::now();
 
-                                let main_data = tracing::debug_span!("commit", shard)
-                                    .in_scope(|| prover.commit(&record, main_traces));
-
-                                let proof = tracing::debug_span!("opening", shard).in_scope(|| {
-                                    prover.open(pk, main_data, &mut challenger.clone()).unwrap()
+                                // Reuse the proof of an identical shard if there is a store.
+                                let key = shard_proof_store.map(|_| {
+                                    shard_proof_key::<SC>(&challenger, &record, &main_traces)
                                 });
+                                let reused =
+                                    shard_proof_store.zip(key.as_ref()).and_then(|(store, key)| {
+                                        reuse_shard_proof::<SC>(store, key, &record, &main_traces)
+                                    });
+
+                                let (proof, commit) = match reused {
+                                    Some(proof) => {
+                                        tracing::debug!(
+                                            "reusing the stored proof of shard {shard}"
+                                        );
+                                        (proof, before.elapsed())
+                                    }
+                                    None => {
+                                        let main_data = tracing::debug_span!("commit", shard)
+                                            .in_scope(|| prover.commit(&record, main_traces));
+                                        let commit = before.elapsed();
+
+                                        let proof = tracing::debug_span!("opening", shard)
+                                            .in_scope(|| {
+                                                prover
+                                                    .open(pk, main_data, &mut challenger.clone())
+                                                    .unwrap()
+                                            });
+                                        if let Some((store, key)) = shard_proof_store.zip(key) {
+                                            store_shard_proof(store, key, &proof);
+                                        }
+                                        (proof, commit)
+                                    }
+                                };
 
                                 let elapsed = before.elapsed();
+                                shard_profile.lock().unwrap().record_shard(ShardProfile {
+                                    layer: 0,
+                                    index: shard as usize,
+                                    shape: proof.shape(),
+                                    timings: StageTimings {
+                                        commit,
+                                        open: elapsed - commit,
+                                        ..timings
+                                    },
+                                });
 
                                 // Log the shard heights/shape as well as how long it took to prove.
                                 let debug_shapes = record.shape.as_ref().map(|shape| {
@@ -484,6 +723,12 @@ where
                                     elapsed.as_nanos(),
                                     debug_shapes
                                 );
+                                shard_progress.report(ProgressEvent::ShardProved {
+                                    shard,
+                                    num_proven: shard_num_proven.fetch_add(1, Ordering::Relaxed) +
+                                        1,
+                                    elapsed,
+                                });
 
                                 #[cfg(debug_assertions)]
                                 {
@@ -499,6 +744,17 @@ where
                                     }
                                 }
 
+                                if let Some(journal) = journal {
+                                    if let Err(e) =
+                                        save_shard_proof(journal, shard, &proof, spill_cipher)
+                                    {
+                                        tracing::warn!(
+                                            "failed to write the proof of shard {shard} to the \
+                                             journal: {e}"
+                                        );
+                                    }
+                                }
+
                                 rayon::spawn(move || {
                                     drop(record);
                                 });
@@ -518,7 +774,7 @@ where
         });
 
         // Wait until the checkpoint generator handle has fully finished.
-        let runtime = checkpoint_generator_handle.join().unwrap().unwrap();
+        let runtime = checkpoint_generator_handle.join().unwrap()?;
         let gas = gas_calculator.map(|calc| calc(runtime.record_estimator.as_ref().unwrap()));
         let public_values_stream = runtime.state.public_values_stream;
 
@@ -527,6 +783,9 @@ where
 
         // Wait until the phase 2 prover has finished.
         p2_prover_handle.join().unwrap();
+        if cancelled() {
+            return Err(SP1CoreProverError::Cancelled);
+        }
 
         // Log some of the `ExecutionReport` information.
         let mut report_aggregate = report_aggregate.lock().unwrap();
@@ -568,6 +827,10 @@ where
         }
 
         let cycles = report_aggregate.total_instruction_count();
+        progress.report(ProgressEvent::CoreFinished {
+            num_shards: num_proven.load(Ordering::Relaxed),
+            elapsed: proving_start.elapsed(),
+        });
 
         // Print the summary.
         let proving_time = proving_start.elapsed().as_secs_f64();
@@ -582,17 +845,20 @@ where
         {
             let all_records = all_records_rx.iter().flatten().collect::<Vec<_>>();
             let mut challenger = prover.machine().config().challenger();
-            let pk_host = prover.pk_to_host(pk);
+            let pk_host = prover.pk_to_host(pk());
             prover.machine().debug_constraints(&pk_host, all_records, &mut challenger);
         }
 
-        Ok((public_values_stream, cycles))
+        let profile = std::mem::take(&mut *profile.lock().unwrap());
+        Ok((public_values_stream, cycles, profile))
     })
 }
 
 pub fn trace_checkpoint<SC: StarkGenericConfig>(
     program: Program,
     file: &File,
+    input_chunks: &[Arc<Vec<u8>>],
+    spill_cipher: Option<&dyn SpillCipher>,
     opts: SP1CoreOpts,
     shape_config: Option<&CoreShapeConfig<SC::Val>>,
 ) -> (Vec<ExecutionRecord>, ExecutionReport)
@@ -601,10 +867,9 @@ where
 {
     let noop = NoOpSubproofVerifier;
 
-    let mut reader = std::io::BufReader::new(file);
-    let state: ExecutionState =
-        bincode::deserialize_from(&mut reader).expect("failed to deserialize state");
+    let state = ExecutionState::load_with(file, spill_cipher).expect("failed to deserialize state");
     let mut runtime = Executor::recover(program, state, opts);
+    runtime.write_input_chunks(input_chunks.to_vec());
     runtime.maximal_shapes = shape_config.map(|config| {
         config.maximal_core_shapes(opts.shard_size.ilog2() as usize).into_iter().collect()
     });
@@ -644,4 +909,70 @@ pub enum SP1CoreProverError {
     IoError(io::Error),
     #[error("serialization error: {0}")]
     SerializationError(bincode::Error),
+    #[error(transparent)]
+    Strict(StrictModeError),
+    #[error(transparent)]
+    FriParams(FriParamsMismatch),
+    #[error("core proving was cancelled")]
+    Cancelled,
+    #[error("a core proving worker panicked")]
+    WorkerPanicked,
+}
+
+#[cfg(test)]
+mod tests {
+    use sp1_stark::{
+        baby_bear_poseidon2::BabyBearPoseidon2, CpuProver, ProgressReporter, SP1CoreOpts,
+    };
+
+    use super::*;
+    use crate::programs::tests::simple_program;
+
+    #[derive(Default)]
+    struct Events(Mutex<Vec<ProgressEvent>>);
+
+    impl ProgressReporter for Events {
+        fn report(&self, event: ProgressEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn test_core_progress() {
+        let prover = CpuProver::new(RiscvAir::machine(BabyBearPoseidon2::new()));
+        let program = simple_program();
+        let (pk, vk) = prover.setup(&program);
+        let events = Arc::new(Events::default());
+        let context = SP1Context::builder().progress(events.clone()).build();
+        let (proof, _, _) = prove_core(
+            &prover,
+            &pk,
+            &vk,
+            program,
+            &SP1Stdin::new(),
+            SP1CoreOpts::default(),
+            context,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let events = events.0.lock().unwrap();
+        let num_shards = proof.shard_proofs.len();
+        assert_eq!(events.len(), num_shards + 1);
+        // Shards may be proven in parallel, so their events may come in any order.
+        let mut num_proven = events[..num_shards]
+            .iter()
+            .map(|event| match event {
+                ProgressEvent::ShardProved { num_proven, .. } => *num_proven,
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect::<Vec<_>>();
+        num_proven.sort_unstable();
+        assert_eq!(num_proven, (1..=num_shards).collect::<Vec<_>>());
+        assert!(matches!(
+            events[num_shards],
+            ProgressEvent::CoreFinished { num_shards: n, .. } if n == num_shards
+        ));
+    }
 }
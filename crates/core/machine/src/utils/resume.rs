@@ -0,0 +1,143 @@
+//! Resuming core proving from a [`CoreJournal`].
+//!
+//! With a journal in the context, the core prover writes the entries:
+//!
+//! - `execution-{i}`, the whole execution state after the execution checkpoint `i`,
+//! - `prover-{i}-{n}`, the state of the prover after the checkpoint `i`, whose shards are `1..=n`,
+//! - `shard-{n}`, the proof of the shard `n`.
+//!
+//! The checkpoint `i` is a resume point once all of them are written, and proving resumes after
+//! the latest one: execution restarts from `execution-{i}`, and only the later shards are proven.
+//! The execution and prover states of older checkpoints are removed as newer resume points are
+//! written, while the shard proofs are kept since they are part of the proof. The last checkpoint
+//! is never a resume point, since its shards complete the proof.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::{
+    journal::CoreJournal, spill::SpillCipher, ExecutionRecord, ExecutionState,
+};
+use sp1_stark::{air::PublicValues, ShardProof, StarkGenericConfig};
+
+/// The progress of core proving after an execution checkpoint, see [`latest_resume_point`].
+pub(crate) struct ResumePoint<SC: StarkGenericConfig> {
+    /// The index of the checkpoint.
+    pub index: usize,
+    pub execution: ExecutionState,
+    pub public_values: PublicValues<u32, u32>,
+    pub deferred: ExecutionRecord,
+    /// The proofs of the shards up to the checkpoint.
+    pub proofs: Vec<ShardProof<SC>>,
+}
+
+fn execution_entry(index: usize) -> String {
+    format!("execution-{index}")
+}
+
+fn shard_entry(shard: u32) -> String {
+    format!("shard-{shard}")
+}
+
+fn parse_prover_entry(name: &str) -> Option<(usize, u32)> {
+    let (index, num_shards) = name.strip_prefix("prover-")?.split_once('-')?;
+    Some((index.parse().ok()?, num_shards.parse().ok()?))
+}
+
+fn read<T: for<'de> Deserialize<'de>>(
+    journal: &CoreJournal,
+    name: &str,
+    cipher: Option<&dyn SpillCipher>,
+) -> io::Result<T> {
+    let bytes = journal
+        .read(name, cipher)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("missing entry {name}")))?;
+    bincode::deserialize(&bytes).map_err(io::Error::other)
+}
+
+fn write<T: Serialize>(
+    journal: &CoreJournal,
+    name: &str,
+    value: &T,
+    cipher: Option<&dyn SpillCipher>,
+) -> io::Result<()> {
+    journal.write(name, &bincode::serialize(value).map_err(io::Error::other)?, cipher)
+}
+
+/// The latest resume point of `journal`, as the index of its checkpoint and its number of shards.
+pub fn latest_resume_point(journal: &CoreJournal) -> io::Result<Option<(usize, u32)>> {
+    let mut points =
+        journal.entries()?.iter().filter_map(|name| parse_prover_entry(name)).collect::<Vec<_>>();
+    points.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(points.into_iter().find(|&(index, num_shards)| {
+        journal.contains(&execution_entry(index)) &&
+            (1..=num_shards).all(|shard| journal.contains(&shard_entry(shard)))
+    }))
+}
+
+/// Load the latest resume point of `journal`, if any.
+pub(crate) fn load_resume_point<SC: StarkGenericConfig>(
+    journal: &CoreJournal,
+    cipher: Option<&dyn SpillCipher>,
+) -> io::Result<Option<ResumePoint<SC>>> {
+    let Some((index, num_shards)) = latest_resume_point(journal)? else { return Ok(None) };
+    let (public_values, deferred) = read(journal, &format!("prover-{index}-{num_shards}"), cipher)?;
+    let proofs = (1..=num_shards)
+        .map(|shard| read(journal, &shard_entry(shard), cipher))
+        .collect::<io::Result<_>>()?;
+    Ok(Some(ResumePoint {
+        index,
+        execution: read(journal, &execution_entry(index), cipher)?,
+        public_values,
+        deferred,
+        proofs,
+    }))
+}
+
+/// Write the execution state after the checkpoint `index`.
+pub(crate) fn save_execution(
+    journal: &CoreJournal,
+    index: usize,
+    execution: &ExecutionState,
+    cipher: Option<&dyn SpillCipher>,
+) -> io::Result<()> {
+    write(journal, &execution_entry(index), execution, cipher)
+}
+
+/// Write the state of the prover after the checkpoint `index`, and remove the states of the
+/// checkpoints before the latest resume point.
+pub(crate) fn save_prover_state(
+    journal: &CoreJournal,
+    index: usize,
+    public_values: &PublicValues<u32, u32>,
+    deferred: &ExecutionRecord,
+    cipher: Option<&dyn SpillCipher>,
+) -> io::Result<()> {
+    let name = format!("prover-{index}-{}", public_values.shard);
+    write(journal, &name, &(public_values, deferred), cipher)?;
+
+    let Some((latest, _)) = latest_resume_point(journal)? else { return Ok(()) };
+    for name in journal.entries()? {
+        let stale = match parse_prover_entry(&name) {
+            Some((index, _)) => index < latest,
+            None => name
+                .strip_prefix("execution-")
+                .and_then(|index| index.parse::<usize>().ok())
+                .is_some_and(|index| index < latest),
+        };
+        if stale {
+            journal.remove(&name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the proof of the shard `shard`.
+pub(crate) fn save_shard_proof<SC: StarkGenericConfig>(
+    journal: &CoreJournal,
+    shard: u32,
+    proof: &ShardProof<SC>,
+    cipher: Option<&dyn SpillCipher>,
+) -> io::Result<()> {
+    write(journal, &shard_entry(shard), proof, cipher)
+}
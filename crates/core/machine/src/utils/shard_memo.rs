@@ -0,0 +1,164 @@
+//! Keys and checks for reusing core shard proofs.
+//!
+//! With a [`ShardProofStore`] in the context, the core prover looks up every shard before
+//! committing to its traces and reuses the stored proof of a byte-identical shard. A shard proof is
+//! a deterministic function of:
+//!
+//! - the challenger after observing the proving key, which commits to the program,
+//! - the public values of the shard,
+//! - the main traces of the shard,
+//!
+//! and [`shard_proof_key`] hashes all three. Two shards of the same proof are never identical,
+//! since their public values include the shard index, so a store only pays off across runs, e.g.
+//! when a failed proof is retried or the same job is proven twice.
+//!
+//! The key does not cover the STARK configuration of the prover, whose FRI parameters do not
+//! serialize, so a store must only be shared between provers with the same configuration. A reused
+//! proof is checked against the public values and the shape of the shard, see
+//! [`reuse_shard_proof`], and proven again if they differ.
+
+use p3_field::PrimeField32;
+use p3_matrix::{dense::RowMajorMatrix, Matrix};
+use sp1_core_executor::{shard_memo::ShardProofStore, ExecutionRecord};
+use sp1_stark::{MachineRecord, ShardProof, StarkGenericConfig, Val};
+
+use crate::shape::Shapeable;
+
+/// The key of the proof of a shard in a [`ShardProofStore`].
+///
+/// `challenger` is the challenger of the prover after observing the proving key.
+pub fn shard_proof_key<SC: StarkGenericConfig>(
+    challenger: &SC::Challenger,
+    record: &ExecutionRecord,
+    main_traces: &[(String, RowMajorMatrix<Val<SC>>)],
+) -> [u8; 32]
+where
+    Val<SC>: PrimeField32,
+{
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&bincode::serialize(challenger).expect("failed to serialize the challenger"));
+    for value in record.public_values::<Val<SC>>() {
+        hasher.update(&value.as_canonical_u32().to_le_bytes());
+    }
+    for (name, trace) in main_traces {
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(&(trace.width() as u64).to_le_bytes());
+        hasher.update(&(trace.height() as u64).to_le_bytes());
+        for value in &trace.values {
+            hasher.update(&value.as_canonical_u32().to_le_bytes());
+        }
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// The proof stored under `key`, if it proves `record`.
+///
+/// A stored proof that does not deserialize, or whose public values or shape differ from those of
+/// the shard, is ignored with a warning.
+pub fn reuse_shard_proof<SC: StarkGenericConfig>(
+    store: &dyn ShardProofStore,
+    key: &[u8; 32],
+    record: &ExecutionRecord,
+    main_traces: &[(String, RowMajorMatrix<Val<SC>>)],
+) -> Option<ShardProof<SC>>
+where
+    Val<SC>: PrimeField32,
+{
+    let bytes = store.get(key)?;
+    let proof: ShardProof<SC> = match bincode::deserialize(&bytes) {
+        Ok(proof) => proof,
+        Err(e) => {
+            tracing::warn!("ignoring a stored shard proof that does not deserialize: {e}");
+            return None;
+        }
+    };
+    let mut shape = main_traces
+        .iter()
+        .map(|(name, trace)| (name.clone(), trace.height().ilog2() as usize))
+        .collect::<Vec<_>>();
+    let mut proof_shape = proof.shape().inner;
+    shape.sort();
+    proof_shape.sort();
+    if proof.public_values != record.public_values::<Val<SC>>() || proof_shape != shape {
+        tracing::warn!(
+            "ignoring a stored proof of shard {} that does not match it",
+            record.shard()
+        );
+        return None;
+    }
+    Some(proof)
+}
+
+/// Store the proof of the shard with key `key`.
+pub fn store_shard_proof<SC: StarkGenericConfig>(
+    store: &dyn ShardProofStore,
+    key: [u8; 32],
+    proof: &ShardProof<SC>,
+) {
+    match bincode::serialize(proof) {
+        Ok(bytes) => store.put(key, bytes),
+        Err(e) => tracing::warn!("failed to serialize a shard proof for the store: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp1_core_executor::{shard_memo::InMemoryShardProofStore, Program, SP1Context};
+    use sp1_stark::{
+        baby_bear_poseidon2::BabyBearPoseidon2, CpuProver, MachineProver, SP1CoreOpts,
+        StarkGenericConfig,
+    };
+
+    use crate::{
+        io::SP1Stdin, programs::tests::simple_program, riscv::RiscvAir, utils::prove_core,
+    };
+
+    #[test]
+    fn test_reuse_shard_proofs() {
+        let prover = CpuProver::new(RiscvAir::machine(BabyBearPoseidon2::new()));
+        let program = simple_program();
+        let (pk, vk) = prover.setup(&program);
+        let store = InMemoryShardProofStore::new();
+        let prove = || {
+            let context = SP1Context::builder().shard_proof_store(&store).build();
+            let (proof, _, _) = prove_core(
+                &prover,
+                &pk,
+                &vk,
+                Program::clone(&program),
+                &SP1Stdin::new(),
+                SP1CoreOpts::default(),
+                context,
+                None,
+                None,
+            )
+            .unwrap();
+            proof
+        };
+
+        let proof = prove();
+        let num_shards = proof.shard_proofs.len();
+        assert_eq!(store.len(), num_shards);
+        assert_eq!(store.hits(), 0);
+
+        // Proving the same program again reuses every shard proof.
+        let reused = prove();
+        assert_eq!(store.hits(), num_shards);
+        assert_eq!(bincode::serialize(&reused).unwrap(), bincode::serialize(&proof).unwrap());
+        let mut challenger = prover.machine().config().challenger();
+        prover.machine().verify(&vk, &reused, &mut challenger).unwrap();
+    }
+
+    #[test]
+    fn test_undecodable_proof_is_ignored() {
+        use sp1_core_executor::{shard_memo::ShardProofStore, ExecutionRecord};
+
+        let store = InMemoryShardProofStore::new();
+        store.put([1; 32], vec![0; 4]);
+        let record = ExecutionRecord::default();
+        let proof = super::reuse_shard_proof::<BabyBearPoseidon2>(&store, &[1; 32], &record, &[]);
+        assert!(proof.is_none());
+        assert_eq!(store.hits(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::BTreeSet, env, fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A class of warnings that [`StrictMode`] can turn into errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WarningClass {
+    /// A recursion program was not found in the program caches and is compiled again.
+    CacheRecompute,
+    /// The gas computed while proving may disagree with the gas computed while executing.
+    GasDivergence,
+    /// A requested shape is not supported and a default one is used instead.
+    ShapeFallback,
+}
+
+impl WarningClass {
+    pub const ALL: [WarningClass; 3] =
+        [Self::CacheRecompute, Self::GasDivergence, Self::ShapeFallback];
+
+    /// The code identifying the class in errors and in `SP1_STRICT`.
+    #[must_use]
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::CacheRecompute => "cache_recompute",
+            Self::GasDivergence => "gas_divergence",
+            Self::ShapeFallback => "shape_fallback",
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("unknown warning class {0:?}")]
+pub struct UnknownWarningClass(pub String);
+
+impl FromStr for WarningClass {
+    type Err = UnknownWarningClass;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|class| class.code() == s)
+            .ok_or_else(|| UnknownWarningClass(s.to_string()))
+    }
+}
+
+/// A warning of a class made strict by [`StrictMode`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("strict mode [{}]: {message}", .class.code())]
+pub struct StrictModeError {
+    pub class: WarningClass,
+    pub message: String,
+}
+
+/// The classes of warnings that are errors instead.
+///
+/// Warnings such as a join program missing from the program cache usually point at a
+/// configuration problem that would otherwise go unnoticed in production.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrictMode {
+    pub classes: BTreeSet<WarningClass>,
+}
+
+impl StrictMode {
+    /// Every class of warnings is an error.
+    #[must_use]
+    pub fn all() -> Self {
+        Self { classes: WarningClass::ALL.into_iter().collect() }
+    }
+
+    /// Make warnings of `class` errors.
+    #[must_use]
+    pub fn with(mut self, class: WarningClass) -> Self {
+        self.classes.insert(class);
+        self
+    }
+
+    #[must_use]
+    pub fn is_strict(&self, class: WarningClass) -> bool {
+        self.classes.contains(&class)
+    }
+
+    /// Read the strict classes from `SP1_STRICT`, which is either `true` for every class or a
+    /// comma separated list of class codes.
+    pub fn from_env() -> Result<Self, UnknownWarningClass> {
+        env::var("SP1_STRICT").map_or(Ok(Self::default()), |v| v.parse())
+    }
+
+    /// Log `message` as a warning of `class`, or return it as an error if the class is strict.
+    pub fn warn(&self, class: WarningClass, message: impl Display) -> Result<(), StrictModeError> {
+        if self.is_strict(class) {
+            return Err(StrictModeError { class, message: message.to_string() });
+        }
+        tracing::warn!("{message}");
+        Ok(())
+    }
+}
+
+impl FromStr for StrictMode {
+    type Err = UnknownWarningClass;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("true") || s == "all" {
+            return Ok(Self::all());
+        }
+        let classes = s
+            .split(',')
+            .map(str::trim)
+            .filter(|code| !code.is_empty() && !code.eq_ignore_ascii_case("false"))
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { classes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_strict_mode() {
+        assert_eq!("true".parse::<StrictMode>().unwrap(), StrictMode::all());
+        assert_eq!("".parse::<StrictMode>().unwrap(), StrictMode::default());
+        assert_eq!(
+            "cache_recompute, shape_fallback".parse::<StrictMode>().unwrap(),
+            StrictMode::default()
+                .with(WarningClass::CacheRecompute)
+                .with(WarningClass::ShapeFallback)
+        );
+        assert!("cache".parse::<StrictMode>().is_err());
+    }
+
+    #[test]
+    fn test_strict_warning() {
+        let strict = StrictMode::default().with(WarningClass::GasDivergence);
+        assert!(strict.warn(WarningClass::CacheRecompute, "recomputing").is_ok());
+        let err = strict.warn(WarningClass::GasDivergence, "gas differs").unwrap_err();
+        assert_eq!(err.class, WarningClass::GasDivergence);
+        assert_eq!(err.to_string(), "strict mode [gas_divergence]: gas differs");
+    }
+}
@@ -104,7 +104,7 @@ pub async fn evaluate_performance<C: SP1ProverComponents>(
     for program in &programs {
         println!("Evaluating program: {}", program.name);
         let (elf, stdin) = load_program(program.elf, program.input);
-        let report = run_evaluation::<C>(program.name, &elf, &stdin, opts);
+        let report = run_evaluation::<C>(program.name, &elf, &stdin, opts.clone());
         reports.push(report);
         println!("Finished Program: {}", program.name);
     }
@@ -179,7 +179,7 @@ fn run_evaluation<C: SP1ProverComponents>(
     let (_, exec_duration) = time_operation(|| prover.execute(elf, stdin, context.clone()));
 
     let (core_proof, core_duration) =
-        time_operation(|| prover.prove_core(&pk_d, program, stdin, opts, context).unwrap());
+        time_operation(|| prover.prove_core(&pk_d, program, stdin, opts.clone(), context).unwrap());
 
     let (_, compress_duration) =
         time_operation(|| prover.compress(&vk, core_proof, vec![], opts).unwrap());
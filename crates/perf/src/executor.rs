@@ -66,6 +66,7 @@ fn main() {
     let mut executor = Executor::new(program, opts.core_opts);
     executor.maximal_shapes = Some(maximal_shapes);
     executor.write_vecs(&stdin.buffer);
+    executor.write_input_chunks(stdin.input_chunks.clone());
     for (proof, vkey) in stdin.proofs.iter() {
         executor.write_proof(proof.clone(), vkey.clone());
     }
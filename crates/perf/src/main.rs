@@ -68,27 +68,28 @@ fn main() {
 
             let cycles = report.expect("execution failed").2.total_instruction_count();
             let (core_proof, prove_core_duration) = time_operation(|| {
-                prover.prove_core(&pk_d, program, &stdin, opts, context).unwrap()
+                prover.prove_core(&pk_d, program, &stdin, opts.clone(), context).unwrap()
             });
 
             let (_, verify_core_duration) =
                 time_operation(|| prover.verify(&core_proof.proof, &vk));
 
             let proofs = stdin.proofs.into_iter().map(|(proof, _)| proof).collect::<Vec<_>>();
-            let (compress_proof, compress_duration) =
-                time_operation(|| prover.compress(&vk, core_proof.clone(), proofs, opts).unwrap());
+            let (compress_proof, compress_duration) = time_operation(|| {
+                prover.compress(&vk, core_proof.clone(), proofs, opts.clone()).unwrap()
+            });
 
             let (_, verify_compressed_duration) =
                 time_operation(|| prover.verify_compressed(&compress_proof, &vk));
 
             let (shrink_proof, shrink_duration) =
-                time_operation(|| prover.shrink(compress_proof.clone(), opts).unwrap());
+                time_operation(|| prover.shrink(compress_proof.clone(), opts.clone()).unwrap());
 
             let (_, verify_shrink_duration) =
                 time_operation(|| prover.verify_shrink(&shrink_proof, &vk));
 
             let (wrapped_bn254_proof, wrap_duration) =
-                time_operation(|| prover.wrap_bn254(shrink_proof, opts).unwrap());
+                time_operation(|| prover.wrap_bn254(shrink_proof, opts.clone()).unwrap());
 
             let (_, verify_wrap_duration) =
                 time_operation(|| prover.verify_wrap_bn254(&wrapped_bn254_proof, &vk));
@@ -108,7 +109,13 @@ fn main() {
             let context = SP1Context::default();
             let (core_proof, _) = time_operation(|| {
                 prover
-                    .prove_core(&pk_verify_proof_d, pk_verify_program, &stdin, opts, context)
+                    .prove_core(
+                        &pk_verify_proof_d,
+                        pk_verify_program,
+                        &stdin,
+                        opts.clone(),
+                        context,
+                    )
                     .unwrap()
             });
             let deferred_proofs =
@@ -61,6 +61,12 @@ pub mod fd {
 
         /// The file descriptor through which to access `hook_fp_inverse`.
         pub const FD_FP_INV: u32 = 11;
+
+        /// The file descriptor through which to mark the start of a program phase.
+        pub const FD_PHASE: u32 = 12;
+
+        /// The file descriptor of the output stream, which is not part of the public values.
+        pub const FD_OUTPUT: u32 = 13;
     }
 }
 
@@ -0,0 +1,122 @@
+//! Commitments to inputs split into chunks.
+//!
+//! A program processing a huge input should not read it at once. Instead, the host splits the
+//! input into chunks and writes a [`InputChunksHeader`] committing to them, followed by the
+//! SHA-256 digests of the chunks, before the chunks themselves. The program checks the digests
+//! against the header before reading any chunk, then reads the chunks one by one, checking each
+//! against its digest before using it.
+//!
+//! The digest of the header is the SHA-256 hash of the number of chunks and of their digests, so a
+//! chunk can be checked as soon as it is read, without waiting for the rest of the input.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The commitment to the chunks of an input, written to the input stream before the chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputChunksHeader {
+    /// The number of chunks.
+    pub num_chunks: u64,
+    /// The total length of the chunks in bytes.
+    pub len: u64,
+    /// The chained digest of the chunks.
+    pub digest: [u8; 32],
+}
+
+impl InputChunksHeader {
+    /// Whether `chunk_digests` are the digests of the chunks committed to by this header.
+    pub fn matches_chunk_digests(&self, chunk_digests: &[[u8; 32]]) -> bool {
+        chunk_digests.len() as u64 == self.num_chunks &&
+            digest(chunk_digests) == self.digest &&
+            (self.num_chunks > 0 || self.len == 0)
+    }
+}
+
+/// The SHA-256 digest of a chunk.
+pub fn chunk_digest(chunk: &[u8]) -> [u8; 32] {
+    Sha256::digest(chunk).into()
+}
+
+/// The digest of the chunks with `chunk_digests`, committed to by their header.
+fn digest(chunk_digests: &[[u8; 32]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update((chunk_digests.len() as u64).to_le_bytes());
+    for chunk_digest in chunk_digests {
+        hasher.update(chunk_digest);
+    }
+    hasher.finalize().into()
+}
+
+/// Computes the [`InputChunksHeader`] of chunks, one chunk at a time.
+#[derive(Debug, Clone, Default)]
+pub struct InputChunksHasher {
+    len: u64,
+    chunk_digests: Vec<[u8; 32]>,
+}
+
+impl InputChunksHasher {
+    /// Create a hasher of no chunks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next chunk.
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.chunk_digests.push(chunk_digest(chunk));
+        self.len += chunk.len() as u64;
+    }
+
+    /// The digests of the chunks added so far, written after their header.
+    pub fn chunk_digests(&self) -> &[[u8; 32]] {
+        &self.chunk_digests
+    }
+
+    /// The header of the chunks added so far.
+    pub fn header(&self) -> InputChunksHeader {
+        InputChunksHeader {
+            num_chunks: self.chunk_digests.len() as u64,
+            len: self.len,
+            digest: digest(&self.chunk_digests),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(chunks: &[&[u8]]) -> InputChunksHeader {
+        let mut hasher = InputChunksHasher::new();
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        hasher.header()
+    }
+
+    #[test]
+    fn test_input_chunks_digest_depends_on_chunking() {
+        let whole = header(&[b"abcdef"]);
+        let split = header(&[b"abc", b"def"]);
+        assert_eq!(whole.len, split.len);
+        assert_ne!(whole.digest, split.digest);
+        assert_eq!(split, header(&[b"abc", b"def"]));
+        assert_ne!(split.digest, header(&[b"def", b"abc"]).digest);
+    }
+
+    #[test]
+    fn test_input_chunks_header_checks_digests() {
+        let mut hasher = InputChunksHasher::new();
+        hasher.update(b"abc");
+        hasher.update(b"def");
+        let header = hasher.header();
+        let digests = hasher.chunk_digests();
+        assert_eq!(digests, [chunk_digest(b"abc"), chunk_digest(b"def")]);
+        assert!(header.matches_chunk_digests(digests));
+        assert!(!header.matches_chunk_digests(&digests[..1]));
+        assert!(!header.matches_chunk_digests(&[digests[1], digests[0]]));
+        assert!(!header.matches_chunk_digests(&[digests[0], chunk_digest(b"deg")]));
+        let empty = InputChunksHasher::new().header();
+        assert!(empty.matches_chunk_digests(&[]));
+        assert!(!InputChunksHeader { len: 1, ..empty }.matches_chunk_digests(&[]));
+    }
+}
@@ -24,6 +24,11 @@ impl SP1PublicValues {
         Self { buffer: Buffer::from(data) }
     }
 
+    /// Create a `SP1PublicValues` from a vector of bytes, without copying it.
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self { buffer: Buffer { data, ptr: 0 } }
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         self.buffer.data.as_slice()
     }
@@ -37,6 +42,13 @@ impl SP1PublicValues {
         self.buffer.read()
     }
 
+    /// Iterate over the values remaining in the buffer, deserializing them one at a time.
+    pub fn iter<T: Serialize + DeserializeOwned>(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || {
+            (self.buffer.ptr < self.buffer.data.len()).then(|| self.buffer.read())
+        })
+    }
+
     /// Read a slice of bytes from the buffer.
     pub fn read_slice(&mut self, slice: &mut [u8]) {
         self.buffer.read_slice(slice);
@@ -89,6 +101,17 @@ impl SP1PublicValues {
     }
 }
 
+/// Reads the bytes remaining in the buffer.
+impl std::io::Read for SP1PublicValues {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = &self.buffer.data[self.buffer.ptr..];
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.buffer.ptr += len;
+        Ok(len)
+    }
+}
+
 impl AsRef<[u8]> for SP1PublicValues {
     fn as_ref(&self) -> &[u8] {
         &self.buffer.data
@@ -140,4 +163,21 @@ mod tests {
 
         assert_eq!(hash, expected_hash_biguint);
     }
+
+    #[test]
+    fn test_public_values_iter_and_read() {
+        let mut public_values = SP1PublicValues::new();
+        for i in 0..3u32 {
+            public_values.write(&i);
+        }
+
+        let mut public_values = SP1PublicValues::from_vec(public_values.to_vec());
+        assert_eq!(public_values.iter::<u32>().collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        let mut public_values = SP1PublicValues::from_vec(public_values.to_vec());
+        assert_eq!(public_values.read::<u32>(), 0);
+        let mut rest = Vec::new();
+        std::io::Read::read_to_end(&mut public_values, &mut rest).unwrap();
+        assert_eq!(rest, [1u32.to_le_bytes(), 2u32.to_le_bytes()].concat());
+    }
 }
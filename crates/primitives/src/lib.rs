@@ -7,6 +7,7 @@ use p3_field::AbstractField;
 use p3_poseidon2::{Poseidon2, Poseidon2ExternalMatrixGeneral};
 
 pub mod consts;
+pub mod input_chunks;
 pub mod io;
 pub mod types;
 
@@ -62,6 +62,8 @@ fn main() {
             buffer: vec![bincode::serialize::<u32>(&iterations).unwrap()],
             ptr: 0,
             proofs: vec![],
+            input_chunks: Default::default(),
+            segments: vec![],
         };
         let leaf_proving_start = Instant::now();
         let proof = prover
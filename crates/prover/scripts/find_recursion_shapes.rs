@@ -7,6 +7,7 @@ use p3_baby_bear::BabyBear;
 use sp1_core_machine::utils::setup_logger;
 use sp1_prover::{
     components::CpuProverComponents,
+    reload::VkVerificationMode,
     shapes::{check_shapes, SP1ProofShape},
     SP1Prover, ShrinkAir, REDUCE_BATCH_SIZE,
 };
@@ -41,8 +42,10 @@ fn main() {
     let mut prover = SP1Prover::<CpuProverComponents>::new();
 
     // Set whether to verify verification keys.
-    prover.vk_verification = !args.dummy;
-    prover.join_programs_map.clear();
+    let core = prover.core_mut().unwrap();
+    core.vk_verification =
+        if args.dummy { VkVerificationMode::Disabled } else { VkVerificationMode::Enforced };
+    core.join_programs_map.clear();
 
     // Get the default compress shape configuration.
     let compress_shape_config =
@@ -53,7 +56,8 @@ fn main() {
     // shape".
     let candidate = compress_shape_config.union_config_with_extra_room().first().unwrap().clone();
 
-    prover.compress_shape_config = Some(RecursionShapeConfig::from_hash_map(&candidate));
+    prover.core_mut().unwrap().compress_shape_config =
+        Some(RecursionShapeConfig::from_hash_map(&candidate));
 
     // Check that this candidate is big enough for all core shapes, including those with
     // precompiles.
@@ -73,7 +77,8 @@ fn main() {
             while !done {
                 new_val -= 1;
                 answer.insert(key.clone(), new_val);
-                prover.compress_shape_config = Some(RecursionShapeConfig::from_hash_map(&answer));
+                prover.core_mut().unwrap().compress_shape_config =
+                    Some(RecursionShapeConfig::from_hash_map(&answer));
                 done = !check_shapes(
                     args.recursion_batch_size,
                     false,
@@ -95,7 +100,7 @@ fn main() {
             while !done {
                 new_val -= 1;
                 no_precompile_answer.insert(key.clone(), new_val);
-                prover.compress_shape_config =
+                prover.core_mut().unwrap().compress_shape_config =
                     Some(RecursionShapeConfig::from_hash_map(&no_precompile_answer));
                 done = !check_shapes(
                     args.recursion_batch_size,
@@ -116,7 +121,8 @@ fn main() {
 
     // TODO: set the join program map to empty.
     assert!({
-        prover.compress_shape_config = Some(RecursionShapeConfig::from_hash_map(&answer));
+        prover.core_mut().unwrap().compress_shape_config =
+            Some(RecursionShapeConfig::from_hash_map(&answer));
         catch_unwind(AssertUnwindSafe(|| {
             prover.shrink_prover.setup(&prover.program_from_shape(
                 sp1_prover::shapes::SP1CompressProgramShape::from_proof_shape(
@@ -124,6 +130,7 @@ fn main() {
                         inner: answer.clone().into_iter().collect::<Vec<_>>(),
                     }),
                     5,
+                    prover.recursion_vk_tree.config,
                 ),
                 Some(shrink_shape.clone().into()),
             ))
@@ -139,7 +146,8 @@ fn main() {
             while !done {
                 new_val -= 1;
                 shrink_shape.insert(key.clone(), new_val);
-                prover.compress_shape_config = Some(RecursionShapeConfig::from_hash_map(&answer));
+                prover.core_mut().unwrap().compress_shape_config =
+                    Some(RecursionShapeConfig::from_hash_map(&answer));
                 done = catch_unwind(AssertUnwindSafe(|| {
                     prover.shrink_prover.setup(&prover.program_from_shape(
                         sp1_prover::shapes::SP1CompressProgramShape::from_proof_shape(
@@ -147,6 +155,7 @@ fn main() {
                                 inner: answer.clone().into_iter().collect::<Vec<_>>(),
                             }),
                             5,
+                            prover.recursion_vk_tree.config,
                         ),
                         Some(shrink_shape.clone().into()),
                     ))
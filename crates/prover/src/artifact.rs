@@ -0,0 +1,492 @@
+//! Ahead-of-time compiled recursion program artifacts.
+//!
+//! Compiling every lift and join program takes a long time, so a fleet of provers can compile
+//! them once on a builder machine and distribute the results. An artifact holds a single compiled
+//! program together with the shape it was compiled for and a digest of its serialized bytes, which
+//! is checked when the artifact is loaded.
+//!
+//! The digest is stored in the artifact itself, so it only detects accidental corruption, such as
+//! a truncated download. Anyone able to modify an artifact can update its digest, so artifacts
+//! must come from a trusted source; sealing them with an
+//! [`ArtifactCipher`](crate::encryption::ArtifactCipher) also authenticates them.
+//!
+//! In locked-down environments, a prover handle can be made read-only with
+//! [`SP1Prover::with_read_only`]. A read-only prover never compiles recursion programs: it proves
+//! only with the programs installed from artifacts and the precompiled join programs, and fails
+//! with [`SP1RecursionProverError::MissingArtifacts`], naming the missing artifact files, before
+//! proving anything that would need another program.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+    sync::Arc,
+};
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sp1_recursion_circuit::{
+    machine::{
+        SP1CompressShape, SP1CompressWithVkeyShape, SP1DeferredShape, SP1DeferredWitnessValues,
+        SP1RecursionShape,
+    },
+    merkle_tree::MerkleTreeConfig,
+};
+use sp1_recursion_core::RecursionProgram;
+use sp1_stark::ShardProof;
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents,
+    encryption::{load_artifact, save_artifact, ArtifactCipher},
+    shapes::{SP1CompressProgramShape, SP1ProofShape},
+    InnerSC, SP1CircuitWitness, SP1Prover, SP1RecursionProverError, REDUCE_BATCH_SIZE,
+    SP1_CIRCUIT_VERSION,
+};
+
+/// The version of the [`RecursionProgramArtifact`] format.
+///
+/// This should be bumped whenever the layout of the artifact changes.
+pub const PROGRAM_ARTIFACT_VERSION: u32 = 2;
+
+/// The file extension of program artifacts written by [`SP1Prover::write_program_artifacts`].
+pub const PROGRAM_ARTIFACT_EXTENSION: &str = "program";
+
+#[derive(Debug, Error)]
+pub enum ProgramArtifactError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("unsupported artifact version {0}, expected {PROGRAM_ARTIFACT_VERSION}")]
+    Version(u32),
+    #[error("artifact was compiled for circuit version {0}, expected {SP1_CIRCUIT_VERSION}")]
+    CircuitVersion(String),
+    #[error("program digest mismatch")]
+    DigestMismatch,
+    #[error("artifact was compiled with a different prover configuration: {0}")]
+    ConfigMismatch(&'static str),
+    #[error("programs of shape {0:?} are not cached by the prover")]
+    NotCacheable(SP1ProofShape),
+    #[error("the prover core is shared with other handles")]
+    SharedCore,
+}
+
+/// A compiled recursion program, tagged with the shape it was compiled for.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecursionProgramArtifact {
+    /// The version of the artifact format, see [`PROGRAM_ARTIFACT_VERSION`].
+    pub version: u32,
+    /// The circuit version of the prover that compiled the program.
+    pub circuit_version: String,
+    /// The shape of the proofs verified by the program.
+    pub shape: SP1ProofShape,
+    /// The height of the Merkle tree of allowed recursion verifying keys.
+    pub merkle_tree_height: usize,
+    /// The arity and hash of the Merkle tree of allowed recursion verifying keys.
+    pub merkle_tree_config: MerkleTreeConfig,
+    /// Whether the program verifies verifying keys against the allowed set.
+    pub vk_verification: bool,
+    /// The SHA-256 digest of `program_bytes`, which only detects accidental corruption.
+    pub program_digest: [u8; 32],
+    /// The program, serialized with bincode.
+    program_bytes: Vec<u8>,
+}
+
+impl RecursionProgramArtifact {
+    /// Create an artifact holding `program`, which was compiled for `shape`.
+    pub fn new(
+        shape: SP1ProofShape,
+        merkle_tree_height: usize,
+        merkle_tree_config: MerkleTreeConfig,
+        vk_verification: bool,
+        program: &RecursionProgram<BabyBear>,
+    ) -> Result<Self, ProgramArtifactError> {
+        let program_bytes = bincode::serialize(program)?;
+        Ok(Self {
+            version: PROGRAM_ARTIFACT_VERSION,
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            shape,
+            merkle_tree_height,
+            merkle_tree_config,
+            vk_verification,
+            program_digest: Sha256::digest(&program_bytes).into(),
+            program_bytes,
+        })
+    }
+
+    /// Check the version and integrity of the artifact and deserialize the program.
+    pub fn program(&self) -> Result<RecursionProgram<BabyBear>, ProgramArtifactError> {
+        if self.version != PROGRAM_ARTIFACT_VERSION {
+            return Err(ProgramArtifactError::Version(self.version));
+        }
+        if self.circuit_version != SP1_CIRCUIT_VERSION {
+            return Err(ProgramArtifactError::CircuitVersion(self.circuit_version.clone()));
+        }
+        if <[u8; 32]>::from(Sha256::digest(&self.program_bytes)) != self.program_digest {
+            return Err(ProgramArtifactError::DigestMismatch);
+        }
+        Ok(bincode::deserialize(&self.program_bytes)?)
+    }
+
+    /// The file name the artifact is written to by [`SP1Prover::write_program_artifacts`].
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        program_artifact_file_name(&SP1CompressProgramShape::from_proof_shape(
+            self.shape.clone(),
+            self.merkle_tree_height,
+            self.merkle_tree_config,
+        ))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProgramArtifactError> {
+        Ok(bincode::serialize_into(BufWriter::new(File::create(path)?), self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProgramArtifactError> {
+        Self::load_with(path, None)
+    }
+
+    /// Save the artifact, encrypted with `cipher` if one is given.
+    pub fn save_with(
+        &self,
+        path: impl AsRef<Path>,
+        cipher: Option<&ArtifactCipher>,
+    ) -> Result<(), ProgramArtifactError> {
+        match cipher {
+            Some(cipher) => Ok(save_artifact(self, path, Some(cipher))?),
+            None => self.save(path),
+        }
+    }
+
+    /// Load an artifact written by [`RecursionProgramArtifact::save_with`] with the same cipher.
+    pub fn load_with(
+        path: impl AsRef<Path>,
+        cipher: Option<&ArtifactCipher>,
+    ) -> Result<Self, ProgramArtifactError> {
+        Ok(load_artifact(path, cipher)?)
+    }
+}
+
+/// The file name of the artifact of the program for `shape`.
+#[must_use]
+pub fn program_artifact_file_name(shape: &SP1CompressProgramShape) -> String {
+    let kind = match shape {
+        SP1CompressProgramShape::Recursion(_) => "lift",
+        SP1CompressProgramShape::Compress(_) => "join",
+        SP1CompressProgramShape::Deferred(_) => "deferred",
+        SP1CompressProgramShape::Shrink(_) => "shrink",
+    };
+    format!("{kind}-{:016x}.{PROGRAM_ARTIFACT_EXTENSION}", shape.hash_u64())
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Compile the program for `shape` and package it as an artifact.
+    pub fn compile_program_artifact(
+        &self,
+        shape: SP1ProofShape,
+    ) -> Result<RecursionProgramArtifact, ProgramArtifactError> {
+        let height = self.recursion_vk_tree.height;
+        let config = self.recursion_vk_tree.config;
+        let program = self.program_from_shape(
+            SP1CompressProgramShape::from_proof_shape(shape.clone(), height, config),
+            None,
+        );
+        RecursionProgramArtifact::new(
+            shape,
+            height,
+            config,
+            self.vk_verification.checks_vks(),
+            &program,
+        )
+    }
+
+    /// Compile the programs for `shapes` and write them to `dir`, sealed with the artifact cipher
+    /// of this handle if any, returning the number of artifacts written.
+    pub fn write_program_artifacts(
+        &self,
+        shapes: impl IntoIterator<Item = SP1ProofShape>,
+        dir: impl AsRef<Path>,
+    ) -> Result<usize, ProgramArtifactError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut count = 0;
+        for shape in shapes {
+            let artifact = self.compile_program_artifact(shape)?;
+            artifact.save_with(dir.join(artifact.file_name()), self.artifact_cipher.as_deref())?;
+            count += 1;
+        }
+        tracing::info!("wrote {} program artifacts to {}", count, dir.display());
+        Ok(count)
+    }
+
+    /// Insert the program of an artifact into the programs of the prover core.
+    ///
+    /// Installed programs are shared with every handle created afterwards and are never evicted,
+    /// so this fails if the core is already shared. A lift program does not depend on whether the
+    /// proof it verifies is complete, so it is installed for both.
+    pub fn install_program_artifact(
+        &mut self,
+        artifact: &RecursionProgramArtifact,
+    ) -> Result<(), ProgramArtifactError> {
+        if artifact.merkle_tree_height != self.recursion_vk_tree.height {
+            return Err(ProgramArtifactError::ConfigMismatch("merkle tree height"));
+        }
+        if artifact.merkle_tree_config != self.recursion_vk_tree.config {
+            return Err(ProgramArtifactError::ConfigMismatch("merkle tree config"));
+        }
+        if artifact.vk_verification != self.vk_verification.checks_vks() {
+            return Err(ProgramArtifactError::ConfigMismatch("vk verification"));
+        }
+
+        let program = Arc::new(artifact.program()?);
+        let core = self.core_mut().ok_or(ProgramArtifactError::SharedCore)?;
+        match SP1CompressProgramShape::from_proof_shape(
+            artifact.shape.clone(),
+            artifact.merkle_tree_height,
+            artifact.merkle_tree_config,
+        ) {
+            SP1CompressProgramShape::Recursion(shape) => {
+                for is_complete in [false, true] {
+                    let shape = SP1CompressProgramShape::Recursion(SP1RecursionShape {
+                        is_complete,
+                        ..shape.clone()
+                    });
+                    core.prebuilt_programs.insert(shape.hash_u64(), program.clone());
+                }
+            }
+            SP1CompressProgramShape::Compress(shape) => {
+                core.join_programs_map.insert(shape, program);
+            }
+            shape @ (SP1CompressProgramShape::Deferred(_) | SP1CompressProgramShape::Shrink(_)) => {
+                core.prebuilt_programs.insert(shape.hash_u64(), program);
+            }
+        }
+        Ok(())
+    }
+
+    /// The program for `shape` installed from an artifact or precompiled, if any.
+    pub(crate) fn installed_program(
+        &self,
+        shape: &SP1CompressProgramShape,
+    ) -> Option<Arc<RecursionProgram<BabyBear>>> {
+        match shape {
+            SP1CompressProgramShape::Compress(shape) => self.join_programs_map.get(shape).cloned(),
+            SP1CompressProgramShape::Recursion(_) |
+            SP1CompressProgramShape::Deferred(_) |
+            SP1CompressProgramShape::Shrink(_) => {
+                self.prebuilt_programs.get(&shape.hash_u64()).cloned()
+            }
+        }
+    }
+
+    /// The shape of the program proving `input` in the recursion tree.
+    pub(crate) fn witness_program_shape(
+        &self,
+        input: &SP1CircuitWitness,
+    ) -> SP1CompressProgramShape {
+        match input {
+            SP1CircuitWitness::Core(input) => SP1CompressProgramShape::Recursion(input.shape()),
+            SP1CircuitWitness::Deferred(input) => {
+                SP1CompressProgramShape::Deferred(self.deferred_program_shape(input))
+            }
+            SP1CircuitWitness::Compress(input) => {
+                SP1CompressProgramShape::Compress(SP1CompressWithVkeyShape {
+                    compress_shape: input.shape(),
+                    merkle_tree_height: self.recursion_vk_tree.height,
+                    merkle_tree_config: self.recursion_vk_tree.config,
+                })
+            }
+        }
+    }
+
+    pub(crate) fn deferred_program_shape(
+        &self,
+        input: &SP1DeferredWitnessValues<InnerSC>,
+    ) -> SP1DeferredShape {
+        self.deferred_batch_shape(input.vks_and_proofs.iter().map(|(_, proof)| proof))
+    }
+
+    /// The shape of the deferred program verifying `proofs`, without generating its input.
+    pub(crate) fn deferred_batch_shape<'a>(
+        &self,
+        proofs: impl IntoIterator<Item = &'a ShardProof<InnerSC>>,
+    ) -> SP1DeferredShape {
+        let proof_shapes: Vec<_> = proofs.into_iter().map(ShardProof::shape).collect();
+        SP1DeferredShape::new(
+            SP1CompressShape::from(proof_shapes),
+            self.recursion_vk_tree.height,
+            self.recursion_vk_tree.config,
+        )
+    }
+
+    /// Check that the programs for `shapes` were installed, if the prover is read-only.
+    ///
+    /// Returns [`SP1RecursionProverError::MissingArtifacts`] with the file names of the artifacts
+    /// of every missing program.
+    pub fn check_installed_programs(
+        &self,
+        shapes: impl IntoIterator<Item = SP1CompressProgramShape>,
+    ) -> Result<(), SP1RecursionProverError> {
+        if !self.read_only {
+            return Ok(());
+        }
+        let missing = shapes
+            .into_iter()
+            .filter(|shape| self.installed_program(shape).is_none())
+            .map(|shape| program_artifact_file_name(&shape))
+            .collect::<BTreeSet<_>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(SP1RecursionProverError::MissingArtifacts(missing.into_iter().collect()))
+        }
+    }
+
+    /// Check that the join programs of every allowed compress shape, up to the max compress arity,
+    /// were installed, if the prover is read-only and compress shapes are fixed.
+    pub(crate) fn check_installed_join_programs(&self) -> Result<(), SP1RecursionProverError> {
+        let Some(config) = self.compress_shape_config.as_ref().filter(|_| self.read_only) else {
+            return Ok(());
+        };
+        let tree = &self.recursion_vk_tree;
+        self.check_installed_programs(
+            (REDUCE_BATCH_SIZE..=self.max_compress_arity)
+                .flat_map(|arity| SP1ProofShape::generate_compress_shapes(config, arity))
+                .map(|shape| {
+                    SP1CompressProgramShape::from_proof_shape(
+                        SP1ProofShape::Compress(shape),
+                        tree.height,
+                        tree.config,
+                    )
+                }),
+        )
+    }
+
+    /// Panic with the name of the missing artifact instead of compiling the program for `shape`,
+    /// if the prover is read-only.
+    ///
+    /// Proving methods check for missing programs before they start, so this only guards against
+    /// programs that could not be anticipated.
+    pub(crate) fn assert_compilable(&self, shape: &SP1CompressProgramShape) {
+        assert!(
+            !self.read_only,
+            "the read-only prover is missing the program artifact {}",
+            program_artifact_file_name(shape)
+        );
+    }
+
+    /// Load every program artifact in `dir` into the program caches, returning the number of
+    /// programs loaded.
+    ///
+    /// The artifacts must be written with the artifact cipher of this handle, if any, as
+    /// [`Self::write_program_artifacts`] does.
+    ///
+    /// This is typically used together with `SP1_DISABLE_PROGRAM_CACHE=true`, so that the join
+    /// programs are not compiled on startup.
+    pub fn load_program_artifacts(
+        &mut self,
+        dir: impl AsRef<Path>,
+    ) -> Result<usize, ProgramArtifactError> {
+        let mut count = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == PROGRAM_ARTIFACT_EXTENSION) {
+                let artifact =
+                    RecursionProgramArtifact::load_with(&path, self.artifact_cipher.as_deref())?;
+                match self.install_program_artifact(&artifact) {
+                    Ok(()) => count += 1,
+                    Err(ProgramArtifactError::NotCacheable(_)) => {
+                        tracing::debug!("skipping uncached program artifact {}", path.display());
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        tracing::info!("loaded {} program artifacts", count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp1_stark::shape::OrderedShape;
+
+    use super::*;
+    use crate::tests::unfixed_prover;
+
+    #[test]
+    fn test_read_only_handle_keeps_installed_programs() {
+        let mut prover = unfixed_prover().with_read_only(true);
+        let proof_shape = OrderedShape { inner: vec![("Program".to_string(), 4)] };
+        let shape = SP1CompressProgramShape::from_proof_shape(
+            SP1ProofShape::Recursion(proof_shape.clone()),
+            prover.recursion_vk_tree.height,
+            prover.recursion_vk_tree.config,
+        );
+        assert!(matches!(
+            prover.check_installed_programs([shape.clone()]),
+            Err(SP1RecursionProverError::MissingArtifacts(_))
+        ));
+        assert!(matches!(prover.wrap_program(), Err(SP1RecursionProverError::MissingArtifacts(_))));
+
+        let artifact = RecursionProgramArtifact::new(
+            SP1ProofShape::Recursion(proof_shape),
+            prover.recursion_vk_tree.height,
+            prover.recursion_vk_tree.config,
+            prover.vk_verification.checks_vks(),
+            &RecursionProgram::default(),
+        )
+        .unwrap();
+        prover.install_program_artifact(&artifact).unwrap();
+
+        // Handles share the installed programs, which survive cache resets.
+        let handle = prover.handle();
+        assert!(handle.read_only);
+        handle.check_installed_programs([shape.clone()]).unwrap();
+        handle.reset_caches();
+        handle.check_installed_programs([shape]).unwrap();
+    }
+
+    #[test]
+    fn test_artifact_sealed() {
+        let path = std::env::temp_dir().join(format!("sp1-program-{}.bin", std::process::id()));
+        let cipher = ArtifactCipher::new([7; 32]);
+        let artifact = RecursionProgramArtifact::new(
+            SP1ProofShape::Recursion(OrderedShape { inner: vec![("Program".to_string(), 4)] }),
+            1,
+            MerkleTreeConfig::default(),
+            true,
+            &RecursionProgram::default(),
+        )
+        .unwrap();
+
+        artifact.save_with(&path, Some(&cipher)).unwrap();
+        let loaded = RecursionProgramArtifact::load_with(&path, Some(&cipher)).unwrap();
+        assert_eq!(loaded.program_digest, artifact.program_digest);
+        loaded.program().unwrap();
+        assert!(RecursionProgramArtifact::load(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_artifact_detects_corruption() {
+        let artifact = RecursionProgramArtifact::new(
+            SP1ProofShape::Recursion(OrderedShape { inner: vec![("Program".to_string(), 4)] }),
+            1,
+            MerkleTreeConfig::default(),
+            true,
+            &RecursionProgram::default(),
+        )
+        .unwrap();
+        artifact.program().unwrap();
+
+        for i in 0..artifact.program_bytes.len() {
+            let mut corrupted = artifact.clone();
+            corrupted.program_bytes[i] ^= 1;
+            assert!(matches!(corrupted.program(), Err(ProgramArtifactError::DigestMismatch)));
+        }
+    }
+}
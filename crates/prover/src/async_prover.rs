@@ -0,0 +1,199 @@
+//! A future-returning façade over the prover.
+//!
+//! The stages of the prover are blocking and already use every core through their own scoped
+//! threads, so running them on the blocking pool of an async runtime ties up one of its threads per
+//! request for the whole proof. An [`SP1AsyncProver`] instead queues the requests to a few
+//! dedicated threads, [`ASYNC_PROVER_THREADS`] by default, and returns a [`ProverFuture`] that
+//! resolves once its request is proven. The futures do not depend on a runtime, so they can be
+//! awaited from tokio or any other executor.
+//!
+//! Requests are proven in the order they are submitted, at most one per thread at a time. Dropping
+//! a future does not cancel its request, which is cancelled with the token of its context or of
+//! the prover, see [`SP1Prover::with_cancellation`].
+
+use std::{
+    env,
+    future::Future,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll},
+    thread,
+};
+
+use futures::channel::oneshot;
+use p3_baby_bear::BabyBear;
+use sp1_core_executor::{Program, SP1Context};
+use sp1_core_machine::{io::SP1Stdin, riscv::RiscvAir};
+use sp1_stark::{baby_bear_poseidon2::BabyBearPoseidon2, MachineProver, SP1ProverOpts};
+
+use crate::{
+    components::SP1ProverComponents, InnerSC, OuterSC, SP1CoreProof, SP1CoreProverError, SP1Prover,
+    SP1RecursionProverError, SP1ReduceProof, SP1VerifyingKey,
+};
+
+/// The default number of threads proving the requests of an [`SP1AsyncProver`].
+pub const ASYNC_PROVER_THREADS: usize = 1;
+
+type Request<C> = Box<dyn FnOnce(&SP1Prover<C>) + Send>;
+
+/// The device proving key of the core prover of `C`.
+pub type CoreDeviceProvingKey<C> = <<C as SP1ProverComponents>::CoreProver as MachineProver<
+    BabyBearPoseidon2,
+    RiscvAir<BabyBear>,
+>>::DeviceProvingKey;
+
+/// A prover whose stages return futures, see the [module documentation](self).
+pub struct SP1AsyncProver<C: SP1ProverComponents> {
+    prover: Arc<SP1Prover<C>>,
+    requests: mpsc::Sender<Request<C>>,
+}
+
+impl<C: SP1ProverComponents + 'static> SP1AsyncProver<C> {
+    /// Prove the requests with `prover` on `SP1_ASYNC_PROVER_THREADS` threads, or
+    /// [`ASYNC_PROVER_THREADS`] if it is not set.
+    pub fn new(prover: SP1Prover<C>) -> Self {
+        let threads = env::var("SP1_ASYNC_PROVER_THREADS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .and_then(NonZeroUsize::new)
+            .unwrap_or(NonZeroUsize::new(ASYNC_PROVER_THREADS).unwrap());
+        Self::with_threads(prover, threads)
+    }
+
+    /// Prove the requests with `prover` on `threads` threads.
+    ///
+    /// Each stage already parallelizes over all cores, so more than one thread only pays off when
+    /// requests are small or wait on a device.
+    pub fn with_threads(prover: SP1Prover<C>, threads: NonZeroUsize) -> Self {
+        let prover = Arc::new(prover);
+        let (requests, receiver) = mpsc::channel::<Request<C>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads.get() {
+            let prover = prover.clone();
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("async-prover-{i}"))
+                .spawn(move || loop {
+                    // The lock is released before proving, so that the other threads can take
+                    // the next requests.
+                    let request = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                    match request {
+                        Ok(request) => request(&prover),
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn an async prover thread");
+        }
+        Self { prover, requests }
+    }
+
+    /// The prover proving the requests.
+    #[must_use]
+    pub fn prover(&self) -> &Arc<SP1Prover<C>> {
+        &self.prover
+    }
+
+    /// Run `f` with the prover on one of the prover threads.
+    ///
+    /// A panic of `f` is resumed when the future is polled.
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&SP1Prover<C>) -> T + Send + 'static,
+    ) -> ProverFuture<T> {
+        let (sender, receiver) = oneshot::channel();
+        let request: Request<C> = Box::new(move |prover| {
+            let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(|| f(prover))));
+        });
+        self.requests.send(request).expect("the async prover threads exited");
+        ProverFuture { receiver }
+    }
+
+    /// See [`SP1Prover::prove_core`].
+    pub fn prove_core(
+        &self,
+        pk_d: Arc<CoreDeviceProvingKey<C>>,
+        program: Program,
+        stdin: SP1Stdin,
+        opts: SP1ProverOpts,
+        context: SP1Context<'static>,
+    ) -> ProverFuture<Result<SP1CoreProof, SP1CoreProverError>> {
+        self.spawn(move |prover| prover.prove_core(&pk_d, program, &stdin, opts, context.scoped()))
+    }
+
+    /// See [`SP1Prover::compress`].
+    pub fn compress(
+        &self,
+        vk: SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> ProverFuture<Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError>> {
+        self.spawn(move |prover| prover.compress(&vk, proof, deferred_proofs, opts))
+    }
+
+    /// See [`SP1Prover::shrink`].
+    pub fn shrink(
+        &self,
+        reduced_proof: SP1ReduceProof<InnerSC>,
+        opts: SP1ProverOpts,
+    ) -> ProverFuture<Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError>> {
+        self.spawn(move |prover| prover.shrink(reduced_proof, opts))
+    }
+
+    /// See [`SP1Prover::wrap_bn254`].
+    pub fn wrap_bn254(
+        &self,
+        compressed_proof: SP1ReduceProof<InnerSC>,
+        opts: SP1ProverOpts,
+    ) -> ProverFuture<Result<SP1ReduceProof<OuterSC>, SP1RecursionProverError>> {
+        self.spawn(move |prover| prover.wrap_bn254(compressed_proof, opts))
+    }
+}
+
+/// The result of a request to an [`SP1AsyncProver`].
+#[must_use]
+pub struct ProverFuture<T> {
+    receiver: oneshot::Receiver<thread::Result<T>>,
+}
+
+impl<T> Future for ProverFuture<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(Ok(output))) => Poll::Ready(output),
+            Poll::Ready(Ok(Err(panic))) => panic::resume_unwind(panic),
+            Poll::Ready(Err(oneshot::Canceled)) => panic!("the async prover dropped the request"),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::tests::unfixed_prover;
+
+    #[test]
+    fn test_async_prover() {
+        let prover = SP1AsyncProver::with_threads(unfixed_prover(), NonZeroUsize::new(2).unwrap());
+        let futures = (0..4)
+            .map(|i| prover.spawn(move |_| (i, thread::current().name().map(String::from))))
+            .collect::<Vec<_>>();
+        for (i, future) in futures.into_iter().enumerate() {
+            let (j, name) = block_on(future);
+            assert_eq!(i, j);
+            assert!(name.unwrap().starts_with("async-prover-"));
+        }
+
+        // A panic is resumed by the future, and the thread keeps proving requests.
+        let panicked =
+            panic::catch_unwind(AssertUnwindSafe(|| block_on(prover.spawn(|_| panic!("boom")))));
+        assert_eq!(*panicked.unwrap_err().downcast::<&str>().unwrap(), "boom");
+        assert_eq!(block_on(prover.spawn(|_| 42)), 42);
+    }
+}
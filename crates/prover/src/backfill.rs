@@ -0,0 +1,314 @@
+//! Re-wrapping archived compressed proofs after the wrap circuit artifacts change.
+//!
+//! Wrapped proofs are only valid against the verifier of the circuit artifacts they were made
+//! with, so services that keep compressed proofs must wrap them again when the artifacts are
+//! updated. [`SP1Prover::rewrap_archived`] streams the compressed proofs of a [`ProofStore`],
+//! shrinks and wraps each of them with the current artifacts, verifies the result, and writes the
+//! new wrapped proof back along with a [`RewrapRecord`] mapping it to the compressed proof.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sp1_recursion_gnark_ffi::{groth16_bn254::Groth16Bn254Prover, plonk_bn254::PlonkBn254Prover};
+use sp1_stark::SP1ProverOpts;
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents, encryption::ArtifactCipher, types::ProofSystem,
+    wrap_cache::wrap_public_inputs, GnarkLimits, InnerSC, SP1Prover, SP1RecursionProverError,
+    SP1ReduceProof, SP1_CIRCUIT_VERSION,
+};
+
+/// An archive of compressed proofs, and of the proofs wrapping them.
+///
+/// Implement this to re-wrap the proofs of an object store or a database.
+pub trait ProofStore: Send + Sync {
+    /// The ids of the archived compressed proofs.
+    fn ids(&self) -> io::Result<Vec<String>>;
+
+    /// The bincode encoded [`SP1ReduceProof`] archived under `id`, if any.
+    fn get_compressed(&self, id: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Store the bincode encoded proof wrapping the compressed proof `id` in `system`, replacing
+    /// any previous one.
+    fn put_wrapped(&self, id: &str, system: ProofSystem, proof: &[u8]) -> io::Result<()>;
+
+    /// Store the record of a re-wrapped proof.
+    fn put_record(&self, record: &RewrapRecord) -> io::Result<()>;
+}
+
+/// A [`ProofStore`] in a directory, with the compressed proofs in `compressed/<id>.bin`, the
+/// wrapped proofs in `<system>/<id>.bin` and the records in `records/<id>.<system>.json`.
+#[derive(Debug, Clone)]
+pub struct DiskProofStore {
+    dir: PathBuf,
+    cipher: Option<ArtifactCipher>,
+}
+
+impl DiskProofStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), cipher: None }
+    }
+
+    /// Seal every file written to the store with `cipher`, and open the compressed proofs read
+    /// from it, which must be sealed with the same key.
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: ArtifactCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Write `value` to `path` through a temporary file, so that readers never see a partial
+    /// file.
+    fn write(&self, path: &Path, value: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let sealed;
+        let value = match &self.cipher {
+            Some(cipher) => {
+                sealed = cipher.seal(value)?;
+                &sealed
+            }
+            None => value,
+        };
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        fs::write(&tmp, value)?;
+        fs::rename(tmp, path)
+    }
+}
+
+impl ProofStore for DiskProofStore {
+    fn ids(&self) -> io::Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(self.dir.join("compressed"))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "bin") {
+                if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn get_compressed(&self, id: &str) -> io::Result<Option<Vec<u8>>> {
+        let bytes = match fs::read(self.dir.join("compressed").join(format!("{id}.bin"))) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match &self.cipher {
+            Some(cipher) => cipher.open(&bytes).map(Some),
+            None => Ok(Some(bytes)),
+        }
+    }
+
+    fn put_wrapped(&self, id: &str, system: ProofSystem, proof: &[u8]) -> io::Result<()> {
+        self.write(&self.dir.join(system.as_str()).join(format!("{id}.bin")), proof)
+    }
+
+    fn put_record(&self, record: &RewrapRecord) -> io::Result<()> {
+        let path =
+            self.dir.join("records").join(format!("{}.{}.json", record.id, record.system.as_str()));
+        self.write(&path, &serde_json::to_vec_pretty(record)?)
+    }
+}
+
+/// The mapping from an archived compressed proof to the proof re-wrapping it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewrapRecord {
+    /// The id of the compressed proof in the store.
+    pub id: String,
+    pub system: ProofSystem,
+    /// The circuit version of the artifacts the proof was wrapped with.
+    pub circuit_version: String,
+    /// The hex encoded SHA-256 digest of the compressed proof, as stored.
+    pub compressed_digest: String,
+    /// The hex encoded SHA-256 digest of the wrapped proof, as stored.
+    pub wrapped_digest: String,
+}
+
+/// An error re-wrapping one of the proofs of a [`ProofStore`].
+#[derive(Debug, Error)]
+pub enum RewrapError {
+    #[error("IO error: {0}")]
+    IO(#[from] io::Error),
+    #[error("Serialization error: {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("the compressed proof is missing from the store")]
+    Missing,
+    #[error(transparent)]
+    Recursion(#[from] SP1RecursionProverError),
+    #[error("the wrapped proof does not verify: {0}")]
+    InvalidWrappedProof(anyhow::Error),
+}
+
+/// The outcome of [`SP1Prover::rewrap_archived`].
+#[derive(Debug, Default)]
+pub struct RewrapReport {
+    /// The records of the proofs re-wrapped, in the order they were written.
+    pub records: Vec<RewrapRecord>,
+    /// The ids of the proofs that could not be re-wrapped, with the reason.
+    pub failures: Vec<(String, RewrapError)>,
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Wrap every compressed proof of `store` in `system` with the current circuit artifacts in
+    /// `build_dir`, re-wrapping up to `parallelism` proofs at a time.
+    ///
+    /// Each wrapped proof is verified against the compressed proof before it is written back, and
+    /// a failure only skips its proof. Listing the proofs of the store is the only fatal error.
+    pub fn rewrap_archived(
+        &self,
+        store: &dyn ProofStore,
+        system: ProofSystem,
+        build_dir: &Path,
+        opts: SP1ProverOpts,
+        parallelism: usize,
+    ) -> io::Result<RewrapReport> {
+        let ids = store.ids()?;
+        tracing::info!("re-wrapping {} compressed proofs in {}", ids.len(), system.as_str());
+
+        let ids = Mutex::new(ids.into_iter());
+        let report = Mutex::new(RewrapReport::default());
+        thread::scope(|s| {
+            for _ in 0..parallelism.max(1) {
+                s.spawn(|| loop {
+                    let Some(id) = ids.lock().unwrap().next() else { break };
+                    match self.rewrap_one(store, &id, system, build_dir, opts.clone()) {
+                        Ok(record) => {
+                            tracing::info!("re-wrapped compressed proof {}", id);
+                            report.lock().unwrap().records.push(record);
+                        }
+                        Err(e) => {
+                            tracing::warn!("failed to re-wrap compressed proof {}: {}", id, e);
+                            report.lock().unwrap().failures.push((id, e));
+                        }
+                    }
+                });
+            }
+        });
+        Ok(report.into_inner().unwrap())
+    }
+
+    fn rewrap_one(
+        &self,
+        store: &dyn ProofStore,
+        id: &str,
+        system: ProofSystem,
+        build_dir: &Path,
+        opts: SP1ProverOpts,
+    ) -> Result<RewrapRecord, RewrapError> {
+        let compressed_bytes = store.get_compressed(id)?.ok_or(RewrapError::Missing)?;
+        let compressed: SP1ReduceProof<InnerSC> = bincode::deserialize(&compressed_bytes)?;
+        let shrink_proof = self.shrink(compressed, opts.clone())?;
+        let (vkey_hash, committed_values_digest) = wrap_public_inputs(&shrink_proof);
+        let outer = self.wrap_bn254(shrink_proof, opts)?;
+        let limits = GnarkLimits::from_env();
+
+        let wrapped_bytes = match system {
+            ProofSystem::Plonk => {
+                let proof = self.try_wrap_plonk_bn254(outer, build_dir, &limits)?;
+                PlonkBn254Prover::new()
+                    .verify(&proof, &vkey_hash, &committed_values_digest, build_dir)
+                    .map_err(RewrapError::InvalidWrappedProof)?;
+                bincode::serialize(&proof)?
+            }
+            ProofSystem::Groth16 => {
+                let proof = self.try_wrap_groth16_bn254(outer, build_dir, &limits)?;
+                Groth16Bn254Prover::new()
+                    .verify(&proof, &vkey_hash, &committed_values_digest, build_dir)
+                    .map_err(RewrapError::InvalidWrappedProof)?;
+                bincode::serialize(&proof)?
+            }
+        };
+        store.put_wrapped(id, system, &wrapped_bytes)?;
+
+        let record = RewrapRecord {
+            id: id.to_string(),
+            system,
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            compressed_digest: hex::encode(Sha256::digest(&compressed_bytes)),
+            wrapped_digest: hex::encode(Sha256::digest(&wrapped_bytes)),
+        };
+        store.put_record(&record)?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_store_sealed() {
+        let dir = std::env::temp_dir().join(format!("sp1-proof-store-{}", std::process::id()));
+        let cipher = ArtifactCipher::new([7; 32]);
+        let store = DiskProofStore::new(&dir).with_cipher(cipher.clone());
+
+        fs::create_dir_all(dir.join("compressed")).unwrap();
+        fs::write(dir.join("compressed").join("a.bin"), cipher.seal(b"compressed").unwrap())
+            .unwrap();
+        assert_eq!(store.ids().unwrap(), ["a"]);
+        assert_eq!(store.get_compressed("a").unwrap().unwrap(), b"compressed");
+        assert!(DiskProofStore::new(&dir).get_compressed("a").unwrap().unwrap() != b"compressed");
+
+        store.put_wrapped("a", ProofSystem::Plonk, b"wrapped").unwrap();
+        let wrapped = fs::read(dir.join(ProofSystem::Plonk.as_str()).join("a.bin")).unwrap();
+        assert_eq!(cipher.open(&wrapped).unwrap(), b"wrapped");
+
+        let record = RewrapRecord {
+            id: "a".to_string(),
+            system: ProofSystem::Plonk,
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            compressed_digest: String::new(),
+            wrapped_digest: String::new(),
+        };
+        store.put_record(&record).unwrap();
+        let sealed = fs::read(dir.join("records").join("a.Plonk.json")).unwrap();
+        let opened: RewrapRecord = serde_json::from_slice(&cipher.open(&sealed).unwrap()).unwrap();
+        assert_eq!(opened, record);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewrap_failures_skip_their_proof() {
+        use crate::tests::unfixed_prover;
+
+        let dir = std::env::temp_dir().join(format!("sp1-rewrap-{}", std::process::id()));
+        fs::create_dir_all(dir.join("compressed")).unwrap();
+        fs::write(dir.join("compressed").join("a.bin"), b"not a proof").unwrap();
+        fs::write(dir.join("compressed").join("b.bin"), b"").unwrap();
+        fs::write(dir.join("compressed").join("notes.txt"), b"not listed").unwrap();
+        let store = DiskProofStore::new(&dir);
+        assert_eq!(store.ids().unwrap(), ["a", "b"]);
+
+        let prover = unfixed_prover();
+        let report = prover
+            .rewrap_archived(&store, ProofSystem::Plonk, &dir, SP1ProverOpts::default(), 2)
+            .unwrap();
+        assert!(report.records.is_empty());
+        let mut failures = report.failures;
+        failures.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(failures.len(), 2);
+        assert!(failures.iter().all(|(_, e)| matches!(e, RewrapError::Bincode(_))));
+        assert_eq!(failures[0].0, "a");
+        assert!(!dir.join(ProofSystem::Plonk.as_str()).exists());
+
+        // Listing the proofs of the store is the only fatal error.
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(prover
+            .rewrap_archived(&store, ProofSystem::Plonk, &dir, SP1ProverOpts::default(), 1)
+            .is_err());
+    }
+}
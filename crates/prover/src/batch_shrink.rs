@@ -0,0 +1,132 @@
+//! Shrinking many compressed proofs at once.
+//!
+//! Services that wrap a backlog of compressed proofs shrink each of them with the same few shrink
+//! programs. [`SP1Prover::shrink_many`] sets up the shrink program of each shape once for the
+//! whole batch, and runs the shrink programs of the next proofs on a pool of workers while the
+//! previous ones are proven, so that proving is never waiting on execution.
+
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc::sync_channel, Arc, Mutex, OnceLock},
+    thread,
+};
+
+use p3_baby_bear::BabyBear;
+use sp1_recursion_circuit::machine::{SP1CompressWithVkeyShape, SP1CompressWitnessValues};
+use sp1_recursion_core::runtime::ExecutionRecord;
+use sp1_stark::{FriParamsMismatch, MachineProver, SP1ProverOpts};
+
+use crate::{
+    components::SP1ProverComponents, shapes::SP1CompressProgramShape, InnerSC, SP1Prover,
+    SP1RecursionProverError, SP1ReduceProof, ShrinkSetup,
+};
+
+/// The shrink setups of a batch, one cell per shape so that setting up a shape does not block
+/// the workers running the programs of the other shapes.
+type ShrinkSetups<C> =
+    Mutex<BTreeMap<SP1CompressWithVkeyShape, Arc<OnceLock<Arc<ShrinkSetup<C>>>>>>;
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Shrink each of `reduced_proofs` like [`Self::shrink`], setting up the shrink program of
+    /// each shape once and executing the shrink programs on `opts.recursion_opts.trace_gen_workers`
+    /// workers while the proofs are proven.
+    ///
+    /// The results are in the order of `reduced_proofs`, and a failure only fails its proof.
+    #[tracing::instrument(name = "shrink_many", level = "info", skip_all)]
+    pub fn shrink_many(
+        &self,
+        reduced_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> Vec<Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError>> {
+        let num_proofs = reduced_proofs.len();
+        if let Err(e) = FriParamsMismatch::check(
+            "shrink",
+            opts.fri_opts.shrink,
+            self.shrink_prover.config().fri_params(),
+        ) {
+            return (0..num_proofs).map(|_| Err(e.into())).collect();
+        }
+        tracing::info!("shrinking {} compressed proofs", num_proofs);
+
+        let proofs = Mutex::new(reduced_proofs.into_iter().enumerate());
+        let setups = Mutex::new(BTreeMap::new());
+        let mut results = (0..num_proofs).map(|_| None).collect::<Vec<_>>();
+        thread::scope(|s| {
+            let (record_tx, record_rx) =
+                sync_channel(opts.recursion_opts.records_and_traces_channel_capacity);
+            for _ in 0..opts.recursion_opts.trace_gen_workers.max(1) {
+                let (proofs, setups, record_tx) = (&proofs, &setups, record_tx.clone());
+                s.spawn(move || loop {
+                    let Some((index, proof)) = proofs.lock().unwrap().next() else { break };
+                    let record = self.execute_shrink(proof, setups);
+                    if record_tx.send((index, record)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(record_tx);
+
+            for (index, record) in record_rx {
+                results[index] = Some(record.and_then(|(setup, record)| {
+                    let proof = self.prove_shrink_record(&setup.pk, record, &opts)?;
+                    tracing::debug!("shrunk compressed proof {}", index);
+                    Ok(SP1ReduceProof { vk: setup.vk.clone(), proof })
+                }));
+            }
+        });
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Run the shrink program of `reduced_proof`, setting it up if it is not in `setups` yet.
+    fn execute_shrink(
+        &self,
+        reduced_proof: SP1ReduceProof<InnerSC>,
+        setups: &ShrinkSetups<C>,
+    ) -> Result<(Arc<ShrinkSetup<C>>, ExecutionRecord<BabyBear>), SP1RecursionProverError> {
+        self.check_cancelled()?;
+        let SP1ReduceProof { vk, proof } = reduced_proof;
+        let input =
+            SP1CompressWitnessValues { vks_and_proofs: vec![(vk, proof)], is_complete: true };
+        let input_with_merkle = self.make_merkle_proofs(input)?;
+
+        let shape = input_with_merkle.shape();
+        self.check_installed_programs([SP1CompressProgramShape::Shrink(shape.clone())])?;
+        let cell = setups.lock().unwrap().entry(shape.clone()).or_default().clone();
+        // Workers needing the same shape wait for the first one to set it up.
+        let setup = cell.get_or_init(|| self.shrink_setup(&shape)).clone();
+        let record = self.execute_shrink_machine(setup.program.clone(), &input_with_merkle)?;
+        Ok((setup, record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+    use sp1_core_machine::utils::setup_logger;
+
+    use super::*;
+    use crate::{
+        components::CpuProverComponents, merge::tests::compressed_keccak_proofs, HashableKey,
+    };
+
+    #[test]
+    #[serial]
+    fn test_shrink_many_matches_shrink() {
+        setup_logger();
+        let prover = SP1Prover::<CpuProverComponents>::new();
+        let opts = SP1ProverOpts::auto();
+        let (_, proofs) = compressed_keccak_proofs(&prover, &[vec![0, 0, 0], vec![1, 2, 3]]);
+
+        let shrunk = prover.shrink_many(proofs.clone(), opts.clone());
+        assert_eq!(shrunk.len(), proofs.len());
+        for (proof, shrunk) in proofs.into_iter().zip(shrunk) {
+            let expected = prover.shrink(proof, opts.clone()).unwrap();
+            let shrunk = shrunk.unwrap();
+            assert_eq!(shrunk.vk.hash_babybear(), expected.vk.hash_babybear());
+            assert_eq!(
+                bincode::serialize(&shrunk.proof).unwrap(),
+                bincode::serialize(&expected.proof).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Throughput benchmarks of the proving pipeline.
+//!
+//! [`run_suite`] proves a set of programs end-to-end up to a given stage and records how long each
+//! stage took, so that hardware and prover configurations can be compared on the same workload.
+//! The results serialize to JSON. [`standard_suite`] runs a fixed set of representative programs: a
+//! plain loop, a keccak-heavy, a memory-heavy and a precompile-heavy program.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::SP1Context;
+use sp1_core_machine::{io::SP1Stdin, utils::SP1CoreProverError};
+use sp1_stark::SP1ProverOpts;
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents, GnarkLimits, SP1Prover, SP1RecursionProverError,
+    SP1_CIRCUIT_VERSION,
+};
+
+/// A stage of the proving pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BenchStage {
+    Core,
+    Compress,
+    Shrink,
+    Wrap,
+    Plonk,
+    Groth16,
+}
+
+impl BenchStage {
+    /// The stages run to reach `self`, in order.
+    ///
+    /// Plonk and Groth16 are alternatives, so each of them is only preceded by wrap.
+    #[must_use]
+    pub fn pipeline(self) -> Vec<BenchStage> {
+        let mut stages = [Self::Core, Self::Compress, Self::Shrink, Self::Wrap]
+            .into_iter()
+            .filter(|stage| *stage <= self)
+            .collect::<Vec<_>>();
+        if matches!(self, Self::Plonk | Self::Groth16) {
+            stages.push(self);
+        }
+        stages
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BenchError {
+    #[error("program {0}: {1}")]
+    Core(String, SP1CoreProverError),
+    #[error("program {0}: {1}")]
+    Recursion(String, SP1RecursionProverError),
+    #[error("the {0:?} stage needs the directory of the circuit artifacts")]
+    MissingBuildDir(BenchStage),
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A program to benchmark, with its input.
+#[derive(Clone)]
+pub struct BenchProgram {
+    pub name: String,
+    pub elf: Vec<u8>,
+    pub stdin: SP1Stdin,
+}
+
+impl BenchProgram {
+    pub fn new(name: impl Into<String>, elf: &[u8], stdin: SP1Stdin) -> Self {
+        Self { name: name.into(), elf: elf.to_vec(), stdin }
+    }
+}
+
+/// Options of a benchmark run.
+#[derive(Debug, Clone)]
+pub struct BenchOpts {
+    /// The options to prove with.
+    pub prover_opts: SP1ProverOpts,
+    /// The last stage to run, see [`BenchStage::pipeline`].
+    pub last_stage: BenchStage,
+    /// The directory of the circuit artifacts, needed for the Plonk and Groth16 stages.
+    pub build_dir: Option<PathBuf>,
+    /// Where to write the results as JSON, if anywhere.
+    pub output: Option<PathBuf>,
+}
+
+impl Default for BenchOpts {
+    fn default() -> Self {
+        Self {
+            prover_opts: SP1ProverOpts::default(),
+            last_stage: BenchStage::Wrap,
+            build_dir: None,
+            output: None,
+        }
+    }
+}
+
+/// The time taken by a stage and the size of the proof it produced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageResult {
+    pub stage: BenchStage,
+    pub seconds: f64,
+    /// The size of the serialized proof, in bytes.
+    pub proof_size: usize,
+}
+
+/// The results of proving one program.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgramResult {
+    pub name: String,
+    pub cycles: u64,
+    pub shards: usize,
+    /// The time taken by setup, which is not part of any stage.
+    pub setup_seconds: f64,
+    pub stages: Vec<StageResult>,
+}
+
+impl ProgramResult {
+    /// The time taken by all the stages.
+    #[must_use]
+    pub fn total_seconds(&self) -> f64 {
+        self.stages.iter().map(|stage| stage.seconds).sum()
+    }
+
+    /// The number of cycles proven per second, over all the stages.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cycles_per_second(&self) -> f64 {
+        self.cycles as f64 / self.total_seconds()
+    }
+}
+
+/// The results of a benchmark run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuiteResults {
+    pub circuit_version: String,
+    pub prover_opts: SP1ProverOpts,
+    pub last_stage: BenchStage,
+    pub programs: Vec<ProgramResult>,
+}
+
+impl SuiteResults {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BenchError> {
+        Ok(serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BenchError> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+}
+
+/// Prove each of `programs` up to `opts.last_stage` and record the time of every stage.
+///
+/// The proofs are not verified. If `opts.output` is set, the results are also written there.
+pub fn run_suite<C: SP1ProverComponents>(
+    prover: &SP1Prover<C>,
+    programs: &[BenchProgram],
+    opts: &BenchOpts,
+) -> Result<SuiteResults, BenchError> {
+    let pipeline = opts.last_stage.pipeline();
+    let build_dir = match opts.last_stage {
+        BenchStage::Plonk | BenchStage::Groth16 => {
+            Some(opts.build_dir.as_deref().ok_or(BenchError::MissingBuildDir(opts.last_stage))?)
+        }
+        _ => None,
+    };
+
+    let mut results = Vec::with_capacity(programs.len());
+    for program in programs {
+        tracing::info!("benchmarking {}", program.name);
+        let result =
+            bench_program(prover, program, &pipeline, opts.prover_opts.clone(), build_dir)?;
+        tracing::info!(
+            "{}: {} cycles in {:.2}s ({:.0} cycles/s)",
+            result.name,
+            result.cycles,
+            result.total_seconds(),
+            result.cycles_per_second()
+        );
+        results.push(result);
+    }
+
+    let results = SuiteResults {
+        circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+        prover_opts: opts.prover_opts.clone(),
+        last_stage: opts.last_stage,
+        programs: results,
+    };
+    if let Some(output) = &opts.output {
+        results.save(output)?;
+    }
+    Ok(results)
+}
+
+/// Time `f` and measure the serialized size of its output.
+fn timed<T: Serialize, E>(
+    stage: BenchStage,
+    stages: &mut Vec<StageResult>,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let output = f()?;
+    let seconds = start.elapsed().as_secs_f64();
+    let proof_size = bincode::serialized_size(&output).map_or(0, |size| size as usize);
+    stages.push(StageResult { stage, seconds, proof_size });
+    Ok(output)
+}
+
+fn bench_program<C: SP1ProverComponents>(
+    prover: &SP1Prover<C>,
+    program: &BenchProgram,
+    pipeline: &[BenchStage],
+    opts: SP1ProverOpts,
+    build_dir: Option<&Path>,
+) -> Result<ProgramResult, BenchError> {
+    let core_err = |e| BenchError::Core(program.name.clone(), e);
+    let recursion_err = |e| BenchError::Recursion(program.name.clone(), e);
+
+    let start = Instant::now();
+    let (_, pk_d, core_program, vk) = prover.setup(&program.elf);
+    let setup_seconds = start.elapsed().as_secs_f64();
+
+    let mut stages = Vec::with_capacity(pipeline.len());
+    let core_proof = timed(BenchStage::Core, &mut stages, || {
+        prover.prove_core(&pk_d, core_program, &program.stdin, opts.clone(), SP1Context::default())
+    })
+    .map_err(core_err)?;
+    let cycles = core_proof.cycles;
+    let shards = core_proof.proof.0.len();
+
+    let finish = |stages| ProgramResult {
+        name: program.name.clone(),
+        cycles,
+        shards,
+        setup_seconds,
+        stages,
+    };
+
+    if !pipeline.contains(&BenchStage::Compress) {
+        return Ok(finish(stages));
+    }
+    let compressed = timed(BenchStage::Compress, &mut stages, || {
+        prover.compress(&vk, core_proof, vec![], opts.clone())
+    })
+    .map_err(recursion_err)?;
+
+    if !pipeline.contains(&BenchStage::Shrink) {
+        return Ok(finish(stages));
+    }
+    let shrunk = timed(BenchStage::Shrink, &mut stages, || prover.shrink(compressed, opts.clone()))
+        .map_err(recursion_err)?;
+
+    if !pipeline.contains(&BenchStage::Wrap) {
+        return Ok(finish(stages));
+    }
+    let wrapped = timed(BenchStage::Wrap, &mut stages, || prover.wrap_bn254(shrunk, opts))
+        .map_err(recursion_err)?;
+
+    if let Some(build_dir) = build_dir {
+        if pipeline.contains(&BenchStage::Plonk) {
+            timed(BenchStage::Plonk, &mut stages, || {
+                prover.try_wrap_plonk_bn254(wrapped, build_dir, &GnarkLimits::from_env())
+            })
+            .map_err(recursion_err)?;
+        } else if pipeline.contains(&BenchStage::Groth16) {
+            timed(BenchStage::Groth16, &mut stages, || {
+                prover.try_wrap_groth16_bn254(wrapped, build_dir, &GnarkLimits::from_env())
+            })
+            .map_err(recursion_err)?;
+        }
+    }
+    Ok(finish(stages))
+}
+
+/// The ELFs of the representative programs of the standard suite.
+///
+/// These are the `fibonacci-program-tests`, `keccak256-test`, `ssz-withdrawals-test` and
+/// `ed25519-program` programs of the test artifacts.
+#[derive(Clone)]
+pub struct StandardPrograms {
+    /// A plain arithmetic loop.
+    pub fibonacci: Vec<u8>,
+    /// Hashing with the keccak precompile.
+    pub keccak: Vec<u8>,
+    /// Merkleization of beacon state, which touches a lot of memory.
+    pub memory: Vec<u8>,
+    /// Signature verification with the elliptic curve precompiles.
+    pub precompile: Vec<u8>,
+}
+
+impl StandardPrograms {
+    /// The names of the ELFs, in the order of the fields.
+    pub const ELF_NAMES: [&'static str; 4] =
+        ["fibonacci-program-tests", "keccak256-test", "ssz-withdrawals-test", "ed25519-program"];
+
+    /// Load the ELFs from a directory they were built into, such as
+    /// `target/elf-compilation/riscv32im-succinct-zkvm-elf/release`.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, BenchError> {
+        let [fibonacci, keccak, memory, precompile] =
+            Self::ELF_NAMES.map(|name| std::fs::read(dir.as_ref().join(name)));
+        Ok(Self {
+            fibonacci: fibonacci?,
+            keccak: keccak?,
+            memory: memory?,
+            precompile: precompile?,
+        })
+    }
+
+    /// The programs with their fixed inputs.
+    #[must_use]
+    pub fn programs(&self) -> Vec<BenchProgram> {
+        let mut keccak_stdin = SP1Stdin::new();
+        let num_cases = 64usize;
+        keccak_stdin.write(&num_cases);
+        for i in 0..num_cases {
+            keccak_stdin.write(&vec![i as u8; 4096]);
+        }
+
+        vec![
+            BenchProgram::new("fibonacci", &self.fibonacci, SP1Stdin::new()),
+            BenchProgram::new("keccak", &self.keccak, keccak_stdin),
+            BenchProgram::new("memory", &self.memory, SP1Stdin::new()),
+            BenchProgram::new("precompile", &self.precompile, SP1Stdin::new()),
+        ]
+    }
+}
+
+/// Run the standard suite of programs, see [`run_suite`].
+pub fn standard_suite<C: SP1ProverComponents>(
+    prover: &SP1Prover<C>,
+    programs: &StandardPrograms,
+    opts: &BenchOpts,
+) -> Result<SuiteResults, BenchError> {
+    run_suite(prover, &programs.programs(), opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::unfixed_prover;
+
+    #[test]
+    fn test_bench_stage_pipeline() {
+        use BenchStage::*;
+        assert_eq!(Core.pipeline(), vec![Core]);
+        assert_eq!(Wrap.pipeline(), vec![Core, Compress, Shrink, Wrap]);
+        assert_eq!(Groth16.pipeline(), vec![Core, Compress, Shrink, Wrap, Groth16]);
+    }
+
+    #[test]
+    fn test_run_suite_without_programs() {
+        let prover = unfixed_prover();
+        let output = std::env::temp_dir().join(format!("sp1-bench-{}.json", std::process::id()));
+        let opts = BenchOpts { output: Some(output.clone()), ..BenchOpts::default() };
+        let results = run_suite(&prover, &[], &opts).unwrap();
+        assert_eq!(SuiteResults::load(&output).unwrap(), results);
+        std::fs::remove_file(&output).unwrap();
+
+        let opts = BenchOpts { last_stage: BenchStage::Plonk, ..BenchOpts::default() };
+        assert!(matches!(
+            run_suite(&prover, &[], &opts),
+            Err(BenchError::MissingBuildDir(BenchStage::Plonk))
+        ));
+    }
+}
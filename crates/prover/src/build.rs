@@ -1,6 +1,6 @@
 #![allow(clippy::print_stdout)] // okay to print to stdout: this is a build script
 
-use std::{borrow::Borrow, path::PathBuf};
+use std::{borrow::Borrow, env, path::PathBuf};
 
 use p3_baby_bear::BabyBear;
 use sp1_core_executor::SP1Context;
@@ -23,7 +23,7 @@ pub use sp1_recursion_core::stark::sp1_dev_mode;
 
 use crate::{
     utils::{babybear_bytes_to_bn254, babybears_to_bn254, words_to_bytes},
-    OuterSC, SP1Prover, WrapAir,
+    OuterSC, SP1Prover, WrapAir, SP1_CIRCUIT_VERSION,
 };
 
 /// Tries to build the PLONK artifacts inside the development directory.
@@ -58,6 +58,28 @@ pub fn groth16_bn254_artifacts_dev_dir() -> PathBuf {
     dirs::home_dir().unwrap().join(".sp1").join("circuits").join("dev")
 }
 
+/// Gets the directory where the PLONK circuit artifacts are installed, set by
+/// `SP1_PLONK_CIRCUIT_PATH` and defaulting to `~/.sp1/circuits/plonk`.
+pub fn plonk_bn254_artifacts_dir() -> PathBuf {
+    env::var("SP1_PLONK_CIRCUIT_PATH")
+        .map_or_else(
+            |_| dirs::home_dir().unwrap().join(".sp1").join("circuits/plonk"),
+            |path| path.parse().unwrap(),
+        )
+        .join(SP1_CIRCUIT_VERSION)
+}
+
+/// Gets the directory where the groth16 circuit artifacts are installed, set by
+/// `SP1_GROTH16_CIRCUIT_PATH` and defaulting to `~/.sp1/circuits/groth16`.
+pub fn groth16_bn254_artifacts_dir() -> PathBuf {
+    env::var("SP1_GROTH16_CIRCUIT_PATH")
+        .map_or_else(
+            |_| dirs::home_dir().unwrap().join(".sp1").join("circuits/groth16"),
+            |path| path.parse().unwrap(),
+        )
+        .join(SP1_CIRCUIT_VERSION)
+}
+
 /// Build the plonk bn254 artifacts to the given directory for the given verification key and
 /// template proof.
 pub fn build_plonk_bn254_artifacts(
@@ -162,13 +184,13 @@ pub fn dummy_proof() -> (StarkVerifyingKey<OuterSC>, ShardProof<OuterSC>) {
     tracing::info!("prove core");
     let mut stdin = SP1Stdin::new();
     stdin.write(&500u32);
-    let core_proof = prover.prove_core(&pk_d, program, &stdin, opts, context).unwrap();
+    let core_proof = prover.prove_core(&pk_d, program, &stdin, opts.clone(), context).unwrap();
 
     tracing::info!("compress");
-    let compressed_proof = prover.compress(&vk, core_proof, vec![], opts).unwrap();
+    let compressed_proof = prover.compress(&vk, core_proof, vec![], opts.clone()).unwrap();
 
     tracing::info!("shrink");
-    let shrink_proof = prover.shrink(compressed_proof, opts).unwrap();
+    let shrink_proof = prover.shrink(compressed_proof, opts.clone()).unwrap();
 
     tracing::info!("wrap");
     let wrapped_proof = prover.wrap_bn254(shrink_proof, opts).unwrap();
@@ -0,0 +1,205 @@
+//! Negotiating compatibility between provers and their clients.
+//!
+//! Proofs and keys only work with provers of the same circuit version, shape configurations and
+//! allowed recursion verifying keys. [`SP1Prover::capabilities`] describes all of these, so that a
+//! client can compare them with its own with [`ProverCapabilities::check_compatible`], or check the
+//! keys and proofs it is about to submit, and fail before sending a job that cannot succeed.
+
+use p3_field::PrimeField32;
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::shape::CoreShapeConfig;
+use sp1_stark::{FriParams, MachineProver, DIGEST_SIZE};
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents,
+    public_values::{PublicValuesError, ReduceProofPublicValues},
+    InnerSC, SP1Prover, SP1ReduceProof, SP1VerifyingKey, SP1_CIRCUIT_VERSION,
+};
+
+/// A kind of proof a prover can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ProofKind {
+    Core,
+    Compressed,
+    Shrink,
+    Wrap,
+    Plonk,
+    Groth16,
+}
+
+impl ProofKind {
+    pub const ALL: [ProofKind; 6] =
+        [Self::Core, Self::Compressed, Self::Shrink, Self::Wrap, Self::Plonk, Self::Groth16];
+}
+
+/// The configuration a stage of the prover proves with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageConfig {
+    /// The name of the STARK configuration, which fixes the field and the hash.
+    pub config: String,
+    /// The FRI parameters.
+    pub fri: FriParams,
+}
+
+/// What a prover supports and the digests of the artifacts its proofs depend on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProverCapabilities {
+    pub circuit_version: String,
+    /// The kinds of proofs the prover produces.
+    pub proof_kinds: Vec<ProofKind>,
+    pub core: StageConfig,
+    pub compress: StageConfig,
+    pub shrink: StageConfig,
+    pub wrap: StageConfig,
+    /// The root of the allowed recursion verifying keys.
+    pub vk_root: [u32; DIGEST_SIZE],
+    /// The root of the previous allowed recursion verifying keys, still accepted during a vk map
+    /// rotation.
+    #[serde(default)]
+    pub retiring_vk_root: Option<[u32; DIGEST_SIZE]>,
+    /// The number of allowed recursion verifying keys.
+    pub num_recursion_vks: usize,
+    /// Whether recursion verifying keys are checked against the allowed ones.
+    pub vk_verification: bool,
+    /// The digest of the core shape configuration, if shapes are fixed.
+    pub core_shape_config_digest: Option<[u8; 32]>,
+}
+
+/// A reason why a proof, key or prover is not compatible with a prover.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    #[error("circuit version {found} does not match {expected}")]
+    CircuitVersion { expected: String, found: String },
+    #[error("the {stage} stage proves with {found:?}, expected {expected:?}")]
+    StageConfig { stage: &'static str, expected: StageConfig, found: StageConfig },
+    #[error("the allowed recursion verifying keys differ")]
+    VkRoot,
+    #[error("vk verification is {}", if *.0 { "enabled" } else { "disabled" })]
+    VkVerification(bool),
+    #[error("the core shape configurations differ")]
+    ShapeConfig,
+    #[error("{0:?} proofs are not supported")]
+    ProofKind(ProofKind),
+    #[error("invalid proof: {0}")]
+    InvalidProof(#[from] PublicValuesError),
+}
+
+impl ProverCapabilities {
+    /// Check that proofs and keys of the prover described by `other` are accepted by the prover
+    /// described by `self`, returning every incompatibility found.
+    pub fn check_compatible(&self, other: &ProverCapabilities) -> Result<(), Vec<Incompatibility>> {
+        let mut incompatibilities = Vec::new();
+        if let Err(e) = self.check_circuit_version(&other.circuit_version) {
+            incompatibilities.push(e);
+        }
+        for (stage, expected, found) in [
+            ("core", &self.core, &other.core),
+            ("compress", &self.compress, &other.compress),
+            ("shrink", &self.shrink, &other.shrink),
+            ("wrap", &self.wrap, &other.wrap),
+        ] {
+            if expected != found {
+                incompatibilities.push(Incompatibility::StageConfig {
+                    stage,
+                    expected: expected.clone(),
+                    found: found.clone(),
+                });
+            }
+        }
+        if self.vk_root != other.vk_root {
+            incompatibilities.push(Incompatibility::VkRoot);
+        }
+        if self.vk_verification != other.vk_verification {
+            incompatibilities.push(Incompatibility::VkVerification(other.vk_verification));
+        }
+        if self.core_shape_config_digest != other.core_shape_config_digest {
+            incompatibilities.push(Incompatibility::ShapeConfig);
+        }
+
+        if incompatibilities.is_empty() {
+            Ok(())
+        } else {
+            Err(incompatibilities)
+        }
+    }
+
+    /// Check that artifacts made for `circuit_version` are accepted.
+    pub fn check_circuit_version(&self, circuit_version: &str) -> Result<(), Incompatibility> {
+        if circuit_version != self.circuit_version {
+            return Err(Incompatibility::CircuitVersion {
+                expected: self.circuit_version.clone(),
+                found: circuit_version.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that proofs of `kind` are supported.
+    pub fn check_proof_kind(&self, kind: ProofKind) -> Result<(), Incompatibility> {
+        if !self.proof_kinds.contains(&kind) {
+            return Err(Incompatibility::ProofKind(kind));
+        }
+        Ok(())
+    }
+
+    /// Check that `vk` was set up with the core shape configuration, circuit version and allowed
+    /// recursion verifying keys of the prover.
+    ///
+    /// Verifying keys that do not record their configuration or artifacts are accepted.
+    pub fn check_vk(&self, vk: &SP1VerifyingKey) -> Result<(), Incompatibility> {
+        if let Some(digest) = vk.shape_config_digest {
+            if Some(digest) != self.core_shape_config_digest {
+                return Err(Incompatibility::ShapeConfig);
+            }
+        }
+        if let Some(artifacts) = &vk.artifacts {
+            self.check_circuit_version(&artifacts.circuit_version)?;
+            if !self.accepts_vk_root(&artifacts.vk_root) {
+                return Err(Incompatibility::VkRoot);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a compressed or shrink proof was made with the allowed recursion verifying keys
+    /// of the prover, so that it can be used as a deferred proof or wrapped.
+    pub fn check_reduce_proof(
+        &self,
+        proof: &SP1ReduceProof<InnerSC>,
+    ) -> Result<(), Incompatibility> {
+        let vk_root = proof.vk_root()?.map(|x| x.as_canonical_u32());
+        if !self.accepts_vk_root(&vk_root) {
+            return Err(Incompatibility::VkRoot);
+        }
+        Ok(())
+    }
+
+    /// Whether proofs under `vk_root` are accepted, either as the active or the retiring root.
+    #[must_use]
+    pub fn accepts_vk_root(&self, vk_root: &[u32; DIGEST_SIZE]) -> bool {
+        *vk_root == self.vk_root || self.retiring_vk_root.as_ref() == Some(vk_root)
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Describe the circuit version, configurations and artifacts of the prover.
+    pub fn capabilities(&self) -> ProverCapabilities {
+        let stage = |config: &str, fri| StageConfig { config: config.to_string(), fri };
+        ProverCapabilities {
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            proof_kinds: ProofKind::ALL.to_vec(),
+            core: stage("BabyBearPoseidon2", self.core_prover.config().fri_params()),
+            compress: stage("BabyBearPoseidon2", self.compress_prover.config().fri_params()),
+            shrink: stage("BabyBearPoseidon2", self.shrink_prover.config().fri_params()),
+            wrap: stage("BabyBearPoseidon2Outer", self.wrap_prover.config().fri_params()),
+            vk_root: self.recursion_vk_root.map(|x| x.as_canonical_u32()),
+            retiring_vk_root: self
+                .retiring_vk_root()
+                .map(|root| root.map(|x| x.as_canonical_u32())),
+            num_recursion_vks: self.recursion_vk_map.len(),
+            vk_verification: self.vk_verification.checks_vks(),
+            core_shape_config_digest: self.core_shape_config.as_ref().map(CoreShapeConfig::digest),
+        }
+    }
+}
@@ -3,7 +3,7 @@ use sp1_stark::{CpuProver, MachineProver, StarkGenericConfig};
 
 use crate::{CompressAir, CoreSC, InnerSC, OuterSC, ShrinkAir, WrapAir};
 
-pub trait SP1ProverComponents: Send + Sync {
+pub trait SP1ProverComponents: Send + Sync + 'static {
     /// The prover for making SP1 core proofs.
     type CoreProver: MachineProver<CoreSC, RiscvAir<<CoreSC as StarkGenericConfig>::Val>>
         + Send
@@ -15,8 +15,13 @@ pub trait SP1ProverComponents: Send + Sync {
         + Sync;
 
     /// The prover for shrinking compressed proofs.
-    type ShrinkProver: MachineProver<InnerSC, ShrinkAir<<InnerSC as StarkGenericConfig>::Val>>
-        + Send
+    ///
+    /// Its proving keys are cached across proofs, see [`crate::SP1Prover::shrink_setup`].
+    type ShrinkProver: MachineProver<
+            InnerSC,
+            ShrinkAir<<InnerSC as StarkGenericConfig>::Val>,
+            DeviceProvingKey: Send + Sync,
+        > + Send
         + Sync;
 
     /// The prover for wrapping compressed proofs into SNARK-friendly field elements.
@@ -0,0 +1,185 @@
+//! Exporting the recursion tree of `compress` for inspection.
+//!
+//! A [`CompressTree`] is the [`ReductionPlan`] of a compress run annotated with what happened to
+//! each node: when its input was ready, which prover worker proved it, the shape and stage
+//! timings of its proof, and when the proof was received. Comparing the ready and proven times
+//! across nodes shows the unbalanced parts of the tree and the bubbles of the pipeline, where
+//! nodes wait for their inputs or for a worker.
+//!
+//! [`SP1Prover::last_compress_tree`] returns the tree of the last compressed proof, and
+//! [`SP1Prover::plan_compress_tree`] the tree planned for a number of first layer proofs, before
+//! any of them is proven. Either can be exported as JSON or as a Graphviz DOT graph.
+
+use std::{fmt::Write, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::utils::StageTimings;
+use sp1_stark::{shape::OrderedShape, SP1ProverOpts};
+
+use crate::{
+    components::SP1ProverComponents, lock_or_reset, reduction::ReductionPlan, SP1Prover,
+    SP1RecursionProverError,
+};
+
+/// A node of a [`CompressTree`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressTreeNode {
+    /// The nodes joined by this node, empty for the first layer.
+    pub inputs: Vec<usize>,
+    /// The height of the node, the first layer being at height zero.
+    pub height: usize,
+    /// When the input of the node was sent to the workers, since the start of the run.
+    pub ready: Option<Duration>,
+    /// When the proof of the node was received, since the start of the run.
+    pub proven: Option<Duration>,
+    /// The prover worker that proved the node, if it was proven rather than passed through.
+    pub worker: Option<usize>,
+    /// The shape of the proof of the node.
+    pub shape: Option<OrderedShape>,
+    /// The time spent in each stage of proving the node.
+    pub timings: Option<StageTimings>,
+}
+
+impl CompressTreeNode {
+    /// Whether the node passes its single input through without proving it.
+    #[must_use]
+    pub fn is_passed_through(&self) -> bool {
+        self.inputs.len() == 1
+    }
+}
+
+/// The recursion tree of a compress run, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressTree {
+    /// The maximum number of proofs joined at a time.
+    pub arity: usize,
+    /// The nodes of the tree, in the order they are proven, as numbered by the [`ReductionPlan`].
+    pub nodes: Vec<CompressTreeNode>,
+}
+
+impl CompressTree {
+    /// The tree planned by `plan`, with no node proven yet.
+    #[must_use]
+    pub fn new(plan: &ReductionPlan, arity: usize) -> Self {
+        let nodes = plan
+            .heights()
+            .into_iter()
+            .enumerate()
+            .map(|(index, height)| CompressTreeNode {
+                inputs: index
+                    .checked_sub(plan.num_inputs)
+                    .map(|join| plan.joins[join].clone())
+                    .unwrap_or_default(),
+                height,
+                ..CompressTreeNode::default()
+            })
+            .collect();
+        Self { arity, nodes }
+    }
+
+    pub(crate) fn node_ready(&mut self, index: usize, elapsed: Duration) {
+        self.nodes[index].ready = Some(elapsed);
+    }
+
+    pub(crate) fn node_proven_by(
+        &mut self,
+        index: usize,
+        worker: usize,
+        shape: OrderedShape,
+        timings: StageTimings,
+    ) {
+        let node = &mut self.nodes[index];
+        node.worker = Some(worker);
+        node.shape = Some(shape);
+        node.timings = Some(timings);
+    }
+
+    pub(crate) fn node_proven(&mut self, index: usize, elapsed: Duration) {
+        self.nodes[index].proven = Some(elapsed);
+    }
+
+    /// The tree as pretty-printed JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// The tree as a Graphviz DOT graph, with the root at the top.
+    ///
+    /// Each node is labelled with its index, height, worker and timings. Passed through nodes are
+    /// dashed, and nodes that were never proven are grey.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph compress {\n    rankdir=BT;\n    node [shape=box];\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut label = format!("node {index}\\nheight {}", node.height);
+            if let Some(worker) = node.worker {
+                write!(label, "\\nworker {worker}").unwrap();
+            }
+            if let Some(timings) = node.timings {
+                write!(label, "\\nproving {:.2?}", timings.total()).unwrap();
+            }
+            if let (Some(ready), Some(proven)) = (node.ready, node.proven) {
+                write!(label, "\\n{ready:.2?} -> {proven:.2?}").unwrap();
+            }
+            let style = if node.is_passed_through() {
+                ", style=dashed"
+            } else if node.proven.is_none() {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+            writeln!(dot, "    n{index} [label=\"{label}\"{style}];").unwrap();
+            for input in node.inputs.iter() {
+                writeln!(dot, "    n{input} -> n{index};").unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The recursion tree of the last compressed proof, if any.
+    ///
+    /// Only the tree of the final proof is kept, not the trees reducing the deferred proofs.
+    pub fn last_compress_tree(&self) -> Option<CompressTree> {
+        lock_or_reset(&self.compress_tree, |tree| *tree = None).clone()
+    }
+
+    /// The recursion tree planned for reducing `num_inputs` first layer proofs with `opts`.
+    pub fn plan_compress_tree(
+        &self,
+        num_inputs: usize,
+        opts: &SP1ProverOpts,
+    ) -> Result<CompressTree, SP1RecursionProverError> {
+        let arity = self.try_compress_arity(opts)?;
+        let plan = self.reduction_strategy.plan(num_inputs, arity);
+        plan.validate(num_inputs, arity).map_err(SP1RecursionProverError::InvalidReductionPlan)?;
+        Ok(CompressTree::new(&plan, arity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduction::{LayeredReduction, ReductionStrategy};
+
+    #[test]
+    fn test_compress_tree_export() {
+        let plan = LayeredReduction.plan(5, 2);
+        let mut tree = CompressTree::new(&plan, 2);
+        assert_eq!(tree.nodes.len(), plan.num_inputs + plan.joins.len());
+        assert_eq!(tree.nodes[5].inputs, vec![0, 1]);
+        assert!(tree.nodes[7].is_passed_through());
+
+        tree.node_ready(0, Duration::from_millis(1));
+        tree.node_proven(0, Duration::from_millis(5));
+        let dot = tree.to_dot();
+        assert!(dot.contains("n0 -> n5;"));
+        assert!(dot.contains("n4 -> n7;"));
+        assert!(dot.contains("n7 [label=\"node 7\\nheight 1\", style=dashed];"));
+
+        let json = tree.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<CompressTree>(&json).unwrap(), tree);
+    }
+}
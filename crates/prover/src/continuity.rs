@@ -0,0 +1,144 @@
+//! Checking that the shards of a core proof chain into a single execution.
+//!
+//! A core proof received over the network may have shards that are missing, duplicated,
+//! reordered or taken from another execution. Each shard can still verify on its own, and
+//! `compress` only rejects such a proof after proving the recursion tree up to the join that
+//! connects the mismatching shards. [`validate_core_proof`] checks the chaining of the public
+//! values of the shards up front, without verifying any STARK, and reports the first shard that
+//! breaks it, which is the repair point from which the proof must be proven again.
+
+use sp1_stark::MachineVerificationError;
+use thiserror::Error;
+
+use crate::{
+    verify::{check_shard_public_values, ShardPublicValuesError},
+    CoreSC, SP1CoreProofData, SP1VerifyingKey,
+};
+
+/// An error returned by [`validate_core_proof`], at the first shard that does not continue the
+/// execution of the shards before it.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CoreProofValidationError {
+    #[error("the core proof has no shards")]
+    Empty,
+    #[error("shard {shard}: {reason}")]
+    Discontinuity { shard: usize, reason: String },
+}
+
+impl CoreProofValidationError {
+    /// The index of the first shard to prove again, every shard before it chaining correctly from
+    /// the start of the execution.
+    #[must_use]
+    pub fn repair_point(&self) -> usize {
+        match self {
+            Self::Empty => 0,
+            Self::Discontinuity { shard, .. } => *shard,
+        }
+    }
+}
+
+impl From<ShardPublicValuesError<CoreSC>> for CoreProofValidationError {
+    fn from(e: ShardPublicValuesError<CoreSC>) -> Self {
+        match e.error {
+            MachineVerificationError::EmptyProof => Self::Empty,
+            error => Self::Discontinuity { shard: e.shard, reason: error.to_string() },
+        }
+    }
+}
+
+/// Check that the shards of `proof` chain into a complete execution of the program of `vk`.
+///
+/// The public values of the shards are checked as by
+/// [`SP1Prover::verify`](crate::SP1Prover::verify): the shards must be numbered from one, each
+/// shard must start at the pc the previous one ended at, from the start pc of `vk` to zero, and the
+/// memory addresses and digests must continue from one shard to the next. The STARKs of the shards
+/// are not verified.
+pub fn validate_core_proof(
+    proof: &SP1CoreProofData,
+    vk: &SP1VerifyingKey,
+) -> Result<(), CoreProofValidationError> {
+    check_shard_public_values(&proof.0, vk.vk.pc_start, true)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::BorrowMut;
+
+    use p3_baby_bear::BabyBear;
+    use p3_field::AbstractField;
+    use sp1_core_machine::riscv::RiscvAir;
+    use sp1_recursion_circuit::stark::dummy_vk_and_shard_proof;
+    use sp1_stark::{air::PublicValues, shape::OrderedShape, ShardProof, Word};
+
+    use super::*;
+
+    /// A core proof of `num_shards` shards with a CPU, chaining from the start pc of its key.
+    fn chained_proof(num_shards: usize) -> (SP1CoreProofData, SP1VerifyingKey) {
+        let machine = RiscvAir::machine(CoreSC::default());
+        let shape = OrderedShape { inner: vec![("Program".into(), 4), ("Cpu".into(), 4)] };
+        let (mut vk, template) = dummy_vk_and_shard_proof(&machine, &shape);
+        vk.pc_start = BabyBear::from_canonical_u32(0x20_0000);
+        let shards = (0..num_shards)
+            .map(|i| {
+                let mut shard: ShardProof<CoreSC> = template.clone();
+                let pv: &mut PublicValues<Word<BabyBear>, BabyBear> =
+                    shard.public_values.as_mut_slice().borrow_mut();
+                pv.shard = BabyBear::from_canonical_usize(i + 1);
+                pv.execution_shard = BabyBear::from_canonical_usize(i + 1);
+                pv.start_pc = vk.pc_start + BabyBear::from_canonical_usize(4 * i);
+                pv.next_pc = if i + 1 == num_shards {
+                    BabyBear::zero()
+                } else {
+                    vk.pc_start + BabyBear::from_canonical_usize(4 * (i + 1))
+                };
+                shard
+            })
+            .collect();
+        let vk =
+            SP1VerifyingKey { vk, shape_config_digest: None, artifacts: None, domain_tag: None };
+        (SP1CoreProofData(shards), vk)
+    }
+
+    fn repair_point(proof: &SP1CoreProofData, vk: &SP1VerifyingKey) -> usize {
+        validate_core_proof(proof, vk).unwrap_err().repair_point()
+    }
+
+    #[test]
+    fn test_validate_core_proof() {
+        let (proof, vk) = chained_proof(4);
+        validate_core_proof(&proof, &vk).unwrap();
+
+        let empty = SP1CoreProofData(vec![]);
+        assert_eq!(validate_core_proof(&empty, &vk), Err(CoreProofValidationError::Empty));
+        assert_eq!(repair_point(&empty, &vk), 0);
+
+        let mut missing = proof.clone();
+        missing.0.remove(2);
+        assert_eq!(repair_point(&missing, &vk), 2);
+
+        let mut reordered = proof.clone();
+        reordered.0.swap(1, 2);
+        assert_eq!(repair_point(&reordered, &vk), 1);
+
+        let mut truncated = proof.clone();
+        truncated.0.pop();
+        assert_eq!(repair_point(&truncated, &vk), 2);
+    }
+
+    #[test]
+    fn test_validate_core_proof_shares_verify_checks() {
+        let (mut proof, vk) = chained_proof(3);
+        let pv: &mut PublicValues<Word<BabyBear>, BabyBear> =
+            proof.0[1].public_values.as_mut_slice().borrow_mut();
+        pv.exit_code = BabyBear::one();
+        let error = validate_core_proof(&proof, &vk).unwrap_err();
+        assert_eq!(error.repair_point(), 1);
+        assert!(error.to_string().contains("exit_code"), "{error}");
+
+        // A shard with too few public values is rejected rather than read out of bounds.
+        let (mut proof, vk) = chained_proof(3);
+        proof.0[2].public_values.truncate(4);
+        assert_eq!(repair_point(&proof, &vk), 2);
+    }
+}
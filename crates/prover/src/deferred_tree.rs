@@ -0,0 +1,133 @@
+//! Folding large sets of deferred proofs into a single proof with bounded memory.
+//!
+//! [`SP1Prover::compress_deferred_tree`] verifies the deferred proofs of a program in batches, like
+//! the deferred programs of `compress`, and joins the results with a balanced tree of compress
+//! programs. The batches are split into subtrees of at most
+//! [`max_subtree_leaves`](sp1_stark::SP1DeferredOpts::max_subtree_leaves) leaves of near-equal
+//! size, each reduced with a [`BalancedReduction`] from inputs generated as it consumes them, and
+//! the roots of the subtrees are joined the same way. Only the proofs of one subtree and the roots
+//! of the subtrees proven so far are held at once, however many deferred proofs are folded.
+
+use p3_baby_bear::BabyBear;
+use sp1_core_machine::reduce::SP1ReduceProof;
+use sp1_stark::{SP1ProverOpts, DIGEST_SIZE};
+
+use crate::{
+    components::SP1ProverComponents, reduction::BalancedReduction, resume::compress_inputs,
+    shapes::SP1CompressProgramShape, HashableKey, InnerSC, SP1CircuitWitness, SP1Prover,
+    SP1RecursionProverError, SP1VerifyingKey,
+};
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Reduce the deferred proofs verified by the program of `vk` into a single proof, chaining
+    /// the deferred digest from `initial_deferred_digest`, with a balanced tree proven one subtree
+    /// at a time.
+    ///
+    /// The proof is that of compressing `deferred_proofs` without core shards, see
+    /// [`Self::compress`]. It is not complete, and with a zero initial digest it is verified with
+    /// [`Self::verify_deferred_only`]. The batches of deferred proofs and the size of the subtrees
+    /// are taken from `opts.deferred_opts`.
+    #[tracing::instrument(name = "compress_deferred_tree", level = "info", skip_all)]
+    pub fn compress_deferred_tree(
+        &self,
+        vk: &SP1VerifyingKey,
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        initial_deferred_digest: [BabyBear; DIGEST_SIZE],
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.check_cancelled()?;
+        if deferred_proofs.is_empty() {
+            return Err(SP1RecursionProverError::InvalidMergeInput("no deferred proofs to reduce"));
+        }
+        self.check_installed_join_programs()?;
+        self.check_join_programs_precompiled()?;
+
+        let batches = Self::deferred_batches(deferred_proofs, &opts.deferred_opts);
+        self.check_installed_programs(batches.iter().map(|batch| {
+            let shape = self.deferred_batch_shape(batch.iter().map(|proof| &proof.proof));
+            SP1CompressProgramShape::Deferred(shape)
+        }))?;
+
+        // Subtrees of at least the arity are needed for the tree to shrink at each level.
+        let arity = self.try_compress_arity(&opts)?;
+        let max_leaves = opts.deferred_opts.max_subtree_leaves.max(arity);
+        tracing::info!(
+            "reducing {} deferred proofs in {} batches, {} batches per subtree",
+            deferred_proofs.len(),
+            batches.len(),
+            max_leaves
+        );
+
+        // Reduce the batches of each subtree, chaining the deferred digest through the subtrees.
+        let (sp1_vk_digest, pc_start) = (vk.hash_babybear(), vk.vk.pc_start);
+        let mut deferred_digest = initial_deferred_digest;
+        let mut roots = Vec::new();
+        let mut rest = batches.as_slice();
+        for size in group_sizes(batches.len(), max_leaves) {
+            let (group, tail) = rest.split_at(size);
+            rest = tail;
+            let (inputs, next_digest) = self.deferred_input_stream(
+                sp1_vk_digest,
+                pc_start,
+                group.to_vec(),
+                deferred_digest,
+            )?;
+            deferred_digest = next_digest;
+            let (vk, proof) = self.reduce_tree_with_strategy(
+                inputs.map(|input| (SP1CircuitWitness::Deferred(input), false)),
+                size,
+                false,
+                opts.clone(),
+                &BalancedReduction,
+            )?;
+            roots.push(SP1ReduceProof { vk, proof });
+        }
+
+        // Join the roots of the subtrees, in subtrees of the same size, until one is left.
+        while roots.len() > 1 {
+            let num_roots = roots.len();
+            let mut roots_left = roots.into_iter();
+            roots = Vec::new();
+            for size in group_sizes(num_roots, max_leaves) {
+                let group = roots_left.by_ref().take(size).collect::<Vec<_>>();
+                if size == 1 {
+                    roots.extend(group);
+                    continue;
+                }
+                let (vk, proof) = self.reduce_tree_with_strategy(
+                    compress_inputs(group),
+                    size,
+                    false,
+                    opts.clone(),
+                    &BalancedReduction,
+                )?;
+                roots.push(SP1ReduceProof { vk, proof });
+            }
+        }
+        Ok(roots.pop().unwrap())
+    }
+}
+
+/// The sizes of the fewest groups of at most `max` of `len` items, differing by at most one.
+fn group_sizes(len: usize, max: usize) -> impl Iterator<Item = usize> {
+    let num_groups = len.div_ceil(max);
+    (0..num_groups).map(move |i| len / num_groups + usize::from(i < len % num_groups))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_sizes() {
+        assert_eq!(group_sizes(10, 4).collect::<Vec<_>>(), vec![4, 3, 3]);
+        assert_eq!(group_sizes(8, 4).collect::<Vec<_>>(), vec![4, 4]);
+        assert_eq!(group_sizes(3, 4).collect::<Vec<_>>(), vec![3]);
+        for len in 1..50 {
+            let sizes = group_sizes(len, 7).collect::<Vec<_>>();
+            assert_eq!(sizes.iter().sum::<usize>(), len);
+            assert_eq!(sizes.len(), len.div_ceil(7));
+            assert!(sizes.iter().all(|&size| size <= 7 && size + 1 >= sizes[0]));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Uploading device proving keys in the background.
+//!
+//! Copying a proving key to the device can take seconds on GPU provers, and [`SP1Prover::setup`]
+//! blocks on it. [`SP1Prover::setup_nonblocking`] starts the upload on a background thread and
+//! returns a [`PendingDeviceKey`] instead, so that callers can prepare their inputs meanwhile.
+//! [`SP1Prover::prove_core_with_pending_key`] starts executing the program right away and only
+//! waits for the key once the first shard is ready to be proven.
+
+use std::{
+    panic,
+    sync::{Mutex, OnceLock},
+    thread::{self, JoinHandle},
+};
+
+use sp1_core_executor::Program;
+use sp1_stark::MachineProver;
+
+use crate::{
+    components::SP1ProverComponents, DeviceProvingKey, SP1Prover, SP1ProvingKey, SP1VerifyingKey,
+};
+
+/// A device proving key that is being uploaded on a background thread.
+pub struct PendingDeviceKey<C: SP1ProverComponents> {
+    key: OnceLock<DeviceProvingKey<C>>,
+    upload: Mutex<Option<JoinHandle<DeviceProvingKey<C>>>>,
+}
+
+impl<C: SP1ProverComponents> PendingDeviceKey<C> {
+    /// A key that is already on the device.
+    pub fn ready(key: DeviceProvingKey<C>) -> Self {
+        Self { key: OnceLock::from(key), upload: Mutex::new(None) }
+    }
+
+    /// Whether the upload has finished, in which case [`PendingDeviceKey::wait`] does not block.
+    pub fn is_ready(&self) -> bool {
+        self.key.get().is_some() ||
+            self.upload.lock().unwrap().as_ref().is_some_and(JoinHandle::is_finished)
+    }
+
+    /// Wait for the upload to finish and return the key.
+    ///
+    /// Panics if the upload panicked.
+    pub fn wait(&self) -> &DeviceProvingKey<C> {
+        self.key.get_or_init(|| {
+            let upload =
+                self.upload.lock().unwrap().take().expect("the upload is only joined once");
+            upload.join().unwrap_or_else(|e| panic::resume_unwind(e))
+        })
+    }
+
+    /// Wait for the upload to finish and take the key.
+    pub fn into_inner(self) -> DeviceProvingKey<C> {
+        self.wait();
+        self.key.into_inner().unwrap()
+    }
+}
+
+impl<C: SP1ProverComponents + 'static> SP1Prover<C> {
+    /// Like [`SP1Prover::setup`], but copies the proving key to the device on a background thread.
+    pub fn setup_nonblocking(
+        &self,
+        elf: &[u8],
+    ) -> eyre::Result<(SP1ProvingKey, PendingDeviceKey<C>, Program, SP1VerifyingKey)> {
+        let program = self.get_program(elf)?;
+        let shape_config_digest = self.core_shape_config.as_ref().map(|config| config.digest());
+        let (pk, vk) = self.setup_host_keys(elf, &program, shape_config_digest);
+
+        let core = self.core.clone();
+        let host_pk = pk.pk.clone();
+        let upload = thread::Builder::new()
+            .name("sp1-pk-upload".to_string())
+            .spawn(move || core.core_prover.pk_to_device(&host_pk))?;
+        let pk_d = PendingDeviceKey { key: OnceLock::new(), upload: Mutex::new(Some(upload)) };
+
+        Ok((pk, pk_d, program, vk))
+    }
+}
@@ -0,0 +1,230 @@
+//! Dry runs of the recursion stages.
+//!
+//! A dry run compiles the program of a stage, executes it on dummy witness values of the right
+//! shape, and generates its traces, without committing to them or proving anything. This is much
+//! faster than proving, so it is a quick way to check that a new shape or new options give a
+//! program that executes and fits the expected trace heights.
+//!
+//! The dummy witness values do not satisfy the assertions of the programs, so the failed
+//! assertions are counted rather than stopping the execution, see
+//! [`DryRunReport::failed_assertions`].
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sp1_recursion_circuit::{
+    machine::{
+        SP1CompressShape, SP1CompressWithVKeyWitnessValues, SP1CompressWithVkeyShape,
+        SP1DeferredWitnessValues, SP1RecursionWitnessValues,
+    },
+    witness::Witnessable,
+};
+use sp1_recursion_compiler::config::InnerConfig;
+use sp1_recursion_core::{
+    air::Block, runtime::ExecutionRecord, RecursionProgram, Runtime as RecursionRuntime,
+};
+use sp1_stark::{shape::OrderedShape, Challenge, MachineProver, MachineRecord, Val};
+
+use crate::{
+    components::SP1ProverComponents, shapes::SP1CompressProgramShape, InnerSC, SP1Prover,
+    SP1RecursionProverError, ShrinkAir,
+};
+
+/// The outcome of a dry run of a recursion stage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DryRunReport {
+    /// The height of each trace, after padding.
+    pub heights: BTreeMap<String, usize>,
+    /// The number of events of each kind recorded by the runtime.
+    pub num_events: BTreeMap<String, usize>,
+    /// The number of assertions failed by the dummy witness values.
+    pub failed_assertions: usize,
+    /// The time to compile the program, or to fetch it from the cache.
+    pub compile_time: Duration,
+    /// The time to execute the program.
+    pub execution_time: Duration,
+    /// The time to generate the traces.
+    pub trace_time: Duration,
+}
+
+impl DryRunReport {
+    /// The total time of the dry run.
+    #[must_use]
+    pub fn total_time(&self) -> Duration {
+        self.compile_time + self.execution_time + self.trace_time
+    }
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "compile: {:?}, execute: {:?}, traces: {:?}",
+            self.compile_time, self.execution_time, self.trace_time
+        )?;
+        write!(f, "heights:")?;
+        for (name, height) in self.heights.iter() {
+            write!(f, "\n  {name}: {height}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Dry run the lift, join, deferred or shrink program with the given shape.
+    pub fn dry_run_compress(
+        &self,
+        shape: SP1CompressProgramShape,
+    ) -> Result<DryRunReport, SP1RecursionProverError> {
+        let start = Instant::now();
+        let mut witness_stream = Vec::new();
+        let is_shrink = matches!(shape, SP1CompressProgramShape::Shrink(_));
+        let program = match shape {
+            SP1CompressProgramShape::Recursion(shape) => {
+                let input = SP1RecursionWitnessValues::dummy(self.core_prover.machine(), &shape);
+                Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+                self.recursion_program(&input)
+            }
+            SP1CompressProgramShape::Deferred(shape) => {
+                let input = SP1DeferredWitnessValues::dummy(self.compress_prover.machine(), &shape);
+                Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+                self.deferred_program(&input)
+            }
+            SP1CompressProgramShape::Compress(shape) => {
+                let input =
+                    SP1CompressWithVKeyWitnessValues::dummy(self.compress_prover.machine(), &shape);
+                Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+                self.compress_program(&input)?
+            }
+            SP1CompressProgramShape::Shrink(shape) => {
+                let input =
+                    SP1CompressWithVKeyWitnessValues::dummy(self.compress_prover.machine(), &shape);
+                Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+                self.shrink_program(ShrinkAir::<BabyBear>::shrink_shape(), &input)
+            }
+        };
+        let compile_time = start.elapsed();
+
+        let (record, failed_assertions, execution_time) =
+            self.dry_run_execute(program, witness_stream)?;
+        let report = if is_shrink {
+            dry_run_traces(
+                &self.shrink_prover,
+                record,
+                failed_assertions,
+                compile_time,
+                execution_time,
+            )
+        } else {
+            dry_run_traces(
+                &self.compress_prover,
+                record,
+                failed_assertions,
+                compile_time,
+                execution_time,
+            )
+        };
+        tracing::info!("dry run:\n{}", report);
+        Ok(report)
+    }
+
+    /// Dry run the wrap program.
+    pub fn dry_run_wrap(&self) -> Result<DryRunReport, SP1RecursionProverError> {
+        let start = Instant::now();
+        let shrink_shape: OrderedShape = ShrinkAir::<BabyBear>::shrink_shape().into();
+        let shape = SP1CompressWithVkeyShape {
+            compress_shape: SP1CompressShape::from(vec![shrink_shape]),
+            merkle_tree_height: self.recursion_vk_tree.height,
+            merkle_tree_config: self.recursion_vk_tree.config,
+        };
+        let input = SP1CompressWithVKeyWitnessValues::dummy(self.shrink_prover.machine(), &shape);
+        let mut witness_stream = Vec::new();
+        Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+        let program = self.wrap_program()?;
+        let compile_time = start.elapsed();
+
+        let (record, failed_assertions, execution_time) =
+            self.dry_run_execute(program, witness_stream)?;
+        let report = dry_run_traces(
+            &self.wrap_prover,
+            record,
+            failed_assertions,
+            compile_time,
+            execution_time,
+        );
+        tracing::info!("wrap dry run:\n{}", report);
+        Ok(report)
+    }
+
+    /// Execute `program` on `witness_stream`, returning the record, the number of failed
+    /// assertions and the execution time.
+    fn dry_run_execute(
+        &self,
+        program: Arc<RecursionProgram<BabyBear>>,
+        witness_stream: Vec<Block<BabyBear>>,
+    ) -> Result<(ExecutionRecord<BabyBear>, usize, Duration), SP1RecursionProverError> {
+        let start = Instant::now();
+        let mut runtime = RecursionRuntime::<Val<InnerSC>, Challenge<InnerSC>, _>::new(
+            program,
+            self.compress_prover.config().perm.clone(),
+        );
+        runtime.witness_stream = witness_stream.into();
+        runtime.count_failed_assertions = true;
+        runtime.run().map_err(|e| SP1RecursionProverError::RuntimeError(e.to_string()))?;
+        runtime.print_stats();
+        Ok((runtime.record, runtime.nb_failed_assertions, start.elapsed()))
+    }
+}
+
+/// Generate the traces of `record` with `prover` and collect their heights.
+fn dry_run_traces<SC, A, P>(
+    prover: &P,
+    record: ExecutionRecord<BabyBear>,
+    failed_assertions: usize,
+    compile_time: Duration,
+    execution_time: Duration,
+) -> DryRunReport
+where
+    SC: sp1_stark::StarkGenericConfig,
+    A: sp1_stark::air::MachineAir<Val<SC>, Record = ExecutionRecord<BabyBear>>,
+    P: MachineProver<SC, A>,
+{
+    let start = Instant::now();
+    let num_events = record.stats().into_iter().collect();
+    // The recursion chips have no dependencies, so the traces can be generated right away.
+    let heights = prover
+        .generate_traces(&record)
+        .into_iter()
+        .map(|(name, trace)| (name, p3_matrix::Matrix::height(&trace)))
+        .collect();
+    DryRunReport {
+        heights,
+        num_events,
+        failed_assertions,
+        compile_time,
+        execution_time,
+        trace_time: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::unfixed_prover;
+
+    #[test]
+    fn test_dry_run_wrap() {
+        let report = unfixed_prover().dry_run_wrap().unwrap();
+        assert!(!report.heights.is_empty());
+        assert!(report.heights.values().all(|height| height.is_power_of_two() || *height == 0));
+        assert!(report.num_events.values().any(|&num_events| num_events > 0));
+        // The dummy proof does not verify.
+        assert!(report.failed_assertions > 0);
+        assert!(report.to_string().starts_with("compile: "));
+    }
+}
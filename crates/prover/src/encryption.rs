@@ -0,0 +1,202 @@
+//! Encryption of the artifacts the prover writes to disk.
+//!
+//! Provers handling private inputs must not leave them in plaintext on disk. An [`ArtifactCipher`]
+//! seals data with AES-256-GCM under a key supplied by the caller, either directly or through a
+//! callback to a key management service. Once set with [`SP1Prover::with_artifact_cipher`], it is
+//! applied to the checkpoints spilled while proving, the core journal, the compress states and the
+//! program artifacts, and it can be attached to a [`crate::wrap_cache::WrapProofCache`] and a
+//! [`crate::backfill::DiskProofStore`] and passed to the `save_with` and `load_with` methods of
+//! exported proofs and bundles.
+//!
+//! Sealed data starts with [`SEALED_ARTIFACT_MAGIC`], followed by a random nonce and the
+//! ciphertext.
+
+use std::{env, fmt, fs, io, path::Path};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sp1_core_executor::spill::SpillCipher;
+
+use crate::{components::SP1ProverComponents, SP1Prover};
+
+/// The bytes sealed artifacts start with.
+pub const SEALED_ARTIFACT_MAGIC: &[u8; 4] = b"SP1E";
+
+const NONCE_LEN: usize = 12;
+
+/// An AES-256-GCM cipher for artifacts written to disk.
+#[derive(Clone)]
+pub struct ArtifactCipher {
+    cipher: Aes256Gcm,
+}
+
+impl ArtifactCipher {
+    #[must_use]
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)) }
+    }
+
+    /// A cipher with the key returned by `provider`, such as a call to a key management service.
+    pub fn from_key_provider(provider: impl FnOnce() -> io::Result<[u8; 32]>) -> io::Result<Self> {
+        Ok(Self::new(provider()?))
+    }
+
+    /// A cipher with the hex encoded key in the `SP1_ARTIFACT_KEY` environment variable, if it is
+    /// set.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let Ok(key) = env::var("SP1_ARTIFACT_KEY") else {
+            return Ok(None);
+        };
+        let key = hex::decode(key.trim_start_matches("0x"))
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "SP1_ARTIFACT_KEY must be 32 hex encoded bytes",
+                )
+            })?;
+        Ok(Some(Self::new(key)))
+    }
+
+    /// Encrypt and authenticate `plaintext`.
+    pub fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let payload = Payload { msg: plaintext, aad: SEALED_ARTIFACT_MAGIC };
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| io::Error::other("failed to encrypt artifact"))?;
+
+        let mut sealed =
+            Vec::with_capacity(SEALED_ARTIFACT_MAGIC.len() + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(SEALED_ARTIFACT_MAGIC);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt data sealed with [`ArtifactCipher::seal`] under the same key.
+    pub fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let invalid = |reason| io::Error::new(io::ErrorKind::InvalidData, reason);
+        let body = sealed
+            .strip_prefix(SEALED_ARTIFACT_MAGIC.as_slice())
+            .ok_or_else(|| invalid("artifact is not encrypted"))?;
+        if body.len() < NONCE_LEN {
+            return Err(invalid("encrypted artifact is truncated"));
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        let payload = Payload { msg: ciphertext, aad: SEALED_ARTIFACT_MAGIC };
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| invalid("failed to decrypt artifact, the key may be wrong"))
+    }
+}
+
+impl fmt::Debug for ArtifactCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArtifactCipher").finish_non_exhaustive()
+    }
+}
+
+impl SpillCipher for ArtifactCipher {
+    fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        ArtifactCipher::seal(self, plaintext)
+    }
+
+    fn open(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        ArtifactCipher::open(self, ciphertext)
+    }
+}
+
+/// Serialize `value` with bincode and write it to `path`, sealed with `cipher` if one is given.
+pub fn save_artifact<T: Serialize>(
+    value: &T,
+    path: impl AsRef<Path>,
+    cipher: Option<&ArtifactCipher>,
+) -> io::Result<()> {
+    let bytes = bincode::serialize(value).map_err(io::Error::other)?;
+    match cipher {
+        Some(cipher) => fs::write(path, cipher.seal(&bytes)?),
+        None => fs::write(path, bytes),
+    }
+}
+
+/// Read a value written by [`save_artifact`] with the same cipher.
+pub fn load_artifact<T: DeserializeOwned>(
+    path: impl AsRef<Path>,
+    cipher: Option<&ArtifactCipher>,
+) -> io::Result<T> {
+    let bytes = fs::read(path)?;
+    let bytes = match cipher {
+        Some(cipher) => cipher.open(&bytes)?,
+        None if bytes.starts_with(SEALED_ARTIFACT_MAGIC) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "artifact is encrypted"));
+        }
+        None => bytes,
+    };
+    bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Encrypt the checkpoints spilled to disk by this handle with `cipher`.
+    ///
+    /// By default, the cipher is read from `SP1_ARTIFACT_KEY`, see [`ArtifactCipher::from_env`].
+    #[must_use]
+    pub fn with_artifact_cipher(mut self, cipher: ArtifactCipher) -> Self {
+        self.artifact_cipher = Some(std::sync::Arc::new(cipher));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let cipher = ArtifactCipher::new([7; 32]);
+        let sealed = cipher.seal(b"private input").unwrap();
+        assert!(sealed.starts_with(SEALED_ARTIFACT_MAGIC));
+        assert!(!sealed.windows(13).any(|window| window == b"private input"));
+        assert_eq!(cipher.open(&sealed).unwrap(), b"private input");
+
+        // Every seal uses a fresh nonce.
+        assert_ne!(cipher.seal(b"private input").unwrap(), sealed);
+    }
+
+    #[test]
+    fn test_open_rejects_tampering() {
+        let cipher = ArtifactCipher::new([7; 32]);
+        let sealed = cipher.seal(b"private input").unwrap();
+        for i in SEALED_ARTIFACT_MAGIC.len()..sealed.len() {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 1;
+            assert!(cipher.open(&tampered).is_err(), "byte {i} was not authenticated");
+        }
+        assert!(cipher.open(&sealed[..sealed.len() - 1]).is_err());
+        assert!(cipher.open(&sealed[..SEALED_ARTIFACT_MAGIC.len() + 4]).is_err());
+        assert!(ArtifactCipher::new([8; 32]).open(&sealed).is_err());
+        assert!(cipher.open(b"private input").is_err());
+    }
+
+    #[test]
+    fn test_save_artifact_round_trip() {
+        let path = std::env::temp_dir().join(format!("sp1-artifact-{}.bin", std::process::id()));
+        let cipher = ArtifactCipher::new([7; 32]);
+        let value = (42u64, "private input".to_string());
+
+        save_artifact(&value, &path, Some(&cipher)).unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(SEALED_ARTIFACT_MAGIC));
+        assert_eq!(load_artifact::<(u64, String)>(&path, Some(&cipher)).unwrap(), value);
+        assert!(load_artifact::<(u64, String)>(&path, None).is_err());
+
+        save_artifact(&value, &path, None).unwrap();
+        assert_eq!(load_artifact::<(u64, String)>(&path, None).unwrap(), value);
+        assert!(load_artifact::<(u64, String)>(&path, Some(&cipher)).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
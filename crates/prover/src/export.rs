@@ -0,0 +1,268 @@
+//! Exporting compressed proofs to external shrink and wrap providers.
+//!
+//! Shrinking and wrapping need different hardware than the rest of the pipeline, so they can be
+//! handed off to a third party. A [`ShrinkInputBundle`] holds everything the shrink program reads:
+//! the compressed proof, its recursion verifying key, the Merkle proof that this key is allowed,
+//! and the verifying key of the program, so that the result can be checked when it comes back.
+//!
+//! The provider proves the bundle with [`SP1Prover::shrink_bundle`] and [`SP1Prover::wrap_bn254`],
+//! and the owner of the bundle checks the returned proof with [`SP1Prover::import_wrapped_proof`].
+
+use std::{
+    borrow::Borrow,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sp1_recursion_circuit::machine::{
+    RootPublicValues, SP1CompressWithVKeyWitnessValues, SP1CompressWitnessValues,
+    SP1MerkleProofWitnessValues,
+};
+use sp1_recursion_core::air::RecursionPublicValues;
+use sp1_stark::{MachineProver, MachineVerificationError, SP1ProverOpts, DIGEST_SIZE};
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents,
+    encryption::{load_artifact, save_artifact, ArtifactCipher},
+    CoreSC, InnerSC, OuterSC, SP1Prover, SP1RecursionProverError, SP1ReduceProof, SP1VerifyingKey,
+    VkNotAllowedError, SP1_CIRCUIT_VERSION,
+};
+
+/// The version of the [`ShrinkInputBundle`] format.
+///
+/// This should be bumped whenever the layout of the bundle changes.
+pub const SHRINK_INPUT_BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum ShrinkBundleError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("unsupported bundle version {0}, expected {SHRINK_INPUT_BUNDLE_VERSION}")]
+    Version(u32),
+    #[error("bundle was made for circuit version {0}, expected {SP1_CIRCUIT_VERSION}")]
+    CircuitVersion(String),
+    #[error("bundle was made with a different set of allowed verifying keys")]
+    VkRootMismatch,
+    #[error(transparent)]
+    VkNotAllowed(#[from] VkNotAllowedError),
+    #[error("invalid compressed proof: {0}")]
+    InvalidCompressedProof(MachineVerificationError<CoreSC>),
+    #[error("invalid wrapped proof: {0}")]
+    InvalidWrappedProof(MachineVerificationError<OuterSC>),
+    #[error("the wrapped proof does not commit to the public values of the compressed proof")]
+    CommittedValuesMismatch,
+    #[error(transparent)]
+    Recursion(#[from] SP1RecursionProverError),
+}
+
+/// The input of the shrink stage, exported after compress.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShrinkInputBundle {
+    /// The version of the bundle format, see [`SHRINK_INPUT_BUNDLE_VERSION`].
+    pub version: u32,
+    /// The circuit version of the prover that made the compressed proof.
+    pub circuit_version: String,
+    /// The verifying key of the program the proof is for.
+    pub vk: SP1VerifyingKey,
+    /// The compressed proof and its recursion verifying key.
+    pub compressed: SP1ReduceProof<InnerSC>,
+    /// The Merkle proof that the recursion verifying key is allowed.
+    pub merkle: SP1MerkleProofWitnessValues<InnerSC>,
+}
+
+impl ShrinkInputBundle {
+    /// The root of the allowed recursion verifying keys the bundle was made with.
+    #[must_use]
+    pub fn vk_root(&self) -> [BabyBear; DIGEST_SIZE] {
+        self.merkle.root
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ShrinkBundleError> {
+        Ok(bincode::serialize_into(BufWriter::new(File::create(path)?), self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ShrinkBundleError> {
+        Ok(bincode::deserialize_from(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Save the bundle, encrypted with `cipher` if one is given.
+    pub fn save_with(
+        &self,
+        path: impl AsRef<Path>,
+        cipher: Option<&ArtifactCipher>,
+    ) -> Result<(), ShrinkBundleError> {
+        Ok(save_artifact(self, path, cipher)?)
+    }
+
+    /// Load a bundle written by [`ShrinkInputBundle::save_with`] with the same cipher.
+    pub fn load_with(
+        path: impl AsRef<Path>,
+        cipher: Option<&ArtifactCipher>,
+    ) -> Result<Self, ShrinkBundleError> {
+        Ok(load_artifact(path, cipher)?)
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Package a compressed proof for an external shrink and wrap provider.
+    ///
+    /// The proof is verified first, so that invalid proofs are not sent out.
+    pub fn export_shrink_input(
+        &self,
+        vk: &SP1VerifyingKey,
+        compressed: SP1ReduceProof<InnerSC>,
+    ) -> Result<ShrinkInputBundle, ShrinkBundleError> {
+        self.verify_compressed(&compressed, vk)
+            .map_err(ShrinkBundleError::InvalidCompressedProof)?;
+
+        let input = SP1CompressWitnessValues {
+            vks_and_proofs: vec![(compressed.vk.clone(), compressed.proof.clone())],
+            is_complete: true,
+        };
+        let merkle = self.make_merkle_proofs(input)?.merkle_val;
+
+        Ok(ShrinkInputBundle {
+            version: SHRINK_INPUT_BUNDLE_VERSION,
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            vk: vk.clone(),
+            compressed,
+            merkle,
+        })
+    }
+
+    /// Check that a bundle can be proven and verified by this prover.
+    pub fn validate_shrink_input(
+        &self,
+        bundle: &ShrinkInputBundle,
+    ) -> Result<(), ShrinkBundleError> {
+        if bundle.version != SHRINK_INPUT_BUNDLE_VERSION {
+            return Err(ShrinkBundleError::Version(bundle.version));
+        }
+        if bundle.circuit_version != SP1_CIRCUIT_VERSION {
+            return Err(ShrinkBundleError::CircuitVersion(bundle.circuit_version.clone()));
+        }
+        if !self.vk_root_status(&bundle.vk_root()).is_accepted() {
+            return Err(ShrinkBundleError::VkRootMismatch);
+        }
+        self.verify_compressed(&bundle.compressed, &bundle.vk)
+            .map_err(ShrinkBundleError::InvalidCompressedProof)
+    }
+
+    /// Shrink the compressed proof of a bundle, using the Merkle proofs it carries.
+    ///
+    /// This is the entry point for external providers. The result is wrapped with
+    /// [`SP1Prover::wrap_bn254`] as usual.
+    pub fn shrink_bundle(
+        &self,
+        bundle: &ShrinkInputBundle,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, ShrinkBundleError> {
+        self.validate_shrink_input(bundle)?;
+        let input_with_merkle = SP1CompressWithVKeyWitnessValues {
+            compress_val: SP1CompressWitnessValues {
+                vks_and_proofs: vec![(
+                    bundle.compressed.vk.clone(),
+                    bundle.compressed.proof.clone(),
+                )],
+                is_complete: true,
+            },
+            merkle_val: bundle.merkle.clone(),
+        };
+        Ok(self.shrink_with_merkle_proofs(&input_with_merkle, opts)?)
+    }
+
+    /// Check a wrapped proof returned by an external provider for `bundle`.
+    ///
+    /// The proof must verify against this prover's wrap verifying key and commit to the same
+    /// program and public values as the compressed proof of the bundle.
+    pub fn import_wrapped_proof(
+        &self,
+        bundle: &ShrinkInputBundle,
+        proof: SP1ReduceProof<OuterSC>,
+    ) -> Result<SP1ReduceProof<OuterSC>, ShrinkBundleError> {
+        if self.wrap_vk.get().is_none() {
+            let program = self.wrap_program()?;
+            self.wrap_vk.get_or_init(|| {
+                tracing::debug_span!("setup wrap").in_scope(|| {
+                    let (_, wrap_vk) = self.wrap_prover.setup(&program);
+                    wrap_vk
+                })
+            });
+        }
+        self.verify_wrap_bn254(&proof, &bundle.vk)
+            .map_err(ShrinkBundleError::InvalidWrappedProof)?;
+
+        let compressed: &RecursionPublicValues<BabyBear> =
+            bundle.compressed.proof.public_values.as_slice().borrow();
+        let wrapped: &RootPublicValues<BabyBear> = proof.proof.public_values.as_slice().borrow();
+        if *wrapped.committed_value_digest() != compressed.committed_value_digest {
+            return Err(ShrinkBundleError::CommittedValuesMismatch);
+        }
+
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p3_field::AbstractField;
+    use sp1_stark::{air::MachineAir, shape::OrderedShape};
+
+    use super::*;
+    use crate::tests::unfixed_prover;
+
+    #[test]
+    fn test_validate_shrink_input() {
+        let prover = unfixed_prover();
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+        let bundle = ShrinkInputBundle {
+            version: SHRINK_INPUT_BUNDLE_VERSION,
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            vk: SP1VerifyingKey {
+                vk: vk.clone(),
+                shape_config_digest: None,
+                artifacts: None,
+                domain_tag: None,
+            },
+            compressed: SP1ReduceProof { vk, proof },
+            merkle: SP1MerkleProofWitnessValues {
+                vk_merkle_proofs: vec![],
+                values: vec![],
+                root: prover.recursion_vk_root,
+            },
+        };
+
+        // The bundle survives a round trip through a file.
+        let path =
+            std::env::temp_dir().join(format!("sp1-shrink-bundle-{}.bin", std::process::id()));
+        bundle.save(&path).unwrap();
+        let bundle = ShrinkInputBundle::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(bundle.vk_root(), prover.recursion_vk_root);
+
+        let check = |change: fn(&mut ShrinkInputBundle)| {
+            let mut bundle = bundle.clone();
+            change(&mut bundle);
+            prover.validate_shrink_input(&bundle)
+        };
+        assert!(matches!(check(|bundle| bundle.version += 1), Err(ShrinkBundleError::Version(2))));
+        assert!(matches!(
+            check(|bundle| bundle.circuit_version = "v0.0.0".to_string()),
+            Err(ShrinkBundleError::CircuitVersion(_))
+        ));
+        assert!(matches!(
+            check(|bundle| bundle.merkle.root = [BabyBear::zero(); DIGEST_SIZE]),
+            Err(ShrinkBundleError::VkRootMismatch)
+        ));
+        // The dummy proof itself does not verify.
+        assert!(matches!(check(|_| {}), Err(ShrinkBundleError::InvalidCompressedProof(_))));
+    }
+}
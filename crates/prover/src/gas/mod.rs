@@ -3,11 +3,16 @@ mod model;
 pub use model::*;
 use thiserror::Error;
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
 
 use enum_map::EnumMap;
 use hashbrown::HashMap;
 use p3_field::PrimeField32;
+use serde::{Deserialize, Serialize};
 
 use sp1_core_executor::{estimator::RecordEstimator, RiscvAirId};
 use sp1_core_machine::shape::{CoreShapeConfig, CoreShapeError, Shapeable, ShardKind};
@@ -27,6 +32,9 @@ pub const GAS_OPTS: SP1CoreOpts = SP1CoreOpts {
     trace_gen_workers: 4,
     checkpoints_channel_capacity: 128,
     records_and_traces_channel_capacity: 4,
+    memory_budget: None,
+    min_phase_shard_size: None,
+    trace_height_histograms: false,
 };
 
 #[derive(Error, Debug)]
@@ -52,6 +60,95 @@ pub fn final_transform(raw_gas: f64) -> Result<u64, GasError> {
     }
 }
 
+/// The gas of an execution, split by shard and by chip.
+///
+/// The gas of a shard is predicted from the heights of its chips. The contribution of a chip to a
+/// shard is the gas the chip adds to a shard without it, see [`predict_breakdown`], so the
+/// contributions of the chips, the base gas of the shards and the overhead sum to the gas, up to
+/// rounding.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GasBreakdown {
+    /// The gas of the execution, as in
+    /// [`ExecutionReport::gas`](sp1_core_executor::ExecutionReport).
+    pub gas: u64,
+    /// The gas of the execution not attributed to any shard.
+    pub overhead: f64,
+    /// The gas of each estimated shard: the core shards, then the global memory shards, then the
+    /// precompile shards.
+    pub shards: Vec<ShardGas>,
+}
+
+/// The gas of an estimated shard, split by chip.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShardGas {
+    /// The gas of a shard without any chip.
+    pub base: f64,
+    /// The contribution of each chip of the shard.
+    pub chips: BTreeMap<RiscvAirId, f64>,
+}
+
+impl ShardGas {
+    /// The gas of the shard.
+    #[must_use]
+    pub fn total(&self) -> f64 {
+        self.base + self.chips.values().sum::<f64>()
+    }
+}
+
+impl GasBreakdown {
+    /// The breakdown of the gas of shards with the given shapes, whose heights are log2 heights.
+    pub fn from_shapes(
+        shapes: impl IntoIterator<Item = Shape<RiscvAirId>>,
+    ) -> Result<Self, GasError> {
+        let mut raw_gas = 0.0;
+        let mut shards = Vec::new();
+        for shape in shapes {
+            let heights = EnumMap::from_iter(shape);
+            raw_gas += predict(heights.as_array());
+            let (contributions, base) = predict_breakdown(heights.as_array());
+            let chips = heights
+                .iter()
+                .zip(contributions)
+                .filter(|((_, &height), _)| height > 0)
+                .map(|((id, _), contribution)| (id, APPROX_CYCLES_PER_RAW_GAS * contribution))
+                .collect();
+            shards.push(ShardGas { base: APPROX_CYCLES_PER_RAW_GAS * base, chips });
+        }
+        Ok(Self {
+            gas: final_transform(raw_gas)?,
+            overhead: APPROX_CYCLES_PER_RAW_GAS * OVERHEAD,
+            shards,
+        })
+    }
+
+    /// The contribution of each chip over all the shards, largest first.
+    #[must_use]
+    pub fn by_chip(&self) -> Vec<(RiscvAirId, f64)> {
+        let mut chips = BTreeMap::<RiscvAirId, f64>::new();
+        for shard in &self.shards {
+            for (&id, &contribution) in &shard.chips {
+                *chips.entry(id).or_default() += contribution;
+            }
+        }
+        let mut chips = chips.into_iter().collect::<Vec<_>>();
+        chips.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        chips
+    }
+}
+
+impl Display for GasBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "gas: {} over {} shards", self.gas, self.shards.len())?;
+        let percent = |gas: f64| 100.0 * gas / self.gas.max(1) as f64;
+        let base = self.overhead + self.shards.iter().map(|shard| shard.base).sum::<f64>();
+        writeln!(f, "  base: {:.0} ({:.1}%)", base, percent(base))?;
+        for (id, gas) in self.by_chip() {
+            writeln!(f, "  {}: {:.0} ({:.1}%)", id.as_str(), gas, percent(gas))?;
+        }
+        Ok(())
+    }
+}
+
 /// Calculates core, precompile, mem records. Does not implement packed or last shard logic.
 #[allow(clippy::manual_repeat_n)]
 pub fn estimated_records<'a>(
@@ -180,3 +277,35 @@ impl Shapeable for CoreShard<'_> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_breakdown() {
+        // A shard with the preprocessed chips and every core chip at the given height.
+        let shard = |height| {
+            let mut shape =
+                Shape::from_log2_heights(&[(RiscvAirId::Program, 10), (RiscvAirId::Byte, 16)]);
+            shape.extend(RiscvAirId::core().into_iter().map(|id| (id, height)));
+            shape
+        };
+        let breakdown = GasBreakdown::from_shapes([shard(21), shard(16)]).unwrap();
+        assert_eq!(breakdown.shards.len(), 2);
+        assert_eq!(breakdown.shards[0].chips.len(), RiscvAirId::core().len() + 2);
+        assert!(breakdown.shards[0].total() > breakdown.shards[1].total());
+
+        // The base gas, the overhead and the contributions of the chips sum to the gas.
+        let total = breakdown.overhead + breakdown.shards.iter().map(ShardGas::total).sum::<f64>();
+        assert!((total - breakdown.gas as f64).abs() <= 1.0);
+        let by_chip = breakdown.by_chip();
+        assert_eq!(by_chip.len(), RiscvAirId::core().len() + 2);
+        assert!(by_chip.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+
+        assert!(breakdown
+            .to_string()
+            .starts_with(&format!("gas: {} over 2 shards", breakdown.gas)));
+        assert_eq!(GasBreakdown::from_shapes([]).unwrap().shards, []);
+    }
+}
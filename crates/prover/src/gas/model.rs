@@ -15,6 +15,35 @@ pub fn predict(input: &[usize; INPUT_SIZE / 2]) -> f64 {
         PARAMS.intercept
 }
 
+/// Split [`predict`] into the contribution of each chip and the base cost of a shard.
+///
+/// The contribution of a chip is the difference its height makes to the prediction compared to a
+/// shard without the chip, so that absent chips contribute nothing. The base cost is the
+/// prediction for a shard without any chip, and the contributions and base cost sum to the
+/// prediction.
+pub fn predict_breakdown(input: &[usize; INPUT_SIZE / 2]) -> ([f64; INPUT_SIZE / 2], f64) {
+    let term = |feature: usize, x: f64| {
+        let term = ((x - PARAMS.mean[feature]) / PARAMS.std[feature]) * PARAMS.coefs[feature];
+        if term.is_finite() {
+            term
+        } else {
+            0.0
+        }
+    };
+    let mut contributions = [0.0; INPUT_SIZE / 2];
+    let mut base = PARAMS.intercept;
+    for (chip, &height) in input.iter().enumerate() {
+        // Each chip has a feature for its log2 height and one for its height.
+        let exp_feature = chip + INPUT_SIZE / 2;
+        let empty = term(chip, 0.0) + term(exp_feature, 1.0);
+        let real =
+            term(chip, height as f64) + term(exp_feature, 2f64.powi(height.try_into().unwrap()));
+        contributions[chip] = real - empty;
+        base += empty;
+    }
+    (contributions, base)
+}
+
 pub(crate) struct Params<const N: usize> {
     pub mean: [f64; N],
     pub std: [f64; N],
@@ -0,0 +1,172 @@
+//! Estimating the gas of a program from a prefix of its execution.
+//!
+//! Calculating the gas of a program executes it to the end with a record estimator, which is too
+//! slow for quick feedback in an editor or a CI job on long programs. [`SP1Prover::estimate_gas`]
+//! only executes the first cycles of the program, fits the shards of this sample to shapes like
+//! [`SP1Prover::execute_with_gas_breakdown`], and extrapolates the gas per cycle of the sample to
+//! the cycles of the whole execution with [`GasEstimate::gas`].
+
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::{ExecutionError, Executor, Program, RiscvAirId, SP1Context};
+use sp1_core_machine::io::SP1Stdin;
+use sp1_stark::SP1CoreOpts;
+use thiserror::Error;
+
+use crate::{components::SP1ProverComponents, gas, gas::GasBreakdown, SP1Prover};
+
+/// The gas of a sample of the execution of a program.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GasEstimate {
+    /// The number of cycles executed.
+    pub sampled_cycles: u64,
+    /// Whether the program halted within the sample, in which case the gas is exact.
+    pub halted: bool,
+    /// The gas of the shards of the sample: the core shards, then the global memory shards if the
+    /// program halted, then the precompile shards.
+    pub breakdown: GasBreakdown,
+    /// The number of core shards of the breakdown.
+    pub num_core_shards: usize,
+    /// The cycles of the core shards of the breakdown.
+    pub core_cycles: u64,
+}
+
+impl GasEstimate {
+    /// The estimated gas of an execution of `total_cycles` cycles, or the exact gas if the
+    /// program halted within the sample.
+    ///
+    /// The gas of the core shards and of the precompile shards of the sample is scaled by the
+    /// cycles they cover. The global memory shards depend on the memory touched rather than on the
+    /// cycles, so they are missing from the estimate of a program that did not halt.
+    #[must_use]
+    pub fn gas(&self, total_cycles: u64) -> u64 {
+        if self.halted {
+            return self.breakdown.gas;
+        }
+        // The sample is a lower bound on the cycles of the execution.
+        let total_cycles = total_cycles.max(self.sampled_cycles) as f64;
+        let (core, precompile) = self.breakdown.shards.split_at(self.num_core_shards);
+        let scale = |gas: f64, cycles: u64| match cycles {
+            0 => gas,
+            cycles => gas * total_cycles / cycles as f64,
+        };
+        let core_gas = scale(core.iter().map(|shard| shard.total()).sum(), self.core_cycles);
+        let precompile_gas =
+            scale(precompile.iter().map(|shard| shard.total()).sum(), self.sampled_cycles);
+        (self.breakdown.overhead + core_gas + precompile_gas).round() as u64
+    }
+}
+
+/// An error estimating the gas of a program.
+#[derive(Debug, Error)]
+pub enum GasEstimateError {
+    #[error("failed to load the program: {0}")]
+    Program(eyre::Report),
+    #[error(transparent)]
+    Execution(#[from] ExecutionError),
+    #[error("failed to calculate the gas of the sample: {0}")]
+    Gas(Box<dyn Error>),
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Estimate the gas of a program from its first `sample_cycles` cycles.
+    ///
+    /// The sample is executed with the gas options, so it should span a few shards of
+    /// [`gas::GAS_OPTS`] for the gas per cycle to be representative. If the sample ends within
+    /// the first shard, that partial shard is used instead, overestimating the gas of the shards
+    /// that follow it.
+    #[tracing::instrument(name = "estimate_gas", level = "info", skip_all)]
+    pub fn estimate_gas<'a>(
+        &'a self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        sample_cycles: u64,
+        mut context: SP1Context<'a>,
+    ) -> Result<GasEstimate, GasEstimateError> {
+        context.subproof_verifier = Some(self);
+        context.max_cycles = Some(sample_cycles);
+
+        let opts = gas::GAS_OPTS;
+        let program = self.get_program(elf).map_err(GasEstimateError::Program)?;
+        let preprocessed_shape = program.preprocessed_shape.clone().unwrap();
+        let mut runtime = self.estimating_executor(program, stdin, opts, context);
+        let halted = match runtime.run_fast() {
+            Ok(_) => true,
+            Err(ExecutionError::ExceededCycleLimit(_)) => false,
+            Err(e) => return Err(e.into()),
+        };
+        if !halted && runtime.record_estimator.as_ref().unwrap().core_records.is_empty() {
+            runtime.bump_record();
+        }
+
+        let estimator = runtime.record_estimator.as_ref().unwrap();
+        let breakdown = self
+            .gas_breakdown(&preprocessed_shape, opts.split_opts, estimator)
+            .map_err(GasEstimateError::Gas)?;
+        let estimate = GasEstimate {
+            sampled_cycles: runtime.state.global_clk,
+            halted,
+            breakdown,
+            num_core_shards: estimator.core_records.len(),
+            core_cycles: estimator.core_records.iter().map(|record| record[RiscvAirId::Cpu]).sum(),
+        };
+        tracing::info!(
+            "sampled {} cycles, halted: {}, gas of the sample: {}",
+            estimate.sampled_cycles,
+            estimate.halted,
+            estimate.breakdown.gas
+        );
+        Ok(estimate)
+    }
+
+    /// An executor of `program` on `stdin` that estimates the records of its shards.
+    ///
+    /// The shards are cut at the maximal core shapes of the prover, if it fixes shapes, so the
+    /// estimated records match the shards that `opts` would prove.
+    pub(crate) fn estimating_executor<'a>(
+        &'a self,
+        program: Program,
+        stdin: &SP1Stdin,
+        opts: SP1CoreOpts,
+        context: SP1Context<'a>,
+    ) -> Executor<'a> {
+        let mut runtime = Executor::with_context(program, opts, context);
+        runtime.maximal_shapes = self.core_shape_config.as_ref().map(|config| {
+            config.maximal_core_shapes(opts.shard_size.ilog2() as usize).into_iter().collect()
+        });
+        runtime.record_estimator = Some(Box::default());
+        runtime.write_vecs(&stdin.buffer);
+        runtime.write_input_chunks(stdin.input_chunks.clone());
+        for (proof, vkey) in stdin.proofs.iter() {
+            runtime.write_proof(proof.clone(), vkey.clone());
+        }
+        runtime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::ShardGas;
+
+    #[test]
+    fn test_extrapolate_gas() {
+        let shard = |base| ShardGas { base, chips: Default::default() };
+        let estimate = GasEstimate {
+            sampled_cycles: 100,
+            halted: false,
+            breakdown: GasBreakdown {
+                gas: 31,
+                overhead: 1.0,
+                shards: vec![shard(10.0), shard(10.0), shard(10.0)],
+            },
+            num_core_shards: 2,
+            core_cycles: 80,
+        };
+        assert_eq!(estimate.gas(100), 36);
+        assert_eq!(estimate.gas(1000), 351);
+        assert_eq!(estimate.gas(10), 36);
+        assert_eq!(GasEstimate { halted: true, ..estimate }.gas(1000), 31);
+    }
+}
@@ -0,0 +1,121 @@
+//! Statistics about compiled recursion programs.
+//!
+//! These are meant for performance work on the recursion circuits: they show which instructions a
+//! program is made of, how tall each trace is expected to be, and how much padding the fixed shape
+//! adds on top of that.
+
+use std::{collections::BTreeMap, fmt};
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sp1_recursion_core::{machine::RecursionAir, RecursionProgram};
+
+use crate::{
+    components::SP1ProverComponents, shapes::SP1CompressProgramShape, SP1Prover,
+    SP1RecursionProverError, COMPRESS_DEGREE, SHRINK_DEGREE, WRAP_DEGREE,
+};
+
+/// Statistics about a compiled recursion program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecursionProgramInfo {
+    /// The total number of instructions.
+    pub num_instructions: usize,
+    /// The number of instructions of each opcode.
+    pub instruction_counts: BTreeMap<String, usize>,
+    /// The number of memory cells used by the program.
+    pub total_memory: usize,
+    /// The estimated trace height of each AIR, before padding.
+    pub heights: BTreeMap<String, usize>,
+    /// The smallest shape that fits the estimated heights, as a map from AIR name to log2 height.
+    pub unfixed_shape: BTreeMap<String, usize>,
+    /// The shape the program was fixed to, if any.
+    pub fixed_shape: Option<BTreeMap<String, usize>>,
+}
+
+impl RecursionProgramInfo {
+    /// Collect the statistics of a program proven with the recursion machine of degree `DEGREE`.
+    #[must_use]
+    pub fn new<const DEGREE: usize>(program: &RecursionProgram<BabyBear>) -> Self {
+        let mut instruction_counts = BTreeMap::new();
+        let mut num_instructions = 0;
+        for instruction in program.inner.iter() {
+            *instruction_counts.entry(instruction.opcode_name().to_string()).or_default() += 1;
+            num_instructions += 1;
+        }
+
+        let heights = RecursionAir::<BabyBear, DEGREE>::heights(program)
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let unfixed_shape = heights
+            .iter()
+            .map(|(name, height)| (name.clone(), height.next_power_of_two().ilog2() as usize))
+            .collect();
+        let fixed_shape =
+            program.shape.as_ref().map(|shape| shape.clone_into_hash_map().into_iter().collect());
+
+        Self {
+            num_instructions,
+            instruction_counts,
+            total_memory: program.total_memory,
+            heights,
+            unfixed_shape,
+            fixed_shape,
+        }
+    }
+
+    /// The number of padding rows added by fixing the shape, summed over all AIRs.
+    #[must_use]
+    pub fn padding_rows(&self) -> Option<usize> {
+        let fixed_shape = self.fixed_shape.as_ref()?;
+        Some(
+            self.heights
+                .iter()
+                .map(|(name, height)| {
+                    fixed_shape.get(name).map_or(0, |log2| (1 << log2) - height.min(&(1 << log2)))
+                })
+                .sum(),
+        )
+    }
+}
+
+impl fmt::Display for RecursionProgramInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions: {}", self.num_instructions)?;
+        for (opcode, count) in self.instruction_counts.iter() {
+            writeln!(f, "  {opcode}: {count}")?;
+        }
+        writeln!(f, "memory: {}", self.total_memory)?;
+        write!(f, "heights:")?;
+        for (name, height) in self.heights.iter() {
+            write!(f, "\n  {name}: {height} (log2 {}", self.unfixed_shape[name])?;
+            if let Some(log2) = self.fixed_shape.as_ref().and_then(|shape| shape.get(name)) {
+                write!(f, ", fixed {log2}")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Get the statistics of the lift, join, deferred or shrink program with the given shape.
+    ///
+    /// The program is compiled if it is not already cached.
+    pub fn program_info(&self, shape: SP1CompressProgramShape) -> RecursionProgramInfo {
+        let is_shrink = matches!(shape, SP1CompressProgramShape::Shrink(_));
+        let program = self.program_from_shape(shape, None);
+        if is_shrink {
+            RecursionProgramInfo::new::<SHRINK_DEGREE>(&program)
+        } else {
+            RecursionProgramInfo::new::<COMPRESS_DEGREE>(&program)
+        }
+    }
+
+    /// Get the statistics of the wrap program.
+    ///
+    /// Fails if the handle is read-only and the wrap program was not compiled yet.
+    pub fn wrap_program_info(&self) -> Result<RecursionProgramInfo, SP1RecursionProverError> {
+        let program = self.wrap_program()?;
+        Ok(RecursionProgramInfo::new::<WRAP_DEGREE>(&program))
+    }
+}
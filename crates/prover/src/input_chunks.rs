@@ -0,0 +1,102 @@
+//! Proving programs with huge inputs.
+//!
+//! An input of gigabytes should not be written to the [`SP1Stdin`] buffer: the executors proving
+//! the program each copy the buffer, and so do the checkpoints of the execution, and a program
+//! reading it at once holds all of it in memory. Instead, [`write_input_chunks`] splits the input
+//! into chunks, which the executors share and read after the buffer, and writes the
+//! [`InputChunksHeader`] committing to them and the digests of the chunks to the buffer. The
+//! program reads the chunks one at a time with `sp1_zkvm::io::read_input_chunks`, which commits to
+//! the header and checks each chunk against it as the chunk is read, so the verifier can compare
+//! the committed header with [`input_chunks_header`].
+
+use std::{
+    io::{self, Read},
+    sync::Arc,
+};
+
+use sp1_core_machine::io::SP1Stdin;
+pub use sp1_primitives::input_chunks::{InputChunksHasher, InputChunksHeader};
+
+/// The default size of the chunks of an input, in bytes.
+pub const DEFAULT_INPUT_CHUNK_SIZE: usize = 1 << 20;
+
+/// Split `input` into chunks of `chunk_size` bytes and write them to `stdin`, after their header
+/// and digests.
+///
+/// The chunks are read after the rest of the buffer, so the input must be written last. Fails if
+/// `stdin` already has input chunks.
+pub fn write_input_chunks(
+    stdin: &mut SP1Stdin,
+    input: impl Read,
+    chunk_size: usize,
+) -> io::Result<InputChunksHeader> {
+    if !stdin.input_chunks.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "stdin already has input chunks"));
+    }
+    let mut chunks = Vec::new();
+    let hasher = read_chunks(input, chunk_size, |chunk| chunks.push(Arc::new(chunk)))?;
+    let header = hasher.header();
+    stdin.write(&header);
+    stdin.write(&hasher.chunk_digests());
+    stdin.input_chunks = chunks;
+    Ok(header)
+}
+
+/// The header of `input` split into chunks of `chunk_size` bytes, as committed by the program.
+///
+/// The input is hashed as it is read, without being kept in memory.
+pub fn input_chunks_header(input: impl Read, chunk_size: usize) -> io::Result<InputChunksHeader> {
+    read_chunks(input, chunk_size, drop).map(|hasher| hasher.header())
+}
+
+/// Read `input` in chunks of `chunk_size` bytes, passing each chunk to `f`, and return their
+/// hasher.
+fn read_chunks(
+    mut input: impl Read,
+    chunk_size: usize,
+    mut f: impl FnMut(Vec<u8>),
+) -> io::Result<InputChunksHasher> {
+    if chunk_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "input chunk size must be positive",
+        ));
+    }
+    let mut hasher = InputChunksHasher::new();
+    loop {
+        let mut chunk = Vec::with_capacity(chunk_size);
+        (&mut input).take(chunk_size as u64).read_to_end(&mut chunk)?;
+        if chunk.is_empty() {
+            return Ok(hasher);
+        }
+        hasher.update(&chunk);
+        f(chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_input_chunks() {
+        let input = (0..10u8).collect::<Vec<_>>();
+        let mut stdin = SP1Stdin::new();
+        let header = write_input_chunks(&mut stdin, input.as_slice(), 4).unwrap();
+        assert_eq!(header, input_chunks_header(input.as_slice(), 4).unwrap());
+        assert_eq!(header.num_chunks, 3);
+        assert_eq!(header.len, 10);
+        assert_eq!(
+            stdin.input_chunks.iter().flat_map(|chunk| chunk.iter().copied()).collect::<Vec<_>>(),
+            input
+        );
+
+        // The program reads the header, then the digests it checks each chunk against.
+        assert_eq!(stdin.read::<InputChunksHeader>(), header);
+        let chunk_digests: Vec<[u8; 32]> = stdin.read();
+        assert!(header.matches_chunk_digests(&chunk_digests));
+
+        assert!(write_input_chunks(&mut stdin, input.as_slice(), 4).is_err());
+        assert!(input_chunks_header(input.as_slice(), 0).is_err());
+    }
+}
@@ -11,38 +11,104 @@
 #![allow(clippy::new_without_default)]
 #![allow(clippy::collapsible_else_if)]
 
+pub mod artifact;
+pub mod async_prover;
+pub mod backfill;
+pub mod batch_shrink;
+pub mod bench;
 pub mod build;
+pub mod capabilities;
 pub mod components;
+pub mod compress_tree;
+pub mod continuity;
+pub mod deferred_tree;
+pub mod device_key;
+pub mod dry_run;
+pub mod encryption;
+pub mod export;
 pub mod gas;
+pub mod gas_estimate;
+pub mod info;
+pub mod input_chunks;
+pub mod merge;
+pub mod metadata;
+pub mod metering;
+pub mod optimize;
+pub mod pipeline;
+pub mod plan;
+pub mod prefix;
+pub mod prove;
+pub mod public_values;
+pub mod reduce_plan;
+pub mod reduction;
+pub mod registry;
+pub mod reload;
+pub mod repair;
+pub mod reservation;
+pub mod resume;
+pub mod rotation;
+pub mod self_test;
+pub mod setup_cache;
 pub mod shapes;
+#[cfg(feature = "simulate")]
+pub mod simulate;
+pub mod throttle;
+pub mod tune;
 pub mod types;
 pub mod utils;
+pub mod verifiers;
 pub mod verify;
+pub mod vk_tree;
+pub mod workers;
+pub mod wrap_cache;
 
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     collections::BTreeMap,
     env,
     error::Error,
+    io,
     num::NonZeroUsize,
+    ops::Deref,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
     path::Path,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        mpsc::{channel, sync_channel},
-        Arc, Mutex, OnceLock,
+        mpsc::{channel, sync_channel, RecvTimeoutError},
+        Arc, Mutex, MutexGuard, OnceLock,
     },
     thread,
+    time::{Duration, Instant},
 };
 
-use crate::shapes::SP1CompressProgramShape;
+use crate::{
+    compress_tree::CompressTree,
+    device_key::PendingDeviceKey,
+    encryption::ArtifactCipher,
+    gas::GasBreakdown,
+    metering::{CostMeter, CostStage, JobCost},
+    reduction::{LayeredReduction, ReductionStrategy},
+    reload::{ProverConfigBundle, VkVerificationMode},
+    repair::CheckpointRecorder,
+    reservation::ReservationManager,
+    resume::StateWriter,
+    rotation::VkAllowlist,
+    shapes::SP1CompressProgramShape,
+    throttle::{DutyCycle, Throttle},
+    workers::{WorkerKind, WorkerPool},
+};
 use lru::LruCache;
 use p3_baby_bear::BabyBear;
 use p3_field::{AbstractField, PrimeField, PrimeField32};
 use p3_matrix::dense::RowMajorMatrix;
 use shapes::SP1ProofShape;
 use sp1_core_executor::{
-    estimator::RecordEstimator, ExecutionError, ExecutionReport, Executor, Program, RiscvAirId,
-    SP1Context,
+    cancel::CancellationToken, estimator::RecordEstimator, spill::SpillCipher, ExecutionError,
+    ExecutionReport, Executor, Program, RiscvAirId, SP1Context, DOMAIN_TAG_WORDS,
+};
+pub use sp1_core_machine::utils::{
+    ProvingProfile, ShardProfile, StageTimings, StrictMode, StrictModeError, UnknownWarningClass,
+    WarningClass,
 };
 use sp1_core_machine::{
     io::SP1Stdin,
@@ -62,35 +128,42 @@ use sp1_recursion_circuit::{
         SP1MerkleProofWitnessValues, SP1RecursionShape, SP1RecursionWitnessValues,
         SP1RecursiveVerifier,
     },
-    merkle_tree::MerkleTree,
+    merkle_tree::{MerkleTree, MerkleTreeConfig},
     witness::Witnessable,
     WrapConfig,
 };
 use sp1_recursion_compiler::{
     circuit::AsmCompiler,
     config::InnerConfig,
-    ir::{Builder, DslIrProgram, Witness},
+    ir::{Builder, DslIrProgram, Felt, Witness},
 };
 use sp1_recursion_core::{
-    air::RecursionPublicValues,
+    air::{Block, RecursionPublicValues},
     machine::RecursionAir,
-    runtime::ExecutionRecord,
+    runtime::{ExecutionRecord, RecursionArena},
     shape::{RecursionShape, RecursionShapeConfig},
     stark::BabyBearPoseidon2Outer,
     RecursionProgram, Runtime as RecursionRuntime,
 };
-pub use sp1_recursion_gnark_ffi::proof::{Groth16Bn254Proof, PlonkBn254Proof};
 use sp1_recursion_gnark_ffi::{groth16_bn254::Groth16Bn254Prover, plonk_bn254::PlonkBn254Prover};
+pub use sp1_recursion_gnark_ffi::{
+    proof::{Groth16Bn254Proof, PlonkBn254Proof},
+    GnarkError, GnarkLimits,
+};
 use sp1_stark::{
+    air::PublicValues,
     baby_bear_poseidon2::BabyBearPoseidon2,
     shape::{OrderedShape, Shape},
-    Challenge, MachineProver, MachineProvingKey, SP1ProverOpts, ShardProof, SplitOpts,
-    StarkGenericConfig, StarkVerifyingKey, Val, Word, DIGEST_SIZE,
+    Challenge, DeferredProofOrdering, FriParamsMismatch, MachineProver, MachineProvingKey,
+    ProgressEvent, SP1DeferredOpts, SP1FriOpts, SP1ProverOpts, ShardProof, SplitOpts,
+    StarkGenericConfig, StarkVerifyingKey, TracePoolStats, Val, Word, DIGEST_SIZE,
 };
 use tracing::instrument;
 
 pub use types::*;
-use utils::{sp1_committed_values_digest_bn254, sp1_vkey_digest_bn254, words_to_bytes};
+use utils::{
+    order_shard_proofs, sp1_committed_values_digest_bn254, sp1_vkey_digest_bn254, words_to_bytes,
+};
 
 use components::{CpuProverComponents, SP1ProverComponents};
 
@@ -115,22 +188,47 @@ pub type DeviceProvingKey<C> = <<C as SP1ProverComponents>::CoreProver as Machin
     RiscvAir<BabyBear>,
 >>::DeviceProvingKey;
 
+pub type ShrinkProvingKey<C> = <<C as SP1ProverComponents>::ShrinkProver as MachineProver<
+    BabyBearPoseidon2,
+    ShrinkAir<BabyBear>,
+>>::DeviceProvingKey;
+
 const COMPRESS_DEGREE: usize = 3;
 const SHRINK_DEGREE: usize = 3;
 const WRAP_DEGREE: usize = 9;
 
 const CORE_CACHE_SIZE: usize = 5;
+const COMPILER_WORKERS: usize = 4;
+/// How often the recursion tree checks whether it was cancelled while waiting for proofs.
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
 pub const REDUCE_BATCH_SIZE: usize = 2;
+/// The largest number of proofs a join program can reduce with fixed recursion shapes.
+pub const MAX_COMPRESS_ARITY: usize = 4;
 
 pub type CompressAir<F> = RecursionAir<F, COMPRESS_DEGREE>;
 pub type ShrinkAir<F> = RecursionAir<F, SHRINK_DEGREE>;
 pub type WrapAir<F> = RecursionAir<F, WRAP_DEGREE>;
 
-/// A end-to-end for the SP1 RISC-V zkVM.
+/// The shrink program for compressed proofs of a shape, with its keys.
+pub struct ShrinkSetup<C: SP1ProverComponents> {
+    pub shape: SP1CompressWithVkeyShape,
+    pub program: Arc<RecursionProgram<BabyBear>>,
+    pub pk: ShrinkProvingKey<C>,
+    pub vk: StarkVerifyingKey<InnerSC>,
+}
+
+/// A shrink setup running on a background thread, see [`SP1Prover::shrink_setup`].
+pub struct PendingShrinkSetup<C: SP1ProverComponents> {
+    pub shape: SP1CompressWithVkeyShape,
+    pub handle: thread::JoinHandle<Arc<ShrinkSetup<C>>>,
+}
+
+/// The state of a prover that is fixed by its configuration and circuit version.
 ///
-/// This object coordinates the proving along all the steps: core, compression, shrinkage, and
-/// wrapping.
-pub struct SP1Prover<C: SP1ProverComponents = CpuProverComponents> {
+/// This holds the machines, the allowed recursion verifying keys and the precompiled programs,
+/// which together take several gigabytes. It is shared behind an [`Arc`] by every [`SP1Prover`]
+/// handle created with [`SP1Prover::handle`] or [`SP1Prover::from_core`].
+pub struct SP1ProverCore<C: SP1ProverComponents = CpuProverComponents> {
     /// The core prover.
     pub core_prover: C::CoreProver,
     /// The compress prover (for both lift and join).
@@ -139,20 +237,21 @@ pub struct SP1Prover<C: SP1ProverComponents = CpuProverComponents> {
     pub shrink_prover: C::ShrinkProver,
     /// The wrap prover.
     pub wrap_prover: C::WrapProver,
-    /// The cache of compiled recursion programs.
-    pub lift_programs_lru: Mutex<LruCache<SP1RecursionShape, Arc<RecursionProgram<BabyBear>>>>,
-    /// The number of cache misses for recursion programs.
-    pub lift_cache_misses: AtomicUsize,
+    /// The thread pool used to compile recursion programs.
+    pub compiler_pool: rayon::ThreadPool,
     /// The cache of compiled compression programs.
     pub join_programs_map: BTreeMap<SP1CompressWithVkeyShape, Arc<RecursionProgram<BabyBear>>>,
-    /// The number of cache misses for compression programs.
-    pub join_cache_misses: AtomicUsize,
+    /// The lift, deferred and shrink programs installed from artifacts, keyed by the hash of their
+    /// program shape. Unlike the lift program cache of each handle, they are never evicted.
+    pub prebuilt_programs: BTreeMap<u64, Arc<RecursionProgram<BabyBear>>>,
     /// The root of the allowed recursion verification keys.
     pub recursion_vk_root: <InnerSC as FieldHasher<BabyBear>>::Digest,
     /// The allowed VKs and their corresponding indices.
     pub recursion_vk_map: BTreeMap<<InnerSC as FieldHasher<BabyBear>>::Digest, usize>,
     /// The Merkle tree for the allowed VKs.
     pub recursion_vk_tree: MerkleTree<BabyBear, InnerSC>,
+    /// The previous allowed VKs, still accepted during a vk map rotation.
+    pub retiring_vk_allowlist: Option<VkAllowlist>,
     /// The core shape configuration.
     pub core_shape_config: Option<CoreShapeConfig<BabyBear>>,
     /// The recursion shape configuration.
@@ -161,32 +260,259 @@ pub struct SP1Prover<C: SP1ProverComponents = CpuProverComponents> {
     pub wrap_program: OnceLock<Arc<RecursionProgram<BabyBear>>>,
     /// The verifying key for wrapping.
     pub wrap_vk: OnceLock<StarkVerifyingKey<OuterSC>>,
-    /// Whether to verify verification keys.
-    pub vk_verification: bool,
+    /// How verifying keys are checked.
+    pub vk_verification: VkVerificationMode,
+    /// The root of the dummy vk map, computed when a proof committing to it is first checked.
+    pub dummy_vk_root: OnceLock<<InnerSC as FieldHasher<BabyBear>>::Digest>,
+    /// The largest number of proofs joined by the precompiled join programs, see
+    /// [`ProverConfigBundle::max_compress_arity`].
+    pub max_compress_arity: usize,
+    /// The bound on the recursion work items running at once across compress calls.
+    pub worker_pool: WorkerPool,
+    /// The resources reserved by the jobs of the prover, see [`SP1Prover::reserve`].
+    pub reservations: ReservationManager,
+}
+
+/// A end-to-end for the SP1 RISC-V zkVM.
+///
+/// This object coordinates the proving along all the steps: core, compression, shrinkage, and
+/// wrapping.
+///
+/// The fields of the shared [`SP1ProverCore`] are reachable through `Deref`. Mutating them
+/// requires the core not to be shared with other handles.
+pub struct SP1Prover<C: SP1ProverComponents = CpuProverComponents> {
+    /// The state shared with other handles to the same prover.
+    pub core: Arc<SP1ProverCore<C>>,
+    /// The cache of compiled recursion programs.
+    pub lift_programs_lru: Mutex<LruCache<SP1RecursionShape, Arc<RecursionProgram<BabyBear>>>>,
+    /// The number of cache misses for recursion programs.
+    pub lift_cache_misses: AtomicUsize,
+    /// The recursion programs currently being compiled, so that concurrent cache misses on the
+    /// same shape wait for a single compilation.
+    #[allow(clippy::type_complexity)]
+    pub lift_programs_inflight:
+        Mutex<BTreeMap<SP1RecursionShape, Arc<OnceLock<Arc<RecursionProgram<BabyBear>>>>>>,
+    /// The compression programs compiled at runtime because they were missing from
+    /// `join_programs_map`.
+    pub join_programs_fallback:
+        Mutex<BTreeMap<SP1CompressWithVkeyShape, Arc<RecursionProgram<BabyBear>>>>,
+    /// The number of cache misses for compression programs.
+    pub join_cache_misses: AtomicUsize,
+    /// The throttle used to park workers according to a duty cycle, if any.
+    pub throttle: Option<Throttle>,
+    /// The cipher applied to the checkpoints spilled to disk while proving, if any.
+    pub artifact_cipher: Option<Arc<ArtifactCipher>>,
+    /// Whether the handle refuses to compile recursion programs, see [`Self::with_read_only`].
+    pub read_only: bool,
+    /// The classes of warnings that are errors instead, see [`Self::with_strict`].
+    pub strict: StrictMode,
+    /// The slowest shards and recursion nodes proven by this handle, see [`Self::profile`].
+    pub profile: Mutex<ProvingProfile>,
+    /// The last shrink program set up, see [`Self::shrink_setup`].
+    pub shrink_setup: Mutex<Option<Arc<ShrinkSetup<C>>>>,
+    /// The shrink program being set up in the background, see [`Self::shrink_setup`].
+    pub pending_shrink_setup: Mutex<Option<PendingShrinkSetup<C>>>,
+    /// The resources used by the jobs proven by this handle, see [`Self::job_cost`].
+    pub cost_meter: CostMeter,
+    /// The token cancelling the stages proven by this handle, see [`Self::with_cancellation`].
+    pub cancellation: Option<CancellationToken>,
+    /// The strategy shaping the recursion tree, see [`Self::with_reduction_strategy`].
+    pub reduction_strategy: Arc<dyn ReductionStrategy>,
+    /// The recursion tree of the last compressed proof, see [`Self::last_compress_tree`].
+    pub compress_tree: Mutex<Option<CompressTree>>,
+}
+
+impl<C: SP1ProverComponents> SP1ProverCore<C> {
+    fn compile_recursion_program(
+        &self,
+        input: &SP1RecursionWitnessValues<CoreSC>,
+    ) -> RecursionProgram<BabyBear> {
+        // Get the operations.
+        let builder_span = tracing::debug_span!("build recursion program").entered();
+        let mut builder = Builder::<InnerConfig>::default();
+
+        let input = tracing::debug_span!("read input").in_scope(|| input.read(&mut builder));
+        tracing::debug_span!("verify").in_scope(|| {
+            SP1RecursiveVerifier::verify(&mut builder, self.core_prover.machine(), input)
+        });
+        let block = tracing::debug_span!("build block").in_scope(|| builder.into_root_block());
+        builder_span.exit();
+        // SAFETY: The circuit is well-formed. It does not use synchronization primitives
+        // (or possibly other means) to violate the invariants.
+        let dsl_program = unsafe { DslIrProgram::new_unchecked(block) };
+
+        // Compile the program.
+        let compiler_span = tracing::debug_span!("compile recursion program").entered();
+        let mut compiler = AsmCompiler::<InnerConfig>::default();
+        let mut program = compiler.compile(dsl_program);
+        if let Some(inn_recursion_shape_config) = &self.compress_shape_config {
+            inn_recursion_shape_config.fix_shape(&mut program);
+        }
+        compiler_span.exit();
+        program
+    }
+}
+
+impl<C: SP1ProverComponents> Deref for SP1Prover<C> {
+    type Target = SP1ProverCore<C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.core
+    }
 }
 
 impl<C: SP1ProverComponents> SP1Prover<C> {
     /// Initializes a new [SP1Prover].
+    ///
+    /// Panics if a prover setting in the environment is invalid, see [`Self::try_new`].
     #[instrument(name = "initialize prover", level = "debug", skip_all)]
     pub fn new() -> Self {
         Self::uninitialized()
     }
 
+    /// Creates a new [SP1Prover], failing if a prover setting in the environment is invalid.
+    pub fn try_new() -> Result<Self, SP1ProverConfigError> {
+        Self::try_with_config(ProverConfigBundle::from_env()?)
+    }
+
     /// Creates a new [SP1Prover] with lazily initialized components.
+    ///
+    /// Panics if a prover setting in the environment is invalid, see [`Self::try_new`].
     pub fn uninitialized() -> Self {
+        Self::try_new().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Creates a new [SP1Prover] with lazily initialized components and the given configuration.
+    ///
+    /// Panics if a prover setting in the environment is invalid, see [`Self::try_with_config`].
+    pub fn with_config(config: ProverConfigBundle) -> Self {
+        Self::try_with_config(config).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Creates a new [SP1Prover] with the given configuration, failing if a prover setting in the
+    /// environment is invalid.
+    pub fn try_with_config(config: ProverConfigBundle) -> Result<Self, SP1ProverConfigError> {
+        Self::from_configs(
+            CoreSC::default(),
+            InnerSC::default(),
+            InnerSC::compressed(),
+            OuterSC::default(),
+            config,
+        )
+    }
+
+    /// Creates a new [SP1Prover] whose stages use the given FRI parameters.
+    ///
+    /// Returns an error if the parameters are insecure and `fri_opts.allow_insecure` is not set.
+    /// The allowed recursion verifying keys are computed for the default parameters, so provers
+    /// with other parameters need vk verification to be disabled.
+    pub fn with_fri_opts(fri_opts: &SP1FriOpts) -> Result<Self, SP1ProverConfigError> {
+        Self::with_fri_opts_and_config(fri_opts, ProverConfigBundle::from_env()?)
+    }
+
+    /// Creates a new [SP1Prover] whose stages use the given FRI parameters, with the shape
+    /// configurations and allowed verifying keys of `config`.
+    pub fn with_fri_opts_and_config(
+        fri_opts: &SP1FriOpts,
+        config: ProverConfigBundle,
+    ) -> Result<Self, SP1ProverConfigError> {
+        fri_opts.check_security()?;
+        tracing::info!(
+            "FRI parameters provide {} bits of conjectured security",
+            fri_opts.conjectured_security_bits()
+        );
+        Self::from_configs(
+            CoreSC::with_fri_params(fri_opts.core),
+            InnerSC::with_fri_params(fri_opts.compress),
+            InnerSC::with_fri_params(fri_opts.shrink),
+            OuterSC::with_fri_params(fri_opts.wrap),
+            config,
+        )
+    }
+
+    fn from_configs(
+        core_config: CoreSC,
+        compress_config: InnerSC,
+        shrink_config: InnerSC,
+        wrap_config: OuterSC,
+        config: ProverConfigBundle,
+    ) -> Result<Self, SP1ProverConfigError> {
         // Initialize the provers.
-        let core_machine = RiscvAir::machine(CoreSC::default());
+        let core_machine = RiscvAir::machine(core_config);
         let core_prover = C::CoreProver::new(core_machine);
 
-        let compress_machine = CompressAir::compress_machine(InnerSC::default());
+        let compress_machine = CompressAir::compress_machine(compress_config);
         let compress_prover = C::CompressProver::new(compress_machine);
 
-        let shrink_machine = ShrinkAir::shrink_machine(InnerSC::compressed());
+        let shrink_machine = ShrinkAir::shrink_machine(shrink_config);
         let shrink_prover = C::ShrinkProver::new(shrink_machine);
 
-        let wrap_machine = WrapAir::wrap_machine(OuterSC::default());
+        let wrap_machine = WrapAir::wrap_machine(wrap_config);
         let wrap_prover = C::WrapProver::new(wrap_machine);
 
+        let compiler_workers = env::var("PROVER_COMPILER_WORKERS")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(COMPILER_WORKERS);
+        let compiler_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(compiler_workers)
+            .thread_name(|i| format!("sp1-compiler-{i}"))
+            .build()
+            .expect("failed to build the compiler thread pool");
+
+        let ProverConfigBundle {
+            core_shape_config,
+            compress_shape_config: recursion_shape_config,
+            vk_verification,
+            vk_map: allowed_vk_map,
+            vk_merkle_config,
+            max_compress_arity,
+        } = config;
+        tracing::debug!("vk verification: {:?}", vk_verification);
+
+        let (root, merkle_tree) = MerkleTree::commit_with_config(
+            allowed_vk_map.keys().copied().collect(),
+            vk_merkle_config,
+        );
+
+        let compress_programs = Self::precompile_join_programs(
+            &compress_prover,
+            recursion_shape_config.as_ref(),
+            vk_verification.checks_vks(),
+            merkle_tree.height,
+            merkle_tree.config,
+            max_compress_arity,
+        );
+
+        Self::from_core(Arc::new(SP1ProverCore {
+            core_prover,
+            compress_prover,
+            shrink_prover,
+            wrap_prover,
+            compiler_pool,
+            join_programs_map: compress_programs,
+            prebuilt_programs: BTreeMap::new(),
+            recursion_vk_root: root,
+            recursion_vk_tree: merkle_tree,
+            recursion_vk_map: allowed_vk_map,
+            retiring_vk_allowlist: None,
+            core_shape_config,
+            compress_shape_config: recursion_shape_config,
+            vk_verification,
+            dummy_vk_root: OnceLock::new(),
+            max_compress_arity,
+            wrap_program: OnceLock::new(),
+            wrap_vk: OnceLock::new(),
+            worker_pool: WorkerPool::from_env(),
+            reservations: ReservationManager::from_env(),
+        }))
+    }
+
+    /// Creates a new prover handle on top of a shared core.
+    ///
+    /// The handle has its own program caches, and its duty cycle, artifact cipher, read-only and
+    /// strict modes are read from the environment.
+    pub fn from_core(core: Arc<SP1ProverCore<C>>) -> Result<Self, SP1ProverConfigError> {
         let core_cache_size = NonZeroUsize::new(
             env::var("PROVER_CORE_CACHE_SIZE")
                 .unwrap_or_else(|_| CORE_CACHE_SIZE.to_string())
@@ -195,75 +521,247 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         )
         .expect("PROVER_CORE_CACHE_SIZE must be a non-zero usize");
 
-        let core_shape_config = env::var("FIX_CORE_SHAPES")
-            .map(|v| v.eq_ignore_ascii_case("true"))
-            .unwrap_or(true)
-            .then_some(CoreShapeConfig::default());
+        let duty_cycle = DutyCycle::from_env();
+        if let Some(duty_cycle) = &duty_cycle {
+            tracing::info!(
+                "prover duty cycle: {:?} every {:?}",
+                duty_cycle.active,
+                duty_cycle.period
+            );
+        }
 
-        let recursion_shape_config = env::var("FIX_RECURSION_SHAPES")
+        let mut handle = Self::bare(core, core_cache_size);
+        handle.throttle = duty_cycle.map(Throttle::new);
+        handle.artifact_cipher =
+            ArtifactCipher::from_env().map_err(SP1ProverConfigError::ArtifactKey)?.map(Arc::new);
+        handle.read_only = env::var("SP1_PROVER_READ_ONLY")
             .map(|v| v.eq_ignore_ascii_case("true"))
-            .unwrap_or(true)
-            .then_some(RecursionShapeConfig::default());
+            .unwrap_or(false);
+        handle.strict = StrictMode::from_env()?;
+        Ok(handle)
+    }
 
-        let vk_verification =
-            env::var("VERIFY_VK").map(|v| v.eq_ignore_ascii_case("true")).unwrap_or(true);
-        tracing::debug!("vk verification: {}", vk_verification);
+    /// A handle on top of `core` with empty caches and default settings.
+    fn bare(core: Arc<SP1ProverCore<C>>, core_cache_size: NonZeroUsize) -> Self {
+        Self {
+            core,
+            lift_programs_lru: Mutex::new(LruCache::new(core_cache_size)),
+            lift_cache_misses: AtomicUsize::new(0),
+            lift_programs_inflight: Mutex::new(BTreeMap::new()),
+            join_programs_fallback: Mutex::new(BTreeMap::new()),
+            join_cache_misses: AtomicUsize::new(0),
+            throttle: None,
+            artifact_cipher: None,
+            read_only: false,
+            strict: StrictMode::default(),
+            profile: Mutex::new(ProvingProfile::default()),
+            shrink_setup: Mutex::new(None),
+            pending_shrink_setup: Mutex::new(None),
+            cost_meter: CostMeter::new(),
+            cancellation: None,
+            reduction_strategy: Arc::new(LayeredReduction),
+            compress_tree: Mutex::new(None),
+        }
+    }
 
-        // Read the shapes from the shapes directory and deserialize them into memory.
-        let allowed_vk_map: BTreeMap<[BabyBear; DIGEST_SIZE], usize> = if vk_verification {
-            bincode::deserialize(include_bytes!(concat!(env!("OUT_DIR"), "/vk_map.bin"))).unwrap()
-        } else {
-            bincode::deserialize(include_bytes!("vk_map_dummy.bin")).unwrap()
-        };
+    /// Creates another handle to the same prover, sharing its machines, allowed verifying keys
+    /// and precompiled programs.
+    ///
+    /// The new handle starts with empty program caches of the same size, and the same duty cycle,
+    /// artifact cipher, read-only and strict modes and reduction strategy.
+    #[must_use]
+    pub fn handle(&self) -> Self {
+        let core_cache_size = lock_or_reset(&self.lift_programs_lru, LruCache::clear).cap();
+        let mut handle = Self::bare(self.core.clone(), core_cache_size);
+        handle.throttle = self.throttle;
+        handle.artifact_cipher = self.artifact_cipher.clone();
+        handle.read_only = self.read_only;
+        handle.strict = self.strict.clone();
+        handle.reduction_strategy = self.reduction_strategy.clone();
+        handle
+    }
 
-        let (root, merkle_tree) = MerkleTree::commit(allowed_vk_map.keys().copied().collect());
+    /// The core of the prover, unless it is shared with other handles.
+    pub fn core_mut(&mut self) -> Option<&mut SP1ProverCore<C>> {
+        Arc::get_mut(&mut self.core)
+    }
+
+    /// Refuse to compile any recursion program at runtime, or allow it again.
+    ///
+    /// A read-only handle proves only with the precompiled join programs and the programs
+    /// installed with [`Self::load_program_artifacts`], which are shared with every handle to the
+    /// same prover and never evicted. Proving fails with
+    /// [`SP1RecursionProverError::MissingArtifacts`] if a program is missing. The wrap program
+    /// has no artifact, so it must be compiled with [`Self::wrap_program`] before the handle is
+    /// made read-only. Read-only mode is also enabled by `SP1_PROVER_READ_ONLY=true`.
+    #[must_use]
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
 
+    /// Turn the warnings of the classes of `strict` into errors.
+    ///
+    /// Proving fails with a [`StrictModeError`] carrying the class of the warning instead of
+    /// recompiling a join program missing from the cache, computing gas with options that differ
+    /// from the gas options, or falling back from an unsupported compress arity. Strict classes
+    /// can also be set with `SP1_STRICT`, either `true` or a comma separated list of
+    /// `cache_recompute`, `gas_divergence` and `shape_fallback`.
+    #[must_use]
+    pub fn with_strict(mut self, strict: StrictMode) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Cancel the stages proven by this handle once `cancellation` is cancelled.
+    ///
+    /// The core prover, compress, shrink and wrap stop their workers between shards and recursion
+    /// proofs and fail with a `Cancelled` error. The core prover uses the token of its context
+    /// instead, if any. Handles made with [`Self::handle`] are not cancelled by the token, since
+    /// they usually prove other jobs.
+    #[must_use]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Shape the recursion tree of `compress` with `strategy` instead of [`LayeredReduction`].
+    ///
+    /// The plan of the strategy is checked before proving, and compress fails with
+    /// [`SP1RecursionProverError::InvalidReductionPlan`] if it does not reduce the inputs into a
+    /// single proof or joins more proofs than the compress arity.
+    #[must_use]
+    pub fn with_reduction_strategy(mut self, strategy: impl ReductionStrategy + 'static) -> Self {
+        self.reduction_strategy = Arc::new(strategy);
+        self
+    }
+
+    /// Whether the stages proven by this handle were cancelled.
+    fn cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Fail with [`SP1RecursionProverError::Cancelled`] if the handle was cancelled.
+    fn check_cancelled(&self) -> Result<(), SP1RecursionProverError> {
+        if self.cancelled() {
+            return Err(SP1RecursionProverError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Compile the join programs of every allowed compress shape joining up to
+    /// `max_compress_arity` proofs, unless disabled by `SP1_DISABLE_PROGRAM_CACHE`.
+    fn precompile_join_programs(
+        compress_prover: &C::CompressProver,
+        shape_config: Option<&RecursionShapeConfig<BabyBear, CompressAir<BabyBear>>>,
+        vk_verification: bool,
+        merkle_tree_height: usize,
+        merkle_tree_config: MerkleTreeConfig,
+        max_compress_arity: usize,
+    ) -> BTreeMap<SP1CompressWithVkeyShape, Arc<RecursionProgram<BabyBear>>> {
         let mut compress_programs = BTreeMap::new();
         let program_cache_disabled = env::var("SP1_DISABLE_PROGRAM_CACHE")
             .map(|v| v.eq_ignore_ascii_case("true"))
             .unwrap_or(false);
         if !program_cache_disabled {
-            if let Some(config) = &recursion_shape_config {
-                SP1ProofShape::generate_compress_shapes(config, REDUCE_BATCH_SIZE).for_each(
-                    |shape| {
+            if let Some(config) = shape_config {
+                (REDUCE_BATCH_SIZE..=max_compress_arity)
+                    .flat_map(|arity| SP1ProofShape::generate_compress_shapes(config, arity))
+                    .for_each(|shape| {
                         let compress_shape = SP1CompressWithVkeyShape {
                             compress_shape: shape.into(),
-                            merkle_tree_height: merkle_tree.height,
+                            merkle_tree_height,
+                            merkle_tree_config,
                         };
                         let input = SP1CompressWithVKeyWitnessValues::dummy(
                             compress_prover.machine(),
                             &compress_shape,
                         );
                         let program = compress_program_from_input::<C>(
-                            recursion_shape_config.as_ref(),
-                            &compress_prover,
+                            shape_config,
+                            compress_prover,
                             vk_verification,
                             &input,
                         );
                         let program = Arc::new(program);
                         compress_programs.insert(compress_shape, program);
-                    },
-                );
+                    });
             }
         }
+        compress_programs
+    }
 
-        Self {
-            core_prover,
-            compress_prover,
-            shrink_prover,
-            wrap_prover,
-            lift_programs_lru: Mutex::new(LruCache::new(core_cache_size)),
-            lift_cache_misses: AtomicUsize::new(0),
-            join_programs_map: compress_programs,
-            join_cache_misses: AtomicUsize::new(0),
-            recursion_vk_root: root,
-            recursion_vk_tree: merkle_tree,
-            recursion_vk_map: allowed_vk_map,
-            core_shape_config,
-            compress_shape_config: recursion_shape_config,
-            vk_verification,
-            wrap_program: OnceLock::new(),
-            wrap_vk: OnceLock::new(),
+    /// Set the duty cycle of the prover, or disable throttling with `None`.
+    ///
+    /// While throttled, the recursion workers park themselves outside of the active part of each
+    /// period. Work already in progress is not interrupted, so the effective duty cycle is
+    /// approximate, with a granularity of a single recursion proof.
+    pub fn set_duty_cycle(&mut self, duty_cycle: Option<DutyCycle>) {
+        self.throttle = duty_cycle.map(Throttle::new);
+    }
+
+    /// Park the current thread if the prover is outside of the active part of its duty cycle.
+    fn throttle(&self) {
+        if let Some(throttle) = &self.throttle {
+            throttle.yield_now();
+        }
+    }
+
+    /// The number of proofs reduced by each join program.
+    ///
+    /// Falls back to [`REDUCE_BATCH_SIZE`] if vk verification is enabled or recursion shapes are
+    /// fixed and the arity is above [`SP1ProverCore::max_compress_arity`], since the allowed
+    /// verifying keys and precompiled join programs only cover joins of up to that many proofs.
+    pub fn compress_arity(&self, opts: &SP1ProverOpts) -> usize {
+        self.try_compress_arity(opts).unwrap_or(REDUCE_BATCH_SIZE)
+    }
+
+    /// The number of proofs reduced by each join program, see [`Self::compress_arity`].
+    ///
+    /// Fails instead of falling back if shape fallbacks are strict.
+    pub fn try_compress_arity(&self, opts: &SP1ProverOpts) -> Result<usize, StrictModeError> {
+        let arity = opts.compress_opts.arity;
+        let fixed = self.vk_verification.checks_vks() || self.compress_shape_config.is_some();
+        if arity < 2 || (fixed && arity > self.max_compress_arity) {
+            if arity != REDUCE_BATCH_SIZE {
+                self.strict.warn(
+                    WarningClass::ShapeFallback,
+                    format!("compress arity {arity} is not supported, using {REDUCE_BATCH_SIZE}"),
+                )?;
+            }
+            return Ok(REDUCE_BATCH_SIZE);
+        }
+        Ok(arity)
+    }
+
+    /// The number of core shard proofs verified by each first layer program for `opts`.
+    ///
+    /// Falls back to one shard per program, with a warning, if the requested batch size is zero
+    /// or larger than one while recursion shapes are fixed.
+    pub fn try_first_layer_batch_size(
+        &self,
+        opts: &SP1ProverOpts,
+    ) -> Result<usize, StrictModeError> {
+        let batch_size = opts.compress_opts.first_layer_batch_size;
+        let fixed = self.vk_verification.checks_vks() || self.compress_shape_config.is_some();
+        if batch_size == 0 || (fixed && batch_size > 1) {
+            self.strict.warn(
+                WarningClass::ShapeFallback,
+                format!("first layer batch size {batch_size} is not supported, using 1"),
+            )?;
+            return Ok(1);
+        }
+        Ok(batch_size)
+    }
+
+    /// The FRI parameters the stages of the prover were created with.
+    pub fn fri_opts(&self) -> SP1FriOpts {
+        SP1FriOpts {
+            core: self.core_prover.config().fri_params(),
+            compress: self.compress_prover.config().fri_params(),
+            shrink: self.shrink_prover.config().fri_params(),
+            wrap: self.wrap_prover.config().fri_params(),
+            allow_insecure: false,
         }
     }
 
@@ -273,57 +771,241 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         &self,
         elf: &[u8],
     ) -> (SP1ProvingKey, DeviceProvingKey<C>, Program, SP1VerifyingKey) {
-        let program = self.get_program(elf).unwrap();
-        let (pk, vk) = self.core_prover.setup(&program);
-        let vk = SP1VerifyingKey { vk };
+        self.setup_with_shape_config(elf, self.core_shape_config.as_ref())
+    }
+
+    /// Creates a proving key and a verifying key for a given RISC-V ELF, fixing its preprocessed
+    /// shape with `core_shape_config` instead of the configuration of the prover.
+    ///
+    /// This lets a fleet pin the shapes its verifying keys are made with, whatever the environment
+    /// of the node running the setup. The digest of the configuration is recorded in the
+    /// verifying key, see [`SP1Prover::check_vk_shape_config`].
+    pub fn setup_with_shape_config(
+        &self,
+        elf: &[u8],
+        core_shape_config: Option<&CoreShapeConfig<BabyBear>>,
+    ) -> (SP1ProvingKey, DeviceProvingKey<C>, Program, SP1VerifyingKey) {
+        let program = self.get_program_with_shape_config(elf, core_shape_config).unwrap();
+        self.setup_program(elf, program, core_shape_config.map(CoreShapeConfig::digest))
+    }
+
+    /// Creates a proving key and a verifying key for a given RISC-V ELF with the given
+    /// preprocessed shape.
+    ///
+    /// The shape configuration is unknown in this case, so it is not recorded in the verifying key.
+    pub fn setup_with_preprocessed_shape(
+        &self,
+        elf: &[u8],
+        preprocessed_shape: Shape<RiscvAirId>,
+    ) -> eyre::Result<(SP1ProvingKey, DeviceProvingKey<C>, Program, SP1VerifyingKey)> {
+        let mut program = Program::from(elf)?;
+        program.preprocessed_shape = Some(preprocessed_shape);
+        Ok(self.setup_program(elf, program, None))
+    }
+
+    fn setup_program(
+        &self,
+        elf: &[u8],
+        program: Program,
+        shape_config_digest: Option<[u8; 32]>,
+    ) -> (SP1ProvingKey, DeviceProvingKey<C>, Program, SP1VerifyingKey) {
+        let (pk, vk) = self.setup_host_keys(elf, &program, shape_config_digest);
+        let pk_d = self.core_prover.pk_to_device(&pk.pk);
+        (pk, pk_d, program, vk)
+    }
+
+    fn setup_host_keys(
+        &self,
+        elf: &[u8],
+        program: &Program,
+        shape_config_digest: Option<[u8; 32]>,
+    ) -> (SP1ProvingKey, SP1VerifyingKey) {
+        let (pk, vk) = self.core_prover.setup(program);
+        let vk = SP1VerifyingKey {
+            vk,
+            shape_config_digest,
+            artifacts: Some(self.setup_artifacts()),
+            domain_tag: program.domain_tag(),
+        };
         let pk = SP1ProvingKey {
             pk: self.core_prover.pk_to_host(&pk),
             elf: elf.to_vec(),
             vk: vk.clone(),
         };
-        let pk_d = self.core_prover.pk_to_device(&pk.pk);
-        (pk, pk_d, program, vk)
+        (pk, vk)
+    }
+
+    /// Creates a proving key and a verifying key for a given RISC-V ELF tagged with a domain.
+    ///
+    /// The tag is placed in the initial memory image of the program, see
+    /// [`Program::tag_domain`], which the verifying key commits to and which is absorbed into the
+    /// transcript of every proof along with the key. The digest of the key, which compressed and
+    /// wrapped proofs expose as a public input, therefore differs for every domain, so that proofs
+    /// made for one domain do not verify against the keys of another. Verifiers derive the key of
+    /// their own domain with this function and verify proofs with it as usual.
+    pub fn setup_with_domain(
+        &self,
+        elf: &[u8],
+        domain_tag: [u32; DOMAIN_TAG_WORDS],
+    ) -> eyre::Result<(SP1ProvingKey, DeviceProvingKey<C>, Program, SP1VerifyingKey)> {
+        let program = self.get_program_in_domain(elf, Some(domain_tag))?;
+        let shape_config_digest = self.core_shape_config.as_ref().map(CoreShapeConfig::digest);
+        Ok(self.setup_program(elf, program, shape_config_digest))
     }
 
     /// Get a program with an allowed preprocessed shape.
     pub fn get_program(&self, elf: &[u8]) -> eyre::Result<Program> {
+        self.get_program_with_shape_config(elf, self.core_shape_config.as_ref())
+    }
+
+    /// Get a program with an allowed preprocessed shape, tagged with `domain_tag`, if any. This
+    /// is the program to prove with keys set up with [`SP1Prover::setup_with_domain`].
+    pub fn get_program_in_domain(
+        &self,
+        elf: &[u8],
+        domain_tag: Option<[u32; DOMAIN_TAG_WORDS]>,
+    ) -> eyre::Result<Program> {
         let mut program = Program::from(elf)?;
-        if let Some(core_shape_config) = &self.core_shape_config {
+        if let Some(domain_tag) = domain_tag {
+            program.tag_domain(domain_tag)?;
+        }
+        if let Some(core_shape_config) = self.core_shape_config.as_ref() {
             core_shape_config.fix_preprocessed_shape(&mut program)?;
         }
         Ok(program)
     }
 
+    /// Get a program whose preprocessed shape is fixed by `core_shape_config`, if any.
+    pub fn get_program_with_shape_config(
+        &self,
+        elf: &[u8],
+        core_shape_config: Option<&CoreShapeConfig<BabyBear>>,
+    ) -> eyre::Result<Program> {
+        let mut program = Program::from(elf)?;
+        if let Some(core_shape_config) = core_shape_config {
+            core_shape_config.fix_preprocessed_shape(&mut program)?;
+        }
+        Ok(program)
+    }
+
+    /// Check that `vk` was set up with the core shape configuration of this prover.
+    ///
+    /// Verifying keys that do not record their configuration are accepted.
+    pub fn check_vk_shape_config(&self, vk: &SP1VerifyingKey) -> Result<(), ShapeConfigMismatch> {
+        let Some(vk_digest) = vk.shape_config_digest else {
+            return Ok(());
+        };
+        let prover_digest = self.core_shape_config.as_ref().map(CoreShapeConfig::digest);
+        if prover_digest != Some(vk_digest) {
+            return Err(ShapeConfigMismatch { vk: Some(vk_digest), prover: prover_digest });
+        }
+        Ok(())
+    }
+
+    /// The artifacts of this prover, as recorded in the verifying keys it sets up.
+    pub fn setup_artifacts(&self) -> SetupArtifacts {
+        SetupArtifacts {
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            vk_root: self.recursion_vk_root.map(|x| x.as_canonical_u32()),
+        }
+    }
+
+    /// Check that `vk` was set up with the circuit version and allowed recursion verifying keys of
+    /// this prover.
+    ///
+    /// Verifying keys that do not record their artifacts are accepted.
+    pub fn check_vk_artifacts(&self, vk: &SP1VerifyingKey) -> Result<(), SetupArtifactsMismatch> {
+        let Some(artifacts) = &vk.artifacts else {
+            return Ok(());
+        };
+        let prover = self.setup_artifacts();
+        if artifacts.circuit_version != prover.circuit_version {
+            return Err(SetupArtifactsMismatch::CircuitVersion {
+                vk: artifacts.circuit_version.clone(),
+                prover: prover.circuit_version,
+            });
+        }
+        if artifacts.vk_root != prover.vk_root {
+            return Err(SetupArtifactsMismatch::VkRoot {
+                vk: artifacts.vk_root,
+                prover: prover.vk_root,
+            });
+        }
+        Ok(())
+    }
+
     fn get_gas_calculator(
         &self,
         preprocessed_shape: Shape<RiscvAirId>,
         split_opts: SplitOpts,
     ) -> impl FnMut(&RecordEstimator) -> Result<u64, Box<dyn Error>> + '_ {
         move |estimator: &RecordEstimator| -> Result<u64, Box<dyn Error>> {
-            let est_records = gas::estimated_records(&split_opts, estimator);
-            let raw_gas =
-                gas::fit_records_to_shapes(self.core_shape_config.as_ref().unwrap(), est_records)
-                    .enumerate()
-                    .map(|(i, shape)| {
-                        let mut shape: Shape<RiscvAirId> = shape.map_err(Box::new)?;
-                        shape.extend(preprocessed_shape.iter().map(|(k, v)| (*k, *v)));
-                        tracing::debug!("shape for estimated shard {i}: {:?}", &shape.inner);
-                        Ok(gas::predict(enum_map::EnumMap::from_iter(shape).as_array()))
-                    })
-                    .sum::<Result<_, Box<dyn Error>>>()?;
-            let gas = gas::final_transform(raw_gas).map_err(Box::new)?;
-            Ok(gas)
+            Ok(self.gas_breakdown(&preprocessed_shape, split_opts, estimator)?.gas)
         }
     }
 
+    /// The gas of the execution estimated by `estimator`, split by shard and by chip.
+    fn gas_breakdown(
+        &self,
+        preprocessed_shape: &Shape<RiscvAirId>,
+        split_opts: SplitOpts,
+        estimator: &RecordEstimator,
+    ) -> Result<GasBreakdown, Box<dyn Error>> {
+        let est_records = gas::estimated_records(&split_opts, estimator);
+        let shapes =
+            gas::fit_records_to_shapes(self.core_shape_config.as_ref().unwrap(), est_records)
+                .enumerate()
+                .map(|(i, shape)| {
+                    let mut shape: Shape<RiscvAirId> = shape.map_err(Box::new)?;
+                    shape.extend(preprocessed_shape.iter().map(|(k, v)| (*k, *v)));
+                    tracing::debug!("shape for estimated shard {i}: {:?}", &shape.inner);
+                    Ok(shape)
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        Ok(GasBreakdown::from_shapes(shapes).map_err(Box::new)?)
+    }
+
     /// Execute an SP1 program with the specified inputs.
+    ///
+    /// If the context streams the public values to a writer, the returned public values are
+    /// empty.
     #[instrument(name = "execute", level = "info", skip_all)]
     pub fn execute<'a>(
         &'a self,
         elf: &[u8],
         stdin: &SP1Stdin,
-        mut context: SP1Context<'a>,
+        context: SP1Context<'a>,
     ) -> Result<(SP1PublicValues, [u8; 32], ExecutionReport), ExecutionError> {
+        let (public_values, committed_value_digest, report, _) =
+            self.execute_inner(elf, stdin, context)?;
+        Ok((public_values, committed_value_digest, report))
+    }
+
+    /// Execute an SP1 program like [`Self::execute`], calculating its gas, and split the gas by
+    /// shard and by chip.
+    ///
+    /// The breakdown is `None` if the gas could not be calculated, like [`ExecutionReport::gas`].
+    #[instrument(name = "execute", level = "info", skip_all)]
+    pub fn execute_with_gas_breakdown<'a>(
+        &'a self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        mut context: SP1Context<'a>,
+    ) -> Result<(SP1PublicValues, [u8; 32], ExecutionReport, Option<GasBreakdown>), ExecutionError>
+    {
+        context.calculate_gas = true;
+        self.execute_inner(elf, stdin, context)
+    }
+
+    fn execute_inner<'a>(
+        &'a self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        mut context: SP1Context<'a>,
+    ) -> Result<(SP1PublicValues, [u8; 32], ExecutionReport, Option<GasBreakdown>), ExecutionError>
+    {
+        let _metadata_span = context.trace_metadata.span().entered();
+        let _cost = self.cost_meter.stage(&context.trace_metadata, CostStage::Execute);
         context.subproof_verifier = Some(self);
 
         let calculate_gas = context.calculate_gas;
@@ -348,19 +1030,25 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         runtime.maybe_setup_profiler(elf);
 
         runtime.write_vecs(&stdin.buffer);
+        runtime.write_input_chunks(stdin.input_chunks.clone());
         for (proof, vkey) in stdin.proofs.iter() {
             runtime.write_proof(proof.clone(), vkey.clone());
         }
         runtime.run_fast()?;
+        runtime.report.input_segments = stdin.segments.clone();
 
+        let mut gas_breakdown = None;
         if calculate_gas {
-            let gas = self.get_gas_calculator(preprocessed_shape.unwrap(), opts.split_opts)(
-                runtime.record_estimator.as_ref().unwrap(),
-            );
-            runtime.report.gas = gas
-                .inspect(|g| tracing::info!("gas: {}", g))
+            gas_breakdown = self
+                .gas_breakdown(
+                    preprocessed_shape.as_ref().unwrap(),
+                    opts.split_opts,
+                    runtime.record_estimator.as_ref().unwrap(),
+                )
+                .inspect(|breakdown| tracing::info!("gas: {}", breakdown.gas))
                 .inspect_err(|e| tracing::error!("Encountered error while calculating gas: {}", e))
                 .ok();
+            runtime.report.gas = gas_breakdown.as_ref().map(|breakdown| breakdown.gas);
         }
 
         let mut committed_value_digest = [0u8; 32];
@@ -372,9 +1060,10 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         );
 
         Ok((
-            SP1PublicValues::from(&runtime.state.public_values_stream),
+            SP1PublicValues::from_vec(std::mem::take(&mut runtime.state.public_values_stream)),
             committed_value_digest,
             runtime.report,
+            gas_breakdown,
         ))
     }
 
@@ -390,9 +1079,96 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         program: Program,
         stdin: &SP1Stdin,
         opts: SP1ProverOpts,
+        context: SP1Context<'a>,
+    ) -> Result<SP1CoreProof, SP1CoreProverError> {
+        self.prove_core_with_key(|| pk_d, program, stdin, opts, context, false)
+    }
+
+    /// Like [`SP1Prover::prove_core`], resuming after the last checkpoint proven to the journal of
+    /// the context instead of starting from cycle zero, see
+    /// [`CoreJournal`](sp1_core_executor::journal::CoreJournal).
+    ///
+    /// A journal is only written to by proofs with a journal in their context: [`Self::prove_core`]
+    /// clears it and starts a new job, while this resumes the job it holds, if any. The journal is
+    /// kept once the proof is done, and should be cleared by the caller when the job is over.
+    ///
+    /// The program, the input and the options must be the same as those of the job that crashed.
+    #[instrument(name = "prove_core_resume", level = "info", skip_all)]
+    pub fn prove_core_resume<'a>(
+        &'a self,
+        pk_d: &DeviceProvingKey<C>,
+        program: Program,
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        context: SP1Context<'a>,
+    ) -> Result<SP1CoreProof, SP1CoreProverError> {
+        if context.core_journal.is_none() {
+            return Err(SP1CoreProverError::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "resuming core proving needs a journal in the context",
+            )));
+        }
+        self.prove_core_with_key(|| pk_d, program, stdin, opts, context, true)
+    }
+
+    /// Like [`SP1Prover::prove_core`], with a device proving key that may still be uploading.
+    ///
+    /// The program starts executing right away, and the key is only waited for once the first
+    /// shard is ready to be proven.
+    pub fn prove_core_with_pending_key<'a>(
+        &'a self,
+        pk_d: &PendingDeviceKey<C>,
+        program: Program,
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        context: SP1Context<'a>,
+    ) -> Result<SP1CoreProof, SP1CoreProverError> {
+        self.prove_core_with_key(|| pk_d.wait(), program, stdin, opts, context, false)
+    }
+
+    fn prove_core_with_key<'a, 'k>(
+        &'a self,
+        pk_d: impl Fn() -> &'k DeviceProvingKey<C> + Send + Sync,
+        program: Program,
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
         mut context: SP1Context<'a>,
+        resume: bool,
     ) -> Result<SP1CoreProof, SP1CoreProverError> {
+        let _metadata_span = context.trace_metadata.span().entered();
+        let _cost = self.cost_meter.stage(&context.trace_metadata, CostStage::Core);
         context.subproof_verifier = Some(self);
+        if let Some(journal) = context.core_journal.filter(|_| !resume) {
+            journal.clear().map_err(SP1CoreProverError::IoError)?;
+        }
+        if context.cancellation.is_none() {
+            context.cancellation = self.cancellation.clone();
+        }
+        if !context.progress.is_enabled() {
+            context.progress = opts.progress.clone();
+        }
+        if context.spill_cipher.is_none() {
+            context.spill_cipher = self.artifact_cipher.as_deref().map(|c| c as &dyn SpillCipher);
+        }
+        if context.io_options.public_values.take().is_some() {
+            tracing::warn!("public values writers are only supported by execute, ignoring it");
+        }
+        FriParamsMismatch::check(
+            "core",
+            opts.fri_opts.core,
+            self.core_prover.config().fri_params(),
+        )
+        .map_err(SP1CoreProverError::FriParams)?;
+        let force_gas = context.calculate_gas && std::env::var("SP1_FORCE_GAS").is_ok();
+        if force_gas && opts.core_opts != gas::GAS_OPTS {
+            self.strict
+                .warn(
+                    WarningClass::GasDivergence,
+                    "The SP1CoreOpts does not match the gas opts. \
+                    Gas will likely disagree with the standard gas calculated when executing.",
+                )
+                .map_err(SP1CoreProverError::Strict)?;
+        }
 
         // Launch two threads to simultaneously prove the core and compile the first few
         // recursion programs in parallel.
@@ -406,30 +1182,21 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             let handle = s.spawn(move || {
                 let _span = span.enter();
 
-                // Copy the proving key to the device.
-                let pk = pk_d;
-
                 // We may calculate gas while proving if the opts match the hardcoded variant.
-                // This ensures that the gas number is consistent between `execute` and `prove_core`.
-                // This behavior is undocumented because it is confusing and not very useful.
+                // This ensures that the gas number is consistent between `execute` and
+                // `prove_core`. This behavior is undocumented because it is
+                // confusing and not very useful.
                 //
                 // If `context.calculate_gas` is set, we use the logic from the `gas` module
                 // after checkpoint execution to print gas as part of the execution report.
                 #[allow(clippy::type_complexity)]
-                let gas_calculator = (context.calculate_gas
-                    && std::env::var("SP1_FORCE_GAS").is_ok())
-                .then(
+                let gas_calculator = force_gas.then(
                     || -> Box<dyn FnOnce(&RecordEstimator) -> Result<u64, Box<dyn Error>> + '_> {
                         tracing::info!("Forcing calculation of gas while proving.");
                         if opts.core_opts == gas::GAS_OPTS {
                             tracing::info!(
                                 "The SP1CoreOpts matches the gas opts, so gas will be consistent."
                             );
-                        } else {
-                            tracing::warn!(
-                                "The SP1CoreOpts does not match the gas opts. \
-                                Gas will likely disagree with the standard gas calculated when executing."
-                            );
                         }
                         let preprocessed_shape = program.preprocessed_shape.clone().unwrap();
                         Box::new(
@@ -441,7 +1208,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 // Prove the core and stream the proofs and shapes.
                 sp1_core_machine::utils::prove_core_stream::<_, C::CoreProver>(
                     &self.core_prover,
-                    pk,
+                    pk_d,
                     program,
                     stdin,
                     opts.core_opts,
@@ -454,7 +1221,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 )
             });
 
-            // Receive the first few shapes and comile the recursion programs.
+            // Receive the first few shapes and compile the recursion programs concurrently.
             for _ in 0..3 {
                 if let Ok((shape, is_complete)) = shape_rx.recv() {
                     let recursion_shape =
@@ -464,34 +1231,343 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                     // case.
                     let compress_shape = SP1CompressProgramShape::Recursion(recursion_shape);
 
-                    // Insert the program into the cache.
-                    self.program_from_shape(compress_shape, None);
+                    // Insert the program into the cache, unless programs cannot be compiled.
+                    if !self.read_only {
+                        s.spawn(move || self.program_from_shape(compress_shape, None));
+                    }
+                }
+            }
+
+            // Collect the shard proofs and the public values stream.
+            let shard_proofs: Vec<ShardProof<_>> = proof_rx.iter().collect();
+            let (public_values_stream, cycles, profile) =
+                handle.join().map_err(|_| SP1CoreProverError::WorkerPanicked)??;
+            let public_values = SP1PublicValues::from(&public_values_stream);
+            Self::check_for_high_cycles(cycles);
+            tracing::info!("slowest core shards:\n{}", profile.summary());
+            lock_or_reset(&self.profile, |p| *p = ProvingProfile::default()).merge(profile);
+            Ok(SP1CoreProof {
+                proof: SP1CoreProofData(shard_proofs),
+                stdin: stdin.clone(),
+                public_values,
+                cycles,
+            })
+        })
+    }
+
+    /// Reduce shards proofs to a single shard proof using the recursion prover.
+    ///
+    /// The shard proofs of `proof` may be in any order, see [`Self::get_first_layer_inputs`].
+    ///
+    /// A core proof with no shards only folds `deferred_proofs`, without executing the program.
+    /// The result is not complete, and is verified with [`Self::verify_deferred_only`].
+    #[instrument(name = "compress", level = "info", skip_all)]
+    pub fn compress(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.compress_with_recorder(
+            vk,
+            proof,
+            deferred_proofs,
+            [BabyBear::zero(); DIGEST_SIZE],
+            opts,
+            None,
+            None,
+        )
+    }
+
+    /// Reduce shard proofs to a single shard proof, chaining the deferred digest from
+    /// `initial_deferred_digest` instead of zero.
+    ///
+    /// `deferred_proofs` are the proofs the program verified after the ones already hashed into
+    /// `initial_deferred_digest`, for example by [`Self::hash_deferred_proofs`] over proofs
+    /// verified in a separately produced compressed proof. With a zero digest, this is
+    /// [`Self::compress`]. Otherwise the proof is not complete, since a complete proof starts its
+    /// deferred digest chain from zero, and it is verified with
+    /// [`Self::verify_compressed_with_initial_deferred_digest`].
+    #[instrument(name = "compress", level = "info", skip_all)]
+    pub fn compress_with_initial_deferred_digest(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        initial_deferred_digest: [BabyBear; DIGEST_SIZE],
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.compress_with_recorder(
+            vk,
+            proof,
+            deferred_proofs,
+            initial_deferred_digest,
+            opts,
+            None,
+            None,
+        )
+    }
+
+    /// Reduce shards proofs to a single shard proof, recording the proofs of the recursion tree in
+    /// `recorder` and persisting its layers with `state`, if any.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn compress_with_recorder(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        initial_deferred_digest: [BabyBear; DIGEST_SIZE],
+        opts: SP1ProverOpts,
+        recorder: Option<&Mutex<CheckpointRecorder>>,
+        state: Option<StateWriter<'_>>,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.check_cancelled()?;
+        self.check_vk_shape_config(vk)?;
+        self.check_vk_artifacts(vk)?;
+
+        // The batch size for reducing the first layer of recursion.
+        let first_layer_batch_size = self.try_first_layer_batch_size(&opts)?;
+
+        let shard_proofs = &proof.proof.0;
+
+        // Generate the first layer inputs.
+        let first_layer_inputs = self.get_first_layer_inputs_with_initial_digest(
+            vk,
+            shard_proofs,
+            &deferred_proofs,
+            initial_deferred_digest,
+            first_layer_batch_size,
+            &opts.deferred_opts,
+        )?;
+        let num_first_layer_inputs = first_layer_inputs.len();
+        self.check_installed_join_programs()?;
+        self.check_join_programs_precompiled()?;
+        self.check_installed_programs(
+            first_layer_inputs.iter().map(|input| self.witness_program_shape(input)),
+        )?;
+
+        // A proof of deferred proofs alone does not cover any execution, so it is never complete.
+        let is_root =
+            initial_deferred_digest == [BabyBear::zero(); DIGEST_SIZE] && !shard_proofs.is_empty();
+
+        // A program that fits in a single shard has no tree to schedule, unless its proofs are
+        // recorded.
+        if num_first_layer_inputs == 1 && recorder.is_none() && state.is_none() {
+            let input = first_layer_inputs.into_iter().next().unwrap();
+            let (vk, proof) = self.reduce_single_input(input, is_root, &opts)?;
+            return Ok(SP1ReduceProof { vk, proof });
+        }
+
+        let (vk, proof) = self.reduce_tree_with_recorder(
+            first_layer_inputs.into_iter().map(|input| (input, false)),
+            num_first_layer_inputs,
+            is_root,
+            opts,
+            &*self.reduction_strategy,
+            recorder,
+            state,
+        )?;
+
+        Ok(SP1ReduceProof { vk, proof })
+    }
+
+    /// Reduce shard proofs to a single shard proof, verifying deferred proofs that are supplied
+    /// while compression is running.
+    ///
+    /// `deferred_proofs` must yield the deferred proofs in the order the program verified them. The
+    /// core shards are reduced into their own subtree while the deferred proofs arrive, and each
+    /// batch of deferred proofs is proven as soon as it is complete. The two are joined once the
+    /// iterator is exhausted, so compression only stalls on deferred proofs at the very end.
+    ///
+    /// Deferred proofs are always batched in input order, with the batch size taken from
+    /// `opts.deferred_opts`.
+    #[instrument(name = "compress_streaming", level = "info", skip_all)]
+    pub fn compress_streaming(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: impl IntoIterator<Item = SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.check_cancelled()?;
+        self.check_vk_shape_config(vk)?;
+        self.check_vk_artifacts(vk)?;
+        let shard_proofs = order_shard_proofs(&proof.proof.0)?;
+
+        // The core proof commits to the digest of all the deferred proofs it verified, so the core
+        // inputs can be generated without waiting for the deferred proofs.
+        let last_shard_proof = shard_proofs.last().ok_or(ShardOrderError::Empty)?;
+        let last_public_values: &PublicValues<Word<BabyBear>, BabyBear> =
+            last_shard_proof.public_values.as_slice().borrow();
+        let deferred_digest = last_public_values.deferred_proofs_digest;
+        let mut deferred_proofs = deferred_proofs.into_iter();
+        if deferred_digest == [BabyBear::zero(); DIGEST_SIZE] {
+            // The program verified no deferred proofs, so none can be folded into its proof.
+            if deferred_proofs.next().is_some() {
+                return Err(SP1RecursionProverError::DeferredDigestMismatch);
+            }
+            return self.compress(vk, proof, vec![], opts);
+        }
+
+        self.check_installed_join_programs()?;
+        self.check_join_programs_precompiled()?;
+        let first_layer_batch_size = self.try_first_layer_batch_size(&opts)?;
+        let deferred_batch_size = opts.deferred_opts.batch_size.max(1);
+        let span = tracing::Span::current().clone();
+        let (core_root, deferred_leaves) = thread::scope(|s| {
+            let _span = span.enter();
+
+            // Reduce the core shards into a single, incomplete, proof.
+            let core_handle = s.spawn(|| {
+                let core_inputs = self.get_recursion_core_inputs(
+                    &vk.vk,
+                    &shard_proofs,
+                    first_layer_batch_size,
+                    false,
+                    deferred_digest,
+                );
+                let num_core_inputs = core_inputs.len();
+                self.check_installed_programs(
+                    core_inputs
+                        .iter()
+                        .map(|input| SP1CompressProgramShape::Recursion(input.shape())),
+                )?;
+                self.reduce_tree(
+                    core_inputs.into_iter().map(|input| (SP1CircuitWitness::Core(input), false)),
+                    num_core_inputs,
+                    false,
+                    opts.clone(),
+                )
+            });
+
+            // Prove each batch of deferred proofs as it arrives, chaining the deferred digest.
+            let mut reconstructed_digest = [BabyBear::zero(); DIGEST_SIZE];
+            let mut deferred_leaves = Vec::new();
+            loop {
+                let batch = deferred_proofs.by_ref().take(deferred_batch_size).collect::<Vec<_>>();
+                if batch.is_empty() {
+                    break;
                 }
+                let (inputs, next_digest) = self.get_recursion_deferred_inputs_from_batches(
+                    &vk.vk,
+                    [batch.as_slice()],
+                    reconstructed_digest,
+                )?;
+                reconstructed_digest = next_digest;
+                self.check_installed_programs(inputs.iter().map(|input| {
+                    SP1CompressProgramShape::Deferred(self.deferred_program_shape(input))
+                }))?;
+                let leaf = self.reduce_tree(
+                    inputs.into_iter().map(|input| (SP1CircuitWitness::Deferred(input), false)),
+                    1,
+                    false,
+                    opts.clone(),
+                )?;
+                deferred_leaves.push(leaf);
             }
 
-            // Collect the shard proofs and the public values stream.
-            let shard_proofs: Vec<ShardProof<_>> = proof_rx.iter().collect();
-            let (public_values_stream, cycles) = handle.join().unwrap().unwrap();
-            let public_values = SP1PublicValues::from(&public_values_stream);
-            Self::check_for_high_cycles(cycles);
-            Ok(SP1CoreProof {
-                proof: SP1CoreProofData(shard_proofs),
-                stdin: stdin.clone(),
-                public_values,
-                cycles,
-            })
-        })
+            let core_root =
+                core_handle.join().map_err(|_| SP1RecursionProverError::WorkerPanicked)??;
+            if reconstructed_digest != deferred_digest {
+                return Err(SP1RecursionProverError::DeferredDigestMismatch);
+            }
+            Ok((core_root, deferred_leaves))
+        })?;
+
+        // Join the deferred proofs and the core subtree into the final proof.
+        let leaves =
+            deferred_leaves.into_iter().chain(std::iter::once(core_root)).collect::<Vec<_>>();
+        let num_leaves = leaves.len();
+        let (vk, proof) = self.reduce_tree(
+            leaves.into_iter().map(|(vk, proof)| {
+                let input = SP1CompressWitnessValues {
+                    vks_and_proofs: vec![(vk, proof)],
+                    is_complete: false,
+                };
+                (SP1CircuitWitness::Compress(input), true)
+            }),
+            num_leaves,
+            true,
+            opts,
+        )?;
+
+        Ok(SP1ReduceProof { vk, proof })
     }
 
-    /// Reduce shards proofs to a single shard proof using the recursion prover.
-    #[instrument(name = "compress", level = "info", skip_all)]
-    pub fn compress(
+    /// Recursively reduce the first layer inputs into a single proof.
+    ///
+    /// Each input is paired with a flag indicating whether it is already proven, in which case it
+    /// must be a [`SP1CircuitWitness::Compress`] holding a single proof, which is passed through to
+    /// the next layer as is. If `is_root` is set, the last proof of the tree is marked as complete.
+    fn reduce_tree<I>(
         &self,
-        vk: &SP1VerifyingKey,
-        proof: SP1CoreProof,
-        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        first_layer_inputs: I,
+        num_first_layer_inputs: usize,
+        is_root: bool,
         opts: SP1ProverOpts,
-    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+    ) -> Result<(StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>), SP1RecursionProverError>
+    where
+        I: IntoIterator<Item = (SP1CircuitWitness, bool)>,
+        I::IntoIter: Send,
+    {
+        self.reduce_tree_with_strategy(
+            first_layer_inputs,
+            num_first_layer_inputs,
+            is_root,
+            opts,
+            &*self.reduction_strategy,
+        )
+    }
+
+    /// Recursively reduce the first layer inputs into a single proof, see [`Self::reduce_tree`],
+    /// with a tree shaped by `strategy` instead of the reduction strategy of the handle.
+    pub(crate) fn reduce_tree_with_strategy<I>(
+        &self,
+        first_layer_inputs: I,
+        num_first_layer_inputs: usize,
+        is_root: bool,
+        opts: SP1ProverOpts,
+        strategy: &dyn ReductionStrategy,
+    ) -> Result<(StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>), SP1RecursionProverError>
+    where
+        I: IntoIterator<Item = (SP1CircuitWitness, bool)>,
+        I::IntoIter: Send,
+    {
+        self.reduce_tree_with_recorder(
+            first_layer_inputs,
+            num_first_layer_inputs,
+            is_root,
+            opts,
+            strategy,
+            None,
+            None,
+        )
+    }
+
+    /// Recursively reduce the first layer inputs into a single proof with a tree shaped by
+    /// `strategy`, see [`Self::reduce_tree`], recording every proof of the tree and the joins
+    /// producing them in `recorder`, and persisting each completed layer with `state`, if any.
+    pub(crate) fn reduce_tree_with_recorder<I>(
+        &self,
+        first_layer_inputs: I,
+        num_first_layer_inputs: usize,
+        is_root: bool,
+        opts: SP1ProverOpts,
+        strategy: &dyn ReductionStrategy,
+        recorder: Option<&Mutex<CheckpointRecorder>>,
+        mut state: Option<StateWriter<'_>>,
+    ) -> Result<(StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>), SP1RecursionProverError>
+    where
+        I: IntoIterator<Item = (SP1CircuitWitness, bool)>,
+        I::IntoIter: Send,
+    {
+        FriParamsMismatch::check(
+            "compress",
+            opts.fri_opts.compress,
+            self.compress_prover.config().fri_params(),
+        )?;
+
         #[allow(clippy::type_complexity)]
         enum TracesOrInput {
             ProgramRecordTraces(
@@ -499,34 +1575,48 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                     Arc<RecursionProgram<BabyBear>>,
                     ExecutionRecord<BabyBear>,
                     Vec<(String, RowMajorMatrix<BabyBear>)>,
+                    StageTimings,
                 )>,
             ),
             CircuitWitness(Box<SP1CircuitWitness>),
+            /// An input skipped since proving was cancelled or a worker failed.
+            Skipped,
         }
 
         // The batch size for reducing two layers of recursion.
-        let batch_size = REDUCE_BATCH_SIZE;
-        // The batch size for reducing the first layer of recursion.
-        let first_layer_batch_size = 1;
-
-        let shard_proofs = &proof.proof.0;
-
-        // Generate the first layer inputs.
-        let first_layer_inputs =
-            self.get_first_layer_inputs(vk, shard_proofs, &deferred_proofs, first_layer_batch_size);
-
-        // Calculate the expected height of the tree.
-        let mut expected_height = if first_layer_inputs.len() == 1 { 0 } else { 1 };
-        let num_first_layer_inputs = first_layer_inputs.len();
-        let mut num_layer_inputs = num_first_layer_inputs;
-        while num_layer_inputs > batch_size {
-            num_layer_inputs = num_layer_inputs.div_ceil(2);
-            expected_height += 1;
-        }
+        let batch_size = self.try_compress_arity(&opts)?;
+
+        // Plan the joins of the tree.
+        let plan = strategy.plan(num_first_layer_inputs, batch_size);
+        plan.validate(num_first_layer_inputs, batch_size)
+            .map_err(SP1RecursionProverError::InvalidReductionPlan)?;
+        let plan = &plan;
+        let heights = plan.heights();
+        let first_inputs = plan.first_inputs();
+        let compress_tree = Mutex::new(CompressTree::new(plan, batch_size));
+        let tree = &compress_tree;
+
+        // Tag the work items of this call in the shared worker pool.
+        let job = self.worker_pool.job();
+
+        // The layers are only reported for the tree of the final proof.
+        let start = Instant::now();
+        let progress = &opts.progress;
+
+        // The first error of the workers, if any.
+        let first_failure = OnceLock::new();
+        let failure = &first_failure;
+
+        // Once cancelled or failed, the workers still take the turns of their inputs so that the
+        // tree drains, but skip proving them.
+        let stopped = || self.cancelled() || failure.get().is_some();
+        let disconnected = || {
+            let _ = failure.set(SP1RecursionProverError::WorkerDisconnected);
+        };
 
         // Generate the proofs.
         let span = tracing::Span::current().clone();
-        let (vk, proof) = thread::scope(|s| {
+        let root = thread::scope(|s| {
             let _span = span.enter();
 
             // Spawn a worker that sends the first layer inputs to a bounded channel.
@@ -535,16 +1625,25 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 opts.recursion_opts.checkpoints_channel_capacity,
             );
             let input_tx = Arc::new(Mutex::new(input_tx));
+            let mut handles = Vec::new();
             {
                 let input_tx = Arc::clone(&input_tx);
                 let input_sync = Arc::clone(&input_sync);
-                s.spawn(move || {
-                    for (index, input) in first_layer_inputs.into_iter().enumerate() {
+                let first_layer_inputs = first_layer_inputs.into_iter();
+                handles.push(s.spawn(move || {
+                    // Once stopped, the turns are still taken so that the next layer generator
+                    // is not blocked on them, but the inputs are dropped.
+                    for (index, (input, is_proven)) in first_layer_inputs.enumerate() {
                         input_sync.wait_for_turn(index);
-                        input_tx.lock().unwrap().send((index, 0, input, false)).unwrap();
+                        if !stopped() &&
+                            input_tx.lock().unwrap().send((index, 0, input, is_proven)).is_err()
+                        {
+                            disconnected();
+                        }
+                        tree.lock().unwrap().node_ready(index, start.elapsed());
                         input_sync.advance_turn();
                     }
-                });
+                }));
             }
 
             // Spawn workers who generate the records and traces.
@@ -556,63 +1655,145 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             let record_and_trace_tx = Arc::new(Mutex::new(record_and_trace_tx));
             let record_and_trace_rx = Arc::new(Mutex::new(record_and_trace_rx));
             let input_rx = Arc::new(Mutex::new(input_rx));
+            // The records proven are given back to the arenas of the workers, so that their
+            // buffers are reused by the next nodes.
+            let (recycled_tx, recycled_rx) =
+                sync_channel::<ExecutionRecord<BabyBear>>(opts.recursion_opts.trace_gen_workers);
+            let recycled_rx = Arc::new(Mutex::new(recycled_rx));
             for _ in 0..opts.recursion_opts.trace_gen_workers {
                 let record_and_trace_sync = Arc::clone(&record_and_trace_sync);
                 let record_and_trace_tx = Arc::clone(&record_and_trace_tx);
                 let input_rx = Arc::clone(&input_rx);
+                let recycled_rx = Arc::clone(&recycled_rx);
                 let span = tracing::debug_span!("generate records and traces");
-                s.spawn(move || {
+                handles.push(s.spawn(move || {
                     let _span = span.enter();
+                    let mut arena = RecursionArena::new();
+
+                    // Send the traces or input of a node to the provers, in the order of the nodes.
+                    let send = |index, height, traces| {
+                        record_and_trace_sync.wait_for_turn(index);
+                        if record_and_trace_tx
+                            .lock()
+                            .unwrap()
+                            .send((index, height, traces))
+                            .is_err()
+                        {
+                            disconnected();
+                        }
+                        record_and_trace_sync.advance_turn();
+                    };
                     loop {
                         let received = { input_rx.lock().unwrap().recv() };
-                        if let Ok((index, height, input, false)) = received {
-                            // Get the program and witness stream.
-                            let (program, witness_stream) = tracing::debug_span!(
-                                "get program and witness stream"
-                            )
-                            .in_scope(|| match input {
-                                SP1CircuitWitness::Core(input) => {
-                                    let mut witness_stream = Vec::new();
-                                    Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
-                                    (self.recursion_program(&input), witness_stream)
-                                }
-                                SP1CircuitWitness::Deferred(input) => {
-                                    let mut witness_stream = Vec::new();
-                                    Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
-                                    (self.deferred_program(&input), witness_stream)
-                                }
-                                SP1CircuitWitness::Compress(input) => {
-                                    let mut witness_stream = Vec::new();
-
-                                    let input_with_merkle = self.make_merkle_proofs(input);
-
-                                    Witnessable::<InnerConfig>::write(
-                                        &input_with_merkle,
-                                        &mut witness_stream,
+                        if let (Ok((index, height, ..)), true) = (&received, stopped()) {
+                            // Skip the input once stopped, passing a marker to the provers so that
+                            // they still take its turn.
+                            if self.cancelled() {
+                                let _ = failure.set(SP1RecursionProverError::Cancelled);
+                            }
+                            send(*index, *height, TracesOrInput::Skipped);
+                        } else if let Ok((index, height, mut input, false)) = received {
+                            // A compress input with a verifying key outside of the vk map cannot
+                            // be proven. The error is recorded and the first proof of the input is
+                            // passed through instead, so that the rest of the tree drains.
+                            if let SP1CircuitWitness::Compress(compress) = &mut input {
+                                let vk_root = committed_vk_root(
+                                    compress.vks_and_proofs.first().map(|(_, proof)| proof),
+                                );
+                                if let Err(e) =
+                                    compress.vks_and_proofs.iter().try_for_each(|(vk, _)| {
+                                        self.recursion_vk_leaf_under(vk, &vk_root).map(drop)
+                                    })
+                                {
+                                    let _ = failure.set(SP1RecursionProverError::from(e));
+                                    compress.vks_and_proofs.truncate(1);
+                                    send(
+                                        index,
+                                        height,
+                                        TracesOrInput::CircuitWitness(Box::new(input)),
                                     );
+                                    continue;
+                                }
+                            }
 
-                                    (self.compress_program(&input_with_merkle), witness_stream)
+                            let is_complete = match &input {
+                                SP1CircuitWitness::Core(input) => input.is_complete,
+                                SP1CircuitWitness::Deferred(_) => false,
+                                SP1CircuitWitness::Compress(input) => input.is_complete,
+                            };
+
+                            // Get the program and witness stream. A program that is not cached
+                            // is compiled on the compiler pool, which is waited for without
+                            // holding a trace generation permit.
+                            let (program, witness_stream) =
+                                match tracing::debug_span!("get program and witness stream")
+                                    .in_scope(|| self.program_and_witness_stream(input))
+                                {
+                                    Ok(program_and_witness_stream) => program_and_witness_stream,
+                                    Err(e) => {
+                                        let _ = failure.set(SP1RecursionProverError::from(e));
+                                        send(index, height, TracesOrInput::Skipped);
+                                        continue;
+                                    }
+                                };
+
+                            // Wait for the active part of the duty cycle, if any.
+                            self.throttle();
+                            let permit = self.worker_pool.acquire(WorkerKind::TraceGen, job);
+
+                            // With fixed shapes, the shape of the root proof is the shape of its
+                            // program, so the shrink program can be set up while it is proven.
+                            if is_root && is_complete && self.compress_shape_config.is_some() {
+                                if let Some(shape) = program.shape.clone() {
+                                    let shape = SP1CompressWithVkeyShape {
+                                        compress_shape: vec![OrderedShape::from(shape)].into(),
+                                        merkle_tree_height: self.recursion_vk_tree.height,
+                                        merkle_tree_config: self.recursion_vk_tree.config,
+                                    };
+                                    let program_shape =
+                                        SP1CompressProgramShape::Shrink(shape.clone());
+                                    if self.check_installed_programs([program_shape]).is_ok() {
+                                        self.start_shrink_setup(shape);
+                                    }
                                 }
-                            });
+                            }
 
                             // Execute the runtime.
-                            let record = tracing::debug_span!("execute runtime").in_scope(|| {
-                                let mut runtime =
-                                    RecursionRuntime::<Val<InnerSC>, Challenge<InnerSC>, _>::new(
+                            let execute_start = Instant::now();
+                            let record: Result<_, SP1RecursionProverError> =
+                                tracing::debug_span!("execute runtime").in_scope(|| {
+                                    if let Ok(record) = recycled_rx.lock().unwrap().try_recv() {
+                                        arena.recycle(record);
+                                    }
+                                    let mut runtime = RecursionRuntime::<
+                                        Val<InnerSC>,
+                                        Challenge<InnerSC>,
+                                        _,
+                                    >::new_in(
                                         program.clone(),
                                         self.compress_prover.config().perm.clone(),
+                                        &mut arena,
                                     );
-                                runtime.witness_stream = witness_stream.into();
-                                runtime
-                                    .run()
-                                    .map_err(|e| {
+                                    runtime.witness_stream = witness_stream.into();
+                                    runtime.run().map_err(|e| {
                                         SP1RecursionProverError::RuntimeError(e.to_string())
-                                    })
-                                    .unwrap();
-                                runtime.record
-                            });
+                                    })?;
+                                    Ok(arena.reclaim(runtime))
+                                });
+                            let record = match record {
+                                Ok(record) => record,
+                                Err(e) => {
+                                    let _ = failure.set(e);
+                                    drop(permit);
+                                    send(index, height, TracesOrInput::Skipped);
+                                    continue;
+                                }
+                            };
+
+                            let execute = execute_start.elapsed();
 
                             // Generate the dependencies.
+                            let trace_gen_start = Instant::now();
                             let mut records = vec![record];
                             tracing::debug_span!("generate dependencies").in_scope(|| {
                                 self.compress_prover.machine().generate_dependencies(
@@ -626,61 +1807,43 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                             let record = records.into_iter().next().unwrap();
                             let traces = tracing::debug_span!("generate traces")
                                 .in_scope(|| self.compress_prover.generate_traces(&record));
-
-                            // Wait for our turn to update the state.
-                            record_and_trace_sync.wait_for_turn(index);
-
-                            // Send the record and traces to the worker.
-                            record_and_trace_tx
-                                .lock()
-                                .unwrap()
-                                .send((
-                                    index,
-                                    height,
-                                    TracesOrInput::ProgramRecordTraces(Box::new((
-                                        program, record, traces,
-                                    ))),
-                                ))
-                                .unwrap();
-
-                            // Advance the turn.
-                            record_and_trace_sync.advance_turn();
+                            let timings = StageTimings {
+                                execute,
+                                trace_gen: trace_gen_start.elapsed(),
+                                ..StageTimings::default()
+                            };
+                            drop(permit);
+
+                            // Send the record and traces to the provers.
+                            send(
+                                index,
+                                height,
+                                TracesOrInput::ProgramRecordTraces(Box::new((
+                                    program, record, traces, timings,
+                                ))),
+                            );
                         } else if let Ok((index, height, input, true)) = received {
-                            record_and_trace_sync.wait_for_turn(index);
-
-                            // Send the record and traces to the worker.
-                            record_and_trace_tx
-                                .lock()
-                                .unwrap()
-                                .send((
-                                    index,
-                                    height,
-                                    TracesOrInput::CircuitWitness(Box::new(input)),
-                                ))
-                                .unwrap();
-
-                            // Advance the turn.
-                            record_and_trace_sync.advance_turn();
+                            send(index, height, TracesOrInput::CircuitWitness(Box::new(input)));
                         } else {
                             break;
                         }
                     }
-                });
+                }));
             }
 
             // Spawn workers who generate the compress proofs.
             let proofs_sync = Arc::new(TurnBasedSync::new());
             let (proofs_tx, proofs_rx) =
                 sync_channel::<(usize, usize, StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>)>(
-                    num_first_layer_inputs * 2,
+                    num_first_layer_inputs + plan.joins.len(),
                 );
             let proofs_tx = Arc::new(Mutex::new(proofs_tx));
             let proofs_rx = Arc::new(Mutex::new(proofs_rx));
-            let mut prover_handles = Vec::new();
-            for _ in 0..opts.recursion_opts.shard_batch_size {
+            for worker in 0..opts.recursion_opts.shard_batch_size {
                 let prover_sync = Arc::clone(&proofs_sync);
                 let record_and_trace_rx = Arc::clone(&record_and_trace_rx);
                 let proofs_tx = Arc::clone(&proofs_tx);
+                let recycled_tx = recycled_tx.clone();
                 let span = tracing::debug_span!("prove");
                 let handle = s.spawn(move || {
                     let _span = span.enter();
@@ -689,8 +1852,17 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                         if let Ok((index, height, TracesOrInput::ProgramRecordTraces(boxed_prt))) =
                             received
                         {
-                            let (program, record, traces) = *boxed_prt;
+                            let (program, record, traces, timings) = *boxed_prt;
+                            if stopped() {
+                                prover_sync.wait_for_turn(index);
+                                prover_sync.advance_turn();
+                                continue;
+                            }
                             tracing::debug_span!("batch").in_scope(|| {
+                                // Wait for the active part of the duty cycle, if any.
+                                self.throttle();
+                                let permit = self.worker_pool.acquire(WorkerKind::Prove, job);
+
                                 // Get the keys.
                                 let (pk, vk) = tracing::debug_span!("Setup compress program")
                                     .in_scope(|| self.compress_prover.setup(&program));
@@ -709,13 +1881,44 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                                 );
 
                                 // Commit to the record and traces.
+                                let commit_start = Instant::now();
                                 let data = tracing::debug_span!("commit")
                                     .in_scope(|| self.compress_prover.commit(&record, traces));
+                                let commit = commit_start.elapsed();
+                                let _ = recycled_tx.try_send(record);
 
                                 // Generate the proof.
+                                let open_start = Instant::now();
                                 let proof = tracing::debug_span!("open").in_scope(|| {
-                                    self.compress_prover.open(&pk, data, &mut challenger).unwrap()
+                                    self.compress_prover.open(&pk, data, &mut challenger)
                                 });
+                                let proof = match proof {
+                                    Ok(proof) => proof,
+                                    Err(e) => {
+                                        let _ = failure.set(SP1RecursionProverError::ProveFailed(
+                                            e.to_string(),
+                                        ));
+                                        drop(permit);
+                                        prover_sync.wait_for_turn(index);
+                                        prover_sync.advance_turn();
+                                        return;
+                                    }
+                                };
+                                let timings =
+                                    StageTimings { commit, open: open_start.elapsed(), ..timings };
+                                tree.lock().unwrap().node_proven_by(
+                                    index,
+                                    worker,
+                                    proof.shape(),
+                                    timings,
+                                );
+                                lock_or_reset(&self.profile, |p| *p = ProvingProfile::default())
+                                    .record_node(ShardProfile {
+                                        layer: height,
+                                        index,
+                                        shape: proof.shape(),
+                                        timings,
+                                    });
 
                                 // Verify the proof.
                                 #[cfg(feature = "debug")]
@@ -729,12 +1932,20 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                                         &mut self.compress_prover.config().challenger(),
                                     )
                                     .unwrap();
+                                drop(permit);
 
                                 // Wait for our turn to update the state.
                                 prover_sync.wait_for_turn(index);
 
                                 // Send the proof.
-                                proofs_tx.lock().unwrap().send((index, height, vk, proof)).unwrap();
+                                if proofs_tx
+                                    .lock()
+                                    .unwrap()
+                                    .send((index, height, vk, proof))
+                                    .is_err()
+                                {
+                                    disconnected();
+                                }
 
                                 // Advance the turn.
                                 prover_sync.advance_turn();
@@ -755,97 +1966,147 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                                 prover_sync.wait_for_turn(index);
 
                                 // Send the proof.
-                                proofs_tx
+                                if proofs_tx
                                     .lock()
                                     .unwrap()
                                     .send((index, height, vk.clone(), proof.clone()))
-                                    .unwrap();
+                                    .is_err()
+                                {
+                                    disconnected();
+                                }
 
                                 // Advance the turn.
                                 prover_sync.advance_turn();
                             }
+                        } else if let Ok((index, _, TracesOrInput::Skipped)) = received {
+                            prover_sync.wait_for_turn(index);
+                            prover_sync.advance_turn();
                         } else {
                             break;
                         }
                     }
                 });
-                prover_handles.push(handle);
+                handles.push(handle);
             }
 
-            // Spawn a worker that generates inputs for the next layer.
+            // Spawn a worker that sends the joins of the plan, each once its inputs are proven.
             let handle = {
                 let input_tx = Arc::clone(&input_tx);
                 let proofs_rx = Arc::clone(&proofs_rx);
                 let span = tracing::debug_span!("generate next layer inputs");
                 s.spawn(move || {
                     let _span = span.enter();
-                    let mut count = num_first_layer_inputs;
-                    let mut batch: Vec<(
-                        usize,
-                        usize,
-                        StarkVerifyingKey<InnerSC>,
-                        ShardProof<InnerSC>,
-                    )> = Vec::new();
-                    loop {
-                        if expected_height == 0 {
-                            break;
+                    let mut proven = BTreeMap::new();
+                    let mut next_join = 0;
+
+                    // The number of nodes left to prove in each layer, and the first layer not
+                    // completed yet.
+                    let mut layer_sizes = vec![0; heights.last().map_or(0, |h| h + 1)];
+                    for &height in heights.iter() {
+                        layer_sizes[height] += 1;
+                    }
+                    let num_proofs = layer_sizes.clone();
+                    let mut next_layer = 0;
+                    while next_join < plan.joins.len() {
+                        // Poll the proofs so that the inputs channel is dropped once stopped, which
+                        // shuts down the workers.
+                        let received = loop {
+                            if self.cancelled() {
+                                let _ = failure.set(SP1RecursionProverError::Cancelled);
+                            }
+                            if stopped() {
+                                break Err(RecvTimeoutError::Disconnected);
+                            }
+                            match proofs_rx.lock().unwrap().recv_timeout(CANCELLATION_POLL_INTERVAL)
+                            {
+                                Err(RecvTimeoutError::Timeout) => continue,
+                                received => break received,
+                            }
+                        };
+                        let Ok((index, height, vk, proof)) = received else { break };
+                        tree.lock().unwrap().node_proven(index, start.elapsed());
+                        if let Some(recorder) = recorder {
+                            recorder.lock().unwrap().record_proof(index, height, &vk, &proof);
                         }
-                        let received = { proofs_rx.lock().unwrap().recv() };
-                        if let Ok((index, height, vk, proof)) = received {
-                            batch.push((index, height, vk, proof));
-
-                            // If we haven't reached the batch size, continue.
-                            if batch.len() < batch_size {
-                                continue;
+                        strategy.node_proven(index, height, &proof);
+
+                        // Persist the unreduced proofs and report the progress once a layer below
+                        // the root is completed.
+                        if let Some(state) = state.as_mut() {
+                            let joined = index
+                                .checked_sub(num_first_layer_inputs)
+                                .map(|join| plan.joins[join].iter().map(|&i| first_inputs[i]))
+                                .into_iter()
+                                .flatten();
+                            state.record_proof(first_inputs[index], joined, &vk, &proof);
+                        }
+                        layer_sizes[heights[index]] -= 1;
+                        while next_layer + 1 < layer_sizes.len() && layer_sizes[next_layer] == 0 {
+                            if let Some(state) = state.as_ref() {
+                                state.layer_completed(next_layer);
                             }
-
-                            // Compute whether we're at the last input of a layer.
-                            let mut is_last = false;
-                            if let Some(first) = batch.first() {
-                                is_last = first.1 != height;
+                            if is_root {
+                                progress.report(ProgressEvent::LayerCompleted {
+                                    layer: next_layer,
+                                    num_proofs: num_proofs[next_layer],
+                                    elapsed: start.elapsed(),
+                                });
                             }
+                            next_layer += 1;
+                        }
+                        proven.insert(index, (height, vk, proof));
 
-                            // If we're at the last input of a layer, we need to only include the
-                            // first input, otherwise we include all inputs.
-                            let inputs =
-                                if is_last { vec![batch[0].clone()] } else { batch.clone() };
-
-                            let next_input_height = inputs[0].1 + 1;
-
-                            let is_complete = next_input_height == expected_height;
+                        // Send the joins whose inputs are all proven, in the order of the plan.
+                        while let Some(inputs) = plan
+                            .joins
+                            .get(next_join)
+                            .filter(|inputs| inputs.iter().all(|i| proven.contains_key(i)))
+                        {
+                            let count = num_first_layer_inputs + next_join;
+
+                            // A join of a single input passes it through without proving it.
+                            let is_last = inputs.len() == 1;
+                            let is_complete = is_root && next_join + 1 == plan.joins.len();
+
+                            if let Some(recorder) = recorder {
+                                recorder.lock().unwrap().record_join(
+                                    count,
+                                    inputs.clone(),
+                                    is_complete,
+                                    is_last,
+                                );
+                            }
+                            strategy.join_started(count, inputs);
 
+                            let mut next_input_height = 0;
                             let vks_and_proofs = inputs
-                                .into_iter()
-                                .map(|(_, _, vk, proof)| (vk, proof))
+                                .iter()
+                                .map(|i| {
+                                    let (height, vk, proof) = proven.remove(i).unwrap();
+                                    next_input_height = next_input_height.max(height + 1);
+                                    (vk, proof)
+                                })
                                 .collect::<Vec<_>>();
                             let input = SP1CircuitWitness::Compress(SP1CompressWitnessValues {
                                 vks_and_proofs,
                                 is_complete,
                             });
 
+                            // The first layer inputs skipped once stopped are never proven, so the
+                            // joins after them are dropped as well.
                             input_sync.wait_for_turn(count);
-                            input_tx
-                                .lock()
-                                .unwrap()
-                                .send((count, next_input_height, input, is_last))
-                                .unwrap();
-                            input_sync.advance_turn();
-                            count += 1;
-
-                            // If we're at the root of the tree, stop generating inputs.
-                            if is_complete {
-                                break;
-                            }
-
-                            // If we were at the last input of a layer, we keep everything but the
-                            // first input. Otherwise, we empty the batch.
-                            if is_last {
-                                batch = vec![batch[1].clone()];
-                            } else {
-                                batch = Vec::new();
+                            if !stopped() &&
+                                input_tx
+                                    .lock()
+                                    .unwrap()
+                                    .send((count, next_input_height, input, is_last))
+                                    .is_err()
+                            {
+                                disconnected();
                             }
-                        } else {
-                            break;
+                            tree.lock().unwrap().node_ready(count, start.elapsed());
+                            input_sync.advance_turn();
+                            next_join += 1;
                         }
                     }
                 })
@@ -856,17 +2117,149 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             drop(record_and_trace_tx);
             drop(proofs_tx);
 
-            for handle in prover_handles {
-                handle.join().unwrap();
+            for handle in handles.into_iter().chain([handle]) {
+                if handle.join().is_err() {
+                    let _ = failure.set(SP1RecursionProverError::WorkerPanicked);
+                }
             }
-            handle.join().unwrap();
             tracing::debug!("joined handles");
 
-            let (_, _, vk, proof) = proofs_rx.lock().unwrap().recv().unwrap();
-            (vk, proof)
+            // Once cancelled, there may be no root.
+            let (index, height, vk, proof) = proofs_rx.lock().unwrap().recv().ok()?;
+            tree.lock().unwrap().node_proven(index, start.elapsed());
+            if let Some(recorder) = recorder {
+                recorder.lock().unwrap().record_proof(index, height, &vk, &proof);
+            }
+            strategy.node_proven(index, height, &proof);
+            Some((vk, proof))
         });
 
-        Ok(SP1ReduceProof { vk, proof })
+        if is_root {
+            *lock_or_reset(&self.compress_tree, |tree| *tree = None) =
+                Some(compress_tree.into_inner().unwrap());
+            tracing::info!("slowest shards and recursion nodes:\n{}", self.profile().summary());
+            tracing::debug!("trace buffer reuse rate: {:.2}", self.trace_pool_stats().reuse_rate());
+            if root.is_some() && first_failure.get().is_none() {
+                progress.report(ProgressEvent::CompressFinished {
+                    num_proofs: plan.num_nodes(),
+                    elapsed: start.elapsed(),
+                });
+            }
+        }
+        match first_failure.into_inner() {
+            Some(e) => Err(e),
+            None => root.ok_or(SP1RecursionProverError::Cancelled),
+        }
+    }
+
+    /// The program proving `input` and its witness stream.
+    ///
+    /// The verifying keys of a compress input must have been checked against the vk map.
+    #[allow(clippy::type_complexity)]
+    fn program_and_witness_stream(
+        &self,
+        input: SP1CircuitWitness,
+    ) -> Result<(Arc<RecursionProgram<BabyBear>>, Vec<Block<BabyBear>>), StrictModeError> {
+        let mut witness_stream = Vec::new();
+        let program = match input {
+            SP1CircuitWitness::Core(input) => {
+                Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+                self.recursion_program(&input)
+            }
+            SP1CircuitWitness::Deferred(input) => {
+                Witnessable::<InnerConfig>::write(&input, &mut witness_stream);
+                self.deferred_program(&input)
+            }
+            SP1CircuitWitness::Compress(input) => {
+                let input_with_merkle =
+                    self.make_merkle_proofs(input).expect("the verifying keys were checked");
+                Witnessable::<InnerConfig>::write(&input_with_merkle, &mut witness_stream);
+                self.compress_program(&input_with_merkle)?
+            }
+        };
+        Ok((program, witness_stream))
+    }
+
+    /// Prove the only input of a recursion tree inline, without the channels and workers of
+    /// [`Self::reduce_tree_with_recorder`].
+    ///
+    /// This is the tree of a program that fits in a single shard, whose lift proof is the root.
+    fn reduce_single_input(
+        &self,
+        input: SP1CircuitWitness,
+        is_root: bool,
+        opts: &SP1ProverOpts,
+    ) -> Result<(StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>), SP1RecursionProverError> {
+        FriParamsMismatch::check(
+            "compress",
+            opts.fri_opts.compress,
+            self.compress_prover.config().fri_params(),
+        )?;
+        let arity = self.try_compress_arity(opts)?;
+        let plan = self.reduction_strategy.plan(1, arity);
+        plan.validate(1, arity).map_err(SP1RecursionProverError::InvalidReductionPlan)?;
+        let mut tree = CompressTree::new(&plan, arity);
+        let start = Instant::now();
+        tree.node_ready(0, start.elapsed());
+
+        let (program, witness_stream) = self.program_and_witness_stream(input)?;
+        self.throttle();
+        let job = self.worker_pool.job();
+        let permit = self.worker_pool.acquire(WorkerKind::Prove, job);
+
+        // Execute the program.
+        let execute_start = Instant::now();
+        let mut runtime = RecursionRuntime::<Val<InnerSC>, Challenge<InnerSC>, _>::new(
+            program.clone(),
+            self.compress_prover.config().perm.clone(),
+        );
+        runtime.witness_stream = witness_stream.into();
+        runtime.run().map_err(|e| SP1RecursionProverError::RuntimeError(e.to_string()))?;
+        let execute = execute_start.elapsed();
+        self.check_cancelled()?;
+
+        // Generate the traces.
+        let trace_gen_start = Instant::now();
+        let mut records = vec![runtime.record];
+        self.compress_prover.machine().generate_dependencies(
+            &mut records,
+            &opts.recursion_opts,
+            None,
+        );
+        let record = records.pop().unwrap();
+        let traces = self.compress_prover.generate_traces(&record);
+        let trace_gen = trace_gen_start.elapsed();
+
+        // Prove the program.
+        let (pk, vk) = tracing::debug_span!("Setup compress program")
+            .in_scope(|| self.compress_prover.setup(&program));
+        let mut challenger = self.compress_prover.config().challenger();
+        pk.observe_into(&mut challenger);
+        let commit_start = Instant::now();
+        let data = self.compress_prover.commit(&record, traces);
+        let commit = commit_start.elapsed();
+        let open_start = Instant::now();
+        let proof = self
+            .compress_prover
+            .open(&pk, data, &mut challenger)
+            .map_err(|e| SP1RecursionProverError::ProveFailed(e.to_string()))?;
+        drop(permit);
+
+        let timings = StageTimings { execute, trace_gen, commit, open: open_start.elapsed() };
+        tree.node_proven_by(0, 0, proof.shape(), timings);
+        tree.node_proven(0, start.elapsed());
+        lock_or_reset(&self.profile, |p| *p = ProvingProfile::default())
+            .record_node(ShardProfile { layer: 0, index: 0, shape: proof.shape(), timings });
+        self.reduction_strategy.node_proven(0, 0, &proof);
+
+        if is_root {
+            *lock_or_reset(&self.compress_tree, |tree| *tree = None) = Some(tree);
+            opts.progress.report(ProgressEvent::CompressFinished {
+                num_proofs: plan.num_nodes(),
+                elapsed: start.elapsed(),
+            });
+        }
+        Ok((vk, proof))
     }
 
     /// Wrap a reduce proof into a STARK proven over a SNARK-friendly field.
@@ -876,6 +2269,13 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         reduced_proof: SP1ReduceProof<InnerSC>,
         opts: SP1ProverOpts,
     ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.check_cancelled()?;
+        FriParamsMismatch::check(
+            "shrink",
+            opts.fri_opts.shrink,
+            self.shrink_prover.config().fri_params(),
+        )?;
+
         // Make the compress proof.
         let SP1ReduceProof { vk: compressed_vk, proof: compressed_proof } = reduced_proof;
         let input = SP1CompressWitnessValues {
@@ -883,19 +2283,43 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             is_complete: true,
         };
 
-        let input_with_merkle = self.make_merkle_proofs(input);
+        let input_with_merkle = self.make_merkle_proofs(input)?;
+
+        self.shrink_with_merkle_proofs(&input_with_merkle, opts)
+    }
 
-        let program =
-            self.shrink_program(ShrinkAir::<BabyBear>::shrink_shape(), &input_with_merkle);
+    /// Shrink a compressed proof whose verifying key Merkle proofs have already been computed.
+    pub(crate) fn shrink_with_merkle_proofs(
+        &self,
+        input_with_merkle: &SP1CompressWithVKeyWitnessValues<InnerSC>,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        opts.progress.report(ProgressEvent::ShrinkStarted);
+        let start = Instant::now();
+        let shape = input_with_merkle.shape();
+        self.check_installed_programs([SP1CompressProgramShape::Shrink(shape.clone())])?;
+        let setup = self.shrink_setup(&shape);
+        let record = self.execute_shrink_machine(setup.program.clone(), input_with_merkle)?;
+        let proof = self.prove_shrink_record(&setup.pk, record, &opts)?;
+        opts.progress.report(ProgressEvent::ShrinkFinished { elapsed: start.elapsed() });
+
+        Ok(SP1ReduceProof { vk: setup.vk.clone(), proof })
+    }
 
+    /// Run `program` of the shrink machine on `input_with_merkle`.
+    pub(crate) fn execute_shrink_machine(
+        &self,
+        program: Arc<RecursionProgram<BabyBear>>,
+        input_with_merkle: &SP1CompressWithVKeyWitnessValues<InnerSC>,
+    ) -> Result<ExecutionRecord<BabyBear>, SP1RecursionProverError> {
         // Run the compress program.
         let mut runtime = RecursionRuntime::<Val<InnerSC>, Challenge<InnerSC>, _>::new(
-            program.clone(),
+            program,
             self.shrink_prover.config().perm.clone(),
         );
 
         let mut witness_stream = Vec::new();
-        Witnessable::<InnerConfig>::write(&input_with_merkle, &mut witness_stream);
+        Witnessable::<InnerConfig>::write(input_with_merkle, &mut witness_stream);
 
         runtime.witness_stream = witness_stream.into();
 
@@ -903,18 +2327,73 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
 
         runtime.print_stats();
         tracing::debug!("Shrink program executed successfully");
+        Ok(runtime.record)
+    }
 
-        let (shrink_pk, shrink_vk) =
-            tracing::debug_span!("setup shrink").in_scope(|| self.shrink_prover.setup(&program));
+    /// Prove a record of the shrink machine with `pk`.
+    pub(crate) fn prove_shrink_record(
+        &self,
+        pk: &ShrinkProvingKey<C>,
+        record: ExecutionRecord<BabyBear>,
+        opts: &SP1ProverOpts,
+    ) -> Result<ShardProof<InnerSC>, SP1RecursionProverError> {
+        self.throttle();
 
         // Prove the compress program.
         let mut compress_challenger = self.shrink_prover.config().challenger();
         let mut compress_proof = self
             .shrink_prover
-            .prove(&shrink_pk, vec![runtime.record], &mut compress_challenger, opts.recursion_opts)
-            .unwrap();
+            .prove(pk, vec![record], &mut compress_challenger, opts.recursion_opts)
+            .map_err(|e| SP1RecursionProverError::ProveFailed(e.to_string()))?;
+        Ok(compress_proof.shard_proofs.pop().unwrap())
+    }
+
+    /// The shrink program for compressed proofs of `shape` and its keys.
+    ///
+    /// The last setup is cached. When recursion shapes are fixed, the shape of the root of a
+    /// compress tree is known as soon as its program is, so the root compress tree starts setting
+    /// up the shrink program in the background while its root is proven, and shrink waits for that
+    /// setup instead of repeating it.
+    pub fn shrink_setup(&self, shape: &SP1CompressWithVkeyShape) -> Arc<ShrinkSetup<C>> {
+        let cached = lock_or_reset(&self.shrink_setup, |setup| *setup = None).clone();
+        if let Some(setup) = cached.filter(|setup| setup.shape == *shape) {
+            return setup;
+        }
+
+        // A background setup of another shape is left to finish on its own.
+        let pending = lock_or_reset(&self.pending_shrink_setup, |pending| *pending = None).take();
+        let background = pending
+            .filter(|pending| pending.shape == *shape)
+            .and_then(|pending| pending.handle.join().ok());
+        let setup = background.unwrap_or_else(|| {
+            let input =
+                SP1CompressWithVKeyWitnessValues::dummy(self.compress_prover.machine(), shape);
+            let program = self.shrink_program(ShrinkAir::<BabyBear>::shrink_shape(), &input);
+            let (pk, vk) = tracing::debug_span!("setup shrink")
+                .in_scope(|| self.shrink_prover.setup(&program));
+            Arc::new(ShrinkSetup { shape: shape.clone(), program, pk, vk })
+        });
+        *lock_or_reset(&self.shrink_setup, |setup| *setup = None) = Some(setup.clone());
+        setup
+    }
 
-        Ok(SP1ReduceProof { vk: shrink_vk, proof: compress_proof.shard_proofs.pop().unwrap() })
+    /// Start setting up the shrink program for compressed proofs of `shape` on a background
+    /// thread, unless it is already set up or being set up.
+    pub(crate) fn start_shrink_setup(&self, shape: SP1CompressWithVkeyShape) {
+        let cached = lock_or_reset(&self.shrink_setup, |setup| *setup = None).clone();
+        if cached.is_some_and(|setup| setup.shape == shape) {
+            return;
+        }
+        let mut pending = lock_or_reset(&self.pending_shrink_setup, |pending| *pending = None);
+        if pending.as_ref().is_some_and(|pending| pending.shape == shape) {
+            return;
+        }
+
+        // The setup runs on its own handle, so that compress returns without waiting for it.
+        let prover = self.handle();
+        let setup_shape = shape.clone();
+        let handle = thread::spawn(move || prover.shrink_setup(&setup_shape));
+        *pending = Some(PendingShrinkSetup { shape, handle });
     }
 
     /// Wrap a reduce proof into a STARK proven over a SNARK-friendly field.
@@ -924,14 +2403,23 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         compressed_proof: SP1ReduceProof<InnerSC>,
         opts: SP1ProverOpts,
     ) -> Result<SP1ReduceProof<OuterSC>, SP1RecursionProverError> {
+        self.check_cancelled()?;
+        FriParamsMismatch::check(
+            "wrap",
+            opts.fri_opts.wrap,
+            self.wrap_prover.config().fri_params(),
+        )?;
+        opts.progress.report(ProgressEvent::WrapStarted);
+        let start = Instant::now();
+
         let SP1ReduceProof { vk: compressed_vk, proof: compressed_proof } = compressed_proof;
         let input = SP1CompressWitnessValues {
             vks_and_proofs: vec![(compressed_vk, compressed_proof)],
             is_complete: true,
         };
-        let input_with_vk = self.make_merkle_proofs(input);
+        let input_with_vk = self.make_merkle_proofs(input)?;
 
-        let program = self.wrap_program();
+        let program = self.wrap_program()?;
 
         // Run the compress program.
         let mut runtime = RecursionRuntime::<Val<InnerSC>, Challenge<InnerSC>, _>::new(
@@ -949,6 +2437,8 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         runtime.print_stats();
         tracing::debug!("wrap program executed successfully");
 
+        self.throttle();
+
         // Setup the wrap program.
         let (wrap_pk, wrap_vk) =
             tracing::debug_span!("setup wrap").in_scope(|| self.wrap_prover.setup(&program));
@@ -963,23 +2453,46 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         let mut wrap_proof = self
             .wrap_prover
             .prove(&wrap_pk, vec![runtime.record], &mut wrap_challenger, opts.recursion_opts)
-            .unwrap();
+            .map_err(|e| SP1RecursionProverError::ProveFailed(e.to_string()))?;
         let elapsed = time.elapsed();
         tracing::debug!("wrap proving time: {:?}", elapsed);
         let mut wrap_challenger = self.wrap_prover.config().challenger();
-        self.wrap_prover.machine().verify(&wrap_vk, &wrap_proof, &mut wrap_challenger).unwrap();
+        self.wrap_prover.machine().verify(&wrap_vk, &wrap_proof, &mut wrap_challenger).map_err(
+            |e| {
+                SP1RecursionProverError::ProveFailed(format!("the wrap proof does not verify: {e}"))
+            },
+        )?;
         tracing::debug!("wrapping successful");
+        opts.progress.report(ProgressEvent::WrapFinished { elapsed: start.elapsed() });
 
         Ok(SP1ReduceProof { vk: wrap_vk, proof: wrap_proof.shard_proofs.pop().unwrap() })
     }
 
     /// Wrap the STARK proven over a SNARK-friendly field into a PLONK proof.
-    #[instrument(name = "wrap_plonk_bn254", level = "info", skip_all)]
+    ///
+    /// Panics if gnark fails, see [`Self::try_wrap_plonk_bn254`].
+    #[deprecated(
+        since = "5.0.8",
+        note = "use `try_wrap_plonk_bn254`, which returns gnark failures as errors"
+    )]
     pub fn wrap_plonk_bn254(
         &self,
         proof: SP1ReduceProof<OuterSC>,
         build_dir: &Path,
     ) -> PlonkBn254Proof {
+        self.try_wrap_plonk_bn254(proof, build_dir, &GnarkLimits::from_env()).unwrap()
+    }
+
+    /// Wrap the STARK proven over a SNARK-friendly field into a PLONK proof, failing if gnark
+    /// exceeds `limits` or fails.
+    #[instrument(name = "wrap_plonk_bn254", level = "info", skip_all)]
+    pub fn try_wrap_plonk_bn254(
+        &self,
+        proof: SP1ReduceProof<OuterSC>,
+        build_dir: &Path,
+        limits: &GnarkLimits,
+    ) -> Result<PlonkBn254Proof, SP1RecursionProverError> {
+        self.check_cancelled()?;
         let input = SP1CompressWitnessValues {
             vks_and_proofs: vec![(proof.vk.clone(), proof.proof.clone())],
             is_complete: true,
@@ -993,7 +2506,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         witness.write_vkey_hash(vkey_hash);
 
         let prover = PlonkBn254Prover::new();
-        let proof = prover.prove(witness, build_dir.to_path_buf());
+        let proof = prover.try_prove(witness, build_dir.to_path_buf(), limits)?;
 
         // Verify the proof.
         prover
@@ -1003,18 +2516,40 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 &committed_values_digest.as_canonical_biguint(),
                 build_dir,
             )
-            .unwrap();
+            .map_err(|e| GnarkError::Failed {
+                system: "plonk",
+                operation: "verify",
+                message: e.to_string(),
+            })?;
 
-        proof
+        Ok(proof)
     }
 
     /// Wrap the STARK proven over a SNARK-friendly field into a Groth16 proof.
-    #[instrument(name = "wrap_groth16_bn254", level = "info", skip_all)]
+    ///
+    /// Panics if gnark fails, see [`Self::try_wrap_groth16_bn254`].
+    #[deprecated(
+        since = "5.0.8",
+        note = "use `try_wrap_groth16_bn254`, which returns gnark failures as errors"
+    )]
     pub fn wrap_groth16_bn254(
         &self,
         proof: SP1ReduceProof<OuterSC>,
         build_dir: &Path,
     ) -> Groth16Bn254Proof {
+        self.try_wrap_groth16_bn254(proof, build_dir, &GnarkLimits::from_env()).unwrap()
+    }
+
+    /// Wrap the STARK proven over a SNARK-friendly field into a Groth16 proof, failing if gnark
+    /// exceeds `limits` or fails.
+    #[instrument(name = "wrap_groth16_bn254", level = "info", skip_all)]
+    pub fn try_wrap_groth16_bn254(
+        &self,
+        proof: SP1ReduceProof<OuterSC>,
+        build_dir: &Path,
+        limits: &GnarkLimits,
+    ) -> Result<Groth16Bn254Proof, SP1RecursionProverError> {
+        self.check_cancelled()?;
         let input = SP1CompressWitnessValues {
             vks_and_proofs: vec![(proof.vk.clone(), proof.proof.clone())],
             is_complete: true,
@@ -1028,7 +2563,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         witness.write_vkey_hash(vkey_hash);
 
         let prover = Groth16Bn254Prover::new();
-        let proof = prover.prove(witness, build_dir.to_path_buf());
+        let proof = prover.try_prove(witness, build_dir.to_path_buf(), limits)?;
 
         // Verify the proof.
         prover
@@ -1038,74 +2573,195 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 &committed_values_digest.as_canonical_biguint(),
                 build_dir,
             )
-            .unwrap();
+            .map_err(|e| GnarkError::Failed {
+                system: "groth16",
+                operation: "verify",
+                message: e.to_string(),
+            })?;
 
-        proof
+        Ok(proof)
     }
 
     pub fn recursion_program(
         &self,
         input: &SP1RecursionWitnessValues<CoreSC>,
     ) -> Arc<RecursionProgram<BabyBear>> {
-        // Check if the program is in the cache.
-        let mut cache = self.lift_programs_lru.lock().unwrap_or_else(|e| e.into_inner());
+        // Check if the program was installed or is in the cache.
         let shape = input.shape();
-        let program = cache.get(&shape).cloned();
-        drop(cache);
-        match program {
-            Some(program) => program,
-            None => {
+        let program_shape = SP1CompressProgramShape::Recursion(shape.clone());
+        if let Some(program) = self.prebuilt_programs.get(&program_shape.hash_u64()) {
+            return program.clone();
+        }
+        let program = lock_or_reset(&self.lift_programs_lru, LruCache::clear).get(&shape).cloned();
+        if let Some(program) = program {
+            return program;
+        }
+        self.assert_compilable(&program_shape);
+
+        // Join the compilation of this shape if one is in flight, or start one.
+        let cell = lock_or_reset(&self.lift_programs_inflight, BTreeMap::clear)
+            .entry(shape.clone())
+            .or_default()
+            .clone();
+        let program = cell
+            .get_or_init(|| {
+                // The program may have been cached since we last checked.
+                let cached =
+                    lock_or_reset(&self.lift_programs_lru, LruCache::clear).get(&shape).cloned();
+                if let Some(program) = cached {
+                    return program;
+                }
+
                 let misses = self.lift_cache_misses.fetch_add(1, Ordering::Relaxed);
                 tracing::debug!("core cache miss, misses: {}", misses);
-                // Get the operations.
-                let builder_span = tracing::debug_span!("build recursion program").entered();
-                let mut builder = Builder::<InnerConfig>::default();
+                let program = self.spawn_compile_recursion_program(input);
 
-                let input =
-                    tracing::debug_span!("read input").in_scope(|| input.read(&mut builder));
-                tracing::debug_span!("verify").in_scope(|| {
-                    SP1RecursiveVerifier::verify(&mut builder, self.core_prover.machine(), input)
-                });
-                let block =
-                    tracing::debug_span!("build block").in_scope(|| builder.into_root_block());
-                builder_span.exit();
-                // SAFETY: The circuit is well-formed. It does not use synchronization primitives
-                // (or possibly other means) to violate the invariants.
-                let dsl_program = unsafe { DslIrProgram::new_unchecked(block) };
+                // Insert the program into the cache.
+                lock_or_reset(&self.lift_programs_lru, LruCache::clear)
+                    .put(shape.clone(), program.clone());
+                program
+            })
+            .clone();
+
+        // Remove the in-flight entry, unless it was already replaced by a newer compilation.
+        let mut inflight = lock_or_reset(&self.lift_programs_inflight, BTreeMap::clear);
+        if inflight.get(&shape).is_some_and(|entry| Arc::ptr_eq(entry, &cell)) {
+            inflight.remove(&shape);
+        }
+        drop(inflight);
+
+        program
+    }
+
+    /// Compile the recursion program of `input` on the compiler pool and wait for it.
+    ///
+    /// The pool bounds the number of programs compiled at once, and the calling thread only waits
+    /// for the result, so callers should not hold a worker permit meanwhile. A panic of the
+    /// compiler is raised in the calling thread.
+    fn spawn_compile_recursion_program(
+        &self,
+        input: &SP1RecursionWitnessValues<CoreSC>,
+    ) -> Arc<RecursionProgram<BabyBear>> {
+        let (program_tx, program_rx) = sync_channel(1);
+        let core = self.core.clone();
+        let input = input.clone();
+        self.compiler_pool.spawn(move || {
+            let program = catch_unwind(AssertUnwindSafe(|| core.compile_recursion_program(&input)));
+            let _ = program_tx.send(program);
+        });
+        match program_rx.recv().expect("the compiler pool dropped a compilation") {
+            Ok(program) => Arc::new(program),
+            Err(panic) => resume_unwind(panic),
+        }
+    }
+
+    /// The join program for `input`, compiled and cached if it was not precompiled.
+    ///
+    /// Fails if cache recomputes are strict and the program is not cached.
+    pub fn compress_program(
+        &self,
+        input: &SP1CompressWithVKeyWitnessValues<InnerSC>,
+    ) -> Result<Arc<RecursionProgram<BabyBear>>, StrictModeError> {
+        if let Some(program) = self.cached_compress_program(&input.shape()) {
+            return Ok(program);
+        }
+        let misses = self.join_cache_misses.fetch_add(1, Ordering::Relaxed);
+        self.strict.warn(
+            WarningClass::CacheRecompute,
+            format!("join program not found in map, recomputing join program (misses: {misses})."),
+        )?;
+        Ok(self.compile_compress_program(input))
+    }
+
+    /// The join program for `shape`, if it was precompiled or already recomputed.
+    fn cached_compress_program(
+        &self,
+        shape: &SP1CompressWithVkeyShape,
+    ) -> Option<Arc<RecursionProgram<BabyBear>>> {
+        if let Some(program) = self.join_programs_map.get(shape) {
+            return Some(program.clone());
+        }
+        lock_or_reset(&self.join_programs_fallback, BTreeMap::clear).get(shape).cloned()
+    }
+
+    /// Compile the join program for `input` and cache it with the recomputed join programs.
+    fn compile_compress_program(
+        &self,
+        input: &SP1CompressWithVKeyWitnessValues<InnerSC>,
+    ) -> Arc<RecursionProgram<BabyBear>> {
+        let shape = input.shape();
+        self.assert_compilable(&SP1CompressProgramShape::Compress(shape.clone()));
+        // Get the operations.
+        let program = Arc::new(compress_program_from_input::<C>(
+            self.compress_shape_config.as_ref(),
+            &self.compress_prover,
+            self.vk_verification.checks_vks(),
+            input,
+        ));
+        lock_or_reset(&self.join_programs_fallback, BTreeMap::clear).insert(shape, program.clone());
+        program
+    }
+
+    /// The stage timings of the slowest core shards and recursion nodes proven by this handle
+    /// since it was created or the profile was last taken.
+    pub fn profile(&self) -> ProvingProfile {
+        lock_or_reset(&self.profile, |p| *p = ProvingProfile::default()).clone()
+    }
+
+    /// Take the profile of the handle, see [`Self::profile`], starting a new one.
+    pub fn take_profile(&self) -> ProvingProfile {
+        std::mem::take(&mut *lock_or_reset(&self.profile, |p| *p = ProvingProfile::default()))
+    }
+
+    /// The reuse statistics of the trace buffers pooled by the provers of every stage, see
+    /// [`TracePool`](sp1_stark::TracePool).
+    pub fn trace_pool_stats(&self) -> TracePoolStats {
+        [
+            self.core_prover.trace_pool_stats(),
+            self.compress_prover.trace_pool_stats(),
+            self.shrink_prover.trace_pool_stats(),
+            self.wrap_prover.trace_pool_stats(),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(TracePoolStats::default(), |total, stats| total.merge(&stats))
+    }
 
-                // Compile the program.
-                let compiler_span = tracing::debug_span!("compile recursion program").entered();
-                let mut compiler = AsmCompiler::<InnerConfig>::default();
-                let mut program = compiler.compile(dsl_program);
-                if let Some(inn_recursion_shape_config) = &self.compress_shape_config {
-                    inn_recursion_shape_config.fix_shape(&mut program);
-                }
-                let program = Arc::new(program);
-                compiler_span.exit();
+    /// The resources used so far by the job `job_id`, see [`CostMeter`].
+    pub fn job_cost(&self, job_id: &str) -> Option<JobCost> {
+        self.cost_meter.job(job_id)
+    }
 
-                // Insert the program into the cache.
-                let mut cache = self.lift_programs_lru.lock().unwrap_or_else(|e| e.into_inner());
-                cache.put(shape, program.clone());
-                drop(cache);
-                program
-            }
+    /// Take the resources used by the job `job_id`, e.g. once it is billed.
+    pub fn take_job_cost(&self, job_id: &str) -> Option<JobCost> {
+        self.cost_meter.take_job(job_id)
+    }
+
+    /// Fail before proving if cache recomputes are strict and the join programs were not
+    /// precompiled, since every join program would then be recompiled.
+    ///
+    /// A join program missing from a non-empty map fails the compress stage once it is needed,
+    /// see [`Self::compress_program`].
+    fn check_join_programs_precompiled(&self) -> Result<(), StrictModeError> {
+        if self.strict.is_strict(WarningClass::CacheRecompute) && self.join_programs_map.is_empty()
+        {
+            return Err(StrictModeError {
+                class: WarningClass::CacheRecompute,
+                message: "the join programs were not precompiled".to_string(),
+            });
         }
+        Ok(())
     }
 
-    pub fn compress_program(
-        &self,
-        input: &SP1CompressWithVKeyWitnessValues<InnerSC>,
-    ) -> Arc<RecursionProgram<BabyBear>> {
-        self.join_programs_map.get(&input.shape()).cloned().unwrap_or_else(|| {
-            tracing::warn!("join program not found in map, recomputing join program.");
-            // Get the operations.
-            Arc::new(compress_program_from_input::<C>(
-                self.compress_shape_config.as_ref(),
-                &self.compress_prover,
-                self.vk_verification,
-                input,
-            ))
-        })
+    /// Clear the caches of compiled recursion programs and reset the cache miss counters.
+    ///
+    /// The precompiled join programs and the wrap program are kept, since they are derived from
+    /// static configuration only, and so are the programs installed from artifacts.
+    pub fn reset_caches(&self) {
+        lock_or_reset(&self.lift_programs_lru, LruCache::clear).clear();
+        lock_or_reset(&self.join_programs_fallback, BTreeMap::clear).clear();
+        self.lift_cache_misses.store(0, Ordering::Relaxed);
+        self.join_cache_misses.store(0, Ordering::Relaxed);
     }
 
     pub fn shrink_program(
@@ -1113,6 +2769,15 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         shrink_shape: RecursionShape,
         input: &SP1CompressWithVKeyWitnessValues<InnerSC>,
     ) -> Arc<RecursionProgram<BabyBear>> {
+        let program_shape = SP1CompressProgramShape::Shrink(input.shape());
+        if let Some(program) = self.installed_program(&program_shape) {
+            let installed_shape = program.shape.as_ref().map(RecursionShape::clone_into_hash_map);
+            if installed_shape == Some(shrink_shape.clone_into_hash_map()) {
+                return program;
+            }
+        }
+        self.assert_compilable(&program_shape);
+
         // Get the operations.
         let builder_span = tracing::debug_span!("build shrink program").entered();
         let mut builder = Builder::<InnerConfig>::default();
@@ -1122,7 +2787,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             &mut builder,
             self.compress_prover.machine(),
             input,
-            self.vk_verification,
+            self.vk_verification.checks_vks(),
             PublicValuesOutputDigest::Reduce,
         );
         let block = builder.into_root_block();
@@ -1142,8 +2807,19 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         program
     }
 
-    pub fn wrap_program(&self) -> Arc<RecursionProgram<BabyBear>> {
-        self.wrap_program
+    /// The wrap program, compiled on first use.
+    ///
+    /// Fails with [`SP1RecursionProverError::MissingArtifacts`] if the handle is read-only and the
+    /// wrap program was not compiled yet.
+    pub fn wrap_program(&self) -> Result<Arc<RecursionProgram<BabyBear>>, SP1RecursionProverError> {
+        if let Some(program) = self.wrap_program.get() {
+            return Ok(program.clone());
+        }
+        if self.read_only {
+            return Err(SP1RecursionProverError::MissingArtifacts(vec!["wrap program".to_string()]));
+        }
+        let program = self
+            .wrap_program
             .get_or_init(|| {
                 // Get the operations.
                 let builder_span = tracing::debug_span!("build compress program").entered();
@@ -1154,6 +2830,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 let shape = SP1CompressWithVkeyShape {
                     compress_shape: input_shape,
                     merkle_tree_height: self.recursion_vk_tree.height,
+                    merkle_tree_config: self.recursion_vk_tree.config,
                 };
                 let dummy_input =
                     SP1CompressWithVKeyWitnessValues::dummy(self.shrink_prover.machine(), &shape);
@@ -1162,15 +2839,35 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
 
                 // Attest that the merkle tree root is correct.
                 let root = input.merkle_var.root;
-                for (val, expected) in root.iter().zip(self.recursion_vk_root.iter()) {
-                    builder.assert_felt_eq(*val, *expected);
+                match self.retiring_vk_root() {
+                    None => {
+                        for (val, expected) in root.iter().zip(self.recursion_vk_root.iter()) {
+                            builder.assert_felt_eq(*val, *expected);
+                        }
+                    }
+                    // The root is one of the accepted roots if it differs from at most one of
+                    // them, that is if the product of any coordinate of its difference to the
+                    // active root and any coordinate of its difference to the retiring root is
+                    // zero.
+                    Some(retiring) => {
+                        let active_diff: [Felt<_>; DIGEST_SIZE] = core::array::from_fn(|i| {
+                            builder.eval(root[i] - self.recursion_vk_root[i])
+                        });
+                        let retiring_diff: [Felt<_>; DIGEST_SIZE] =
+                            core::array::from_fn(|i| builder.eval(root[i] - retiring[i]));
+                        for a in active_diff {
+                            for r in retiring_diff {
+                                builder.assert_felt_eq(a * r, BabyBear::zero());
+                            }
+                        }
+                    }
                 }
                 // Verify the proof.
                 SP1CompressRootVerifierWithVKey::verify(
                     &mut builder,
                     self.shrink_prover.machine(),
                     input,
-                    self.vk_verification,
+                    self.vk_verification.checks_vks(),
                     PublicValuesOutputDigest::Root,
                 );
 
@@ -1187,13 +2884,20 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
                 compiler_span.exit();
                 program
             })
-            .clone()
+            .clone();
+        Ok(program)
     }
 
     pub fn deferred_program(
         &self,
         input: &SP1DeferredWitnessValues<InnerSC>,
     ) -> Arc<RecursionProgram<BabyBear>> {
+        let shape = SP1CompressProgramShape::Deferred(self.deferred_program_shape(input));
+        if let Some(program) = self.installed_program(&shape) {
+            return program;
+        }
+        self.assert_compilable(&shape);
+
         // Compile the program.
 
         // Get the operations.
@@ -1210,7 +2914,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             &mut builder,
             self.compress_prover.machine(),
             input,
-            self.vk_verification,
+            self.vk_verification.checks_vks(),
         );
         verify_span.exit();
         let block = builder.into_root_block();
@@ -1260,38 +2964,137 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         &'a self,
         vk: &'a StarkVerifyingKey<CoreSC>,
         deferred_proofs: &[SP1ReduceProof<InnerSC>],
-        mut deferred_digest: [Val<CoreSC>; 8],
+        deferred_digest: [Val<CoreSC>; 8],
         batch_size: usize,
-    ) -> (Vec<SP1DeferredWitnessValues<InnerSC>>, [BabyBear; 8]) {
+    ) -> Result<(Vec<SP1DeferredWitnessValues<InnerSC>>, [BabyBear; 8]), VkNotAllowedError> {
+        self.get_recursion_deferred_inputs_from_batches(
+            vk,
+            deferred_proofs.chunks(batch_size),
+            deferred_digest,
+        )
+    }
+
+    /// Prepare the deferred proof inputs for the given batches, chaining the deferred digest
+    /// through them starting from `deferred_digest`.
+    pub fn get_recursion_deferred_inputs_from_batches<'a, 'b>(
+        &'a self,
+        vk: &'a StarkVerifyingKey<CoreSC>,
+        batches: impl IntoIterator<Item = &'b [SP1ReduceProof<InnerSC>]>,
+        deferred_digest: [Val<CoreSC>; 8],
+    ) -> Result<(Vec<SP1DeferredWitnessValues<InnerSC>>, [BabyBear; 8]), VkNotAllowedError> {
+        self.get_recursion_deferred_inputs_for(
+            vk.hash_babybear(),
+            vk.pc_start,
+            batches,
+            deferred_digest,
+        )
+    }
+
+    /// Like [`Self::get_recursion_deferred_inputs_from_batches`], for the program with the given
+    /// verifying key digest and start pc.
+    pub(crate) fn get_recursion_deferred_inputs_for<'b>(
+        &self,
+        sp1_vk_digest: [BabyBear; DIGEST_SIZE],
+        pc_start: BabyBear,
+        batches: impl IntoIterator<Item = &'b [SP1ReduceProof<InnerSC>]>,
+        mut deferred_digest: [Val<CoreSC>; 8],
+    ) -> Result<(Vec<SP1DeferredWitnessValues<InnerSC>>, [BabyBear; 8]), VkNotAllowedError> {
         // Prepare the inputs for the deferred proofs recursive verification.
         let mut deferred_inputs = Vec::new();
 
-        for batch in deferred_proofs.chunks(batch_size) {
-            let vks_and_proofs =
-                batch.iter().cloned().map(|proof| (proof.vk, proof.proof)).collect::<Vec<_>>();
-
-            let input = SP1CompressWitnessValues { vks_and_proofs, is_complete: true };
-            let input = self.make_merkle_proofs(input);
-            let SP1CompressWithVKeyWitnessValues { compress_val, merkle_val } = input;
-
-            deferred_inputs.push(SP1DeferredWitnessValues {
-                vks_and_proofs: compress_val.vks_and_proofs,
-                vk_merkle_data: merkle_val,
-                start_reconstruct_deferred_digest: deferred_digest,
-                is_complete: false,
-                sp1_vk_digest: vk.hash_babybear(),
-                end_pc: vk.pc_start,
-                end_shard: BabyBear::one(),
-                end_execution_shard: BabyBear::one(),
-                init_addr_bits: [BabyBear::zero(); 32],
-                finalize_addr_bits: [BabyBear::zero(); 32],
-                committed_value_digest: [Word::<BabyBear>([BabyBear::zero(); 4]); 8],
-                deferred_proofs_digest: [BabyBear::zero(); 8],
-            });
+        for batch in batches {
+            deferred_inputs.push(self.deferred_input(
+                sp1_vk_digest,
+                pc_start,
+                batch,
+                deferred_digest,
+            )?);
+            deferred_digest = Self::hash_deferred_proofs(deferred_digest, batch);
+        }
+        Ok((deferred_inputs, deferred_digest))
+    }
 
+    /// Like [`Self::get_recursion_deferred_inputs_for`], generating each input only when it is
+    /// consumed.
+    ///
+    /// The verifying keys of every batch are opened in the vk map ahead of time, which fails if
+    /// any of them is not allowed, and the deferred digest chain is hashed from the public values
+    /// of the proofs alone. Only the inputs buffered by the consumer then hold copies of the
+    /// proofs, which keeps the memory of folding many thousands of deferred proofs bounded.
+    ///
+    /// The deferred digest itself is unchanged: it is a single hash chain over all the proofs,
+    /// each input verifying the next batch of it.
+    pub(crate) fn deferred_input_stream<'a, 'b: 'a>(
+        &'a self,
+        sp1_vk_digest: [BabyBear; DIGEST_SIZE],
+        pc_start: BabyBear,
+        batches: Vec<&'b [SP1ReduceProof<InnerSC>]>,
+        mut deferred_digest: [Val<CoreSC>; 8],
+    ) -> Result<
+        (impl Iterator<Item = SP1DeferredWitnessValues<InnerSC>> + Send + 'a, [BabyBear; 8]),
+        VkNotAllowedError,
+    > {
+        let mut starts = Vec::with_capacity(batches.len());
+        for batch in batches.iter() {
+            let merkle_val = self.batch_vk_merkle_proofs(batch)?;
+            starts.push((merkle_val, deferred_digest));
             deferred_digest = Self::hash_deferred_proofs(deferred_digest, batch);
         }
-        (deferred_inputs, deferred_digest)
+        let inputs =
+            batches.into_iter().zip(starts).map(move |(batch, (merkle_val, start_digest))| {
+                self.deferred_witness(sp1_vk_digest, pc_start, batch, merkle_val, start_digest)
+            });
+        Ok((inputs, deferred_digest))
+    }
+
+    /// The input of the deferred program verifying `batch`, starting the deferred digest chain
+    /// from `start_digest`.
+    fn deferred_input(
+        &self,
+        sp1_vk_digest: [BabyBear; DIGEST_SIZE],
+        pc_start: BabyBear,
+        batch: &[SP1ReduceProof<InnerSC>],
+        start_digest: [Val<CoreSC>; 8],
+    ) -> Result<SP1DeferredWitnessValues<InnerSC>, VkNotAllowedError> {
+        let merkle_val = self.batch_vk_merkle_proofs(batch)?;
+        Ok(self.deferred_witness(sp1_vk_digest, pc_start, batch, merkle_val, start_digest))
+    }
+
+    /// The Merkle proofs of the verifying keys of `batch`, see [`Self::make_merkle_proofs`].
+    fn batch_vk_merkle_proofs(
+        &self,
+        batch: &[SP1ReduceProof<InnerSC>],
+    ) -> Result<SP1MerkleProofWitnessValues<InnerSC>, VkNotAllowedError> {
+        self.vk_merkle_proofs(
+            committed_vk_root(batch.first().map(|proof| &proof.proof)),
+            batch.iter().map(|proof| &proof.vk),
+        )
+    }
+
+    /// The input of the deferred program verifying `batch`, whose verifying keys are opened by
+    /// `merkle_val`.
+    fn deferred_witness(
+        &self,
+        sp1_vk_digest: [BabyBear; DIGEST_SIZE],
+        pc_start: BabyBear,
+        batch: &[SP1ReduceProof<InnerSC>],
+        merkle_val: SP1MerkleProofWitnessValues<InnerSC>,
+        start_digest: [Val<CoreSC>; 8],
+    ) -> SP1DeferredWitnessValues<InnerSC> {
+        SP1DeferredWitnessValues {
+            vks_and_proofs: batch.iter().cloned().map(|proof| (proof.vk, proof.proof)).collect(),
+            vk_merkle_data: merkle_val,
+            start_reconstruct_deferred_digest: start_digest,
+            is_complete: false,
+            sp1_vk_digest,
+            end_pc: pc_start,
+            end_shard: BabyBear::one(),
+            end_execution_shard: BabyBear::one(),
+            init_addr_bits: [BabyBear::zero(); 32],
+            finalize_addr_bits: [BabyBear::zero(); 32],
+            committed_value_digest: [Word::<BabyBear>([BabyBear::zero(); 4]); 8],
+            deferred_proofs_digest: [BabyBear::zero(); 8],
+        }
     }
 
     pub fn get_recursion_deferred_inputs<'a>(
@@ -1299,7 +3102,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         vk: &'a StarkVerifyingKey<CoreSC>,
         deferred_proofs: &[SP1ReduceProof<InnerSC>],
         batch_size: usize,
-    ) -> (Vec<SP1DeferredWitnessValues<InnerSC>>, [BabyBear; 8]) {
+    ) -> Result<(Vec<SP1DeferredWitnessValues<InnerSC>>, [BabyBear; 8]), VkNotAllowedError> {
         self.get_recursion_deferred_inputs_with_initial_digest(
             vk,
             deferred_proofs,
@@ -1308,7 +3111,28 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         )
     }
 
+    /// Split the deferred proofs into the batches verified by each deferred recursion program.
+    ///
+    /// Batches are always consecutive runs of `deferred_proofs`, since the deferred digest depends
+    /// on the order of the proofs.
+    pub fn deferred_batches<'b>(
+        deferred_proofs: &'b [SP1ReduceProof<InnerSC>],
+        opts: &SP1DeferredOpts,
+    ) -> Vec<&'b [SP1ReduceProof<InnerSC>]> {
+        let batch_size = opts.batch_size.max(1);
+        match opts.ordering {
+            DeferredProofOrdering::InputOrder => deferred_proofs.chunks(batch_size).collect(),
+            DeferredProofOrdering::GroupByVk => deferred_proofs
+                .chunk_by(|a, b| a.vk.hash_babybear() == b.vk.hash_babybear())
+                .flat_map(|group| group.chunks(batch_size))
+                .collect(),
+        }
+    }
+
     /// Generate the inputs for the first layer of recursive proofs.
+    ///
+    /// The shard proofs may be in any order; they are sorted by shard index, and an error is
+    /// returned if a shard is missing or proven twice.
     #[allow(clippy::type_complexity)]
     pub fn get_first_layer_inputs<'a>(
         &'a self,
@@ -1316,14 +3140,70 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         shard_proofs: &[ShardProof<InnerSC>],
         deferred_proofs: &[SP1ReduceProof<InnerSC>],
         batch_size: usize,
-    ) -> Vec<SP1CircuitWitness> {
-        let (deferred_inputs, deferred_digest) =
-            self.get_recursion_deferred_inputs(&vk.vk, deferred_proofs, batch_size);
+    ) -> Result<Vec<SP1CircuitWitness>, SP1RecursionProverError> {
+        let deferred_opts = SP1DeferredOpts {
+            batch_size,
+            ordering: DeferredProofOrdering::InputOrder,
+            ..SP1DeferredOpts::default()
+        };
+        self.get_first_layer_inputs_with_deferred_opts(
+            vk,
+            shard_proofs,
+            deferred_proofs,
+            batch_size,
+            &deferred_opts,
+        )
+    }
+
+    /// Generate the inputs for the first layer of recursive proofs, batching the deferred proofs
+    /// according to `deferred_opts`.
+    pub fn get_first_layer_inputs_with_deferred_opts<'a>(
+        &'a self,
+        vk: &'a SP1VerifyingKey,
+        shard_proofs: &[ShardProof<InnerSC>],
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        batch_size: usize,
+        deferred_opts: &SP1DeferredOpts,
+    ) -> Result<Vec<SP1CircuitWitness>, SP1RecursionProverError> {
+        self.get_first_layer_inputs_with_initial_digest(
+            vk,
+            shard_proofs,
+            deferred_proofs,
+            [BabyBear::zero(); DIGEST_SIZE],
+            batch_size,
+            deferred_opts,
+        )
+    }
+
+    /// Generate the inputs for the first layer of recursive proofs, chaining the deferred digest
+    /// from `initial_deferred_digest`, see [`Self::compress_with_initial_deferred_digest`].
+    pub fn get_first_layer_inputs_with_initial_digest<'a>(
+        &'a self,
+        vk: &'a SP1VerifyingKey,
+        shard_proofs: &[ShardProof<InnerSC>],
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        initial_deferred_digest: [BabyBear; DIGEST_SIZE],
+        batch_size: usize,
+        deferred_opts: &SP1DeferredOpts,
+    ) -> Result<Vec<SP1CircuitWitness>, SP1RecursionProverError> {
+        // Without core shards, only the deferred proofs are folded.
+        let shard_proofs = if shard_proofs.is_empty() && !deferred_proofs.is_empty() {
+            Cow::Borrowed(shard_proofs)
+        } else {
+            order_shard_proofs(shard_proofs)?
+        };
+        let (deferred_inputs, deferred_digest) = self.get_recursion_deferred_inputs_from_batches(
+            &vk.vk,
+            Self::deferred_batches(deferred_proofs, deferred_opts),
+            initial_deferred_digest,
+        )?;
 
-        let is_complete = shard_proofs.len() == 1 && deferred_proofs.is_empty();
+        let is_complete = shard_proofs.len() == 1 &&
+            deferred_proofs.is_empty() &&
+            initial_deferred_digest == [BabyBear::zero(); DIGEST_SIZE];
         let core_inputs = self.get_recursion_core_inputs(
             &vk.vk,
-            shard_proofs,
+            &shard_proofs,
             batch_size,
             is_complete,
             deferred_digest,
@@ -1332,7 +3212,7 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         let mut inputs = Vec::new();
         inputs.extend(deferred_inputs.into_iter().map(SP1CircuitWitness::Deferred));
         inputs.extend(core_inputs.into_iter().map(SP1CircuitWitness::Core));
-        inputs
+        Ok(inputs)
     }
 
     /// Accumulate deferred proofs into a single digest.
@@ -1354,48 +3234,94 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         digest
     }
 
+    /// The index of a recursion verifying key in the vk map and the digest committed to for it.
+    ///
+    /// Without vk verification, any verifying key is mapped to an arbitrary leaf of the vk map.
+    pub fn recursion_vk_leaf(
+        &self,
+        vk: &StarkVerifyingKey<InnerSC>,
+    ) -> Result<(usize, [BabyBear; DIGEST_SIZE]), VkNotAllowedError> {
+        self.recursion_vk_leaf_under(vk, &self.recursion_vk_root)
+    }
+
+    /// Like [`Self::recursion_vk_leaf`], in the vk map of `vk_root` if it is the retiring root.
+    pub fn recursion_vk_leaf_under(
+        &self,
+        vk: &StarkVerifyingKey<InnerSC>,
+        vk_root: &[BabyBear; DIGEST_SIZE],
+    ) -> Result<(usize, [BabyBear; DIGEST_SIZE]), VkNotAllowedError> {
+        let (vk_root, vk_map, _) = self.vk_allowlist(vk_root);
+        let vk_digest = vk.hash_babybear();
+        if !self.vk_verification.checks_vks() {
+            let index = (vk_digest[0].as_canonical_u32() as usize) % vk_map.len();
+            return Ok((index, [BabyBear::from_canonical_usize(index); DIGEST_SIZE]));
+        }
+        match vk_map.get(&vk_digest) {
+            Some(index) => Ok((*index, vk_digest)),
+            None => Err(self.vk_not_allowed(&vk_digest, vk_root, vk_map.len())),
+        }
+    }
+
+    /// The error for a verifying key with digest `vk_digest` missing from the vk map of `vk_root`.
+    pub(crate) fn vk_not_allowed(
+        &self,
+        vk_digest: &[BabyBear; DIGEST_SIZE],
+        vk_root: &[BabyBear; DIGEST_SIZE],
+        num_allowed_vks: usize,
+    ) -> VkNotAllowedError {
+        VkNotAllowedError {
+            vk_digest: vk_digest.map(|x| x.as_canonical_u32()),
+            circuit_version: SP1_CIRCUIT_VERSION,
+            vk_root: vk_root.map(|x| x.as_canonical_u32()),
+            num_allowed_vks,
+            remediation: if self.compress_shape_config.is_some() {
+                VkRemediation::UpgradeShapes
+            } else {
+                VkRemediation::FixShapes
+            },
+        }
+    }
+
+    /// Attach the Merkle proofs of the verifying keys of `input` in the vk map.
+    ///
+    /// The proofs of `input` are opened in the vk map of the root they commit to, which is the
+    /// retiring one for proofs made before a vk map rotation.
     pub fn make_merkle_proofs(
         &self,
         input: SP1CompressWitnessValues<CoreSC>,
-    ) -> SP1CompressWithVKeyWitnessValues<CoreSC> {
-        let num_vks = self.recursion_vk_map.len();
-        let (vk_indices, vk_digest_values): (Vec<_>, Vec<_>) = if self.vk_verification {
-            input
-                .vks_and_proofs
-                .iter()
-                .map(|(vk, _)| {
-                    let vk_digest = vk.hash_babybear();
-                    let index = self.recursion_vk_map.get(&vk_digest).expect("vk not allowed");
-                    (index, vk_digest)
-                })
-                .unzip()
-        } else {
-            input
-                .vks_and_proofs
-                .iter()
-                .map(|(vk, _)| {
-                    let vk_digest = vk.hash_babybear();
-                    let index = (vk_digest[0].as_canonical_u32() as usize) % num_vks;
-                    (index, [BabyBear::from_canonical_usize(index); 8])
-                })
-                .unzip()
-        };
+    ) -> Result<SP1CompressWithVKeyWitnessValues<CoreSC>, VkNotAllowedError> {
+        let vk_root = committed_vk_root(input.vks_and_proofs.first().map(|(_, proof)| proof));
+        let merkle_val =
+            self.vk_merkle_proofs(vk_root, input.vks_and_proofs.iter().map(|(vk, _)| vk))?;
+        Ok(SP1CompressWithVKeyWitnessValues { compress_val: input, merkle_val })
+    }
+
+    /// The Merkle proofs of `vks` in the vk map of `vk_root`.
+    fn vk_merkle_proofs<'v>(
+        &self,
+        vk_root: [BabyBear; DIGEST_SIZE],
+        vks: impl Iterator<Item = &'v StarkVerifyingKey<InnerSC>>,
+    ) -> Result<SP1MerkleProofWitnessValues<InnerSC>, VkNotAllowedError> {
+        let (vk_root, _, vk_tree) = self.vk_allowlist(&vk_root);
+        let (vk_indices, vk_digest_values): (Vec<_>, Vec<_>) = vks
+            .map(|vk| self.recursion_vk_leaf_under(vk, vk_root))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
 
         let proofs = vk_indices
             .iter()
             .map(|index| {
-                let (_, proof) = MerkleTree::open(&self.recursion_vk_tree, *index);
+                let (_, proof) = MerkleTree::open(vk_tree, *index);
                 proof
             })
             .collect();
 
-        let merkle_val = SP1MerkleProofWitnessValues {
-            root: self.recursion_vk_root,
+        Ok(SP1MerkleProofWitnessValues {
+            root: *vk_root,
             values: vk_digest_values,
             vk_merkle_proofs: proofs,
-        };
-
-        SP1CompressWithVKeyWitnessValues { compress_val: input, merkle_val }
+        })
     }
 
     fn check_for_high_cycles(cycles: u64) {
@@ -1408,6 +3334,31 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
     }
 }
 
+/// The vk root committed to by `proof`, the first proof of an input.
+fn committed_vk_root(proof: Option<&ShardProof<CoreSC>>) -> [BabyBear; DIGEST_SIZE] {
+    proof.map_or([BabyBear::zero(); DIGEST_SIZE], |proof| {
+        let pv: &RecursionPublicValues<BabyBear> = proof.public_values.as_slice().borrow();
+        pv.vk_root
+    })
+}
+
+/// Lock a mutex guarding a cache, recovering from poisoning.
+///
+/// A panic while the lock was held may have left the cache partially updated, so on recovery the
+/// cache is reset with `reset` before the poison flag is cleared.
+pub(crate) fn lock_or_reset<T>(mutex: &Mutex<T>, reset: impl FnOnce(&mut T)) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            tracing::warn!("recovering from a poisoned prover cache, clearing it");
+            let mut guard = poisoned.into_inner();
+            reset(&mut guard);
+            mutex.clear_poison();
+            guard
+        }
+    }
+}
+
 pub fn compress_program_from_input<C: SP1ProverComponents>(
     config: Option<&RecursionShapeConfig<BabyBear, CompressAir<BabyBear>>>,
     compress_prover: &C::CompressProver,
@@ -1480,6 +3431,64 @@ pub mod tests {
         All,
     }
 
+    /// A dummy proof of the first shard of a program of `prover`, with the verifying key of the
+    /// program.
+    pub(crate) fn dummy_core_proof(
+        prover: &SP1Prover<CpuProverComponents>,
+    ) -> (SP1VerifyingKey, ShardProof<CoreSC>) {
+        use std::borrow::BorrowMut;
+
+        let shape = OrderedShape { inner: vec![("Program".to_string(), 4)] };
+        let (vk, mut proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(
+            prover.core_prover.machine(),
+            &shape,
+        );
+        let pv: &mut PublicValues<Word<BabyBear>, BabyBear> =
+            proof.public_values.as_mut_slice().borrow_mut();
+        pv.shard = BabyBear::one();
+        (
+            SP1VerifyingKey { vk, shape_config_digest: None, artifacts: None, domain_tag: None },
+            proof,
+        )
+    }
+
+    /// A prover that does not fix recursion shapes, so that no join program is precompiled.
+    pub(crate) fn unfixed_prover() -> SP1Prover<CpuProverComponents> {
+        SP1Prover::with_config(ProverConfigBundle {
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env().unwrap()
+        })
+    }
+
+    /// A minimal RISC-V ELF halting with `exit_code`, for tests that need ELF bytes.
+    pub(crate) fn halting_elf(exit_code: u16) -> Vec<u8> {
+        const VADDR: u32 = 0x0020_1000;
+        const CODE_OFFSET: u32 = 52 + 32;
+        // addi t0, x0, HALT; addi a0, x0, exit_code; ecall
+        let code = [0x0000_0293, (u32::from(exit_code) << 20) | 0x0513, 0x0000_0073];
+
+        let mut elf = Vec::new();
+        elf.extend_from_slice(b"\x7fELF\x01\x01\x01");
+        elf.resize(16, 0);
+        for half in [2u16, 0xf3] {
+            elf.extend_from_slice(&half.to_le_bytes());
+        }
+        for word in [1, VADDR, 52, 0, 0] {
+            elf.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        for half in [52u16, 32, 1, 40, 0, 0] {
+            elf.extend_from_slice(&half.to_le_bytes());
+        }
+        let code_size = 4 * code.len() as u32;
+        for word in [1, CODE_OFFSET, VADDR, VADDR, code_size, code_size, 5, 4] {
+            elf.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        for word in code {
+            elf.extend_from_slice(&word.to_le_bytes());
+        }
+        elf
+    }
+
     pub fn test_e2e_prover<C: SP1ProverComponents>(
         prover: &SP1Prover<C>,
         elf: &[u8],
@@ -1515,7 +3524,7 @@ pub mod tests {
         let (_, pk_d, program, vk) = prover.setup(elf);
 
         tracing::info!("prove core");
-        let core_proof = prover.prove_core(&pk_d, program, &stdin, opts, context)?;
+        let core_proof = prover.prove_core(&pk_d, program, &stdin, opts.clone(), context)?;
         let public_values = core_proof.public_values.clone();
 
         if env::var("COLLECT_SHAPES").is_ok() {
@@ -1540,7 +3549,7 @@ pub mod tests {
 
         tracing::info!("compress");
         let compress_span = tracing::debug_span!("compress").entered();
-        let compressed_proof = prover.compress(&vk, core_proof, vec![], opts)?;
+        let compressed_proof = prover.compress(&vk, core_proof, vec![], opts.clone())?;
         compress_span.exit();
 
         if verify {
@@ -1553,7 +3562,7 @@ pub mod tests {
         }
 
         tracing::info!("shrink");
-        let shrink_proof = prover.shrink(compressed_proof, opts)?;
+        let shrink_proof = prover.shrink(compressed_proof, opts.clone())?;
 
         if verify {
             tracing::info!("verify shrink");
@@ -1611,8 +3620,11 @@ pub mod tests {
             &wrapped_bn254_proof.vk,
             &wrapped_bn254_proof.proof,
         );
-        let plonk_bn254_proof =
-            prover.wrap_plonk_bn254(wrapped_bn254_proof.clone(), &artifacts_dir);
+        let plonk_bn254_proof = prover.try_wrap_plonk_bn254(
+            wrapped_bn254_proof.clone(),
+            &artifacts_dir,
+            &GnarkLimits::from_env(),
+        )?;
         println!("{plonk_bn254_proof:?}");
 
         prover.verify_plonk_bn254(&plonk_bn254_proof, &vk, &public_values, &artifacts_dir)?;
@@ -1622,7 +3634,11 @@ pub mod tests {
             &wrapped_bn254_proof.vk,
             &wrapped_bn254_proof.proof,
         );
-        let groth16_bn254_proof = prover.wrap_groth16_bn254(wrapped_bn254_proof, &artifacts_dir);
+        let groth16_bn254_proof = prover.try_wrap_groth16_bn254(
+            wrapped_bn254_proof,
+            &artifacts_dir,
+            &GnarkLimits::from_env(),
+        )?;
         println!("{groth16_bn254_proof:?}");
 
         if verify {
@@ -1663,7 +3679,7 @@ pub mod tests {
             &keccak_pk_d,
             keccak_program.clone(),
             &stdin,
-            opts,
+            opts.clone(),
             Default::default(),
         )?;
         let pv_1 = deferred_proof_1.public_values.as_slice().to_vec().clone();
@@ -1675,18 +3691,25 @@ pub mod tests {
         stdin.write(&vec![0u8, 1, 2]);
         stdin.write(&vec![2, 3, 4]);
         stdin.write(&vec![5, 6, 7]);
-        let deferred_proof_2 =
-            prover.prove_core(&keccak_pk_d, keccak_program, &stdin, opts, Default::default())?;
+        let deferred_proof_2 = prover.prove_core(
+            &keccak_pk_d,
+            keccak_program,
+            &stdin,
+            opts.clone(),
+            Default::default(),
+        )?;
         let pv_2 = deferred_proof_2.public_values.as_slice().to_vec().clone();
 
         // Generate recursive proof of first subproof.
         tracing::info!("compress subproof 1");
-        let deferred_reduce_1 = prover.compress(&keccak_vk, deferred_proof_1, vec![], opts)?;
+        let deferred_reduce_1 =
+            prover.compress(&keccak_vk, deferred_proof_1, vec![], opts.clone())?;
         prover.verify_compressed(&deferred_reduce_1, &keccak_vk)?;
 
         // Generate recursive proof of second subproof.
         tracing::info!("compress subproof 2");
-        let deferred_reduce_2 = prover.compress(&keccak_vk, deferred_proof_2, vec![], opts)?;
+        let deferred_reduce_2 =
+            prover.compress(&keccak_vk, deferred_proof_2, vec![], opts.clone())?;
         prover.verify_compressed(&deferred_reduce_2, &keccak_vk)?;
 
         // Run verify program with keccak vkey, subproofs, and their committed values.
@@ -1705,8 +3728,13 @@ pub mod tests {
         stdin.write_proof(deferred_reduce_2.clone(), keccak_vk.vk.clone());
 
         tracing::info!("proving verify program (core)");
-        let verify_proof =
-            prover.prove_core(&verify_pk_d, verify_program, &stdin, opts, Default::default())?;
+        let verify_proof = prover.prove_core(
+            &verify_pk_d,
+            verify_program,
+            &stdin,
+            opts.clone(),
+            Default::default(),
+        )?;
         // let public_values = verify_proof.public_values.clone();
 
         // Generate recursive proof of verify program
@@ -1715,7 +3743,7 @@ pub mod tests {
             &verify_vk,
             verify_proof,
             vec![deferred_reduce_1, deferred_reduce_2.clone(), deferred_reduce_2],
-            opts,
+            opts.clone(),
         )?;
         let reduce_pv: &RecursionPublicValues<_> =
             verify_reduce.proof.public_values.as_slice().borrow();
@@ -1725,7 +3753,7 @@ pub mod tests {
         tracing::info!("verify verify program");
         prover.verify_compressed(&verify_reduce, &verify_vk)?;
 
-        let shrink_proof = prover.shrink(verify_reduce, opts)?;
+        let shrink_proof = prover.shrink(verify_reduce, opts.clone())?;
 
         tracing::info!("verify shrink");
         prover.verify_shrink(&shrink_proof, &verify_vk)?;
@@ -1767,4 +3795,408 @@ pub mod tests {
         setup_logger();
         test_e2e_with_deferred_proofs_prover::<CpuProverComponents>(SP1ProverOpts::auto())
     }
+
+    #[test]
+    fn test_check_vk_artifacts() {
+        use sp1_stark::{air::MachineAir, MachineVerificationError};
+
+        let prover = SP1Prover::<CpuProverComponents>::new();
+        let (mut vk, _) = dummy_core_proof(&prover);
+        assert_eq!(prover.check_vk_artifacts(&vk), Ok(()));
+        vk.artifacts = Some(prover.setup_artifacts());
+        assert_eq!(prover.check_vk_artifacts(&vk), Ok(()));
+
+        let mut other = vk.clone();
+        other.artifacts.as_mut().unwrap().circuit_version = "v0.0.0".to_string();
+        assert!(matches!(
+            prover.check_vk_artifacts(&other),
+            Err(SetupArtifactsMismatch::CircuitVersion { .. })
+        ));
+
+        // Keys set up with other artifacts are rejected before the proof is even looked at.
+        let mut other = vk;
+        other.artifacts.as_mut().unwrap().vk_root[0] += 1;
+        assert!(matches!(
+            prover.check_vk_artifacts(&other),
+            Err(SetupArtifactsMismatch::VkRoot { .. })
+        ));
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (compress_vk, proof) =
+            sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+        assert!(matches!(
+            prover.verify_compressed(&SP1ReduceProof { vk: compress_vk, proof }, &other),
+            Err(MachineVerificationError::IncompatibleVerificationKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_domain_tag_binds_the_vk() {
+        use sp1_core_executor::{Instruction, Opcode};
+
+        // Without core shapes, so that the program is proven at its own small size.
+        let prover = SP1Prover::<CpuProverComponents>::with_fri_opts_and_config(
+            &SP1FriOpts::default(),
+            ProverConfigBundle {
+                core_shape_config: None,
+                compress_shape_config: None,
+                ..ProverConfigBundle::from_env().unwrap()
+            },
+        )
+        .unwrap();
+        let setup = |domain_tag: Option<[u32; DOMAIN_TAG_WORDS]>| {
+            // A program halting with exit code zero.
+            let mut program = Program::new(
+                vec![
+                    Instruction::new(Opcode::ADD, 5, 0, 0, false, true),
+                    Instruction::new(Opcode::ADD, 10, 0, 0, false, true),
+                    Instruction::new(Opcode::ECALL, 5, 10, 11, false, false),
+                ],
+                0x1000,
+                0x1000,
+            );
+            if let Some(domain_tag) = domain_tag {
+                program.tag_domain(domain_tag).unwrap();
+            }
+            let (pk, vk) = prover.setup_host_keys(&[], &program, None);
+            (pk, program, vk)
+        };
+
+        let (pk, program, vk) = setup(Some([1; DOMAIN_TAG_WORDS]));
+        let (_, _, other_vk) = setup(Some([2; DOMAIN_TAG_WORDS]));
+        let (_, _, untagged_vk) = setup(None);
+        assert_eq!(vk.domain_tag, Some([1; DOMAIN_TAG_WORDS]));
+        assert_eq!(untagged_vk.domain_tag, None);
+        assert_ne!(vk.hash_babybear(), other_vk.hash_babybear());
+        assert_ne!(vk.hash_babybear(), untagged_vk.hash_babybear());
+
+        // A proof made in one domain is rejected by the keys of the others.
+        let pk_d = prover.core_prover.pk_to_device(&pk.pk);
+        let proof = prover
+            .prove_core(
+                &pk_d,
+                program,
+                &SP1Stdin::new(),
+                SP1ProverOpts::default(),
+                SP1Context::default(),
+            )
+            .unwrap();
+        prover.verify(&proof.proof, &vk).unwrap();
+        assert!(prover.verify(&proof.proof, &other_vk).is_err());
+        assert!(prover.verify(&proof.proof, &untagged_vk).is_err());
+    }
+
+    #[test]
+    fn test_first_layer_inputs_chain_the_initial_digest() {
+        let prover = SP1Prover::<CpuProverComponents>::new();
+        let (vk, proof) = dummy_core_proof(&prover);
+        let core_input = |digest| {
+            let inputs = prover
+                .get_first_layer_inputs_with_initial_digest(
+                    &vk,
+                    &[proof.clone()],
+                    &[],
+                    digest,
+                    1,
+                    &SP1DeferredOpts::default(),
+                )
+                .unwrap();
+            let Ok([SP1CircuitWitness::Core(input)]) = <[_; 1]>::try_from(inputs) else {
+                panic!("expected a single lift input");
+            };
+            input
+        };
+
+        let input = core_input([BabyBear::zero(); DIGEST_SIZE]);
+        assert!(input.is_complete);
+        assert_eq!(input.reconstruct_deferred_digest, [BabyBear::zero(); DIGEST_SIZE]);
+
+        // A proof continuing an earlier digest is never complete on its own.
+        let digest = [BabyBear::from_canonical_u32(7); DIGEST_SIZE];
+        let input = core_input(digest);
+        assert!(!input.is_complete);
+        assert_eq!(input.reconstruct_deferred_digest, digest);
+    }
+
+    #[test]
+    fn test_deferred_proofs_without_core_shards() {
+        use sp1_stark::air::MachineAir;
+
+        let prover = SP1Prover::<CpuProverComponents>::with_config(ProverConfigBundle {
+            core_shape_config: None,
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env_with_vk_verification(VkVerificationMode::Disabled)
+        });
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+        let deferred = SP1ReduceProof { vk, proof };
+        let (vk, _) = dummy_core_proof(&prover);
+
+        // Without core shards, only the deferred proofs are folded.
+        let inputs =
+            prover.get_first_layer_inputs(&vk, &[], std::slice::from_ref(&deferred), 1).unwrap();
+        assert!(matches!(inputs.as_slice(), [SP1CircuitWitness::Deferred(_)]));
+        assert!(prover.get_first_layer_inputs(&vk, &[], &[], 1).is_err());
+
+        // The deferred proof does not commit to the program of `vk`.
+        assert!(matches!(
+            prover.verify_deferred_only(&deferred, &vk, &[]),
+            Err(sp1_stark::MachineVerificationError::InvalidPublicValues("sp1 vk hash mismatch"))
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_strict_mode_is_an_error() {
+        let prover = unfixed_prover();
+
+        std::env::set_var("SP1_STRICT", "not_a_warning_class");
+        let result = SP1Prover::from_core(prover.core.clone());
+        std::env::remove_var("SP1_STRICT");
+        assert!(matches!(result, Err(SP1ProverConfigError::Strict(_))));
+
+        // Handles copy the settings of their prover instead of reading them again.
+        let prover = prover.with_strict(StrictMode::all());
+        assert_eq!(prover.handle().strict, StrictMode::all());
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_artifact_key_is_an_error() {
+        let prover = unfixed_prover();
+
+        std::env::set_var("SP1_ARTIFACT_KEY", "not hex");
+        let result = SP1Prover::from_core(prover.core.clone());
+        std::env::remove_var("SP1_ARTIFACT_KEY");
+        assert!(matches!(result, Err(SP1ProverConfigError::ArtifactKey(_))));
+
+        std::env::set_var("SP1_ARTIFACT_KEY", hex::encode([7; 32]));
+        let result = SP1Prover::from_core(prover.core.clone());
+        std::env::remove_var("SP1_ARTIFACT_KEY");
+        assert!(result.unwrap().artifact_cipher.is_some());
+    }
+
+    #[test]
+    fn test_single_input_validates_plan() {
+        use crate::reduction::ReductionPlan;
+
+        /// A strategy planning a tree of two inputs whatever the number of inputs.
+        struct TwoInputs;
+
+        impl ReductionStrategy for TwoInputs {
+            fn plan(&self, _num_inputs: usize, _arity: usize) -> ReductionPlan {
+                ReductionPlan { num_inputs: 2, joins: vec![vec![0, 1]] }
+            }
+        }
+
+        let prover = unfixed_prover().with_reduction_strategy(TwoInputs);
+        let (vk, proof) = dummy_core_proof(&prover);
+        let input = prover
+            .get_first_layer_inputs(&vk, &[proof], &[], 1)
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap();
+
+        let result = prover.reduce_single_input(input, true, &SP1ProverOpts::default());
+        assert!(matches!(result, Err(SP1RecursionProverError::InvalidReductionPlan(_))));
+    }
+
+    #[test]
+    fn test_mismatched_fri_opts_are_an_error() {
+        use sp1_stark::air::MachineAir;
+
+        let prover = unfixed_prover();
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+        let reduced = SP1ReduceProof { vk, proof };
+
+        let fri_opts = prover.fri_opts().insecure();
+        let opts = SP1ProverOpts { fri_opts, ..SP1ProverOpts::default() };
+        let result = prover.shrink(reduced.clone(), opts.clone());
+        assert!(matches!(
+            result,
+            Err(SP1RecursionProverError::FriParams(FriParamsMismatch { stage: "shrink", .. }))
+        ));
+        let results = prover.shrink_many(vec![reduced.clone(), reduced], opts);
+        assert!(results.iter().all(|result| matches!(
+            result,
+            Err(SP1RecursionProverError::FriParams(FriParamsMismatch { stage: "shrink", .. }))
+        )));
+    }
+
+    #[test]
+    fn test_recursion_program_compiles_once() {
+        let prover = unfixed_prover();
+        let (vk, proof) = dummy_core_proof(&prover);
+        let Some(SP1CircuitWitness::Core(input)) =
+            prover.get_first_layer_inputs(&vk, &[proof], &[], 1).unwrap().into_iter().next()
+        else {
+            panic!("expected a lift input");
+        };
+
+        // Concurrent requests for the same shape wait for a single compilation on the pool.
+        let programs = thread::scope(|s| {
+            let handles = (0..4).map(|_| s.spawn(|| prover.recursion_program(&input)));
+            handles.collect::<Vec<_>>().into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(prover.lift_cache_misses.load(Ordering::Relaxed), 1);
+        assert!(programs.iter().all(|program| Arc::ptr_eq(program, &programs[0])));
+    }
+
+    #[test]
+    fn test_deferred_input_stream() {
+        use sp1_stark::air::MachineAir;
+
+        let config = |vk_verification| ProverConfigBundle {
+            compress_shape_config: None,
+            vk_verification,
+            ..ProverConfigBundle::from_env().unwrap()
+        };
+        let prover =
+            SP1Prover::<CpuProverComponents>::with_config(config(VkVerificationMode::Disabled));
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+        let proofs = vec![SP1ReduceProof { vk, proof }; 5];
+        let batches = proofs.chunks(2).collect::<Vec<_>>();
+        let (sp1_vk_digest, pc_start) = ([BabyBear::one(); DIGEST_SIZE], BabyBear::one());
+        let start = [BabyBear::zero(); DIGEST_SIZE];
+
+        // The stream yields the inputs generated eagerly, and ends at the same deferred digest.
+        let (expected, expected_digest) = prover
+            .get_recursion_deferred_inputs_for(sp1_vk_digest, pc_start, batches.clone(), start)
+            .unwrap();
+        let (inputs, digest) =
+            prover.deferred_input_stream(sp1_vk_digest, pc_start, batches.clone(), start).unwrap();
+        let inputs = inputs.collect::<Vec<_>>();
+        assert_eq!(digest, expected_digest);
+        assert_eq!(inputs.len(), expected.len());
+        for (input, expected) in inputs.iter().zip(&expected) {
+            assert_eq!(
+                input.start_reconstruct_deferred_digest,
+                expected.start_reconstruct_deferred_digest
+            );
+            assert_eq!(input.vks_and_proofs.len(), expected.vks_and_proofs.len());
+            assert_eq!(input.vk_merkle_data.values, expected.vk_merkle_data.values);
+        }
+
+        // A verifying key outside of the vk map fails before any input is generated.
+        let prover =
+            SP1Prover::<CpuProverComponents>::with_config(config(VkVerificationMode::Enforced));
+        assert!(prover.deferred_input_stream(sp1_vk_digest, pc_start, batches, start).is_err());
+    }
+
+    #[test]
+    fn test_prover_handle() {
+        let mut prover = unfixed_prover();
+        prover.lift_cache_misses.store(1, Ordering::Relaxed);
+
+        // A handle shares the core of the prover but has its own program caches.
+        let handle = prover.handle();
+        assert!(Arc::ptr_eq(&prover.core, &handle.core));
+        assert_eq!(handle.recursion_vk_root, prover.recursion_vk_root);
+        assert_eq!(handle.lift_cache_misses.load(Ordering::Relaxed), 0);
+
+        // The core can only be modified once it is no longer shared.
+        assert!(prover.core_mut().is_none());
+        drop(handle);
+        prover.core_mut().unwrap().vk_verification = VkVerificationMode::Disabled;
+        assert_eq!(prover.vk_verification, VkVerificationMode::Disabled);
+    }
+
+    #[test]
+    fn test_shrink_setup_is_cached() {
+        let prover = unfixed_prover();
+        let shape = SP1CompressWithVkeyShape {
+            compress_shape: vec![OrderedShape::from(ShrinkAir::<BabyBear>::shrink_shape())].into(),
+            merkle_tree_height: prover.recursion_vk_tree.height,
+            merkle_tree_config: prover.recursion_vk_tree.config,
+        };
+        let setup = prover.shrink_setup(&shape);
+        assert_eq!(setup.shape, shape);
+        assert!(Arc::ptr_eq(&setup, &prover.shrink_setup(&shape)));
+        prover.start_shrink_setup(shape.clone());
+        assert!(prover.pending_shrink_setup.lock().unwrap().is_none());
+
+        // A setup started in the background is taken over by the next setup of its shape.
+        let prover = prover.handle();
+        prover.start_shrink_setup(shape.clone());
+        let pending = prover.pending_shrink_setup.lock().unwrap().as_ref().map(|p| p.shape.clone());
+        assert_eq!(pending, Some(shape.clone()));
+        let setup = prover.shrink_setup(&shape);
+        assert!(prover.pending_shrink_setup.lock().unwrap().is_none());
+        assert!(Arc::ptr_eq(&setup, &prover.shrink_setup(&shape)));
+    }
+
+    #[test]
+    fn test_strict_join_program_recompute_is_an_error() {
+        let prover = unfixed_prover().with_strict(StrictMode::all());
+        let shape = SP1CompressWithVkeyShape {
+            compress_shape: vec![OrderedShape::from(ShrinkAir::<BabyBear>::shrink_shape())].into(),
+            merkle_tree_height: prover.recursion_vk_tree.height,
+            merkle_tree_config: prover.recursion_vk_tree.config,
+        };
+        let input =
+            SP1CompressWithVKeyWitnessValues::dummy(prover.compress_prover.machine(), &shape);
+
+        let result = prover.compress_program(&input);
+        assert!(matches!(result, Err(StrictModeError { class: WarningClass::CacheRecompute, .. })));
+        assert!(lock_or_reset(&prover.join_programs_fallback, BTreeMap::clear).is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_stages_fail() {
+        use sp1_stark::air::MachineAir;
+
+        let cancellation = CancellationToken::new();
+        let prover = SP1Prover::<CpuProverComponents>::with_config(ProverConfigBundle {
+            core_shape_config: None,
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env().unwrap()
+        })
+        .with_cancellation(cancellation.clone());
+        let (_, pk_d, program, _) = prover.setup(&halting_elf(0));
+        cancellation.cancel();
+
+        let result = prover.prove_core(
+            &pk_d,
+            program,
+            &SP1Stdin::new(),
+            SP1ProverOpts::default(),
+            SP1Context::default(),
+        );
+        assert!(matches!(result, Err(SP1CoreProverError::Cancelled)));
+
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+        let result = prover.shrink(SP1ReduceProof { vk, proof }, SP1ProverOpts::default());
+        assert!(matches!(result, Err(SP1RecursionProverError::Cancelled)));
+    }
+
+    #[test]
+    fn test_compress_streaming_rejects_deferred_proofs_not_verified_by_the_program() {
+        use sp1_stark::air::MachineAir;
+
+        let prover = unfixed_prover();
+        let (vk, shard_proof) = dummy_core_proof(&prover);
+        let proof = SP1CoreProof {
+            proof: SP1CoreProofData(vec![shard_proof]),
+            stdin: SP1Stdin::new(),
+            public_values: SP1PublicValues::new(),
+            cycles: 0,
+        };
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (deferred_vk, deferred_proof) =
+            sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+
+        // The core proof commits to an empty deferred digest.
+        let deferred = SP1ReduceProof { vk: deferred_vk, proof: deferred_proof };
+        let result = prover.compress_streaming(&vk, proof, [deferred], SP1ProverOpts::default());
+        assert!(matches!(result, Err(SP1RecursionProverError::DeferredDigestMismatch)));
+    }
 }
@@ -0,0 +1,343 @@
+//! Merging compressed proofs of the same program, and aggregating proofs of any programs.
+//!
+//! Merging reuses the deferred proof verification program: each compressed proof is verified as a
+//! deferred proof, and the resulting proofs are joined with the compress program. The deferred
+//! digest exposed in the public values of the merged proof is a hash chain over the verifying key
+//! digest and committed values digest of each merged proof, so it commits to all of them in order.
+//!
+//! Aggregation works the same way without requiring the proofs to be of the same program, since
+//! the digest chain binds the verifying key digest of each proof anyway. The aggregated proof is
+//! not a proof of any program, so its own verifying key digest is zero.
+
+use p3_baby_bear::BabyBear;
+use p3_field::AbstractField;
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::reduce::SP1ReduceProof;
+use sp1_primitives::hash_deferred_proof;
+use sp1_stark::{
+    MachineProof, MachineProver, MachineVerificationError, StarkGenericConfig, DIGEST_SIZE,
+};
+
+use crate::{
+    components::SP1ProverComponents,
+    public_values::{PublicValuesError, ReduceProofPublicValues},
+    utils::is_recursion_public_values_valid,
+    CoreSC, HashableKey, InnerSC, SP1CircuitWitness, SP1Prover, SP1ProverOpts,
+    SP1RecursionProverError, SP1VerifyingKey,
+};
+
+/// A proof that several compressed proofs of the same program were verified.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SP1MergedProof {
+    /// The proof of the merge.
+    pub proof: SP1ReduceProof<InnerSC>,
+    /// The committed values digests of the merged proofs, in the order they were merged.
+    pub committed_value_digests: Vec<[u8; 32]>,
+}
+
+impl SP1MergedProof {
+    /// The digest the merged proof commits to, given the verifying key of the merged program.
+    #[must_use]
+    pub fn digest(&self, vk: &SP1VerifyingKey) -> [BabyBear; DIGEST_SIZE] {
+        merged_digest(vk, &self.committed_value_digests)
+    }
+}
+
+/// A proof that several compressed proofs, of any programs, were verified.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SP1AggregatedProof {
+    /// The proof of the aggregation.
+    pub proof: SP1ReduceProof<InnerSC>,
+    /// The verifying key digest and committed values digest of each aggregated proof, in the
+    /// order they were aggregated.
+    pub aggregated: Vec<([BabyBear; DIGEST_SIZE], [u8; 32])>,
+}
+
+impl SP1AggregatedProof {
+    /// The digest the aggregated proof commits to.
+    #[must_use]
+    pub fn digest(&self) -> [BabyBear; DIGEST_SIZE] {
+        aggregated_digest(&self.aggregated)
+    }
+}
+
+/// Compute the digest committed to by a merge of proofs of `vk` with the given committed values
+/// digests.
+#[must_use]
+pub fn merged_digest(
+    vk: &SP1VerifyingKey,
+    committed_value_digests: &[[u8; 32]],
+) -> [BabyBear; DIGEST_SIZE] {
+    let vk_digest = vk.hash_babybear();
+    chain_digest(committed_value_digests.iter().map(|committed| (&vk_digest, committed)))
+}
+
+/// Compute the digest committed to by an aggregation of proofs with the given verifying key
+/// digests and committed values digests.
+#[must_use]
+pub fn aggregated_digest(
+    aggregated: &[([BabyBear; DIGEST_SIZE], [u8; 32])],
+) -> [BabyBear; DIGEST_SIZE] {
+    chain_digest(aggregated.iter().map(|(vk_digest, committed)| (vk_digest, committed)))
+}
+
+fn chain_digest<'a>(
+    proofs: impl Iterator<Item = (&'a [BabyBear; DIGEST_SIZE], &'a [u8; 32])>,
+) -> [BabyBear; DIGEST_SIZE] {
+    proofs.fold([BabyBear::zero(); DIGEST_SIZE], |digest, (vk_digest, committed)| {
+        let committed = committed.map(BabyBear::from_canonical_u8);
+        hash_deferred_proof(&digest, vk_digest, &committed)
+    })
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Merge two compressed proofs of the same program into a single proof exposing both of their
+    /// committed values digests.
+    #[tracing::instrument(name = "merge_compressed", level = "info", skip_all)]
+    pub fn merge_compressed(
+        &self,
+        vk: &SP1VerifyingKey,
+        first: SP1ReduceProof<InnerSC>,
+        second: SP1ReduceProof<InnerSC>,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1MergedProof, SP1RecursionProverError> {
+        self.merge_compressed_many(vk, vec![first, second], opts)
+    }
+
+    /// Merge compressed proofs of the same program into a single proof exposing all of their
+    /// committed values digests.
+    ///
+    /// The proofs are merged with a balanced tree of compress programs, so this can be used to
+    /// aggregate large numbers of proofs outside of the normal shard tree.
+    pub fn merge_compressed_many(
+        &self,
+        vk: &SP1VerifyingKey,
+        proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1MergedProof, SP1RecursionProverError> {
+        if proofs.is_empty() {
+            return Err(SP1RecursionProverError::InvalidMergeInput("no proofs to merge"));
+        }
+
+        let vk_digest = vk.hash_babybear();
+        let mut committed_value_digests = Vec::with_capacity(proofs.len());
+        for proof in proofs.iter() {
+            let invalid =
+                |e: PublicValuesError| SP1RecursionProverError::InvalidMergeInput(e.reason());
+            if proof.sp1_vk_digest().map_err(invalid)? != vk_digest {
+                return Err(SP1RecursionProverError::InvalidMergeInput("sp1 vk hash mismatch"));
+            }
+            if !proof.is_complete().map_err(invalid)? {
+                return Err(SP1RecursionProverError::InvalidMergeInput("proof is not complete"));
+            }
+            committed_value_digests.push(proof.committed_value_digest().map_err(invalid)?);
+        }
+
+        let proof = self.reduce_deferred(vk.vk.hash_babybear(), vk.vk.pc_start, &proofs, opts)?;
+        Ok(SP1MergedProof { proof, committed_value_digests })
+    }
+
+    /// Aggregate compressed proofs of any programs into a single proof exposing the verifying key
+    /// digest and committed values digest of each of them.
+    ///
+    /// Like [`Self::merge_compressed_many`], the proofs are joined with a tree of compress
+    /// programs, shaped by the reduction strategy of the prover.
+    #[tracing::instrument(name = "aggregate", level = "info", skip_all)]
+    pub fn aggregate(
+        &self,
+        proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1AggregatedProof, SP1RecursionProverError> {
+        if proofs.is_empty() {
+            return Err(SP1RecursionProverError::InvalidMergeInput("no proofs to aggregate"));
+        }
+
+        let mut aggregated = Vec::with_capacity(proofs.len());
+        for proof in proofs.iter() {
+            let invalid =
+                |e: PublicValuesError| SP1RecursionProverError::InvalidMergeInput(e.reason());
+            if !proof.is_complete().map_err(invalid)? {
+                return Err(SP1RecursionProverError::InvalidMergeInput("proof is not complete"));
+            }
+            aggregated.push((
+                proof.sp1_vk_digest().map_err(invalid)?,
+                proof.committed_value_digest().map_err(invalid)?,
+            ));
+        }
+
+        let proof =
+            self.reduce_deferred([BabyBear::zero(); DIGEST_SIZE], BabyBear::zero(), &proofs, opts)?;
+        Ok(SP1AggregatedProof { proof, aggregated })
+    }
+
+    /// Verify each of `proofs` as a deferred proof of the program with the given verifying key
+    /// digest and start pc, and join the results into a single proof.
+    fn reduce_deferred(
+        &self,
+        sp1_vk_digest: [BabyBear; DIGEST_SIZE],
+        pc_start: BabyBear,
+        proofs: &[SP1ReduceProof<InnerSC>],
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        // The inputs are generated as the tree consumes them, so that only the proofs being
+        // verified are copied, however many proofs are folded.
+        let (deferred_inputs, _) = self.deferred_input_stream(
+            sp1_vk_digest,
+            pc_start,
+            proofs.chunks(1).collect(),
+            [BabyBear::zero(); DIGEST_SIZE],
+        )?;
+        let (vk, proof) = self.reduce_tree(
+            deferred_inputs.map(|input| (SP1CircuitWitness::Deferred(input), false)),
+            proofs.len(),
+            false,
+            opts,
+        )?;
+        Ok(SP1ReduceProof { vk, proof })
+    }
+
+    /// Verify a merged proof of compressed proofs of `vk`.
+    ///
+    /// On success, every entry of `merged.committed_value_digests` is the committed values digest
+    /// of a valid execution of the program.
+    pub fn verify_merged(
+        &self,
+        merged: &SP1MergedProof,
+        vk: &SP1VerifyingKey,
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        // The deferred digest chain binds the vk digest of every merged proof, so checking it
+        // against `vk` also checks that all the merged proofs are proofs of `vk`.
+        self.verify_deferred_chain(&merged.proof, merged.digest(vk), "merged digest mismatch")
+    }
+
+    /// Verify an aggregated proof of compressed proofs.
+    ///
+    /// On success, every entry of `aggregated.aggregated` is the verifying key digest and
+    /// committed values digest of a valid execution of a program.
+    pub fn verify_aggregated(
+        &self,
+        aggregated: &SP1AggregatedProof,
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        self.verify_deferred_chain(
+            &aggregated.proof,
+            aggregated.digest(),
+            "aggregated digest mismatch",
+        )
+    }
+
+    /// Verify a proof compressed from deferred proofs alone, see [`Self::compress`].
+    ///
+    /// On success, the proof verified `deferred_proofs` for the program of `vk`, in order.
+    pub fn verify_deferred_only(
+        &self,
+        proof: &SP1ReduceProof<InnerSC>,
+        vk: &SP1VerifyingKey,
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        let sp1_vk_digest = proof
+            .sp1_vk_digest()
+            .map_err(|e| MachineVerificationError::InvalidPublicValues(e.reason()))?;
+        if sp1_vk_digest != vk.hash_babybear() {
+            return Err(MachineVerificationError::InvalidPublicValues("sp1 vk hash mismatch"));
+        }
+        let digest = Self::hash_deferred_proofs([BabyBear::zero(); DIGEST_SIZE], deferred_proofs);
+        self.verify_deferred_chain(proof, digest, "deferred proofs digest mismatch")
+    }
+
+    /// Verify a proof made by [`Self::reduce_deferred`], whose deferred digest chain must end at
+    /// `digest`.
+    fn verify_deferred_chain(
+        &self,
+        proof: &SP1ReduceProof<InnerSC>,
+        digest: [BabyBear; DIGEST_SIZE],
+        mismatch: &'static str,
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        let merge_vk = &proof.vk;
+        let mut challenger = self.compress_prover.config().challenger();
+        let machine_proof = MachineProof { shard_proofs: vec![proof.proof.clone()] };
+        self.compress_prover.machine().verify(merge_vk, &machine_proof, &mut challenger)?;
+
+        let public_values = proof
+            .recursion_public_values()
+            .map_err(|e| MachineVerificationError::InvalidPublicValues(e.reason()))?;
+        if !is_recursion_public_values_valid(self.compress_prover.machine().config(), public_values)
+        {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "recursion public values are invalid",
+            ));
+        }
+
+        if !self.vk_root_status(&public_values.vk_root).is_accepted() {
+            return Err(MachineVerificationError::InvalidPublicValues("vk_root mismatch"));
+        }
+
+        if !self.is_vk_allowed_under(&public_values.vk_root, &merge_vk.hash_babybear()) {
+            return Err(MachineVerificationError::InvalidVerificationKey);
+        }
+
+        if public_values.start_reconstruct_deferred_digest != [BabyBear::zero(); DIGEST_SIZE] {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "deferred digest should start at zero",
+            ));
+        }
+        if public_values.end_reconstruct_deferred_digest != digest {
+            return Err(MachineVerificationError::InvalidPublicValues(mismatch));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serial_test::serial;
+    use sp1_core_machine::{io::SP1Stdin, utils::setup_logger};
+
+    use super::*;
+    use crate::components::CpuProverComponents;
+
+    /// Compressed proofs of the Keccak-256 program, one for each input.
+    pub(crate) fn compressed_keccak_proofs(
+        prover: &SP1Prover<CpuProverComponents>,
+        inputs: &[Vec<u8>],
+    ) -> (SP1VerifyingKey, Vec<SP1ReduceProof<InnerSC>>) {
+        let opts = SP1ProverOpts::auto();
+        let (_, pk_d, program, vk) = prover.setup(test_artifacts::KECCAK256_ELF);
+        let proofs = inputs
+            .iter()
+            .map(|input| {
+                let mut stdin = SP1Stdin::new();
+                stdin.write(&1usize);
+                stdin.write(input);
+                let core_proof = prover
+                    .prove_core(&pk_d, program.clone(), &stdin, opts.clone(), Default::default())
+                    .unwrap();
+                prover.compress(&vk, core_proof, vec![], opts.clone()).unwrap()
+            })
+            .collect();
+        (vk, proofs)
+    }
+
+    #[test]
+    #[serial]
+    fn test_merge_compressed() {
+        setup_logger();
+        let prover = SP1Prover::<CpuProverComponents>::new();
+        let (vk, proofs) = compressed_keccak_proofs(&prover, &[vec![0, 0, 0], vec![1, 2, 3]]);
+        let (first, second) = (proofs[0].clone(), proofs[1].clone());
+
+        let merged = prover.merge_compressed(&vk, first, second, SP1ProverOpts::auto()).unwrap();
+        assert_eq!(merged.committed_value_digests.len(), 2);
+        prover.verify_merged(&merged, &vk).unwrap();
+
+        // The merged proof does not verify against other committed values digests.
+        let mut tampered = merged.clone();
+        tampered.committed_value_digests[1][0] ^= 1;
+        assert!(prover.verify_merged(&tampered, &vk).is_err());
+
+        // Nor against the same digests in another order.
+        let mut reordered = merged;
+        reordered.committed_value_digests.reverse();
+        assert!(prover.verify_merged(&reordered, &vk).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Execution statistics read from proofs.
+//!
+//! Consumers of a proof often want statistics about the execution it proves, such as its number
+//! of shards, without trusting whoever sent the proof. [`ProofMetadata`] reads them from the
+//! public values of a core or reduce proof, which the guest cannot forge since they are
+//! constrained by the circuits rather than committed by the program. They should only be relied on
+//! once the proof is verified.
+//!
+//! The cycle count and the labeled segments of the standard input are not constrained by the
+//! circuits, so they are only carried as reported by the prover and are not attested by the proof.
+
+use std::borrow::Borrow;
+
+use p3_baby_bear::BabyBear;
+use p3_field::PrimeField32;
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::{InputKind, InputSegment};
+use sp1_core_machine::io::SP1Stdin;
+use sp1_stark::{air::PublicValues, StarkGenericConfig, Word};
+
+use crate::{
+    components::SP1ProverComponents,
+    public_values::{PublicValuesError, ReduceProofPublicValues},
+    reload::VkVerificationMode,
+    rotation::VkRootStatus,
+    SP1CoreProof, SP1Prover, SP1ReduceProof, SP1_CIRCUIT_VERSION,
+};
+
+/// Statistics about the execution proven by a proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofMetadata {
+    /// The circuit version of the prover reading the metadata, which the proof only verifies
+    /// against if it was made with the same version.
+    pub circuit_version: String,
+    /// The number of shards proven, including precompile shards.
+    pub num_shards: u32,
+    /// The number of execution shards proven.
+    pub num_execution_shards: u32,
+    /// Whether the proof covers the whole execution.
+    pub is_complete: bool,
+    /// The number of cycles, as reported by the prover. This is not attested by the proof.
+    pub reported_cycles: Option<u64>,
+    /// Whether the recursion programs of the proof checked verifying keys, as either
+    /// [`VkVerificationMode::Enforced`] or [`VkVerificationMode::Disabled`].
+    ///
+    /// This is only known for reduce proofs read by [`SP1Prover::reduce_proof_metadata`], from the
+    /// vk root they commit to.
+    #[serde(default)]
+    pub vk_verification: Option<VkVerificationMode>,
+    /// The labeled segments of the standard input, telling the hints apart from the committed
+    /// inputs, as reported by the prover. These are not attested by the proof.
+    #[serde(default)]
+    pub input_segments: Vec<InputSegment>,
+}
+
+impl ProofMetadata {
+    /// Read the metadata of a core proof. The cycle count of the proof is reported as is.
+    #[must_use]
+    pub fn from_core_proof(proof: &SP1CoreProof) -> Self {
+        let mut metadata = Self {
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            num_shards: 0,
+            num_execution_shards: 0,
+            is_complete: false,
+            reported_cycles: Some(proof.cycles),
+            vk_verification: None,
+            input_segments: Vec::new(),
+        };
+        for shard_proof in &proof.proof.0 {
+            let public_values: &PublicValues<Word<BabyBear>, BabyBear> =
+                shard_proof.public_values.as_slice().borrow();
+            metadata.num_shards = metadata.num_shards.max(public_values.shard.as_canonical_u32());
+            metadata.num_execution_shards =
+                metadata.num_execution_shards.max(public_values.execution_shard.as_canonical_u32());
+            metadata.is_complete |= public_values.next_pc.as_canonical_u32() == 0;
+        }
+        metadata
+    }
+
+    /// Read the metadata of a compress, shrink or wrap proof, with the cycle count reported by
+    /// the prover, if any.
+    pub fn from_reduce_proof<SC: StarkGenericConfig<Val = BabyBear>>(
+        proof: &SP1ReduceProof<SC>,
+        reported_cycles: Option<u64>,
+    ) -> Result<Self, PublicValuesError> {
+        let public_values = proof.recursion_public_values()?;
+        let shards = proof.shard_range()?;
+        let execution_shards = public_values.start_execution_shard.as_canonical_u32()..
+            public_values.next_execution_shard.as_canonical_u32();
+        Ok(Self {
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            num_shards: shards.len() as u32,
+            num_execution_shards: execution_shards.len() as u32,
+            is_complete: proof.is_complete()?,
+            reported_cycles,
+            vk_verification: None,
+            input_segments: Vec::new(),
+        })
+    }
+
+    /// Record the labeled segments of `stdin`, the standard input the proof was made with.
+    #[must_use]
+    pub fn with_input_segments(mut self, stdin: &SP1Stdin) -> Self {
+        self.input_segments = stdin.segments.clone();
+        self
+    }
+
+    /// Whether the standard input of the proof had hint segments, as reported by the prover.
+    #[must_use]
+    pub fn has_hints(&self) -> bool {
+        self.input_segments.iter().any(|segment| segment.kind == InputKind::Hint)
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Like [`ProofMetadata::from_reduce_proof`], also reading how the proof checked verifying
+    /// keys from its vk root, if the prover knows the root.
+    pub fn reduce_proof_metadata<SC: StarkGenericConfig<Val = BabyBear>>(
+        &self,
+        proof: &SP1ReduceProof<SC>,
+        reported_cycles: Option<u64>,
+    ) -> Result<ProofMetadata, PublicValuesError> {
+        let mut metadata = ProofMetadata::from_reduce_proof(proof, reported_cycles)?;
+        metadata.vk_verification = match self.vk_root_status(&proof.vk_root()?) {
+            VkRootStatus::Active | VkRootStatus::Retiring => {
+                Some(if self.vk_verification.checks_vks() {
+                    VkVerificationMode::Enforced
+                } else {
+                    VkVerificationMode::Disabled
+                })
+            }
+            VkRootStatus::Unverified => Some(VkVerificationMode::Disabled),
+            VkRootStatus::Unknown => None,
+        };
+        Ok(metadata)
+    }
+}
@@ -0,0 +1,306 @@
+//! Cost accounting of proving jobs.
+//!
+//! A multi-tenant prover service bills jobs by the resources they use. A [`CostMeter`] attributes
+//! CPU-seconds, GPU-seconds and memory-byte-seconds to the job id of the [`TraceMetadata`] of each
+//! stage it meters, and reports them per stage in a [`JobCost`].
+//!
+//! The usage of the process is sampled every [`COST_SAMPLE_INTERVAL`] while a stage is metered,
+//! and at the start and end of every stage. When stages of several jobs run at the same time, each
+//! sample is split evenly between them, since the process cannot tell which job a thread works
+//! for. Every metered stage is also emitted as an `info` event with target
+//! `sp1_prover::metering`, so that a metrics layer can export it.
+//!
+//! Stages are only metered when the job id is set. The GPU time of stages proven on a device is
+//! charged by whoever drives the device, see [`CostMeter::charge_gpu`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::TraceMetadata;
+use sysinfo::{Pid, ProcessRefreshKind, System};
+
+use crate::lock_or_reset;
+
+/// How often the usage of the process is sampled while a stage is metered.
+pub const COST_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A stage of the proving pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum CostStage {
+    /// Executing the program.
+    Execute,
+    /// Proving the core shards.
+    Core,
+    /// Compressing the core proof.
+    Compress,
+    /// Shrinking and wrapping the compressed proof, including the gnark proof.
+    Wrap,
+}
+
+impl fmt::Display for CostStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Execute => "execute",
+            Self::Core => "core",
+            Self::Compress => "compress",
+            Self::Wrap => "wrap",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The resources used by a stage or a job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// The CPU time, summed over all cores.
+    pub cpu_seconds: f64,
+    /// The GPU time, summed over all devices.
+    pub gpu_seconds: f64,
+    /// The resident memory integrated over time.
+    pub memory_byte_seconds: f64,
+}
+
+impl ResourceUsage {
+    pub fn add(&mut self, other: &ResourceUsage) {
+        self.cpu_seconds += other.cpu_seconds;
+        self.gpu_seconds += other.gpu_seconds;
+        self.memory_byte_seconds += other.memory_byte_seconds;
+    }
+}
+
+/// The resources used by a job, by stage.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobCost {
+    pub job_id: String,
+    pub tenant_id: Option<String>,
+    pub stages: BTreeMap<CostStage, ResourceUsage>,
+}
+
+impl JobCost {
+    /// The resources used by all the stages of the job.
+    #[must_use]
+    pub fn total(&self) -> ResourceUsage {
+        let mut total = ResourceUsage::default();
+        self.stages.values().for_each(|usage| total.add(usage));
+        total
+    }
+}
+
+/// Attributes the resources used by the process to the jobs it proves.
+///
+/// See the [module documentation](self) for how the usage is attributed.
+#[derive(Debug, Default)]
+pub struct CostMeter {
+    state: Arc<Mutex<MeterState>>,
+}
+
+#[derive(Debug, Default)]
+struct MeterState {
+    next_id: u64,
+    active: HashMap<u64, ActiveStage>,
+    jobs: HashMap<String, JobCost>,
+    sampler: Option<Sampler>,
+}
+
+#[derive(Debug)]
+struct ActiveStage {
+    job_id: String,
+    tenant_id: Option<String>,
+    stage: CostStage,
+    usage: ResourceUsage,
+}
+
+/// The process usage since the last sample.
+#[derive(Debug)]
+struct Sampler {
+    system: System,
+    pid: Pid,
+    last_sample: Instant,
+}
+
+impl MeterState {
+    /// Split the usage of the process since the last sample between the active stages.
+    fn sample(&mut self) {
+        let Some(sampler) = &mut self.sampler else { return };
+        let now = Instant::now();
+        let elapsed = now.duration_since(sampler.last_sample).as_secs_f64();
+        sampler.last_sample = now;
+        if self.active.is_empty() {
+            return;
+        }
+        sampler.system.refresh_process_specifics(sampler.pid, ProcessRefreshKind::new().with_cpu());
+        let Some(process) = sampler.system.process(sampler.pid) else { return };
+        let share = self.active.len() as f64;
+        let cpu_seconds = f64::from(process.cpu_usage()) / 100.0 * elapsed / share;
+        let memory_byte_seconds = process.memory() as f64 * elapsed / share;
+        for stage in self.active.values_mut() {
+            stage.usage.cpu_seconds += cpu_seconds;
+            stage.usage.memory_byte_seconds += memory_byte_seconds;
+        }
+    }
+
+    fn charge(&mut self, job_id: &str, tenant_id: Option<&str>, stage: CostStage) -> &mut JobCost {
+        let job = self
+            .jobs
+            .entry(job_id.to_string())
+            .or_insert_with(|| JobCost { job_id: job_id.to_string(), ..JobCost::default() });
+        if job.tenant_id.is_none() {
+            job.tenant_id = tenant_id.map(ToString::to_string);
+        }
+        job.stages.entry(stage).or_default();
+        job
+    }
+}
+
+impl CostMeter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Meter a stage of the job of `metadata` until the returned guard is dropped.
+    ///
+    /// Nothing is metered if the job id is not set.
+    pub fn stage(&self, metadata: &TraceMetadata, stage: CostStage) -> StageCost<'_> {
+        let Some(job_id) = metadata.job_id.clone() else {
+            return StageCost { meter: self, id: None };
+        };
+        let mut state = lock_or_reset(&self.state, |s| *s = MeterState::default());
+        if state.sampler.is_none() {
+            let Ok(pid) = sysinfo::get_current_pid() else {
+                tracing::warn!("cannot meter the cost of job {job_id}: no process id");
+                return StageCost { meter: self, id: None };
+            };
+            let mut system = System::new();
+            system.refresh_process_specifics(pid, ProcessRefreshKind::new().with_cpu());
+            state.sampler = Some(Sampler { system, pid, last_sample: Instant::now() });
+        }
+        state.sample();
+
+        let id = state.next_id;
+        state.next_id += 1;
+        let tenant_id = metadata.tenant_id.clone();
+        state.charge(&job_id, tenant_id.as_deref(), stage);
+        state
+            .active
+            .insert(id, ActiveStage { job_id, tenant_id, stage, usage: ResourceUsage::default() });
+        if state.active.len() == 1 {
+            let state = self.state.clone();
+            thread::Builder::new()
+                .name("cost-sampler".to_string())
+                .spawn(move || loop {
+                    thread::sleep(COST_SAMPLE_INTERVAL);
+                    let mut state = lock_or_reset(&state, |s| *s = MeterState::default());
+                    if state.active.is_empty() {
+                        break;
+                    }
+                    state.sample();
+                })
+                .expect("failed to spawn the cost sampler");
+        }
+        StageCost { meter: self, id: Some(id) }
+    }
+
+    /// Charge `duration` of GPU time to a stage of the job of `metadata`.
+    pub fn charge_gpu(&self, metadata: &TraceMetadata, stage: CostStage, duration: Duration) {
+        let Some(job_id) = &metadata.job_id else { return };
+        let mut state = lock_or_reset(&self.state, |s| *s = MeterState::default());
+        let job = state.charge(job_id, metadata.tenant_id.as_deref(), stage);
+        job.stages.get_mut(&stage).unwrap().gpu_seconds += duration.as_secs_f64();
+        tracing::info!(
+            target: "sp1_prover::metering",
+            job_id = job_id.as_str(),
+            tenant_id = metadata.tenant_id.as_deref(),
+            stage = %stage,
+            gpu_seconds = duration.as_secs_f64(),
+            "gpu cost"
+        );
+    }
+
+    /// The resources used so far by the job `job_id`, if any of its stages was metered.
+    #[must_use]
+    pub fn job(&self, job_id: &str) -> Option<JobCost> {
+        lock_or_reset(&self.state, |s| *s = MeterState::default()).jobs.get(job_id).cloned()
+    }
+
+    /// Remove and return the resources used by the job `job_id`, e.g. once it is billed.
+    pub fn take_job(&self, job_id: &str) -> Option<JobCost> {
+        lock_or_reset(&self.state, |s| *s = MeterState::default()).jobs.remove(job_id)
+    }
+
+    fn finish(&self, id: u64) {
+        let mut state = lock_or_reset(&self.state, |s| *s = MeterState::default());
+        state.sample();
+        let Some(active) = state.active.remove(&id) else { return };
+        let job = state.charge(&active.job_id, active.tenant_id.as_deref(), active.stage);
+        job.stages.get_mut(&active.stage).unwrap().add(&active.usage);
+        tracing::info!(
+            target: "sp1_prover::metering",
+            job_id = active.job_id.as_str(),
+            tenant_id = active.tenant_id.as_deref(),
+            stage = %active.stage,
+            cpu_seconds = active.usage.cpu_seconds,
+            memory_byte_seconds = active.usage.memory_byte_seconds,
+            "stage cost"
+        );
+    }
+}
+
+/// A stage metered by a [`CostMeter`], until it is dropped.
+#[must_use]
+pub struct StageCost<'a> {
+    meter: &'a CostMeter,
+    id: Option<u64>,
+}
+
+impl Drop for StageCost<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.meter.finish(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_meter() {
+        let meter = CostMeter::new();
+        drop(meter.stage(&TraceMetadata::default(), CostStage::Execute));
+        meter.charge_gpu(&TraceMetadata::default(), CostStage::Core, Duration::from_secs(1));
+        assert!(meter.state.lock().unwrap().jobs.is_empty());
+
+        let metadata = TraceMetadata {
+            job_id: Some("job".to_string()),
+            tenant_id: Some("tenant".to_string()),
+        };
+        {
+            let _execute = meter.stage(&metadata, CostStage::Execute);
+            let _core = meter.stage(&metadata, CostStage::Core);
+            assert_eq!(meter.state.lock().unwrap().active.len(), 2);
+        }
+        assert!(meter.state.lock().unwrap().active.is_empty());
+        meter.charge_gpu(&metadata, CostStage::Core, Duration::from_secs(2));
+        meter.charge_gpu(&metadata, CostStage::Wrap, Duration::from_secs(3));
+
+        let cost = meter.job("job").unwrap();
+        assert_eq!(cost.tenant_id.as_deref(), Some("tenant"));
+        assert_eq!(
+            cost.stages.keys().copied().collect::<Vec<_>>(),
+            [CostStage::Execute, CostStage::Core, CostStage::Wrap]
+        );
+        assert_eq!(cost.stages[&CostStage::Core].gpu_seconds, 2.0);
+        assert_eq!(cost.total().gpu_seconds, 5.0);
+
+        assert_eq!(meter.take_job("job"), Some(cost));
+        assert!(meter.job("job").is_none());
+    }
+}
@@ -0,0 +1,400 @@
+//! Choosing the recursion tree arity and the FRI parameters of compress.
+//!
+//! The size of a compressed proof is dominated by its FRI openings, and the time to produce it by
+//! the number and the size of the join proofs. Both are controlled by the arity of the recursion
+//! tree and by the blowup and query count of the compress stage. The optimizer predicts the proof
+//! size and proving time of every combination from the proving plan of the program and a rough
+//! cost model, preferring measurements from a [`BenchmarkCache`] where available, and proves with
+//! the best combination for the requested [`OptimizationTarget`].
+//!
+//! The FRI parameters of a prover are fixed when it is created, so
+//! [`SP1Prover::prove_optimized`] only chooses among the configurations with the parameters of
+//! the prover. The configurations with other parameters are compared by [`SP1Prover::optimize`],
+//! to choose the parameters of a prover to create.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::SP1Context;
+use sp1_core_machine::{io::SP1Stdin, utils::SP1CoreProverError};
+use sp1_stark::{FriParams, SP1CoreOpts, SP1ProverOpts, MIN_SECURITY_BITS};
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents,
+    plan::{compress_tree_num_nodes, PlanCostModel, PlanStage, SP1PlanError},
+    tune::core_opts_candidates,
+    InnerSC, SP1Prover, SP1RecursionProverError, SP1ReduceProof, REDUCE_BATCH_SIZE,
+};
+
+/// The recursion tree arities considered by the optimizer.
+pub const COMPRESS_ARITIES: [usize; 3] = [2, 3, 4];
+
+/// The compress blowups considered by the optimizer, as log2 of the blowup factor.
+pub const COMPRESS_LOG_BLOWUPS: [usize; 3] = [1, 2, 3];
+
+/// The log2 height of the largest trace of a default join proof, used to scale proof sizes.
+const REFERENCE_LOG_HEIGHT: f64 = 20.0;
+
+/// The size of a default compressed proof, in bytes.
+const REFERENCE_PROOF_SIZE: u64 = 1_300_000;
+
+/// What the optimizer minimizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptimizationTarget {
+    /// The smallest compressed proof.
+    ProofSize,
+    /// The fastest proving, up to and including compress.
+    ProvingTime,
+}
+
+/// The configuration of the compress stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CompressConfig {
+    /// The number of proofs reduced by each join program.
+    pub arity: usize,
+    /// The FRI parameters of the compress stage.
+    pub fri: FriParams,
+}
+
+/// The measured cost of a compress configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressBenchmark {
+    /// The average time to prove a single node of the recursion tree, in milliseconds.
+    pub node_ms: u64,
+    /// The size of the compressed proof, in bytes.
+    pub proof_size_bytes: u64,
+}
+
+/// Measured costs of compress configurations, persisted across runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BenchmarkCache {
+    entries: Vec<(CompressConfig, CompressBenchmark)>,
+}
+
+impl BenchmarkCache {
+    /// The measured cost of `config`, if any.
+    #[must_use]
+    pub fn get(&self, config: &CompressConfig) -> Option<CompressBenchmark> {
+        self.entries.iter().find(|(c, _)| c == config).map(|(_, benchmark)| *benchmark)
+    }
+
+    /// Record the measured cost of `config`, replacing any previous measurement.
+    pub fn record(&mut self, config: CompressConfig, benchmark: CompressBenchmark) {
+        match self.entries.iter_mut().find(|(c, _)| *c == config) {
+            Some((_, entry)) => *entry = benchmark,
+            None => self.entries.push((config, benchmark)),
+        }
+    }
+
+    /// Load a cache written by [`BenchmarkCache::save`], or an empty cache if `path` does not
+    /// exist.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        Ok(serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?)
+    }
+}
+
+/// A candidate configuration and its predicted cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptimizerCandidate {
+    /// The core prover options.
+    pub core_opts: SP1CoreOpts,
+    /// The compress configuration.
+    pub compress: CompressConfig,
+    /// The estimated number of core shards.
+    pub num_shards: usize,
+    /// The number of proofs generated by compress.
+    pub num_compress_nodes: usize,
+    /// The predicted size of the compressed proof, in bytes.
+    pub proof_size_bytes: u64,
+    /// The predicted time to prove the core shards and compress them, in milliseconds.
+    pub proving_time_ms: u64,
+    /// Whether the compress cost comes from a [`BenchmarkCache`] rather than the cost model.
+    pub measured: bool,
+}
+
+/// The result of searching the configuration space for a program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptimizerSelection {
+    /// The target the search optimized for.
+    pub target: OptimizationTarget,
+    /// The evaluated candidates.
+    pub candidates: Vec<OptimizerCandidate>,
+    /// The index of the chosen candidate.
+    pub chosen: usize,
+}
+
+impl OptimizerSelection {
+    /// The chosen candidate.
+    #[must_use]
+    pub fn chosen(&self) -> &OptimizerCandidate {
+        &self.candidates[self.chosen]
+    }
+
+    /// The prover options of the chosen candidate, with everything else taken from `opts`.
+    #[must_use]
+    pub fn prover_opts(&self, mut opts: SP1ProverOpts) -> SP1ProverOpts {
+        let chosen = self.chosen();
+        opts.core_opts = chosen.core_opts;
+        opts.compress_opts.arity = chosen.compress.arity;
+        opts.fri_opts.compress = chosen.compress.fri;
+        opts
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SP1OptimizeError {
+    #[error(transparent)]
+    Plan(#[from] SP1PlanError),
+    #[error(transparent)]
+    Core(#[from] SP1CoreProverError),
+    #[error(transparent)]
+    Recursion(#[from] SP1RecursionProverError),
+}
+
+/// Predict the cost of `config` relative to the default compress configuration `reference`.
+///
+/// Join programs are dominated by verifying their inputs, so their traces grow with the arity and
+/// the query count of the inputs, and every trace is committed at the given blowup. Every query
+/// opens a Merkle path whose length grows with the trace height and the blowup.
+fn predicted_compress_cost(
+    config: &CompressConfig,
+    reference: &FriParams,
+    reference_node: Duration,
+) -> CompressBenchmark {
+    let trace_factor = (config.arity * config.fri.num_queries) as f64 /
+        (REDUCE_BATCH_SIZE * reference.num_queries) as f64;
+    let blowup_factor = 2f64.powi(config.fri.log_blowup as i32 - reference.log_blowup as i32);
+    let node_ms = reference_node.as_millis() as f64 * trace_factor * blowup_factor;
+
+    let path_length = REFERENCE_LOG_HEIGHT + trace_factor.log2() + config.fri.log_blowup as f64;
+    let reference_path_length = REFERENCE_LOG_HEIGHT + reference.log_blowup as f64;
+    let proof_size_bytes = REFERENCE_PROOF_SIZE as f64 *
+        (config.fri.num_queries as f64 / reference.num_queries as f64) *
+        (path_length / reference_path_length);
+
+    CompressBenchmark { node_ms: node_ms as u64, proof_size_bytes: proof_size_bytes as u64 }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The compress configurations this prover can use.
+    ///
+    /// The query count of each blowup is the smallest one that keeps at least the conjectured
+    /// security of `reference`. Provers with vk verification or fixed recursion shapes are limited
+    /// to the default FRI parameters and to the arities of their precompiled join programs.
+    #[must_use]
+    pub fn compress_config_candidates(&self, reference: FriParams) -> Vec<CompressConfig> {
+        if self.has_fixed_compress_configs() {
+            return self.compress_arity_candidates(reference);
+        }
+
+        let security_bits = reference.conjectured_security_bits().max(MIN_SECURITY_BITS);
+        let mut candidates = Vec::new();
+        for log_blowup in COMPRESS_LOG_BLOWUPS {
+            let num_queries =
+                security_bits.saturating_sub(reference.proof_of_work_bits).div_ceil(log_blowup);
+            let fri = FriParams { log_blowup, num_queries, ..reference };
+            candidates.extend(self.compress_arity_candidates(fri));
+        }
+        candidates
+    }
+
+    /// The compress configurations with the FRI parameters `fri`, one for each arity this prover
+    /// can use.
+    #[must_use]
+    pub fn compress_arity_candidates(&self, fri: FriParams) -> Vec<CompressConfig> {
+        let arities = if self.has_fixed_compress_configs() {
+            (REDUCE_BATCH_SIZE..=self.max_compress_arity).collect::<Vec<_>>()
+        } else {
+            COMPRESS_ARITIES.to_vec()
+        };
+        arities.into_iter().map(|arity| CompressConfig { arity, fri }).collect()
+    }
+
+    /// Whether the compress configurations are limited to the precompiled join programs.
+    fn has_fixed_compress_configs(&self) -> bool {
+        self.vk_verification.checks_vks() || self.compress_shape_config.is_some()
+    }
+
+    /// Search the core options and compress configurations for the best one for `target`.
+    ///
+    /// The program is executed once per core options candidate to plan its shards. The cost of
+    /// each compress configuration is taken from `cache` if it has been measured, and predicted
+    /// from `costs` otherwise.
+    #[tracing::instrument(name = "optimize", level = "info", skip_all)]
+    pub fn optimize(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        target: OptimizationTarget,
+        costs: &PlanCostModel,
+        cache: &BenchmarkCache,
+    ) -> Result<OptimizerSelection, SP1PlanError> {
+        let reference = self.fri_opts().compress;
+        let compress_configs = self.compress_config_candidates(reference);
+        self.optimize_among(elf, stdin, opts, target, costs, cache, &compress_configs)
+    }
+
+    /// Search the core options and `compress_configs` for the best one for `target`, see
+    /// [`Self::optimize`].
+    #[allow(clippy::too_many_arguments)]
+    fn optimize_among(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        target: OptimizationTarget,
+        costs: &PlanCostModel,
+        cache: &BenchmarkCache,
+        compress_configs: &[CompressConfig],
+    ) -> Result<OptimizerSelection, SP1PlanError> {
+        let reference = self.fri_opts().compress;
+        let mut candidates = Vec::new();
+        for core_opts in core_opts_candidates(opts.core_opts) {
+            let plan = self.plan_with_cost_model(
+                elf,
+                stdin,
+                SP1ProverOpts { core_opts, ..opts.clone() },
+                costs,
+            )?;
+            let core_ms = plan
+                .stages
+                .iter()
+                .find(|stage| stage.stage == PlanStage::Core)
+                .map_or(0, |stage| stage.estimated_duration_ms);
+
+            for compress in compress_configs.iter().copied() {
+                let num_compress_nodes =
+                    compress_tree_num_nodes(plan.recursion.num_first_layer_inputs, compress.arity);
+                let (benchmark, measured) = match cache.get(&compress) {
+                    Some(benchmark) => (benchmark, true),
+                    None => {
+                        (predicted_compress_cost(&compress, &reference, costs.compress_node), false)
+                    }
+                };
+                candidates.push(OptimizerCandidate {
+                    core_opts,
+                    compress,
+                    num_shards: plan.num_shards,
+                    num_compress_nodes,
+                    proof_size_bytes: benchmark.proof_size_bytes,
+                    proving_time_ms: core_ms + benchmark.node_ms * num_compress_nodes as u64,
+                    measured,
+                });
+            }
+        }
+
+        let chosen = candidates
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| match target {
+                OptimizationTarget::ProofSize => (c.proof_size_bytes, c.proving_time_ms),
+                OptimizationTarget::ProvingTime => (c.proving_time_ms, c.proof_size_bytes),
+            })
+            .map_or(0, |(i, _)| i);
+        let selection = OptimizerSelection { target, candidates, chosen };
+
+        let chosen = selection.chosen();
+        tracing::info!(
+            "chose arity {}, log blowup {}, {} queries and shard size {}: ~{} bytes, ~{:?}",
+            chosen.compress.arity,
+            chosen.compress.fri.log_blowup,
+            chosen.compress.fri.num_queries,
+            chosen.core_opts.shard_size,
+            chosen.proof_size_bytes,
+            Duration::from_millis(chosen.proving_time_ms)
+        );
+        Ok(selection)
+    }
+
+    /// Generate a compressed proof with the best configuration for `target` among those with the
+    /// compress FRI parameters of this prover.
+    ///
+    /// The measured cost of the chosen compress configuration is recorded in `cache`.
+    pub fn prove_optimized(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        target: OptimizationTarget,
+        cache: &mut BenchmarkCache,
+    ) -> Result<(SP1ReduceProof<InnerSC>, OptimizerSelection), SP1OptimizeError> {
+        let compress_configs = self.compress_arity_candidates(self.fri_opts().compress);
+        let selection = self.optimize_among(
+            elf,
+            stdin,
+            opts.clone(),
+            target,
+            &PlanCostModel::default(),
+            cache,
+            &compress_configs,
+        )?;
+        let chosen = *selection.chosen();
+        let opts = selection.prover_opts(opts);
+
+        let (_, pk_d, program, vk) = self.setup(elf);
+        let core_proof =
+            self.prove_core(&pk_d, program, stdin, opts.clone(), SP1Context::default())?;
+        let deferred_proofs =
+            stdin.proofs.iter().map(|(reduce_proof, _)| reduce_proof.clone()).collect();
+
+        let start = Instant::now();
+        let num_nodes = chosen.num_compress_nodes.max(1) as u128;
+        let proof = self.compress(&vk, core_proof, deferred_proofs, opts)?;
+        let node_ms = (start.elapsed().as_millis() / num_nodes) as u64;
+        match bincode::serialized_size(&proof) {
+            Ok(proof_size_bytes) => {
+                cache.record(chosen.compress, CompressBenchmark { node_ms, proof_size_bytes })
+            }
+            Err(e) => tracing::warn!("failed to measure the compressed proof size: {}", e),
+        }
+
+        Ok((proof, selection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::CpuProverComponents,
+        reload::{ProverConfigBundle, VkVerificationMode},
+    };
+
+    #[test]
+    fn test_compress_config_candidates() {
+        let prover = SP1Prover::<CpuProverComponents>::with_config(ProverConfigBundle {
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env_with_vk_verification(VkVerificationMode::Disabled)
+        });
+        let reference = prover.fri_opts().compress;
+
+        let candidates = prover.compress_config_candidates(reference);
+        assert_eq!(candidates.len(), COMPRESS_ARITIES.len() * COMPRESS_LOG_BLOWUPS.len());
+        for candidate in &candidates {
+            assert!(candidate.fri.conjectured_security_bits() >= MIN_SECURITY_BITS);
+        }
+
+        // Proving only considers the FRI parameters of the prover.
+        let arity_candidates = prover.compress_arity_candidates(reference);
+        assert_eq!(
+            arity_candidates.iter().map(|config| config.arity).collect::<Vec<_>>(),
+            COMPRESS_ARITIES
+        );
+        assert!(arity_candidates.iter().all(|config| config.fri == reference));
+    }
+}
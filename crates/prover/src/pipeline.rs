@@ -0,0 +1,286 @@
+//! Proving pipelines of programs that consume each other's outputs.
+//!
+//! A common pattern is a program B that verifies a compressed proof of a program A and uses its
+//! public values. A [`PipelineManifest`] declares such programs and which ones each consumes, and
+//! [`SP1Prover::prove_pipeline`] proves them in order, piping the outputs of every stage into the
+//! stdin of the stages consuming it and deferring their proofs. The compressed proof of the last
+//! stage then attests to the whole pipeline.
+//!
+//! For every input, in the order they are listed, a stage reads from its stdin, after its own
+//! buffers:
+//!
+//! 1. the `[u32; 8]` verifying key digest of the input program,
+//! 2. the public values of the input program, as a `Vec<u8>`,
+//!
+//! and should call `sp1_zkvm::lib::verify::verify_sp1_proof` with the digest and the SHA-256 hash
+//! of the public values. A stage should also check the verifying key digests against the programs
+//! it expects, as the prover accepts any program in their place.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::SP1Context;
+use sp1_core_machine::{io::SP1Stdin, utils::SP1CoreProverError};
+use sp1_primitives::io::SP1PublicValues;
+use sp1_stark::{MachineVerificationError, SP1ProverOpts};
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents, CoreSC, HashableKey, InnerSC, SP1Prover,
+    SP1RecursionProverError, SP1ReduceProof, SP1VerifyingKey,
+};
+
+/// A program of a pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStage {
+    /// The name other stages refer to the stage by.
+    pub name: String,
+    /// The path to the ELF of the program.
+    pub elf: PathBuf,
+    /// The path to a bincode serialized [`SP1Stdin`] with the inputs of the program that do not
+    /// come from other stages.
+    #[serde(default)]
+    pub stdin: Option<PathBuf>,
+    /// The names of the stages whose outputs the program consumes.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+}
+
+/// The programs of a pipeline and how their outputs are piped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineManifest {
+    pub stages: Vec<PipelineStage>,
+}
+
+/// The outputs of a stage of a pipeline.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PipelineStageProof {
+    pub name: String,
+    pub vk: SP1VerifyingKey,
+    pub public_values: SP1PublicValues,
+    pub cycles: u64,
+    /// The compressed proof of the program, with the proofs of its inputs deferred.
+    pub proof: SP1ReduceProof<InnerSC>,
+}
+
+/// The proofs of the stages of a pipeline, in the order they were proven.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PipelineProof {
+    pub stages: Vec<PipelineStageProof>,
+}
+
+impl PipelineProof {
+    /// The proof of the last stage, which attests to the whole pipeline.
+    #[must_use]
+    pub fn output(&self) -> &PipelineStageProof {
+        self.stages.last().expect("a pipeline has at least one stage")
+    }
+
+    /// The proof of the stage named `name`.
+    #[must_use]
+    pub fn stage(&self, name: &str) -> Option<&PipelineStageProof> {
+        self.stages.iter().find(|stage| stage.name == name)
+    }
+}
+
+/// An error that occurs while proving a pipeline.
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    #[error("failed to read the manifest: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to parse the manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("failed to read the stdin of stage {stage}: {source}")]
+    Stdin { stage: String, source: bincode::Error },
+    #[error("the pipeline has no stages")]
+    Empty,
+    #[error("stage {0} is declared more than once")]
+    DuplicateStage(String),
+    #[error("stage {stage} consumes unknown stage {input}")]
+    UnknownInput { stage: String, input: String },
+    #[error("the inputs of stage {0} form a cycle")]
+    Cycle(String),
+    #[error("the output of stage {0} is not consumed, so the pipeline has several outputs")]
+    UnusedStage(String),
+    #[error("failed to set up stage {stage}: {error}")]
+    Setup { stage: String, error: eyre::Report },
+    #[error("failed to prove stage {stage}: {source}")]
+    Core { stage: String, source: SP1CoreProverError },
+    #[error("failed to compress stage {stage}: {source}")]
+    Recursion { stage: String, source: SP1RecursionProverError },
+}
+
+impl PipelineManifest {
+    /// Read a JSON manifest. Relative paths are resolved against the directory of the manifest.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PipelineError> {
+        let path = path.as_ref();
+        let mut manifest: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for stage in &mut manifest.stages {
+            stage.elf = dir.join(&stage.elf);
+            if let Some(stdin) = &mut stage.stdin {
+                *stdin = dir.join(&*stdin);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// The indices of the stages in an order in which every stage comes after its inputs, keeping
+    /// the declaration order where possible.
+    ///
+    /// Fails unless the stages form an acyclic graph whose only output is the last stage proven.
+    pub fn order(&self) -> Result<Vec<usize>, PipelineError> {
+        let mut indices = BTreeMap::new();
+        for (i, stage) in self.stages.iter().enumerate() {
+            if indices.insert(stage.name.as_str(), i).is_some() {
+                return Err(PipelineError::DuplicateStage(stage.name.clone()));
+            }
+        }
+        let mut consumed = BTreeSet::new();
+        for stage in &self.stages {
+            for input in &stage.inputs {
+                let i = *indices.get(input.as_str()).ok_or_else(|| {
+                    PipelineError::UnknownInput { stage: stage.name.clone(), input: input.clone() }
+                })?;
+                consumed.insert(i);
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.stages.len());
+        let mut done = vec![false; self.stages.len()];
+        while order.len() < self.stages.len() {
+            let next = (0..self.stages.len()).find(|&i| {
+                !done[i] && self.stages[i].inputs.iter().all(|input| done[indices[input.as_str()]])
+            });
+            let Some(next) = next else {
+                let stuck = done.iter().position(|done| !done).unwrap();
+                return Err(PipelineError::Cycle(self.stages[stuck].name.clone()));
+            };
+            done[next] = true;
+            order.push(next);
+        }
+
+        let Some(&last) = order.last() else {
+            return Err(PipelineError::Empty);
+        };
+        if let Some(unused) = order.iter().find(|&&i| i != last && !consumed.contains(&i)) {
+            return Err(PipelineError::UnusedStage(self.stages[*unused].name.clone()));
+        }
+        Ok(order)
+    }
+}
+
+impl<C: SP1ProverComponents + 'static> SP1Prover<C> {
+    /// Prove every stage of a pipeline and compress it with the proofs of its inputs deferred, see
+    /// the [module documentation](self).
+    pub fn prove_pipeline(
+        &self,
+        manifest: &PipelineManifest,
+        opts: SP1ProverOpts,
+    ) -> Result<PipelineProof, PipelineError> {
+        let order = manifest.order()?;
+        let mut proofs = BTreeMap::<&str, PipelineStageProof>::new();
+        for i in order.iter().copied() {
+            let stage = &manifest.stages[i];
+            let name = stage.name.clone();
+            tracing::info!("proving pipeline stage {name}");
+
+            let mut stdin = match &stage.stdin {
+                Some(path) => bincode::deserialize_from(BufReader::new(File::open(path)?))
+                    .map_err(|source| PipelineError::Stdin { stage: name.clone(), source })?,
+                None => SP1Stdin::new(),
+            };
+            let mut deferred_proofs = Vec::with_capacity(stage.inputs.len());
+            for input in &stage.inputs {
+                let input = &proofs[input.as_str()];
+                stdin.write(&input.vk.hash_u32());
+                stdin.write_vec(input.public_values.to_vec());
+                stdin.write_proof(input.proof.clone(), input.vk.vk.clone());
+                deferred_proofs.push(input.proof.clone());
+            }
+
+            let elf = fs::read(&stage.elf)?;
+            let (_, pk_d, program, vk) = self
+                .setup_nonblocking(&elf)
+                .map_err(|error| PipelineError::Setup { stage: name.clone(), error })?;
+            let core_proof = self
+                .prove_core_with_pending_key(
+                    &pk_d,
+                    program,
+                    &stdin,
+                    opts.clone(),
+                    SP1Context::default(),
+                )
+                .map_err(|source| PipelineError::Core { stage: name.clone(), source })?;
+            let public_values = core_proof.public_values.clone();
+            let cycles = core_proof.cycles;
+            let proof = self
+                .compress(&vk, core_proof, deferred_proofs, opts.clone())
+                .map_err(|source| PipelineError::Recursion { stage: name.clone(), source })?;
+
+            proofs.insert(
+                stage.name.as_str(),
+                PipelineStageProof { name, vk, public_values, cycles, proof },
+            );
+        }
+
+        let stages =
+            order.iter().map(|&i| proofs.remove(manifest.stages[i].name.as_str()).unwrap());
+        Ok(PipelineProof { stages: stages.collect() })
+    }
+
+    /// Verify the output of a pipeline against the verifying key of its last program.
+    ///
+    /// The proofs of the other stages are deferred into the output, so they are covered as long as
+    /// the last program checks the verifying key digests of its inputs.
+    pub fn verify_pipeline(
+        &self,
+        proof: &PipelineProof,
+        vk: &SP1VerifyingKey,
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        self.verify_compressed(&proof.output().proof, vk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(stages: &[(&str, &[&str])]) -> PipelineManifest {
+        let stages = stages
+            .iter()
+            .map(|(name, inputs)| PipelineStage {
+                name: name.to_string(),
+                elf: PathBuf::from(format!("{name}.elf")),
+                stdin: None,
+                inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            })
+            .collect();
+        PipelineManifest { stages }
+    }
+
+    #[test]
+    fn test_pipeline_order() {
+        let order = manifest(&[("c", &["a", "b"]), ("b", &["a"]), ("a", &[])]).order().unwrap();
+        assert_eq!(order, vec![2, 1, 0]);
+
+        assert!(matches!(manifest(&[]).order(), Err(PipelineError::Empty)));
+        assert!(matches!(
+            manifest(&[("a", &["b"]), ("b", &["a"])]).order(),
+            Err(PipelineError::Cycle(_))
+        ));
+        assert!(matches!(
+            manifest(&[("a", &[]), ("b", &[])]).order(),
+            Err(PipelineError::UnusedStage(name)) if name == "a"
+        ));
+        assert!(matches!(
+            manifest(&[("a", &["x"])]).order(),
+            Err(PipelineError::UnknownInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,564 @@
+//! Machine-readable proving plans.
+//!
+//! A plan describes the work the prover intends to do for a given program and input: the number of
+//! core shards and their shapes, the shape of the recursion tree, and the stages that follow it.
+//! Plans are computed by executing the program with the record estimator, so no proofs are
+//! generated.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::{ExecutionError, Program, RiscvAirId, SP1Context};
+use sp1_core_machine::{io::SP1Stdin, reduce::SP1ReduceProof};
+use sp1_stark::{
+    shape::{OrderedShape, Shape},
+    SP1ProverOpts,
+};
+use thiserror::Error;
+
+use crate::{
+    bench::{BenchStage, SuiteResults},
+    components::SP1ProverComponents,
+    gas, InnerSC, SP1Prover, StrictModeError, REDUCE_BATCH_SIZE,
+};
+
+/// The version of the [`SP1ProvingPlan`] format.
+///
+/// This should be bumped whenever a field is added, removed or changes meaning.
+pub const PROVING_PLAN_VERSION: u32 = 1;
+
+/// An error computing a proving plan.
+#[derive(Debug, Error)]
+pub enum SP1PlanError {
+    #[error("failed to load the program: {0}")]
+    Program(eyre::Report),
+    #[error(transparent)]
+    Execution(#[from] ExecutionError),
+    #[error("too many proofs to estimate their duration: {0}")]
+    TooManyProofs(usize),
+    #[error(transparent)]
+    Strict(#[from] StrictModeError),
+}
+
+/// A stage of the proving pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum PlanStage {
+    /// Proving the core shards.
+    Core,
+    /// Recursively reducing the shard proofs into a single proof.
+    Compress,
+    /// Shrinking the compressed proof.
+    Shrink,
+    /// Wrapping the shrunk proof into a SNARK-friendly field.
+    WrapBn254,
+    /// Wrapping the bn254 proof into a Plonk or Groth16 proof.
+    Snark,
+}
+
+/// The work scheduled for a single stage of the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagePlan {
+    /// The stage.
+    pub stage: PlanStage,
+    /// The number of proofs generated in this stage.
+    pub num_proofs: usize,
+    /// The estimated wall-clock duration of this stage, in milliseconds.
+    pub estimated_duration_ms: u64,
+}
+
+/// The shape of the recursion tree built by `compress`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecursionTreePlan {
+    /// The number of deferred proofs verified in the first layer.
+    pub num_deferred_proofs: usize,
+    /// The number of batches the deferred proofs are split into.
+    pub num_deferred_batches: usize,
+    /// The number of inputs to the first layer of recursion.
+    pub num_first_layer_inputs: usize,
+    /// The number of inputs reduced by each join program.
+    pub batch_size: usize,
+    /// The height of the tree, not counting the first layer.
+    pub height: usize,
+    /// The total number of recursion proofs generated, including the first layer.
+    pub num_nodes: usize,
+}
+
+/// A machine-readable description of the full proving pipeline for a program and input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SP1ProvingPlan {
+    /// The version of the plan format, see [`PROVING_PLAN_VERSION`].
+    pub version: u32,
+    /// The number of cycles executed by the program.
+    pub cycles: u64,
+    /// The estimated number of core shards.
+    pub num_shards: usize,
+    /// The estimated shape of each core shard, as a map from AIR name to log2 height.
+    ///
+    /// Empty if the prover does not fix core shapes.
+    pub shard_shapes: Vec<BTreeMap<String, usize>>,
+    /// The recursion tree.
+    pub recursion: RecursionTreePlan,
+    /// The stages of the pipeline, in the order they are executed.
+    pub stages: Vec<StagePlan>,
+}
+
+impl SP1ProvingPlan {
+    /// The total estimated duration of the pipeline.
+    #[must_use]
+    pub fn estimated_duration(&self) -> Duration {
+        Duration::from_millis(self.stages.iter().map(|s| s.estimated_duration_ms).sum())
+    }
+
+    /// Serialize the plan to a pretty-printed JSON string.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for SP1ProvingPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "cycles: {}", self.cycles)?;
+        writeln!(f, "shards: {}", self.num_shards)?;
+        writeln!(
+            f,
+            "recursion tree: {} first layer inputs ({} deferred), height {}, {} nodes",
+            self.recursion.num_first_layer_inputs,
+            self.recursion.num_deferred_proofs,
+            self.recursion.height,
+            self.recursion.num_nodes
+        )?;
+        for stage in self.stages.iter() {
+            writeln!(
+                f,
+                "{:?}: {} proofs, ~{:?}",
+                stage.stage,
+                stage.num_proofs,
+                Duration::from_millis(stage.estimated_duration_ms)
+            )?;
+        }
+        write!(f, "total: ~{:?}", self.estimated_duration())
+    }
+}
+
+/// A rough model of how long each unit of work takes, used to estimate stage durations.
+///
+/// The defaults are ballpark figures for a commodity CPU machine. Callers with measurements from
+/// their own hardware should supply their own model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanCostModel {
+    /// The time to prove a single core shard.
+    pub core_shard: Duration,
+    /// The time to prove a single node of the recursion tree.
+    pub compress_node: Duration,
+    /// The time to shrink the compressed proof.
+    pub shrink: Duration,
+    /// The time to wrap the shrunk proof into bn254.
+    pub wrap_bn254: Duration,
+    /// The time to generate the final SNARK.
+    pub snark: Duration,
+}
+
+impl Default for PlanCostModel {
+    fn default() -> Self {
+        Self {
+            core_shard: Duration::from_secs(20),
+            compress_node: Duration::from_secs(6),
+            shrink: Duration::from_secs(10),
+            wrap_bn254: Duration::from_secs(30),
+            snark: Duration::from_secs(60),
+        }
+    }
+}
+
+impl PlanCostModel {
+    /// Derive the model from the results of a benchmark run, see [`crate::bench::run_suite`].
+    ///
+    /// Each duration is averaged over the programs that ran the corresponding stage. Durations
+    /// that the run did not measure are taken from the default model.
+    #[must_use]
+    pub fn from_bench(results: &SuiteResults) -> Self {
+        let arity = match results.prover_opts.compress_opts.arity {
+            arity if arity >= 2 => arity,
+            _ => REDUCE_BATCH_SIZE,
+        };
+        let average = |stage: BenchStage, units: &dyn Fn(usize) -> usize| {
+            let (seconds, units) = results
+                .programs
+                .iter()
+                .flat_map(|program| {
+                    program
+                        .stages
+                        .iter()
+                        .filter(move |result| result.stage == stage)
+                        .map(|result| (result.seconds, units(program.shards)))
+                })
+                .fold((0.0, 0), |(seconds, units), (s, u)| (seconds + s, units + u));
+            (units > 0).then(|| Duration::from_secs_f64(seconds / units as f64))
+        };
+
+        let default = Self::default();
+        Self {
+            core_shard: average(BenchStage::Core, &|shards| shards).unwrap_or(default.core_shard),
+            compress_node: average(BenchStage::Compress, &|shards| {
+                compress_tree_num_nodes(shards, arity)
+            })
+            .unwrap_or(default.compress_node),
+            shrink: average(BenchStage::Shrink, &|_| 1).unwrap_or(default.shrink),
+            wrap_bn254: average(BenchStage::Wrap, &|_| 1).unwrap_or(default.wrap_bn254),
+            snark: average(BenchStage::Plonk, &|_| 1)
+                .or_else(|| average(BenchStage::Groth16, &|_| 1))
+                .unwrap_or(default.snark),
+        }
+    }
+}
+
+/// The estimated cost of verifying a set of deferred proofs in `compress`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeferredCostEstimate {
+    /// The number of deferred proofs.
+    pub num_deferred_proofs: usize,
+    /// The number of batches the deferred proofs are split into, each verified by one deferred
+    /// program in the first layer.
+    pub num_batches: usize,
+    /// The size of the serialized deferred proofs written to the witness, in bytes.
+    pub witness_bytes: u64,
+    /// The shapes of the proofs verified by each batch, which determine its deferred program.
+    pub batch_shapes: Vec<Vec<OrderedShape>>,
+    /// The number of distinct deferred programs, which may need to be compiled.
+    pub num_programs: usize,
+    /// The number of recursion proofs added to the tree.
+    pub extra_nodes: usize,
+    /// The number of layers added to the tree.
+    pub extra_height: usize,
+    /// The estimated time added to the compress stage, in milliseconds.
+    pub estimated_duration_ms: u64,
+}
+
+/// Compute the number of inputs of each layer of the recursion tree built over
+/// `num_first_layer_inputs` inputs, from the first layer to the root.
+#[must_use]
+pub fn compress_tree_layer_sizes(num_first_layer_inputs: usize, batch_size: usize) -> Vec<usize> {
+    let mut sizes = vec![num_first_layer_inputs];
+    let mut num_layer_inputs = num_first_layer_inputs;
+    while num_layer_inputs > 1 {
+        num_layer_inputs = num_layer_inputs.div_ceil(batch_size);
+        sizes.push(num_layer_inputs);
+    }
+    sizes
+}
+
+/// Compute the height of the recursion tree built over `num_first_layer_inputs` inputs, not
+/// counting the first layer.
+#[must_use]
+pub fn compress_tree_height(num_first_layer_inputs: usize, batch_size: usize) -> usize {
+    compress_tree_layer_sizes(num_first_layer_inputs, batch_size).len() - 1
+}
+
+/// Compute the total number of proofs generated by `compress` over `num_first_layer_inputs`
+/// inputs, including the first layer.
+///
+/// A lone input at the end of a layer is passed through to the next layer without being proven,
+/// so it is not counted.
+#[must_use]
+pub fn compress_tree_num_nodes(num_first_layer_inputs: usize, batch_size: usize) -> usize {
+    let sizes = compress_tree_layer_sizes(num_first_layer_inputs, batch_size);
+    let joins = sizes[..sizes.len() - 1]
+        .iter()
+        .map(|size| size / batch_size + usize::from(size % batch_size >= 2))
+        .sum::<usize>();
+    num_first_layer_inputs + joins
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Compute the proving plan for a program and input without generating any proofs.
+    ///
+    /// The program is executed once with the record estimator enabled to find the shard boundaries
+    /// and their shapes. Stage durations are estimated with the default [`PlanCostModel`].
+    #[tracing::instrument(name = "plan", level = "info", skip_all)]
+    pub fn plan(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ProvingPlan, SP1PlanError> {
+        self.plan_with_cost_model(elf, stdin, opts, &PlanCostModel::default())
+    }
+
+    /// Compute the proving plan for a program and input, estimating durations with `costs`.
+    pub fn plan_with_cost_model(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        costs: &PlanCostModel,
+    ) -> Result<SP1ProvingPlan, SP1PlanError> {
+        let context = SP1Context { subproof_verifier: Some(self), ..Default::default() };
+
+        let program = match self.core_shape_config {
+            Some(_) => self.get_program(elf),
+            None => Program::from(elf),
+        }
+        .map_err(SP1PlanError::Program)?;
+        let preprocessed_shape = program.preprocessed_shape.clone();
+
+        let mut runtime = self.estimating_executor(program, stdin, opts.core_opts, context);
+        runtime.run_fast()?;
+
+        let estimator = runtime.record_estimator.as_ref().unwrap();
+        let records = gas::estimated_records(&opts.core_opts.split_opts, estimator);
+        let (num_shards, shard_shapes) = match &self.core_shape_config {
+            Some(config) => {
+                let shapes = gas::fit_records_to_shapes(config, records)
+                    .filter_map(|shape| {
+                        shape
+                            .inspect_err(|e| tracing::warn!("failed to fit shard to shape: {}", e))
+                            .ok()
+                    })
+                    .map(|mut shape: Shape<RiscvAirId>| {
+                        if let Some(preprocessed_shape) = &preprocessed_shape {
+                            shape.extend(preprocessed_shape.iter().map(|(k, v)| (*k, *v)));
+                        }
+                        shape.inner.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+                    })
+                    .collect::<Vec<_>>();
+                (shapes.len(), shapes)
+            }
+            None => (records.count(), Vec::new()),
+        };
+
+        let deferred_proofs =
+            stdin.proofs.iter().map(|(proof, _)| proof.clone()).collect::<Vec<_>>();
+        let recursion = self.recursion_tree_plan(num_shards, &deferred_proofs, &opts)?;
+
+        let stage = |stage, num_proofs: usize, unit: Duration| -> Result<_, SP1PlanError> {
+            Ok(StagePlan {
+                stage,
+                num_proofs,
+                estimated_duration_ms: estimated_duration_ms(unit, num_proofs)?,
+            })
+        };
+        let stages = vec![
+            stage(PlanStage::Core, num_shards, costs.core_shard)?,
+            stage(PlanStage::Compress, recursion.num_nodes, costs.compress_node)?,
+            stage(PlanStage::Shrink, 1, costs.shrink)?,
+            stage(PlanStage::WrapBn254, 1, costs.wrap_bn254)?,
+            stage(PlanStage::Snark, 1, costs.snark)?,
+        ];
+
+        let plan = SP1ProvingPlan {
+            version: PROVING_PLAN_VERSION,
+            cycles: runtime.state.global_clk,
+            num_shards,
+            shard_shapes,
+            recursion,
+            stages,
+        };
+        tracing::info!("proving plan:\n{}", plan);
+        Ok(plan)
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The recursion tree reducing `num_shards` shard proofs and `deferred_proofs`.
+    ///
+    /// The first layer proves each batch of deferred proofs and each batch of shards, batched as
+    /// by [`Self::get_first_layer_inputs_with_deferred_opts`].
+    pub fn recursion_tree_plan(
+        &self,
+        num_shards: usize,
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        opts: &SP1ProverOpts,
+    ) -> Result<RecursionTreePlan, SP1PlanError> {
+        let num_deferred_batches =
+            Self::deferred_batches(deferred_proofs, &opts.deferred_opts).len();
+        let num_first_layer_inputs =
+            num_deferred_batches + num_shards.div_ceil(self.try_first_layer_batch_size(opts)?);
+        let batch_size = self.try_compress_arity(opts)?;
+        let tree = self.reduction_strategy.plan(num_first_layer_inputs, batch_size);
+        Ok(RecursionTreePlan {
+            num_deferred_proofs: deferred_proofs.len(),
+            num_deferred_batches,
+            num_first_layer_inputs,
+            batch_size,
+            height: tree.height(),
+            num_nodes: tree.num_nodes(),
+        })
+    }
+
+    /// Estimate the cost of verifying `deferred_proofs` when compressing a proof of `num_shards`
+    /// shards, such as the one computed by [`SP1Prover::plan`].
+    pub fn estimate_deferred_cost(
+        &self,
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        num_shards: usize,
+        opts: SP1ProverOpts,
+    ) -> Result<DeferredCostEstimate, SP1PlanError> {
+        self.estimate_deferred_cost_with_cost_model(
+            deferred_proofs,
+            num_shards,
+            opts,
+            &PlanCostModel::default(),
+        )
+    }
+
+    /// Estimate the cost of verifying deferred proofs, with durations taken from `costs`, such as
+    /// a model derived from cached benchmarks with [`PlanCostModel::from_bench`].
+    pub fn estimate_deferred_cost_with_cost_model(
+        &self,
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        num_shards: usize,
+        opts: SP1ProverOpts,
+        costs: &PlanCostModel,
+    ) -> Result<DeferredCostEstimate, SP1PlanError> {
+        let batches = Self::deferred_batches(deferred_proofs, &opts.deferred_opts);
+        let batch_shapes = batches
+            .iter()
+            .map(|batch| batch.iter().map(|proof| proof.proof.shape()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let num_programs = batch_shapes.iter().collect::<BTreeSet<_>>().len();
+        let witness_bytes = deferred_proofs
+            .iter()
+            .map(|proof| bincode::serialized_size(proof).unwrap_or_default())
+            .sum();
+
+        let batch_size = self.try_compress_arity(&opts)?;
+        let num_shard_inputs = num_shards.div_ceil(self.try_first_layer_batch_size(&opts)?);
+        let tree = self.reduction_strategy.plan(num_shard_inputs + batches.len(), batch_size);
+        let shards_tree = self.reduction_strategy.plan(num_shard_inputs, batch_size);
+        let extra_nodes = tree.num_nodes().saturating_sub(shards_tree.num_nodes());
+        let extra_height = tree.height().saturating_sub(shards_tree.height());
+
+        Ok(DeferredCostEstimate {
+            num_deferred_proofs: deferred_proofs.len(),
+            num_batches: batches.len(),
+            witness_bytes,
+            batch_shapes,
+            num_programs,
+            extra_nodes,
+            extra_height,
+            estimated_duration_ms: estimated_duration_ms(costs.compress_node, extra_nodes)?,
+        })
+    }
+}
+
+/// The duration of `num_proofs` proofs taking `unit` each, in milliseconds.
+fn estimated_duration_ms(unit: Duration, num_proofs: usize) -> Result<u64, SP1PlanError> {
+    u32::try_from(num_proofs)
+        .ok()
+        .and_then(|n| unit.checked_mul(n))
+        .and_then(|duration| u64::try_from(duration.as_millis()).ok())
+        .ok_or(SP1PlanError::TooManyProofs(num_proofs))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::BorrowMut;
+
+    use p3_baby_bear::BabyBear;
+    use p3_field::AbstractField;
+    use sp1_recursion_circuit::stark::dummy_vk_and_shard_proof;
+    use sp1_stark::{air::PublicValues, MachineProver, Word};
+
+    use super::*;
+    use crate::{
+        components::CpuProverComponents,
+        reload::{ProverConfigBundle, VkVerificationMode},
+        SP1VerifyingKey,
+    };
+
+    #[test]
+    fn test_compress_tree_dimensions() {
+        assert_eq!(compress_tree_height(1, 2), 0);
+        assert_eq!(compress_tree_num_nodes(1, 2), 1);
+
+        assert_eq!(compress_tree_height(2, 2), 1);
+        assert_eq!(compress_tree_num_nodes(2, 2), 3);
+
+        // 5 -> 3 -> 2 -> 1, with the odd input of the first two layers passed through.
+        assert_eq!(compress_tree_height(5, 2), 3);
+        assert_eq!(compress_tree_num_nodes(5, 2), 9);
+
+        // 5 -> 2 -> 1, with the last two inputs of the first layer joined together.
+        assert_eq!(compress_tree_layer_sizes(5, 3), vec![5, 2, 1]);
+        assert_eq!(compress_tree_num_nodes(5, 3), 8);
+    }
+
+    #[test]
+    fn test_cost_model_from_bench() {
+        use crate::bench::{ProgramResult, StageResult};
+
+        let stage = |stage, seconds| StageResult { stage, seconds, proof_size: 0 };
+        let results = SuiteResults {
+            circuit_version: String::new(),
+            prover_opts: SP1ProverOpts::default(),
+            last_stage: BenchStage::Compress,
+            programs: vec![ProgramResult {
+                name: "fibonacci".to_string(),
+                cycles: 0,
+                shards: 4,
+                setup_seconds: 0.0,
+                stages: vec![stage(BenchStage::Core, 8.0), stage(BenchStage::Compress, 14.0)],
+            }],
+        };
+
+        // 4 shards are reduced by 4 + 2 + 1 proofs.
+        let costs = PlanCostModel::from_bench(&results);
+        assert_eq!(costs.core_shard, Duration::from_secs(2));
+        assert_eq!(costs.compress_node, Duration::from_secs(2));
+        assert_eq!(costs.shrink, PlanCostModel::default().shrink);
+    }
+
+    #[test]
+    fn test_estimated_duration_overflow() {
+        assert_eq!(estimated_duration_ms(Duration::from_millis(3), 5).unwrap(), 15);
+        let num_proofs = u32::MAX as usize + 1;
+        assert!(matches!(
+            estimated_duration_ms(Duration::from_millis(1), num_proofs),
+            Err(SP1PlanError::TooManyProofs(n)) if n == num_proofs
+        ));
+        assert!(matches!(
+            estimated_duration_ms(Duration::MAX, 2),
+            Err(SP1PlanError::TooManyProofs(2))
+        ));
+    }
+
+    #[test]
+    fn test_plan_matches_first_layer_inputs() {
+        let prover = SP1Prover::<CpuProverComponents>::with_config(ProverConfigBundle {
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env_with_vk_verification(VkVerificationMode::Disabled)
+        });
+        let shape = OrderedShape { inner: vec![("Program".to_string(), 4)] };
+        let (vk, proof) = dummy_vk_and_shard_proof(prover.core_prover.machine(), &shape);
+        let vk =
+            SP1VerifyingKey { vk, shape_config_digest: None, artifacts: None, domain_tag: None };
+        let shard_proofs = (1..=8)
+            .map(|shard| {
+                let mut proof = proof.clone();
+                let pv: &mut PublicValues<Word<BabyBear>, BabyBear> =
+                    proof.public_values.as_mut_slice().borrow_mut();
+                pv.shard = BabyBear::from_canonical_u32(shard);
+                proof
+            })
+            .collect::<Vec<_>>();
+
+        for (num_shards, first_layer_batch_size) in [(1, 1), (7, 1), (7, 3), (8, 4), (8, 9)] {
+            let mut opts = SP1ProverOpts::default();
+            opts.compress_opts.first_layer_batch_size = first_layer_batch_size;
+            let inputs = prover
+                .get_first_layer_inputs(
+                    &vk,
+                    &shard_proofs[..num_shards],
+                    &[],
+                    prover.try_first_layer_batch_size(&opts).unwrap(),
+                )
+                .unwrap();
+            let plan = prover.recursion_tree_plan(num_shards, &[], &opts).unwrap();
+            assert_eq!(plan.num_first_layer_inputs, inputs.len());
+        }
+    }
+}
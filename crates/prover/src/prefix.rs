@@ -0,0 +1,111 @@
+//! Fast proofs of the first shards of an execution.
+//!
+//! Proving a whole program takes long, which slows down iterating on the toolchain, the ABI of a
+//! program and the wiring of its public values. [`SP1Prover::prove_core_prefix`] stops proving
+//! after a given number of execution shards, so that the first shard of a program is proven in a
+//! few seconds. The resulting [`SP1CorePrefixProof`] is not a proof of the execution and cannot be
+//! compressed, but [`SP1Prover::verify_prefix`] checks that its shards are valid and form a
+//! consistent prefix of the execution.
+
+use std::{borrow::Borrow, num::NonZeroU32};
+
+use p3_baby_bear::BabyBear;
+use p3_field::{AbstractField, PrimeField32};
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::{Program, SP1Context};
+use sp1_core_machine::{io::SP1Stdin, utils::SP1CoreProverError};
+use sp1_stark::{
+    air::PublicValues, MachineProof, MachineProver, MachineVerificationError, SP1ProverOpts,
+    StarkGenericConfig, Word,
+};
+
+use crate::{
+    components::SP1ProverComponents, CoreSC, DeviceProvingKey, SP1CoreProof, SP1Prover,
+    SP1VerifyingKey,
+};
+
+/// A proof of the first shards of an execution.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SP1CorePrefixProof {
+    /// The proof of the first shards. Its public values and cycles only cover the prefix.
+    pub proof: SP1CoreProof,
+    /// The number of execution shards the proof was limited to.
+    pub shard_limit: u32,
+}
+
+impl SP1CorePrefixProof {
+    /// Whether the execution halted within the prefix, in which case the proof covers the whole
+    /// execution.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.proof.proof.0.last().is_some_and(|shard_proof| {
+            let public_values: &PublicValues<Word<BabyBear>, BabyBear> =
+                shard_proof.public_values.as_slice().borrow();
+            public_values.next_pc == BabyBear::zero()
+        })
+    }
+
+    /// The number of shard proofs, including the precompile shards of the prefix.
+    #[must_use]
+    pub fn num_shards(&self) -> usize {
+        self.proof.proof.0.len()
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Prove the first `n_shards` execution shards of a program, see [`SP1CorePrefixProof`].
+    ///
+    /// The proof is incomplete unless the program halts within `n_shards` shards, and it should
+    /// only be used for development.
+    pub fn prove_core_prefix<'a>(
+        &'a self,
+        pk_d: &DeviceProvingKey<C>,
+        program: Program,
+        stdin: &SP1Stdin,
+        n_shards: NonZeroU32,
+        opts: SP1ProverOpts,
+        mut context: SP1Context<'a>,
+    ) -> Result<SP1CorePrefixProof, SP1CoreProverError> {
+        context.shard_limit = Some(n_shards.get());
+        let proof = self.prove_core(pk_d, program, stdin, opts, context)?;
+        let proof = SP1CorePrefixProof { proof, shard_limit: n_shards.get() };
+        if !proof.is_complete() {
+            tracing::warn!(
+                "proved the first {} execution shards only, the proof is incomplete",
+                n_shards
+            );
+        }
+        Ok(proof)
+    }
+
+    /// Verify the shards of a prefix proof and that they are contiguous from the start of the
+    /// execution.
+    ///
+    /// Unlike [`SP1Prover::verify`], this does not require the execution to halt or the lookups
+    /// between shards to balance, unless the proof is complete.
+    pub fn verify_prefix(
+        &self,
+        proof: &SP1CorePrefixProof,
+        vk: &SP1VerifyingKey,
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        if proof.is_complete() {
+            return self.verify(&proof.proof.proof, vk);
+        }
+
+        let shard_proofs = &proof.proof.proof.0;
+        self.verify_core_public_values(&proof.proof.proof, vk, false)?;
+        let last_public_values: &PublicValues<Word<BabyBear>, BabyBear> =
+            shard_proofs.last().unwrap().public_values.as_slice().borrow();
+        if last_public_values.execution_shard.as_canonical_u32() > proof.shard_limit {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "the prefix has more execution shards than its limit",
+            ));
+        }
+
+        let mut challenger = self.core_prover.config().challenger();
+        let machine_proof = MachineProof { shard_proofs: shard_proofs.clone() };
+        self.core_prover.machine().verify_shards(&vk.vk, &machine_proof, &mut challenger)?;
+
+        Ok(())
+    }
+}
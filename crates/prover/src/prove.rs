@@ -0,0 +1,133 @@
+//! One-shot proving.
+//!
+//! Generating a proof of a given kind means chaining the stages of the prover, each taking the
+//! output of the previous one: the core proof is compressed, then shrunk and wrapped over BN254,
+//! then proven with gnark. [`SP1Prover::prove`] runs the stages needed for a [`SP1ProofKind`] and
+//! returns the proof with its public values.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::SP1Context;
+use sp1_core_machine::{io::SP1Stdin, reduce::SP1ReduceProof};
+use sp1_recursion_gnark_ffi::{Groth16Bn254Proof, PlonkBn254Proof};
+use sp1_stark::{MachineProver, SP1ProverOpts};
+
+use crate::{
+    build::{
+        groth16_bn254_artifacts_dir, plonk_bn254_artifacts_dir, sp1_dev_mode,
+        try_build_groth16_bn254_artifacts_dev, try_build_plonk_bn254_artifacts_dev,
+    },
+    components::SP1ProverComponents,
+    GnarkLimits, InnerSC, ProofSystem, SP1CoreProofData, SP1ProofWithMetadata, SP1Prover,
+    SP1ProverError, SP1ProvingKey, SP1RecursionProverError,
+};
+
+/// The kind of proof generated by [`SP1Prover::prove`]. Each kind is made from a proof of the
+/// previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SP1ProofKind {
+    /// The proofs of the shards of the execution.
+    Core,
+    /// A single recursive proof of the core proof.
+    Compressed,
+    /// A Groth16 proof of the compressed proof, verifiable onchain.
+    Groth16,
+    /// A PLONK proof of the compressed proof, verifiable onchain.
+    Plonk,
+}
+
+/// A proof of any [`SP1ProofKind`].
+#[derive(Serialize, Deserialize, Clone)]
+pub enum SP1AnyProofData {
+    Core(SP1CoreProofData),
+    Compressed(Box<SP1ReduceProof<InnerSC>>),
+    Groth16(Groth16Bn254Proof),
+    Plonk(PlonkBn254Proof),
+}
+
+impl SP1AnyProofData {
+    /// The kind of the proof.
+    pub fn kind(&self) -> SP1ProofKind {
+        match self {
+            Self::Core(_) => SP1ProofKind::Core,
+            Self::Compressed(_) => SP1ProofKind::Compressed,
+            Self::Groth16(_) => SP1ProofKind::Groth16,
+            Self::Plonk(_) => SP1ProofKind::Plonk,
+        }
+    }
+}
+
+/// A proof generated by [`SP1Prover::prove`].
+pub type SP1AnyProof = SP1ProofWithMetadata<SP1AnyProofData>;
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Prove the program of `pk` on `stdin`, running every stage needed for a proof of `kind`.
+    ///
+    /// The proofs verified by the program are taken from `stdin`. For Groth16 and PLONK proofs,
+    /// the circuit artifacts are built in development mode, and otherwise must be installed in
+    /// [`groth16_bn254_artifacts_dir`] or [`plonk_bn254_artifacts_dir`].
+    pub fn prove(
+        &self,
+        pk: &SP1ProvingKey,
+        stdin: &SP1Stdin,
+        kind: SP1ProofKind,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1AnyProof, SP1ProverError> {
+        self.check_vk_shape_config(&pk.vk).map_err(SP1RecursionProverError::from)?;
+        let program = self
+            .get_program_in_domain(&pk.elf, pk.vk.domain_tag)
+            .map_err(SP1ProverError::Program)?;
+
+        // Generate the core proof.
+        let pk_d = self.core_prover.pk_to_device(&pk.pk);
+        let core_proof =
+            self.prove_core(&pk_d, program, stdin, opts.clone(), SP1Context::default())?;
+        let public_values = core_proof.public_values.clone();
+        let cycles = core_proof.cycles;
+        let with_metadata =
+            |proof| SP1ProofWithMetadata { proof, stdin: stdin.clone(), public_values, cycles };
+        if kind == SP1ProofKind::Core {
+            return Ok(with_metadata(SP1AnyProofData::Core(core_proof.proof)));
+        }
+
+        // Compress the core proof.
+        let deferred_proofs = stdin.proofs.iter().map(|(proof, _)| proof.clone()).collect();
+        let compressed = self.compress(&pk.vk, core_proof, deferred_proofs, opts.clone())?;
+        if kind == SP1ProofKind::Compressed {
+            return Ok(with_metadata(SP1AnyProofData::Compressed(Box::new(compressed))));
+        }
+
+        // Shrink and wrap the compressed proof.
+        let shrunk = self.shrink(compressed, opts.clone())?;
+        let wrapped = self.wrap_bn254(shrunk, opts)?;
+
+        // Prove the wrapped proof with gnark.
+        let limits = GnarkLimits::from_env();
+        let proof = if kind == SP1ProofKind::Groth16 {
+            let build_dir = if sp1_dev_mode() {
+                try_build_groth16_bn254_artifacts_dev(&wrapped.vk, &wrapped.proof)
+            } else {
+                installed_artifacts(ProofSystem::Groth16, groth16_bn254_artifacts_dir())?
+            };
+            SP1AnyProofData::Groth16(self.try_wrap_groth16_bn254(wrapped, &build_dir, &limits)?)
+        } else {
+            let build_dir = if sp1_dev_mode() {
+                try_build_plonk_bn254_artifacts_dev(&wrapped.vk, &wrapped.proof)
+            } else {
+                installed_artifacts(ProofSystem::Plonk, plonk_bn254_artifacts_dir())?
+            };
+            SP1AnyProofData::Plonk(self.try_wrap_plonk_bn254(wrapped, &build_dir, &limits)?)
+        };
+        Ok(with_metadata(proof))
+    }
+}
+
+/// The directory of the installed circuit artifacts of `system`, if it exists.
+fn installed_artifacts(system: ProofSystem, dir: PathBuf) -> Result<PathBuf, SP1ProverError> {
+    if dir.exists() {
+        Ok(dir)
+    } else {
+        Err(SP1ProverError::MissingCircuitArtifacts(system, dir))
+    }
+}
@@ -0,0 +1,157 @@
+//! Typed accessors for the public values of reduce proofs.
+//!
+//! The public values of compress, shrink and wrap proofs are a flat vector of field elements laid
+//! out as [`RecursionPublicValues`]. [`ReduceProofPublicValues`] reads them with their layout and
+//! range checked, so callers do not need to borrow the raw vector themselves.
+
+use std::{borrow::Borrow, ops::Range};
+
+use p3_baby_bear::BabyBear;
+use p3_field::{AbstractField, PrimeField32};
+use sp1_core_executor::SP1ReduceProof;
+use sp1_recursion_core::air::{RecursionPublicValues, RECURSIVE_PROOF_NUM_PV_ELTS};
+use sp1_stark::{air::POSEIDON_NUM_WORDS, StarkGenericConfig, DIGEST_SIZE};
+use thiserror::Error;
+
+use crate::utils::words_to_bytes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum PublicValuesError {
+    #[error("expected {RECURSIVE_PROOF_NUM_PV_ELTS} public values, found {0}")]
+    Length(usize),
+    #[error("{0}")]
+    Invalid(&'static str),
+}
+
+impl PublicValuesError {
+    /// A description of the error, as used by `MachineVerificationError::InvalidPublicValues`.
+    #[must_use]
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Length(_) => "wrong number of recursion public values",
+            Self::Invalid(reason) => reason,
+        }
+    }
+}
+
+/// Accessors for the public values of a compress, shrink or wrap proof.
+pub trait ReduceProofPublicValues {
+    /// The raw public values, after checking their length.
+    fn recursion_public_values(
+        &self,
+    ) -> Result<&RecursionPublicValues<BabyBear>, PublicValuesError>;
+
+    /// The digest of the verifying key of the program the proof is for.
+    fn sp1_vk_digest(&self) -> Result<[BabyBear; DIGEST_SIZE], PublicValuesError> {
+        Ok(self.recursion_public_values()?.sp1_vk_digest)
+    }
+
+    /// The digest of the public values committed by the program.
+    fn committed_value_digest(&self) -> Result<[u8; 32], PublicValuesError> {
+        let bytes = words_to_bytes(&self.recursion_public_values()?.committed_value_digest);
+        let mut digest = [0u8; 32];
+        for (byte, value) in digest.iter_mut().zip(bytes) {
+            *byte = u8::try_from(value.as_canonical_u32())
+                .map_err(|_| PublicValuesError::Invalid("committed_value_digest is not bytes"))?;
+        }
+        Ok(digest)
+    }
+
+    /// The digest of the deferred proofs verified by the program.
+    fn deferred_proofs_digest(&self) -> Result<[BabyBear; POSEIDON_NUM_WORDS], PublicValuesError> {
+        Ok(self.recursion_public_values()?.deferred_proofs_digest)
+    }
+
+    /// The root of the allowed recursion verifying keys.
+    fn vk_root(&self) -> Result<[BabyBear; DIGEST_SIZE], PublicValuesError> {
+        Ok(self.recursion_public_values()?.vk_root)
+    }
+
+    /// Whether the proof covers the whole execution of the program.
+    fn is_complete(&self) -> Result<bool, PublicValuesError> {
+        let is_complete = self.recursion_public_values()?.is_complete;
+        if is_complete == BabyBear::one() {
+            Ok(true)
+        } else if is_complete == BabyBear::zero() {
+            Ok(false)
+        } else {
+            Err(PublicValuesError::Invalid("is_complete is not boolean"))
+        }
+    }
+
+    /// The pc at the start of the proven shards and the pc expected next.
+    fn pc_range(&self) -> Result<Range<u32>, PublicValuesError> {
+        let public_values = self.recursion_public_values()?;
+        Ok(public_values.start_pc.as_canonical_u32()..public_values.next_pc.as_canonical_u32())
+    }
+
+    /// The first proven shard and the shard expected next.
+    fn shard_range(&self) -> Result<Range<u32>, PublicValuesError> {
+        let public_values = self.recursion_public_values()?;
+        Ok(public_values.start_shard.as_canonical_u32()..
+            public_values.next_shard.as_canonical_u32())
+    }
+}
+
+impl<SC: StarkGenericConfig<Val = BabyBear>> ReduceProofPublicValues for SP1ReduceProof<SC> {
+    fn recursion_public_values(
+        &self,
+    ) -> Result<&RecursionPublicValues<BabyBear>, PublicValuesError> {
+        let public_values = &self.proof.public_values;
+        if public_values.len() != RECURSIVE_PROOF_NUM_PV_ELTS {
+            return Err(PublicValuesError::Length(public_values.len()));
+        }
+        Ok(public_values.as_slice().borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::BorrowMut;
+
+    use sp1_stark::{air::MachineAir, shape::OrderedShape};
+
+    use super::*;
+    use crate::{CompressAir, InnerSC};
+
+    #[test]
+    fn test_reduce_proof_public_values() {
+        let machine = CompressAir::<BabyBear>::compress_machine(InnerSC::default());
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, mut proof) =
+            sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(&machine, &shape);
+        proof.public_values = vec![BabyBear::zero(); RECURSIVE_PROOF_NUM_PV_ELTS];
+        let public_values: &mut RecursionPublicValues<BabyBear> =
+            proof.public_values.as_mut_slice().borrow_mut();
+        public_values.start_pc = BabyBear::from_canonical_u32(4);
+        public_values.next_pc = BabyBear::from_canonical_u32(8);
+        public_values.committed_value_digest[0][0] = BabyBear::from_canonical_u32(7);
+        public_values.is_complete = BabyBear::one();
+        let reduce_proof = SP1ReduceProof { vk, proof };
+
+        assert_eq!(reduce_proof.pc_range(), Ok(4..8));
+        assert_eq!(reduce_proof.is_complete(), Ok(true));
+        assert_eq!(reduce_proof.committed_value_digest().unwrap()[0], 7);
+
+        let mut invalid = reduce_proof.clone();
+        let public_values: &mut RecursionPublicValues<BabyBear> =
+            invalid.proof.public_values.as_mut_slice().borrow_mut();
+        public_values.is_complete = BabyBear::two();
+        public_values.committed_value_digest[0][0] = BabyBear::from_canonical_u32(256);
+        assert_eq!(
+            invalid.is_complete(),
+            Err(PublicValuesError::Invalid("is_complete is not boolean"))
+        );
+        assert_eq!(
+            invalid.committed_value_digest(),
+            Err(PublicValuesError::Invalid("committed_value_digest is not bytes"))
+        );
+
+        let mut truncated = reduce_proof;
+        truncated.proof.public_values.pop();
+        assert_eq!(
+            truncated.vk_root(),
+            Err(PublicValuesError::Length(RECURSIVE_PROOF_NUM_PV_ELTS - 1))
+        );
+    }
+}
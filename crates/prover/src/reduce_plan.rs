@@ -0,0 +1,222 @@
+//! Planning the recursion proofs of `compress` for scheduling them outside of the prover.
+//!
+//! A [`ReducePlan`] lists every proof `compress` generates for a core proof, from the number of
+//! core shards and deferred proofs and the batch sizes alone: the deferred proofs and lifts of the
+//! first layer, and the joins of the recursion tree reducing them, each with the proofs it
+//! depends on. Orchestrators distributing the proofs over many machines can schedule the whole
+//! tree before the core proof is even finished, and hand each task its inputs as they are proven.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use sp1_stark::{DeferredProofOrdering, SP1ProverOpts};
+
+use crate::{
+    components::SP1ProverComponents,
+    reduction::{ReductionPlan, ReductionStrategy},
+    SP1Prover, SP1RecursionProverError,
+};
+
+/// The proof generated by a node of a [`ReducePlan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReduceTask {
+    /// Verify a batch of deferred proofs, by their indices in the order the program verified
+    /// them.
+    Deferred { proofs: Range<usize> },
+    /// Lift a batch of core shard proofs, by their indices in shard order.
+    Lift { shards: Range<usize> },
+    /// Join the proofs of the given nodes, in order.
+    ///
+    /// A join of a single node passes its proof through without proving anything.
+    Join { inputs: Vec<usize> },
+}
+
+/// A node of a [`ReducePlan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReduceNode {
+    pub task: ReduceTask,
+    /// The height of the node in the tree, the first layer being at height zero.
+    pub height: usize,
+}
+
+impl ReduceNode {
+    /// The nodes whose proofs are needed to prove this one.
+    #[must_use]
+    pub fn dependencies(&self) -> &[usize] {
+        match &self.task {
+            ReduceTask::Join { inputs } => inputs,
+            ReduceTask::Deferred { .. } | ReduceTask::Lift { .. } => &[],
+        }
+    }
+
+    /// Whether the node passes its single input through without proving it.
+    #[must_use]
+    pub fn is_passed_through(&self) -> bool {
+        self.dependencies().len() == 1
+    }
+}
+
+/// Every proof generated by `compress` for a core proof, see the [module documentation](self).
+///
+/// The nodes are in the order the prover numbers them: the deferred proofs batches first, then
+/// the lifts, then the joins, so every node comes after its dependencies and the last one is the
+/// root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReducePlan {
+    /// The maximum number of proofs joined at a time.
+    pub arity: usize,
+    pub nodes: Vec<ReduceNode>,
+}
+
+impl ReducePlan {
+    /// The plan for `num_shards` core shards and `num_deferred` deferred proofs, lifting
+    /// `first_layer_batch_size` shards and verifying `deferred_batch_size` deferred proofs at a
+    /// time, and joining the first layer with `strategy`.
+    ///
+    /// Deferred proofs are batched in input order.
+    #[must_use]
+    pub fn new(
+        num_shards: usize,
+        num_deferred: usize,
+        first_layer_batch_size: usize,
+        deferred_batch_size: usize,
+        arity: usize,
+        strategy: &dyn ReductionStrategy,
+    ) -> Self {
+        let first_layer =
+            first_layer(num_shards, num_deferred, first_layer_batch_size, deferred_batch_size);
+        let plan = strategy.plan(first_layer.len(), arity);
+        Self::from_reduction(first_layer, &plan, arity)
+    }
+
+    fn from_reduction(first_layer: Vec<ReduceTask>, plan: &ReductionPlan, arity: usize) -> Self {
+        let tasks = first_layer
+            .into_iter()
+            .chain(plan.joins.iter().map(|inputs| ReduceTask::Join { inputs: inputs.clone() }));
+        let nodes =
+            tasks.zip(plan.heights()).map(|(task, height)| ReduceNode { task, height }).collect();
+        Self { arity, nodes }
+    }
+
+    /// The index of the root node, whose proof is the compressed proof.
+    #[must_use]
+    pub fn root(&self) -> Option<usize> {
+        self.nodes.len().checked_sub(1)
+    }
+
+    /// The height of the tree, not counting the first layer.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.nodes.last().map_or(0, |node| node.height)
+    }
+
+    /// The number of lift proofs.
+    #[must_use]
+    pub fn num_lifts(&self) -> usize {
+        self.nodes.iter().filter(|node| matches!(node.task, ReduceTask::Lift { .. })).count()
+    }
+
+    /// The number of deferred proofs batches.
+    #[must_use]
+    pub fn num_deferred(&self) -> usize {
+        self.nodes.iter().filter(|node| matches!(node.task, ReduceTask::Deferred { .. })).count()
+    }
+
+    /// The number of join proofs, not counting the passed through nodes.
+    #[must_use]
+    pub fn num_joins(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                matches!(node.task, ReduceTask::Join { .. }) && !node.is_passed_through()
+            })
+            .count()
+    }
+
+    /// The number of proofs generated, not counting the passed through nodes.
+    #[must_use]
+    pub fn num_proofs(&self) -> usize {
+        self.nodes.iter().filter(|node| !node.is_passed_through()).count()
+    }
+
+    /// The node whose proof is passed to `index`, if any.
+    #[must_use]
+    pub fn dependent(&self, index: usize) -> Option<usize> {
+        self.nodes.iter().position(|node| node.dependencies().contains(&index))
+    }
+
+    /// The plan as pretty-printed JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// The deferred proofs batches and lifts of the first layer, in the order of the prover.
+fn first_layer(
+    num_shards: usize,
+    num_deferred: usize,
+    first_layer_batch_size: usize,
+    deferred_batch_size: usize,
+) -> Vec<ReduceTask> {
+    let deferred =
+        batches(num_deferred, deferred_batch_size).map(|proofs| ReduceTask::Deferred { proofs });
+    let lifts =
+        batches(num_shards, first_layer_batch_size).map(|shards| ReduceTask::Lift { shards });
+    deferred.chain(lifts).collect()
+}
+
+/// Consecutive ranges of at most `batch_size`, and at least one, of the first `len` indices.
+fn batches(len: usize, batch_size: usize) -> impl Iterator<Item = Range<usize>> {
+    let batch_size = batch_size.max(1);
+    (0..len).step_by(batch_size).map(move |start| start..len.min(start + batch_size))
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The proofs `compress` generates with `opts` for a core proof of `num_shards` shards
+    /// verifying `num_deferred` deferred proofs, with the reduction strategy of the prover.
+    ///
+    /// The plan assumes deferred proofs are batched in input order. With
+    /// [`DeferredProofOrdering::GroupByVk`], batches also end whenever the verifying key changes,
+    /// which the plan does not know about.
+    pub fn reduce_plan(
+        &self,
+        num_shards: usize,
+        num_deferred: usize,
+        opts: &SP1ProverOpts,
+    ) -> Result<ReducePlan, SP1RecursionProverError> {
+        if opts.deferred_opts.ordering != DeferredProofOrdering::InputOrder {
+            tracing::warn!("planning deferred proofs batches in input order");
+        }
+        let first_layer_batch_size = self.try_first_layer_batch_size(opts)?;
+        let deferred_batch_size = opts.deferred_opts.batch_size.max(1);
+        let arity = self.try_compress_arity(opts)?;
+        let first_layer =
+            first_layer(num_shards, num_deferred, first_layer_batch_size, deferred_batch_size);
+        let num_inputs = first_layer.len();
+        let plan = self.reduction_strategy.plan(num_inputs, arity);
+        plan.validate(num_inputs, arity).map_err(SP1RecursionProverError::InvalidReductionPlan)?;
+        Ok(ReducePlan::from_reduction(first_layer, &plan, arity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reduction::LayeredReduction;
+
+    #[test]
+    fn test_reduce_plan() {
+        let plan = ReducePlan::new(5, 3, 2, 2, 2, &LayeredReduction);
+        assert_eq!(plan.num_deferred(), 2);
+        assert_eq!(plan.num_lifts(), 3);
+        assert_eq!(plan.nodes[0].task, ReduceTask::Deferred { proofs: 0..2 });
+        assert_eq!(plan.nodes[4].task, ReduceTask::Lift { shards: 4..5 });
+        assert_eq!(plan.nodes[5].dependencies(), &[0, 1]);
+        assert_eq!(plan.dependent(4), Some(7));
+        assert!(plan.nodes[7].is_passed_through());
+        assert_eq!(plan.num_joins(), 4);
+        assert_eq!(plan.num_proofs(), 9);
+        assert_eq!(plan.root(), Some(10));
+        assert_eq!(plan.height(), 3);
+    }
+}
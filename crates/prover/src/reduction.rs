@@ -0,0 +1,188 @@
+//! Strategies for reducing the first layer proofs of `compress` into a single proof.
+//!
+//! A [`ReductionStrategy`] plans the joins of the recursion tree before any of them is proven, as
+//! a [`ReductionPlan`]. The prover then proves the joins in the order of the plan, each as soon as
+//! its inputs are proven, so a strategy only decides the shape of the tree: k-ary trees, chains
+//! folding one proof at a time, or unbalanced trees joining the first proofs early all reuse the
+//! same pipeline. The default strategy, [`LayeredReduction`], reduces each layer of the tree in
+//! consecutive batches of the compress arity.
+
+use sp1_stark::ShardProof;
+
+use crate::InnerSC;
+
+/// The joins reducing the first layer proofs of the recursion tree into a single proof.
+///
+/// The nodes of the tree are numbered in the order they are proven: the first layer proofs are the
+/// nodes `0..num_inputs`, and the join `j` produces the node `num_inputs + j`. The last join
+/// produces the root, and there is no join if there is a single input. A join of a single node
+/// passes it through without proving it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReductionPlan {
+    /// The number of first layer proofs.
+    pub num_inputs: usize,
+    /// The nodes joined by each join.
+    pub joins: Vec<Vec<usize>>,
+}
+
+impl ReductionPlan {
+    /// The height of each node of the tree, the first layer being at height zero.
+    #[must_use]
+    pub fn heights(&self) -> Vec<usize> {
+        let mut heights = vec![0; self.num_inputs];
+        for join in self.joins.iter() {
+            let height = join.iter().map(|&node| heights[node]).max().unwrap_or(0) + 1;
+            heights.push(height);
+        }
+        heights
+    }
+
+    /// The first of the first layer proofs reduced by each node of the tree.
+    #[must_use]
+    pub fn first_inputs(&self) -> Vec<usize> {
+        let mut first_inputs = (0..self.num_inputs).collect::<Vec<_>>();
+        for join in self.joins.iter() {
+            first_inputs.push(join.first().map_or(0, |&node| first_inputs[node]));
+        }
+        first_inputs
+    }
+
+    /// The height of the tree, not counting the first layer.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.heights().last().copied().unwrap_or(0)
+    }
+
+    /// The number of proofs generated, including the first layer but not the passed through
+    /// nodes.
+    #[must_use]
+    pub fn num_nodes(&self) -> usize {
+        self.num_inputs + self.joins.iter().filter(|join| join.len() > 1).count()
+    }
+
+    /// Check that the plan reduces `num_inputs` proofs into a single proof, joining at most
+    /// `arity` proofs at a time.
+    pub fn validate(&self, num_inputs: usize, arity: usize) -> Result<(), &'static str> {
+        if self.num_inputs != num_inputs || num_inputs == 0 {
+            return Err("the plan does not reduce the first layer inputs");
+        }
+        let mut joined = vec![false; num_inputs + self.joins.len()];
+        for (j, join) in self.joins.iter().enumerate() {
+            if join.is_empty() || join.len() > arity {
+                return Err("a join has no inputs or more inputs than the compress arity");
+            }
+            for &node in join.iter() {
+                if node >= num_inputs + j {
+                    return Err("a join depends on a node proven after it");
+                }
+                if std::mem::replace(&mut joined[node], true) {
+                    return Err("a node is joined more than once");
+                }
+            }
+        }
+        if joined[..joined.len() - 1].iter().any(|&joined| !joined) {
+            return Err("a node other than the root is never joined");
+        }
+        if self.joins.last().is_some_and(|join| join.len() == 1) {
+            return Err("the root is passed through instead of proven");
+        }
+        Ok(())
+    }
+}
+
+/// A strategy shaping the recursion tree of `compress`, see the [module documentation](self).
+pub trait ReductionStrategy: Send + Sync {
+    /// Plan the joins reducing `num_inputs` proofs into one, joining at most `arity` proofs at a
+    /// time.
+    fn plan(&self, num_inputs: usize, arity: usize) -> ReductionPlan;
+
+    /// Called when the join producing the node `index` of the tree is sent to be proven.
+    fn join_started(&self, _index: usize, _inputs: &[usize]) {}
+
+    /// Called when the node `index` of the tree is proven, at `height`.
+    fn node_proven(&self, _index: usize, _height: usize, _proof: &ShardProof<InnerSC>) {}
+}
+
+/// The default strategy, reducing each layer of the tree in consecutive batches of the compress
+/// arity.
+///
+/// A lone proof at the end of a layer is passed through to the next layer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayeredReduction;
+
+impl ReductionStrategy for LayeredReduction {
+    fn plan(&self, num_inputs: usize, arity: usize) -> ReductionPlan {
+        let mut joins = Vec::new();
+        let mut layer = (0..num_inputs).collect::<Vec<_>>();
+        while layer.len() > 1 {
+            let mut next_layer = Vec::new();
+            for batch in layer.chunks(arity) {
+                next_layer.push(num_inputs + joins.len());
+                joins.push(batch.to_vec());
+            }
+            layer = next_layer;
+        }
+        ReductionPlan { num_inputs, joins }
+    }
+}
+
+/// A strategy reducing each layer of the tree in as few joins as [`LayeredReduction`], with
+/// numbers of proofs differing by at most one.
+///
+/// The tree has the height of the layered tree, but a layer only passes a proof through when the
+/// arity is two and the layer has an odd number of proofs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BalancedReduction;
+
+impl ReductionStrategy for BalancedReduction {
+    fn plan(&self, num_inputs: usize, arity: usize) -> ReductionPlan {
+        let mut joins = Vec::new();
+        let mut layer = (0..num_inputs).collect::<Vec<_>>();
+        while layer.len() > 1 {
+            let mut next_layer = Vec::new();
+            let mut rest = layer.as_slice();
+            for num_joins_left in (1..=layer.len().div_ceil(arity)).rev() {
+                let (batch, tail) = rest.split_at(rest.len().div_ceil(num_joins_left));
+                next_layer.push(num_inputs + joins.len());
+                joins.push(batch.to_vec());
+                rest = tail;
+            }
+            layer = next_layer;
+        }
+        ReductionPlan { num_inputs, joins }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::{compress_tree_height, compress_tree_num_nodes};
+
+    #[test]
+    fn test_layered_reduction_plan() {
+        for num_inputs in 1..20 {
+            for arity in 2..5 {
+                let plan = LayeredReduction.plan(num_inputs, arity);
+                plan.validate(num_inputs, arity).unwrap();
+                assert_eq!(plan.height(), compress_tree_height(num_inputs, arity));
+                assert_eq!(plan.num_nodes(), compress_tree_num_nodes(num_inputs, arity));
+
+                let balanced = BalancedReduction.plan(num_inputs, arity);
+                balanced.validate(num_inputs, arity).unwrap();
+                assert_eq!(balanced.height(), plan.height());
+                assert_eq!(balanced.joins.len(), plan.joins.len());
+            }
+        }
+        let balanced = BalancedReduction.plan(5, 4);
+        assert_eq!(balanced.joins, vec![vec![0, 1, 2], vec![3, 4], vec![5, 6]]);
+
+        // A chain folding one proof at a time.
+        let chain = ReductionPlan { num_inputs: 3, joins: vec![vec![0, 1], vec![3, 2]] };
+        chain.validate(3, 2).unwrap();
+        assert_eq!(chain.height(), 2);
+        assert_eq!(chain.first_inputs(), vec![0, 1, 2, 0, 0]);
+        assert!(chain.validate(3, 1).is_err());
+        let reused = ReductionPlan { num_inputs: 3, joins: vec![vec![0, 1], vec![1, 2]] };
+        assert!(reused.validate(3, 2).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Setting up the keys of many programs at once.
+//!
+//! Registries of programs, such as proof marketplaces, set up hundreds of ELFs at a time.
+//! [`SP1Prover::setup_many`] sets them up in parallel on a pool of setup workers, skipping
+//! duplicate ELFs, and returns a [`SetupRegistry`] of their keys indexed by the SHA-256 digest of
+//! the ELF. Only the host keys are generated, so that the keys of many programs can be set up
+//! without holding their device keys.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sp1_core_machine::shape::CoreShapeConfig;
+
+use crate::{components::SP1ProverComponents, SP1Prover, SP1ProvingKey, SP1VerifyingKey};
+
+/// The digest identifying an ELF in a [`SetupRegistry`].
+#[must_use]
+pub fn elf_digest(elf: &[u8]) -> [u8; 32] {
+    Sha256::digest(elf).into()
+}
+
+/// The keys of a set of programs, indexed by the digests of their ELFs.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SetupRegistry {
+    pub keys: BTreeMap<[u8; 32], (SP1ProvingKey, SP1VerifyingKey)>,
+}
+
+impl SetupRegistry {
+    /// The keys of `elf`, if it was set up.
+    #[must_use]
+    pub fn get(&self, elf: &[u8]) -> Option<&(SP1ProvingKey, SP1VerifyingKey)> {
+        self.keys.get(&elf_digest(elf))
+    }
+
+    /// The keys of the ELF with the given digest, if it was set up.
+    #[must_use]
+    pub fn get_by_digest(&self, digest: &[u8; 32]) -> Option<&(SP1ProvingKey, SP1VerifyingKey)> {
+        self.keys.get(digest)
+    }
+
+    /// The number of distinct programs set up.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// The progress of [`SP1Prover::setup_many_with_progress`], reported after every program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetupProgress {
+    /// The digest of the ELF that was just set up.
+    pub digest: [u8; 32],
+    /// The number of distinct programs set up so far.
+    pub completed: usize,
+    /// The number of distinct programs to set up.
+    pub total: usize,
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Set up the keys of many ELFs in parallel, see the [module documentation](self).
+    pub fn setup_many(&self, elfs: &[&[u8]]) -> eyre::Result<SetupRegistry> {
+        self.setup_many_with_progress(elfs, |_| {})
+    }
+
+    /// Set up the keys of many ELFs in parallel, calling `progress` after every program.
+    ///
+    /// The programs are set up by `SP1_SETUP_WORKERS` workers, or by one worker per core if it is
+    /// not set. Setup stops at the first program that fails.
+    pub fn setup_many_with_progress(
+        &self,
+        elfs: &[&[u8]],
+        progress: impl Fn(SetupProgress) + Sync,
+    ) -> eyre::Result<SetupRegistry> {
+        let mut unique = BTreeMap::new();
+        for elf in elfs {
+            unique.entry(elf_digest(elf)).or_insert(*elf);
+        }
+        let total = unique.len();
+        tracing::info!("setting up {total} programs, {} duplicates skipped", elfs.len() - total);
+
+        let num_workers = env::var("SP1_SETUP_WORKERS")
+            .ok()
+            .and_then(|s| s.parse::<NonZeroUsize>().ok())
+            .map_or(0, NonZeroUsize::get);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_workers)
+            .thread_name(|i| format!("sp1-setup-{i}"))
+            .build()?;
+
+        let shape_config_digest = self.core_shape_config.as_ref().map(CoreShapeConfig::digest);
+        let completed = AtomicUsize::new(0);
+        let keys = pool.install(|| {
+            unique
+                .into_par_iter()
+                .map(|(digest, elf)| {
+                    let program = self.get_program(elf).map_err(|e| {
+                        e.wrap_err(format!("failed to set up program {}", hex::encode(digest)))
+                    })?;
+                    let keys = self.setup_host_keys(elf, &program, shape_config_digest);
+                    let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                    tracing::debug!("set up program {} ({completed}/{total})", hex::encode(digest));
+                    progress(SetupProgress { digest, completed, total });
+                    Ok((digest, keys))
+                })
+                .collect::<eyre::Result<BTreeMap<_, _>>>()
+        })?;
+
+        Ok(SetupRegistry { keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        components::CpuProverComponents, reload::ProverConfigBundle, tests::halting_elf,
+        HashableKey,
+    };
+
+    #[test]
+    fn test_setup_many() {
+        // Without core shapes, so that the programs are set up at their own small size.
+        let prover = SP1Prover::<CpuProverComponents>::with_config(ProverConfigBundle {
+            core_shape_config: None,
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env().unwrap()
+        });
+        let (first, second) = (halting_elf(0), halting_elf(1));
+        let elfs = [first.as_slice(), second.as_slice(), first.as_slice()];
+
+        // Duplicate ELFs are only set up once.
+        let reports = Mutex::new(Vec::new());
+        let registry = prover
+            .setup_many_with_progress(&elfs, |progress| reports.lock().unwrap().push(progress))
+            .unwrap();
+        assert_eq!(registry.len(), 2);
+        let mut completed = reports
+            .into_inner()
+            .unwrap()
+            .iter()
+            .map(|p| (p.completed, p.total))
+            .collect::<Vec<_>>();
+        completed.sort_unstable();
+        assert_eq!(completed, vec![(1, 2), (2, 2)]);
+
+        let (_, first_vk) = registry.get(&first).unwrap();
+        let (_, second_vk) = registry.get_by_digest(&elf_digest(&second)).unwrap();
+        assert_eq!(first_vk.hash_babybear(), prover.setup(&first).3.hash_babybear());
+        assert_ne!(first_vk.hash_babybear(), second_vk.hash_babybear());
+
+        // A program that fails to load fails the whole setup.
+        assert!(prover.setup_many(&[first.as_slice(), b"not an elf"]).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+//! Reloading the shape configurations and allowed verifying keys of a running prover.
+//!
+//! Long-running provers need to pick up new shape and vk map releases without restarting. A
+//! [`ProverConfigBundle`] holds everything that is derived from such a release, and
+//! [`ReloadableProver`] swaps in a prover built from a new bundle while proofs that are already
+//! running finish with the prover they started with.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::shape::CoreShapeConfig;
+use sp1_recursion_circuit::merkle_tree::{MerkleHash, MerkleTree, MerkleTreeConfig};
+use sp1_recursion_core::shape::RecursionShapeConfig;
+use sp1_stark::{SP1FriOpts, DIGEST_SIZE};
+
+use crate::{
+    components::SP1ProverComponents, lock_or_reset, shapes::VkBuildError, CompressAir, SP1Prover,
+    SP1ProverConfigError, MAX_COMPRESS_ARITY, REDUCE_BATCH_SIZE,
+};
+
+/// How a prover checks the recursion verifying keys of the proofs it makes and verifies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VkVerificationMode {
+    /// The recursion programs assert that every verifying key is in the vk map, and only proofs
+    /// committing to the root of the vk map are accepted.
+    #[default]
+    Enforced,
+    /// Proofs are made as with [`VkVerificationMode::Enforced`], but proofs made with vk
+    /// verification disabled are accepted too, with a warning.
+    ///
+    /// This is meant for test networks migrating from disabled vk verification.
+    Permissive,
+    /// The dummy vk map is used and no verifying key is checked, so proofs of programs with any
+    /// shape can be made. Their vk root is that of the dummy vk map, which enforcing provers
+    /// reject.
+    ///
+    /// This is insecure, and only meant for development and controlled environments.
+    Disabled,
+}
+
+impl VkVerificationMode {
+    /// The mode set by `SP1_VK_VERIFICATION`, one of `enforced`, `permissive` or `disabled`.
+    ///
+    /// If it is not set, `VERIFY_VK=false` disables vk verification, as in earlier versions.
+    pub fn from_env() -> Result<Self, SP1ProverConfigError> {
+        match env::var("SP1_VK_VERIFICATION") {
+            Ok(v) => v.parse(),
+            Err(_) => match env::var("VERIFY_VK") {
+                Ok(v) if !v.eq_ignore_ascii_case("true") => {
+                    tracing::warn!(
+                        "VERIFY_VK is deprecated, set SP1_VK_VERIFICATION=disabled instead"
+                    );
+                    Ok(Self::Disabled)
+                }
+                _ => Ok(Self::Enforced),
+            },
+        }
+    }
+
+    /// Whether the recursion programs check verifying keys against the vk map.
+    #[must_use]
+    pub fn checks_vks(self) -> bool {
+        self != Self::Disabled
+    }
+}
+
+impl FromStr for VkVerificationMode {
+    type Err = SP1ProverConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            s if s.eq_ignore_ascii_case("enforced") => Ok(Self::Enforced),
+            s if s.eq_ignore_ascii_case("permissive") => Ok(Self::Permissive),
+            s if s.eq_ignore_ascii_case("disabled") => Ok(Self::Disabled),
+            s => Err(SP1ProverConfigError::VkVerification(s.to_string())),
+        }
+    }
+}
+
+/// The shape configurations and allowed recursion verifying keys of a prover.
+pub struct ProverConfigBundle {
+    /// The core shape configuration, or `None` to not fix core shapes.
+    pub core_shape_config: Option<CoreShapeConfig<BabyBear>>,
+    /// The recursion shape configuration, or `None` to not fix recursion shapes.
+    pub compress_shape_config: Option<RecursionShapeConfig<BabyBear, CompressAir<BabyBear>>>,
+    /// How verifying keys are checked.
+    pub vk_verification: VkVerificationMode,
+    /// The allowed recursion verifying keys and their indices in the vk Merkle tree.
+    pub vk_map: BTreeMap<[BabyBear; DIGEST_SIZE], usize>,
+    /// The arity and hash of the vk Merkle tree.
+    ///
+    /// The recursion programs verify proofs of this tree, so a vk map built for the same
+    /// configuration is needed to verify verification keys with any other than the default.
+    pub vk_merkle_config: MerkleTreeConfig,
+    /// The largest number of proofs joined by a join program with fixed recursion shapes, from
+    /// [`REDUCE_BATCH_SIZE`] to [`MAX_COMPRESS_ARITY`].
+    ///
+    /// The join programs of every arity up to this one are precompiled, which takes more time and
+    /// memory for larger arities, and the vk map must be built with the same reduce batch size
+    /// for vk verification.
+    pub max_compress_arity: usize,
+}
+
+impl ProverConfigBundle {
+    /// The configuration built into the prover, adjusted by the `FIX_CORE_SHAPES`,
+    /// `FIX_RECURSION_SHAPES`, `SP1_VK_VERIFICATION`, `SP1_VK_MERKLE_ARITY`, `SP1_VK_MERKLE_HASH`
+    /// and `SP1_MAX_COMPRESS_ARITY` environment variables, see
+    /// [`VkVerificationMode::from_env`].
+    pub fn from_env() -> Result<Self, SP1ProverConfigError> {
+        Ok(Self::from_env_with_vk_verification(VkVerificationMode::from_env()?))
+    }
+
+    /// The configuration of [`Self::from_env`], checking verifying keys with `vk_verification`
+    /// whatever `SP1_VK_VERIFICATION` is set to.
+    #[must_use]
+    pub fn from_env_with_vk_verification(vk_verification: VkVerificationMode) -> Self {
+        let core_shape_config = env::var("FIX_CORE_SHAPES")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(true)
+            .then_some(CoreShapeConfig::default());
+
+        let compress_shape_config = env::var("FIX_RECURSION_SHAPES")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(true)
+            .then_some(RecursionShapeConfig::default());
+
+        // Read the shapes from the shapes directory and deserialize them into memory.
+        let vk_map = if vk_verification.checks_vks() {
+            bincode::deserialize(include_bytes!(concat!(env!("OUT_DIR"), "/vk_map.bin"))).unwrap()
+        } else {
+            dummy_vk_map()
+        };
+
+        Self {
+            core_shape_config,
+            compress_shape_config,
+            vk_verification,
+            vk_map,
+            vk_merkle_config: vk_merkle_config_from_env(),
+            max_compress_arity: env::var("SP1_MAX_COMPRESS_ARITY")
+                .map(|v| v.parse().expect("SP1_MAX_COMPRESS_ARITY must be a usize"))
+                .map_or(REDUCE_BATCH_SIZE, checked_max_compress_arity),
+        }
+    }
+
+    /// Precompile the join programs of up to `arity` proofs, see
+    /// [`ProverConfigBundle::max_compress_arity`].
+    #[must_use]
+    pub fn with_max_compress_arity(mut self, arity: usize) -> Self {
+        self.max_compress_arity = checked_max_compress_arity(arity);
+        self
+    }
+
+    /// Check verifying keys with `mode`.
+    ///
+    /// Switching from or to [`VkVerificationMode::Disabled`] replaces the allowed verifying keys
+    /// with the dummy or built-in vk map.
+    #[must_use]
+    pub fn with_vk_verification(mut self, mode: VkVerificationMode) -> Self {
+        if mode.checks_vks() != self.vk_verification.checks_vks() {
+            self.vk_map = if mode.checks_vks() {
+                bincode::deserialize(include_bytes!(concat!(env!("OUT_DIR"), "/vk_map.bin")))
+                    .unwrap()
+            } else {
+                dummy_vk_map()
+            };
+        }
+        self.vk_verification = mode;
+        self
+    }
+
+    /// Replace the allowed verifying keys with the vk map at `path`, as written by
+    /// [`crate::shapes::build_vk_map_to_file`].
+    pub fn with_vk_map_file(mut self, path: impl AsRef<Path>) -> Result<Self, VkBuildError> {
+        self.vk_map = bincode::deserialize_from(BufReader::new(File::open(path)?))?;
+        Ok(self)
+    }
+}
+
+/// The vk map of provers without vk verification.
+pub(crate) fn dummy_vk_map() -> BTreeMap<[BabyBear; DIGEST_SIZE], usize> {
+    bincode::deserialize(include_bytes!("vk_map_dummy.bin")).unwrap()
+}
+
+fn checked_max_compress_arity(arity: usize) -> usize {
+    assert!(
+        (REDUCE_BATCH_SIZE..=MAX_COMPRESS_ARITY).contains(&arity),
+        "the max compress arity must be between {REDUCE_BATCH_SIZE} and {MAX_COMPRESS_ARITY}, got \
+         {arity}"
+    );
+    arity
+}
+
+/// The vk Merkle tree configuration set by `SP1_VK_MERKLE_ARITY` and `SP1_VK_MERKLE_HASH`.
+///
+/// The hash is either `compress` or `sponge`, and defaults to `compress` for binary trees and to
+/// `sponge` otherwise.
+fn vk_merkle_config_from_env() -> MerkleTreeConfig {
+    let arity = env::var("SP1_VK_MERKLE_ARITY")
+        .map(|v| v.parse().expect("SP1_VK_MERKLE_ARITY must be a usize"))
+        .unwrap_or(2);
+    let hash = match env::var("SP1_VK_MERKLE_HASH") {
+        Ok(v) if v.eq_ignore_ascii_case("compress") => MerkleHash::Compress,
+        Ok(v) if v.eq_ignore_ascii_case("sponge") => MerkleHash::Sponge,
+        Ok(v) => panic!("SP1_VK_MERKLE_HASH must be compress or sponge, got {v}"),
+        Err(_) if arity == 2 => MerkleHash::Compress,
+        Err(_) => MerkleHash::Sponge,
+    };
+    let config = MerkleTreeConfig { arity, hash };
+    assert!(config.is_valid(), "unsupported vk merkle tree: {config:?}");
+    config
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Replace the shape configurations and allowed verifying keys of the prover.
+    ///
+    /// Every cached program is dropped, and the join programs are compiled again for the new
+    /// configuration. This takes exclusive access to the prover, so it can only happen between
+    /// proofs; use [`ReloadableProver`] to keep proving while a new configuration is prepared.
+    ///
+    /// Fails if the core of the prover is shared with other handles.
+    pub fn reload(&mut self, config: ProverConfigBundle) -> Result<(), SP1ProverConfigError> {
+        if self.core_mut().is_none() {
+            return Err(SP1ProverConfigError::SharedCore);
+        }
+        let ProverConfigBundle {
+            core_shape_config,
+            compress_shape_config,
+            vk_verification,
+            vk_map,
+            vk_merkle_config,
+            max_compress_arity,
+        } = config;
+        let (root, merkle_tree) =
+            MerkleTree::commit_with_config(vk_map.keys().copied().collect(), vk_merkle_config);
+        tracing::info!(
+            "reloading prover configuration: {} allowed vks, vk verification: {:?}",
+            vk_map.len(),
+            vk_verification
+        );
+
+        let join_programs_map = Self::precompile_join_programs(
+            &self.compress_prover,
+            compress_shape_config.as_ref(),
+            vk_verification.checks_vks(),
+            merkle_tree.height,
+            merkle_tree.config,
+            max_compress_arity,
+        );
+        let core = self.core_mut().ok_or(SP1ProverConfigError::SharedCore)?;
+        core.join_programs_map = join_programs_map;
+        core.core_shape_config = core_shape_config;
+        core.compress_shape_config = compress_shape_config;
+        core.vk_verification = vk_verification;
+        core.max_compress_arity = max_compress_arity;
+        core.recursion_vk_root = root;
+        core.recursion_vk_tree = merkle_tree;
+        core.recursion_vk_map = vk_map;
+        core.dummy_vk_root = OnceLock::new();
+        core.wrap_program = OnceLock::new();
+        core.wrap_vk = OnceLock::new();
+
+        lock_or_reset(&self.lift_programs_inflight, BTreeMap::clear).clear();
+        *lock_or_reset(&self.shrink_setup, |setup| *setup = None) = None;
+        *lock_or_reset(&self.pending_shrink_setup, |pending| *pending = None) = None;
+        self.reset_caches();
+        Ok(())
+    }
+}
+
+/// A prover whose configuration can be replaced while it is in use.
+///
+/// Each proof should be generated with the prover returned by [`ReloadableProver::current`].
+/// [`ReloadableProver::reload`] builds a new prover from scratch and swaps it in atomically, so
+/// new proofs use the new configuration while proofs already running keep the old prover alive
+/// until they finish.
+pub struct ReloadableProver<C: SP1ProverComponents> {
+    current: RwLock<Arc<SP1Prover<C>>>,
+}
+
+impl<C: SP1ProverComponents> ReloadableProver<C> {
+    #[must_use]
+    pub fn new(prover: SP1Prover<C>) -> Self {
+        Self { current: RwLock::new(Arc::new(prover)) }
+    }
+
+    /// The prover to use for new proofs.
+    pub fn current(&self) -> Arc<SP1Prover<C>> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Build a prover with the same FRI parameters and duty cycle as the current one and the
+    /// configuration of `config`, and swap it in.
+    ///
+    /// The new prover is built before the swap, so proving is not interrupted while the join
+    /// programs are compiled.
+    pub fn reload(&self, config: ProverConfigBundle) -> Result<(), SP1ProverConfigError> {
+        let current = self.current();
+        // The FRI parameters were already accepted when the current prover was created.
+        let fri_opts = SP1FriOpts { allow_insecure: true, ..current.fri_opts() };
+        let mut prover = SP1Prover::with_fri_opts_and_config(&fri_opts, config)?;
+        prover.throttle = current.throttle;
+        drop(current);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(prover);
+        tracing::info!("swapped in reloaded prover");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vk_verification_mode_from_str() {
+        assert_eq!("enforced".parse::<VkVerificationMode>().unwrap(), VkVerificationMode::Enforced);
+        assert_eq!(
+            "Permissive".parse::<VkVerificationMode>().unwrap(),
+            VkVerificationMode::Permissive
+        );
+        assert_eq!("DISABLED".parse::<VkVerificationMode>().unwrap(), VkVerificationMode::Disabled);
+        assert!(matches!(
+            "off".parse::<VkVerificationMode>(),
+            Err(SP1ProverConfigError::VkVerification(mode)) if mode == "off"
+        ));
+    }
+
+    #[test]
+    fn test_reload() {
+        use p3_field::AbstractField;
+
+        use crate::{components::CpuProverComponents, tests::unfixed_prover};
+
+        let config = || ProverConfigBundle {
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env().unwrap()
+        };
+        let vk_map = [([BabyBear::one(); DIGEST_SIZE], 0), ([BabyBear::two(); DIGEST_SIZE], 1)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        // Reloading in place replaces the vk map and its root.
+        let mut prover = unfixed_prover();
+        let root = prover.recursion_vk_root;
+        prover.reload(ProverConfigBundle { vk_map: vk_map.clone(), ..config() }).unwrap();
+        assert_eq!(prover.recursion_vk_map, vk_map);
+        assert_ne!(prover.recursion_vk_root, root);
+
+        // A reloadable prover swaps in a new prover, and the old one stays usable.
+        let reloadable = ReloadableProver::<CpuProverComponents>::new(unfixed_prover());
+        let old = reloadable.current();
+        reloadable
+            .reload(ProverConfigBundle {
+                vk_map: vk_map.clone(),
+                vk_verification: VkVerificationMode::Permissive,
+                ..config()
+            })
+            .unwrap();
+        let new = reloadable.current();
+        assert!(!Arc::ptr_eq(&old, &new));
+        assert_eq!(new.recursion_vk_map, vk_map);
+        assert_eq!(new.vk_verification, VkVerificationMode::Permissive);
+        assert_eq!(old.recursion_vk_root, root);
+        assert_eq!(new.recursion_vk_root, prover.recursion_vk_root);
+    }
+
+    #[test]
+    fn test_max_compress_arity() {
+        use sp1_stark::{SP1CompressOpts, SP1ProverOpts};
+
+        use crate::components::CpuProverComponents;
+
+        // With vk verification, only the arities up to the max compress arity are used.
+        let prover = SP1Prover::<CpuProverComponents>::with_config(
+            ProverConfigBundle {
+                compress_shape_config: None,
+                ..ProverConfigBundle::from_env().unwrap()
+            }
+            .with_max_compress_arity(3),
+        );
+        assert!(prover.vk_verification.checks_vks());
+        let opts = |arity| SP1ProverOpts {
+            compress_opts: SP1CompressOpts { arity, ..SP1CompressOpts::default() },
+            ..SP1ProverOpts::default()
+        };
+        assert_eq!(prover.compress_arity(&opts(3)), 3);
+        assert_eq!(prover.compress_arity(&opts(4)), REDUCE_BATCH_SIZE);
+        let candidates = prover.compress_config_candidates(prover.fri_opts().compress);
+        assert_eq!(candidates.iter().map(|config| config.arity).collect::<Vec<_>>(), [2, 3]);
+
+        let result = std::panic::catch_unwind(|| {
+            ProverConfigBundle::from_env().unwrap().with_max_compress_arity(MAX_COMPRESS_ARITY + 1)
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Repairing compressed proofs without re-proving the whole recursion tree.
+//!
+//! If the final compressed proof fails to verify, for example because a proof was corrupted by a
+//! faulty worker, proving the tree again from scratch takes as long as the original compression.
+//! [`SP1Prover::compress_checkpointed`] keeps every proof of the tree in a [`CompressCheckpoint`],
+//! and [`SP1Prover::repair_compress`] verifies them bottom-up and re-proves only the invalid proofs
+//! the root depends on, reusing every valid subtree.
+//!
+//! A valid proof attests that the proofs it joins were valid when it was proven, so an invalid
+//! proof below a valid one does not need to be re-proven.
+
+use std::{collections::BTreeMap, iter, sync::Mutex};
+
+use p3_baby_bear::BabyBear;
+use p3_field::AbstractField;
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::reduce::SP1ReduceProof;
+use sp1_recursion_circuit::machine::SP1CompressWitnessValues;
+use sp1_stark::{
+    MachineProof, MachineProver, ShardProof, StarkGenericConfig, StarkVerifyingKey, DIGEST_SIZE,
+};
+
+use crate::{
+    components::SP1ProverComponents, HashableKey, InnerSC, SP1CircuitWitness, SP1CoreProof,
+    SP1Prover, SP1ProverOpts, SP1RecursionProverError, SP1VerifyingKey,
+};
+
+/// The verification status of a proof of a [`CompressCheckpoint`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProofStatus {
+    /// The proof was not verified since it was proven or loaded.
+    #[default]
+    Unverified,
+    Valid,
+    Invalid,
+}
+
+/// A proof of the recursion tree built by compress.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompressNode {
+    /// The layer of the tree, the first layer being zero.
+    pub layer: usize,
+    /// The indices of the proofs joined into this one, empty in the first layer.
+    pub inputs: Vec<usize>,
+    /// Whether the proof is marked as complete, which is only the case for the root.
+    pub is_complete: bool,
+    /// Whether the proof is its only input passed through from the previous layer.
+    pub passed_through: bool,
+    pub vk: StarkVerifyingKey<InnerSC>,
+    pub proof: ShardProof<InnerSC>,
+    pub status: ProofStatus,
+}
+
+/// Every proof of a recursion tree built by compress, in the order they were generated.
+///
+/// The first layer comes first and the root is last, and every proof comes after its inputs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompressCheckpoint {
+    pub nodes: Vec<CompressNode>,
+}
+
+impl CompressCheckpoint {
+    /// The root of the tree, which is the compressed proof.
+    #[must_use]
+    pub fn root(&self) -> Option<SP1ReduceProof<InnerSC>> {
+        self.nodes
+            .last()
+            .map(|node| SP1ReduceProof { vk: node.vk.clone(), proof: node.proof.clone() })
+    }
+
+    /// The indices of the proofs found invalid by the last verification.
+    pub fn invalid(&self) -> impl Iterator<Item = usize> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| (node.status == ProofStatus::Invalid).then_some(i))
+    }
+}
+
+/// Collects the proofs of a recursion tree while it is being built.
+#[derive(Default)]
+pub(crate) struct CheckpointRecorder {
+    /// The inputs of each join, whether it is complete and whether it is passed through.
+    joins: BTreeMap<usize, (Vec<usize>, bool, bool)>,
+    /// The layer, verifying key and proof of each proof.
+    proofs: BTreeMap<usize, (usize, StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>)>,
+}
+
+impl CheckpointRecorder {
+    pub(crate) fn record_join(
+        &mut self,
+        index: usize,
+        inputs: Vec<usize>,
+        is_complete: bool,
+        passed_through: bool,
+    ) {
+        self.joins.insert(index, (inputs, is_complete, passed_through));
+    }
+
+    pub(crate) fn record_proof(
+        &mut self,
+        index: usize,
+        layer: usize,
+        vk: &StarkVerifyingKey<InnerSC>,
+        proof: &ShardProof<InnerSC>,
+    ) {
+        self.proofs.insert(index, (layer, vk.clone(), proof.clone()));
+    }
+
+    fn into_checkpoint(mut self) -> CompressCheckpoint {
+        let nodes = self
+            .proofs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (index, (layer, vk, proof)))| {
+                assert_eq!(i, index, "the recursion tree skipped proof {i}");
+                let (inputs, is_complete, passed_through) =
+                    self.joins.remove(&index).unwrap_or_default();
+                CompressNode {
+                    layer,
+                    inputs,
+                    is_complete,
+                    passed_through,
+                    vk,
+                    proof,
+                    status: ProofStatus::Unverified,
+                }
+            })
+            .collect();
+        CompressCheckpoint { nodes }
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Reduce shard proofs to a single shard proof, see [`Self::compress`], keeping every proof of
+    /// the recursion tree so that the proof can be repaired with [`Self::repair_compress`].
+    pub fn compress_checkpointed(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+    ) -> Result<(SP1ReduceProof<InnerSC>, CompressCheckpoint), SP1RecursionProverError> {
+        let recorder = Mutex::new(CheckpointRecorder::default());
+        let reduced = self.compress_with_recorder(
+            vk,
+            proof,
+            deferred_proofs,
+            [BabyBear::zero(); DIGEST_SIZE],
+            opts,
+            Some(&recorder),
+            None,
+        )?;
+        Ok((reduced, recorder.into_inner().unwrap().into_checkpoint()))
+    }
+
+    /// Verify every proof of a checkpoint, from the first layer to the root, updating their
+    /// statuses. Returns the number of invalid proofs.
+    pub fn verify_checkpoint(&self, checkpoint: &mut CompressCheckpoint) -> usize {
+        for node in &mut checkpoint.nodes {
+            node.status = self.compress_node_status(&node.vk, &node.proof);
+        }
+        checkpoint.invalid().count()
+    }
+
+    /// Repair the compressed proof of a checkpoint, re-proving only the invalid proofs the root
+    /// depends on.
+    ///
+    /// `proof` and `deferred_proofs` must be the ones the checkpoint was compressed from, as the
+    /// invalid proofs of the first layer are proven again from them. The repaired proofs replace
+    /// the invalid ones in the checkpoint.
+    pub fn repair_compress(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: &SP1CoreProof,
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        checkpoint: &mut CompressCheckpoint,
+        opts: SP1ProverOpts,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.check_vk_shape_config(vk)?;
+        self.check_vk_artifacts(vk)?;
+        let num_invalid = self.verify_checkpoint(checkpoint);
+        let nodes = &mut checkpoint.nodes;
+        let Some(root) = nodes.len().checked_sub(1) else {
+            return Err(SP1RecursionProverError::CheckpointMismatch("the checkpoint is empty"));
+        };
+
+        // Starting from the root, re-prove the invalid proofs whose joins are re-proven.
+        let mut reprove = vec![false; nodes.len()];
+        reprove[root] = nodes[root].status == ProofStatus::Invalid;
+        for i in (0..nodes.len()).rev() {
+            if !reprove[i] {
+                continue;
+            }
+            for &input in &nodes[i].inputs {
+                if input >= i {
+                    return Err(SP1RecursionProverError::CheckpointMismatch(
+                        "a proof comes before its inputs",
+                    ));
+                }
+                reprove[input] = nodes[input].status == ProofStatus::Invalid;
+            }
+        }
+        let num_reproven = reprove.iter().filter(|reprove| **reprove).count();
+        tracing::info!(
+            "{num_invalid} of {} compress proofs are invalid, re-proving {num_reproven}",
+            nodes.len()
+        );
+        if num_reproven == 0 {
+            return Ok(checkpoint.root().unwrap());
+        }
+
+        // The first layer inputs are only generated if a proof of the first layer is re-proven.
+        let mut first_layer_inputs = Vec::new();
+        if (0..nodes.len()).any(|i| reprove[i] && nodes[i].layer == 0) {
+            first_layer_inputs = self
+                .get_first_layer_inputs_with_deferred_opts(
+                    vk,
+                    &proof.proof.0,
+                    deferred_proofs,
+                    self.try_first_layer_batch_size(&opts)?,
+                    &opts.deferred_opts,
+                )?
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>();
+            let num_leaves = nodes.iter().filter(|node| node.layer == 0).count();
+            if first_layer_inputs.len() != num_leaves {
+                return Err(SP1RecursionProverError::CheckpointMismatch(
+                    "the number of first layer proofs differs",
+                ));
+            }
+        }
+
+        for i in (0..nodes.len()).filter(|&i| reprove[i]) {
+            let node = &nodes[i];
+            let (vk, proof) = if node.passed_through {
+                let input = &nodes[node.inputs[0]];
+                (input.vk.clone(), input.proof.clone())
+            } else {
+                let input = if node.layer == 0 {
+                    first_layer_inputs[i].take().unwrap()
+                } else {
+                    SP1CircuitWitness::Compress(SP1CompressWitnessValues {
+                        vks_and_proofs: node
+                            .inputs
+                            .iter()
+                            .map(|&input| (nodes[input].vk.clone(), nodes[input].proof.clone()))
+                            .collect(),
+                        is_complete: node.is_complete,
+                    })
+                };
+                self.reduce_tree(iter::once((input, false)), 1, false, opts.clone())?
+            };
+
+            if self.compress_node_status(&vk, &proof) != ProofStatus::Valid {
+                return Err(SP1RecursionProverError::RepairFailed(i));
+            }
+            let node = &mut nodes[i];
+            node.vk = vk;
+            node.proof = proof;
+            node.status = ProofStatus::Valid;
+        }
+
+        Ok(checkpoint.root().unwrap())
+    }
+
+    /// Verify a proof of the recursion tree and that its verifying key is allowed.
+    fn compress_node_status(
+        &self,
+        vk: &StarkVerifyingKey<InnerSC>,
+        proof: &ShardProof<InnerSC>,
+    ) -> ProofStatus {
+        if self.vk_verification.checks_vks() &&
+            !self.recursion_vk_map.contains_key(&vk.hash_babybear())
+        {
+            return ProofStatus::Invalid;
+        }
+        let mut challenger = self.compress_prover.config().challenger();
+        let machine_proof = MachineProof { shard_proofs: vec![proof.clone()] };
+        match self.compress_prover.machine().verify(vk, &machine_proof, &mut challenger) {
+            Ok(()) => ProofStatus::Valid,
+            Err(_) => ProofStatus::Invalid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp1_core_machine::io::SP1Stdin;
+    use sp1_primitives::io::SP1PublicValues;
+    use sp1_stark::{air::MachineAir, shape::OrderedShape};
+
+    use super::*;
+    use crate::{tests::unfixed_prover, SP1CoreProofData};
+
+    #[test]
+    fn test_compress_checkpoint() {
+        let prover = unfixed_prover();
+        let machine = prover.compress_prover.machine();
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(machine, &shape);
+
+        // Proofs are recorded as they are done, but the checkpoint is in tree order.
+        let mut recorder = CheckpointRecorder::default();
+        recorder.record_proof(1, 0, &vk, &proof);
+        recorder.record_join(2, vec![0, 1], true, false);
+        recorder.record_proof(2, 1, &vk, &proof);
+        recorder.record_proof(0, 0, &vk, &proof);
+        let mut checkpoint = recorder.into_checkpoint();
+        let layers = checkpoint.nodes.iter().map(|node| node.layer).collect::<Vec<_>>();
+        assert_eq!(layers, vec![0, 0, 1]);
+        assert_eq!(checkpoint.nodes[2].inputs, vec![0, 1]);
+        assert!(checkpoint.nodes[2].is_complete);
+
+        // The dummy proofs do not verify.
+        assert_eq!(prover.verify_checkpoint(&mut checkpoint), 3);
+        assert_eq!(checkpoint.invalid().collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        // A checkpoint whose proofs come before their inputs cannot be repaired.
+        let core_machine = prover.core_prover.machine();
+        let core_shape = OrderedShape { inner: vec![("Program".to_string(), 4)] };
+        let (core_vk, _) =
+            sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(core_machine, &core_shape);
+        let core_vk = SP1VerifyingKey {
+            vk: core_vk,
+            shape_config_digest: None,
+            artifacts: None,
+            domain_tag: None,
+        };
+        let core_proof = SP1CoreProof {
+            proof: SP1CoreProofData(vec![]),
+            stdin: SP1Stdin::new(),
+            public_values: SP1PublicValues::new(),
+            cycles: 0,
+        };
+        checkpoint.nodes[2].inputs = vec![0, 2];
+        let opts = SP1ProverOpts::default();
+        assert!(matches!(
+            prover.repair_compress(&core_vk, &core_proof, &[], &mut checkpoint, opts.clone()),
+            Err(SP1RecursionProverError::CheckpointMismatch("a proof comes before its inputs"))
+        ));
+        let mut empty = CompressCheckpoint { nodes: vec![] };
+        assert!(matches!(
+            prover.repair_compress(&core_vk, &core_proof, &[], &mut empty, opts),
+            Err(SP1RecursionProverError::CheckpointMismatch("the checkpoint is empty"))
+        ));
+    }
+}
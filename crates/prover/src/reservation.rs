@@ -0,0 +1,248 @@
+//! Reserving the memory and threads of a proving job before accepting it.
+//!
+//! A service running several jobs on one machine can only bound their total memory if each job
+//! declares what it needs up front. [`SP1Prover::estimate_resources`] estimates the memory and
+//! threads of a job from its [`SP1ProvingPlan`], and [`SP1Prover::reserve`] takes them from the
+//! [`ReservationManager`] of the prover for as long as the returned [`Reservation`] is held.
+//! Reservations that cannot be satisfied wait in the order they were requested, so a large job is
+//! not starved by smaller ones.
+
+use std::{
+    collections::BTreeSet,
+    env,
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::utils::estimate_shard_memory;
+use sp1_stark::SP1ProverOpts;
+use thiserror::Error;
+
+use crate::{components::SP1ProverComponents, plan::SP1ProvingPlan, SP1Prover};
+
+/// The resources a proving job needs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceEstimate {
+    /// The peak memory of the job, in bytes.
+    pub memory_bytes: usize,
+    /// The number of worker threads of the job.
+    pub threads: usize,
+}
+
+/// The error returned when a reservation can never be satisfied.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("the job needs {requested:?}, but at most {capacity:?} can be reserved")]
+pub struct ReservationError {
+    pub requested: ResourceEstimate,
+    /// The resources of the manager, `usize::MAX` standing for unbounded.
+    pub capacity: ResourceEstimate,
+}
+
+/// Hands out the memory and threads of the machine to proving jobs.
+#[derive(Debug)]
+pub struct ReservationManager {
+    capacity: ResourceEstimate,
+    state: Mutex<ReservationState>,
+    released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct ReservationState {
+    reserved: ResourceEstimate,
+    next_ticket: u64,
+    /// The tickets of the reservations waiting, served in order.
+    waiting: BTreeSet<u64>,
+}
+
+impl ReservationManager {
+    /// A manager reserving at most `memory_bytes` of memory and `threads` threads. `None` does not
+    /// bound the corresponding resource.
+    #[must_use]
+    pub fn new(memory_bytes: Option<usize>, threads: Option<usize>) -> Self {
+        Self {
+            capacity: ResourceEstimate {
+                memory_bytes: memory_bytes.unwrap_or(usize::MAX),
+                threads: threads.unwrap_or(usize::MAX),
+            },
+            state: Mutex::default(),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Read the capacity from the `SP1_RESERVABLE_MEMORY_GB` and `SP1_RESERVABLE_THREADS`
+    /// environment variables. Resources are not bounded if they are not set.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let var = |var| env::var(var).ok().and_then(|s| s.parse::<usize>().ok());
+        Self::new(var("SP1_RESERVABLE_MEMORY_GB").map(|gb| gb << 30), var("SP1_RESERVABLE_THREADS"))
+    }
+
+    /// The resources reserved by the reservations held.
+    #[must_use]
+    pub fn reserved(&self) -> ResourceEstimate {
+        self.lock().reserved
+    }
+
+    /// The number of reservations waiting for resources to be released.
+    #[must_use]
+    pub fn num_waiting(&self) -> usize {
+        self.lock().waiting.len()
+    }
+
+    /// Wait until `estimate` can be reserved, after the reservations requested before it.
+    ///
+    /// Fails right away if `estimate` exceeds the capacity of the manager.
+    pub fn reserve(&self, estimate: ResourceEstimate) -> Result<Reservation<'_>, ReservationError> {
+        self.check_capacity(estimate)?;
+        let mut state = self.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.insert(ticket);
+        while state.waiting.first() != Some(&ticket) || !self.fits(&state, estimate) {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.waiting.remove(&ticket);
+        Ok(self.take(state, estimate))
+    }
+
+    /// Reserve `estimate` if it can be reserved right away, without waiting.
+    ///
+    /// Returns `Ok(None)` if other reservations are waiting or the resources are not free.
+    pub fn try_reserve(
+        &self,
+        estimate: ResourceEstimate,
+    ) -> Result<Option<Reservation<'_>>, ReservationError> {
+        self.check_capacity(estimate)?;
+        let state = self.lock();
+        if !state.waiting.is_empty() || !self.fits(&state, estimate) {
+            return Ok(None);
+        }
+        Ok(Some(self.take(state, estimate)))
+    }
+
+    fn check_capacity(&self, estimate: ResourceEstimate) -> Result<(), ReservationError> {
+        if estimate.memory_bytes > self.capacity.memory_bytes ||
+            estimate.threads > self.capacity.threads
+        {
+            return Err(ReservationError { requested: estimate, capacity: self.capacity });
+        }
+        Ok(())
+    }
+
+    fn fits(&self, state: &ReservationState, estimate: ResourceEstimate) -> bool {
+        state.reserved.memory_bytes.saturating_add(estimate.memory_bytes) <=
+            self.capacity.memory_bytes &&
+            state.reserved.threads.saturating_add(estimate.threads) <= self.capacity.threads
+    }
+
+    fn take(
+        &self,
+        mut state: MutexGuard<'_, ReservationState>,
+        estimate: ResourceEstimate,
+    ) -> Reservation<'_> {
+        state.reserved.memory_bytes =
+            state.reserved.memory_bytes.saturating_add(estimate.memory_bytes);
+        state.reserved.threads = state.reserved.threads.saturating_add(estimate.threads);
+        drop(state);
+        // The next waiter may fit as well.
+        self.released.notify_all();
+        Reservation { manager: self, estimate }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ReservationState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Resources reserved for a job, released when dropped.
+#[derive(Debug)]
+pub struct Reservation<'a> {
+    manager: &'a ReservationManager,
+    estimate: ResourceEstimate,
+}
+
+impl Reservation<'_> {
+    /// The resources reserved.
+    #[must_use]
+    pub fn estimate(&self) -> ResourceEstimate {
+        self.estimate
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut state = self.manager.lock();
+        state.reserved.memory_bytes -= self.estimate.memory_bytes;
+        state.reserved.threads -= self.estimate.threads;
+        drop(state);
+        self.manager.released.notify_all();
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Estimate the peak memory and threads of proving the job of `plan` with `opts`.
+    ///
+    /// The memory is that of the core shards held at once by the trace generation workers, the
+    /// records and traces channel and the prover, or the memory budget of `opts.core_opts` if
+    /// there is one. The recursion stages hold much smaller records, so the core stage bounds the
+    /// job.
+    #[must_use]
+    pub fn estimate_resources(
+        &self,
+        plan: &SP1ProvingPlan,
+        opts: &SP1ProverOpts,
+    ) -> ResourceEstimate {
+        let core_opts = &opts.core_opts;
+        let shard_bytes =
+            estimate_shard_memory(self.core_shape_config.as_ref(), core_opts.shard_size);
+        let (_, records_and_traces) = core_opts.channel_capacities(shard_bytes);
+        let in_flight = (records_and_traces + core_opts.trace_gen_workers + 1)
+            .saturating_mul(core_opts.shard_batch_size.max(1))
+            .min(plan.num_shards.max(1));
+        let memory_bytes = match core_opts.memory_budget {
+            Some(budget) => budget,
+            None => shard_bytes.saturating_mul(in_flight),
+        };
+
+        let recursion_opts = &opts.recursion_opts;
+        let threads = (core_opts.trace_gen_workers + core_opts.shard_batch_size)
+            .max(recursion_opts.trace_gen_workers + recursion_opts.shard_batch_size);
+        ResourceEstimate { memory_bytes, threads }
+    }
+
+    /// Reserve the resources of proving the job of `plan` with `opts` from the reservation
+    /// manager of the prover, waiting for them to be released by other jobs if needed.
+    ///
+    /// The reservation should be held until the job is proven.
+    pub fn reserve(
+        &self,
+        plan: &SP1ProvingPlan,
+        opts: &SP1ProverOpts,
+    ) -> Result<Reservation<'_>, ReservationError> {
+        let estimate = self.estimate_resources(plan, opts);
+        tracing::debug!("reserving {:?} for a job of {} shards", estimate, plan.num_shards);
+        self.reservations.reserve(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations() {
+        let manager = ReservationManager::new(Some(100), Some(8));
+        let estimate = |memory_bytes, threads| ResourceEstimate { memory_bytes, threads };
+
+        let first = manager.reserve(estimate(60, 4)).unwrap();
+        assert!(manager.try_reserve(estimate(60, 1)).unwrap().is_none());
+        let second = manager.try_reserve(estimate(40, 4)).unwrap().unwrap();
+        assert_eq!(manager.reserved(), estimate(100, 8));
+        assert!(manager.reserve(estimate(101, 1)).is_err());
+
+        drop(first);
+        assert_eq!(manager.reserved(), estimate(40, 4));
+        drop(second);
+        assert_eq!(manager.reserved(), ResourceEstimate::default());
+    }
+}
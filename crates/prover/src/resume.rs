@@ -0,0 +1,476 @@
+//! Resuming compress from its last completed layer.
+//!
+//! Compressing a large proof can take hours, and a crash loses every proof of the recursion tree.
+//! [`SP1Prover::compress_with_state`] persists the tree to a [`CompressState`] file each time one
+//! of its layers is completed, and [`SP1Prover::compress_resume`] reduces the proofs of the file
+//! instead of proving the tree again from the first layer. The file is sealed with the artifact
+//! cipher of the prover, if any.
+//!
+//! A state can also be reduced by several machines: [`CompressState::split`] divides its proofs
+//! into consecutive parts, each worker reduces its part to a single proof with
+//! [`SP1Prover::compress_part`], and [`CompressState::stitch`] puts the reduced parts back
+//! together into a state that [`SP1Prover::compress_resume`] reduces to the compressed proof.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use p3_baby_bear::BabyBear;
+use p3_field::AbstractField;
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::reduce::SP1ReduceProof;
+use sp1_recursion_circuit::machine::SP1CompressWitnessValues;
+use sp1_stark::{ShardProof, StarkVerifyingKey, DIGEST_SIZE};
+
+use crate::{
+    components::SP1ProverComponents,
+    encryption::{load_artifact, save_artifact, ArtifactCipher},
+    public_values::ReduceProofPublicValues,
+    HashableKey, InnerSC, SP1CircuitWitness, SP1CoreProof, SP1Prover, SP1ProverOpts,
+    SP1RecursionProverError, SP1VerifyingKey,
+};
+
+/// The version of the [`CompressState`] format.
+///
+/// This should be bumped whenever a field is added, removed or changes meaning.
+pub const COMPRESS_STATE_VERSION: u32 = 2;
+
+/// The proofs of the last completed layer of a recursion tree built by compress.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompressState {
+    /// The version of the format, see [`COMPRESS_STATE_VERSION`].
+    pub version: u32,
+    /// The last completed layer, the first layer being zero.
+    pub layer: usize,
+    /// The proofs reducing the first layer up to the layer, in order. Together they cover every
+    /// shard and deferred proof exactly once.
+    pub proofs: Vec<SP1ReduceProof<InnerSC>>,
+    /// The index of the part among the parts of a split state, zero for a whole state.
+    pub part: usize,
+    /// The number of parts the state was split into, one for a whole state.
+    pub num_parts: usize,
+}
+
+impl CompressState {
+    /// Whether the state covers the whole tree rather than a part of it.
+    #[must_use]
+    pub fn is_whole(&self) -> bool {
+        self.num_parts == 1
+    }
+
+    /// Split the proofs of a whole state into at most `num_parts` parts of consecutive proofs, to
+    /// be reduced separately by [`SP1Prover::compress_part`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is already a part of a split state.
+    #[must_use]
+    pub fn split(self, num_parts: usize) -> Vec<Self> {
+        assert!(self.is_whole(), "cannot split a part of a split state");
+        let chunk_size = self.proofs.len().div_ceil(num_parts.max(1)).max(1);
+        let num_parts = self.proofs.len().div_ceil(chunk_size);
+        let mut proofs = self.proofs.into_iter();
+        (0..num_parts)
+            .map(|part| Self {
+                version: self.version,
+                layer: self.layer,
+                proofs: proofs.by_ref().take(chunk_size).collect(),
+                part,
+                num_parts,
+            })
+            .collect()
+    }
+
+    /// Put the parts of a split state back together into a whole state, in the order of the
+    /// parts.
+    ///
+    /// Every part must be given exactly once. The layer of the state is the highest layer of the
+    /// parts.
+    pub fn stitch(parts: impl IntoIterator<Item = Self>) -> Result<Self, SP1RecursionProverError> {
+        let mut parts = parts.into_iter().collect::<Vec<_>>();
+        parts.sort_by_key(|part| part.part);
+        let num_parts = parts.first().map_or(0, |part| part.num_parts);
+        if num_parts == 0 ||
+            parts.len() != num_parts ||
+            parts
+                .iter()
+                .enumerate()
+                .any(|(i, part)| part.part != i || part.num_parts != num_parts)
+        {
+            return Err(SP1RecursionProverError::CheckpointMismatch(
+                "the parts of the compress state are missing or duplicated",
+            ));
+        }
+        Ok(Self {
+            version: COMPRESS_STATE_VERSION,
+            layer: parts.iter().map(|part| part.layer).max().unwrap_or(0),
+            proofs: parts.into_iter().flat_map(|part| part.proofs).collect(),
+            part: 0,
+            num_parts: 1,
+        })
+    }
+
+    /// Load the state from `path`, if it exists.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        Self::load_with(path, None)
+    }
+
+    /// Load the state written to `path` by [`CompressState::save_with`] with the same cipher, if
+    /// it exists.
+    pub fn load_with(path: &Path, cipher: Option<&ArtifactCipher>) -> io::Result<Option<Self>> {
+        let state: Self = match load_artifact(path, cipher) {
+            Ok(state) => state,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if state.version != COMPRESS_STATE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported compress state version {}", state.version),
+            ));
+        }
+        Ok(Some(state))
+    }
+
+    /// Save the state to `path` atomically, so that a crash leaves either the previous or the new
+    /// state.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.save_with(path, None)
+    }
+
+    /// Save the state like [`CompressState::save`], encrypted with `cipher` if one is given.
+    pub fn save_with(&self, path: &Path, cipher: Option<&ArtifactCipher>) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        save_artifact(self, &tmp, cipher)?;
+        fs::rename(tmp, path)
+    }
+}
+
+/// Persists the state of a recursion tree each time one of its layers is completed.
+pub(crate) struct StateWriter<'a> {
+    path: &'a Path,
+    cipher: Option<&'a ArtifactCipher>,
+    /// The layer the first layer of the tree is in the state, nonzero when resuming.
+    base_layer: usize,
+    /// The proofs not reduced yet, keyed by the first input they reduce.
+    unreduced: BTreeMap<usize, SP1ReduceProof<InnerSC>>,
+}
+
+impl<'a> StateWriter<'a> {
+    pub(crate) fn new(
+        path: &'a Path,
+        cipher: Option<&'a ArtifactCipher>,
+        base_layer: usize,
+    ) -> Self {
+        Self { path, cipher, base_layer, unreduced: BTreeMap::new() }
+    }
+
+    /// Record that the node reducing the inputs from `first_input` was proven, replacing the nodes
+    /// it joins.
+    pub(crate) fn record_proof(
+        &mut self,
+        first_input: usize,
+        joined: impl IntoIterator<Item = usize>,
+        vk: &StarkVerifyingKey<InnerSC>,
+        proof: &ShardProof<InnerSC>,
+    ) {
+        for input in joined {
+            self.unreduced.remove(&input);
+        }
+        self.unreduced.insert(first_input, SP1ReduceProof { vk: vk.clone(), proof: proof.clone() });
+    }
+
+    /// Persist the unreduced proofs once `layer` is completed.
+    pub(crate) fn layer_completed(&self, layer: usize) {
+        // The first layer of a resumed tree is the state it was resumed from.
+        if layer == 0 && self.base_layer > 0 {
+            return;
+        }
+        let state = CompressState {
+            version: COMPRESS_STATE_VERSION,
+            layer: self.base_layer + layer,
+            proofs: self.unreduced.values().cloned().collect(),
+            part: 0,
+            num_parts: 1,
+        };
+        match state.save_with(self.path, self.cipher) {
+            Ok(()) => tracing::debug!("persisted compress layer {}", state.layer),
+            Err(e) => tracing::warn!("failed to persist compress layer {}: {e}", state.layer),
+        }
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Reduce shard proofs to a single shard proof, see [`Self::compress`], persisting the
+    /// recursion tree to `state_path` each time one of its layers is completed.
+    ///
+    /// Any previous state at `state_path` is removed first. The state is left in place once the
+    /// proof is done.
+    pub fn compress_with_state(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+        state_path: &Path,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        match fs::remove_file(state_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(SP1RecursionProverError::IoError(e))
+            }
+            _ => {}
+        }
+        self.compress_inner(vk, proof, deferred_proofs, opts, state_path)
+    }
+
+    /// Resume compressing from the last layer persisted to `state_path` by
+    /// [`Self::compress_with_state`], or compress from scratch if there is no state.
+    ///
+    /// `proof` and `deferred_proofs` must be the ones the state was compressed from. They are only
+    /// used when starting from scratch.
+    pub fn compress_resume(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+        state_path: &Path,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        let Some(state) = CompressState::load_with(state_path, self.artifact_cipher.as_deref())
+            .map_err(SP1RecursionProverError::IoError)?
+        else {
+            return self.compress_inner(vk, proof, deferred_proofs, opts, state_path);
+        };
+        self.check_compress_state(vk, &state)?;
+        if !state.is_whole() {
+            return Err(SP1RecursionProverError::CheckpointMismatch(
+                "the compress state is a part of a split state",
+            ));
+        }
+
+        // A state always has several proofs, since the root is not persisted.
+        if state.proofs.len() < 2 {
+            return Err(SP1RecursionProverError::CheckpointMismatch(
+                "the compress state has fewer than two proofs",
+            ));
+        }
+        tracing::info!(
+            "resuming compress after layer {} with {} proofs",
+            state.layer,
+            state.proofs.len()
+        );
+
+        let num_inputs = state.proofs.len();
+        let inputs = compress_inputs(state.proofs);
+        let writer = StateWriter::new(state_path, self.artifact_cipher.as_deref(), state.layer);
+        let (vk, proof) = self.reduce_tree_with_recorder(
+            inputs,
+            num_inputs,
+            true,
+            opts,
+            &*self.reduction_strategy,
+            None,
+            Some(writer),
+        )?;
+        Ok(SP1ReduceProof { vk, proof })
+    }
+
+    /// Reduce the proofs of a part of a split state to a single proof, returning the reduced part
+    /// to be stitched back with the others by [`CompressState::stitch`].
+    ///
+    /// A part with a single proof is returned as is.
+    pub fn compress_part(
+        &self,
+        vk: &SP1VerifyingKey,
+        part: CompressState,
+        opts: SP1ProverOpts,
+    ) -> Result<CompressState, SP1RecursionProverError> {
+        self.check_compress_state(vk, &part)?;
+        let num_inputs = part.proofs.len();
+        if num_inputs < 2 {
+            return Ok(part);
+        }
+        let arity = self.try_compress_arity(&opts)?;
+        let height = self.reduction_strategy.plan(num_inputs, arity).height();
+        tracing::info!(
+            "reducing part {} of {} after layer {} with {num_inputs} proofs",
+            part.part,
+            part.num_parts,
+            part.layer
+        );
+
+        let inputs = compress_inputs(part.proofs);
+        let (vk, proof) = self.reduce_tree(inputs, num_inputs, false, opts)?;
+        Ok(CompressState {
+            version: COMPRESS_STATE_VERSION,
+            layer: part.layer + height,
+            proofs: vec![SP1ReduceProof { vk, proof }],
+            part: part.part,
+            num_parts: part.num_parts,
+        })
+    }
+
+    /// Check that the prover can reduce the proofs of `state`, made for the program of `vk`.
+    fn check_compress_state(
+        &self,
+        vk: &SP1VerifyingKey,
+        state: &CompressState,
+    ) -> Result<(), SP1RecursionProverError> {
+        self.check_cancelled()?;
+        self.check_vk_shape_config(vk)?;
+        self.check_vk_artifacts(vk)?;
+        self.check_installed_join_programs()?;
+        self.check_join_programs_precompiled()?;
+
+        if state.proofs.is_empty() {
+            return Err(SP1RecursionProverError::CheckpointMismatch(
+                "the compress state has no proofs",
+            ));
+        }
+        let vk_digest = vk.hash_babybear();
+        if state.proofs.iter().any(|proof| proof.sp1_vk_digest().ok() != Some(vk_digest)) {
+            return Err(SP1RecursionProverError::CheckpointMismatch(
+                "the compress state is for another program",
+            ));
+        }
+        Ok(())
+    }
+
+    fn compress_inner(
+        &self,
+        vk: &SP1VerifyingKey,
+        proof: SP1CoreProof,
+        deferred_proofs: Vec<SP1ReduceProof<InnerSC>>,
+        opts: SP1ProverOpts,
+        state_path: &Path,
+    ) -> Result<SP1ReduceProof<InnerSC>, SP1RecursionProverError> {
+        self.compress_with_recorder(
+            vk,
+            proof,
+            deferred_proofs,
+            [BabyBear::zero(); DIGEST_SIZE],
+            opts,
+            None,
+            Some(StateWriter::new(state_path, self.artifact_cipher.as_deref(), 0)),
+        )
+    }
+}
+
+/// The first layer inputs of a tree reducing `proofs`, which are already proven.
+pub(crate) fn compress_inputs(
+    proofs: Vec<SP1ReduceProof<InnerSC>>,
+) -> impl Iterator<Item = (SP1CircuitWitness, bool)> {
+    proofs.into_iter().map(|SP1ReduceProof { vk, proof }| {
+        let input =
+            SP1CompressWitnessValues { vks_and_proofs: vec![(vk, proof)], is_complete: false };
+        (SP1CircuitWitness::Compress(input), true)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_state_sealed() {
+        let path = std::env::temp_dir().join(format!("sp1-compress-state-{}", std::process::id()));
+        let cipher = ArtifactCipher::new([7; 32]);
+        let state = CompressState {
+            version: COMPRESS_STATE_VERSION,
+            layer: 3,
+            proofs: Vec::new(),
+            part: 0,
+            num_parts: 1,
+        };
+
+        state.save_with(&path, Some(&cipher)).unwrap();
+        let loaded = CompressState::load_with(&path, Some(&cipher)).unwrap().unwrap();
+        assert_eq!((loaded.layer, loaded.num_parts), (3, 1));
+        assert!(CompressState::load(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+        assert!(CompressState::load_with(&path, Some(&cipher)).unwrap().is_none());
+    }
+
+    /// A dummy compress proof tagged with `tag` in its first public value.
+    fn tagged_proof(tag: usize) -> SP1ReduceProof<InnerSC> {
+        use sp1_stark::{air::MachineAir, shape::OrderedShape};
+
+        use crate::CompressAir;
+
+        let machine = CompressAir::<BabyBear>::compress_machine(InnerSC::default());
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, mut proof) =
+            sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(&machine, &shape);
+        proof.public_values[0] = BabyBear::from_canonical_usize(tag);
+        SP1ReduceProof { vk, proof }
+    }
+
+    fn tags(proofs: &[SP1ReduceProof<InnerSC>]) -> Vec<BabyBear> {
+        proofs.iter().map(|p| p.proof.public_values[0]).collect()
+    }
+
+    #[test]
+    fn test_state_writer() {
+        let path = std::env::temp_dir().join(format!("sp1-state-writer-{}", std::process::id()));
+
+        // Three first layer proofs, the first two of which are joined.
+        let mut writer = StateWriter::new(&path, None, 0);
+        for i in 0..3 {
+            let SP1ReduceProof { vk, proof } = tagged_proof(i);
+            writer.record_proof(i, [], &vk, &proof);
+        }
+        writer.layer_completed(0);
+        assert_eq!(CompressState::load(&path).unwrap().unwrap().proofs.len(), 3);
+        let SP1ReduceProof { vk, proof } = tagged_proof(3);
+        writer.record_proof(0, [0, 1], &vk, &proof);
+        writer.layer_completed(1);
+        let state = CompressState::load(&path).unwrap().unwrap();
+        assert_eq!(state.layer, 1);
+        assert_eq!(tags(&state.proofs), [3, 2].map(BabyBear::from_canonical_usize));
+
+        // The first layer of a resumed tree is not persisted again.
+        let resumed = StateWriter::new(&path, None, 4);
+        resumed.layer_completed(0);
+        assert_eq!(CompressState::load(&path).unwrap().unwrap().layer, 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_split_and_stitch() {
+        let state = CompressState {
+            version: COMPRESS_STATE_VERSION,
+            layer: 1,
+            proofs: (0..5).map(tagged_proof).collect(),
+            part: 0,
+            num_parts: 1,
+        };
+
+        // Five proofs in at most three parts of consecutive proofs.
+        let mut parts = state.split(3);
+        assert_eq!(parts.iter().map(|part| part.proofs.len()).collect::<Vec<_>>(), [2, 2, 1]);
+        assert!(parts.iter().enumerate().all(|(i, part)| part.part == i && part.num_parts == 3));
+        assert!(!parts[0].is_whole());
+
+        // The parts are stitched in the order of their index, at the highest layer.
+        parts.swap(0, 2);
+        parts[1].layer = 3;
+        let stitched = CompressState::stitch(parts.clone()).unwrap();
+        assert!(stitched.is_whole());
+        assert_eq!(stitched.layer, 3);
+        assert_eq!(
+            tags(&stitched.proofs),
+            (0..5).map(BabyBear::from_canonical_usize).collect::<Vec<_>>()
+        );
+
+        parts.pop();
+        assert!(CompressState::stitch(parts.clone()).is_err());
+        parts.push(parts[0].clone());
+        assert!(CompressState::stitch(parts).is_err());
+        assert!(CompressState::stitch([]).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Rotating the allowed recursion verifying keys.
+//!
+//! Upgrading the shape set of a network changes the vk map, and so the vk root that recursion
+//! proofs commit to. Proofs made under the previous root are still in flight while the new release
+//! rolls out, so for a transition period both roots are accepted: [`SP1Prover::rotate_vk_map`]
+//! makes the root of a new release active and keeps the previous one as the retiring root, and
+//! [`SP1Prover::retire_vk_root`] drops the retiring root once the transition is over.
+//!
+//! New proofs are always made under the active root. A proof under the retiring root is verified,
+//! shrunk and wrapped with the Merkle proofs of the retiring vk map, and its public values keep
+//! committing to the root it was made under. It cannot be used as a deferred proof of a new proof,
+//! since every proof of a recursion tree commits to the same root.
+//!
+//! While a root is retiring, the wrap program accepts either root, so the wrap verifying key and
+//! the PLONK and Groth16 artifacts built for it differ from those of a single root.
+
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sp1_recursion_circuit::{hash::FieldHasher, merkle_tree::MerkleTree};
+
+use crate::{
+    components::SP1ProverComponents,
+    reload::{dummy_vk_map, ProverConfigBundle},
+    InnerSC, SP1Prover, SP1ProverConfigError,
+};
+
+type VkDigest = <InnerSC as FieldHasher<BabyBear>>::Digest;
+
+/// A vk map, with the Merkle tree committing to it.
+#[derive(Clone)]
+pub struct VkAllowlist {
+    /// The root of the Merkle tree.
+    pub root: VkDigest,
+    /// The allowed verifying keys and their indices in the Merkle tree.
+    pub map: BTreeMap<VkDigest, usize>,
+    pub tree: MerkleTree<BabyBear, InnerSC>,
+}
+
+/// Whether a prover accepts proofs committing to a vk root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VkRootStatus {
+    /// The root new proofs are made under.
+    Active,
+    /// The previous root, still accepted until it is retired.
+    Retiring,
+    /// The root of the dummy vk map, committed to by proofs made without vk verification. Only
+    /// provers in [permissive](crate::reload::VkVerificationMode::Permissive) mode accept it.
+    Unverified,
+    Unknown,
+}
+
+impl VkRootStatus {
+    /// Whether proofs committing to the root are accepted whatever the vk verification mode.
+    #[must_use]
+    pub fn is_accepted(self) -> bool {
+        matches!(self, Self::Active | Self::Retiring)
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Make the vk map of `config` active, keeping the current vk map as the retiring one.
+    ///
+    /// A vk map that was already retiring is dropped. If `config` commits to the active root, the
+    /// prover is reloaded without starting a rotation.
+    ///
+    /// Fails if the core of the prover is shared with other handles.
+    pub fn rotate_vk_map(
+        &mut self,
+        config: ProverConfigBundle,
+    ) -> Result<(), SP1ProverConfigError> {
+        let retiring = VkAllowlist {
+            root: self.recursion_vk_root,
+            map: self.recursion_vk_map.clone(),
+            tree: self.recursion_vk_tree.clone(),
+        };
+        self.reload(config)?;
+        if retiring.root == self.recursion_vk_root {
+            return Ok(());
+        }
+        tracing::info!("retiring vk root {:?}", retiring.root);
+        self.core_mut().ok_or(SP1ProverConfigError::SharedCore)?.retiring_vk_allowlist =
+            Some(retiring);
+        Ok(())
+    }
+
+    /// Stop accepting the retiring vk root, returning it if there was one.
+    ///
+    /// Fails if the core of the prover is shared with other handles.
+    pub fn retire_vk_root(&mut self) -> Result<Option<VkDigest>, SP1ProverConfigError> {
+        let core = self.core_mut().ok_or(SP1ProverConfigError::SharedCore)?;
+        let Some(retired) = core.retiring_vk_allowlist.take() else {
+            return Ok(None);
+        };
+        tracing::info!("retired vk root {:?}", retired.root);
+        core.wrap_program = OnceLock::new();
+        core.wrap_vk = OnceLock::new();
+        Ok(Some(retired.root))
+    }
+
+    /// The retiring vk root, if a rotation is in progress.
+    #[must_use]
+    pub fn retiring_vk_root(&self) -> Option<VkDigest> {
+        self.retiring_vk_allowlist.as_ref().map(|allowlist| allowlist.root)
+    }
+
+    /// The vk roots proofs are accepted under, the active one first.
+    #[must_use]
+    pub fn accepted_vk_roots(&self) -> Vec<VkDigest> {
+        std::iter::once(self.recursion_vk_root).chain(self.retiring_vk_root()).collect()
+    }
+
+    #[must_use]
+    pub fn vk_root_status(&self, vk_root: &VkDigest) -> VkRootStatus {
+        if *vk_root == self.recursion_vk_root {
+            VkRootStatus::Active
+        } else if self.retiring_vk_root().as_ref() == Some(vk_root) {
+            VkRootStatus::Retiring
+        } else if *vk_root == self.dummy_vk_root() {
+            VkRootStatus::Unverified
+        } else {
+            VkRootStatus::Unknown
+        }
+    }
+
+    /// The root of the dummy vk map, with the Merkle tree configuration of the prover.
+    pub fn dummy_vk_root(&self) -> VkDigest {
+        if !self.vk_verification.checks_vks() {
+            return self.recursion_vk_root;
+        }
+        *self.dummy_vk_root.get_or_init(|| {
+            let leaves = dummy_vk_map().into_keys().collect();
+            MerkleTree::<BabyBear, InnerSC>::commit_with_config(
+                leaves,
+                self.recursion_vk_tree.config,
+            )
+            .0
+        })
+    }
+
+    /// Whether the verifying key with digest `vk_digest` is allowed under `vk_root`.
+    ///
+    /// Every verifying key is allowed without vk verification, and under the dummy vk root.
+    #[must_use]
+    pub fn is_vk_allowed_under(&self, vk_root: &VkDigest, vk_digest: &VkDigest) -> bool {
+        !self.vk_verification.checks_vks() ||
+            self.vk_root_status(vk_root) == VkRootStatus::Unverified ||
+            self.vk_allowlist(vk_root).1.contains_key(vk_digest)
+    }
+
+    /// The root, vk map and Merkle tree of the retiring vk map if `vk_root` is the retiring root,
+    /// and of the active one otherwise.
+    pub(crate) fn vk_allowlist(
+        &self,
+        vk_root: &VkDigest,
+    ) -> (&VkDigest, &BTreeMap<VkDigest, usize>, &MerkleTree<BabyBear, InnerSC>) {
+        match &self.retiring_vk_allowlist {
+            Some(retiring) if retiring.root == *vk_root => {
+                (&retiring.root, &retiring.map, &retiring.tree)
+            }
+            _ => (&self.recursion_vk_root, &self.recursion_vk_map, &self.recursion_vk_tree),
+        }
+    }
+}
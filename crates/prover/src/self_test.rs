@@ -0,0 +1,258 @@
+//! A self-test of the proving pipeline.
+//!
+//! [`SP1Prover::self_test`] proves a tiny program embedded in the prover through the stages of a
+//! [`SelfTestLevel`] and verifies every proof, so that an installation can be checked without
+//! writing, building and proving a program of one's own.
+
+use std::{
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::SP1Context;
+use sp1_core_machine::io::SP1Stdin;
+use sp1_stark::SP1ProverOpts;
+
+use crate::{
+    build::try_build_groth16_bn254_artifacts_dev, components::SP1ProverComponents, GnarkLimits,
+    SP1Prover, SP1_CIRCUIT_VERSION,
+};
+
+/// The program proven by the self-test, which computes a Fibonacci number.
+const SELF_TEST_ELF: &[u8] = include_bytes!("../elf/riscv32im-succinct-zkvm-elf");
+
+/// The input of [`SELF_TEST_ELF`].
+const SELF_TEST_INPUT: u32 = 20;
+
+/// How far the self-test goes through the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SelfTestLevel {
+    /// Prove and verify the core proof.
+    CoreOnly,
+    /// Also compress the core proof.
+    Compress,
+    /// Also shrink and wrap the compressed proof, without proving the wrapped proof with gnark.
+    WrapMock,
+    /// Also prove the wrapped proof with Groth16, using development circuit artifacts built for
+    /// the occasion.
+    Full,
+}
+
+impl SelfTestLevel {
+    /// The stages run at this level, in order.
+    #[must_use]
+    pub fn stages(self) -> &'static [SelfTestStage] {
+        use SelfTestStage::*;
+        match self {
+            Self::CoreOnly => &[Setup, Core],
+            Self::Compress => &[Setup, Core, Compress],
+            Self::WrapMock => &[Setup, Core, Compress, Shrink, Wrap],
+            Self::Full => &[Setup, Core, Compress, Shrink, Wrap, Groth16],
+        }
+    }
+}
+
+/// A stage of the self-test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SelfTestStage {
+    Setup,
+    Core,
+    Compress,
+    Shrink,
+    Wrap,
+    Groth16,
+}
+
+/// The outcome of a stage of the self-test.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelfTestStageReport {
+    pub stage: SelfTestStage,
+    /// The time taken to prove and verify.
+    pub seconds: f64,
+    /// Why the stage failed, or `None` if it passed.
+    pub error: Option<String>,
+}
+
+/// The outcome of a self-test.
+///
+/// The stages after a failed one are not run, so a report only lists the stages up to the first
+/// failure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub circuit_version: String,
+    pub level: SelfTestLevel,
+    pub stages: Vec<SelfTestStageReport>,
+}
+
+impl SelfTestReport {
+    /// Whether every stage of the level ran and passed.
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.stages.len() == self.level.stages().len() &&
+            self.stages.iter().all(|stage| stage.error.is_none())
+    }
+
+    /// The stage that failed, if any.
+    #[must_use]
+    pub fn failure(&self) -> Option<&SelfTestStageReport> {
+        self.stages.iter().find(|stage| stage.error.is_some())
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.passed() { "passed" } else { "failed" };
+        writeln!(f, "sp1 self-test {:?} ({}): {outcome}", self.level, self.circuit_version)?;
+        for stage in &self.stages {
+            match &stage.error {
+                None => writeln!(f, "  {:?}: ok in {:.2}s", stage.stage, stage.seconds)?,
+                Some(e) => {
+                    writeln!(f, "  {:?}: failed in {:.2}s: {e}", stage.stage, stage.seconds)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run a stage, recording its outcome in `stages`.
+///
+/// Panics are caught and reported as failures, since several stages of the prover panic rather
+/// than return an error.
+fn run_stage<T>(
+    stage: SelfTestStage,
+    stages: &mut Vec<SelfTestStageReport>,
+    f: impl FnOnce() -> Result<T, String>,
+) -> Option<T> {
+    let start = Instant::now();
+    let result = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        Err(panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(ToString::to_string))
+            .unwrap_or_else(|| "panicked".to_string()))
+    });
+    let seconds = start.elapsed().as_secs_f64();
+    match result {
+        Ok(output) => {
+            stages.push(SelfTestStageReport { stage, seconds, error: None });
+            Some(output)
+        }
+        Err(e) => {
+            tracing::error!("self-test stage {stage:?} failed: {e}");
+            stages.push(SelfTestStageReport { stage, seconds, error: Some(e) });
+            None
+        }
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Prove a tiny embedded program through the stages of `level`, verifying every proof.
+    ///
+    /// Failures are recorded in the report rather than returned, see [`SelfTestReport::passed`].
+    pub fn self_test(&self, level: SelfTestLevel) -> SelfTestReport {
+        let mut report = SelfTestReport {
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            level,
+            stages: Vec::new(),
+        };
+        self.run_self_test(level, &mut report.stages);
+        tracing::info!("{report}");
+        report
+    }
+
+    fn run_self_test(&self, level: SelfTestLevel, stages: &mut Vec<SelfTestStageReport>) {
+        let opts = SP1ProverOpts::auto();
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&SELF_TEST_INPUT);
+        let stages_of_level = level.stages();
+
+        let Some((pk, program, vk)) = run_stage(SelfTestStage::Setup, stages, || {
+            let (_, pk, program, vk) = self.setup(SELF_TEST_ELF);
+            Ok((pk, program, vk))
+        }) else {
+            return;
+        };
+
+        let Some(core_proof) = run_stage(SelfTestStage::Core, stages, || {
+            let proof = self
+                .prove_core(&pk, program, &stdin, opts.clone(), SP1Context::default())
+                .map_err(|e| e.to_string())?;
+            self.verify(&proof.proof, &vk).map_err(|e| e.to_string())?;
+            Ok(proof)
+        }) else {
+            return;
+        };
+        let public_values = core_proof.public_values.clone();
+        if !stages_of_level.contains(&SelfTestStage::Compress) {
+            return;
+        }
+
+        let Some(compressed) = run_stage(SelfTestStage::Compress, stages, || {
+            let proof =
+                self.compress(&vk, core_proof, vec![], opts.clone()).map_err(|e| e.to_string())?;
+            self.verify_compressed(&proof, &vk).map_err(|e| e.to_string())?;
+            Ok(proof)
+        }) else {
+            return;
+        };
+        if !stages_of_level.contains(&SelfTestStage::Shrink) {
+            return;
+        }
+
+        let Some(shrunk) = run_stage(SelfTestStage::Shrink, stages, || {
+            let proof = self.shrink(compressed, opts.clone()).map_err(|e| e.to_string())?;
+            self.verify_shrink(&proof, &vk).map_err(|e| e.to_string())?;
+            Ok(proof)
+        }) else {
+            return;
+        };
+
+        let Some(wrapped) = run_stage(SelfTestStage::Wrap, stages, || {
+            let proof = self.wrap_bn254(shrunk, opts).map_err(|e| e.to_string())?;
+            self.verify_wrap_bn254(&proof, &vk).map_err(|e| e.to_string())?;
+            Ok(proof)
+        }) else {
+            return;
+        };
+        if !stages_of_level.contains(&SelfTestStage::Groth16) {
+            return;
+        }
+
+        run_stage(SelfTestStage::Groth16, stages, || {
+            let build_dir = try_build_groth16_bn254_artifacts_dev(&wrapped.vk, &wrapped.proof);
+            let proof = self
+                .try_wrap_groth16_bn254(wrapped, &build_dir, &GnarkLimits::from_env())
+                .map_err(|e| e.to_string())?;
+            self.verify_groth16_bn254(&proof, &vk, &public_values, &build_dir)
+                .map_err(|e| e.to_string())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_report() {
+        let stage = |stage, error: Option<&str>| SelfTestStageReport {
+            stage,
+            seconds: 0.0,
+            error: error.map(ToString::to_string),
+        };
+        let mut report = SelfTestReport {
+            circuit_version: SP1_CIRCUIT_VERSION.to_string(),
+            level: SelfTestLevel::Compress,
+            stages: vec![stage(SelfTestStage::Setup, None), stage(SelfTestStage::Core, None)],
+        };
+        assert!(!report.passed());
+        report.stages.push(stage(SelfTestStage::Compress, None));
+        assert!(report.passed());
+        report.stages[2].error = Some("invalid proof".to_string());
+        assert!(!report.passed());
+        assert_eq!(report.failure().map(|stage| stage.stage), Some(SelfTestStage::Compress));
+    }
+}
@@ -0,0 +1,103 @@
+//! Caching of proving keys across processes.
+//!
+//! [`SP1Prover::setup`] commits to the preprocessed traces of the program, which takes a while for
+//! large programs, and a restarted process pays it again. Device keys cannot be persisted, since
+//! they live in the memory of the device, but they are made from the host keys, which can. A
+//! [`SetupCache`] stores the host proving key of a program keyed by the digest of its ELF and of
+//! the configuration of the prover, so that [`SP1Prover::setup_cached`] only copies the cached key
+//! to the device.
+
+use std::{io, path::PathBuf};
+
+use sha2::{Digest, Sha256};
+use sp1_core_executor::Program;
+use sp1_stark::MachineProver;
+
+use crate::{
+    components::SP1ProverComponents,
+    encryption::ArtifactCipher,
+    wrap_cache::{CacheEntries, DiskWrapProofStore, WrapProofStore},
+    DeviceProvingKey, SP1Prover, SP1ProvingKey, SP1VerifyingKey, SP1_CIRCUIT_VERSION,
+};
+
+/// A cache of proving keys, keyed by the ELF of their program and the configuration of the prover.
+pub struct SetupCache {
+    entries: CacheEntries,
+}
+
+impl SetupCache {
+    pub fn new(store: impl WrapProofStore + 'static) -> Self {
+        Self { entries: CacheEntries::new("setup cache", store) }
+    }
+
+    /// Encrypt the entries of the cache with `cipher`.
+    ///
+    /// Entries written without the cipher, or with another key, are treated as misses.
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: ArtifactCipher) -> Self {
+        self.entries.cipher = Some(cipher);
+        self
+    }
+
+    /// A cache backed by the files in `dir`.
+    pub fn on_disk(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        Ok(Self::new(DiskWrapProofStore::new(dir)?))
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The key of the proving key of `elf` in a [`SetupCache`].
+    ///
+    /// This is the SHA-256 digest of the ELF, the circuit version, the core shape configuration
+    /// and the core FRI parameters, which together determine the proving key.
+    pub fn setup_cache_key(&self, elf: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(elf);
+        hasher.update(SP1_CIRCUIT_VERSION.as_bytes());
+        if let Some(config) = &self.core_shape_config {
+            hasher.update(config.digest());
+        }
+        let fri_params = self.fri_opts().core;
+        for value in [fri_params.log_blowup, fri_params.num_queries, fri_params.proof_of_work_bits]
+        {
+            hasher.update((value as u64).to_le_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Like [`SP1Prover::setup`], reusing the proving key of `elf` in `cache` if there is one and
+    /// storing it otherwise.
+    ///
+    /// Only the host key is cached, so a hit still copies it to the device.
+    pub fn setup_cached(
+        &self,
+        elf: &[u8],
+        cache: &SetupCache,
+    ) -> eyre::Result<(SP1ProvingKey, DeviceProvingKey<C>, Program, SP1VerifyingKey)> {
+        let key = self.setup_cache_key(elf);
+        if let Some(pk) = cache.entries.load::<SP1ProvingKey>(&key) {
+            match self.check_cached_pk(elf, &pk) {
+                Ok(()) => {
+                    tracing::info!("reusing cached proving key {}", key);
+                    let program = self.get_program(elf)?;
+                    let pk_d = self.core_prover.pk_to_device(&pk.pk);
+                    let vk = pk.vk.clone();
+                    return Ok((pk, pk_d, program, vk));
+                }
+                Err(e) => tracing::warn!("discarding invalid setup cache entry {}: {}", key, e),
+            }
+        }
+
+        let (pk, pk_d, program, vk) = self.setup(elf);
+        cache.entries.store(&key, &pk);
+        Ok((pk, pk_d, program, vk))
+    }
+
+    /// Check that a cached proving key was set up for `elf` by a prover like this one.
+    fn check_cached_pk(&self, elf: &[u8], pk: &SP1ProvingKey) -> eyre::Result<()> {
+        eyre::ensure!(pk.elf == elf, "the key was set up for another program");
+        self.check_vk_shape_config(&pk.vk)?;
+        self.check_vk_artifacts(&pk.vk)?;
+        Ok(())
+    }
+}
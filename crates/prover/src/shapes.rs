@@ -12,8 +12,11 @@ use p3_baby_bear::BabyBear;
 use p3_field::AbstractField;
 use serde::{Deserialize, Serialize};
 use sp1_core_machine::shape::CoreShapeConfig;
-use sp1_recursion_circuit::machine::{
-    SP1CompressWithVKeyWitnessValues, SP1DeferredWitnessValues, SP1RecursionWitnessValues,
+use sp1_recursion_circuit::{
+    machine::{
+        SP1CompressWithVKeyWitnessValues, SP1DeferredWitnessValues, SP1RecursionWitnessValues,
+    },
+    merkle_tree::MerkleTreeConfig,
 };
 use sp1_recursion_core::{
     shape::{RecursionShape, RecursionShapeConfig},
@@ -26,7 +29,10 @@ pub use sp1_recursion_circuit::machine::{
     SP1CompressWithVkeyShape, SP1DeferredShape, SP1RecursionShape,
 };
 
-use crate::{components::SP1ProverComponents, CompressAir, HashableKey, SP1Prover, ShrinkAir};
+use crate::{
+    components::SP1ProverComponents, reload::VkVerificationMode, CompressAir, HashableKey,
+    SP1Prover, ShrinkAir,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum SP1ProofShape {
@@ -60,6 +66,10 @@ pub enum VkBuildError {
     Bincode(#[from] bincode::Error),
 }
 
+/// Check that the program of every maximal shape can be compiled.
+///
+/// Panics if the core of `prover` is shared with other handles, since its join programs are
+/// dropped to compile them again.
 pub fn check_shapes<C: SP1ProverComponents>(
     reduce_batch_size: usize,
     no_precompiles: bool,
@@ -86,10 +96,15 @@ pub fn check_shapes<C: SP1ProverComponents>(
     tracing::debug!("number of shapes: {}", num_shapes);
 
     // The Merkle tree height.
-    let height = num_shapes.next_power_of_two().ilog2() as usize;
+    let merkle_config = prover.recursion_vk_tree.config;
+    let height = merkle_config.height(num_shapes);
 
     // Empty the join program map so that we recompute the join program.
-    prover.join_programs_map.clear();
+    prover
+        .core_mut()
+        .expect("cannot check the shapes of a prover core shared with other handles")
+        .join_programs_map
+        .clear();
 
     let compress_ok = std::thread::scope(|s| {
         // Initialize compiler workers.
@@ -122,7 +137,11 @@ pub fn check_shapes<C: SP1ProverComponents>(
         // Generate shapes and send them to the compiler workers.
         all_maximal_shapes.into_iter().for_each(|program_shape| {
             shape_tx
-                .send(SP1CompressProgramShape::from_proof_shape(program_shape, height))
+                .send(SP1CompressProgramShape::from_proof_shape(
+                    program_shape,
+                    height,
+                    merkle_config,
+                ))
                 .unwrap();
         });
 
@@ -145,9 +164,11 @@ pub fn build_vk_map<C: SP1ProverComponents + 'static>(
 ) -> (BTreeSet<[BabyBear; DIGEST_SIZE]>, Vec<usize>, usize) {
     // Setup the prover.
     let mut prover = SP1Prover::<C>::new();
-    prover.vk_verification = !dummy;
+    let core = prover.core_mut().expect("a new prover does not share its core");
+    core.vk_verification =
+        if dummy { VkVerificationMode::Disabled } else { VkVerificationMode::Enforced };
     if !dummy {
-        prover.join_programs_map.clear();
+        core.join_programs_map.clear();
     }
     let prover = Arc::new(prover);
 
@@ -156,6 +177,7 @@ pub fn build_vk_map<C: SP1ProverComponents + 'static>(
     let recursion_shape_config =
         prover.compress_shape_config.as_ref().expect("recursion shape config not found");
 
+    let merkle_config = prover.recursion_vk_tree.config;
     let (vk_set, panic_indices, height) = if dummy {
         tracing::warn!("building a dummy vk map");
         let dummy_set = SP1ProofShape::dummy_vk_map(
@@ -165,7 +187,7 @@ pub fn build_vk_map<C: SP1ProverComponents + 'static>(
         )
         .into_keys()
         .collect::<BTreeSet<_>>();
-        let height = dummy_set.len().next_power_of_two().ilog2() as usize;
+        let height = merkle_config.height(dummy_set.len());
         (dummy_set, vec![], height)
     } else {
         tracing::debug!("building vk map");
@@ -195,7 +217,7 @@ pub fn build_vk_map<C: SP1ProverComponents + 'static>(
         let num_shapes = all_shapes.len();
         tracing::debug!("number of shapes: {} in {:?}", num_shapes, start.elapsed());
 
-        let height = num_shapes.next_power_of_two().ilog2() as usize;
+        let height = merkle_config.height(num_shapes);
         let chunk_size = indices_set.as_ref().map(|indices| indices.len()).unwrap_or(num_shapes);
 
         std::thread::scope(|s| {
@@ -281,7 +303,9 @@ pub fn build_vk_map<C: SP1ProverComponents + 'static>(
             subset_shapes
                 .clone()
                 .into_iter()
-                .map(|(i, shape)| (i, SP1CompressProgramShape::from_proof_shape(shape, height)))
+                .map(|(i, shape)| {
+                    (i, SP1CompressProgramShape::from_proof_shape(shape, height, merkle_config))
+                })
                 .for_each(|(i, program_shape)| {
                     shape_tx.send((i, program_shape)).unwrap();
                 });
@@ -417,19 +441,23 @@ impl SP1ProofShape {
 }
 
 impl SP1CompressProgramShape {
-    pub fn from_proof_shape(shape: SP1ProofShape, height: usize) -> Self {
+    /// The shape of the program for `shape`, verifying proofs of a vk Merkle tree of the given
+    /// height and configuration.
+    pub fn from_proof_shape(shape: SP1ProofShape, height: usize, config: MerkleTreeConfig) -> Self {
         match shape {
             SP1ProofShape::Recursion(proof_shape) => Self::Recursion(proof_shape.into()),
             SP1ProofShape::Deferred(proof_shape) => {
-                Self::Deferred(SP1DeferredShape::new(vec![proof_shape].into(), height))
+                Self::Deferred(SP1DeferredShape::new(vec![proof_shape].into(), height, config))
             }
             SP1ProofShape::Compress(proof_shapes) => Self::Compress(SP1CompressWithVkeyShape {
                 compress_shape: proof_shapes.into(),
                 merkle_tree_height: height,
+                merkle_tree_config: config,
             }),
             SP1ProofShape::Shrink(proof_shape) => Self::Shrink(SP1CompressWithVkeyShape {
                 compress_shape: vec![proof_shape].into(),
                 merkle_tree_height: height,
+                merkle_tree_config: config,
             }),
         }
     }
@@ -453,7 +481,8 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             SP1CompressProgramShape::Compress(shape) => {
                 let input =
                     SP1CompressWithVKeyWitnessValues::dummy(self.compress_prover.machine(), &shape);
-                self.compress_program(&input)
+                self.cached_compress_program(&input.shape())
+                    .unwrap_or_else(|| self.compile_compress_program(&input))
             }
             SP1CompressProgramShape::Shrink(shape) => {
                 let input =
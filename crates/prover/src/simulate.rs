@@ -0,0 +1,275 @@
+//! Simulating the proving pipeline without cryptography.
+//!
+//! Proving a large program takes hours, which makes testing the orchestration around the prover,
+//! such as distributed coordination and cache policies, slow. [`SP1Prover::simulate`] runs the
+//! shape and flow logic of the pipeline in seconds instead: it plans the shards as
+//! [`SP1Prover::plan`] does, builds the recursion tree, looks the programs of the first layer up in
+//! the program cache, and schedules the tree on the recursion workers, producing stub proofs whose
+//! timings come from a [`PlanCostModel`].
+//!
+//! The simulation does not compile, execute or prove any recursion program, and it does not change
+//! the program cache of the prover.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use sp1_core_machine::io::SP1Stdin;
+use sp1_recursion_circuit::machine::SP1RecursionShape;
+use sp1_stark::{shape::OrderedShape, SP1ProverOpts};
+
+use crate::{
+    components::SP1ProverComponents,
+    lock_or_reset,
+    plan::{compress_tree_layer_sizes, PlanCostModel, PlanStage, SP1PlanError, SP1ProvingPlan},
+    SP1Prover,
+};
+
+/// A stub for a proof the pipeline would generate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulatedProof {
+    /// The stage generating the proof.
+    pub stage: PlanStage,
+    /// The layer of the recursion tree, or zero outside of the compress stage.
+    pub layer: usize,
+    /// The index of the proof within its layer or stage.
+    pub index: usize,
+    /// The indices of the proofs of the previous layer the proof reduces.
+    pub inputs: Vec<usize>,
+    /// Whether the program of the proof was found in the program cache, for the core shards of
+    /// the first layer.
+    pub cache_hit: Option<bool>,
+    /// When the proof would start being generated, in milliseconds from the start of the
+    /// pipeline.
+    pub start_ms: u64,
+    /// When the proof would be done, in milliseconds from the start of the pipeline.
+    pub end_ms: u64,
+}
+
+/// The outcome of a simulated run of the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    /// The plan the simulation followed.
+    pub plan: SP1ProvingPlan,
+    /// The stub proofs, in the order they are done.
+    pub proofs: Vec<SimulatedProof>,
+    /// The number of core shards whose lift program was in the program cache.
+    pub lift_cache_hits: usize,
+    /// The number of core shards whose lift program would have been compiled.
+    pub lift_cache_misses: usize,
+    /// The simulated duration of the whole pipeline, in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl SimulationReport {
+    /// The simulated duration of the whole pipeline.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Simulate proving a program and input with the default [`PlanCostModel`].
+    pub fn simulate(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+    ) -> Result<SimulationReport, SP1PlanError> {
+        self.simulate_with_cost_model(elf, stdin, opts, &PlanCostModel::default())
+    }
+
+    /// Simulate proving a program and input, with stage durations from `costs`.
+    pub fn simulate_with_cost_model(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        costs: &PlanCostModel,
+    ) -> Result<SimulationReport, SP1PlanError> {
+        let plan = self.plan_with_cost_model(elf, stdin, opts.clone(), costs)?;
+        Ok(self.simulate_plan(plan, &opts, costs))
+    }
+
+    /// Simulate the pipeline of an existing plan, with stage durations from `costs`.
+    #[must_use]
+    pub fn simulate_plan(
+        &self,
+        plan: SP1ProvingPlan,
+        opts: &SP1ProverOpts,
+        costs: &PlanCostModel,
+    ) -> SimulationReport {
+        let ms = |duration: Duration| duration.as_millis() as u64;
+        let mut proofs = Vec::new();
+
+        // The core shards are proven one after the other.
+        for index in 0..plan.num_shards {
+            proofs.push(SimulatedProof {
+                stage: PlanStage::Core,
+                layer: 0,
+                index,
+                inputs: Vec::new(),
+                cache_hit: None,
+                start_ms: ms(costs.core_shard) * index as u64,
+                end_ms: ms(costs.core_shard) * (index as u64 + 1),
+            });
+        }
+        let core_end = proofs.last().map_or(0, |proof| proof.end_ms);
+
+        // Look the lift programs of the shards up in a copy of the program cache. The shapes of the
+        // shards are the ones they are expected to be fixed to, so this is only exact with fixed
+        // core shapes.
+        let is_complete = plan.num_shards == 1 && plan.recursion.num_deferred_proofs == 0;
+        let mut lift_cache = {
+            let cache = lock_or_reset(&self.lift_programs_lru, LruCache::clear);
+            let mut copy = LruCache::new(cache.cap());
+            for (shape, _) in cache.iter().rev() {
+                copy.put(shape.clone(), ());
+            }
+            copy
+        };
+        let cache_hits = plan
+            .shard_shapes
+            .iter()
+            .map(|shape| {
+                let shape = SP1RecursionShape {
+                    proof_shapes: vec![shape.clone().into_iter().collect::<OrderedShape>()],
+                    is_complete,
+                };
+                let hit = lift_cache.get(&shape).is_some();
+                lift_cache.put(shape, ());
+                hit
+            })
+            .collect::<Vec<_>>();
+        let lift_cache_hits = cache_hits.iter().filter(|hit| **hit).count();
+        let lift_cache_misses = cache_hits.len() - lift_cache_hits;
+
+        // Schedule the recursion tree on the proving workers. Each layer starts with the deferred
+        // batches, followed by the core shards, and every node waits for its inputs and for a free
+        // worker. A lone input at the end of a layer is passed through without being proven.
+        let batch_size = plan.recursion.batch_size;
+        let num_workers =
+            NonZeroUsize::new(opts.recursion_opts.shard_batch_size).map_or(1, NonZeroUsize::get);
+        let mut workers = vec![core_end; num_workers];
+        let mut schedule = |ready: u64| {
+            let worker = workers.iter_mut().min().unwrap();
+            let start = ready.max(*worker);
+            *worker = start + ms(costs.compress_node);
+            (start, *worker)
+        };
+
+        let layer_sizes =
+            compress_tree_layer_sizes(plan.recursion.num_first_layer_inputs, batch_size);
+        let num_deferred_batches = plan.recursion.num_deferred_batches;
+        let mut layer_ends = Vec::with_capacity(layer_sizes[0]);
+        for index in 0..layer_sizes[0] {
+            let (start_ms, end_ms) = schedule(core_end);
+            let cache_hit = index.checked_sub(num_deferred_batches).map(|i| cache_hits.get(i));
+            proofs.push(SimulatedProof {
+                stage: PlanStage::Compress,
+                layer: 0,
+                index,
+                inputs: Vec::new(),
+                cache_hit: cache_hit.flatten().copied(),
+                start_ms,
+                end_ms,
+            });
+            layer_ends.push(end_ms);
+        }
+        for (layer, &size) in layer_sizes.iter().enumerate().skip(1) {
+            let mut ends = Vec::with_capacity(size);
+            for (index, chunk) in layer_ends.chunks(batch_size).enumerate() {
+                let ready = chunk.iter().copied().max().unwrap();
+                if chunk.len() == 1 {
+                    ends.push(ready);
+                    continue;
+                }
+                let (start_ms, end_ms) = schedule(ready);
+                proofs.push(SimulatedProof {
+                    stage: PlanStage::Compress,
+                    layer,
+                    index,
+                    inputs: (index * batch_size..index * batch_size + chunk.len()).collect(),
+                    cache_hit: None,
+                    start_ms,
+                    end_ms,
+                });
+                ends.push(end_ms);
+            }
+            layer_ends = ends;
+        }
+
+        // The remaining stages each generate a single proof from the previous one.
+        let mut end = layer_ends.iter().copied().max().unwrap_or(core_end);
+        for (stage, duration) in [
+            (PlanStage::Shrink, costs.shrink),
+            (PlanStage::WrapBn254, costs.wrap_bn254),
+            (PlanStage::Snark, costs.snark),
+        ] {
+            proofs.push(SimulatedProof {
+                stage,
+                layer: 0,
+                index: 0,
+                inputs: vec![0],
+                cache_hit: None,
+                start_ms: end,
+                end_ms: end + ms(duration),
+            });
+            end += ms(duration);
+        }
+
+        proofs.sort_by_key(|proof| proof.end_ms);
+        let report =
+            SimulationReport { plan, proofs, lift_cache_hits, lift_cache_misses, duration_ms: end };
+        tracing::info!(
+            "simulated pipeline: {} proofs, {} lift cache misses, ~{:?}",
+            report.proofs.len(),
+            report.lift_cache_misses,
+            report.duration()
+        );
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{plan::PROVING_PLAN_VERSION, tests::unfixed_prover};
+
+    #[test]
+    fn test_simulate_plan() {
+        let prover = unfixed_prover();
+        let opts = SP1ProverOpts::default();
+        let costs = PlanCostModel::default();
+        let num_shards = 3;
+        let plan = SP1ProvingPlan {
+            version: PROVING_PLAN_VERSION,
+            cycles: 0,
+            num_shards,
+            shard_shapes: vec![BTreeMap::from([("Cpu".to_string(), 16)]); num_shards],
+            recursion: prover.recursion_tree_plan(num_shards, &[], &opts).unwrap(),
+            stages: Vec::new(),
+        };
+        let report = prover.simulate_plan(plan, &opts, &costs);
+
+        // The shards share a shape, so only the first one misses the program cache.
+        assert_eq!((report.lift_cache_hits, report.lift_cache_misses), (2, 1));
+        let core = report.proofs.iter().filter(|proof| proof.stage == PlanStage::Core);
+        assert_eq!(core.count(), num_shards);
+        assert!(report.proofs.windows(2).all(|w| w[0].end_ms <= w[1].end_ms));
+
+        // The pipeline ends with the SNARK, after at least the sequential stages.
+        let last = report.proofs.last().unwrap();
+        assert_eq!(last.stage, PlanStage::Snark);
+        assert_eq!(last.end_ms, report.duration_ms);
+        let sequential = costs.core_shard * num_shards as u32 +
+            costs.compress_node * 2 +
+            costs.shrink +
+            costs.wrap_bn254 +
+            costs.snark;
+        assert!(report.duration() >= sequential);
+    }
+}
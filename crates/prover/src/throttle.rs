@@ -0,0 +1,82 @@
+//! Cooperative time-slicing of the prover.
+//!
+//! When a duty cycle is configured, the recursion workers park themselves for the inactive part of
+//! every period, so that background proving jobs leave room for latency-sensitive services running
+//! on the same machine.
+
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
+
+/// The default length of a duty cycle period.
+const DEFAULT_DUTY_PERIOD: Duration = Duration::from_millis(1000);
+
+/// A duty cycle: the prover is active for the first `active` of every `period`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DutyCycle {
+    /// The portion of the period during which workers may run.
+    pub active: Duration,
+    /// The length of a period.
+    pub period: Duration,
+}
+
+impl DutyCycle {
+    /// Create a duty cycle that is active for `fraction` of every `period`.
+    ///
+    /// Returns `None` if `fraction` is not in `(0, 1]` or the period is zero.
+    #[must_use]
+    pub fn from_fraction(fraction: f64, period: Duration) -> Option<Self> {
+        (fraction > 0.0 && fraction <= 1.0 && !period.is_zero())
+            .then(|| Self { active: period.mul_f64(fraction), period })
+    }
+
+    /// Read the duty cycle from the `SP1_PROVER_DUTY_CYCLE` (a fraction in `(0, 1]`) and
+    /// `SP1_PROVER_DUTY_PERIOD_MS` environment variables.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let fraction = env::var("SP1_PROVER_DUTY_CYCLE").ok()?.parse::<f64>().ok()?;
+        let period = env::var("SP1_PROVER_DUTY_PERIOD_MS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map_or(DEFAULT_DUTY_PERIOD, Duration::from_millis);
+        Self::from_fraction(fraction, period)
+    }
+}
+
+/// Parks worker threads according to a [`DutyCycle`].
+///
+/// All workers share the same clock, so the whole prover pauses together.
+#[derive(Debug, Clone, Copy)]
+pub struct Throttle {
+    duty_cycle: DutyCycle,
+    epoch: Instant,
+}
+
+impl Throttle {
+    /// Create a throttle whose first period starts now.
+    #[must_use]
+    pub fn new(duty_cycle: DutyCycle) -> Self {
+        Self { duty_cycle, epoch: Instant::now() }
+    }
+
+    /// The duty cycle of the throttle.
+    #[must_use]
+    pub fn duty_cycle(&self) -> DutyCycle {
+        self.duty_cycle
+    }
+
+    /// Park the current thread until the next active window, if we are outside of one.
+    pub fn yield_now(&self) {
+        let DutyCycle { active, period } = self.duty_cycle;
+        let period_nanos = period.as_nanos();
+        let position = Duration::from_nanos(
+            (self.epoch.elapsed().as_nanos() % period_nanos).try_into().unwrap_or(u64::MAX),
+        );
+        if position >= active {
+            let pause = period - position;
+            tracing::trace!("duty cycle: parking worker for {:?}", pause);
+            thread::sleep(pause);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Choosing the core prover options for a program.
+//!
+//! How cheap a program is to prove depends on how its execution is split into shards. Given a set
+//! of candidate [`SP1CoreOpts`], the prover executes the program with the record estimator under
+//! each of them, predicts the gas of the resulting shards, and proves with the cheapest candidate.
+
+use serde::{Deserialize, Serialize};
+use sp1_core_executor::{ExecutionError, Program, SP1Context};
+use sp1_core_machine::{
+    io::SP1Stdin,
+    utils::{ProvingProfile, SP1CoreProverError},
+};
+use sp1_stark::{SP1CoreOpts, SP1ProverOpts, SplitOpts};
+use thiserror::Error;
+
+use crate::{
+    components::SP1ProverComponents, lock_or_reset, DeviceProvingKey, SP1CoreProof, SP1Prover,
+};
+
+/// An error choosing the core prover options of a program.
+#[derive(Debug, Error)]
+pub enum SP1TuneError {
+    #[error("no core opts candidates")]
+    NoCandidates,
+    #[error("failed to execute program: {0}")]
+    Execution(#[from] ExecutionError),
+    #[error(transparent)]
+    Core(#[from] SP1CoreProverError),
+}
+
+/// A candidate configuration of the core prover and its predicted cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreOptsCandidate {
+    /// The core prover options.
+    pub opts: SP1CoreOpts,
+    /// The predicted gas of proving the program with these options, or `None` if it could not be
+    /// estimated.
+    pub gas: Option<u64>,
+}
+
+/// The result of evaluating several core prover configurations for a program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreOptsSelection {
+    /// The evaluated candidates, in the order they were given.
+    pub candidates: Vec<CoreOptsCandidate>,
+    /// The index of the chosen candidate.
+    pub chosen: usize,
+}
+
+impl CoreOptsSelection {
+    /// The options of the chosen candidate.
+    #[must_use]
+    pub fn chosen_opts(&self) -> SP1CoreOpts {
+        self.candidates[self.chosen].opts
+    }
+}
+
+/// Generate candidate configurations derived from `base`.
+///
+/// The candidates use the shard size and deferred split threshold of `base`, and halvings of them.
+/// They never exceed the values of `base`, which are assumed to be the largest that fit in memory.
+#[must_use]
+pub fn core_opts_candidates(base: SP1CoreOpts) -> Vec<SP1CoreOpts> {
+    let mut candidates = Vec::new();
+    for shard_size_divisor in [1, 2, 4] {
+        for split_divisor in [1, 2] {
+            let shard_size = base.shard_size / shard_size_divisor;
+            let deferred = base.split_opts.deferred / split_divisor;
+            if shard_size == 0 || deferred == 0 {
+                continue;
+            }
+            let split_opts = SplitOpts {
+                deferred,
+                keccak: base.split_opts.keccak / split_divisor,
+                sha_extend: base.split_opts.sha_extend / split_divisor,
+                sha_compress: base.split_opts.sha_compress / split_divisor,
+                memory: base.split_opts.memory / split_divisor,
+                ..base.split_opts
+            };
+            candidates.push(SP1CoreOpts { shard_size, split_opts, ..base });
+        }
+    }
+    candidates
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Predict the gas of proving `program` under each of the candidate core prover options and
+    /// choose the cheapest one.
+    ///
+    /// The program is executed once per candidate. Gas can only be predicted when the prover fixes
+    /// core shapes; otherwise, or if no candidate could be estimated, the first candidate is
+    /// chosen.
+    #[tracing::instrument(name = "select_core_opts", level = "info", skip_all)]
+    pub fn select_core_opts(
+        &self,
+        program: &Program,
+        stdin: &SP1Stdin,
+        candidates: &[SP1CoreOpts],
+    ) -> Result<CoreOptsSelection, SP1TuneError> {
+        if candidates.is_empty() {
+            return Err(SP1TuneError::NoCandidates);
+        }
+
+        let mut evaluated = Vec::with_capacity(candidates.len());
+        for opts in candidates.iter().copied() {
+            let gas = match (&self.core_shape_config, &program.preprocessed_shape) {
+                (Some(_), Some(preprocessed_shape)) => {
+                    let context =
+                        SP1Context { subproof_verifier: Some(self), ..Default::default() };
+                    let mut runtime =
+                        self.estimating_executor(program.clone(), stdin, opts, context);
+                    runtime.run_fast()?;
+
+                    self.get_gas_calculator(preprocessed_shape.clone(), opts.split_opts)(
+                        runtime.record_estimator.as_ref().unwrap(),
+                    )
+                    .inspect_err(|e| tracing::warn!("failed to estimate gas: {}", e))
+                    .ok()
+                }
+                _ => None,
+            };
+            tracing::debug!(
+                "shard size {}, deferred split threshold {}: gas {:?}",
+                opts.shard_size,
+                opts.split_opts.deferred,
+                gas
+            );
+            evaluated.push(CoreOptsCandidate { opts, gas });
+        }
+
+        let chosen = evaluated
+            .iter()
+            .enumerate()
+            .filter_map(|(i, candidate)| candidate.gas.map(|gas| (gas, i)))
+            .min()
+            .map_or(0, |(_, i)| i);
+        tracing::info!(
+            "chose core opts with shard size {} and deferred split threshold {}",
+            evaluated[chosen].opts.shard_size,
+            evaluated[chosen].opts.split_opts.deferred
+        );
+
+        Ok(CoreOptsSelection { candidates: evaluated, chosen })
+    }
+
+    /// Generate the core proof with the cheapest of the candidate core prover options.
+    ///
+    /// The core options in `opts` are replaced by the chosen candidate, and the options the core
+    /// proof was generated with are recorded in the proving profile of the prover. The returned
+    /// selection records the chosen configuration and the predicted cost of every candidate.
+    pub fn prove_core_with_best_opts<'a>(
+        &'a self,
+        pk_d: &DeviceProvingKey<C>,
+        program: Program,
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        candidates: &[SP1CoreOpts],
+        context: SP1Context<'a>,
+    ) -> Result<(SP1CoreProof, CoreOptsSelection), SP1TuneError> {
+        let selection = self.select_core_opts(&program, stdin, candidates)?;
+        let opts = SP1ProverOpts { core_opts: selection.chosen_opts(), ..opts };
+        let core_opts = opts.core_opts;
+        let proof = self.prove_core(pk_d, program, stdin, opts, context)?;
+        lock_or_reset(&self.profile, |p| *p = ProvingProfile::default()).selected_core_opts =
+            Some(core_opts);
+        Ok((proof, selection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::CpuProverComponents, reload::ProverConfigBundle};
+
+    fn shapeless_prover() -> SP1Prover<CpuProverComponents> {
+        SP1Prover::with_config(ProverConfigBundle {
+            core_shape_config: None,
+            compress_shape_config: None,
+            ..ProverConfigBundle::from_env().unwrap()
+        })
+    }
+
+    #[test]
+    fn test_core_opts_candidates() {
+        let base = SP1CoreOpts::default();
+        let candidates = core_opts_candidates(base);
+        assert_eq!(candidates.len(), 6);
+        assert_eq!(candidates[0], base);
+        for candidate in &candidates {
+            assert!(candidate.shard_size <= base.shard_size);
+            assert!(candidate.split_opts.deferred <= base.split_opts.deferred);
+        }
+    }
+
+    #[test]
+    fn test_select_core_opts_without_candidates() {
+        let prover = shapeless_prover();
+        let program = Program::new(vec![], 0, 0);
+        let result = prover.select_core_opts(&program, &SP1Stdin::new(), &[]);
+        assert!(matches!(result, Err(SP1TuneError::NoCandidates)));
+    }
+
+    #[test]
+    fn test_select_core_opts_without_shapes() {
+        let prover = shapeless_prover();
+        let program = Program::new(vec![], 0, 0);
+        let candidates = core_opts_candidates(SP1CoreOpts::default());
+        let selection = prover.select_core_opts(&program, &SP1Stdin::new(), &candidates).unwrap();
+        assert_eq!(selection.chosen, 0);
+        assert_eq!(selection.chosen_opts(), candidates[0]);
+        assert!(selection.candidates.iter().all(|candidate| candidate.gas.is_none()));
+    }
+}
@@ -1,27 +1,43 @@
-use std::{fs::File, path::Path};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 use clap::ValueEnum;
 use p3_baby_bear::BabyBear;
 use p3_bn254_fr::Bn254Fr;
 use p3_commit::{Pcs, TwoAdicMultiplicativeCoset};
-use p3_field::{AbstractField, PrimeField, PrimeField32, TwoAdicField};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use sp1_core_machine::{io::SP1Stdin, reduce::SP1ReduceProof};
+use p3_field::{AbstractField, PrimeField32, TwoAdicField};
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use sha2::{Digest, Sha256};
+use sp1_core_executor::DOMAIN_TAG_WORDS;
+use sp1_core_machine::{io::SP1Stdin, reduce::SP1ReduceProof, utils::SP1CoreProverError};
 use sp1_primitives::{io::SP1PublicValues, poseidon2_hash};
 
 use sp1_recursion_circuit::machine::{
     SP1CompressWitnessValues, SP1DeferredWitnessValues, SP1RecursionWitnessValues,
 };
 
-use sp1_recursion_gnark_ffi::proof::{Groth16Bn254Proof, PlonkBn254Proof};
+use sp1_recursion_gnark_ffi::{
+    proof::{Groth16Bn254Proof, PlonkBn254Proof},
+    GnarkError,
+};
 
-use sp1_stark::{ShardProof, StarkGenericConfig, StarkProvingKey, StarkVerifyingKey, DIGEST_SIZE};
+use sp1_stark::{
+    FriParamsMismatch, InsecureFriParams, ShardProof, StarkGenericConfig, StarkProvingKey,
+    StarkVerifyingKey, DIGEST_SIZE,
+};
 use thiserror::Error;
 
 use crate::{
-    utils::{babybears_to_bn254, words_to_bytes_be},
-    CoreSC, InnerSC,
+    encryption::{load_artifact, save_artifact, ArtifactCipher},
+    utils::{babybears_to_bn254, bn254_to_bytes_be, words_to_bytes_be},
+    CoreSC, InnerSC, StrictModeError, UnknownWarningClass,
 };
 
 /// The information necessary to generate a proof for a given RISC-V program.
@@ -34,9 +50,146 @@ pub struct SP1ProvingKey {
 }
 
 /// The information necessary to verify a proof for a given RISC-V program.
-#[derive(Clone, Serialize, Deserialize)]
+///
+/// In binary formats, the key is encoded as [`VK_ENCODING_MAGIC`], the encoding version and its
+/// fields, so that keys encoded as a bare [`StarkVerifyingKey`] before the key carried any
+/// metadata are still decoded, with no metadata. Human-readable formats use a map of the fields,
+/// and missing metadata fields default to `None`.
+#[derive(Clone)]
 pub struct SP1VerifyingKey {
     pub vk: StarkVerifyingKey<CoreSC>,
+    /// The digest of the core shape configuration the program was set up with, if known.
+    pub shape_config_digest: Option<[u8; 32]>,
+    /// The artifacts of the prover the program was set up with, if known. They are not part of
+    /// the key itself and are only used to report mismatched provers early.
+    pub artifacts: Option<SetupArtifacts>,
+    /// The domain tag the program was set up with, if any, see
+    /// [`SP1Prover::setup_with_domain`](crate::SP1Prover::setup_with_domain). The tag itself is
+    /// committed to by the key, this only records it to set up the program again when proving.
+    pub domain_tag: Option<[u32; DOMAIN_TAG_WORDS]>,
+}
+
+/// The first element of the binary encoding of an [`SP1VerifyingKey`].
+///
+/// A key encoded as a bare [`StarkVerifyingKey`] starts with a canonical BabyBear element of its
+/// commitment instead, which is always smaller.
+pub const VK_ENCODING_MAGIC: u32 = u32::MAX;
+
+/// The version of the binary encoding of an [`SP1VerifyingKey`].
+pub const VK_ENCODING_VERSION: u32 = 1;
+
+/// The number of elements of the binary encoding of a bare [`StarkVerifyingKey`], with each
+/// element of its commitment counted separately.
+const LEGACY_VK_ENCODING_LEN: usize = DIGEST_SIZE + 4;
+
+/// The fields of an [`SP1VerifyingKey`], as encoded in human-readable formats.
+#[derive(Serialize, Deserialize)]
+struct SP1VerifyingKeyFields {
+    vk: StarkVerifyingKey<CoreSC>,
+    #[serde(default)]
+    shape_config_digest: Option<[u8; 32]>,
+    #[serde(default)]
+    artifacts: Option<SetupArtifacts>,
+    #[serde(default)]
+    domain_tag: Option<[u32; DOMAIN_TAG_WORDS]>,
+}
+
+impl Serialize for SP1VerifyingKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return SP1VerifyingKeyFields {
+                vk: self.vk.clone(),
+                shape_config_digest: self.shape_config_digest,
+                artifacts: self.artifacts.clone(),
+                domain_tag: self.domain_tag,
+            }
+            .serialize(serializer);
+        }
+        let mut tuple = serializer.serialize_tuple(6)?;
+        tuple.serialize_element(&VK_ENCODING_MAGIC)?;
+        tuple.serialize_element(&VK_ENCODING_VERSION)?;
+        tuple.serialize_element(&self.vk)?;
+        tuple.serialize_element(&self.shape_config_digest)?;
+        tuple.serialize_element(&self.artifacts)?;
+        tuple.serialize_element(&self.domain_tag)?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SP1VerifyingKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let fields = SP1VerifyingKeyFields::deserialize(deserializer)?;
+            return Ok(Self {
+                vk: fields.vk,
+                shape_config_digest: fields.shape_config_digest,
+                artifacts: fields.artifacts,
+                domain_tag: fields.domain_tag,
+            });
+        }
+        deserializer.deserialize_tuple(LEGACY_VK_ENCODING_LEN, SP1VerifyingKeyVisitor)
+    }
+}
+
+struct SP1VerifyingKeyVisitor;
+
+impl<'de> Visitor<'de> for SP1VerifyingKeyVisitor {
+    type Value = SP1VerifyingKey;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an encoded SP1VerifyingKey")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let first: u32 = next_element(&mut seq, 0)?;
+        if first == VK_ENCODING_MAGIC {
+            let version: u32 = next_element(&mut seq, 1)?;
+            if version != VK_ENCODING_VERSION {
+                return Err(de::Error::custom(format!(
+                    "unsupported verifying key encoding version {version}"
+                )));
+            }
+            return Ok(SP1VerifyingKey {
+                vk: next_element(&mut seq, 2)?,
+                shape_config_digest: next_element(&mut seq, 3)?,
+                artifacts: next_element(&mut seq, 4)?,
+                domain_tag: next_element(&mut seq, 5)?,
+            });
+        }
+
+        // A bare stark verifying key, whose first element is the first element of its commitment.
+        let mut commit = [BabyBear::zero(); DIGEST_SIZE];
+        commit[0] = BabyBear::deserialize(first.into_deserializer())
+            .map_err(|e: de::value::Error| de::Error::custom(e))?;
+        for (i, element) in commit.iter_mut().enumerate().skip(1) {
+            *element = next_element(&mut seq, i)?;
+        }
+        let vk = StarkVerifyingKey {
+            commit: commit.into(),
+            pc_start: next_element(&mut seq, DIGEST_SIZE)?,
+            initial_global_cumulative_sum: next_element(&mut seq, DIGEST_SIZE + 1)?,
+            chip_information: next_element(&mut seq, DIGEST_SIZE + 2)?,
+            chip_ordering: next_element(&mut seq, DIGEST_SIZE + 3)?,
+        };
+        Ok(SP1VerifyingKey { vk, shape_config_digest: None, artifacts: None, domain_tag: None })
+    }
+}
+
+fn next_element<'de, A: SeqAccess<'de>, T: Deserialize<'de>>(
+    seq: &mut A,
+    index: usize,
+) -> Result<T, A::Error> {
+    seq.next_element()?.ok_or_else(|| de::Error::invalid_length(index, &SP1VerifyingKeyVisitor))
+}
+
+/// The versions of the artifacts of the prover a verifying key was set up with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupArtifacts {
+    /// The circuit version of the prover.
+    pub circuit_version: String,
+    /// The root of the allowed recursion verifying keys, which are derived from the recursion
+    /// shape configuration.
+    pub vk_root: [u32; DIGEST_SIZE],
 }
 
 /// A trait for keys that can be hashed into a digest.
@@ -56,19 +209,14 @@ pub trait HashableKey {
     ///
     /// This is ideal for generating a vkey hash for onchain verification.
     fn bytes32(&self) -> String {
-        let vkey_digest_bn254 = self.hash_bn254();
-        format!("0x{:0>64}", vkey_digest_bn254.as_canonical_biguint().to_str_radix(16))
+        format!("0x{}", hex::encode(self.bytes32_raw()))
     }
 
     /// Hash the key into a 32 byte array.
     ///
     /// This has the same value as `bytes32`, but as a raw byte array.
     fn bytes32_raw(&self) -> [u8; 32] {
-        let vkey_digest_bn254 = self.hash_bn254();
-        let vkey_bytes = vkey_digest_bn254.as_canonical_biguint().to_bytes_be();
-        let mut result = [0u8; 32];
-        result[1..].copy_from_slice(&vkey_bytes);
-        result
+        bn254_to_bytes_be(&self.hash_bn254())
     }
 
     /// Hash the key into a digest of bytes elements.
@@ -151,6 +299,16 @@ impl<P: Serialize + DeserializeOwned + Clone> SP1ProofWithMetadata<P> {
         bincode::deserialize_from(File::open(path).expect("failed to open file"))
             .map_err(Into::into)
     }
+
+    /// Save the proof and its inputs, encrypted with `cipher` if one is given.
+    pub fn save_with(&self, path: impl AsRef<Path>, cipher: Option<&ArtifactCipher>) -> Result<()> {
+        save_artifact(self, path, cipher).map_err(Into::into)
+    }
+
+    /// Load a proof written by [`SP1ProofWithMetadata::save_with`] with the same cipher.
+    pub fn load_with(path: impl AsRef<Path>, cipher: Option<&ArtifactCipher>) -> Result<Self> {
+        load_artifact(path, cipher).map_err(Into::into)
+    }
 }
 
 impl<P: std::fmt::Debug + Clone> std::fmt::Debug for SP1ProofWithMetadata<P> {
@@ -219,7 +377,7 @@ pub enum ProverMode {
     Mock,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ProofSystem {
     Plonk,
     Groth16,
@@ -241,10 +399,351 @@ pub enum SP1ReduceProofWrapper {
     Recursive(SP1ReduceProof<InnerSC>),
 }
 
+/// The error returned when a verifying key was set up with a different core shape configuration
+/// than the one of the prover.
+#[derive(Error, Debug)]
+#[error(
+    "the verifying key was set up with core shape config {}, but the prover uses {}",
+    Self::describe(.vk),
+    Self::describe(.prover)
+)]
+pub struct ShapeConfigMismatch {
+    /// The digest of the configuration of the verifying key.
+    pub vk: Option<[u8; 32]>,
+    /// The digest of the configuration of the prover.
+    pub prover: Option<[u8; 32]>,
+}
+
+impl ShapeConfigMismatch {
+    fn describe(digest: &Option<[u8; 32]>) -> String {
+        digest.map_or_else(|| "none".to_string(), hex::encode)
+    }
+}
+
+/// The error returned when a verifying key was set up with different artifacts than the ones of
+/// the prover.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SetupArtifactsMismatch {
+    #[error(
+        "the verifying key was set up with circuit version {vk}, but the prover uses {prover}"
+    )]
+    CircuitVersion { vk: String, prover: String },
+    #[error(
+        "the verifying key was set up with vk root {}, but the prover uses {}",
+        hex::encode(words_to_bytes_be(vk)),
+        hex::encode(words_to_bytes_be(prover))
+    )]
+    VkRoot { vk: [u32; DIGEST_SIZE], prover: [u32; DIGEST_SIZE] },
+}
+
+/// The default size of the chunks a serialized proof is split into for transfer.
+pub const DEFAULT_PROOF_CHUNK_SIZE: usize = 8 << 20;
+
+/// A piece of a serialized proof, addressed by the SHA-256 digest of its bytes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProofChunk {
+    /// The position of the chunk in the manifest.
+    pub index: usize,
+    pub data: Vec<u8>,
+}
+
+impl ProofChunk {
+    #[must_use]
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(&self.data).into()
+    }
+}
+
+/// The list of chunks a serialized proof was split into.
+///
+/// The manifest is sent ahead of the chunks. The receiver checks every chunk against it as it
+/// arrives, so an interrupted transfer only needs to resend the chunks that are still missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    /// The length of the serialized proof.
+    pub total_len: u64,
+    /// The length of every chunk but the last.
+    pub chunk_size: u64,
+    /// The SHA-256 digest of the serialized proof.
+    pub digest: [u8; 32],
+    /// The SHA-256 digests of the chunks, in order.
+    pub chunks: Vec<[u8; 32]>,
+}
+
+impl ChunkManifest {
+    /// Split `bytes` into chunks of `chunk_size` bytes.
+    pub fn split(bytes: &[u8], chunk_size: usize) -> Result<(Self, Vec<ProofChunk>), ChunkError> {
+        if chunk_size == 0 {
+            return Err(ChunkError::ZeroChunkSize);
+        }
+        let chunks = bytes
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(index, data)| ProofChunk { index, data: data.to_vec() })
+            .collect::<Vec<_>>();
+        let manifest = Self {
+            total_len: bytes.len() as u64,
+            chunk_size: chunk_size as u64,
+            digest: Sha256::digest(bytes).into(),
+            chunks: chunks.iter().map(ProofChunk::digest).collect(),
+        };
+        Ok((manifest, chunks))
+    }
+
+    /// The hex encoded digest of the serialized proof, usable as a transfer id.
+    #[must_use]
+    pub fn id(&self) -> String {
+        hex::encode(self.digest)
+    }
+
+    /// Check that `chunk` is the chunk at its index.
+    pub fn verify_chunk(&self, chunk: &ProofChunk) -> Result<(), ChunkError> {
+        let expected = self
+            .chunks
+            .get(chunk.index)
+            .ok_or(ChunkError::IndexOutOfRange(chunk.index, self.chunks.len()))?;
+        if chunk.digest() != *expected {
+            return Err(ChunkError::DigestMismatch(chunk.index));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ChunkError {
+    #[error("the chunk size must be positive")]
+    ZeroChunkSize,
+    #[error("chunk index {0} is out of range for a manifest of {1} chunks")]
+    IndexOutOfRange(usize, usize),
+    #[error("chunk {0} does not match its digest in the manifest")]
+    DigestMismatch(usize),
+    #[error("missing chunks {0:?}")]
+    Missing(Vec<usize>),
+    #[error("the reassembled proof does not match the manifest")]
+    ProofMismatch,
+    #[error("the assembler holds {0} chunks for a manifest of {1} chunks")]
+    ChunkCountMismatch(usize, usize),
+    #[error("Serialization error: {0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+/// Collects the chunks of a proof as they arrive, in any order.
+///
+/// The assembler can be serialized, so that a receiver which restarts in the middle of a transfer
+/// can pick up where it left off and ask only for [`ChunkAssembler::missing`].
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "ChunkAssemblerState")]
+pub struct ChunkAssembler {
+    manifest: ChunkManifest,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+/// The serialized fields of a [`ChunkAssembler`], checked against each other when it is loaded.
+#[derive(Deserialize)]
+struct ChunkAssemblerState {
+    manifest: ChunkManifest,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl TryFrom<ChunkAssemblerState> for ChunkAssembler {
+    type Error = ChunkError;
+
+    fn try_from(state: ChunkAssemblerState) -> Result<Self, Self::Error> {
+        let ChunkAssemblerState { manifest, chunks } = state;
+        if chunks.len() != manifest.chunks.len() {
+            return Err(ChunkError::ChunkCountMismatch(chunks.len(), manifest.chunks.len()));
+        }
+        for (index, data) in chunks.iter().enumerate() {
+            if let Some(data) = data {
+                if <[u8; 32]>::from(Sha256::digest(data)) != manifest.chunks[index] {
+                    return Err(ChunkError::DigestMismatch(index));
+                }
+            }
+        }
+        Ok(Self { manifest, chunks })
+    }
+}
+
+impl ChunkAssembler {
+    #[must_use]
+    pub fn new(manifest: ChunkManifest) -> Self {
+        let chunks = vec![None; manifest.chunks.len()];
+        Self { manifest, chunks }
+    }
+
+    #[must_use]
+    pub fn manifest(&self) -> &ChunkManifest {
+        &self.manifest
+    }
+
+    /// Add a chunk after checking it against the manifest.
+    ///
+    /// Returns `false` if the chunk was already received.
+    pub fn insert(&mut self, chunk: ProofChunk) -> Result<bool, ChunkError> {
+        self.manifest.verify_chunk(&chunk)?;
+        let num_chunks = self.chunks.len();
+        let slot = self
+            .chunks
+            .get_mut(chunk.index)
+            .ok_or(ChunkError::IndexOutOfRange(chunk.index, num_chunks))?;
+        if slot.is_some() {
+            return Ok(false);
+        }
+        *slot = Some(chunk.data);
+        Ok(true)
+    }
+
+    /// The indices of the chunks that have not been received yet.
+    #[must_use]
+    pub fn missing(&self) -> Vec<usize> {
+        self.chunks.iter().enumerate().filter(|(_, c)| c.is_none()).map(|(i, _)| i).collect()
+    }
+
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.chunks.iter().all(Option::is_some)
+    }
+
+    /// Concatenate the chunks and check the result against the digest of the whole proof.
+    pub fn finish(self) -> Result<Vec<u8>, ChunkError> {
+        let missing = self.missing();
+        if !missing.is_empty() {
+            return Err(ChunkError::Missing(missing));
+        }
+        let bytes = self.chunks.into_iter().flatten().flatten().collect::<Vec<_>>();
+        if bytes.len() as u64 != self.manifest.total_len ||
+            <[u8; 32]>::from(Sha256::digest(&bytes)) != self.manifest.digest
+        {
+            return Err(ChunkError::ProofMismatch);
+        }
+        Ok(bytes)
+    }
+}
+
+/// The error returned when the shard proofs of a core proof do not cover a contiguous range of
+/// shards starting at the first one.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ShardOrderError {
+    #[error("the core proof has no shard proofs")]
+    Empty,
+    #[error("shard {0} was proven more than once")]
+    Duplicate(u32),
+    #[error("shards {0:?} are missing")]
+    Missing(Vec<u32>),
+}
+
+/// How to resolve a [`VkNotAllowedError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VkRemediation {
+    /// The recursion shapes of the prover are not fixed, so its recursion programs do not have the
+    /// verifying keys of the vk map. Set a recursion shape configuration.
+    FixShapes,
+    /// The vk map was built for other recursion shapes than those of the prover. Use the shape
+    /// configuration and vk map of the same circuit version.
+    UpgradeShapes,
+}
+
+impl std::fmt::Display for VkRemediation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FixShapes => write!(f, "set a recursion shape configuration"),
+            Self::UpgradeShapes => {
+                write!(f, "use the shape configuration and vk map of the same circuit version")
+            }
+        }
+    }
+}
+
+/// The error returned when a recursion verifying key is not in the vk map of the prover.
+///
+/// Proving with vk verification disabled always avoids it, but the resulting proofs are rejected
+/// by verifiers that check the verifying keys against the vk root.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "recursion vk {vk_digest:?} is not in the vk map of circuit version {circuit_version} \
+     ({num_allowed_vks} vks, root {vk_root:?}); {remediation}, or disable vk verification"
+)]
+pub struct VkNotAllowedError {
+    /// The digest of the verifying key.
+    pub vk_digest: [u32; DIGEST_SIZE],
+    /// The circuit version of the prover, which the vk map belongs to.
+    pub circuit_version: &'static str,
+    /// The root of the vk map.
+    pub vk_root: [u32; DIGEST_SIZE],
+    /// The number of verifying keys in the vk map.
+    pub num_allowed_vks: usize,
+    /// How to resolve the error without disabling vk verification.
+    pub remediation: VkRemediation,
+}
+
 #[derive(Error, Debug)]
 pub enum SP1RecursionProverError {
     #[error("Runtime error: {0}")]
     RuntimeError(String),
+    #[error("the deferred proofs do not match the deferred proofs digest of the core proof")]
+    DeferredDigestMismatch,
+    #[error("Invalid proof to merge: {0}")]
+    InvalidMergeInput(&'static str),
+    #[error("Invalid shard proofs: {0}")]
+    ShardOrder(#[from] ShardOrderError),
+    #[error(transparent)]
+    FriParams(#[from] FriParamsMismatch),
+    #[error(transparent)]
+    VkNotAllowed(#[from] VkNotAllowedError),
+    #[error(transparent)]
+    ShapeConfig(#[from] ShapeConfigMismatch),
+    #[error(transparent)]
+    SetupArtifacts(#[from] SetupArtifactsMismatch),
+    #[error("the compress checkpoint does not match the proof: {0}")]
+    CheckpointMismatch(&'static str),
+    #[error("compress proof {0} does not verify after being re-proven")]
+    RepairFailed(usize),
+    #[error("the read-only prover is missing the program artifacts {}", .0.join(", "))]
+    MissingArtifacts(Vec<String>),
+    #[error(transparent)]
+    Strict(#[from] StrictModeError),
+    #[error(transparent)]
+    Gnark(#[from] GnarkError),
+    #[error("io error: {0}")]
+    IoError(std::io::Error),
+    #[error("invalid reduction plan: {0}")]
+    InvalidReductionPlan(&'static str),
+    #[error("recursion proving was cancelled")]
+    Cancelled,
+    #[error("failed to prove a recursion program: {0}")]
+    ProveFailed(String),
+    #[error("a recursion worker panicked")]
+    WorkerPanicked,
+    #[error("a recursion worker exited before the proofs were reduced")]
+    WorkerDisconnected,
+}
+
+/// A prover configuration, given or read from the environment, that cannot be used.
+#[derive(Error, Debug)]
+pub enum SP1ProverConfigError {
+    #[error(transparent)]
+    InsecureFriParams(#[from] InsecureFriParams),
+    #[error("invalid SP1_STRICT: {0}")]
+    Strict(#[from] UnknownWarningClass),
+    #[error("the vk verification mode must be enforced, permissive or disabled, got {0:?}")]
+    VkVerification(String),
+    #[error("invalid SP1_ARTIFACT_KEY: {0}")]
+    ArtifactKey(std::io::Error),
+    #[error("the prover core is shared with other handles")]
+    SharedCore,
+}
+
+/// The error of any stage of the prover, from the core proof to the wrapped proof.
+#[derive(Error, Debug)]
+pub enum SP1ProverError {
+    #[error(transparent)]
+    Core(#[from] SP1CoreProverError),
+    #[error(transparent)]
+    Recursion(#[from] SP1RecursionProverError),
+    #[error("invalid program: {0}")]
+    Program(eyre::Report),
+    #[error("the {} circuit artifacts are not installed in {}", .0.as_str(), .1.display())]
+    MissingCircuitArtifacts(ProofSystem, PathBuf),
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -253,3 +752,143 @@ pub enum SP1CircuitWitness {
     Deferred(SP1DeferredWitnessValues<InnerSC>),
     Compress(SP1CompressWitnessValues<InnerSC>),
 }
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashMap;
+    use p3_matrix::Dimensions;
+    use sp1_stark::septic_digest::SepticDigest;
+
+    use super::*;
+
+    fn stark_vk() -> StarkVerifyingKey<CoreSC> {
+        StarkVerifyingKey {
+            commit: core::array::from_fn(|i| BabyBear::from_canonical_usize(i + 1)).into(),
+            pc_start: BabyBear::from_canonical_u32(0x20_0800),
+            initial_global_cumulative_sum: SepticDigest::zero(),
+            chip_information: vec![(
+                "Program".to_string(),
+                TwoAdicMultiplicativeCoset { log_n: 4, shift: BabyBear::one() },
+                Dimensions { width: 3, height: 16 },
+            )],
+            chip_ordering: HashMap::from([("Program".to_string(), 0)]),
+        }
+    }
+
+    /// The layout of [`SP1VerifyingKey`] before it carried any metadata.
+    #[derive(Serialize)]
+    struct LegacyVerifyingKey {
+        vk: StarkVerifyingKey<CoreSC>,
+    }
+
+    #[test]
+    fn test_vk_decodes_legacy_encoding() {
+        let legacy = LegacyVerifyingKey { vk: stark_vk() };
+
+        // A legacy key followed by another value, as in a payload embedding the key.
+        let bytes = bincode::serialize(&(&legacy, 7u64)).unwrap();
+        let (vk, next): (SP1VerifyingKey, u64) = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(vk.hash_u32(), legacy.vk.hash_u32());
+        assert_eq!(vk.shape_config_digest, None);
+        assert!(vk.artifacts.is_none());
+        assert_eq!(vk.domain_tag, None);
+        assert_eq!(next, 7);
+
+        let json = serde_json::to_string(&legacy).unwrap();
+        let vk: SP1VerifyingKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(vk.hash_u32(), legacy.vk.hash_u32());
+        assert_eq!(vk.shape_config_digest, None);
+    }
+
+    #[test]
+    fn test_vk_round_trip() {
+        let vk = SP1VerifyingKey {
+            vk: stark_vk(),
+            shape_config_digest: Some([3; 32]),
+            artifacts: Some(SetupArtifacts {
+                circuit_version: "v5.0.0".to_string(),
+                vk_root: [5; DIGEST_SIZE],
+            }),
+            domain_tag: Some([9; DOMAIN_TAG_WORDS]),
+        };
+
+        let bytes = bincode::serialize(&(&vk, 7u64)).unwrap();
+        assert_eq!(bytes[..4], VK_ENCODING_MAGIC.to_le_bytes());
+        let (decoded, next): (SP1VerifyingKey, u64) = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.hash_u32(), vk.hash_u32());
+        assert_eq!(decoded.shape_config_digest, vk.shape_config_digest);
+        assert_eq!(decoded.artifacts, vk.artifacts);
+        assert_eq!(decoded.domain_tag, vk.domain_tag);
+        assert_eq!(next, 7);
+
+        let json = serde_json::to_string(&vk).unwrap();
+        let decoded: SP1VerifyingKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.hash_u32(), vk.hash_u32());
+        assert_eq!(decoded.artifacts, vk.artifacts);
+        assert_eq!(decoded.domain_tag, vk.domain_tag);
+    }
+
+    #[test]
+    fn test_vk_rejects_unknown_encoding_version() {
+        let mut bytes = bincode::serialize(&SP1VerifyingKey {
+            vk: stark_vk(),
+            shape_config_digest: None,
+            artifacts: None,
+            domain_tag: None,
+        })
+        .unwrap();
+        bytes[4..8].copy_from_slice(&(VK_ENCODING_VERSION + 1).to_le_bytes());
+        assert!(bincode::deserialize::<SP1VerifyingKey>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let bytes = (0..100u8).collect::<Vec<_>>();
+        let (manifest, mut chunks) = ChunkManifest::split(&bytes, 30).unwrap();
+        assert_eq!(manifest.chunks.len(), 4);
+        assert!(matches!(ChunkManifest::split(&bytes, 0), Err(ChunkError::ZeroChunkSize)));
+
+        // Chunks may arrive in any order, and more than once.
+        chunks.reverse();
+        let mut assembler = ChunkAssembler::new(manifest.clone());
+        assert!(assembler.insert(chunks[0].clone()).unwrap());
+        assert!(!assembler.insert(chunks[0].clone()).unwrap());
+        assert_eq!(assembler.missing(), vec![0, 1, 2]);
+
+        // A restarted receiver picks up the received chunks.
+        let mut assembler: ChunkAssembler =
+            bincode::deserialize(&bincode::serialize(&assembler).unwrap()).unwrap();
+        for chunk in chunks.into_iter().skip(1) {
+            assert!(assembler.insert(chunk).unwrap());
+        }
+        assert_eq!(assembler.finish().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_chunk_rejects_invalid() {
+        let bytes = (0..100u8).collect::<Vec<_>>();
+        let (manifest, chunks) = ChunkManifest::split(&bytes, 30).unwrap();
+        let mut assembler = ChunkAssembler::new(manifest.clone());
+
+        let mut tampered = chunks[1].clone();
+        tampered.data[0] ^= 1;
+        assert!(matches!(assembler.insert(tampered), Err(ChunkError::DigestMismatch(1))));
+        let out_of_range = ProofChunk { index: 4, data: vec![] };
+        assert!(matches!(assembler.insert(out_of_range), Err(ChunkError::IndexOutOfRange(4, 4))));
+        assembler.insert(chunks[0].clone()).unwrap();
+        assert!(
+            matches!(assembler.finish(), Err(ChunkError::Missing(missing)) if missing == [1, 2, 3])
+        );
+
+        // A saved assembler whose chunks do not match its manifest is rejected when loaded.
+        let short = ChunkAssemblerState { manifest: manifest.clone(), chunks: vec![None; 2] };
+        assert!(matches!(
+            ChunkAssembler::try_from(short),
+            Err(ChunkError::ChunkCountMismatch(2, 4))
+        ));
+        let mut state = vec![None; 4];
+        state[2] = Some(chunks[1].data.clone());
+        let swapped = ChunkAssemblerState { manifest, chunks: state };
+        assert!(matches!(ChunkAssembler::try_from(swapped), Err(ChunkError::DigestMismatch(2))));
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     fs::{self, File},
     io::Read,
     iter::{Skip, Take},
@@ -8,8 +8,9 @@ use std::{
 use itertools::Itertools;
 use p3_baby_bear::BabyBear;
 use p3_bn254_fr::Bn254Fr;
-use p3_field::{AbstractField, PrimeField32};
+use p3_field::{AbstractField, PrimeField, PrimeField32};
 use p3_symmetric::CryptographicHasher;
+use serde::{de::DeserializeOwned, Serialize};
 use sp1_core_executor::{Executor, Program};
 use sp1_core_machine::{io::SP1Stdin, reduce::SP1ReduceProof};
 use sp1_recursion_circuit::machine::RootPublicValues;
@@ -17,9 +18,14 @@ use sp1_recursion_core::{
     air::{RecursionPublicValues, NUM_PV_ELMS_TO_HASH},
     stark::BabyBearPoseidon2Outer,
 };
-use sp1_stark::{baby_bear_poseidon2::MyHash as InnerHash, SP1CoreOpts, Word};
+use sp1_stark::{
+    air::PublicValues, baby_bear_poseidon2::MyHash as InnerHash, SP1CoreOpts, ShardProof, Word,
+};
 
-use crate::{InnerSC, SP1CoreProofData};
+use crate::{
+    types::{ChunkAssembler, ChunkError, ChunkManifest, HashableKey, ProofChunk, ShardOrderError},
+    CoreSC, InnerSC, SP1CoreProofData, SP1VerifyingKey,
+};
 
 /// Get the SP1 vkey BabyBear Poseidon2 digest this reduce proof is representing.
 pub fn sp1_vkey_digest_babybear(proof: &SP1ReduceProof<BabyBearPoseidon2Outer>) -> [BabyBear; 8] {
@@ -33,6 +39,51 @@ pub fn sp1_vkey_digest_bn254(proof: &SP1ReduceProof<BabyBearPoseidon2Outer>) ->
     babybears_to_bn254(&sp1_vkey_digest_babybear(proof))
 }
 
+/// Every encoding of the hash of a program verifying key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VKeyHashes {
+    /// The Poseidon2 digest, as committed in recursion public values and used by the vk map.
+    pub babybear: [BabyBear; 8],
+    /// The Poseidon2 digest as u32 words, as passed to `verify_sp1_proof` in guest programs.
+    pub u32: [u32; 8],
+    /// The u32 words in big-endian bytes.
+    pub bytes: [u8; 32],
+    /// The digest packed into a BN254 scalar, as committed in PLONK and Groth16 proofs.
+    pub bn254: Bn254Fr,
+    /// The BN254 scalar as 32 big-endian bytes, as passed to on-chain verifiers.
+    pub bytes32: [u8; 32],
+}
+
+impl VKeyHashes {
+    /// The BN254 scalar as a `0x` prefixed hex string.
+    #[must_use]
+    pub fn bytes32_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.bytes32))
+    }
+}
+
+/// Compute every encoding of the hash of `vk`, hashing the key only once.
+pub fn vkey_hashes(vk: &SP1VerifyingKey) -> VKeyHashes {
+    let babybear = vk.hash_babybear();
+    let u32 = babybear.map(|n| n.as_canonical_u32());
+    let bn254 = babybears_to_bn254(&babybear);
+    VKeyHashes {
+        babybear,
+        u32,
+        bytes: words_to_bytes_be(&u32),
+        bn254,
+        bytes32: bn254_to_bytes_be(&bn254),
+    }
+}
+
+/// Convert a Bn254Fr field element into 32 big-endian bytes.
+pub fn bn254_to_bytes_be(value: &Bn254Fr) -> [u8; 32] {
+    let bytes = value.as_canonical_biguint().to_bytes_be();
+    let mut result = [0u8; 32];
+    result[32 - bytes.len()..].copy_from_slice(&bytes);
+    result
+}
+
 /// Compute the digest of the public values.
 pub fn recursion_public_values_digest(
     config: &InnerSC,
@@ -97,6 +148,47 @@ pub fn sp1_committed_values_digest_bn254(
     babybear_bytes_to_bn254(&committed_values_digest_bytes)
 }
 
+/// The index of the shard a core shard proof is for.
+pub fn shard_index(proof: &ShardProof<CoreSC>) -> u32 {
+    let pv: &PublicValues<Word<BabyBear>, BabyBear> = proof.public_values.as_slice().borrow();
+    pv.shard.as_canonical_u32()
+}
+
+/// Sort core shard proofs by shard index, checking that they cover every shard from the first one
+/// exactly once.
+///
+/// Shard proofs proven on different machines can arrive in any order. The proofs are only copied
+/// if they are not already in order.
+pub fn order_shard_proofs(
+    shard_proofs: &[ShardProof<CoreSC>],
+) -> Result<Cow<'_, [ShardProof<CoreSC>]>, ShardOrderError> {
+    if shard_proofs.is_empty() {
+        return Err(ShardOrderError::Empty);
+    }
+
+    let mut indices = shard_proofs.iter().map(shard_index).enumerate().collect::<Vec<_>>();
+    indices.sort_by_key(|(_, shard)| *shard);
+
+    let mut missing = Vec::new();
+    let mut expected = 1;
+    for (_, shard) in indices.iter() {
+        if *shard < expected {
+            return Err(ShardOrderError::Duplicate(*shard));
+        }
+        missing.extend(expected..*shard);
+        expected = shard + 1;
+    }
+    if !missing.is_empty() {
+        return Err(ShardOrderError::Missing(missing));
+    }
+
+    if indices.iter().enumerate().all(|(position, (i, _))| position == *i) {
+        return Ok(Cow::Borrowed(shard_proofs));
+    }
+    tracing::debug!("reordering {} shard proofs", shard_proofs.len());
+    Ok(Cow::Owned(indices.into_iter().map(|(i, _)| shard_proofs[i].clone()).collect()))
+}
+
 impl SP1CoreProofData {
     pub fn save(&self, path: &str) -> Result<(), std::io::Error> {
         let data = serde_json::to_string(self).unwrap();
@@ -105,11 +197,33 @@ impl SP1CoreProofData {
     }
 }
 
+/// Serialize a proof and split it into content-addressed chunks of `chunk_size` bytes.
+pub fn chunk_proof<T: Serialize>(
+    proof: &T,
+    chunk_size: usize,
+) -> Result<(ChunkManifest, Vec<ProofChunk>), ChunkError> {
+    let bytes = bincode::serialize(proof)?;
+    ChunkManifest::split(&bytes, chunk_size)
+}
+
+/// Reassemble and deserialize a proof from its manifest and chunks, checking every chunk.
+pub fn reassemble_proof<T: DeserializeOwned>(
+    manifest: ChunkManifest,
+    chunks: impl IntoIterator<Item = ProofChunk>,
+) -> Result<T, ChunkError> {
+    let mut assembler = ChunkAssembler::new(manifest);
+    for chunk in chunks {
+        assembler.insert(chunk)?;
+    }
+    Ok(bincode::deserialize(&assembler.finish()?)?)
+}
+
 /// Get the number of cycles for a given program.
 pub fn get_cycles(elf: &[u8], stdin: &SP1Stdin) -> u64 {
     let program = Program::from(elf).unwrap();
     let mut runtime = Executor::new(program, SP1CoreOpts::default());
     runtime.write_vecs(&stdin.buffer);
+    runtime.write_input_chunks(stdin.input_chunks.clone());
     runtime.run_fast().unwrap();
     runtime.state.global_clk
 }
@@ -218,3 +332,60 @@ impl<I: Iterator> Iterator for RangedIterator<I> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::BorrowMut;
+
+    use sp1_core_machine::riscv::RiscvAir;
+    use sp1_stark::shape::OrderedShape;
+
+    use super::*;
+
+    #[test]
+    fn test_order_shard_proofs() {
+        let machine = RiscvAir::<BabyBear>::machine(CoreSC::default());
+        let shape = OrderedShape { inner: vec![("Program".to_string(), 4)] };
+        let (_, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(&machine, &shape);
+        let shard = |index: u32| {
+            let mut proof = proof.clone();
+            let pv: &mut PublicValues<Word<BabyBear>, BabyBear> =
+                proof.public_values.as_mut_slice().borrow_mut();
+            pv.shard = BabyBear::from_canonical_u32(index);
+            proof
+        };
+        let indices = |proofs: &[ShardProof<CoreSC>]| proofs.iter().map(shard_index).collect_vec();
+
+        // Proofs in order are not copied, others are sorted.
+        let ordered = [shard(1), shard(2), shard(3)];
+        assert!(matches!(order_shard_proofs(&ordered), Ok(Cow::Borrowed(_))));
+        let shuffled = [shard(3), shard(1), shard(2)];
+        assert_eq!(indices(&order_shard_proofs(&shuffled).unwrap()), vec![1, 2, 3]);
+
+        let error = |proofs: &[ShardProof<CoreSC>]| order_shard_proofs(proofs).err();
+        assert_eq!(error(&[]), Some(ShardOrderError::Empty));
+        assert_eq!(error(&[shard(2), shard(1), shard(2)]), Some(ShardOrderError::Duplicate(2)));
+        assert_eq!(error(&[shard(4), shard(2)]), Some(ShardOrderError::Missing(vec![1, 3])));
+    }
+
+    #[test]
+    fn test_vkey_hashes() {
+        let machine = RiscvAir::<BabyBear>::machine(CoreSC::default());
+        let shape = OrderedShape { inner: vec![("Program".to_string(), 4)] };
+        let (vk, _) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(&machine, &shape);
+        let vk =
+            SP1VerifyingKey { vk, shape_config_digest: None, artifacts: None, domain_tag: None };
+
+        let hashes = vkey_hashes(&vk);
+        assert_eq!(hashes.babybear, vk.hash_babybear());
+        assert_eq!(hashes.u32, vk.hash_u32());
+        assert_eq!(hashes.bytes, vk.hash_bytes());
+        assert_eq!(hashes.bn254, vk.hash_bn254());
+        assert_eq!(hashes.bytes32, vk.bytes32_raw());
+        assert_eq!(hashes.bytes32_hex(), vk.bytes32());
+        assert_eq!(
+            format!("0x{:0>64}", hashes.bn254.as_canonical_biguint().to_str_radix(16)),
+            vk.bytes32()
+        );
+    }
+}
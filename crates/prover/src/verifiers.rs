@@ -0,0 +1,215 @@
+//! A persistent registry of the verifiers of each circuit version.
+//!
+//! PLONK and Groth16 proofs are verified on chain by the verifier of their circuit version, which
+//! the gateway contract selects with the first 4 bytes of the vkey hash of the proof. Fleets
+//! running several SP1 versions at once record, for every circuit version they use, the digest of
+//! its wrap verifying key and the vkey hashes of its PLONK and Groth16 circuits in a
+//! [`VerifierRegistry`], and look up which circuit version and verifier a proof targets.
+
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{components::SP1ProverComponents, ProofSystem, SP1Prover, SP1_CIRCUIT_VERSION};
+
+/// The verifiers of a circuit version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitVerifiers {
+    /// The SHA-256 digest of the bincode serialized wrap verifying key.
+    pub wrap_vk_digest: Option<[u8; 32]>,
+    /// The vkey hash of the PLONK circuit, as in the proofs and the verifier contract.
+    pub plonk_vkey_hash: Option<[u8; 32]>,
+    /// The vkey hash of the Groth16 circuit, as in the proofs and the verifier contract.
+    pub groth16_vkey_hash: Option<[u8; 32]>,
+}
+
+impl CircuitVerifiers {
+    /// The vkey hash of the circuit of `system`, if it is known.
+    #[must_use]
+    pub fn vkey_hash(&self, system: ProofSystem) -> Option<[u8; 32]> {
+        match system {
+            ProofSystem::Plonk => self.plonk_vkey_hash,
+            ProofSystem::Groth16 => self.groth16_vkey_hash,
+        }
+    }
+}
+
+/// The verifier a proof targets, see [`VerifierRegistry::target`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifierTarget {
+    /// The circuit version of the verifier.
+    pub circuit_version: String,
+    pub system: ProofSystem,
+    /// The vkey hash of the circuit, whose first 4 bytes select the verifier in the gateway.
+    pub vkey_hash: [u8; 32],
+}
+
+/// The verifiers of every circuit version used locally, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifierRegistry {
+    pub versions: BTreeMap<String, CircuitVerifiers>,
+}
+
+impl VerifierRegistry {
+    /// The path of the registry, `SP1_VERIFIER_REGISTRY` or `~/.sp1/circuits/verifiers.json` if it
+    /// is not set.
+    #[must_use]
+    pub fn default_path() -> PathBuf {
+        env::var("SP1_VERIFIER_REGISTRY").map(PathBuf::from).unwrap_or_else(|_| {
+            dirs::home_dir().unwrap().join(".sp1").join("circuits").join("verifiers.json")
+        })
+    }
+
+    /// Load the registry from `path`, or an empty registry if it does not exist.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save the registry to `path` atomically.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        fs::write(&tmp, serde_json::to_vec_pretty(self).map_err(io::Error::other)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// The verifiers recorded for `circuit_version`, if any.
+    #[must_use]
+    pub fn get(&self, circuit_version: &str) -> Option<&CircuitVerifiers> {
+        self.versions.get(circuit_version)
+    }
+
+    /// Record the known verifiers of `circuit_version`, keeping the ones already recorded that are
+    /// unknown in `verifiers`.
+    ///
+    /// A circuit version has a single set of verifiers, so a conflicting digest is replaced with a
+    /// warning.
+    pub fn record(&mut self, circuit_version: &str, verifiers: CircuitVerifiers) {
+        let entry = self.versions.entry(circuit_version.to_string()).or_default();
+        for (name, recorded, new) in [
+            ("wrap vk digest", &mut entry.wrap_vk_digest, verifiers.wrap_vk_digest),
+            ("plonk vkey hash", &mut entry.plonk_vkey_hash, verifiers.plonk_vkey_hash),
+            ("groth16 vkey hash", &mut entry.groth16_vkey_hash, verifiers.groth16_vkey_hash),
+        ] {
+            let Some(new) = new else { continue };
+            if recorded.is_some_and(|recorded| recorded != new) {
+                tracing::warn!(
+                    "replacing the {name} of circuit version {circuit_version}, {} with {}",
+                    hex::encode(recorded.unwrap()),
+                    hex::encode(new)
+                );
+            }
+            *recorded = Some(new);
+        }
+    }
+
+    /// The verifier of the circuit with the vkey hash `vkey_hash`, such as the
+    /// `plonk_vkey_hash` of a PLONK proof, if it is recorded.
+    #[must_use]
+    pub fn target(&self, vkey_hash: &[u8; 32]) -> Option<VerifierTarget> {
+        self.find(|hash| hash == vkey_hash)
+    }
+
+    /// The verifier selected by `selector`, the first 4 bytes of an encoded proof, if it is
+    /// recorded.
+    #[must_use]
+    pub fn target_by_selector(&self, selector: [u8; 4]) -> Option<VerifierTarget> {
+        self.find(|hash| hash[..4] == selector)
+    }
+
+    fn find(&self, matches: impl Fn(&[u8; 32]) -> bool) -> Option<VerifierTarget> {
+        self.versions.iter().find_map(|(circuit_version, verifiers)| {
+            [ProofSystem::Plonk, ProofSystem::Groth16].into_iter().find_map(|system| {
+                let vkey_hash = verifiers.vkey_hash(system).filter(|hash| matches(hash))?;
+                Some(VerifierTarget { circuit_version: circuit_version.clone(), system, vkey_hash })
+            })
+        })
+    }
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The verifiers of the circuit version of the prover that are known locally: the wrap
+    /// verifying key once it is set up, and the vkey hashes of the circuits whose artifacts are in
+    /// `plonk_dir` and `groth16_dir`.
+    pub fn circuit_verifiers(
+        &self,
+        plonk_dir: Option<&Path>,
+        groth16_dir: Option<&Path>,
+    ) -> io::Result<CircuitVerifiers> {
+        let vkey_hash = |dir: Option<&Path>, file: &str| -> io::Result<Option<[u8; 32]>> {
+            match dir.map(|dir| fs::read(dir.join(file))).transpose() {
+                Ok(bytes) => Ok(bytes.map(|bytes| Sha256::digest(bytes).into())),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }
+        };
+        let wrap_vk_digest = self
+            .wrap_vk
+            .get()
+            .map(|vk| bincode::serialize(vk).map(|bytes| Sha256::digest(bytes).into()))
+            .transpose()
+            .map_err(io::Error::other)?;
+        Ok(CircuitVerifiers {
+            wrap_vk_digest,
+            plonk_vkey_hash: vkey_hash(plonk_dir, "plonk_vk.bin")?,
+            groth16_vkey_hash: vkey_hash(groth16_dir, "groth16_vk.bin")?,
+        })
+    }
+
+    /// Record the verifiers of the circuit version of the prover, see [`Self::circuit_verifiers`],
+    /// in the registry at `path`.
+    pub fn record_circuit_verifiers(
+        &self,
+        path: &Path,
+        plonk_dir: Option<&Path>,
+        groth16_dir: Option<&Path>,
+    ) -> io::Result<VerifierRegistry> {
+        let mut registry = VerifierRegistry::load(path)?;
+        registry.record(SP1_CIRCUIT_VERSION, self.circuit_verifiers(plonk_dir, groth16_dir)?);
+        registry.save(path)?;
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verifier_registry_targets() {
+        let mut registry = VerifierRegistry::default();
+        registry.record(
+            "v4.0.0-rc.3",
+            CircuitVerifiers { plonk_vkey_hash: Some([1; 32]), ..Default::default() },
+        );
+        registry.record(
+            "v5.0.0",
+            CircuitVerifiers {
+                plonk_vkey_hash: Some([2; 32]),
+                groth16_vkey_hash: Some([3; 32]),
+                ..Default::default()
+            },
+        );
+        registry.record("v5.0.0", CircuitVerifiers::default());
+        assert_eq!(registry.get("v5.0.0").unwrap().groth16_vkey_hash, Some([3; 32]));
+
+        let target = registry.target(&[3; 32]).unwrap();
+        assert_eq!(target.circuit_version, "v5.0.0");
+        assert_eq!(target.system, ProofSystem::Groth16);
+        assert_eq!(registry.target_by_selector([1; 4]).unwrap().circuit_version, "v4.0.0-rc.3");
+        assert!(registry.target(&[4; 32]).is_none());
+    }
+}
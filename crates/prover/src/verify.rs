@@ -12,21 +12,27 @@ use sp1_primitives::{
 };
 
 use sp1_recursion_circuit::machine::RootPublicValues;
-use sp1_recursion_core::{air::RecursionPublicValues, stark::BabyBearPoseidon2Outer};
+use sp1_recursion_core::stark::BabyBearPoseidon2Outer;
 use sp1_recursion_gnark_ffi::{
     Groth16Bn254Proof, Groth16Bn254Prover, PlonkBn254Proof, PlonkBn254Prover,
 };
 use sp1_stark::{
-    air::{PublicValues, POSEIDON_NUM_WORDS, PV_DIGEST_NUM_WORDS},
+    air::{PublicValues, POSEIDON_NUM_WORDS, PV_DIGEST_NUM_WORDS, SP1_PROOF_NUM_PV_ELTS},
     baby_bear_poseidon2::BabyBearPoseidon2,
-    MachineProof, MachineProver, MachineVerificationError, StarkGenericConfig, Word,
+    septic_digest::SepticDigest,
+    MachineProof, MachineProver, MachineVerificationError, ShardProof, StarkGenericConfig, Word,
+    DIGEST_SIZE,
 };
 use thiserror::Error;
 
 use crate::{
     components::SP1ProverComponents,
+    public_values::{PublicValuesError, ReduceProofPublicValues},
+    reload::VkVerificationMode,
+    rotation::VkRootStatus,
     utils::{is_recursion_public_values_valid, is_root_public_values_valid},
-    CoreSC, HashableKey, OuterSC, SP1CoreProofData, SP1Prover, SP1VerifyingKey,
+    CoreSC, HashableKey, InnerSC, OuterSC, SP1CoreProofData, SP1Prover, SP1VerifyingKey,
+    SetupArtifactsMismatch,
 };
 
 #[derive(Error, Debug)]
@@ -61,235 +67,8 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         proof: &SP1CoreProofData,
         vk: &SP1VerifyingKey,
     ) -> Result<(), MachineVerificationError<CoreSC>> {
-        // The proof should not be empty.
-        if proof.0.is_empty() {
-            return Err(MachineVerificationError::EmptyProof);
-        }
-
-        // First shard has a "CPU" constraint.
-        //
-        // Check that the first shard has a "CPU".
-        // SAFETY: The proof is already checked to not be empty.
-        let first_shard = proof.0.first().unwrap();
-        if !first_shard.contains_cpu() {
-            return Err(MachineVerificationError::MissingCpuInFirstShard);
-        }
-
-        // CPU log degree bound constraints.
-        //
-        // Check that the CPU log degree does not exceed `MAX_CPU_LOG_DEGREE`. This is to ensure
-        // that the lookup argument's multiplicities do not overflow.
-        for shard_proof in proof.0.iter() {
-            if shard_proof.contains_cpu() {
-                let log_degree_cpu = shard_proof.log_degree_cpu();
-                if log_degree_cpu > MAX_CPU_LOG_DEGREE {
-                    return Err(MachineVerificationError::CpuLogDegreeTooLarge(log_degree_cpu));
-                }
-            }
-        }
-
-        // Shard constraints.
-        //
-        // Initialization:
-        // - Shard should start at one.
-        //
-        // Transition:
-        // - Shard should increment by one for each shard.
-        let mut current_shard = BabyBear::zero();
-        for shard_proof in proof.0.iter() {
-            let public_values: &PublicValues<Word<_>, _> =
-                shard_proof.public_values.as_slice().borrow();
-            current_shard += BabyBear::one();
-            if public_values.shard != current_shard {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "shard index should be the previous shard index + 1 and start at 1",
-                ));
-            }
-        }
-
-        // Execution shard constraints.
-        //
-        // Initialization:
-        // - Execution shard should start at one.
-        //
-        // Transition:
-        // - Execution shard should increment by one for each shard with "CPU".
-        // - Execution shard should stay the same for non-CPU shards.
-        // - For the other shards, execution shard does not matter.
-        let mut current_execution_shard = BabyBear::zero();
-        for shard_proof in proof.0.iter() {
-            let public_values: &PublicValues<Word<_>, _> =
-                shard_proof.public_values.as_slice().borrow();
-            if shard_proof.contains_cpu() {
-                current_execution_shard += BabyBear::one();
-                if public_values.execution_shard != current_execution_shard {
-                    return Err(MachineVerificationError::InvalidPublicValues(
-                        "execution shard index should be the previous execution shard index + 1 if cpu exists and start at 1",
-                    ));
-                }
-            }
-        }
-
-        // Program counter constraints.
-        //
-        // Initialization:
-        // - `start_pc` should start as `vk.start_pc`.
-        //
-        // Transition:
-        // - `next_pc` of the previous shard should equal `start_pc`.
-        // - If it's not a shard with "CPU", then `start_pc` equals `next_pc`.
-        // - If it's a shard with "CPU", then `start_pc` should never equal zero.
-        //
-        // Finalization:
-        // - `next_pc` should equal zero.
-        let mut prev_next_pc = BabyBear::zero();
-        for (i, shard_proof) in proof.0.iter().enumerate() {
-            let public_values: &PublicValues<Word<_>, _> =
-                shard_proof.public_values.as_slice().borrow();
-            if i == 0 && public_values.start_pc != vk.vk.pc_start {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "start_pc != vk.start_pc: program counter should start at vk.start_pc",
-                ));
-            } else if i != 0 && public_values.start_pc != prev_next_pc {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "start_pc != next_pc_prev: start_pc should equal next_pc_prev for all shards",
-                ));
-            } else if !shard_proof.contains_cpu() && public_values.start_pc != public_values.next_pc
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "start_pc != next_pc: start_pc should equal next_pc for non-cpu shards",
-                ));
-            } else if shard_proof.contains_cpu() && public_values.start_pc == BabyBear::zero() {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "start_pc == 0: execution should never start at halted state",
-                ));
-            } else if i == proof.0.len() - 1 && public_values.next_pc != BabyBear::zero() {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "next_pc != 0: execution should have halted",
-                ));
-            }
-            prev_next_pc = public_values.next_pc;
-        }
-
-        // Exit code constraints.
-        //
-        // - In every shard, the exit code should be zero.
-        for shard_proof in proof.0.iter() {
-            let public_values: &PublicValues<Word<_>, _> =
-                shard_proof.public_values.as_slice().borrow();
-            if public_values.exit_code != BabyBear::zero() {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "exit_code != 0: exit code should be zero for all shards",
-                ));
-            }
-        }
-
-        // Memory initialization & finalization constraints.
-        //
-        // Initialization:
-        // - `previous_init_addr_bits` should be zero.
-        // - `previous_finalize_addr_bits` should be zero.
-        //
-        // Transition:
-        // - For all shards, `previous_init_addr_bits` should equal `last_init_addr_bits` of the
-        //   previous shard.
-        // - For all shards, `previous_finalize_addr_bits` should equal `last_finalize_addr_bits` of
-        //   the previous shard.
-        // - For shards without "MemoryInit", `previous_init_addr_bits` should equal
-        //   `last_init_addr_bits`.
-        // - For shards without "MemoryFinalize", `previous_finalize_addr_bits` should equal
-        //   `last_finalize_addr_bits`.
-        let mut last_init_addr_bits_prev = [BabyBear::zero(); 32];
-        let mut last_finalize_addr_bits_prev = [BabyBear::zero(); 32];
-        for shard_proof in proof.0.iter() {
-            let public_values: &PublicValues<Word<_>, _> =
-                shard_proof.public_values.as_slice().borrow();
-            if public_values.previous_init_addr_bits != last_init_addr_bits_prev {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "previous_init_addr_bits != last_init_addr_bits_prev",
-                ));
-            } else if public_values.previous_finalize_addr_bits != last_finalize_addr_bits_prev {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "last_init_addr_bits != last_finalize_addr_bits_prev",
-                ));
-            } else if !shard_proof.contains_global_memory_init() &&
-                public_values.previous_init_addr_bits != public_values.last_init_addr_bits
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "previous_init_addr_bits != last_init_addr_bits",
-                ));
-            } else if !shard_proof.contains_global_memory_finalize() &&
-                public_values.previous_finalize_addr_bits !=
-                    public_values.last_finalize_addr_bits
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "previous_finalize_addr_bits != last_finalize_addr_bits",
-                ));
-            }
-            last_init_addr_bits_prev = public_values.last_init_addr_bits;
-            last_finalize_addr_bits_prev = public_values.last_finalize_addr_bits;
-        }
-
-        // Digest constraints.
-        //
-        // Initialization:
-        // - `committed_value_digest` should be zero.
-        // - `deferred_proofs_digest` should be zero.
-        //
-        // Transition:
-        // - If `committed_value_digest_prev` is not zero, then `committed_value_digest` should
-        //   equal
-        //  `committed_value_digest_prev`. Otherwise, `committed_value_digest` should equal zero.
-        // - If `deferred_proofs_digest_prev` is not zero, then `deferred_proofs_digest` should
-        //   equal
-        //  `deferred_proofs_digest_prev`. Otherwise, `deferred_proofs_digest` should equal zero.
-        // - If it's not a shard with "CPU", then `committed_value_digest` should not change from
-        //   the
-        //  previous shard.
-        // - If it's not a shard with "CPU", then `deferred_proofs_digest` should not change from
-        //   the
-        //  previous shard.
-        let zero_committed_value_digest =
-            [Word([BabyBear::zero(); WORD_SIZE]); PV_DIGEST_NUM_WORDS];
-        let zero_deferred_proofs_digest = [BabyBear::zero(); POSEIDON_NUM_WORDS];
-        let mut committed_value_digest_prev = zero_committed_value_digest;
-        let mut deferred_proofs_digest_prev = zero_deferred_proofs_digest;
-        for shard_proof in proof.0.iter() {
-            let public_values: &PublicValues<Word<_>, _> =
-                shard_proof.public_values.as_slice().borrow();
-            if committed_value_digest_prev != zero_committed_value_digest &&
-                public_values.committed_value_digest != committed_value_digest_prev
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "committed_value_digest != committed_value_digest_prev",
-                ));
-            } else if deferred_proofs_digest_prev != zero_deferred_proofs_digest &&
-                public_values.deferred_proofs_digest != deferred_proofs_digest_prev
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "deferred_proofs_digest != deferred_proofs_digest_prev",
-                ));
-            } else if !shard_proof.contains_cpu() &&
-                public_values.committed_value_digest != committed_value_digest_prev
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "committed_value_digest != committed_value_digest_prev",
-                ));
-            } else if !shard_proof.contains_cpu() &&
-                public_values.deferred_proofs_digest != deferred_proofs_digest_prev
-            {
-                return Err(MachineVerificationError::InvalidPublicValues(
-                    "deferred_proofs_digest != deferred_proofs_digest_prev",
-                ));
-            }
-            committed_value_digest_prev = public_values.committed_value_digest;
-            deferred_proofs_digest_prev = public_values.deferred_proofs_digest;
-        }
-
-        // Verify that the number of shards is not too large.
-        if proof.0.len() >= 1 << 16 {
-            return Err(MachineVerificationError::TooManyShards);
-        }
+        self.check_vk_artifacts(vk).map_err(incompatible_vk)?;
+        self.verify_core_public_values(proof, vk, true)?;
 
         // Verify the shard proof.
         let mut challenger = self.core_prover.config().challenger();
@@ -299,19 +78,32 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         Ok(())
     }
 
+    /// Verify that the public values of the shards of a core proof are contiguous, starting from
+    /// the beginning of the execution. If `halted` is set, the execution must also end in the last
+    /// shard.
+    pub(crate) fn verify_core_public_values(
+        &self,
+        proof: &SP1CoreProofData,
+        vk: &SP1VerifyingKey,
+        halted: bool,
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        check_shard_public_values(&proof.0, vk.vk.pc_start, halted).map_err(|e| e.error)
+    }
+
     /// Verify a compressed proof.
     pub fn verify_compressed(
         &self,
         proof: &SP1ReduceProof<BabyBearPoseidon2>,
         vk: &SP1VerifyingKey,
     ) -> Result<(), MachineVerificationError<CoreSC>> {
-        let SP1ReduceProof { vk: compress_vk, proof } = proof;
+        self.check_vk_artifacts(vk).map_err(incompatible_vk)?;
+        let compress_vk = &proof.vk;
         let mut challenger = self.compress_prover.config().challenger();
-        let machine_proof = MachineProof { shard_proofs: vec![proof.clone()] };
+        let machine_proof = MachineProof { shard_proofs: vec![proof.proof.clone()] };
         self.compress_prover.machine().verify(compress_vk, &machine_proof, &mut challenger)?;
 
         // Validate public values
-        let public_values: &RecursionPublicValues<_> = proof.public_values.as_slice().borrow();
+        let public_values = proof.recursion_public_values().map_err(invalid_public_values)?;
 
         if !is_recursion_public_values_valid(self.compress_prover.machine().config(), public_values)
         {
@@ -320,30 +112,119 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
             ));
         }
 
-        if public_values.vk_root != self.recursion_vk_root {
-            return Err(MachineVerificationError::InvalidPublicValues("vk_root mismatch"));
-        }
+        self.check_vk_root(&public_values.vk_root)?;
 
-        if self.vk_verification && !self.recursion_vk_map.contains_key(&compress_vk.hash_babybear())
-        {
+        if !self.is_vk_allowed_under(&public_values.vk_root, &compress_vk.hash_babybear()) {
             return Err(MachineVerificationError::InvalidVerificationKey);
         }
 
         // `is_complete` should be 1. In the reduce program, this ensures that the proof is fully
         // reduced.
-        if public_values.is_complete != BabyBear::one() {
+        if !proof.is_complete().map_err(invalid_public_values)? {
             return Err(MachineVerificationError::InvalidPublicValues("is_complete is not 1"));
         }
 
         // Verify that the proof is for the sp1 vkey we are expecting.
         let vkey_hash = vk.hash_babybear();
-        if public_values.sp1_vk_digest != vkey_hash {
+        if proof.sp1_vk_digest().map_err(invalid_public_values)? != vkey_hash {
             return Err(MachineVerificationError::InvalidPublicValues("sp1 vk hash mismatch"));
         }
 
         Ok(())
     }
 
+    /// Verify a compressed proof made by [`SP1Prover::compress_with_initial_deferred_digest`].
+    ///
+    /// The proof is checked like a complete proof, except that its deferred digest chain starts
+    /// from `initial_deferred_digest` instead of zero. With a zero digest, this is
+    /// [`Self::verify_compressed`].
+    pub fn verify_compressed_with_initial_deferred_digest(
+        &self,
+        proof: &SP1ReduceProof<BabyBearPoseidon2>,
+        vk: &SP1VerifyingKey,
+        initial_deferred_digest: [BabyBear; DIGEST_SIZE],
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        if initial_deferred_digest == [BabyBear::zero(); DIGEST_SIZE] {
+            return self.verify_compressed(proof, vk);
+        }
+        self.check_vk_artifacts(vk).map_err(incompatible_vk)?;
+        let compress_vk = &proof.vk;
+        let mut challenger = self.compress_prover.config().challenger();
+        let machine_proof = MachineProof { shard_proofs: vec![proof.proof.clone()] };
+        self.compress_prover.machine().verify(compress_vk, &machine_proof, &mut challenger)?;
+
+        let public_values = proof.recursion_public_values().map_err(invalid_public_values)?;
+        if !is_recursion_public_values_valid(self.compress_prover.machine().config(), public_values)
+        {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "recursion public values are invalid",
+            ));
+        }
+        self.check_vk_root(&public_values.vk_root)?;
+        if !self.is_vk_allowed_under(&public_values.vk_root, &compress_vk.hash_babybear()) {
+            return Err(MachineVerificationError::InvalidVerificationKey);
+        }
+
+        // The boundary conditions the compress program checks for complete proofs, with the
+        // deferred digest chain starting from the initial digest.
+        if public_values.next_pc != BabyBear::zero() {
+            return Err(MachineVerificationError::InvalidPublicValues("next_pc is not 0"));
+        }
+        if public_values.start_shard != BabyBear::one() ||
+            public_values.start_execution_shard != BabyBear::one()
+        {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "the proof does not start at the first shard",
+            ));
+        }
+        if public_values.next_shard == BabyBear::one() ||
+            public_values.contains_execution_shard != BabyBear::one()
+        {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "the proof contains no execution shard",
+            ));
+        }
+        if public_values.start_reconstruct_deferred_digest != initial_deferred_digest {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "the deferred digest does not start from the initial digest",
+            ));
+        }
+        if public_values.end_reconstruct_deferred_digest != public_values.deferred_proofs_digest {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "the deferred digest does not end at the deferred proofs digest",
+            ));
+        }
+        if public_values.global_cumulative_sum != SepticDigest::zero() {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "global cumulative sum is not zero",
+            ));
+        }
+
+        if public_values.sp1_vk_digest != vk.hash_babybear() {
+            return Err(MachineVerificationError::InvalidPublicValues("sp1 vk hash mismatch"));
+        }
+
+        Ok(())
+    }
+
+    /// Check that hashing `deferred_proofs` onto `initial_deferred_digest` gives
+    /// `end_deferred_digest`, that is that they are the deferred proofs verified between two
+    /// points of a deferred digest chain.
+    pub fn verify_deferred_digest_chain(
+        initial_deferred_digest: [BabyBear; DIGEST_SIZE],
+        deferred_proofs: &[SP1ReduceProof<InnerSC>],
+        end_deferred_digest: [BabyBear; DIGEST_SIZE],
+    ) -> Result<(), MachineVerificationError<CoreSC>> {
+        if Self::hash_deferred_proofs(initial_deferred_digest, deferred_proofs) !=
+            end_deferred_digest
+        {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "the deferred proofs do not hash to the end of the deferred digest chain",
+            ));
+        }
+        Ok(())
+    }
+
     /// Verify a shrink proof.
     pub fn verify_shrink(
         &self,
@@ -355,31 +236,28 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
         self.shrink_prover.machine().verify(&proof.vk, &machine_proof, &mut challenger)?;
 
         // Validate public values
-        let public_values: &RecursionPublicValues<_> =
-            proof.proof.public_values.as_slice().borrow();
+        let public_values = proof.recursion_public_values().map_err(invalid_public_values)?;
         if !is_recursion_public_values_valid(self.compress_prover.machine().config(), public_values)
         {
             return Err(MachineVerificationError::InvalidPublicValues(
                 "recursion public values are invalid",
             ));
         }
-        if public_values.vk_root != self.recursion_vk_root {
-            return Err(MachineVerificationError::InvalidPublicValues("vk_root mismatch"));
-        }
+        self.check_vk_root(&public_values.vk_root)?;
 
-        if self.vk_verification && !self.recursion_vk_map.contains_key(&proof.vk.hash_babybear()) {
+        if !self.is_vk_allowed_under(&public_values.vk_root, &proof.vk.hash_babybear()) {
             return Err(MachineVerificationError::InvalidVerificationKey);
         }
 
         // `is_complete` should be 1. In the reduce program, this ensures that the proof is fully
         // reduced.
-        if public_values.is_complete != BabyBear::one() {
+        if !proof.is_complete().map_err(invalid_public_values)? {
             return Err(MachineVerificationError::InvalidPublicValues("is_complete is not 1"));
         }
 
         // Verify that the proof is for the sp1 vkey we are expecting.
         let vkey_hash = vk.hash_babybear();
-        if public_values.sp1_vk_digest != vkey_hash {
+        if proof.sp1_vk_digest().map_err(invalid_public_values)? != vkey_hash {
             return Err(MachineVerificationError::InvalidPublicValues("sp1 vk hash mismatch"));
         }
 
@@ -456,6 +334,27 @@ impl<C: SP1ProverComponents> SP1Prover<C> {
 
         Ok(())
     }
+
+    /// Check that proofs committing to `vk_root` are accepted, as set by the vk verification mode
+    /// of the prover.
+    fn check_vk_root<SC: StarkGenericConfig>(
+        &self,
+        vk_root: &[BabyBear; DIGEST_SIZE],
+    ) -> Result<(), MachineVerificationError<SC>> {
+        match self.vk_root_status(vk_root) {
+            VkRootStatus::Active | VkRootStatus::Retiring => Ok(()),
+            VkRootStatus::Unverified if self.vk_verification == VkVerificationMode::Permissive => {
+                tracing::warn!("accepting a proof made without vk verification");
+                Ok(())
+            }
+            VkRootStatus::Unverified => Err(MachineVerificationError::InvalidPublicValues(
+                "the proof was made without vk verification",
+            )),
+            VkRootStatus::Unknown => {
+                Err(MachineVerificationError::InvalidPublicValues("vk_root mismatch"))
+            }
+        }
+    }
 }
 
 /// Verify the vk_hash and public_values_hash in the public inputs of the PlonkBn254Proof match the
@@ -532,24 +431,48 @@ impl<C: SP1ProverComponents> SubproofVerifier for SP1Prover<C> {
         vk_hash: [u32; 8],
         committed_value_digest: [u32; 8],
     ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
-        // Check that the vk hash matches the vk hash from the input.
-        if vk.hash_u32() != vk_hash {
-            return Err(MachineVerificationError::InvalidPublicValues(
-                "vk hash from syscall does not match vkey from input",
-            ));
-        }
+        self.preverify_deferred_proof(proof, vk)?;
+        self.check_deferred_proof(proof, vk, vk_hash, committed_value_digest)
+    }
+
+    fn preverify_deferred_proof(
+        &self,
+        proof: &sp1_core_machine::reduce::SP1ReduceProof<BabyBearPoseidon2>,
+        vk: &sp1_stark::StarkVerifyingKey<BabyBearPoseidon2>,
+    ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
         // Check that proof is valid.
         self.verify_compressed(
             &SP1ReduceProof { vk: proof.vk.clone(), proof: proof.proof.clone() },
-            &SP1VerifyingKey { vk: vk.clone() },
+            &SP1VerifyingKey {
+                vk: vk.clone(),
+                shape_config_digest: None,
+                artifacts: None,
+                domain_tag: None,
+            },
         )?;
-        // Check that the committed value digest matches the one from syscall
-        let public_values: &RecursionPublicValues<_> =
-            proof.proof.public_values.as_slice().borrow();
-        if public_values.vk_root != self.recursion_vk_root {
+        // Deferred proofs must commit to the root of the proofs that verify them, so the dummy
+        // root is never accepted.
+        if !self.vk_root_status(&proof.vk_root().map_err(invalid_public_values)?).is_accepted() {
             return Err(MachineVerificationError::InvalidPublicValues("vk_root mismatch"));
         }
+        Ok(())
+    }
 
+    fn check_deferred_proof(
+        &self,
+        proof: &sp1_core_machine::reduce::SP1ReduceProof<BabyBearPoseidon2>,
+        vk: &sp1_stark::StarkVerifyingKey<BabyBearPoseidon2>,
+        vk_hash: [u32; 8],
+        committed_value_digest: [u32; 8],
+    ) -> Result<(), MachineVerificationError<BabyBearPoseidon2>> {
+        // Check that the vk hash matches the vk hash from the input.
+        if vk.hash_u32() != vk_hash {
+            return Err(MachineVerificationError::InvalidPublicValues(
+                "vk hash from syscall does not match vkey from input",
+            ));
+        }
+        // Check that the committed value digest matches the one from syscall
+        let public_values = proof.recursion_public_values().map_err(invalid_public_values)?;
         for (i, word) in public_values.committed_value_digest.iter().enumerate() {
             if *word != committed_value_digest[i].into() {
                 return Err(MachineVerificationError::InvalidPublicValues(
@@ -560,3 +483,219 @@ impl<C: SP1ProverComponents> SubproofVerifier for SP1Prover<C> {
         Ok(())
     }
 }
+
+fn invalid_public_values<SC: StarkGenericConfig>(
+    error: PublicValuesError,
+) -> MachineVerificationError<SC> {
+    MachineVerificationError::InvalidPublicValues(error.reason())
+}
+
+fn incompatible_vk<SC: StarkGenericConfig>(
+    error: SetupArtifactsMismatch,
+) -> MachineVerificationError<SC> {
+    MachineVerificationError::IncompatibleVerificationKey(error.to_string())
+}
+
+/// The first shard of a core proof whose public values do not continue the execution of the
+/// shards before it.
+pub(crate) struct ShardPublicValuesError<SC: StarkGenericConfig> {
+    /// The index of the shard, every shard before it being contiguous.
+    pub(crate) shard: usize,
+    /// The constraint the shard breaks.
+    pub(crate) error: MachineVerificationError<SC>,
+}
+
+/// Check that the public values of the shards of a core proof of the program starting at
+/// `pc_start` are contiguous, shard by shard, failing at the first shard that breaks a
+/// constraint. If `halted` is set, the execution must also end in the last shard.
+pub(crate) fn check_shard_public_values<SC: StarkGenericConfig<Val = BabyBear>>(
+    shard_proofs: &[ShardProof<SC>],
+    pc_start: BabyBear,
+    halted: bool,
+) -> Result<(), ShardPublicValuesError<SC>> {
+    // The proof should not be empty.
+    if shard_proofs.is_empty() {
+        return Err(ShardPublicValuesError {
+            shard: 0,
+            error: MachineVerificationError::EmptyProof,
+        });
+    }
+
+    let zero_committed_value_digest = [Word([BabyBear::zero(); WORD_SIZE]); PV_DIGEST_NUM_WORDS];
+    let zero_deferred_proofs_digest = [BabyBear::zero(); POSEIDON_NUM_WORDS];
+
+    let mut current_shard = BabyBear::zero();
+    let mut current_execution_shard = BabyBear::zero();
+    let mut prev_next_pc = BabyBear::zero();
+    let mut last_init_addr_bits_prev = [BabyBear::zero(); 32];
+    let mut last_finalize_addr_bits_prev = [BabyBear::zero(); 32];
+    let mut committed_value_digest_prev = zero_committed_value_digest;
+    let mut deferred_proofs_digest_prev = zero_deferred_proofs_digest;
+    for (i, shard_proof) in shard_proofs.iter().enumerate() {
+        let fail = |error| Err(ShardPublicValuesError { shard: i, error });
+        let invalid = |reason| fail(MachineVerificationError::InvalidPublicValues(reason));
+
+        // Verify that the number of shards is not too large.
+        if i >= (1 << 16) - 1 {
+            return fail(MachineVerificationError::TooManyShards);
+        }
+
+        let Some(public_values) = shard_proof.public_values.get(..SP1_PROOF_NUM_PV_ELTS) else {
+            return invalid("the shard has fewer public values than a core shard");
+        };
+        let public_values: &PublicValues<Word<_>, _> = public_values.borrow();
+
+        // First shard has a "CPU" constraint.
+        //
+        // Check that the first shard has a "CPU".
+        if i == 0 && !shard_proof.contains_cpu() {
+            return fail(MachineVerificationError::MissingCpuInFirstShard);
+        }
+
+        // CPU log degree bound constraints.
+        //
+        // Check that the CPU log degree does not exceed `MAX_CPU_LOG_DEGREE`. This is to ensure
+        // that the lookup argument's multiplicities do not overflow.
+        if shard_proof.contains_cpu() {
+            let log_degree_cpu = shard_proof.log_degree_cpu();
+            if log_degree_cpu > MAX_CPU_LOG_DEGREE {
+                return fail(MachineVerificationError::CpuLogDegreeTooLarge(log_degree_cpu));
+            }
+        }
+
+        // Shard constraints.
+        //
+        // Initialization:
+        // - Shard should start at one.
+        //
+        // Transition:
+        // - Shard should increment by one for each shard.
+        current_shard += BabyBear::one();
+        if public_values.shard != current_shard {
+            return invalid("shard index should be the previous shard index + 1 and start at 1");
+        }
+
+        // Execution shard constraints.
+        //
+        // Initialization:
+        // - Execution shard should start at one.
+        //
+        // Transition:
+        // - Execution shard should increment by one for each shard with "CPU".
+        // - Execution shard should stay the same for non-CPU shards.
+        // - For the other shards, execution shard does not matter.
+        if shard_proof.contains_cpu() {
+            current_execution_shard += BabyBear::one();
+            if public_values.execution_shard != current_execution_shard {
+                return invalid(
+                    "execution shard index should be the previous execution shard index + 1 if cpu exists and start at 1",
+                );
+            }
+        }
+
+        // Program counter constraints.
+        //
+        // Initialization:
+        // - `start_pc` should start as `vk.start_pc`.
+        //
+        // Transition:
+        // - `next_pc` of the previous shard should equal `start_pc`.
+        // - If it's not a shard with "CPU", then `start_pc` equals `next_pc`.
+        // - If it's a shard with "CPU", then `start_pc` should never equal zero.
+        //
+        // Finalization:
+        // - `next_pc` should equal zero, if the execution halted.
+        if i == 0 && public_values.start_pc != pc_start {
+            return invalid("start_pc != vk.start_pc: program counter should start at vk.start_pc");
+        } else if i != 0 && public_values.start_pc != prev_next_pc {
+            return invalid(
+                "start_pc != next_pc_prev: start_pc should equal next_pc_prev for all shards",
+            );
+        } else if !shard_proof.contains_cpu() && public_values.start_pc != public_values.next_pc {
+            return invalid("start_pc != next_pc: start_pc should equal next_pc for non-cpu shards");
+        } else if shard_proof.contains_cpu() && public_values.start_pc == BabyBear::zero() {
+            return invalid("start_pc == 0: execution should never start at halted state");
+        } else if halted && i == shard_proofs.len() - 1 && public_values.next_pc != BabyBear::zero()
+        {
+            return invalid("next_pc != 0: execution should have halted");
+        }
+        prev_next_pc = public_values.next_pc;
+
+        // Exit code constraints.
+        //
+        // - In every shard, the exit code should be zero.
+        if public_values.exit_code != BabyBear::zero() {
+            return invalid("exit_code != 0: exit code should be zero for all shards");
+        }
+
+        // Memory initialization & finalization constraints.
+        //
+        // Initialization:
+        // - `previous_init_addr_bits` should be zero.
+        // - `previous_finalize_addr_bits` should be zero.
+        //
+        // Transition:
+        // - For all shards, `previous_init_addr_bits` should equal `last_init_addr_bits` of the
+        //   previous shard.
+        // - For all shards, `previous_finalize_addr_bits` should equal `last_finalize_addr_bits` of
+        //   the previous shard.
+        // - For shards without "MemoryInit", `previous_init_addr_bits` should equal
+        //   `last_init_addr_bits`.
+        // - For shards without "MemoryFinalize", `previous_finalize_addr_bits` should equal
+        //   `last_finalize_addr_bits`.
+        if public_values.previous_init_addr_bits != last_init_addr_bits_prev {
+            return invalid("previous_init_addr_bits != last_init_addr_bits_prev");
+        } else if public_values.previous_finalize_addr_bits != last_finalize_addr_bits_prev {
+            return invalid("last_init_addr_bits != last_finalize_addr_bits_prev");
+        } else if !shard_proof.contains_global_memory_init() &&
+            public_values.previous_init_addr_bits != public_values.last_init_addr_bits
+        {
+            return invalid("previous_init_addr_bits != last_init_addr_bits");
+        } else if !shard_proof.contains_global_memory_finalize() &&
+            public_values.previous_finalize_addr_bits != public_values.last_finalize_addr_bits
+        {
+            return invalid("previous_finalize_addr_bits != last_finalize_addr_bits");
+        }
+        last_init_addr_bits_prev = public_values.last_init_addr_bits;
+        last_finalize_addr_bits_prev = public_values.last_finalize_addr_bits;
+
+        // Digest constraints.
+        //
+        // Initialization:
+        // - `committed_value_digest` should be zero.
+        // - `deferred_proofs_digest` should be zero.
+        //
+        // Transition:
+        // - If `committed_value_digest_prev` is not zero, then `committed_value_digest` should
+        //  equal `committed_value_digest_prev`. Otherwise, `committed_value_digest` should equal
+        //  zero.
+        // - If `deferred_proofs_digest_prev` is not zero, then `deferred_proofs_digest` should
+        //  equal `deferred_proofs_digest_prev`. Otherwise, `deferred_proofs_digest` should equal
+        //  zero.
+        // - If it's not a shard with "CPU", then `committed_value_digest` should not change from
+        //  the previous shard.
+        // - If it's not a shard with "CPU", then `deferred_proofs_digest` should not change from
+        //  the previous shard.
+        if committed_value_digest_prev != zero_committed_value_digest &&
+            public_values.committed_value_digest != committed_value_digest_prev
+        {
+            return invalid("committed_value_digest != committed_value_digest_prev");
+        } else if deferred_proofs_digest_prev != zero_deferred_proofs_digest &&
+            public_values.deferred_proofs_digest != deferred_proofs_digest_prev
+        {
+            return invalid("deferred_proofs_digest != deferred_proofs_digest_prev");
+        } else if !shard_proof.contains_cpu() &&
+            public_values.committed_value_digest != committed_value_digest_prev
+        {
+            return invalid("committed_value_digest != committed_value_digest_prev");
+        } else if !shard_proof.contains_cpu() &&
+            public_values.deferred_proofs_digest != deferred_proofs_digest_prev
+        {
+            return invalid("deferred_proofs_digest != deferred_proofs_digest_prev");
+        }
+        committed_value_digest_prev = public_values.committed_value_digest;
+        deferred_proofs_digest_prev = public_values.deferred_proofs_digest;
+    }
+
+    Ok(())
+}
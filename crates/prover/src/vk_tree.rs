@@ -0,0 +1,185 @@
+//! Opening the vk Merkle tree for circuits outside of SP1.
+//!
+//! Every recursion program proves that the verifying keys of the proofs it verifies are in the vk
+//! map, by opening the Merkle tree committing to it at the vk root in its public values. Circuits
+//! that verify SP1 proofs can mirror this allowlist check with a [`VkMembershipProof`], which is
+//! the `(vk_digest, index, merkle_path)` opening the recursion programs are given as witness, and
+//! [`verify_vk_membership`] checks such an opening natively with the same logic as the circuit.
+
+use p3_baby_bear::BabyBear;
+use serde::{Deserialize, Serialize};
+use sp1_recursion_circuit::merkle_tree::{MerkleProof, MerkleTree, MerkleTreeConfig};
+use sp1_stark::DIGEST_SIZE;
+use thiserror::Error;
+
+use crate::{components::SP1ProverComponents, InnerSC, SP1Prover, VkNotAllowedError};
+
+type VkDigest = [BabyBear; DIGEST_SIZE];
+
+/// An opening of the vk Merkle tree at an allowed verifying key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VkMembershipProof {
+    /// The digest of the verifying key, which is the leaf opened.
+    pub vk_digest: VkDigest,
+    /// The index of the leaf in the vk map.
+    pub index: usize,
+    /// The siblings of the path from the leaf to the root, `config.num_siblings()` per layer.
+    pub merkle_path: Vec<VkDigest>,
+    /// The arity and hash of the tree.
+    pub config: MerkleTreeConfig,
+}
+
+impl VkMembershipProof {
+    /// The number of layers of the tree, not counting the root.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.merkle_path.len() / self.config.num_siblings()
+    }
+
+    /// The bits witnessing the position of the leaf in the recursion programs, one per sibling.
+    ///
+    /// For the default binary tree, the bits are those of the index from the least significant
+    /// one. Otherwise, each layer has `config.num_siblings()` bits encoding the position of the
+    /// node among its siblings in unary.
+    #[must_use]
+    pub fn index_bits(&self) -> Vec<bool> {
+        self.merkle_proof().index_bits()
+    }
+
+    /// The `(vk_digest, index, merkle_path)` tuple of the opening.
+    #[must_use]
+    pub fn into_parts(self) -> (VkDigest, usize, Vec<VkDigest>) {
+        (self.vk_digest, self.index, self.merkle_path)
+    }
+
+    fn merkle_proof(&self) -> MerkleProof<BabyBear, InnerSC> {
+        MerkleProof { index: self.index, path: self.merkle_path.clone(), config: self.config }
+    }
+}
+
+/// An error returned by [`verify_vk_membership`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VkMembershipError {
+    #[error("unsupported vk merkle tree: {0:?}")]
+    InvalidConfig(MerkleTreeConfig),
+    #[error("the merkle path has {len} siblings, not a multiple of {num_siblings}")]
+    PathLength { len: usize, num_siblings: usize },
+    #[error("the merkle path has height {actual}, expected {expected}")]
+    Height { expected: usize, actual: usize },
+    #[error("leaf index {index} is out of range for a tree of height {height}")]
+    IndexOutOfRange { index: usize, height: usize },
+    #[error("the merkle path does not open the vk root at the vk digest")]
+    RootMismatch,
+}
+
+/// Check that `proof` opens the vk Merkle tree with root `vk_root` at `proof.vk_digest`.
+///
+/// The recursion programs are compiled for a tree height, so `expected_height` should be set to
+/// the height of the vk tree of the prover, see [`SP1Prover::vk_tree_height`], to reject openings
+/// that the programs would not accept.
+pub fn verify_vk_membership(
+    proof: &VkMembershipProof,
+    vk_root: &VkDigest,
+    expected_height: Option<usize>,
+) -> Result<(), VkMembershipError> {
+    let config = proof.config;
+    if !config.is_valid() {
+        return Err(VkMembershipError::InvalidConfig(config));
+    }
+    let num_siblings = config.num_siblings();
+    if !proof.merkle_path.len().is_multiple_of(num_siblings) {
+        return Err(VkMembershipError::PathLength { len: proof.merkle_path.len(), num_siblings });
+    }
+    let height = proof.height();
+    if let Some(expected) = expected_height.filter(|expected| *expected != height) {
+        return Err(VkMembershipError::Height { expected, actual: height });
+    }
+    // The circuit only witnesses the position of the leaf within the tree, so an index past the
+    // last leaf would verify as a different leaf.
+    let num_leaves = u32::try_from(height).ok().and_then(|height| config.arity.checked_pow(height));
+    if num_leaves.is_some_and(|num_leaves| proof.index >= num_leaves) {
+        return Err(VkMembershipError::IndexOutOfRange { index: proof.index, height });
+    }
+    MerkleTree::<BabyBear, InnerSC>::verify(proof.merkle_proof(), proof.vk_digest, *vk_root)
+        .map_err(|_| VkMembershipError::RootMismatch)
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// The height of the active vk tree, which the recursion programs are compiled for.
+    #[must_use]
+    pub fn vk_tree_height(&self) -> usize {
+        self.recursion_vk_tree.height
+    }
+
+    /// Open the active vk tree at the verifying key with digest `vk_digest`.
+    pub fn open_vk_tree(
+        &self,
+        vk_digest: &VkDigest,
+    ) -> Result<VkMembershipProof, VkNotAllowedError> {
+        self.open_vk_tree_under(&self.recursion_vk_root, vk_digest)
+    }
+
+    /// Like [`Self::open_vk_tree`], in the vk tree of `vk_root` if it is the retiring root.
+    ///
+    /// Unlike [`Self::recursion_vk_leaf`], this fails for verifying keys missing from the vk map
+    /// even without vk verification.
+    pub fn open_vk_tree_under(
+        &self,
+        vk_root: &VkDigest,
+        vk_digest: &VkDigest,
+    ) -> Result<VkMembershipProof, VkNotAllowedError> {
+        let (vk_root, vk_map, vk_tree) = self.vk_allowlist(vk_root);
+        let index = *vk_map
+            .get(vk_digest)
+            .ok_or_else(|| self.vk_not_allowed(vk_digest, vk_root, vk_map.len()))?;
+        Ok(open(vk_tree, index))
+    }
+
+    /// Open the active vk tree at every allowed verifying key, in the order of their digests.
+    pub fn vk_tree_openings(&self) -> impl Iterator<Item = VkMembershipProof> + '_ {
+        self.recursion_vk_map.values().map(|index| open(&self.recursion_vk_tree, *index))
+    }
+}
+
+fn open(vk_tree: &MerkleTree<BabyBear, InnerSC>, index: usize) -> VkMembershipProof {
+    let (vk_digest, proof) = vk_tree.open(index);
+    VkMembershipProof { vk_digest, index, merkle_path: proof.path, config: proof.config }
+}
+
+#[cfg(test)]
+mod tests {
+    use p3_field::AbstractField;
+
+    use super::*;
+    use crate::tests::unfixed_prover;
+
+    #[test]
+    fn test_vk_membership() {
+        let prover = unfixed_prover();
+        let root = prover.recursion_vk_root;
+        let height = Some(prover.vk_tree_height());
+        for proof in prover.vk_tree_openings().take(3) {
+            assert_eq!(prover.open_vk_tree(&proof.vk_digest).unwrap(), proof);
+            verify_vk_membership(&proof, &root, height).unwrap();
+        }
+
+        let proof = prover.vk_tree_openings().next().unwrap();
+        let mismatch =
+            VkMembershipProof { vk_digest: [BabyBear::zero(); DIGEST_SIZE], ..proof.clone() };
+        assert_eq!(
+            verify_vk_membership(&mismatch, &root, height),
+            Err(VkMembershipError::RootMismatch)
+        );
+        assert!(matches!(
+            verify_vk_membership(&proof, &root, height.map(|h| h + 1)),
+            Err(VkMembershipError::Height { .. })
+        ));
+        let out_of_range = VkMembershipProof { index: usize::MAX, ..proof };
+        assert!(matches!(
+            verify_vk_membership(&out_of_range, &root, height),
+            Err(VkMembershipError::IndexOutOfRange { .. })
+        ));
+
+        assert!(prover.open_vk_tree(&[BabyBear::zero(); DIGEST_SIZE]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Sharing recursion workers between concurrent compress calls.
+//!
+//! Every compress call spawns `recursion_opts.trace_gen_workers` trace generation workers and
+//! `recursion_opts.shard_batch_size` proving workers, which bounds the concurrency of that call.
+//! When several calls run at once on handles to the same prover, the [`WorkerPool`] of the prover
+//! bounds their total: each work item is tagged with the call it belongs to and holds a permit
+//! while it runs, and a freed permit goes to the call with the fewest work items running, so that
+//! no call is starved.
+
+use std::{
+    collections::HashMap,
+    env,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex, MutexGuard,
+    },
+};
+
+/// The kind of work a permit is taken for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerKind {
+    /// Executing recursion programs and generating their traces.
+    TraceGen,
+    /// Committing to traces and opening the recursion proofs.
+    Prove,
+}
+
+/// The tag of the work items of one compress call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobTag(u64);
+
+/// Bounds the number of recursion work items running at once across compress calls.
+#[derive(Debug, Default)]
+pub struct WorkerPool {
+    trace_gen: Permits,
+    prove: Permits,
+    next_job: AtomicU64,
+}
+
+impl WorkerPool {
+    /// A pool allowing at most `trace_gen` trace generation and `prove` proving work items to run
+    /// at once. `None` does not bound the corresponding work.
+    #[must_use]
+    pub fn new(trace_gen: Option<NonZeroUsize>, prove: Option<NonZeroUsize>) -> Self {
+        let pool = Self::default();
+        pool.set_limit(WorkerKind::TraceGen, trace_gen);
+        pool.set_limit(WorkerKind::Prove, prove);
+        pool
+    }
+
+    /// Read the limits from the `SP1_SHARED_TRACE_GEN_WORKERS` and `SP1_SHARED_PROVE_WORKERS`
+    /// environment variables. Work is not bounded if they are not set.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let limit = |var| env::var(var).ok().and_then(|s| s.parse::<NonZeroUsize>().ok());
+        Self::new(limit("SP1_SHARED_TRACE_GEN_WORKERS"), limit("SP1_SHARED_PROVE_WORKERS"))
+    }
+
+    fn permits(&self, kind: WorkerKind) -> &Permits {
+        match kind {
+            WorkerKind::TraceGen => &self.trace_gen,
+            WorkerKind::Prove => &self.prove,
+        }
+    }
+
+    /// The maximum number of work items of `kind` running at once, if bounded.
+    #[must_use]
+    pub fn limit(&self, kind: WorkerKind) -> Option<NonZeroUsize> {
+        self.permits(kind).lock().limit
+    }
+
+    /// Change the maximum number of work items of `kind` running at once.
+    ///
+    /// Work items that are already running are not interrupted.
+    pub fn set_limit(&self, kind: WorkerKind, limit: Option<NonZeroUsize>) {
+        let permits = self.permits(kind);
+        permits.lock().limit = limit;
+        permits.released.notify_all();
+    }
+
+    /// The number of work items of `kind` running.
+    #[must_use]
+    pub fn running(&self, kind: WorkerKind) -> usize {
+        self.permits(kind).lock().running
+    }
+
+    /// A new tag for the work items of a compress call.
+    pub fn job(&self) -> JobTag {
+        JobTag(self.next_job.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Wait for a permit to run a work item of `kind` for `job`.
+    ///
+    /// The permit is released when it is dropped. It should not be held while waiting on other
+    /// work items, or calls sharing the pool may deadlock.
+    pub fn acquire(&self, kind: WorkerKind, job: JobTag) -> WorkerPermit<'_> {
+        let permits = self.permits(kind);
+        let mut state = permits.lock();
+        *state.waiting.entry(job).or_default() += 1;
+        while !state.can_run(job) {
+            state = permits.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        match state.waiting.get_mut(&job) {
+            Some(1) => {
+                state.waiting.remove(&job);
+            }
+            Some(waiting) => *waiting -= 1,
+            None => unreachable!("the waiting count of a job is only decremented by its waiters"),
+        }
+        state.running += 1;
+        *state.running_by_job.entry(job).or_default() += 1;
+        WorkerPermit { permits, job }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Permits {
+    state: Mutex<PermitState>,
+    released: Condvar,
+}
+
+impl Permits {
+    fn lock(&self) -> MutexGuard<'_, PermitState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Debug, Default)]
+struct PermitState {
+    limit: Option<NonZeroUsize>,
+    running: usize,
+    running_by_job: HashMap<JobTag, usize>,
+    waiting: HashMap<JobTag, usize>,
+}
+
+impl PermitState {
+    /// Whether a waiter of `job` may take a permit: one must be free, and no other waiting job
+    /// may have fewer work items running.
+    fn can_run(&self, job: JobTag) -> bool {
+        if self.limit.is_some_and(|limit| self.running >= limit.get()) {
+            return false;
+        }
+        let running = |job| self.running_by_job.get(job).copied().unwrap_or(0);
+        let own = running(&job);
+        self.waiting.keys().all(|other| running(other) >= own)
+    }
+}
+
+/// A permit to run one work item, released when dropped.
+#[derive(Debug)]
+pub struct WorkerPermit<'a> {
+    permits: &'a Permits,
+    job: JobTag,
+}
+
+impl Drop for WorkerPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.permits.lock();
+        state.running -= 1;
+        if let Some(running) = state.running_by_job.get_mut(&self.job) {
+            *running -= 1;
+            if *running == 0 {
+                state.running_by_job.remove(&self.job);
+            }
+        }
+        drop(state);
+        self.permits.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn test_worker_pool_limit() {
+        let pool = WorkerPool::new(NonZeroUsize::new(1), None);
+        let job = pool.job();
+        assert_eq!(pool.limit(WorkerKind::Prove), None);
+
+        let permit = pool.acquire(WorkerKind::TraceGen, job);
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                let _permit = pool.acquire(WorkerKind::TraceGen, pool.job());
+            });
+            // Proving is not bounded, and the trace generation waiter is blocked on the permit.
+            let _prove = pool.acquire(WorkerKind::Prove, job);
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(pool.running(WorkerKind::TraceGen), 1);
+            assert!(!waiter.is_finished());
+
+            drop(permit);
+            waiter.join().unwrap();
+        });
+        assert_eq!(pool.running(WorkerKind::TraceGen), 0);
+        assert_eq!(pool.running(WorkerKind::Prove), 0);
+    }
+
+    #[test]
+    fn test_worker_pool_fairness() {
+        let (a, b) = (JobTag(0), JobTag(1));
+        let mut state = PermitState {
+            limit: NonZeroUsize::new(2),
+            running: 1,
+            running_by_job: HashMap::from([(a, 1)]),
+            waiting: HashMap::from([(a, 1), (b, 1)]),
+        };
+
+        // The free permit goes to the job with fewer work items running.
+        assert!(!state.can_run(a));
+        assert!(state.can_run(b));
+
+        // No permit is free once the limit is reached.
+        state.running = 2;
+        state.running_by_job.insert(b, 1);
+        state.waiting.remove(&b);
+        assert!(!state.can_run(a));
+    }
+}
@@ -0,0 +1,290 @@
+//! Caching of wrapped BN254 proofs.
+//!
+//! Wrapping a shrink proof into a PLONK or Groth16 proof is the most expensive step of the
+//! pipeline, and retried jobs repeat it for the exact same input. A [`WrapProofCache`] stores the
+//! result keyed by the digest of the shrink proof, the circuit version and the proof system, so a
+//! retry can skip both the outer STARK and the gnark prover. Cached proofs are always verified
+//! again against the shrink proof before they are returned, so a corrupted or foreign entry is
+//! never handed out.
+
+use std::{
+    borrow::Borrow,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use num_bigint::BigUint;
+use p3_baby_bear::BabyBear;
+use p3_field::PrimeField;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use sp1_recursion_core::air::RecursionPublicValues;
+use sp1_recursion_gnark_ffi::{
+    groth16_bn254::Groth16Bn254Prover, plonk_bn254::PlonkBn254Prover, Groth16Bn254Proof,
+    PlonkBn254Proof,
+};
+use sp1_stark::SP1ProverOpts;
+
+use crate::{
+    components::SP1ProverComponents,
+    encryption::ArtifactCipher,
+    types::ProofSystem,
+    utils::{babybear_bytes_to_bn254, babybears_to_bn254, words_to_bytes},
+    GnarkLimits, InnerSC, OuterSC, SP1Prover, SP1RecursionProverError, SP1ReduceProof,
+    SP1_CIRCUIT_VERSION,
+};
+
+/// A key-value store for wrapped proofs, also used by the
+/// [`SetupCache`](crate::setup_cache::SetupCache).
+///
+/// Implement this to keep the cache in an object store shared between machines.
+pub trait WrapProofStore: Send + Sync {
+    /// Fetch the value stored under `key`, if any.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`, replacing any previous value.
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()>;
+}
+
+/// A [`WrapProofStore`] that keeps one file per proof in a directory.
+#[derive(Debug, Clone)]
+pub struct DiskWrapProofStore {
+    dir: PathBuf,
+}
+
+impl DiskWrapProofStore {
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.bin"))
+    }
+}
+
+impl WrapProofStore for DiskWrapProofStore {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        // Write to a temporary file first, so that readers never see a partial proof.
+        let tmp = self.dir.join(format!("{key}.{}.tmp", std::process::id()));
+        fs::write(&tmp, value)?;
+        fs::rename(tmp, self.path(key))
+    }
+}
+
+/// A cache of wrapped proofs, keyed by the shrink proof they wrap.
+pub struct WrapProofCache {
+    entries: CacheEntries,
+}
+
+impl WrapProofCache {
+    pub fn new(store: impl WrapProofStore + 'static) -> Self {
+        Self { entries: CacheEntries::new("wrap cache", store) }
+    }
+
+    /// Encrypt the entries of the cache with `cipher`.
+    ///
+    /// Entries written without the cipher, or with another key, are treated as misses.
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: ArtifactCipher) -> Self {
+        self.entries.cipher = Some(cipher);
+        self
+    }
+
+    /// A cache backed by the files in `dir`.
+    pub fn on_disk(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        Ok(Self::new(DiskWrapProofStore::new(dir)?))
+    }
+
+    /// The key of the wrapped proof of `shrink_proof` in `system`.
+    ///
+    /// This is the SHA-256 digest of the serialized shrink proof, the circuit version and the
+    /// proof system, so that proofs made with other circuit artifacts are never reused.
+    pub fn key(
+        shrink_proof: &SP1ReduceProof<InnerSC>,
+        system: ProofSystem,
+    ) -> Result<String, bincode::Error> {
+        let mut hasher = Sha256::new();
+        hasher.update(bincode::serialize(shrink_proof)?);
+        hasher.update(SP1_CIRCUIT_VERSION.as_bytes());
+        hasher.update(system.as_str().as_bytes());
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+/// The bincode encoded entries of a cache in a [`WrapProofStore`], optionally encrypted.
+pub(crate) struct CacheEntries {
+    name: &'static str,
+    store: Box<dyn WrapProofStore>,
+    pub(crate) cipher: Option<ArtifactCipher>,
+}
+
+impl CacheEntries {
+    pub(crate) fn new(name: &'static str, store: impl WrapProofStore + 'static) -> Self {
+        Self { name, store: Box::new(store), cipher: None }
+    }
+
+    /// Fetch and decode the entry under `key`, treating any failure as a miss.
+    pub(crate) fn load<P: DeserializeOwned>(&self, key: &str) -> Option<P> {
+        let name = self.name;
+        let bytes = match self.store.get(key) {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                tracing::warn!("failed to read {} entry {}: {}", name, key, e);
+                return None;
+            }
+        };
+        let bytes = match &self.cipher {
+            Some(cipher) => cipher
+                .open(&bytes)
+                .map_err(|e| tracing::warn!("failed to decrypt {} entry {}: {}", name, key, e))
+                .ok()?,
+            None => bytes,
+        };
+        bincode::deserialize(&bytes)
+            .map_err(|e| tracing::warn!("failed to decode {} entry {}: {}", name, key, e))
+            .ok()
+    }
+
+    /// Store `value` under `key`. Failures are logged, since the value is still valid.
+    pub(crate) fn store<P: Serialize>(&self, key: &str, value: &P) {
+        let result = bincode::serialize(value)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|bytes| match &self.cipher {
+                Some(cipher) => cipher.seal(&bytes),
+                None => Ok(bytes),
+            })
+            .and_then(|bytes| self.store.put(key, &bytes));
+        if let Err(e) = result {
+            tracing::warn!("failed to write {} entry {}: {}", self.name, key, e);
+        }
+    }
+}
+
+/// The vkey hash and committed values digest the wrapped proof of `shrink_proof` commits to.
+pub(crate) fn wrap_public_inputs(shrink_proof: &SP1ReduceProof<InnerSC>) -> (BigUint, BigUint) {
+    let pv: &RecursionPublicValues<BabyBear> = shrink_proof.proof.public_values.as_slice().borrow();
+    let committed_values_digest_bytes: [BabyBear; 32] =
+        words_to_bytes(&pv.committed_value_digest).try_into().unwrap();
+    (
+        babybears_to_bn254(&pv.sp1_vk_digest).as_canonical_biguint(),
+        babybear_bytes_to_bn254(&committed_values_digest_bytes).as_canonical_biguint(),
+    )
+}
+
+impl<C: SP1ProverComponents> SP1Prover<C> {
+    /// Wrap a shrink proof into a PLONK proof, reusing the proof in `cache` if there is one.
+    pub fn wrap_plonk_bn254_cached(
+        &self,
+        shrink_proof: SP1ReduceProof<InnerSC>,
+        opts: SP1ProverOpts,
+        build_dir: &Path,
+        cache: &WrapProofCache,
+    ) -> Result<PlonkBn254Proof, SP1RecursionProverError> {
+        self.wrap_cached(
+            shrink_proof,
+            opts,
+            cache,
+            ProofSystem::Plonk,
+            |outer| self.try_wrap_plonk_bn254(outer, build_dir, &GnarkLimits::from_env()),
+            |proof, vkey_hash, committed_values_digest| {
+                PlonkBn254Prover::new().verify(proof, vkey_hash, committed_values_digest, build_dir)
+            },
+        )
+    }
+
+    /// Wrap a shrink proof into a Groth16 proof, reusing the proof in `cache` if there is one.
+    pub fn wrap_groth16_bn254_cached(
+        &self,
+        shrink_proof: SP1ReduceProof<InnerSC>,
+        opts: SP1ProverOpts,
+        build_dir: &Path,
+        cache: &WrapProofCache,
+    ) -> Result<Groth16Bn254Proof, SP1RecursionProverError> {
+        self.wrap_cached(
+            shrink_proof,
+            opts,
+            cache,
+            ProofSystem::Groth16,
+            |outer| self.try_wrap_groth16_bn254(outer, build_dir, &GnarkLimits::from_env()),
+            |proof, vkey_hash, committed_values_digest| {
+                Groth16Bn254Prover::new().verify(
+                    proof,
+                    vkey_hash,
+                    committed_values_digest,
+                    build_dir,
+                )
+            },
+        )
+    }
+
+    fn wrap_cached<P: Serialize + DeserializeOwned>(
+        &self,
+        shrink_proof: SP1ReduceProof<InnerSC>,
+        opts: SP1ProverOpts,
+        cache: &WrapProofCache,
+        system: ProofSystem,
+        prove: impl FnOnce(SP1ReduceProof<OuterSC>) -> Result<P, SP1RecursionProverError>,
+        verify: impl Fn(&P, &BigUint, &BigUint) -> anyhow::Result<()>,
+    ) -> Result<P, SP1RecursionProverError> {
+        let key = WrapProofCache::key(&shrink_proof, system)
+            .map_err(|e| SP1RecursionProverError::RuntimeError(e.to_string()))?;
+
+        if let Some(proof) = cache.entries.load::<P>(&key) {
+            let (vkey_hash, committed_values_digest) = wrap_public_inputs(&shrink_proof);
+            match verify(&proof, &vkey_hash, &committed_values_digest) {
+                Ok(()) => {
+                    tracing::info!("reusing cached {} proof {}", system.as_str(), key);
+                    return Ok(proof);
+                }
+                Err(e) => tracing::warn!("discarding invalid wrap cache entry {}: {}", key, e),
+            }
+        }
+
+        let outer = self.wrap_bn254(shrink_proof, opts)?;
+        let proof = prove(outer)?;
+        cache.entries.store(&key, &proof);
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp1_stark::{air::MachineAir, shape::OrderedShape};
+
+    use super::*;
+    use crate::CompressAir;
+
+    #[test]
+    fn test_wrap_proof_cache() {
+        let machine = CompressAir::<BabyBear>::compress_machine(InnerSC::default());
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        let (vk, proof) = sp1_recursion_circuit::stark::dummy_vk_and_shard_proof(&machine, &shape);
+        let shrink_proof = SP1ReduceProof { vk, proof };
+
+        // The key depends on the proof system.
+        let key = WrapProofCache::key(&shrink_proof, ProofSystem::Plonk).unwrap();
+        assert_eq!(key, WrapProofCache::key(&shrink_proof, ProofSystem::Plonk).unwrap());
+        assert_ne!(key, WrapProofCache::key(&shrink_proof, ProofSystem::Groth16).unwrap());
+
+        let dir = std::env::temp_dir().join(format!("sp1-wrap-cache-{}", std::process::id()));
+        let cache = WrapProofCache::on_disk(&dir).unwrap();
+        assert_eq!(cache.entries.load::<Vec<u32>>(&key), None);
+        cache.entries.store(&key, &vec![1u32, 2, 3]);
+        assert_eq!(cache.entries.load::<Vec<u32>>(&key), Some(vec![1, 2, 3]));
+
+        // Entries that fail to decode are treated as misses.
+        cache.entries.store.put(&key, &[1]).unwrap();
+        assert_eq!(cache.entries.load::<Vec<u32>>(&key), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
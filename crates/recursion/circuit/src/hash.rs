@@ -28,6 +28,9 @@ pub trait FieldHasher<F: Field> {
     type Digest: Copy + Default + Eq + Ord + Copy + Debug + Send + Sync;
 
     fn constant_compress(input: [Self::Digest; 2]) -> Self::Digest;
+
+    /// Hashes any number of digests with a sponge.
+    fn constant_hash(input: &[Self::Digest]) -> Self::Digest;
 }
 
 pub trait Posedion2BabyBearHasherVariable<C: CircuitConfig> {
@@ -59,6 +62,12 @@ pub trait FieldHasherVariable<C: CircuitConfig>: FieldHasher<C::F> {
     fn compress(builder: &mut Builder<C>, input: [Self::DigestVariable; 2])
         -> Self::DigestVariable;
 
+    /// Hashes any number of digests with a sponge, matching [`FieldHasher::constant_hash`].
+    fn hash_digests(
+        builder: &mut Builder<C>,
+        input: &[Self::DigestVariable],
+    ) -> Self::DigestVariable;
+
     fn assert_digest_eq(builder: &mut Builder<C>, a: Self::DigestVariable, b: Self::DigestVariable);
 
     // Encountered many issues trying to make the following two parametrically polymorphic.
@@ -80,6 +89,16 @@ impl FieldHasher<BabyBear> for BabyBearPoseidon2 {
         (inner_perm()).permute_mut(&mut pre);
         pre[..DIGEST_SIZE].try_into().unwrap()
     }
+
+    fn constant_hash(input: &[Self::Digest]) -> Self::Digest {
+        let input = input.iter().flatten().copied().collect::<Vec<_>>();
+        let mut state = [BabyBear::zero(); PERMUTATION_WIDTH];
+        for input_chunk in input.chunks(HASH_RATE) {
+            state[..input_chunk.len()].copy_from_slice(input_chunk);
+            inner_perm().permute_mut(&mut state);
+        }
+        state[..DIGEST_SIZE].try_into().unwrap()
+    }
 }
 
 impl<C: CircuitConfig<F = BabyBear>> Posedion2BabyBearHasherVariable<C> for BabyBearPoseidon2 {
@@ -118,6 +137,14 @@ impl<C: CircuitConfig<F = BabyBear, Bit = Felt<BabyBear>>> FieldHasherVariable<C
         builder.poseidon2_compress_v2(input.into_iter().flatten())
     }
 
+    fn hash_digests(
+        builder: &mut Builder<C>,
+        input: &[Self::DigestVariable],
+    ) -> Self::DigestVariable {
+        let input = input.iter().flatten().copied().collect::<Vec<_>>();
+        <Self as Posedion2BabyBearHasherVariable<C>>::poseidon2_hash(builder, &input)
+    }
+
     fn assert_digest_eq(
         builder: &mut Builder<C>,
         a: Self::DigestVariable,
@@ -166,6 +193,17 @@ impl FieldHasher<BabyBear> for BabyBearPoseidon2Outer {
         outer_perm().permute_mut(&mut state);
         [state[0]; BN254_DIGEST_SIZE]
     }
+
+    fn constant_hash(input: &[Self::Digest]) -> Self::Digest {
+        let mut state = [Bn254Fr::zero(); OUTER_MULTI_FIELD_CHALLENGER_WIDTH];
+        for input_chunk in input.chunks(OUTER_MULTI_FIELD_CHALLENGER_WIDTH - 1) {
+            for (i, digest) in input_chunk.iter().enumerate() {
+                state[i] = digest[0];
+            }
+            outer_perm().permute_mut(&mut state);
+        }
+        [state[0]; BN254_DIGEST_SIZE]
+    }
 }
 
 impl<C: CircuitConfig<F = BabyBear, N = Bn254Fr, Bit = Var<Bn254Fr>>> FieldHasherVariable<C>
@@ -200,6 +238,21 @@ impl<C: CircuitConfig<F = BabyBear, N = Bn254Fr, Bit = Var<Bn254Fr>>> FieldHashe
         [state[0]; BN254_DIGEST_SIZE]
     }
 
+    fn hash_digests(
+        builder: &mut Builder<C>,
+        input: &[Self::DigestVariable],
+    ) -> Self::DigestVariable {
+        let mut state: [Var<C::N>; OUTER_MULTI_FIELD_CHALLENGER_WIDTH] =
+            [builder.eval(C::N::zero()), builder.eval(C::N::zero()), builder.eval(C::N::zero())];
+        for input_chunk in input.chunks(OUTER_MULTI_FIELD_CHALLENGER_WIDTH - 1) {
+            for (i, digest) in input_chunk.iter().enumerate() {
+                state[i] = builder.eval(digest[0]);
+            }
+            builder.push_op(DslIr::CircuitPoseidon2Permute(state));
+        }
+        [state[0]; BN254_DIGEST_SIZE]
+    }
+
     fn assert_digest_eq(
         builder: &mut Builder<C>,
         a: Self::DigestVariable,
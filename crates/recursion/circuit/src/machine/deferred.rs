@@ -30,6 +30,7 @@ use crate::{
     constraints::RecursiveVerifierConstraintFolder,
     hash::{FieldHasher, FieldHasherVariable},
     machine::assert_recursion_public_values_valid,
+    merkle_tree::MerkleTreeConfig,
     stark::{ShardProofVariable, StarkVerifier},
     BabyBearFriConfig, BabyBearFriConfigVariable, CircuitConfig, VerifyingKeyVariable,
 };
@@ -47,6 +48,7 @@ pub struct SP1DeferredVerifier<C, SC, A> {
 pub struct SP1DeferredShape {
     inner: SP1CompressShape,
     height: usize,
+    merkle_tree_config: MerkleTreeConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -257,7 +259,11 @@ impl SP1DeferredWitnessValues<BabyBearPoseidon2> {
             SP1CompressWitnessValues::<BabyBearPoseidon2>::dummy(machine, &shape.inner);
         let vks_and_proofs = inner_witness.vks_and_proofs;
 
-        let vk_merkle_data = SP1MerkleProofWitnessValues::dummy(vks_and_proofs.len(), shape.height);
+        let vk_merkle_data = SP1MerkleProofWitnessValues::dummy(
+            vks_and_proofs.len(),
+            shape.height,
+            shape.merkle_tree_config,
+        );
 
         Self {
             vks_and_proofs,
@@ -277,7 +283,11 @@ impl SP1DeferredWitnessValues<BabyBearPoseidon2> {
 }
 
 impl SP1DeferredShape {
-    pub const fn new(inner: SP1CompressShape, height: usize) -> Self {
-        Self { inner, height }
+    pub const fn new(
+        inner: SP1CompressShape,
+        height: usize,
+        merkle_tree_config: MerkleTreeConfig,
+    ) -> Self {
+        Self { inner, height, merkle_tree_config }
     }
 }
@@ -17,7 +17,7 @@ use crate::{
     challenger::DuplexChallengerVariable,
     constraints::RecursiveVerifierConstraintFolder,
     hash::{FieldHasher, FieldHasherVariable},
-    merkle_tree::{verify, MerkleProof},
+    merkle_tree::{verify, MerkleProof, MerkleTreeConfig},
     stark::MerkleProofVariable,
     witness::{WitnessWriter, Witnessable},
     BabyBearFriConfig, BabyBearFriConfigVariable, CircuitConfig, TwoAdicPcsProofVariable,
@@ -39,6 +39,7 @@ pub struct SP1MerkleProofVerifier<C, SC> {
 pub struct SP1CompressWithVkeyShape {
     pub compress_shape: SP1CompressShape,
     pub merkle_tree_height: usize,
+    pub merkle_tree_config: MerkleTreeConfig,
 }
 
 /// Witness layout for the compress stage verifier.
@@ -144,16 +145,20 @@ where
 
 impl<SC: BabyBearFriConfig + FieldHasher<BabyBear>> SP1CompressWithVKeyWitnessValues<SC> {
     pub fn shape(&self) -> SP1CompressWithVkeyShape {
-        let merkle_tree_height = self.merkle_val.vk_merkle_proofs.first().unwrap().path.len();
-        SP1CompressWithVkeyShape { compress_shape: self.compress_val.shape(), merkle_tree_height }
+        let merkle_proof = self.merkle_val.vk_merkle_proofs.first().unwrap();
+        SP1CompressWithVkeyShape {
+            compress_shape: self.compress_val.shape(),
+            merkle_tree_height: merkle_proof.height(),
+            merkle_tree_config: merkle_proof.config,
+        }
     }
 }
 
 impl SP1MerkleProofWitnessValues<BabyBearPoseidon2> {
-    pub fn dummy(num_proofs: usize, height: usize) -> Self {
+    pub fn dummy(num_proofs: usize, height: usize, config: MerkleTreeConfig) -> Self {
         let dummy_digest = [BabyBear::zero(); DIGEST_SIZE];
-        let vk_merkle_proofs =
-            vec![MerkleProof { index: 0, path: vec![dummy_digest; height] }; num_proofs];
+        let path = vec![dummy_digest; height * config.num_siblings()];
+        let vk_merkle_proofs = vec![MerkleProof { index: 0, path, config }; num_proofs];
         let values = vec![dummy_digest; num_proofs];
 
         Self { vk_merkle_proofs, values, root: dummy_digest }
@@ -171,6 +176,7 @@ impl SP1CompressWithVKeyWitnessValues<BabyBearPoseidon2> {
         let merkle_val = SP1MerkleProofWitnessValues::<BabyBearPoseidon2>::dummy(
             num_proofs,
             shape.merkle_tree_height,
+            shape.merkle_tree_config,
         );
         Self { compress_val, merkle_val }
     }
@@ -222,23 +222,15 @@ where
     type WitnessVariable = MerkleProofVariable<C, HV>;
 
     fn read(&self, builder: &mut Builder<C>) -> Self::WitnessVariable {
-        let mut bits = vec![];
-        let mut index = self.index;
-        for _ in 0..self.path.len() {
-            bits.push(index % 2 == 1);
-            index >>= 1;
-        }
-        let index_bits = bits.read(builder);
+        let index_bits = self.index_bits().read(builder);
         let path = self.path.read(builder);
 
-        MerkleProofVariable { index: index_bits, path }
+        MerkleProofVariable { index: index_bits, path, config: self.config }
     }
 
     fn write(&self, witness: &mut impl WitnessWriter<C>) {
-        let mut index = self.index;
-        for _ in 0..self.path.len() {
-            (index % 2 == 1).write(witness);
-            index >>= 1;
+        for bit in self.index_bits() {
+            bit.write(witness);
         }
         self.path.write(witness);
     }
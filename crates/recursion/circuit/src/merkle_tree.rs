@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, iter};
 
 use rayon::prelude::*;
 
@@ -14,17 +14,97 @@ use crate::{
     CircuitConfig,
 };
 
+/// How the children of a node of a [`MerkleTree`] are hashed into the node.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum MerkleHash {
+    /// The two children are compressed with a single permutation. Only binary trees support it.
+    #[default]
+    Compress,
+    /// The children are absorbed into a sponge, which supports any arity.
+    Sponge,
+}
+
+/// The arity and hash of a [`MerkleTree`].
+///
+/// A tree of higher arity is shallower, so its proofs have fewer layers, each with more siblings.
+/// The default is a binary tree whose leaves are stored in bit-reversed order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MerkleTreeConfig {
+    /// The number of children of every node.
+    pub arity: usize,
+    pub hash: MerkleHash,
+}
+
+impl Default for MerkleTreeConfig {
+    fn default() -> Self {
+        Self { arity: 2, hash: MerkleHash::Compress }
+    }
+}
+
+impl MerkleTreeConfig {
+    /// Whether the arity is at least two and supported by the hash.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        match self.hash {
+            MerkleHash::Compress => self.arity == 2,
+            MerkleHash::Sponge => self.arity >= 2,
+        }
+    }
+
+    /// The number of siblings of a node, which is the number of digests per layer of a proof.
+    #[must_use]
+    pub const fn num_siblings(&self) -> usize {
+        self.arity - 1
+    }
+
+    /// The height of a tree with `num_leaves` leaves.
+    #[must_use]
+    pub fn height(&self, num_leaves: usize) -> usize {
+        if self.is_bit_reversed() {
+            return num_leaves.next_power_of_two().ilog2() as usize;
+        }
+        let (mut height, mut len) = (1, self.arity);
+        while len < num_leaves {
+            height += 1;
+            len *= self.arity;
+        }
+        height
+    }
+
+    /// Hash the children of a node.
+    pub fn hash_children<F: Field, HV: FieldHasher<F>>(
+        &self,
+        children: &[HV::Digest],
+    ) -> HV::Digest {
+        match self.hash {
+            MerkleHash::Compress => HV::constant_compress([children[0], children[1]]),
+            MerkleHash::Sponge => HV::constant_hash(children),
+        }
+    }
+
+    /// Whether the tree is the default binary tree, whose leaves are stored in bit-reversed order.
+    fn is_bit_reversed(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(bound(serialize = "HV::Digest: Serialize"))]
 #[serde(bound(deserialize = "HV::Digest: Deserialize<'de>"))]
 pub struct MerkleTree<F: Field, HV: FieldHasher<F>> {
-    /// The height of the tree, not counting the root layer. This is the same as the logarithm of
-    /// the number of leaves.
+    /// The height of the tree, not counting the root layer. This is the same as the logarithm in
+    /// base `config.arity` of the number of leaves.
     pub height: usize,
 
-    /// All the layers but the root. If there are `n` leaves where `n` is a power of 2, there are
-    /// `2n - 2` elements in this vector. The leaves are at the beginning of the vector.
+    /// All the layers but the root. If there are `n` leaves where `n` is a power of the arity `k`,
+    /// there are `(kn - k) / (k - 1)` elements in this vector. The leaves are at the beginning of
+    /// the vector.
     pub digest_layers: Vec<HV::Digest>,
+
+    #[serde(default)]
+    pub config: MerkleTreeConfig,
 }
 pub struct VcsError;
 
@@ -33,7 +113,35 @@ pub struct VcsError;
 #[serde(bound(deserialize = "HV::Digest: Deserialize<'de>"))]
 pub struct MerkleProof<F: Field, HV: FieldHasher<F>> {
     pub index: usize,
+    /// The siblings of the path from the leaf to the root, `config.num_siblings()` per layer.
     pub path: Vec<HV::Digest>,
+    #[serde(default)]
+    pub config: MerkleTreeConfig,
+}
+
+impl<F: Field, HV: FieldHasher<F>> MerkleProof<F, HV> {
+    /// The number of layers of the tree the proof was opened from.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.path.len() / self.config.num_siblings()
+    }
+
+    /// The bits witnessing the position of the leaf in the circuit, see [`verify`].
+    #[must_use]
+    pub fn index_bits(&self) -> Vec<bool> {
+        if self.config.is_bit_reversed() {
+            return (0..self.path.len()).map(|i| (self.index >> i) % 2 == 1).collect();
+        }
+        // The position of the node among its siblings in every layer, in unary.
+        let mut index = self.index;
+        let mut bits = Vec::with_capacity(self.path.len());
+        for _ in 0..self.height() {
+            let position = index % self.config.arity;
+            bits.extend((0..self.config.num_siblings()).map(|i| i < position));
+            index /= self.config.arity;
+        }
+        bits
+    }
 }
 
 impl Debug for VcsError {
@@ -44,6 +152,23 @@ impl Debug for VcsError {
 
 impl<F: Field, HV: FieldHasher<F>> MerkleTree<F, HV> {
     pub fn commit(leaves: Vec<HV::Digest>) -> (HV::Digest, Self) {
+        Self::commit_with_config(leaves, MerkleTreeConfig::default())
+    }
+
+    /// Commit to `leaves` with a tree of the given arity and hash.
+    pub fn commit_with_config(
+        leaves: Vec<HV::Digest>,
+        config: MerkleTreeConfig,
+    ) -> (HV::Digest, Self) {
+        assert!(config.is_valid(), "invalid merkle tree config {config:?}");
+        if config.is_bit_reversed() {
+            Self::commit_bit_reversed(leaves)
+        } else {
+            Self::commit_in_order(leaves, config)
+        }
+    }
+
+    fn commit_bit_reversed(leaves: Vec<HV::Digest>) -> (HV::Digest, Self) {
         assert!(!leaves.is_empty());
         let new_len = leaves.len().next_power_of_two();
         let height = log2_strict_usize(new_len);
@@ -81,10 +206,38 @@ impl<F: Field, HV: FieldHasher<F>> MerkleTree<F, HV> {
         debug_assert_eq!(digest_layers.len(), 2 * new_len - 2);
 
         let root = HV::constant_compress([last_layer[0], last_layer[1]]);
-        (root, Self { height, digest_layers })
+        (root, Self { height, digest_layers, config: MerkleTreeConfig::default() })
+    }
+
+    /// Commit to `leaves` stored in order, padded to a power of the arity.
+    fn commit_in_order(
+        mut leaves: Vec<HV::Digest>,
+        config: MerkleTreeConfig,
+    ) -> (HV::Digest, Self) {
+        assert!(!leaves.is_empty());
+        let arity = config.arity;
+        let height = config.height(leaves.len());
+        leaves.resize(arity.pow(height as u32), HV::Digest::default());
+
+        let mut digest_layers = leaves.clone();
+        let mut last_layer = leaves;
+        for _ in 0..height - 1 {
+            let next_layer = last_layer
+                .par_chunks_exact(arity)
+                .map(|children| config.hash_children::<F, HV>(children))
+                .collect::<Vec<_>>();
+            digest_layers.extend(next_layer.iter());
+            last_layer = next_layer;
+        }
+
+        let root = config.hash_children::<F, HV>(&last_layer);
+        (root, Self { height, digest_layers, config })
     }
 
     pub fn open(&self, index: usize) -> (HV::Digest, MerkleProof<F, HV>) {
+        if !self.config.is_bit_reversed() {
+            return self.open_in_order(index);
+        }
         let mut path = Vec::with_capacity(self.height);
         let mut bit_rev_index = reverse_bits_len(index, self.height);
         let value = self.digest_layers[bit_rev_index];
@@ -104,7 +257,31 @@ impl<F: Field, HV: FieldHasher<F>> MerkleTree<F, HV> {
             offset += 1 << (self.height - i);
         }
         debug_assert_eq!(path.len(), self.height);
-        (value, MerkleProof { index, path })
+        (value, MerkleProof { index, path, config: self.config })
+    }
+
+    fn open_in_order(&self, index: usize) -> (HV::Digest, MerkleProof<F, HV>) {
+        let arity = self.config.arity;
+        let mut path = Vec::with_capacity(self.height * self.config.num_siblings());
+        let value = self.digest_layers[index];
+
+        // The position of the current node in its layer, and the index of the first element and
+        // the number of elements of the layer.
+        let mut position = index;
+        let mut offset = 0;
+        let mut layer_len = arity.pow(self.height as u32);
+        for _ in 0..self.height {
+            let first = position - position % arity;
+            path.extend(
+                (first..first + arity)
+                    .filter(|&i| i != position)
+                    .map(|i| self.digest_layers[offset + i]),
+            );
+            position /= arity;
+            offset += layer_len;
+            layer_len /= arity;
+        }
+        (value, MerkleProof { index, path, config: self.config })
     }
 
     pub fn verify(
@@ -112,7 +289,10 @@ impl<F: Field, HV: FieldHasher<F>> MerkleTree<F, HV> {
         value: HV::Digest,
         commitment: HV::Digest,
     ) -> Result<(), VcsError> {
-        let MerkleProof { index, path } = proof;
+        let MerkleProof { index, path, config } = proof;
+        if !config.is_bit_reversed() {
+            return Self::verify_in_order(index, &path, config, value, commitment);
+        }
 
         let mut value = value;
 
@@ -130,6 +310,30 @@ impl<F: Field, HV: FieldHasher<F>> MerkleTree<F, HV> {
             Err(VcsError)
         }
     }
+
+    fn verify_in_order(
+        index: usize,
+        path: &[HV::Digest],
+        config: MerkleTreeConfig,
+        mut value: HV::Digest,
+        commitment: HV::Digest,
+    ) -> Result<(), VcsError> {
+        if !config.is_valid() || path.len() % config.num_siblings() != 0 {
+            return Err(VcsError);
+        }
+        let mut position = index;
+        for siblings in path.chunks_exact(config.num_siblings()) {
+            let mut children = siblings.to_vec();
+            children.insert(position % config.arity, value);
+            value = config.hash_children::<F, HV>(&children);
+            position /= config.arity;
+        }
+        if value == commitment {
+            Ok(())
+        } else {
+            Err(VcsError)
+        }
+    }
 }
 
 pub fn verify<C: CircuitConfig, HV: FieldHasherVariable<C>>(
@@ -139,12 +343,34 @@ pub fn verify<C: CircuitConfig, HV: FieldHasherVariable<C>>(
     commitment: HV::DigestVariable,
 ) {
     let mut value = value;
-    for (sibling, bit) in proof.path.iter().zip(proof.index.iter().rev()) {
-        let sibling = *sibling;
+    if proof.config.is_bit_reversed() {
+        for (sibling, bit) in proof.path.iter().zip(proof.index.iter().rev()) {
+            let sibling = *sibling;
 
-        // If the index is odd, swap the order of [value, sibling].
-        let new_pair = HV::select_chain_digest(builder, *bit, [value, sibling]);
-        value = HV::compress(builder, new_pair);
+            // If the index is odd, swap the order of [value, sibling].
+            let new_pair = HV::select_chain_digest(builder, *bit, [value, sibling]);
+            value = HV::compress(builder, new_pair);
+        }
+    } else {
+        let num_siblings = proof.config.num_siblings();
+        for (siblings, bits) in
+            proof.path.chunks_exact(num_siblings).zip(proof.index.chunks_exact(num_siblings))
+        {
+            // Move the value past the siblings that come before it, one swap per sibling. The bits
+            // are the position of the value in unary, see [`MerkleProof::index_bits`].
+            let mut children =
+                iter::once(value).chain(siblings.iter().copied()).collect::<Vec<_>>();
+            for (i, bit) in bits.iter().enumerate() {
+                let [left, right] =
+                    HV::select_chain_digest(builder, *bit, [children[i], children[i + 1]]);
+                children[i] = left;
+                children[i + 1] = right;
+            }
+            value = match proof.config.hash {
+                MerkleHash::Compress => HV::compress(builder, [children[0], children[1]]),
+                MerkleHash::Sponge => HV::hash_digests(builder, &children),
+            };
+        }
     }
     HV::assert_digest_eq(builder, value, commitment);
 }
@@ -165,7 +391,7 @@ mod tests {
     use zkhash::ark_ff::UniformRand;
 
     use crate::{
-        merkle_tree::{verify, MerkleTree},
+        merkle_tree::{verify, MerkleHash, MerkleTree, MerkleTreeConfig},
         stark::MerkleProofVariable,
         utils::tests::run_test_recursion,
         CircuitConfig,
@@ -205,6 +431,7 @@ mod tests {
                     let proof_variable = MerkleProofVariable::<InnerConfig, BabyBearPoseidon2> {
                         index: index_bits,
                         path: path_variable,
+                        config: proof.config,
                     };
 
                     verify::<InnerConfig, BabyBearPoseidon2>(
@@ -219,4 +446,50 @@ mod tests {
 
         run_test_recursion(builder.into_root_block(), std::iter::empty());
     }
+
+    #[test]
+    fn test_merkle_tree_arity() {
+        let mut rng = OsRng;
+        let mut builder = Builder::<InnerConfig>::default();
+        for arity in 2..5 {
+            let config = MerkleTreeConfig { arity, hash: MerkleHash::Sponge };
+            for j in [1, arity, arity + 1, 20] {
+                let leaves: Vec<[F; DIGEST_SIZE]> =
+                    (0..j).map(|_| std::array::from_fn(|_| F::rand(&mut rng))).collect();
+                let (root, tree) =
+                    MerkleTree::<BabyBear, HV>::commit_with_config(leaves.clone(), config);
+                for (i, leaf) in leaves.iter().enumerate() {
+                    let (value, proof) = tree.open(i);
+                    assert_eq!(value, *leaf);
+                    assert_eq!(proof.height(), tree.height);
+                    MerkleTree::<BabyBear, HV>::verify(proof.clone(), *leaf, root).unwrap();
+
+                    let index = proof
+                        .index_bits()
+                        .into_iter()
+                        .map(|bit| builder.constant(F::from_bool(bit)))
+                        .collect();
+                    let path = proof
+                        .path
+                        .iter()
+                        .map(|x| std::array::from_fn(|i| builder.constant(x[i])))
+                        .collect();
+                    let proof_variable =
+                        MerkleProofVariable::<InnerConfig, HV> { index, path, config };
+                    let value_variable: [Felt<_>; 8] =
+                        std::array::from_fn(|i| builder.constant(leaf[i]));
+                    let root_variable: [Felt<_>; 8] =
+                        std::array::from_fn(|i| builder.constant(root[i]));
+                    verify::<InnerConfig, HV>(
+                        &mut builder,
+                        proof_variable,
+                        value_variable,
+                        root_variable,
+                    );
+                }
+            }
+        }
+
+        run_test_recursion(builder.into_root_block(), std::iter::empty());
+    }
 }
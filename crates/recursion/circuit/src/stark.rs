@@ -7,6 +7,7 @@ use crate::{
     challenger::CanObserveVariable,
     fri::{dummy_hash, dummy_pcs_proof, PolynomialBatchShape, PolynomialShape},
     hash::FieldHasherVariable,
+    merkle_tree::MerkleTreeConfig,
     BabyBearFriConfig, CircuitConfig, TwoAdicPcsMatsVariable, TwoAdicPcsProofVariable,
 };
 use p3_air::{Air, BaseAir};
@@ -205,6 +206,8 @@ fn dummy_opened_values<F: Field, EF: ExtensionField<F>, A: MachineAir<F>>(
 pub struct MerkleProofVariable<C: CircuitConfig, HV: FieldHasherVariable<C>> {
     pub index: Vec<C::Bit>,
     pub path: Vec<HV::DigestVariable>,
+    /// The arity and hash of the tree, which are fixed when the program is compiled.
+    pub config: MerkleTreeConfig,
 }
 
 pub const EMPTY: usize = 0x_1111_1111;
@@ -381,4 +381,45 @@ pub mod tests {
 
         test_recursion_linear_program(instructions);
     }
+
+    #[test]
+    pub fn arena_reuses_buffers() {
+        let program = |n: u32| {
+            let instructions = once(instr::mem(MemAccessKind::Write, 1, 0, 0))
+                .chain(once(instr::mem(MemAccessKind::Write, 1, 1, 1)))
+                .chain((2..=n).map(|i| instr::base_alu(BaseAluOpcode::AddF, 1, i, i - 2, i - 1)))
+                .collect::<Vec<_>>();
+            Arc::new(linear_program(instructions).unwrap())
+        };
+        let run = |program: &Arc<RecursionProgram<F>>, arena: Option<&mut RecursionArena<F>>| {
+            let perm = SC::new().perm;
+            match arena {
+                Some(arena) => {
+                    let mut runtime = Runtime::<F, EF, DiffusionMatrixBabyBear>::new_in(
+                        program.clone(),
+                        perm,
+                        arena,
+                    );
+                    runtime.run().unwrap();
+                    arena.reclaim(runtime)
+                }
+                None => {
+                    let mut runtime =
+                        Runtime::<F, EF, DiffusionMatrixBabyBear>::new(program.clone(), perm);
+                    runtime.run().unwrap();
+                    runtime.record
+                }
+            }
+        };
+
+        // A smaller program run in the buffers of a larger one leaves no stale events.
+        let mut arena = RecursionArena::new();
+        let large = run(&program(20), Some(&mut arena));
+        let capacity = large.base_alu_events.capacity();
+        arena.recycle(large);
+        let small = program(10);
+        let record = run(&small, Some(&mut arena));
+        assert_eq!(record.base_alu_events.capacity(), capacity);
+        assert_eq!(format!("{record:?}"), format!("{:?}", run(&small, None)));
+    }
 }
@@ -0,0 +1,68 @@
+use std::{mem, sync::Arc};
+
+use p3_field::{ExtensionField, PrimeField32};
+
+use super::{memory::MemVec, ExecutionRecord, RecursionProgram, Runtime};
+
+/// Buffers of the recursion runtime reused across the programs run by a worker.
+///
+/// Every node of the recursion tree is a program run with a fresh runtime, whose memory and record
+/// are allocated anew. A worker proving many nodes keeps an arena instead: the runtime of each node
+/// is created with [`Runtime::new_in`], which takes the buffers of the arena, and the buffers are
+/// reset and given back with [`Self::reclaim`] once the node is run. Records are given back with
+/// [`Self::recycle`] once they are no longer needed, e.g. after they are proven.
+#[derive(Debug, Default)]
+pub struct RecursionArena<F> {
+    memory: MemVec<F>,
+    record: ExecutionRecord<F>,
+}
+
+impl<F: PrimeField32> RecursionArena<F> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { memory: MemVec::default(), record: ExecutionRecord::default() }
+    }
+
+    /// Take the buffers for a run of `program`, reset to its memory size and with an empty record.
+    pub(crate) fn take(
+        &mut self,
+        program: &Arc<RecursionProgram<F>>,
+    ) -> (MemVec<F>, ExecutionRecord<F>) {
+        let mut memory = mem::take(&mut self.memory);
+        memory.reset(program.total_memory);
+        let mut record = mem::take(&mut self.record);
+        record.clear();
+        record.program = program.clone();
+        (memory, record)
+    }
+
+    /// Give back the memory of `runtime` once its program is run, and return its record.
+    pub fn reclaim<EF: ExtensionField<F>, Diffusion>(
+        &mut self,
+        runtime: Runtime<'_, F, EF, Diffusion>,
+    ) -> ExecutionRecord<F> {
+        self.memory = runtime.memory;
+        runtime.record
+    }
+
+    /// Give back the buffers of `record` once it is no longer needed, keeping the largest ones.
+    pub fn recycle(&mut self, mut record: ExecutionRecord<F>) {
+        if record_capacity(&record) > record_capacity(&self.record) {
+            record.program = Arc::default();
+            self.record = record;
+        }
+    }
+}
+
+/// The total capacity of the event buffers of `record`.
+fn record_capacity<F>(record: &ExecutionRecord<F>) -> usize {
+    record.base_alu_events.capacity() +
+        record.ext_alu_events.capacity() +
+        record.mem_var_events.capacity() +
+        record.poseidon2_events.capacity() +
+        record.select_events.capacity() +
+        record.exp_reverse_bits_len_events.capacity() +
+        record.fri_fold_events.capacity() +
+        record.batch_fri_events.capacity() +
+        record.commit_pv_hash_events.capacity()
+}
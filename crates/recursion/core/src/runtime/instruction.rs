@@ -140,6 +140,44 @@ impl<F: Copy> Instruction<F> {
     }
 }
 
+impl<F> Instruction<F> {
+    /// The name of the instruction's opcode, used when reporting program statistics.
+    #[must_use]
+    pub fn opcode_name(&self) -> &'static str {
+        match self {
+            Instruction::BaseAlu(instr) => match instr.opcode {
+                BaseAluOpcode::AddF => "AddF",
+                BaseAluOpcode::SubF => "SubF",
+                BaseAluOpcode::MulF => "MulF",
+                BaseAluOpcode::DivF => "DivF",
+            },
+            Instruction::ExtAlu(instr) => match instr.opcode {
+                ExtAluOpcode::AddE => "AddE",
+                ExtAluOpcode::SubE => "SubE",
+                ExtAluOpcode::MulE => "MulE",
+                ExtAluOpcode::DivE => "DivE",
+            },
+            Instruction::Mem(instr) => match instr.kind {
+                MemAccessKind::Read => "MemRead",
+                MemAccessKind::Write => "MemWrite",
+            },
+            Instruction::Poseidon2(_) => "Poseidon2",
+            Instruction::Select(_) => "Select",
+            Instruction::ExpReverseBitsLen(_) => "ExpReverseBitsLen",
+            Instruction::HintBits(_) => "HintBits",
+            Instruction::HintAddCurve(_) => "HintAddCurve",
+            Instruction::FriFold(_) => "FriFold",
+            Instruction::BatchFRI(_) => "BatchFRI",
+            Instruction::Print(_) => "Print",
+            Instruction::HintExt2Felts(_) => "HintExt2Felts",
+            Instruction::CommitPublicValues(_) => "CommitPublicValues",
+            Instruction::Hint(_) => "Hint",
+            #[cfg(feature = "debug")]
+            Instruction::DebugBacktrace(_) => "DebugBacktrace",
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct HintBitsInstr<F> {
     /// Addresses and mults of the output bits.
@@ -36,6 +36,14 @@ impl<F: PrimeField64> MemVec<F> {
         })
     }
 
+    /// Resize the memory to `capacity` entries, reusing its allocation.
+    ///
+    /// The entries are left as they are: the program writes every address before reading it.
+    pub fn reset(&mut self, capacity: usize) {
+        self.0.truncate(capacity);
+        self.0.resize_with(capacity, || SyncUnsafeCell(UnsafeCell::new(MaybeUninit::uninit())));
+    }
+
     pub fn mr(&mut self, addr: Address<F>) -> &MemoryEntry<F> {
         // SAFETY: We have exclusive access to the memory, so no data races can occur.
         unsafe { self.mr_unchecked(addr) }
@@ -1,3 +1,4 @@
+mod arena;
 pub mod instruction;
 mod memory;
 mod opcode;
@@ -5,6 +6,7 @@ mod program;
 mod record;
 
 // Avoid triggering annoying branch of thiserror derive macro.
+pub use arena::*;
 use backtrace::Backtrace as Trace;
 pub use instruction::Instruction;
 use instruction::{
@@ -29,7 +31,10 @@ use std::{
     io::{stdout, Write},
     iter::zip,
     marker::PhantomData,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 use thiserror::Error;
 
@@ -97,6 +102,16 @@ pub struct Runtime<'a, F: PrimeField32, EF: ExtensionField<F>, Diffusion> {
 
     pub nb_print_e: usize,
 
+    /// Whether failed assertions are counted in [`Self::nb_failed_assertions`] instead of stopping
+    /// the execution with an error.
+    ///
+    /// Assertions fail as divisions of a nonzero value by zero, which then give one. The events
+    /// recorded are the same as those of an execution on valid inputs, so this is only meant for
+    /// measuring programs on dummy inputs.
+    pub count_failed_assertions: bool,
+
+    pub nb_failed_assertions: usize,
+
     /// The program.
     pub program: Arc<RecursionProgram<F>>,
 
@@ -161,6 +176,38 @@ where
     ) -> Self {
         let record = ExecutionRecord::<F> { program: program.clone(), ..Default::default() };
         let memory = MemVec::with_capacity(program.total_memory);
+        Self::with_buffers(program, perm, memory, record)
+    }
+
+    /// Create a runtime reusing the memory and record buffers of `arena`, which are given back with
+    /// [`RecursionArena::reclaim`] once the program is run.
+    pub fn new_in(
+        program: Arc<RecursionProgram<F>>,
+        perm: Poseidon2<
+            F,
+            Poseidon2ExternalMatrixGeneral,
+            Diffusion,
+            PERMUTATION_WIDTH,
+            POSEIDON2_SBOX_DEGREE,
+        >,
+        arena: &mut RecursionArena<F>,
+    ) -> Self {
+        let (memory, record) = arena.take(&program);
+        Self::with_buffers(program, perm, memory, record)
+    }
+
+    fn with_buffers(
+        program: Arc<RecursionProgram<F>>,
+        perm: Poseidon2<
+            F,
+            Poseidon2ExternalMatrixGeneral,
+            Diffusion,
+            PERMUTATION_WIDTH,
+            POSEIDON2_SBOX_DEGREE,
+        >,
+        memory: MemVec<F>,
+        record: ExecutionRecord<F>,
+    ) -> Self {
         Self {
             timestamp: 0,
             nb_poseidons: 0,
@@ -176,6 +223,8 @@ where
             nb_batch_fri: 0,
             nb_print_f: 0,
             nb_print_e: 0,
+            count_failed_assertions: false,
+            nb_failed_assertions: 0,
             program,
             memory,
             record,
@@ -213,7 +262,7 @@ where
         witness_stream: Option<&mut VecDeque<Block<F>>>,
         instruction: Instruction<F>,
     ) -> Result<(), RuntimeError<F, EF>> {
-        let ExecEnv { memory, perm, debug_stdout } = state.env;
+        let ExecEnv { memory, perm, debug_stdout, failed_assertions } = state.env;
         let record = &mut state.record;
         match instruction {
             Instruction::BaseAlu(instr @ BaseAluInstr { opcode, mult: _, addrs }) => {
@@ -231,6 +280,9 @@ where
                             // to be 1.
                             if in1.is_zero() {
                                 AbstractField::one()
+                            } else if let Some(failed_assertions) = failed_assertions {
+                                failed_assertions.fetch_add(1, Ordering::Relaxed);
+                                AbstractField::one()
                             } else {
                                 return Err(RuntimeError::DivFOutOfDomain {
                                     in1,
@@ -262,6 +314,9 @@ where
                             // to be 1.
                             if in1_ef.is_zero() {
                                 AbstractField::one()
+                            } else if let Some(failed_assertions) = failed_assertions {
+                                failed_assertions.fetch_add(1, Ordering::Relaxed);
+                                AbstractField::one()
                             } else {
                                 return Err(RuntimeError::DivEOutOfDomain {
                                     in1: in1_ef,
@@ -564,13 +619,14 @@ where
         program: &RawProgram<Instruction<F>>,
         root_program: &Arc<RecursionProgram<F>>,
         mut witness_stream: Option<&mut VecDeque<Block<F>>>,
+        record: ExecutionRecord<F>,
     ) -> Result<ExecutionRecord<F>, RuntimeError<F, EF>> {
         let fresh_record =
             || ExecutionRecord { program: Arc::clone(root_program), ..Default::default() };
 
         let mut state = ExecState {
             env: env.clone(),
-            record: fresh_record(),
+            record,
             #[cfg(feature = "debug")]
             last_trace: None,
         };
@@ -595,7 +651,13 @@ where
                             .map(|subprogram| {
                                 // Witness stream may not be called inside parallel contexts to
                                 // avoid nondeterminism.
-                                Self::execute_raw(env, subprogram, root_program, None)
+                                Self::execute_raw(
+                                    env,
+                                    subprogram,
+                                    root_program,
+                                    None,
+                                    fresh_record(),
+                                )
                             })
                             .try_reduce(fresh_record, |mut record, mut res| {
                                 record.append(&mut res);
@@ -610,20 +672,28 @@ where
 
     /// Run the program.
     pub fn run(&mut self) -> Result<(), RuntimeError<F, EF>> {
+        // Reuse the buffers of the record, e.g. the ones taken from a `RecursionArena`.
+        let mut record = std::mem::take(&mut self.record);
+        record.clear();
+        record.program = self.program.clone();
+        let failed_assertions = AtomicUsize::new(0);
         let record = unsafe {
             Self::execute_raw(
                 &ExecEnv {
                     memory: &self.memory,
                     perm: self.perm.as_ref().unwrap(),
                     debug_stdout: &Mutex::new(&mut self.debug_stdout),
+                    failed_assertions: self.count_failed_assertions.then_some(&failed_assertions),
                 },
                 &self.program.inner,
                 &self.program,
                 Some(&mut self.witness_stream),
+                record,
             )
         }?;
 
         self.record = record;
+        self.nb_failed_assertions = failed_assertions.into_inner();
 
         Ok(())
     }
@@ -691,18 +761,20 @@ struct ExecEnv<'a, 'b, F, Diffusion> {
     pub memory: &'a MemVec<F>,
     pub perm: &'a Perm<F, Diffusion>,
     pub debug_stdout: &'a Mutex<dyn Write + Send + 'b>,
+    pub failed_assertions: Option<&'a AtomicUsize>,
 }
 
 impl<F, Diffusion> Clone for ExecEnv<'_, '_, F, Diffusion> {
     fn clone(&self) -> Self {
-        let Self { memory, perm, debug_stdout } = self;
-        Self { memory, perm, debug_stdout }
+        let Self { memory, perm, debug_stdout, failed_assertions } = self;
+        Self { memory, perm, debug_stdout, failed_assertions: *failed_assertions }
     }
 
     fn clone_from(&mut self, source: &Self) {
-        let Self { memory, perm, debug_stdout } = self;
+        let Self { memory, perm, debug_stdout, failed_assertions } = self;
         memory.clone_from(&source.memory);
         perm.clone_from(&source.perm);
         debug_stdout.clone_from(&source.debug_stdout);
+        failed_assertions.clone_from(&source.failed_assertions);
     }
 }
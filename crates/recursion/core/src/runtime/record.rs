@@ -101,6 +101,38 @@ impl<F: Field> ExecutionRecord<F> {
         self.program.fixed_log2_rows(air)
     }
 
+    /// Clear the events of the record, keeping the capacity of its buffers.
+    pub fn clear(&mut self) {
+        // Exhaustive destructuring for refactoring purposes.
+        let Self {
+            program: _,
+            index,
+            base_alu_events,
+            ext_alu_events,
+            mem_const_count,
+            mem_var_events,
+            public_values,
+            poseidon2_events,
+            select_events,
+            exp_reverse_bits_len_events,
+            fri_fold_events,
+            batch_fri_events,
+            commit_pv_hash_events,
+        } = self;
+        *index = 0;
+        base_alu_events.clear();
+        ext_alu_events.clear();
+        *mem_const_count = 0;
+        mem_var_events.clear();
+        *public_values = RecursionPublicValues::default();
+        poseidon2_events.clear();
+        select_events.clear();
+        exp_reverse_bits_len_events.clear();
+        fri_fold_events.clear();
+        batch_fri_events.clear();
+        commit_pv_hash_events.clear();
+    }
+
     pub fn preallocate(&mut self) {
         let event_counts =
             self.program.inner.iter().fold(RecursionAirEventCount::default(), Add::add);
@@ -12,7 +12,7 @@ use p3_merkle_tree::FieldMerkleTreeMmcs;
 use p3_poseidon2::{Poseidon2, Poseidon2ExternalMatrixGeneral};
 use p3_symmetric::{Hash, MultiField32PaddingFreeSponge, TruncatedPermutation};
 use serde::{Deserialize, Serialize};
-use sp1_stark::{Com, StarkGenericConfig, ZeroCommitment};
+use sp1_stark::{Com, FriParams, StarkGenericConfig, ZeroCommitment};
 
 use super::{poseidon2::bn254_poseidon2_rc3, sp1_dev_mode};
 
@@ -104,6 +104,20 @@ pub fn outer_fri_config_with_blowup(log_blowup: usize) -> FriConfig<OuterChallen
     FriConfig { log_blowup, num_queries, proof_of_work_bits: 16, mmcs: challenge_mmcs }
 }
 
+/// The FRI config for outer recursion with the given parameters.
+pub fn outer_fri_config_with_params(params: FriParams) -> FriConfig<OuterChallengeMmcs> {
+    let perm = outer_perm();
+    let hash = OuterHash::new(perm.clone()).unwrap();
+    let compress = OuterCompress::new(perm.clone());
+    let challenge_mmcs = OuterChallengeMmcs::new(OuterValMmcs::new(hash, compress));
+    FriConfig {
+        log_blowup: params.log_blowup,
+        num_queries: params.num_queries,
+        proof_of_work_bits: params.proof_of_work_bits,
+        mmcs: challenge_mmcs,
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(from = "std::marker::PhantomData<BabyBearPoseidon2Outer>")]
 pub struct BabyBearPoseidon2Outer {
@@ -113,7 +127,7 @@ pub struct BabyBearPoseidon2Outer {
 
 impl Clone for BabyBearPoseidon2Outer {
     fn clone(&self) -> Self {
-        Self::new()
+        Self::with_fri_params(self.fri_params())
     }
 }
 
@@ -153,6 +167,26 @@ impl BabyBearPoseidon2Outer {
         let pcs = OuterPcs::new(27, dft, val_mmcs, fri_config);
         Self { pcs, perm }
     }
+    pub fn with_fri_params(params: FriParams) -> Self {
+        let perm = outer_perm();
+        let hash = OuterHash::new(perm.clone()).unwrap();
+        let compress = OuterCompress::new(perm.clone());
+        let val_mmcs = OuterValMmcs::new(hash, compress);
+        let dft = OuterDft {};
+        let fri_config = outer_fri_config_with_params(params);
+        let pcs = OuterPcs::new(27, dft, val_mmcs, fri_config);
+        Self { pcs, perm }
+    }
+
+    /// The FRI parameters of the config.
+    pub fn fri_params(&self) -> FriParams {
+        let fri_config = self.pcs.fri_config();
+        FriParams {
+            log_blowup: fri_config.log_blowup,
+            num_queries: fri_config.num_queries,
+            proof_of_work_bits: fri_config.proof_of_work_bits,
+        }
+    }
 }
 
 impl Default for BabyBearPoseidon2Outer {
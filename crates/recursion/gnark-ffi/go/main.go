@@ -18,7 +18,6 @@ typedef struct {
 */
 import "C"
 import (
-	"encoding/json"
 	"fmt"
 	"os"
 	"sync"
@@ -182,21 +181,13 @@ func TestMain() error {
 		fileName = "plonk_witness.json"
 	}
 
-	// Read the file.
-	data, err := os.ReadFile(fileName)
-	if err != nil {
-		return err
-	}
-
-	// Deserialize the JSON data into a slice of Instruction structs
-	var inputs sp1.WitnessInput
-	err = json.Unmarshal(data, &inputs)
+	// Read the witness.
+	circuit, _, err := sp1.ReadWitness(fileName)
 	if err != nil {
 		return err
 	}
 
 	// Compile the circuit.
-	circuit := sp1.NewCircuit(inputs)
 	builder := scs.NewBuilder
 	scs, err := frontend.Compile(ecc.BN254.ScalarField(), builder, &circuit)
 	if err != nil {
@@ -216,7 +207,10 @@ func TestMain() error {
 	}
 
 	// Generate witness.
-	assignment := sp1.NewCircuit(inputs)
+	assignment, _, err := sp1.ReadWitness(fileName)
+	if err != nil {
+		return err
+	}
 	witness, err := frontend.NewWitness(&assignment, ecc.BN254.ScalarField())
 	if err != nil {
 		return err
@@ -80,6 +80,14 @@ func NewF(value string) Variable {
 	}
 }
 
+// NewFFromBigInt is NewF for a value that is already parsed.
+func NewFFromBigInt(value *big.Int) Variable {
+	return Variable{
+		Value:      frontend.Variable(value),
+		UpperBound: new(big.Int).SetUint64(uint64(math.Pow(2, 32))),
+	}
+}
+
 func NewE(value []string) ExtensionVariable {
 	a := NewF(value[0])
 	b := NewF(value[1])
@@ -2,7 +2,6 @@ package sp1
 
 import (
 	"bufio"
-	"encoding/json"
 	"fmt"
 	"os"
 	"sync"
@@ -56,21 +55,13 @@ func ProvePlonk(dataDir string, witnessPath string) Proof {
 	vk.ReadFrom(vkFile)
 	defer vkFile.Close()
 
-	// Read the file.
-	data, err := os.ReadFile(witnessPath)
-	if err != nil {
-		panic(err)
-	}
-
-	// Deserialize the JSON data into a slice of Instruction structs
-	var witnessInput WitnessInput
-	err = json.Unmarshal(data, &witnessInput)
+	// Read the witness.
+	assignment, publicInputs, err := ReadWitness(witnessPath)
 	if err != nil {
 		panic(err)
 	}
 
 	// Generate the witness.
-	assignment := NewCircuit(witnessInput)
 	witness, err := frontend.NewWitness(&assignment, ecc.BN254.ScalarField())
 	if err != nil {
 		panic(err)
@@ -92,7 +83,7 @@ func ProvePlonk(dataDir string, witnessPath string) Proof {
 		panic(err)
 	}
 
-	return NewSP1PlonkBn254Proof(&proof, witnessInput)
+	return NewSP1PlonkBn254Proof(&proof, publicInputs)
 }
 
 func ProveGroth16(dataDir string, witnessPath string) Proof {
@@ -139,25 +130,15 @@ func ProveGroth16(dataDir string, witnessPath string) Proof {
 	globalMutex.Unlock()
 
 	start = time.Now()
-	// Read the file.
-	data, err := os.ReadFile(witnessPath)
+	// Read the witness.
+	assignment, publicInputs, err := ReadWitness(witnessPath)
 	if err != nil {
 		panic(err)
 	}
 	fmt.Printf("Reading witness file took %s\n", time.Since(start))
 
-	start = time.Now()
-	// Deserialize the JSON data into a slice of Instruction structs
-	var witnessInput WitnessInput
-	err = json.Unmarshal(data, &witnessInput)
-	if err != nil {
-		panic(err)
-	}
-	fmt.Printf("Deserializing JSON data took %s\n", time.Since(start))
-
 	start = time.Now()
 	// Generate the witness.
-	assignment := NewCircuit(witnessInput)
 	witness, err := frontend.NewWitness(&assignment, ecc.BN254.ScalarField())
 	if err != nil {
 		panic(err)
@@ -173,5 +154,5 @@ func ProveGroth16(dataDir string, witnessPath string) Proof {
 	}
 	fmt.Printf("Generating proof took %s\n", time.Since(start))
 
-	return NewSP1Groth16Proof(&proof, witnessInput)
+	return NewSP1Groth16Proof(&proof, publicInputs)
 }
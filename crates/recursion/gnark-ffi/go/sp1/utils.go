@@ -12,15 +12,11 @@ import (
 	"github.com/succinctlabs/sp1-recursion-gnark/sp1/babybear"
 )
 
-func NewSP1PlonkBn254Proof(proof *plonk.Proof, witnessInput WitnessInput) Proof {
+func NewSP1PlonkBn254Proof(proof *plonk.Proof, publicInputs [2]string) Proof {
 	var buf bytes.Buffer
 	(*proof).WriteRawTo(&buf)
 	proofBytes := buf.Bytes()
 
-	var publicInputs [2]string
-	publicInputs[0] = witnessInput.VkeyHash
-	publicInputs[1] = witnessInput.CommittedValuesDigest
-
 	// Cast plonk proof into plonk_bn254 proof so we can call MarshalSolidity.
 	p := (*proof).(*plonk_bn254.Proof)
 
@@ -33,15 +29,11 @@ func NewSP1PlonkBn254Proof(proof *plonk.Proof, witnessInput WitnessInput) Proof
 	}
 }
 
-func NewSP1Groth16Proof(proof *groth16.Proof, witnessInput WitnessInput) Proof {
+func NewSP1Groth16Proof(proof *groth16.Proof, publicInputs [2]string) Proof {
 	var buf bytes.Buffer
 	(*proof).WriteRawTo(&buf)
 	proofBytes := buf.Bytes()
 
-	var publicInputs [2]string
-	publicInputs[0] = witnessInput.VkeyHash
-	publicInputs[1] = witnessInput.CommittedValuesDigest
-
 	// Cast groth16 proof into groth16_bn254 proof so we can call MarshalSolidity.
 	p := (*proof).(*groth16_bn254.Proof)
 
@@ -0,0 +1,166 @@
+package sp1
+
+import (
+	"bufio"
+	"bytes"
+	"encoding/binary"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"math/big"
+	"os"
+
+	"github.com/consensys/gnark-crypto/ecc"
+	"github.com/consensys/gnark/frontend"
+	"github.com/succinctlabs/sp1-recursion-gnark/sp1/babybear"
+)
+
+// The canonical witness format, see `GnarkWitness::to_canonical_bytes` in the Rust crate.
+var witnessMagic = []byte("SP1W")
+
+const witnessFormatVersion = 1
+const witnessExtDegree = 4
+
+// ReadWitness reads a witness in the canonical byte format, or in JSON, as the assignment of the
+// circuit along with its two public inputs.
+//
+// The canonical format is decoded straight into the assignment as it is read, so neither the file
+// nor an intermediate WitnessInput is ever held in memory as a whole.
+func ReadWitness(path string) (Circuit, [2]string, error) {
+	file, err := os.Open(path)
+	if err != nil {
+		return Circuit{}, [2]string{}, err
+	}
+	defer file.Close()
+	info, err := file.Stat()
+	if err != nil {
+		return Circuit{}, [2]string{}, err
+	}
+	return decodeWitness(bufio.NewReader(file), info.Size())
+}
+
+// DecodeWitness decodes a witness in the canonical byte format, or in JSON, see ReadWitness.
+func DecodeWitness(data []byte) (Circuit, [2]string, error) {
+	return decodeWitness(bufio.NewReader(bytes.NewReader(data)), int64(len(data)))
+}
+
+// decodeWitness decodes a witness of size bytes from input.
+func decodeWitness(input *bufio.Reader, size int64) (Circuit, [2]string, error) {
+	magic, err := input.Peek(len(witnessMagic))
+	if err != nil || !bytes.Equal(magic, witnessMagic) {
+		var witnessInput WitnessInput
+		if err := json.NewDecoder(input).Decode(&witnessInput); err != nil {
+			return Circuit{}, [2]string{}, err
+		}
+		publicInputs := [2]string{witnessInput.VkeyHash, witnessInput.CommittedValuesDigest}
+		return NewCircuit(witnessInput), publicInputs, nil
+	}
+
+	reader := witnessReader{input: input, remaining: size}
+	reader.take(len(witnessMagic))
+	version := reader.readU32()
+	if reader.err == nil && version != witnessFormatVersion {
+		return Circuit{}, [2]string{}, fmt.Errorf("unsupported witness format version %d, expected %d", version, witnessFormatVersion)
+	}
+	vkeyHash := reader.readValue()
+	committedValuesDigest := reader.readValue()
+	var circuit Circuit
+	circuit.Vars = readVariables(&reader, 1, func(values []*big.Int) frontend.Variable {
+		return values[0]
+	})
+	circuit.Felts = readVariables(&reader, 1, func(values []*big.Int) babybear.Variable {
+		return babybear.NewFFromBigInt(values[0])
+	})
+	circuit.Exts = readVariables(&reader, witnessExtDegree, func(values []*big.Int) babybear.ExtensionVariable {
+		var ext babybear.ExtensionVariable
+		for i, value := range values {
+			ext.Value[i] = babybear.NewFFromBigInt(value)
+		}
+		return ext
+	})
+	if reader.err != nil {
+		return Circuit{}, [2]string{}, reader.err
+	}
+	if reader.remaining != 0 {
+		return Circuit{}, [2]string{}, fmt.Errorf("%d trailing bytes after the witness", reader.remaining)
+	}
+	circuit.VkeyHash = vkeyHash
+	circuit.CommittedValuesDigest = committedValuesDigest
+	publicInputs := [2]string{vkeyHash.String(), committedValuesDigest.String()}
+	return circuit, publicInputs, nil
+}
+
+// readVariables reads a length-prefixed list of groups of `group` values, turning each group into
+// a variable of the assignment as soon as it is read.
+func readVariables[T any](r *witnessReader, group int, variable func([]*big.Int) T) []T {
+	n := int64(r.readU32())
+	// Check the length before allocating, so a corrupted length fails cleanly.
+	if r.err == nil && r.remaining/int64(32*group) < n {
+		r.err = errTruncatedWitness
+	}
+	if r.err != nil {
+		return nil
+	}
+	variables := make([]T, n)
+	values := make([]*big.Int, group)
+	for i := range variables {
+		for j := range values {
+			values[j] = r.readValue()
+		}
+		if r.err != nil {
+			return nil
+		}
+		variables[i] = variable(values)
+	}
+	return variables
+}
+
+// witnessReader decodes a canonical witness from a stream, keeping the first error.
+type witnessReader struct {
+	input *bufio.Reader
+	// The number of bytes of the witness left to read.
+	remaining int64
+	buf       [32]byte
+	err       error
+}
+
+var errTruncatedWitness = errors.New("truncated witness")
+
+// take reads the next n bytes, at most 32, into the buffer of the reader.
+func (r *witnessReader) take(n int) []byte {
+	if r.err != nil {
+		return nil
+	}
+	if r.remaining < int64(n) {
+		r.err = errTruncatedWitness
+		return nil
+	}
+	if _, err := io.ReadFull(r.input, r.buf[:n]); err != nil {
+		r.err = errTruncatedWitness
+		return nil
+	}
+	r.remaining -= int64(n)
+	return r.buf[:n]
+}
+
+func (r *witnessReader) readU32() uint32 {
+	b := r.take(4)
+	if b == nil {
+		return 0
+	}
+	return binary.LittleEndian.Uint32(b)
+}
+
+func (r *witnessReader) readValue() *big.Int {
+	b := r.take(32)
+	if b == nil {
+		return new(big.Int)
+	}
+	value := new(big.Int).SetBytes(b)
+	if value.Cmp(ecc.BN254.ScalarField()) >= 0 {
+		r.err = fmt.Errorf("witness value %s is not below the BN254 modulus", value)
+		return new(big.Int)
+	}
+	return value
+}
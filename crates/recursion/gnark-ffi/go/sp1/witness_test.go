@@ -0,0 +1,68 @@
+package sp1
+
+import (
+	"bytes"
+	"encoding/binary"
+	"math/big"
+	"testing"
+)
+
+func encodeWitness(vkeyHash, digest uint64, vars, felts []uint64, exts [][4]uint64) []byte {
+	var buf bytes.Buffer
+	buf.Write(witnessMagic)
+	binary.Write(&buf, binary.LittleEndian, uint32(witnessFormatVersion))
+	value := func(v uint64) {
+		var b [32]byte
+		new(big.Int).SetUint64(v).FillBytes(b[:])
+		buf.Write(b[:])
+	}
+	value(vkeyHash)
+	value(digest)
+	for _, values := range [][]uint64{vars, felts} {
+		binary.Write(&buf, binary.LittleEndian, uint32(len(values)))
+		for _, v := range values {
+			value(v)
+		}
+	}
+	binary.Write(&buf, binary.LittleEndian, uint32(len(exts)))
+	for _, ext := range exts {
+		for _, v := range ext {
+			value(v)
+		}
+	}
+	return buf.Bytes()
+}
+
+func TestDecodeWitness(t *testing.T) {
+	data := encodeWitness(7, 8, []uint64{1, 2}, []uint64{3}, [][4]uint64{{4, 5, 6, 9}})
+	circuit, publicInputs, err := DecodeWitness(data)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if publicInputs != [2]string{"7", "8"} {
+		t.Fatalf("unexpected public inputs %v", publicInputs)
+	}
+	if len(circuit.Vars) != 2 || circuit.Vars[1].(*big.Int).Uint64() != 2 {
+		t.Fatalf("unexpected vars %v", circuit.Vars)
+	}
+	if len(circuit.Felts) != 1 || circuit.Felts[0].Value.(*big.Int).Uint64() != 3 {
+		t.Fatalf("unexpected felts %v", circuit.Felts)
+	}
+	if len(circuit.Exts) != 1 || circuit.Exts[0].Value[3].Value.(*big.Int).Uint64() != 9 {
+		t.Fatalf("unexpected exts %v", circuit.Exts)
+	}
+
+	// The JSON format decodes to the same public inputs.
+	_, publicInputs, err = DecodeWitness([]byte(`{"vars":["1"],"felts":[],"exts":[],"vkey_hash":"7","committed_values_digest":"8"}`))
+	if err != nil || publicInputs != [2]string{"7", "8"} {
+		t.Fatalf("unexpected JSON decoding %v, %v", publicInputs, err)
+	}
+
+	// Truncated witnesses and trailing bytes are rejected.
+	if _, _, err := DecodeWitness(data[:len(data)-1]); err != errTruncatedWitness {
+		t.Fatalf("expected a truncated witness, got %v", err)
+	}
+	if _, _, err := DecodeWitness(append(data, 0)); err == nil {
+		t.Fatal("expected trailing bytes to be rejected")
+	}
+}
@@ -1,6 +1,13 @@
-use crate::{Groth16Bn254Proof, PlonkBn254Proof, ProofBn254, SP1_CIRCUIT_VERSION};
+use crate::{
+    supervise::{supervise, GnarkError, GnarkLimits},
+    Groth16Bn254Proof, PlonkBn254Proof, ProofBn254, SP1_CIRCUIT_VERSION,
+};
 use anyhow::{anyhow, Result};
-use std::{io::Write, process::Command};
+use std::{
+    io::Write,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 /// Represents the proof system being used
 enum ProofSystem {
@@ -40,9 +47,33 @@ fn get_docker_image() -> String {
 /// Note: files created here by `call_docker` are read-only for after the process exits.
 /// To fix this, manually set the docker user to the current user by supplying a `-u` flag.
 fn call_docker(args: &[&str], mounts: &[(&str, &str)]) -> Result<()> {
+    Ok(call_docker_with_limits(args, mounts, &GnarkLimits::default())?)
+}
+
+/// Calls `docker run` like [`call_docker`], killing the container if it exceeds `limits`.
+fn call_docker_with_limits(
+    args: &[&str],
+    mounts: &[(&str, &str)],
+    limits: &GnarkLimits,
+) -> Result<(), GnarkError> {
+    static CONTAINERS: AtomicUsize = AtomicUsize::new(0);
+
+    // The arguments are the operation, `--system` and the proof system.
+    let operation = operation_name(args[0]);
+    let system = operation_name(args.get(2).copied().unwrap_or_default());
+    let failed = |message: String| GnarkError::Failed { system, operation, message };
+
     tracing::info!("Running {} in docker", args[0]);
+    let name =
+        format!("sp1-gnark-{}-{}", std::process::id(), CONTAINERS.fetch_add(1, Ordering::Relaxed));
     let mut cmd = Command::new("docker");
-    cmd.args(["run", "--rm"]);
+    cmd.args(["run", "--rm", "--name", &name]);
+    if let Some(memory_bytes) = limits.memory_bytes {
+        cmd.arg("--memory").arg(memory_bytes.to_string());
+    }
+    if let Some(cpus) = limits.cpus {
+        cmd.arg("--cpus").arg(cpus.to_string());
+    }
     for (src, dest) in mounts {
         cmd.arg("-v").arg(format!("{src}:{dest}"));
     }
@@ -50,7 +81,17 @@ fn call_docker(args: &[&str], mounts: &[(&str, &str)]) -> Result<()> {
     cmd.args(args);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
-    let result = cmd.output()?;
+    let child = cmd.spawn().map_err(|e| failed(e.to_string()))?;
+
+    let result =
+        match supervise(system, operation, limits.timeout, move || child.wait_with_output()) {
+            Err(e @ GnarkError::TimedOut { .. }) => {
+                // Killing the container also ends the abandoned `docker run`.
+                let _ = Command::new("docker").args(["kill", &name]).output();
+                return Err(e);
+            }
+            result => result?.map_err(|e| failed(e.to_string()))?,
+        };
     if !result.status.success() {
         let stderr = String::from_utf8_lossy(&result.stderr);
         let stdout = String::from_utf8_lossy(&result.stdout);
@@ -58,44 +99,85 @@ fn call_docker(args: &[&str], mounts: &[(&str, &str)]) -> Result<()> {
         tracing::error!("status: {:?}", result.status);
         tracing::error!("stderr: {:?}", stderr);
 
-        return Err(anyhow!("Docker command failed \n stdout: {:?}\n stderr: {:?}", stdout, stderr));
+        // Docker reports a container killed by the OOM killer with the exit code of SIGKILL.
+        if limits.memory_bytes.is_some() && result.status.code() == Some(137) {
+            return Err(GnarkError::OutOfMemory { system, operation });
+        }
+        return Err(failed(format!(
+            "Docker command failed \n stdout: {stdout:?}\n stderr: {stderr:?}"
+        )));
     }
     Ok(())
 }
 
-fn prove(system: ProofSystem, data_dir: &str, witness_path: &str) -> Result<Vec<u8>> {
-    let output_file = tempfile::NamedTempFile::new()?;
+/// The static name of an operation or proof system passed to docker, for error reporting.
+fn operation_name(arg: &str) -> &'static str {
+    match arg {
+        "prove" => "prove",
+        "build" => "build",
+        "verify" => "verify",
+        "test" => "test",
+        "plonk" => "plonk",
+        "groth16" => "groth16",
+        _ => "unknown",
+    }
+}
+
+fn prove(
+    system: ProofSystem,
+    data_dir: &str,
+    witness_path: &str,
+    limits: &GnarkLimits,
+) -> Result<ProofBn254, GnarkError> {
+    let failed = |message: String| GnarkError::Failed {
+        system: system.as_str(),
+        operation: "prove",
+        message,
+    };
+    let output_file = tempfile::NamedTempFile::new().map_err(|e| failed(e.to_string()))?;
     let mounts = [
         (data_dir, "/circuit"),
         (witness_path, "/witness"),
         (output_file.path().to_str().unwrap(), "/output"),
     ];
     assert_docker();
-    call_docker(
+    call_docker_with_limits(
         &["prove", "--system", system.as_str(), "/circuit", "/witness", "/output"],
         &mounts,
+        limits,
     )?;
-    Ok(std::fs::read(output_file.path())?)
+    let result = std::fs::read(output_file.path()).map_err(|e| failed(e.to_string()))?;
+    bincode::deserialize(&result).map_err(|e| failed(format!("failed to deserialize result: {e}")))
 }
 
 pub fn prove_plonk_bn254(data_dir: &str, witness_path: &str) -> PlonkBn254Proof {
-    let result =
-        prove(ProofSystem::Plonk, data_dir, witness_path).expect("failed to prove with docker");
-    let deserialized: ProofBn254 =
-        bincode::deserialize(&result).expect("failed to deserialize result");
-    match deserialized {
-        ProofBn254::Plonk(proof) => proof,
+    try_prove_plonk_bn254(data_dir, witness_path, &GnarkLimits::default())
+        .expect("failed to prove with docker")
+}
+
+pub fn try_prove_plonk_bn254(
+    data_dir: &str,
+    witness_path: &str,
+    limits: &GnarkLimits,
+) -> Result<PlonkBn254Proof, GnarkError> {
+    match prove(ProofSystem::Plonk, data_dir, witness_path, limits)? {
+        ProofBn254::Plonk(proof) => Ok(proof),
         _ => panic!("unexpected proof type"),
     }
 }
 
 pub fn prove_groth16_bn254(data_dir: &str, witness_path: &str) -> Groth16Bn254Proof {
-    let result =
-        prove(ProofSystem::Groth16, data_dir, witness_path).expect("failed to prove with docker");
-    let deserialized: ProofBn254 =
-        bincode::deserialize(&result).expect("failed to deserialize result");
-    match deserialized {
-        ProofBn254::Groth16(proof) => proof,
+    try_prove_groth16_bn254(data_dir, witness_path, &GnarkLimits::default())
+        .expect("failed to prove with docker")
+}
+
+pub fn try_prove_groth16_bn254(
+    data_dir: &str,
+    witness_path: &str,
+    limits: &GnarkLimits,
+) -> Result<Groth16Bn254Proof, GnarkError> {
+    match prove(ProofSystem::Groth16, data_dir, witness_path, limits)? {
+        ProofBn254::Groth16(proof) => Ok(proof),
         _ => panic!("unexpected proof type"),
     }
 }
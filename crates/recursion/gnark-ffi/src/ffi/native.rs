@@ -5,7 +5,10 @@
 //! Although we cast to *mut c_char because the Go signatures can't be immutable, the Go functions
 //! should not modify the strings.
 
-use crate::{Groth16Bn254Proof, PlonkBn254Proof, SP1_CIRCUIT_VERSION};
+use crate::{
+    supervise::{ensure_unlimited, GnarkError, GnarkLimits},
+    Groth16Bn254Proof, PlonkBn254Proof, SP1_CIRCUIT_VERSION,
+};
 use cfg_if::cfg_if;
 use std::{
     ffi::{c_char, CStr, CString},
@@ -152,6 +155,17 @@ pub fn prove_plonk_bn254(data_dir: &str, witness_path: &str) -> PlonkBn254Proof
     }
 }
 
+/// Prove with PLONK, failing if `limits` sets anything, since the in-process prover cannot be
+/// stopped.
+pub fn try_prove_plonk_bn254(
+    data_dir: &str,
+    witness_path: &str,
+    limits: &GnarkLimits,
+) -> Result<PlonkBn254Proof, GnarkError> {
+    ensure_unlimited("plonk", "prove", limits)?;
+    Ok(prove_plonk_bn254(data_dir, witness_path))
+}
+
 pub fn verify_plonk_bn254(
     data_dir: &str,
     proof: &str,
@@ -176,6 +190,17 @@ pub fn prove_groth16_bn254(data_dir: &str, witness_path: &str) -> Groth16Bn254Pr
     }
 }
 
+/// Prove with Groth16, failing if `limits` sets anything, since the in-process prover cannot be
+/// stopped.
+pub fn try_prove_groth16_bn254(
+    data_dir: &str,
+    witness_path: &str,
+    limits: &GnarkLimits,
+) -> Result<Groth16Bn254Proof, GnarkError> {
+    ensure_unlimited("groth16", "prove", limits)?;
+    Ok(prove_groth16_bn254(data_dir, witness_path))
+}
+
 pub fn verify_groth16_bn254(
     data_dir: &str,
     proof: &str,
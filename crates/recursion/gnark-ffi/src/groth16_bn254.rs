@@ -5,8 +5,9 @@ use std::{
 };
 
 use crate::{
-    ffi::{build_groth16_bn254, prove_groth16_bn254, test_groth16_bn254, verify_groth16_bn254},
-    witness::GnarkWitness,
+    ffi::{build_groth16_bn254, test_groth16_bn254, try_prove_groth16_bn254, verify_groth16_bn254},
+    supervise::{GnarkError, GnarkLimits},
+    witness::{canonical_witness_file, GnarkWitness},
     Groth16Bn254Proof, SP1_CIRCUIT_VERSION,
 };
 
@@ -44,10 +45,7 @@ impl Groth16Bn254Prover {
         constraints_file.write_all(serialized.as_bytes()).unwrap();
 
         // Write witness.
-        let mut witness_file = tempfile::NamedTempFile::new().unwrap();
-        let gnark_witness = GnarkWitness::new(witness);
-        let serialized = serde_json::to_string(&gnark_witness).unwrap();
-        witness_file.write_all(serialized.as_bytes()).unwrap();
+        let witness_file = canonical_witness_file(&witness).unwrap();
 
         test_groth16_bn254(
             witness_file.path().to_str().unwrap(),
@@ -91,16 +89,27 @@ impl Groth16Bn254Prover {
 
     /// Generates a Groth16 proof given a witness.
     pub fn prove<C: Config>(&self, witness: Witness<C>, build_dir: PathBuf) -> Groth16Bn254Proof {
-        // Write witness.
-        let mut witness_file = tempfile::NamedTempFile::new().unwrap();
-        let gnark_witness = GnarkWitness::new(witness);
-        let serialized = serde_json::to_string(&gnark_witness).unwrap();
-        witness_file.write_all(serialized.as_bytes()).unwrap();
+        self.try_prove(witness, build_dir, &GnarkLimits::from_env()).unwrap()
+    }
 
-        let mut proof =
-            prove_groth16_bn254(build_dir.to_str().unwrap(), witness_file.path().to_str().unwrap());
+    /// Generates a Groth16 proof given a witness, failing if the proof exceeds `limits`.
+    pub fn try_prove<C: Config>(
+        &self,
+        witness: Witness<C>,
+        build_dir: PathBuf,
+        limits: &GnarkLimits,
+    ) -> Result<Groth16Bn254Proof, GnarkError> {
+        // Stream the witness to a file, freeing it before proving.
+        let witness_file = canonical_witness_file(&witness).unwrap();
+        drop(witness);
+
+        let mut proof = try_prove_groth16_bn254(
+            build_dir.to_str().unwrap(),
+            witness_file.path().to_str().unwrap(),
+            limits,
+        )?;
         proof.groth16_vkey_hash = Self::get_vkey_hash(&build_dir);
-        proof
+        Ok(proof)
     }
 
     /// Verify a Groth16proof and verify that the supplied vkey_hash and committed_values_digest
@@ -4,11 +4,13 @@ pub mod ffi;
 pub mod groth16_bn254;
 pub mod plonk_bn254;
 pub mod proof;
+pub mod supervise;
 pub mod witness;
 
 pub use groth16_bn254::*;
 pub use plonk_bn254::*;
 pub use proof::*;
+pub use supervise::{GnarkError, GnarkLimits};
 pub use witness::*;
 
 /// The global version for all components of SP1.
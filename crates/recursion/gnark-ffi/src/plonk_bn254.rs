@@ -5,8 +5,9 @@ use std::{
 };
 
 use crate::{
-    ffi::{build_plonk_bn254, prove_plonk_bn254, test_plonk_bn254, verify_plonk_bn254},
-    witness::GnarkWitness,
+    ffi::{build_plonk_bn254, test_plonk_bn254, try_prove_plonk_bn254, verify_plonk_bn254},
+    supervise::{GnarkError, GnarkLimits},
+    witness::{canonical_witness_file, GnarkWitness},
     PlonkBn254Proof, SP1_CIRCUIT_VERSION,
 };
 use anyhow::Result;
@@ -43,10 +44,7 @@ impl PlonkBn254Prover {
         constraints_file.write_all(serialized.as_bytes()).unwrap();
 
         // Write witness.
-        let mut witness_file = tempfile::NamedTempFile::new().unwrap();
-        let gnark_witness = GnarkWitness::new(witness);
-        let serialized = serde_json::to_string(&gnark_witness).unwrap();
-        witness_file.write_all(serialized.as_bytes()).unwrap();
+        let witness_file = canonical_witness_file(&witness).unwrap();
 
         test_plonk_bn254(
             witness_file.path().to_str().unwrap(),
@@ -84,16 +82,27 @@ impl PlonkBn254Prover {
 
     /// Generates a PLONK proof given a witness.
     pub fn prove<C: Config>(&self, witness: Witness<C>, build_dir: PathBuf) -> PlonkBn254Proof {
-        // Write witness.
-        let mut witness_file = tempfile::NamedTempFile::new().unwrap();
-        let gnark_witness = GnarkWitness::new(witness);
-        let serialized = serde_json::to_string(&gnark_witness).unwrap();
-        witness_file.write_all(serialized.as_bytes()).unwrap();
+        self.try_prove(witness, build_dir, &GnarkLimits::from_env()).unwrap()
+    }
 
-        let mut proof =
-            prove_plonk_bn254(build_dir.to_str().unwrap(), witness_file.path().to_str().unwrap());
+    /// Generates a PLONK proof given a witness, failing if the proof exceeds `limits`.
+    pub fn try_prove<C: Config>(
+        &self,
+        witness: Witness<C>,
+        build_dir: PathBuf,
+        limits: &GnarkLimits,
+    ) -> Result<PlonkBn254Proof, GnarkError> {
+        // Stream the witness to a file, freeing it before proving.
+        let witness_file = canonical_witness_file(&witness).unwrap();
+        drop(witness);
+
+        let mut proof = try_prove_plonk_bn254(
+            build_dir.to_str().unwrap(),
+            witness_file.path().to_str().unwrap(),
+            limits,
+        )?;
         proof.plonk_vkey_hash = Self::get_vkey_hash(&build_dir);
-        proof
+        Ok(proof)
     }
 
     /// Verify a PLONK proof and verify that the supplied vkey_hash and committed_values_digest
@@ -1,4 +1,13 @@
+use anyhow::{anyhow, ensure, Result};
+use bn::{AffineG1, AffineG2, Fr, G1, G2};
+use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sp1_verifier::converter::{
+    g1_point_to_uncompressed_bytes, g2_point_to_uncompressed_bytes,
+    unchecked_compressed_x_to_g2_point, uncompressed_bytes_to_g1_point,
+    uncompressed_bytes_to_g2_point,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProofBn254 {
@@ -21,3 +30,91 @@ pub struct Groth16Bn254Proof {
     pub raw_proof: String,
     pub groth16_vkey_hash: [u8; 32],
 }
+
+/// The length of the points A, B and C at the start of an encoded or raw Groth16 proof.
+const GROTH16_POINTS_LENGTH: usize = 256;
+
+impl Groth16Bn254Proof {
+    /// Re-randomize the proof, see [`Self::rerandomize_with_rng`].
+    pub fn rerandomize(&self, groth16_vk: &[u8]) -> Result<Self> {
+        self.rerandomize_with_rng(groth16_vk, &mut rand::thread_rng())
+    }
+
+    /// Re-randomize the proof with fresh randomness from `rng`.
+    ///
+    /// The result is a different proof of the same statement that verifies against the same
+    /// verifying key, so that the proofs cannot be linked. `groth16_vk` is the `groth16_vk.bin`
+    /// the proof was made with. For random nonzero `r` and `s`, the points of the proof become
+    /// `A' = A / r`, `B' = r * B + r * s * delta` and `C' = C + s * A`.
+    pub fn rerandomize_with_rng<R: Rng + CryptoRng>(
+        &self,
+        groth16_vk: &[u8],
+        rng: &mut R,
+    ) -> Result<Self> {
+        let vkey_hash: [u8; 32] = Sha256::digest(groth16_vk).into();
+        ensure!(
+            vkey_hash == self.groth16_vkey_hash,
+            "the verifying key does not match the proof, expected hash {}",
+            hex::encode(self.groth16_vkey_hash)
+        );
+        ensure!(groth16_vk.len() >= 288, "the verifying key is too short");
+        let delta: G2 = unchecked_compressed_x_to_g2_point(&groth16_vk[224..288])?.into();
+
+        let encoded_proof = hex::decode(&self.encoded_proof)?;
+        let raw_proof = hex::decode(&self.raw_proof)?;
+        ensure!(
+            encoded_proof.len() >= GROTH16_POINTS_LENGTH &&
+                raw_proof.len() >= GROTH16_POINTS_LENGTH,
+            "the proof is too short"
+        );
+        ensure!(
+            encoded_proof[..GROTH16_POINTS_LENGTH] == raw_proof[..GROTH16_POINTS_LENGTH],
+            "the encoded and raw proofs differ"
+        );
+
+        let a: G1 = uncompressed_bytes_to_g1_point(&encoded_proof[..64])?.into();
+        let b: G2 = uncompressed_bytes_to_g2_point(&encoded_proof[64..192])?.into();
+        let c: G1 = uncompressed_bytes_to_g1_point(&encoded_proof[192..256])?.into();
+
+        let r = random_nonzero(rng);
+        let s = random_nonzero(rng);
+        let a_prime = a * r.inverse().unwrap();
+        let b_prime = b * r + delta * (r * s);
+        let c_prime = c + a * s;
+
+        // The commitments following the points, if any, are left unchanged.
+        let mut points = Vec::with_capacity(GROTH16_POINTS_LENGTH);
+        points.extend(g1_point_to_uncompressed_bytes(&affine_g1(a_prime)?)?);
+        points.extend(g2_point_to_uncompressed_bytes(&affine_g2(b_prime)?)?);
+        points.extend(g1_point_to_uncompressed_bytes(&affine_g1(c_prime)?)?);
+        let rerandomized = |proof: &[u8]| {
+            let mut bytes = points.clone();
+            bytes.extend_from_slice(&proof[GROTH16_POINTS_LENGTH..]);
+            hex::encode(bytes)
+        };
+
+        Ok(Self {
+            public_inputs: self.public_inputs.clone(),
+            encoded_proof: rerandomized(&encoded_proof),
+            raw_proof: rerandomized(&raw_proof),
+            groth16_vkey_hash: self.groth16_vkey_hash,
+        })
+    }
+}
+
+fn random_nonzero<R: Rng>(rng: &mut R) -> Fr {
+    loop {
+        let x = Fr::random(rng);
+        if !x.is_zero() {
+            return x;
+        }
+    }
+}
+
+fn affine_g1(point: G1) -> Result<AffineG1> {
+    AffineG1::from_jacobian(point).ok_or_else(|| anyhow!("point at infinity"))
+}
+
+fn affine_g2(point: G2) -> Result<AffineG2> {
+    AffineG2::from_jacobian(point).ok_or_else(|| anyhow!("point at infinity"))
+}
@@ -0,0 +1,188 @@
+//! Supervision of the gnark provers.
+//!
+//! A PLONK or Groth16 proof can take a long time, and broken artifacts or a pathological witness
+//! can make it hang forever. [`GnarkLimits`] bounds how long a proof may take and the memory and
+//! CPUs it may use, and a proof exceeding them fails with a [`GnarkError`] instead of blocking the
+//! caller.
+//!
+//! The limits are only enforced in docker, where a proof exceeding them is killed with its
+//! container. With the `native` feature, gnark runs in the process and cannot be stopped, so a
+//! proof with any limit set fails with [`GnarkError::LimitsUnsupported`] rather than run
+//! unbounded.
+
+use std::{env, time::Duration};
+#[cfg(not(feature = "native"))]
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+};
+
+use thiserror::Error;
+
+/// The limits of a gnark invocation, none by default. Only enforced in docker.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GnarkLimits {
+    /// How long the invocation may take.
+    pub timeout: Option<Duration>,
+    /// The memory, in bytes, the invocation may use.
+    pub memory_bytes: Option<u64>,
+    /// The number of CPUs the invocation may use.
+    pub cpus: Option<f64>,
+}
+
+impl GnarkLimits {
+    /// Read the limits from `SP1_GNARK_TIMEOUT_SECS`, `SP1_GNARK_MEMORY_GB` and `SP1_GNARK_CPUS`.
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            timeout: env::var("SP1_GNARK_TIMEOUT_SECS")
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .map(Duration::from_secs),
+            memory_bytes: env::var("SP1_GNARK_MEMORY_GB")
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .map(|gb| gb << 30),
+            cpus: env::var("SP1_GNARK_CPUS").ok().and_then(|s| s.parse::<f64>().ok()),
+        }
+    }
+
+    /// Whether no limit is set.
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        self.timeout.is_none() && self.memory_bytes.is_none() && self.cpus.is_none()
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    pub fn with_memory_bytes(mut self, memory_bytes: u64) -> Self {
+        self.memory_bytes = Some(memory_bytes);
+        self
+    }
+
+    #[must_use]
+    pub fn with_cpus(mut self, cpus: f64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+}
+
+/// Why a gnark invocation failed.
+#[derive(Debug, Error)]
+pub enum GnarkError {
+    #[error("gnark {system} {operation} timed out after {timeout:?}")]
+    TimedOut { system: &'static str, operation: &'static str, timeout: Duration },
+    #[error("gnark {system} {operation} ran out of memory")]
+    OutOfMemory { system: &'static str, operation: &'static str },
+    #[error("gnark {system} {operation} failed: {message}")]
+    Failed { system: &'static str, operation: &'static str, message: String },
+    #[error("gnark {system} {operation} limits are only enforced in docker")]
+    LimitsUnsupported { system: &'static str, operation: &'static str },
+}
+
+/// Fail if `limits` sets anything, for gnark invocations that cannot enforce limits.
+#[cfg(feature = "native")]
+pub(crate) fn ensure_unlimited(
+    system: &'static str,
+    operation: &'static str,
+    limits: &GnarkLimits,
+) -> Result<(), GnarkError> {
+    if limits.is_unlimited() {
+        Ok(())
+    } else {
+        Err(GnarkError::LimitsUnsupported { system, operation })
+    }
+}
+
+/// Run `f` on its own thread, failing if it takes longer than `timeout` or panics.
+///
+/// A call that times out is left running, so `f` must be something the caller can stop, such as
+/// waiting on a child process it kills.
+#[cfg(not(feature = "native"))]
+pub(crate) fn supervise<T: Send + 'static>(
+    system: &'static str,
+    operation: &'static str,
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, GnarkError> {
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::Builder::new()
+        .name(format!("gnark-{system}-{operation}"))
+        .spawn(move || {
+            let _ = tx.send(f());
+        })
+        .map_err(|e| GnarkError::Failed { system, operation, message: e.to_string() })?;
+
+    let result = match timeout {
+        Some(timeout) => rx.recv_timeout(timeout),
+        None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    match result {
+        Ok(output) => Ok(output),
+        Err(RecvTimeoutError::Timeout) => {
+            let timeout = timeout.unwrap();
+            tracing::error!("gnark {system} {operation} timed out after {timeout:?}, stopping it");
+            Err(GnarkError::TimedOut { system, operation, timeout })
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            let message = match handle.join() {
+                Err(panic) => panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(ToString::to_string))
+                    .unwrap_or_else(|| "panicked".to_string()),
+                Ok(()) => "exited without a result".to_string(),
+            };
+            Err(GnarkError::Failed { system, operation, message })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_unlimited_by_default() {
+        assert!(GnarkLimits::default().is_unlimited());
+        assert!(!GnarkLimits::default().with_timeout(Duration::from_secs(1)).is_unlimited());
+        assert!(!GnarkLimits::default().with_memory_bytes(1 << 30).is_unlimited());
+        assert!(!GnarkLimits::default().with_cpus(1.0).is_unlimited());
+    }
+
+    #[cfg(not(feature = "native"))]
+    #[test]
+    fn test_supervise_times_out() {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let result = supervise("plonk", "prove", Some(Duration::from_millis(10)), move || {
+            let _ = stopped.recv();
+        });
+        assert!(matches!(result, Err(GnarkError::TimedOut { .. })));
+        drop(stop);
+    }
+
+    #[cfg(not(feature = "native"))]
+    #[test]
+    fn test_supervise_reports_panics() {
+        let result = supervise("plonk", "prove", None, || panic!("bad witness"));
+        assert!(
+            matches!(result, Err(GnarkError::Failed { message, .. }) if message == "bad witness")
+        );
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_native_rejects_limits() {
+        assert!(ensure_unlimited("plonk", "prove", &GnarkLimits::default()).is_ok());
+        let limits = GnarkLimits::default().with_timeout(Duration::from_secs(1));
+        assert!(matches!(
+            ensure_unlimited("plonk", "prove", &limits),
+            Err(GnarkError::LimitsUnsupported { .. })
+        ));
+    }
+}
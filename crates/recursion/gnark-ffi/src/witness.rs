@@ -1,8 +1,59 @@
-use std::{fs::File, io::Write};
+//! The witness handed to the gnark provers.
+//!
+//! Besides JSON, a [`GnarkWitness`] has a canonical byte format, which is what crosses the FFI
+//! boundary when proving. Every value is a BN254 scalar field element, and the format (version 1)
+//! is, with lengths and the version as little-endian `u32`s and values as 32-byte big-endian
+//! integers below the field modulus:
+//!
+//! ```text
+//! magic                    b"SP1W"
+//! version                  u32 = 1
+//! vkey_hash                value
+//! committed_values_digest  value
+//! vars                     u32 length, then the values
+//! felts                    u32 length, then the values
+//! exts                     u32 length, then four values per extension element
+//! ```
+//!
+//! A witness has exactly one canonical encoding: values are written in decimal without leading
+//! zeros in JSON and below the modulus in bytes, and decoding rejects anything else, including
+//! trailing bytes.
+//!
+//! The provers stream the witness to the FFI with [`canonical_witness_file`], which encodes it one
+//! value at a time into a file that the gnark side decodes as it reads, so neither side holds a
+//! second copy of the whole witness.
 
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, ensure, Result};
+use num_bigint::BigUint;
 use p3_field::{AbstractExtensionField, AbstractField, PrimeField};
 use serde::{Deserialize, Serialize};
 use sp1_recursion_compiler::ir::{Config, Witness};
+use tempfile::NamedTempFile;
+
+/// The first bytes of a canonically serialized witness.
+pub const WITNESS_MAGIC: &[u8; 4] = b"SP1W";
+
+/// The version of the canonical witness format.
+pub const WITNESS_FORMAT_VERSION: u32 = 1;
+
+/// The number of base field coefficients of an extension element.
+const EXT_DEGREE: usize = 4;
+
+/// The modulus of the BN254 scalar field.
+fn bn254_modulus() -> &'static BigUint {
+    static MODULUS: OnceLock<BigUint> = OnceLock::new();
+    MODULUS.get_or_init(|| {
+        "21888242871839275222246405745257275088548364400416422868183447271981504581785"
+            .parse()
+            .unwrap()
+    })
+}
 
 /// A witness that can be used to initialize values for witness generation inside Gnark.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -48,4 +99,214 @@ impl GnarkWitness {
         let mut file = File::create(path).unwrap();
         file.write_all(serialized.as_bytes()).unwrap();
     }
+
+    /// Serializes the witness in the canonical byte format, see the module documentation.
+    ///
+    /// Fails if a value is not a canonical decimal BN254 scalar or an extension element does not
+    /// have four coefficients.
+    pub fn to_canonical_bytes(&self) -> Result<Vec<u8>> {
+        let values = 2 + self.vars.len() + self.felts.len() + EXT_DEGREE * self.exts.len();
+        let mut bytes = Vec::with_capacity(4 + 4 + 3 * 4 + 32 * values);
+        bytes.extend_from_slice(WITNESS_MAGIC);
+        bytes.extend_from_slice(&WITNESS_FORMAT_VERSION.to_le_bytes());
+        write_value(&mut bytes, &self.vkey_hash)?;
+        write_value(&mut bytes, &self.committed_values_digest)?;
+        for values in [&self.vars, &self.felts] {
+            write_len(&mut bytes, values.len())?;
+            for value in values {
+                write_value(&mut bytes, value)?;
+            }
+        }
+        write_len(&mut bytes, self.exts.len())?;
+        for ext in &self.exts {
+            ensure!(
+                ext.len() == EXT_DEGREE,
+                "extension element with {} coefficients instead of {EXT_DEGREE}",
+                ext.len()
+            );
+            for value in ext {
+                write_value(&mut bytes, value)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Deserializes a witness in the canonical byte format, see the module documentation.
+    pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        ensure!(reader.take(4)? == WITNESS_MAGIC, "not a canonical witness");
+        let version = reader.read_u32()?;
+        ensure!(
+            version == WITNESS_FORMAT_VERSION,
+            "unsupported witness format version {version}, expected {WITNESS_FORMAT_VERSION}"
+        );
+        let vkey_hash = reader.read_value()?;
+        let committed_values_digest = reader.read_value()?;
+        let vars = reader.read_values(1)?.concat();
+        let felts = reader.read_values(1)?.concat();
+        let exts = reader.read_values(EXT_DEGREE)?;
+        ensure!(reader.0.is_empty(), "{} trailing bytes after the witness", reader.0.len());
+        Ok(Self { vars, felts, exts, vkey_hash, committed_values_digest })
+    }
+
+    /// Writes the witness to a given path in the canonical byte format.
+    pub fn save_canonical(&self, path: &str) {
+        let bytes = self.to_canonical_bytes().unwrap();
+        let mut file = File::create(path).unwrap();
+        file.write_all(&bytes).unwrap();
+    }
+}
+
+/// Writes the canonical bytes of the [`GnarkWitness`] of `witness` to `writer`, one value at a
+/// time, without building the [`GnarkWitness`].
+pub fn write_canonical_witness<C: Config>(
+    witness: &Witness<C>,
+    writer: &mut impl Write,
+) -> Result<()> {
+    writer.write_all(WITNESS_MAGIC)?;
+    writer.write_all(&WITNESS_FORMAT_VERSION.to_le_bytes())?;
+    write_field(writer, witness.vkey_hash)?;
+    write_field(writer, witness.committed_values_digest)?;
+
+    // Each list ends with the value 999, as in `GnarkWitness::new`.
+    write_stream_len(writer, witness.vars.len() + 1)?;
+    for var in witness.vars.iter().copied().chain([C::N::from_canonical_usize(999)]) {
+        write_field(writer, var)?;
+    }
+    write_stream_len(writer, witness.felts.len() + 1)?;
+    for felt in witness.felts.iter().copied().chain([C::F::from_canonical_usize(999)]) {
+        write_field(writer, felt)?;
+    }
+    write_stream_len(writer, witness.exts.len() + 1)?;
+    for ext in witness.exts.iter().copied().chain([C::EF::from_canonical_usize(999)]) {
+        for coefficient in ext.as_base_slice() {
+            write_field(writer, *coefficient)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `witness` in the canonical byte format to a temporary file, for the gnark provers.
+pub fn canonical_witness_file<C: Config>(witness: &Witness<C>) -> Result<NamedTempFile> {
+    let mut writer = BufWriter::new(NamedTempFile::new()?);
+    write_canonical_witness(witness, &mut writer)?;
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+fn write_stream_len(writer: &mut impl Write, len: usize) -> Result<()> {
+    let len = u32::try_from(len).map_err(|_| anyhow!("{len} values do not fit in a witness"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_field<F: PrimeField>(writer: &mut impl Write, value: F) -> Result<()> {
+    let be = value.as_canonical_biguint().to_bytes_be();
+    ensure!(be.len() <= 32, "witness value {value} does not fit in 32 bytes");
+    writer.write_all(&[0; 32][be.len()..])?;
+    writer.write_all(&be)?;
+    Ok(())
+}
+
+fn write_len(bytes: &mut Vec<u8>, len: usize) -> Result<()> {
+    let len = u32::try_from(len).map_err(|_| anyhow!("{len} values do not fit in a witness"))?;
+    bytes.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn write_value(bytes: &mut Vec<u8>, value: &str) -> Result<()> {
+    let parsed = value
+        .parse::<BigUint>()
+        .ok()
+        .filter(|parsed| parsed.to_string() == value && parsed < bn254_modulus())
+        .ok_or_else(|| anyhow!("{value:?} is not a canonical BN254 scalar"))?;
+    let be = parsed.to_bytes_be();
+    bytes.resize(bytes.len() + 32 - be.len(), 0);
+    bytes.extend_from_slice(&be);
+    Ok(())
+}
+
+/// The bytes of a canonical witness left to decode.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        ensure!(self.0.len() >= len, "truncated witness");
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_value(&mut self) -> Result<String> {
+        let value = BigUint::from_bytes_be(self.take(32)?);
+        ensure!(&value < bn254_modulus(), "witness value {value} is not below the BN254 modulus");
+        Ok(value.to_string())
+    }
+
+    /// Reads a length-prefixed list of groups of `group` values.
+    fn read_values(&mut self, group: usize) -> Result<Vec<Vec<String>>> {
+        let len = self.read_u32()? as usize;
+        // Check the length before allocating, so a corrupted length fails cleanly.
+        ensure!(self.0.len() / (32 * group) >= len, "truncated witness");
+        (0..len).map(|_| (0..group).map(|_| self.read_value()).collect()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p3_baby_bear::BabyBear;
+    use sp1_recursion_compiler::config::OuterConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_canonical_bytes_round_trip() {
+        let witness = GnarkWitness {
+            vars: vec!["0".to_string(), "123456789".to_string()],
+            felts: vec!["2013265920".to_string()],
+            exts: vec![vec!["1".to_string(), "2".to_string(), "3".to_string(), "999".to_string()]],
+            vkey_hash: (bn254_modulus() - 1u32).to_string(),
+            committed_values_digest: "42".to_string(),
+        };
+        let bytes = witness.to_canonical_bytes().unwrap();
+        assert_eq!(&bytes[..4], WITNESS_MAGIC);
+        let decoded = GnarkWitness::from_canonical_bytes(&bytes).unwrap();
+        assert_eq!(
+            serde_json::to_string(&decoded).unwrap(),
+            serde_json::to_string(&witness).unwrap()
+        );
+
+        assert!(GnarkWitness::from_canonical_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(GnarkWitness::from_canonical_bytes(&trailing).is_err());
+
+        let non_canonical = GnarkWitness { committed_values_digest: "042".to_string(), ..witness };
+        assert!(non_canonical.to_canonical_bytes().is_err());
+    }
+
+    #[test]
+    fn test_streamed_witness_matches_canonical_bytes() {
+        type C = OuterConfig;
+        type N = <C as Config>::N;
+        type EF = <C as Config>::EF;
+        let witness = Witness::<C> {
+            vars: vec![N::from_canonical_u32(7), -N::one()],
+            felts: vec![BabyBear::from_canonical_u32(2013265920)],
+            exts: vec![EF::from_base_slice(&[
+                BabyBear::one(),
+                BabyBear::two(),
+                BabyBear::zero(),
+                BabyBear::from_canonical_u32(5),
+            ])],
+            vkey_hash: N::from_canonical_u32(3),
+            committed_values_digest: N::from_canonical_u32(42),
+        };
+        let mut streamed = Vec::new();
+        write_canonical_witness(&witness, &mut streamed).unwrap();
+        assert_eq!(streamed, GnarkWitness::new(witness).to_canonical_bytes().unwrap());
+    }
 }
@@ -82,7 +82,7 @@ impl ProverClientBuilder {
     /// ```
     #[must_use]
     pub fn mock(&self) -> CpuProverBuilder {
-        CpuProverBuilder { mock: true }
+        CpuProverBuilder { mock: true, vk_verification: None, insecure: false }
     }
 
     /// Builds a [`CpuProver`] specifically for local CPU proving.
@@ -100,7 +100,7 @@ impl ProverClientBuilder {
     /// ```
     #[must_use]
     pub fn cpu(&self) -> CpuProverBuilder {
-        CpuProverBuilder { mock: false }
+        CpuProverBuilder { mock: false, vk_verification: None, insecure: false }
     }
 
     /// Builds a [`CudaProver`] specifically for local proving on NVIDIA GPUs.
@@ -0,0 +1,345 @@
+//! # Confidential Public Values
+//!
+//! Proofs whose public values are encrypted for a designated recipient.
+//!
+//! The proof still commits to the digest of the plaintext public values, so anyone can check that
+//! an [`SP1ProofWithEncryptedPublicValues`] is bound to the values it carries, but only the holder
+//! of the recipient key can read them and verify the proof against them.
+
+use std::{borrow::Borrow, fs::File, io, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use itertools::Itertools;
+use num_bigint::BigUint;
+use p3_baby_bear::BabyBear;
+use p3_field::PrimeField32;
+use serde::{Deserialize, Serialize};
+use sp1_primitives::io::{blake3_hash, sha256_hash, SP1PublicValues};
+use sp1_recursion_core::air::{RecursionPublicValues, RECURSIVE_PROOF_NUM_PV_ELTS};
+use sp1_stark::{
+    air::{PublicValues, SP1_PROOF_NUM_PV_ELTS},
+    Word,
+};
+
+use crate::{SP1Proof, SP1ProofWithPublicValues};
+
+/// The version of the [`EncryptedPublicValues`] format.
+pub const ENCRYPTED_PUBLIC_VALUES_VERSION: u32 = 1;
+
+/// A cipher encrypting public values for a recipient.
+///
+/// This needs to be passed in rather than implemented here so that the recipient keys can be
+/// managed by the caller, e.g. with a public key encryption scheme where only the recipient can
+/// open the envelope.
+pub trait PublicValuesCipher: Send + Sync {
+    /// An identifier of the recipient key, recorded in the envelope so that the recipient can
+    /// pick the key to open it with.
+    fn recipient(&self) -> Vec<u8>;
+
+    /// Encrypt and authenticate `plaintext` for the recipient.
+    fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decrypt `ciphertext`, failing if it was not sealed for this recipient.
+    fn open(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// The hash function a program commits to its public values with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PublicValuesHash {
+    /// SHA-256, used by `sp1_zkvm::io::commit`.
+    Sha256,
+    /// Blake3, used by programs built with the `blake3` feature of `sp1-zkvm`.
+    Blake3,
+}
+
+impl PublicValuesHash {
+    fn hash(self, input: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => sha256_hash(input),
+            Self::Blake3 => blake3_hash(input),
+        }
+    }
+}
+
+/// Public values encrypted for a recipient, with the digest the proof commits to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedPublicValues {
+    /// The version of the format, [`ENCRYPTED_PUBLIC_VALUES_VERSION`].
+    pub version: u32,
+    /// The identifier of the recipient key, see [`PublicValuesCipher::recipient`].
+    pub recipient: Vec<u8>,
+    /// The hash function the proof commits to the public values with.
+    pub hash: PublicValuesHash,
+    /// The digest of the plaintext public values.
+    pub digest: Vec<u8>,
+    /// The public values, sealed by [`PublicValuesCipher::seal`].
+    pub ciphertext: Vec<u8>,
+}
+
+/// A proof with its public values encrypted for a recipient.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SP1ProofWithEncryptedPublicValues {
+    /// The raw proof generated by the SP1 RISC-V zkVM.
+    pub proof: SP1Proof,
+    /// The encrypted public values.
+    pub public_values: EncryptedPublicValues,
+    /// The version of the SP1 RISC-V zkVM.
+    pub sp1_version: String,
+    /// The integrity proof generated by the TEE server.
+    pub tee_proof: Option<Vec<u8>>,
+}
+
+impl SP1ProofWithPublicValues {
+    /// Encrypt the public values of the proof for the recipient of `cipher`.
+    ///
+    /// Fails if the proof does not commit to the digest of its public values.
+    pub fn encrypt_public_values(
+        self,
+        cipher: &dyn PublicValuesCipher,
+    ) -> Result<SP1ProofWithEncryptedPublicValues> {
+        let plaintext = self.public_values.as_slice();
+        let hash = [PublicValuesHash::Sha256, PublicValuesHash::Blake3]
+            .into_iter()
+            .find(|&hash| commits_to(&self.proof, &hash.hash(plaintext)))
+            .ok_or_else(|| anyhow!("the proof does not commit to its public values"))?;
+        let public_values = EncryptedPublicValues {
+            version: ENCRYPTED_PUBLIC_VALUES_VERSION,
+            recipient: cipher.recipient(),
+            hash,
+            digest: hash.hash(plaintext),
+            ciphertext: cipher.seal(plaintext).context("failed to encrypt the public values")?,
+        };
+        Ok(SP1ProofWithEncryptedPublicValues {
+            proof: self.proof,
+            public_values,
+            sp1_version: self.sp1_version,
+            tee_proof: self.tee_proof,
+        })
+    }
+}
+
+impl SP1ProofWithEncryptedPublicValues {
+    /// Whether the proof commits to the digest of the encrypted public values.
+    ///
+    /// This does not verify the proof, which needs the plaintext public values, see
+    /// [`Self::decrypt`].
+    #[must_use]
+    pub fn is_bound(&self) -> bool {
+        commits_to(&self.proof, &self.public_values.digest)
+    }
+
+    /// Decrypt the public values with the key of the recipient, failing if they do not match the
+    /// digest the proof commits to.
+    pub fn decrypt(self, cipher: &dyn PublicValuesCipher) -> Result<SP1ProofWithPublicValues> {
+        let envelope = &self.public_values;
+        ensure!(
+            envelope.version == ENCRYPTED_PUBLIC_VALUES_VERSION,
+            "unsupported encrypted public values version {}",
+            envelope.version
+        );
+        ensure!(self.is_bound(), "the proof does not commit to the encrypted public values");
+        let plaintext =
+            cipher.open(&envelope.ciphertext).context("failed to decrypt the public values")?;
+        if envelope.hash.hash(&plaintext) != envelope.digest {
+            bail!("the decrypted public values do not match their digest");
+        }
+        Ok(SP1ProofWithPublicValues {
+            proof: self.proof,
+            public_values: SP1PublicValues::from_vec(plaintext),
+            sp1_version: self.sp1_version,
+            tee_proof: self.tee_proof,
+        })
+    }
+
+    /// Saves the proof to a path.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        bincode::serialize_into(
+            File::create(path.as_ref()).with_context(|| {
+                format!("failed to create file for saving proof: {}", path.as_ref().display())
+            })?,
+            self,
+        )
+        .map_err(Into::into)
+    }
+
+    /// Loads a proof from a path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        bincode::deserialize_from(File::open(path.as_ref()).with_context(|| {
+            format!("failed to open file for loading proof: {}", path.as_ref().display())
+        })?)
+        .map_err(Into::into)
+    }
+}
+
+/// Whether `proof` commits to the public values digest `digest`.
+fn commits_to(proof: &SP1Proof, digest: &[u8]) -> bool {
+    if digest.len() != 32 {
+        return false;
+    }
+    match proof {
+        SP1Proof::Core(proof) => proof.last().is_some_and(|shard| {
+            if shard.public_values.len() < SP1_PROOF_NUM_PV_ELTS {
+                return false;
+            }
+            let public_values: &PublicValues<Word<BabyBear>, BabyBear> =
+                shard.public_values.as_slice().borrow();
+            digest_bytes(&public_values.committed_value_digest) == digest
+        }),
+        SP1Proof::Compressed(proof) => {
+            if proof.proof.public_values.len() < RECURSIVE_PROOF_NUM_PV_ELTS {
+                return false;
+            }
+            let public_values: &RecursionPublicValues<BabyBear> =
+                proof.proof.public_values.as_slice().borrow();
+            digest_bytes(&public_values.committed_value_digest) == digest
+        }
+        SP1Proof::Plonk(proof) => proof.public_inputs[1] == bn254_digest(digest),
+        SP1Proof::Groth16(proof) => proof.public_inputs[1] == bn254_digest(digest),
+    }
+}
+
+/// The bytes of a committed value digest, each word holding four bytes.
+fn digest_bytes(words: &[Word<BabyBear>]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.0.iter().map(|x| x.as_canonical_u32() as u8)).collect_vec()
+}
+
+/// The public input of a PLONK or Groth16 proof committing to `digest`, with the top 3 bits
+/// masked as in `SP1PublicValues::hash_bn254`.
+fn bn254_digest(digest: &[u8]) -> String {
+    let mut digest = digest.to_vec();
+    digest[0] &= 0b0001_1111;
+    BigUint::from_bytes_be(&digest).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::BorrowMut;
+
+    use p3_field::AbstractField;
+    use sp1_core_executor::SP1ReduceProof;
+    use sp1_prover::{CompressAir, InnerSC, PlonkBn254Proof};
+    use sp1_recursion_circuit::stark::dummy_vk_and_shard_proof;
+    use sp1_stark::{air::MachineAir, shape::OrderedShape, ShardProof, StarkVerifyingKey};
+
+    use super::*;
+
+    /// A cipher xoring the plaintext with the recipient key.
+    struct XorCipher(u8);
+
+    impl PublicValuesCipher for XorCipher {
+        fn recipient(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(plaintext.iter().map(|b| b ^ self.0).collect())
+        }
+
+        fn open(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+            self.seal(ciphertext)
+        }
+    }
+
+    fn digest_words(digest: &[u8]) -> [Word<BabyBear>; 8] {
+        core::array::from_fn(|i| {
+            Word(core::array::from_fn(|j| BabyBear::from_canonical_u8(digest[4 * i + j])))
+        })
+    }
+
+    /// A dummy verifying key and shard proof, with all public values zero.
+    fn dummy_vk_and_shard() -> (StarkVerifyingKey<InnerSC>, ShardProof<InnerSC>) {
+        let machine = CompressAir::compress_machine(InnerSC::default());
+        let shape = OrderedShape { inner: vec![(machine.chips()[0].name(), 4)] };
+        dummy_vk_and_shard_proof(&machine, &shape)
+    }
+
+    fn core_proof(values: &SP1PublicValues) -> SP1ProofWithPublicValues {
+        let (_, mut shard) = dummy_vk_and_shard();
+        let public_values: &mut PublicValues<Word<BabyBear>, BabyBear> =
+            shard.public_values.as_mut_slice().borrow_mut();
+        public_values.committed_value_digest = digest_words(&sha256_hash(values.as_slice()));
+        SP1ProofWithPublicValues {
+            proof: SP1Proof::Core(vec![shard]),
+            public_values: values.clone(),
+            sp1_version: String::new(),
+            tee_proof: None,
+        }
+    }
+
+    fn compressed_proof(values: &SP1PublicValues) -> SP1ProofWithPublicValues {
+        let (vk, mut proof) = dummy_vk_and_shard();
+        let public_values: &mut RecursionPublicValues<BabyBear> =
+            proof.public_values.as_mut_slice().borrow_mut();
+        public_values.committed_value_digest = digest_words(&blake3_hash(values.as_slice()));
+        SP1ProofWithPublicValues {
+            proof: SP1Proof::Compressed(Box::new(SP1ReduceProof { vk, proof })),
+            public_values: values.clone(),
+            sp1_version: String::new(),
+            tee_proof: None,
+        }
+    }
+
+    fn values() -> SP1PublicValues {
+        let mut values = SP1PublicValues::new();
+        values.write(&42u32);
+        values.write(&"confidential".to_string());
+        values
+    }
+
+    #[test]
+    fn test_core_round_trip() {
+        let values = values();
+        let encrypted = core_proof(&values).encrypt_public_values(&XorCipher(7)).unwrap();
+        assert_eq!(encrypted.public_values.hash, PublicValuesHash::Sha256);
+        assert_ne!(encrypted.public_values.ciphertext, values.as_slice());
+        assert!(encrypted.is_bound());
+        let decrypted = encrypted.decrypt(&XorCipher(7)).unwrap();
+        assert_eq!(decrypted.public_values.as_slice(), values.as_slice());
+    }
+
+    #[test]
+    fn test_compressed_reads_recursion_public_values() {
+        let values = values();
+        let encrypted = compressed_proof(&values).encrypt_public_values(&XorCipher(7)).unwrap();
+        assert_eq!(encrypted.public_values.hash, PublicValuesHash::Blake3);
+        assert!(encrypted.is_bound());
+        assert!(encrypted.decrypt(&XorCipher(7)).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_unbound_values() {
+        let mut proof = compressed_proof(&values());
+        proof.public_values.write(&1u8);
+        assert!(proof.encrypt_public_values(&XorCipher(7)).is_err());
+
+        let mut encrypted = core_proof(&values()).encrypt_public_values(&XorCipher(7)).unwrap();
+        encrypted.public_values.digest[0] ^= 1;
+        assert!(!encrypted.is_bound());
+        assert!(encrypted.decrypt(&XorCipher(7)).is_err());
+    }
+
+    #[test]
+    fn test_rejects_wrong_recipient() {
+        let encrypted = core_proof(&values()).encrypt_public_values(&XorCipher(7)).unwrap();
+        assert!(encrypted.decrypt(&XorCipher(8)).is_err());
+    }
+
+    #[test]
+    fn test_plonk_commits_to_masked_digest() {
+        let values = values();
+        let proof = SP1ProofWithPublicValues {
+            proof: SP1Proof::Plonk(PlonkBn254Proof {
+                encoded_proof: String::new(),
+                plonk_vkey_hash: [0; 32],
+                public_inputs: [String::new(), values.hash_bn254().to_string()],
+                raw_proof: String::new(),
+            }),
+            public_values: values,
+            sp1_version: String::new(),
+            tee_proof: None,
+        };
+        let encrypted = proof.encrypt_public_values(&XorCipher(7)).unwrap();
+        assert_eq!(encrypted.public_values.hash, PublicValuesHash::Sha256);
+        assert!(encrypted.is_bound());
+    }
+}
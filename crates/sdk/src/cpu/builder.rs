@@ -2,6 +2,8 @@
 //!
 //! This module provides a builder for the [`CpuProver`].
 
+use sp1_prover::reload::VkVerificationMode;
+
 use super::CpuProver;
 
 /// A builder for the [`CpuProver`].
@@ -9,9 +11,58 @@ use super::CpuProver;
 /// The builder is used to configure the [`CpuProver`] before it is built.
 pub struct CpuProverBuilder {
     pub(crate) mock: bool,
+    pub(crate) vk_verification: Option<VkVerificationMode>,
+    pub(crate) insecure: bool,
 }
 
 impl CpuProverBuilder {
+    /// Sets how the prover checks the verifying keys of recursive proofs.
+    ///
+    /// # Details
+    /// By default, the mode is read from the `SP1_VK_VERIFICATION` environment variable and
+    /// verifying keys are checked against the vk map. Only [`VkVerificationMode::Enforced`]
+    /// should be used in production: proofs made with [`VkVerificationMode::Disabled`] commit to
+    /// a dummy vk root and are rejected by enforcing verifiers.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use sp1_sdk::{ProverClient, VkVerificationMode};
+    ///
+    /// let prover =
+    ///     ProverClient::builder().cpu().vk_verification(VkVerificationMode::Disabled).build();
+    /// ```
+    #[must_use]
+    pub fn vk_verification(mut self, mode: VkVerificationMode) -> Self {
+        self.vk_verification = Some(mode);
+        self
+    }
+
+    /// Generates insecure proofs for staging environments.
+    ///
+    /// # Details
+    /// The prover uses reduced FRI queries and does not check verifying keys. It only proves
+    /// with [`CpuProver::prove_insecure`] and verifies with [`CpuProver::verify_insecure`], and
+    /// rejects the secure proving and verifying methods, so that its proofs cannot be accepted in
+    /// production by mistake. This overrides [`Self::vk_verification`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use sp1_sdk::{ProverClient, SP1ProofMode, SP1Stdin};
+    ///
+    /// let elf = &[1, 2, 3];
+    /// let stdin = SP1Stdin::new();
+    ///
+    /// let prover = ProverClient::builder().cpu().insecure().build();
+    /// let (pk, vk) = prover.setup(elf);
+    /// let proof = prover.prove_insecure(&pk, &stdin, SP1ProofMode::Compressed).unwrap();
+    /// prover.verify_insecure(&proof, &vk).unwrap();
+    /// ```
+    #[must_use]
+    pub fn insecure(mut self) -> Self {
+        self.insecure = true;
+        self
+    }
+
     /// Builds a [`CpuProver`].
     ///
     /// # Details
@@ -26,10 +77,13 @@ impl CpuProverBuilder {
     /// ```
     #[must_use]
     pub fn build(self) -> CpuProver {
-        if self.mock {
-            CpuProver::mock()
-        } else {
-            CpuProver::new()
+        if self.insecure {
+            return CpuProver::insecure(self.mock);
+        }
+        match self.vk_verification {
+            Some(mode) => CpuProver::with_vk_verification(mode, self.mock),
+            None if self.mock => CpuProver::mock(),
+            None => CpuProver::new(),
         }
     }
 }
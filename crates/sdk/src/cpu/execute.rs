@@ -179,6 +179,50 @@ impl<'a> CpuExecuteBuilder<'a> {
         self
     }
 
+    /// Stream the public values of the guest program to a writer as they are committed.
+    ///
+    /// This avoids keeping very large public values in memory. The public values returned by
+    /// [`CpuExecuteBuilder::run`] are then empty.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use sp1_sdk::{include_elf, Prover, ProverClient, SP1Stdin};
+    ///
+    /// let mut file = std::fs::File::create("public_values.bin").unwrap();
+    ///
+    /// let elf = &[1, 2, 3];
+    /// let stdin = SP1Stdin::new();
+    ///
+    /// let client = ProverClient::builder().cpu().build();
+    /// client.execute(elf, &stdin).public_values(&mut file).run();
+    /// ```
+    #[must_use]
+    pub fn public_values<W: IoWriter>(mut self, writer: &'a mut W) -> Self {
+        self.context_builder.public_values(writer);
+        self
+    }
+
+    /// Stream the output of the guest program, written with `sp1_zkvm::io::output`, to a writer
+    /// as the program executes.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use sp1_sdk::{include_elf, Prover, ProverClient, SP1Stdin};
+    ///
+    /// let mut output = std::io::stdout();
+    ///
+    /// let elf = &[1, 2, 3];
+    /// let stdin = SP1Stdin::new();
+    ///
+    /// let client = ProverClient::builder().cpu().build();
+    /// client.execute(elf, &stdin).output(&mut output).run();
+    /// ```
+    #[must_use]
+    pub fn output<W: IoWriter>(mut self, writer: &'a mut W) -> Self {
+        self.context_builder.output(writer);
+        self
+    }
+
     /// Executes the program on the input with the built arguments.
     ///
     /// # Details
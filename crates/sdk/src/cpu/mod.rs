@@ -6,27 +6,32 @@ pub mod builder;
 pub mod execute;
 pub mod prove;
 
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
 use execute::CpuExecuteBuilder;
 use prove::CpuProveBuilder;
-use sp1_core_executor::{SP1Context, SP1ContextBuilder};
+use sp1_core_executor::{SP1Context, SP1ContextBuilder, DOMAIN_TAG_WORDS};
 use sp1_core_machine::io::SP1Stdin;
 use sp1_prover::{
     components::CpuProverComponents,
+    metering::CostStage,
+    reload::{ProverConfigBundle, VkVerificationMode},
     verify::{verify_groth16_bn254_public_inputs, verify_plonk_bn254_public_inputs},
-    Groth16Bn254Proof, PlonkBn254Proof, SP1CoreProofData, SP1ProofWithMetadata, SP1Prover,
+    GnarkLimits, Groth16Bn254Proof, PlonkBn254Proof, SP1CoreProofData, SP1ProofWithMetadata,
+    SP1Prover,
 };
-use sp1_stark::{SP1CoreOpts, SP1ProverOpts};
+use sp1_stark::{FriParamsMismatch, ProgressOpts, SP1CoreOpts, SP1FriOpts, SP1ProverOpts};
 
 use crate::{
-    install::try_install_circuit_artifacts, prover::verify_proof, Prover, SP1Proof, SP1ProofMode,
-    SP1ProofWithPublicValues, SP1ProvingKey, SP1VerificationError, SP1VerifyingKey,
+    install::try_install_circuit_artifacts, prover::verify_proof, Prover,
+    SP1InsecureProofWithPublicValues, SP1Proof, SP1ProofMode, SP1ProofWithPublicValues,
+    SP1ProvingKey, SP1VerificationError, SP1VerifyingKey,
 };
 
 /// A prover that uses the CPU to execute and prove programs.
 pub struct CpuProver {
     pub(crate) prover: SP1Prover<CpuProverComponents>,
     pub(crate) mock: bool,
+    pub(crate) insecure: bool,
 }
 
 impl CpuProver {
@@ -39,7 +44,44 @@ impl CpuProver {
     /// Creates a new [`CpuProver`] in mock mode.
     #[must_use]
     pub fn mock() -> Self {
-        Self { prover: SP1Prover::new(), mock: true }
+        Self { prover: SP1Prover::new(), mock: true, insecure: false }
+    }
+
+    /// Creates a new [`CpuProver`] checking verifying keys with `mode`.
+    #[must_use]
+    pub fn with_vk_verification(mode: VkVerificationMode, mock: bool) -> Self {
+        let config = ProverConfigBundle::from_env_with_vk_verification(mode);
+        Self { prover: SP1Prover::with_config(config), mock, insecure: false }
+    }
+
+    /// Creates a new [`CpuProver`] generating insecure proofs for staging environments, see
+    /// [`Self::prove_insecure`].
+    ///
+    /// The prover uses [`SP1FriOpts::insecure`] parameters and does not check verifying keys,
+    /// since its recursion programs are not in the vk map.
+    ///
+    /// Panics if a prover setting in the environment is invalid.
+    #[must_use]
+    pub fn insecure(mock: bool) -> Self {
+        let config =
+            ProverConfigBundle::from_env_with_vk_verification(VkVerificationMode::Disabled);
+        let prover = SP1Prover::with_fri_opts_and_config(&SP1FriOpts::default().insecure(), config)
+            .unwrap_or_else(|e| panic!("{e}"));
+        Self { prover, mock, insecure: true }
+    }
+
+    /// Generate the proving and verifying keys of a program tagged with a domain, so that its
+    /// proofs only verify against keys set up with the same tag.
+    ///
+    /// See [`SP1Prover::setup_with_domain`] for details.
+    pub fn setup_with_domain(
+        &self,
+        elf: &[u8],
+        domain_tag: [u32; DOMAIN_TAG_WORDS],
+    ) -> Result<(SP1ProvingKey, SP1VerifyingKey)> {
+        let (pk, _, _, vk) =
+            self.prover.setup_with_domain(elf, domain_tag).map_err(|e| anyhow::anyhow!(e))?;
+        Ok((pk, vk))
     }
 
     /// Creates a new [`CpuExecuteBuilder`] for simulating the execution of a program on the CPU.
@@ -92,10 +134,74 @@ impl CpuProver {
             context_builder: SP1ContextBuilder::default(),
             core_opts: SP1CoreOpts::default(),
             recursion_opts: SP1CoreOpts::recursion(),
+            progress: ProgressOpts::default(),
             mock: self.mock,
         }
     }
 
+    /// Proves the given program on the given input in the core or compressed mode with insecure
+    /// parameters, for staging environments that want realistic proof objects quickly.
+    ///
+    /// The client must be built with
+    /// [`CpuProverBuilder::insecure`](builder::CpuProverBuilder::insecure). The proof is
+    /// returned as an [`SP1InsecureProofWithPublicValues`], which only
+    /// [`Self::verify_insecure`] accepts, so it cannot be mistaken for a secure proof.
+    pub fn prove_insecure(
+        &self,
+        pk: &SP1ProvingKey,
+        stdin: &SP1Stdin,
+        mode: SP1ProofMode,
+    ) -> Result<SP1InsecureProofWithPublicValues> {
+        ensure!(self.insecure, "insecure proofs are generated by a client built with `insecure()`");
+        ensure!(
+            matches!(mode, SP1ProofMode::Core | SP1ProofMode::Compressed),
+            "insecure proofs are only generated in the core and compressed modes, not {mode:?}"
+        );
+
+        let fri_opts = self.prover.fri_opts();
+        let opts = SP1ProverOpts { fri_opts, ..SP1ProverOpts::default() };
+        let proof = self.prove_stages(pk, stdin, opts, SP1Context::default(), mode)?;
+        Ok(SP1InsecureProofWithPublicValues {
+            proof: proof.proof,
+            public_values: proof.public_values,
+            sp1_version: proof.sp1_version,
+            fri_opts,
+        })
+    }
+
+    /// Verifies an insecure proof generated by [`Self::prove_insecure`] with the parameters of
+    /// this client. Never use this to accept proofs in production.
+    pub fn verify_insecure(
+        &self,
+        bundle: &SP1InsecureProofWithPublicValues,
+        vkey: &SP1VerifyingKey,
+    ) -> Result<(), SP1VerificationError> {
+        if !self.insecure {
+            return Err(SP1VerificationError::Other(anyhow!(
+                "insecure proofs are verified by a client built with `insecure()`"
+            )));
+        }
+        let fri_opts = self.prover.fri_opts();
+        for ((stage, requested), (_, actual)) in
+            bundle.fri_opts.stages().into_iter().zip(fri_opts.stages())
+        {
+            FriParamsMismatch::check(stage, requested, actual)
+                .map_err(|e| SP1VerificationError::Other(e.into()))?;
+        }
+        let bundle = SP1ProofWithPublicValues {
+            proof: bundle.proof.clone(),
+            public_values: bundle.public_values.clone(),
+            sp1_version: bundle.sp1_version.clone(),
+            tee_proof: None,
+        };
+        if self.mock {
+            tracing::warn!("using mock verifier");
+            return Self::mock_verify(&bundle, vkey);
+        }
+        tracing::warn!("verifying a proof generated with insecure parameters");
+        verify_proof(self.inner(), self.version(), &bundle, vkey)
+    }
+
     pub(crate) fn prove_impl<'a>(
         &'a self,
         pk: &SP1ProvingKey,
@@ -104,7 +210,20 @@ impl CpuProver {
         context: SP1Context<'a>,
         mode: SP1ProofMode,
     ) -> Result<SP1ProofWithPublicValues> {
-        let program = self.prover.get_program(&pk.elf).unwrap();
+        ensure!(!self.insecure, "an insecure client only generates proofs with `prove_insecure`");
+        self.prove_stages(pk, stdin, opts, context, mode)
+    }
+
+    fn prove_stages<'a>(
+        &'a self,
+        pk: &SP1ProvingKey,
+        stdin: &SP1Stdin,
+        opts: SP1ProverOpts,
+        context: SP1Context<'a>,
+        mode: SP1ProofMode,
+    ) -> Result<SP1ProofWithPublicValues> {
+        self.prover.check_vk_shape_config(&pk.vk)?;
+        let program = self.prover.get_program_in_domain(&pk.elf, pk.vk.domain_tag).unwrap();
 
         // If we're in mock mode, return a mock proof.
         if self.mock {
@@ -112,8 +231,13 @@ impl CpuProver {
         }
 
         // Generate the core proof.
+        let metadata = context.trace_metadata.clone();
+        let metadata_span = metadata.span();
         let proof: SP1ProofWithMetadata<SP1CoreProofData> =
-            self.prover.prove_core(&pk.pk, program, stdin, opts, context)?;
+            self.prover.prove_core(&pk.pk, program, stdin, opts.clone(), context)?;
+
+        // The core prover records the metadata itself, the remaining stages are recorded here.
+        let _metadata_span = metadata_span.entered();
         if mode == SP1ProofMode::Core {
             return Ok(SP1ProofWithPublicValues::new(
                 SP1Proof::Core(proof.proof.0),
@@ -126,7 +250,9 @@ impl CpuProver {
         let deferred_proofs =
             stdin.proofs.iter().map(|(reduce_proof, _)| reduce_proof.clone()).collect();
         let public_values = proof.public_values.clone();
-        let reduce_proof = self.prover.compress(&pk.vk, proof, deferred_proofs, opts)?;
+        let compress_cost = self.prover.cost_meter.stage(&metadata, CostStage::Compress);
+        let reduce_proof = self.prover.compress(&pk.vk, proof, deferred_proofs, opts.clone())?;
+        drop(compress_cost);
         if mode == SP1ProofMode::Compressed {
             return Ok(SP1ProofWithPublicValues::new(
                 SP1Proof::Compressed(Box::new(reduce_proof)),
@@ -136,7 +262,8 @@ impl CpuProver {
         }
 
         // Generate the shrink proof.
-        let compress_proof = self.prover.shrink(reduce_proof, opts)?;
+        let _wrap_cost = self.prover.cost_meter.stage(&metadata, CostStage::Wrap);
+        let compress_proof = self.prover.shrink(reduce_proof, opts.clone())?;
 
         // Generate the wrap proof.
         let outer_proof = self.prover.wrap_bn254(compress_proof, opts)?;
@@ -153,7 +280,11 @@ impl CpuProver {
                     try_install_circuit_artifacts("groth16")
                 };
 
-                let proof = self.prover.wrap_groth16_bn254(outer_proof, &groth16_bn254_artifacts);
+                let proof = self.prover.try_wrap_groth16_bn254(
+                    outer_proof,
+                    &groth16_bn254_artifacts,
+                    &GnarkLimits::from_env(),
+                )?;
                 Ok(SP1ProofWithPublicValues::new(
                     SP1Proof::Groth16(proof),
                     public_values,
@@ -169,7 +300,11 @@ impl CpuProver {
                 } else {
                     try_install_circuit_artifacts("plonk")
                 };
-                let proof = self.prover.wrap_plonk_bn254(outer_proof, &plonk_bn254_artifacts);
+                let proof = self.prover.try_wrap_plonk_bn254(
+                    outer_proof,
+                    &plonk_bn254_artifacts,
+                    &GnarkLimits::from_env(),
+                )?;
                 Ok(SP1ProofWithPublicValues::new(
                     SP1Proof::Plonk(proof),
                     public_values,
@@ -233,6 +368,9 @@ impl Prover<CpuProverComponents> for CpuProver {
         bundle: &SP1ProofWithPublicValues,
         vkey: &SP1VerifyingKey,
     ) -> Result<(), SP1VerificationError> {
+        if self.insecure {
+            return Err(SP1VerificationError::InsecureProver);
+        }
         if self.mock {
             tracing::warn!("using mock verifier");
             return Self::mock_verify(bundle, vkey);
@@ -244,6 +382,6 @@ impl Prover<CpuProverComponents> for CpuProver {
 impl Default for CpuProver {
     fn default() -> Self {
         let prover = SP1Prover::new();
-        Self { prover, mock: false }
+        Self { prover, mock: false, insecure: false }
     }
 }
@@ -6,7 +6,11 @@ use anyhow::Result;
 use sp1_core_executor::{IoWriter, SP1ContextBuilder};
 use sp1_core_machine::io::SP1Stdin;
 use sp1_prover::SP1ProvingKey;
-use sp1_stark::{SP1CoreOpts, SP1ProverOpts};
+use std::sync::Arc;
+
+use sp1_stark::{
+    ProgressOpts, ProgressReporter, SP1CompressOpts, SP1CoreOpts, SP1DeferredOpts, SP1ProverOpts,
+};
 
 use super::CpuProver;
 use crate::{SP1ProofMode, SP1ProofWithPublicValues};
@@ -23,6 +27,7 @@ pub struct CpuProveBuilder<'a> {
     pub(crate) stdin: SP1Stdin,
     pub(crate) core_opts: SP1CoreOpts,
     pub(crate) recursion_opts: SP1CoreOpts,
+    pub(crate) progress: ProgressOpts,
     pub(crate) mock: bool,
 }
 
@@ -284,6 +289,66 @@ impl<'a> CpuProveBuilder<'a> {
         self
     }
 
+    /// Stream the output of the guest program, written with `sp1_zkvm::io::output`, to a writer
+    /// as the program executes. The output is not part of the proof.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use sp1_sdk::{include_elf, Prover, ProverClient, SP1Stdin};
+    ///
+    /// let mut output = std::io::stdout();
+    ///
+    /// let elf = &[1, 2, 3];
+    /// let stdin = SP1Stdin::new();
+    ///
+    /// let client = ProverClient::builder().cpu().build();
+    /// let (pk, vk) = client.setup(elf);
+    /// let proof = client.prove(&pk, &stdin).output(&mut output).run();
+    /// ```
+    #[must_use]
+    pub fn output<W: IoWriter>(mut self, writer: &'a mut W) -> Self {
+        self.context_builder.output(writer);
+        self
+    }
+
+    /// Set the job id recorded on the tracing spans of the prover.
+    ///
+    /// The resources used by each stage of the proof are charged to the job, see
+    /// [`sp1_prover::metering`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use sp1_sdk::{include_elf, Prover, ProverClient, SP1Stdin};
+    ///
+    /// let elf = &[1, 2, 3];
+    /// let stdin = SP1Stdin::new();
+    ///
+    /// let client = ProverClient::builder().cpu().build();
+    /// let (pk, vk) = client.setup(elf);
+    /// let proof = client.prove(&pk, &stdin).job_id("job-42").tenant_id("acme").run();
+    /// let cost = client.inner().take_job_cost("job-42");
+    /// ```
+    #[must_use]
+    pub fn job_id(mut self, job_id: impl Into<String>) -> Self {
+        self.context_builder.job_id(job_id);
+        self
+    }
+
+    /// Set the tenant id recorded on the tracing spans of the prover.
+    #[must_use]
+    pub fn tenant_id(mut self, tenant_id: impl Into<String>) -> Self {
+        self.context_builder.tenant_id(tenant_id);
+        self
+    }
+
+    /// Report the progress of the proof to `reporter`, e.g. when a shard is proven or the proof is
+    /// wrapped.
+    #[must_use]
+    pub fn progress(mut self, reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.progress = ProgressOpts::new(reporter);
+        self
+    }
+
     /// Run the prover with the built arguments.
     ///
     /// # Details
@@ -303,9 +368,25 @@ impl<'a> CpuProveBuilder<'a> {
     /// ```
     pub fn run(self) -> Result<SP1ProofWithPublicValues> {
         // Get the arguments.
-        let Self { prover, mode, pk, stdin, mut context_builder, core_opts, recursion_opts, mock } =
-            self;
-        let opts = SP1ProverOpts { core_opts, recursion_opts };
+        let Self {
+            prover,
+            mode,
+            pk,
+            stdin,
+            mut context_builder,
+            core_opts,
+            recursion_opts,
+            progress,
+            mock,
+        } = self;
+        let opts = SP1ProverOpts {
+            core_opts,
+            recursion_opts,
+            deferred_opts: SP1DeferredOpts::default(),
+            fri_opts: prover.prover.fri_opts(),
+            compress_opts: SP1CompressOpts::default(),
+            progress,
+        };
         let context = context_builder.build();
 
         // Dump the program and stdin to files for debugging if `SP1_DUMP` is set.
@@ -10,7 +10,7 @@ use prove::CudaProveBuilder;
 use sp1_core_executor::SP1ContextBuilder;
 use sp1_core_machine::io::SP1Stdin;
 use sp1_cuda::{MoongateServer, SP1CudaProver};
-use sp1_prover::{components::CpuProverComponents, SP1Prover};
+use sp1_prover::{components::CpuProverComponents, GnarkLimits, SP1Prover};
 
 use crate::{
     cpu::execute::CpuExecuteBuilder, install::try_install_circuit_artifacts, Prover, SP1Proof,
@@ -129,7 +129,11 @@ impl CudaProver {
             } else {
                 try_install_circuit_artifacts("plonk")
             };
-            let proof = self.cpu_prover.wrap_plonk_bn254(outer_proof, &plonk_bn254_artifacts);
+            let proof = self.cpu_prover.try_wrap_plonk_bn254(
+                outer_proof,
+                &plonk_bn254_artifacts,
+                &GnarkLimits::from_env(),
+            )?;
             let proof_with_pv = SP1ProofWithPublicValues::new(
                 SP1Proof::Plonk(proof),
                 public_values,
@@ -146,7 +150,11 @@ impl CudaProver {
                 try_install_circuit_artifacts("groth16")
             };
 
-            let proof = self.cpu_prover.wrap_groth16_bn254(outer_proof, &groth16_bn254_artifacts);
+            let proof = self.cpu_prover.try_wrap_groth16_bn254(
+                outer_proof,
+                &groth16_bn254_artifacts,
+                &GnarkLimits::from_env(),
+            )?;
             let proof_with_pv = SP1ProofWithPublicValues::new(
                 SP1Proof::Groth16(proof),
                 public_values,
@@ -26,6 +26,7 @@
 
 pub mod artifacts;
 pub mod client;
+pub mod confidential;
 pub mod cpu;
 pub mod cuda;
 pub mod env;
@@ -52,13 +53,16 @@ pub use prover::{Prover, SP1VerificationError};
 
 // Re-export the build utilities and executor primitives.
 pub use sp1_build::include_elf;
-pub use sp1_core_executor::{ExecutionReport, Executor, HookEnv, SP1Context, SP1ContextBuilder};
+pub use sp1_core_executor::{
+    ExecutionReport, Executor, HookEnv, InputKind, InputSegment, SP1Context, SP1ContextBuilder,
+};
 
 // Re-export the machine/prover primitives.
-pub use sp1_core_machine::io::SP1Stdin;
+pub use sp1_core_machine::io::{HintReplayError, HintReplayer, SP1Stdin};
 pub use sp1_primitives::io::SP1PublicValues;
 pub use sp1_prover::{
-    HashableKey, ProverMode, SP1Prover, SP1ProvingKey, SP1VerifyingKey, SP1_CIRCUIT_VERSION,
+    reload::VkVerificationMode, HashableKey, ProverMode, SP1Prover, SP1ProvingKey, SP1VerifyingKey,
+    SP1_CIRCUIT_VERSION,
 };
 
 // Re-export the utilities.
@@ -67,8 +71,12 @@ pub use utils::setup_logger;
 #[cfg(test)]
 mod tests {
     use sp1_primitives::io::SP1PublicValues;
+    use sp1_stark::INSECURE_NUM_QUERIES;
 
-    use crate::{utils, Prover, ProverClient, SP1Stdin};
+    use crate::{
+        utils, Prover, ProverClient, SP1ProofMode, SP1ProofWithPublicValues, SP1Stdin,
+        SP1VerificationError,
+    };
 
     #[test]
     fn test_execute() {
@@ -122,6 +130,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_insecure_client_parameters() {
+        let client = ProverClient::builder().mock().insecure().build();
+        let fri_opts = client.inner().fri_opts();
+        assert!(fri_opts.stages().iter().all(|(_, p)| p.num_queries == INSECURE_NUM_QUERIES));
+        assert!(!ProverClient::builder().mock().build().insecure);
+    }
+
+    #[test]
+    fn test_e2e_insecure_core() {
+        utils::setup_logger();
+        let client = ProverClient::builder().cpu().insecure().build();
+        let elf = test_artifacts::FIBONACCI_ELF;
+        let (pk, vk) = client.setup(elf);
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&10usize);
+
+        // The insecure client only proves and verifies insecure proofs.
+        let proof = client.prove_insecure(&pk, &stdin, SP1ProofMode::Core).unwrap();
+        client.verify_insecure(&proof, &vk).unwrap();
+        assert!(client.prove(&pk, &stdin).run().is_err());
+        let bundle = SP1ProofWithPublicValues::new(
+            proof.proof.clone(),
+            proof.public_values.clone(),
+            proof.sp1_version.clone(),
+        );
+        assert!(matches!(client.verify(&bundle, &vk), Err(SP1VerificationError::InsecureProver)));
+
+        // A secure client rejects them.
+        let secure = ProverClient::builder().cpu().build();
+        assert!(secure.prove_insecure(&pk, &stdin, SP1ProofMode::Core).is_err());
+        assert!(secure.verify_insecure(&proof, &vk).is_err());
+        assert!(secure.verify(&bundle, &vk).is_err());
+    }
+
     #[test]
     fn test_e2e_io_override() {
         utils::setup_logger();
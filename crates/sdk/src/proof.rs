@@ -19,7 +19,8 @@ use sp1_core_executor::SP1ReduceProof;
 use sp1_primitives::io::SP1PublicValues;
 use sp1_prover::{CoreSC, Groth16Bn254Proof, HashableKey, InnerSC, PlonkBn254Proof, SP1ProvingKey};
 use sp1_stark::{
-    septic_digest::SepticDigest, ShardCommitment, ShardOpenedValues, ShardProof, StarkVerifyingKey,
+    septic_digest::SepticDigest, SP1FriOpts, ShardCommitment, ShardOpenedValues, ShardProof,
+    StarkVerifyingKey,
 };
 use strum_macros::{EnumDiscriminants, EnumTryAs};
 
@@ -90,6 +91,25 @@ impl From<ProofFromNetwork> for SP1ProofWithPublicValues {
     }
 }
 
+/// A core or compressed proof generated with insecure parameters for staging environments, see
+/// [`CpuProver::prove_insecure`](crate::CpuProver::prove_insecure).
+///
+/// It is a separate type from [`SP1ProofWithPublicValues`] so that it cannot be passed to
+/// [`Prover::verify`](crate::Prover::verify) by mistake. Only
+/// [`CpuProver::verify_insecure`](crate::CpuProver::verify_insecure) accepts it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SP1InsecureProofWithPublicValues {
+    /// The core or compressed proof.
+    pub proof: SP1Proof,
+    /// The public values generated by the SP1 RISC-V zkVM.
+    pub public_values: SP1PublicValues,
+    /// The version of the SP1 RISC-V zkVM.
+    pub sp1_version: String,
+    /// The FRI parameters the proof was generated with. The recursion programs were built without
+    /// vk verification.
+    pub fri_opts: SP1FriOpts,
+}
+
 impl SP1ProofWithPublicValues {
     /// Creates a new [`SP1ProofWithPublicValues`] from the proof, public values, and SP1 version.
     ///
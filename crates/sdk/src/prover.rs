@@ -81,6 +81,10 @@ pub enum SP1VerificationError {
     /// An error that occurs when the Groth16 verification fails.
     #[error("Groth16 verification error: {0}")]
     Groth16(anyhow::Error),
+    /// An error that occurs when a prover set up with insecure parameters is asked to verify a
+    /// proof as secure.
+    #[error("Insecure prover, its proofs are only accepted by verify_insecure")]
+    InsecureProver,
     /// An error that occurs when the proof is invalid.
     #[error("Unexpected error: {0:?}")]
     Other(anyhow::Error),
@@ -164,7 +164,7 @@ pub mod baby_bear_poseidon2 {
     use serde::{Deserialize, Serialize};
     use sp1_primitives::RC_16_30;
 
-    use crate::{Com, StarkGenericConfig, ZeroCommitment, DIGEST_SIZE};
+    use crate::{Com, FriParams, StarkGenericConfig, ZeroCommitment, DIGEST_SIZE};
 
     pub type Val = BabyBear;
     pub type Challenge = BinomialExtensionField<Val, 4>;
@@ -246,9 +246,24 @@ pub mod baby_bear_poseidon2 {
         FriConfig { log_blowup: 3, num_queries, proof_of_work_bits: 16, mmcs: challenge_mmcs }
     }
 
+    #[must_use]
+    pub fn fri_config_with_params(params: FriParams) -> FriConfig<ChallengeMmcs> {
+        let perm = my_perm();
+        let hash = MyHash::new(perm.clone());
+        let compress = MyCompress::new(perm.clone());
+        let challenge_mmcs = ChallengeMmcs::new(ValMmcs::new(hash, compress));
+        FriConfig {
+            log_blowup: params.log_blowup,
+            num_queries: params.num_queries,
+            proof_of_work_bits: params.proof_of_work_bits,
+            mmcs: challenge_mmcs,
+        }
+    }
+
     enum BabyBearPoseidon2Type {
         Default,
         Compressed,
+        Custom(FriParams),
     }
 
     #[derive(Deserialize)]
@@ -295,6 +310,29 @@ pub mod baby_bear_poseidon2 {
             let pcs = Pcs::new(27, dft, val_mmcs, fri_config);
             Self { pcs, perm, config_type: BabyBearPoseidon2Type::Compressed }
         }
+
+        #[must_use]
+        pub fn with_fri_params(params: FriParams) -> Self {
+            let perm = my_perm();
+            let hash = MyHash::new(perm.clone());
+            let compress = MyCompress::new(perm.clone());
+            let val_mmcs = ValMmcs::new(hash, compress);
+            let dft = Dft {};
+            let fri_config = fri_config_with_params(params);
+            let pcs = Pcs::new(27, dft, val_mmcs, fri_config);
+            Self { pcs, perm, config_type: BabyBearPoseidon2Type::Custom(params) }
+        }
+
+        /// The FRI parameters of the config.
+        #[must_use]
+        pub fn fri_params(&self) -> FriParams {
+            let fri_config = self.pcs.fri_config();
+            FriParams {
+                log_blowup: fri_config.log_blowup,
+                num_queries: fri_config.num_queries,
+                proof_of_work_bits: fri_config.proof_of_work_bits,
+            }
+        }
     }
 
     impl Clone for BabyBearPoseidon2 {
@@ -302,6 +340,7 @@ pub mod baby_bear_poseidon2 {
             match self.config_type {
                 BabyBearPoseidon2Type::Default => Self::new(),
                 BabyBearPoseidon2Type::Compressed => Self::compressed(),
+                BabyBearPoseidon2Type::Custom(params) => Self::with_fri_params(params),
             }
         }
     }
@@ -30,6 +30,7 @@ mod lookup;
 mod machine;
 mod opts;
 mod permutation;
+mod progress;
 mod prover;
 mod quotient;
 mod record;
@@ -37,6 +38,7 @@ pub mod septic_curve;
 pub mod septic_digest;
 pub mod septic_extension;
 pub mod shape;
+mod trace_pool;
 mod types;
 mod util;
 mod verifier;
@@ -51,9 +53,11 @@ pub use lookup::*;
 pub use machine::*;
 pub use opts::*;
 pub use permutation::*;
+pub use progress::*;
 pub use prover::*;
 pub use quotient::*;
 pub use record::*;
+pub use trace_pool::*;
 pub use types::*;
 pub use verifier::*;
 pub use word::*;
@@ -504,6 +504,42 @@ impl<SC: StarkGenericConfig, A: MachineAir<Val<SC>> + Air<SymbolicAirBuilder<Val
         proof: &MachineProof<SC>,
         challenger: &mut SC::Challenger,
     ) -> Result<(), MachineVerificationError<SC>>
+    where
+        SC::Challenger: Clone,
+        A: for<'a> Air<VerifierConstraintFolder<'a, SC>>,
+    {
+        self.verify_shards(vk, proof, challenger)?;
+
+        // Verify the cumulative sum is 0.
+        tracing::debug_span!("verify global cumulative sum is 0").in_scope(|| {
+            let sum = proof
+                .shard_proofs
+                .iter()
+                .map(ShardProof::global_cumulative_sum)
+                .chain(once(vk.initial_global_cumulative_sum))
+                .sum::<SepticDigest<Val<SC>>>();
+
+            if !sum.is_zero() {
+                return Err(MachineVerificationError::NonZeroCumulativeSum(
+                    InteractionScope::Global,
+                    0,
+                ));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Verify each shard proof of a proof given a verifying key.
+    ///
+    /// The global cumulative sum is not checked, so this also accepts a prefix of the shards of a
+    /// proof, but does not show that the proof is complete.
+    pub fn verify_shards(
+        &self,
+        vk: &StarkVerifyingKey<SC>,
+        proof: &MachineProof<SC>,
+        challenger: &mut SC::Challenger,
+    ) -> Result<(), MachineVerificationError<SC>>
     where
         SC::Challenger: Clone,
         A: for<'a> Air<VerifierConstraintFolder<'a, SC>>,
@@ -535,25 +571,6 @@ impl<SC: StarkGenericConfig, A: MachineAir<Val<SC>> + Air<SymbolicAirBuilder<Val
                 })?;
             }
 
-            Ok(())
-        })?;
-
-        // Verify the cumulative sum is 0.
-        tracing::debug_span!("verify global cumulative sum is 0").in_scope(|| {
-            let sum = proof
-                .shard_proofs
-                .iter()
-                .map(ShardProof::global_cumulative_sum)
-                .chain(once(vk.initial_global_cumulative_sum))
-                .sum::<SepticDigest<Val<SC>>>();
-
-            if !sum.is_zero() {
-                return Err(MachineVerificationError::NonZeroCumulativeSum(
-                    InteractionScope::Global,
-                    0,
-                ));
-            }
-
             Ok(())
         })
     }
@@ -585,6 +602,8 @@ pub enum MachineVerificationError<SC: StarkGenericConfig> {
     CpuLogDegreeTooLarge(usize),
     /// The verification key is not allowed.
     InvalidVerificationKey,
+    /// The verification key was set up for a different version or configuration of the prover.
+    IncompatibleVerificationKey(String),
 }
 
 impl<SC: StarkGenericConfig> Debug for MachineVerificationError<SC> {
@@ -627,6 +646,9 @@ impl<SC: StarkGenericConfig> Debug for MachineVerificationError<SC> {
             MachineVerificationError::InvalidVerificationKey => {
                 write!(f, "Invalid verification key")
             }
+            MachineVerificationError::IncompatibleVerificationKey(s) => {
+                write!(f, "Incompatible verification key: {}", s)
+            }
         }
     }
 }
@@ -1,23 +1,59 @@
-use std::env;
+use std::{env, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
 
+use crate::{ProgressOpts, ProgressReporter};
+
 const MAX_SHARD_SIZE: usize = 1 << 21;
 const RECURSION_MAX_SHARD_SIZE: usize = 1 << 22;
 const MAX_SHARD_BATCH_SIZE: usize = 8;
 const DEFAULT_TRACE_GEN_WORKERS: usize = 1;
 const DEFAULT_CHECKPOINTS_CHANNEL_CAPACITY: usize = 128;
 const DEFAULT_RECORDS_AND_TRACES_CHANNEL_CAPACITY: usize = 1;
+const MAX_CHECKPOINTS_CHANNEL_CAPACITY: usize = 512;
+const MAX_RECORDS_AND_TRACES_CHANNEL_CAPACITY: usize = 32;
+/// A rough estimate of the size of a checkpoint per cycle it covers.
+const CHECKPOINT_BYTES_PER_CYCLE: usize = 16;
 const MAX_DEFERRED_SPLIT_THRESHOLD: usize = 1 << 15;
+const DEFAULT_DEFERRED_BATCH_SIZE: usize = 1;
+const DEFAULT_DEFERRED_MAX_SUBTREE_LEAVES: usize = 1 << 10;
+const DEFAULT_COMPRESS_ARITY: usize = 2;
+const DEFAULT_FIRST_LAYER_BATCH_SIZE: usize = 1;
+const DEFAULT_PROOF_OF_WORK_BITS: usize = 16;
+
+/// The minimum conjectured security, in bits, accepted by [`SP1FriOpts::check_security`].
+pub const MIN_SECURITY_BITS: usize = 100;
+
+/// The number of FRI queries of every stage of [`SP1FriOpts::insecure`].
+pub const INSECURE_NUM_QUERIES: usize = 8;
+
+/// The number of bits of the challenge field, which caps the achievable security.
+const CHALLENGE_FIELD_BITS: usize = 124;
 
 /// Options to configure the SP1 prover for core and recursive proofs.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SP1ProverOpts {
     /// Options for the core prover.
     pub core_opts: SP1CoreOpts,
     /// Options for the recursion prover.
     pub recursion_opts: SP1CoreOpts,
+    /// Options for recursively verifying deferred proofs.
+    #[serde(default)]
+    pub deferred_opts: SP1DeferredOpts,
+    /// The FRI parameters of each stage.
+    ///
+    /// They must be those the prover was created with, since its machines and recursion programs
+    /// are fixed by them: the prover returns [`FriParamsMismatch`] for a stage requested with
+    /// other parameters.
+    #[serde(default)]
+    pub fri_opts: SP1FriOpts,
+    /// Options for the recursion tree built by compress.
+    #[serde(default)]
+    pub compress_opts: SP1CompressOpts,
+    /// The reporter of the progress of proving, if any. It is not serialized.
+    #[serde(skip)]
+    pub progress: ProgressOpts,
 }
 
 impl SP1ProverOpts {
@@ -99,6 +135,121 @@ impl SP1ProverOpts {
 
         opts
     }
+
+    /// The options proving a single job as fast as this machine allows, see
+    /// [`SP1ProverOptsPreset::Latency`].
+    #[must_use]
+    pub fn latency_optimized() -> Self {
+        SP1ProverOptsPreset::Latency.opts()
+    }
+
+    /// The options proving the most jobs per hour on this machine, see
+    /// [`SP1ProverOptsPreset::Throughput`].
+    #[must_use]
+    pub fn throughput() -> Self {
+        SP1ProverOptsPreset::Throughput.opts()
+    }
+
+    /// The options keeping the memory of proving within `memory_budget` bytes, see
+    /// [`SP1ProverOptsPreset::LowMemory`].
+    #[must_use]
+    pub fn low_memory(memory_budget: usize) -> Self {
+        SP1ProverOptsPreset::LowMemory(memory_budget).opts()
+    }
+
+    /// The options for fast, insecure proofs in development, see
+    /// [`SP1ProverOptsPreset::DevInsecure`].
+    #[must_use]
+    pub fn dev_insecure() -> Self {
+        SP1ProverOptsPreset::DevInsecure.opts()
+    }
+
+    /// Report the progress of proving to `reporter`.
+    #[must_use]
+    pub fn with_progress(mut self, reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.progress = ProgressOpts::new(reporter);
+        self
+    }
+}
+
+/// A named set of prover options, computed from the hardware on top of the options of
+/// [`SP1ProverOpts::auto`].
+///
+/// The FRI parameters of a preset only take effect in a prover created with them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SP1ProverOptsPreset {
+    /// Prove a single job as fast as possible.
+    ///
+    /// Core shards are proven one at a time, as soon as their traces are generated, and the
+    /// memory the automatic options give to batches of shards goes to more trace generation
+    /// workers instead, up to one per eight cores. The recursion stages get as many workers.
+    Latency,
+    /// Prove as many jobs per hour as possible, several jobs sharing the machine.
+    ///
+    /// Each job keeps a single trace generation worker per stage and the largest shard batches
+    /// the memory allows, so that the cores are shared between the jobs rather than idling on the
+    /// traces of one of them. The records and traces of a second batch are buffered on machines
+    /// with enough memory, so that the prover does not wait on trace generation.
+    Throughput,
+    /// Keep the buffered checkpoints, records and traces within a memory budget, in bytes.
+    ///
+    /// The shard size and split thresholds are those of a machine with the memory of the budget,
+    /// shards are proven one at a time with a single trace generation worker, and the channel
+    /// capacities are derived from the budget, see [`SP1CoreOpts::channel_capacities`].
+    LowMemory(usize),
+    /// Make proofs quickly in development, with [`SP1FriOpts::insecure`] parameters.
+    ///
+    /// The blowups are kept so that programs have the verifying keys of secure proofs, but the
+    /// proofs must never be relied on.
+    DevInsecure,
+}
+
+impl SP1ProverOptsPreset {
+    /// The options of the preset on this machine, layered on [`SP1ProverOpts::auto`].
+    #[must_use]
+    pub fn opts(self) -> SP1ProverOpts {
+        let num_cpus = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+        self.layer_on(SP1ProverOpts::auto(), num_cpus)
+    }
+
+    /// The options of the preset on a machine with `num_cpus` cores, layered on `opts`.
+    #[must_use]
+    pub fn layer_on(self, mut opts: SP1ProverOpts, num_cpus: usize) -> SP1ProverOpts {
+        match self {
+            Self::Latency => {
+                // A batch of the automatic options is proven while the next two are buffered, so
+                // that memory holds as many single shards, split between the workers and the
+                // channel of records and traces.
+                let batch_size = opts.core_opts.shard_batch_size.max(1);
+                let workers = ((3 * batch_size - 1) / 2).clamp(1, (num_cpus / 8).max(1));
+                opts.core_opts.shard_batch_size = 1;
+                opts.core_opts.trace_gen_workers = workers;
+                opts.core_opts.records_and_traces_channel_capacity = workers;
+                opts.recursion_opts.trace_gen_workers = (num_cpus / 8).max(1);
+                opts.recursion_opts.records_and_traces_channel_capacity = (num_cpus / 8).max(1);
+                opts
+            }
+            Self::Throughput => {
+                if opts.core_opts.shard_batch_size > 1 {
+                    opts.core_opts.records_and_traces_channel_capacity = 2;
+                }
+                opts
+            }
+            Self::LowMemory(memory_budget) => {
+                let budget_opts = SP1ProverOpts::cpu(memory_budget >> 30);
+                opts.core_opts = budget_opts.core_opts;
+                opts.recursion_opts = budget_opts.recursion_opts;
+                opts.core_opts.shard_batch_size = 1;
+                opts.core_opts.memory_budget = Some(memory_budget);
+                opts.recursion_opts.shard_batch_size = 1;
+                opts
+            }
+            Self::DevInsecure => {
+                opts.fri_opts = opts.fri_opts.insecure();
+                opts
+            }
+        }
+    }
 }
 
 /// Options for the core prover.
@@ -116,11 +267,32 @@ pub struct SP1CoreOpts {
     pub checkpoints_channel_capacity: usize,
     /// The capacity of the channel for records and traces.
     pub records_and_traces_channel_capacity: usize,
+    /// The memory, in bytes, the buffered checkpoints, records and traces may take. If set, the
+    /// channel capacities are derived from it, see [`SP1CoreOpts::channel_capacities`].
+    ///
+    /// Only the core prover uses it.
+    #[serde(default)]
+    pub memory_budget: Option<usize>,
+    /// The minimum size of a shard, in cycles, before a phase marker of the program ends it. If
+    /// unset, phase markers do not affect sharding.
+    #[serde(default)]
+    pub min_phase_shard_size: Option<usize>,
+    /// Whether the core prover collects a histogram of the real trace heights of every chip into
+    /// its proving profile.
+    #[serde(default)]
+    pub trace_height_histograms: bool,
 }
 
 impl Default for SP1ProverOpts {
     fn default() -> Self {
-        Self { core_opts: SP1CoreOpts::default(), recursion_opts: SP1CoreOpts::recursion() }
+        Self {
+            core_opts: SP1CoreOpts::default(),
+            recursion_opts: SP1CoreOpts::recursion(),
+            deferred_opts: SP1DeferredOpts::default(),
+            fri_opts: SP1FriOpts::default(),
+            compress_opts: SP1CompressOpts::default(),
+            progress: ProgressOpts::default(),
+        }
     }
 }
 
@@ -153,6 +325,9 @@ impl Default for SP1CoreOpts {
                     |_| DEFAULT_RECORDS_AND_TRACES_CHANNEL_CAPACITY,
                     |s| s.parse::<usize>().unwrap_or(DEFAULT_RECORDS_AND_TRACES_CHANNEL_CAPACITY),
                 ),
+            memory_budget: memory_budget_from_env(),
+            min_phase_shard_size: min_phase_shard_size_from_env(),
+            trace_height_histograms: trace_height_histograms_from_env(),
         };
 
         let divisor = 1 << default_log2_divisor;
@@ -207,10 +382,133 @@ impl SP1CoreOpts {
                     |_| DEFAULT_RECORDS_AND_TRACES_CHANNEL_CAPACITY,
                     |s| s.parse::<usize>().unwrap_or(DEFAULT_RECORDS_AND_TRACES_CHANNEL_CAPACITY),
                 ),
+            memory_budget: memory_budget_from_env(),
+            min_phase_shard_size: min_phase_shard_size_from_env(),
+            trace_height_histograms: trace_height_histograms_from_env(),
+        }
+    }
+
+    /// The capacities of the checkpoints channel and of the records and traces channel, given an
+    /// estimate of the memory taken by the records and traces of a shard.
+    ///
+    /// Without a memory budget, these are the configured capacities. Otherwise, an eighth of the
+    /// budget goes to checkpoints and the rest to records and traces, besides the batches held by
+    /// the trace generation workers and the prover.
+    #[must_use]
+    pub fn channel_capacities(&self, shard_bytes: usize) -> (usize, usize) {
+        let Some(budget) = self.memory_budget else {
+            return (self.checkpoints_channel_capacity, self.records_and_traces_channel_capacity);
+        };
+        let batch_size = self.shard_batch_size.max(1);
+
+        let checkpoint_bytes = (self.shard_size * batch_size * CHECKPOINT_BYTES_PER_CYCLE).max(1);
+        let checkpoints =
+            (budget / 8 / checkpoint_bytes).clamp(1, MAX_CHECKPOINTS_CHANNEL_CAPACITY);
+
+        let batch_bytes = shard_bytes.saturating_mul(batch_size).max(1);
+        let in_flight = self.trace_gen_workers + 1;
+        let records_and_traces = ((budget - budget / 8) / batch_bytes)
+            .saturating_sub(in_flight)
+            .clamp(1, MAX_RECORDS_AND_TRACES_CHANNEL_CAPACITY);
+
+        (checkpoints, records_and_traces)
+    }
+}
+
+/// Read the minimum size of a phase shard from `MIN_PHASE_SHARD_SIZE`, in cycles.
+fn min_phase_shard_size_from_env() -> Option<usize> {
+    env::var("MIN_PHASE_SHARD_SIZE").ok().and_then(|s| s.parse::<usize>().ok())
+}
+
+/// Read whether to collect trace height histograms from `TRACE_HEIGHT_HISTOGRAMS`.
+fn trace_height_histograms_from_env() -> bool {
+    env::var("TRACE_HEIGHT_HISTOGRAMS").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Read the memory budget from `MEMORY_BUDGET_GB`, in GiB.
+fn memory_budget_from_env() -> Option<usize> {
+    env::var("MEMORY_BUDGET_GB").ok().and_then(|s| s.parse::<usize>().ok()).map(|gb| gb << 30)
+}
+
+/// Options for recursively verifying deferred proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SP1DeferredOpts {
+    /// The maximum number of deferred proofs verified by a single recursion program.
+    ///
+    /// The allowed recursion verifying keys only cover deferred programs verifying a single proof,
+    /// so larger batches require vk verification to be disabled or a custom vk map.
+    pub batch_size: usize,
+    /// How deferred proofs are grouped into batches.
+    pub ordering: DeferredProofOrdering,
+    /// The maximum number of batches reduced by each subtree of a deferred aggregation tree, which
+    /// bounds the number of proofs held in memory at once.
+    pub max_subtree_leaves: usize,
+}
+
+impl Default for SP1DeferredOpts {
+    fn default() -> Self {
+        Self {
+            batch_size: env::var("DEFERRED_BATCH_SIZE").map_or_else(
+                |_| DEFAULT_DEFERRED_BATCH_SIZE,
+                |s| s.parse::<usize>().unwrap_or(DEFAULT_DEFERRED_BATCH_SIZE),
+            ),
+            ordering: DeferredProofOrdering::default(),
+            max_subtree_leaves: env::var("DEFERRED_MAX_SUBTREE_LEAVES").map_or_else(
+                |_| DEFAULT_DEFERRED_MAX_SUBTREE_LEAVES,
+                |s| s.parse::<usize>().unwrap_or(DEFAULT_DEFERRED_MAX_SUBTREE_LEAVES),
+            ),
         }
     }
 }
 
+/// Options for the recursion tree built by compress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SP1CompressOpts {
+    /// The number of proofs reduced by each join program.
+    ///
+    /// One of 2, 3 or 4 with fixed recursion shapes, up to the max compress arity the prover
+    /// precompiled join programs for (2 by default, see `SP1_MAX_COMPRESS_ARITY`) and the vk map
+    /// was built for. Larger arities join more proofs per recursion layer, so the tree has fewer
+    /// layers, at the cost of more memory per join.
+    pub arity: usize,
+    /// The number of core shard proofs verified by each program of the first recursion layer.
+    ///
+    /// Larger batches cut the number of recursion proofs of long executions, at the cost of larger
+    /// first layer programs. Only 1 is supported with fixed recursion shapes, since the shapes and
+    /// vk map only cover programs verifying a single shard.
+    pub first_layer_batch_size: usize,
+}
+
+impl Default for SP1CompressOpts {
+    fn default() -> Self {
+        Self {
+            arity: env::var("COMPRESS_ARITY").map_or_else(
+                |_| DEFAULT_COMPRESS_ARITY,
+                |s| s.parse::<usize>().unwrap_or(DEFAULT_COMPRESS_ARITY),
+            ),
+            first_layer_batch_size: env::var("COMPRESS_FIRST_LAYER_BATCH_SIZE").map_or_else(
+                |_| DEFAULT_FIRST_LAYER_BATCH_SIZE,
+                |s| s.parse::<usize>().unwrap_or(DEFAULT_FIRST_LAYER_BATCH_SIZE),
+            ),
+        }
+    }
+}
+
+/// The policy used to group deferred proofs into batches.
+///
+/// The deferred proofs digest is a hash chain over the proofs in the order the program verified
+/// them, so batches are always formed from consecutive proofs. The policy only decides where one
+/// batch ends and the next begins.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeferredProofOrdering {
+    /// Fill each batch up to the batch size, in input order.
+    #[default]
+    InputOrder,
+    /// Start a new batch whenever the verifying key changes, so that every batch verifies proofs
+    /// of a single program. This keeps batch shapes uniform, which improves program cache hits.
+    GroupByVk,
+}
+
 /// Options for splitting deferred events.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SplitOpts {
@@ -247,12 +545,293 @@ impl SplitOpts {
     }
 }
 
+/// The parameters of the FRI protocol used to prove a stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FriParams {
+    /// The log2 of the blowup factor of the Reed-Solomon code.
+    pub log_blowup: usize,
+    /// The number of FRI queries.
+    pub num_queries: usize,
+    /// The number of proof of work bits ground before sampling queries.
+    pub proof_of_work_bits: usize,
+}
+
+impl FriParams {
+    /// Create FRI parameters with the default proof of work.
+    #[must_use]
+    pub const fn new(log_blowup: usize, num_queries: usize) -> Self {
+        Self { log_blowup, num_queries, proof_of_work_bits: DEFAULT_PROOF_OF_WORK_BITS }
+    }
+
+    /// Create FRI parameters with `num_queries` overridden by `FRI_QUERIES`, if set.
+    fn from_env(log_blowup: usize, default_num_queries: usize) -> Self {
+        let num_queries = env::var("FRI_QUERIES")
+            .map_or(default_num_queries, |s| s.parse::<usize>().unwrap_or(default_num_queries));
+        Self::new(log_blowup, num_queries)
+    }
+
+    /// The conjectured security of the parameters, in bits.
+    ///
+    /// Under the standard conjecture on the proximity gaps of Reed-Solomon codes, every query
+    /// contributes `log_blowup` bits and the proof of work adds its bits on top. The total is
+    /// capped by the size of the challenge field.
+    #[must_use]
+    pub fn conjectured_security_bits(&self) -> usize {
+        (self.log_blowup * self.num_queries + self.proof_of_work_bits).min(CHALLENGE_FIELD_BITS)
+    }
+}
+
+/// The FRI parameters of every stage of the prover.
+///
+/// The parameters are fixed when the machines of the prover are created. Changing them changes the
+/// recursion programs, so proofs made with non-default parameters can only be verified with vk
+/// verification disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SP1FriOpts {
+    /// The parameters for the core shards.
+    pub core: FriParams,
+    /// The parameters for the lift, join and deferred programs.
+    pub compress: FriParams,
+    /// The parameters for the shrink program.
+    pub shrink: FriParams,
+    /// The parameters for the wrap program.
+    pub wrap: FriParams,
+    /// Whether to accept parameters below [`MIN_SECURITY_BITS`]. Defaults to whether `SP1_DEV`
+    /// is set.
+    pub allow_insecure: bool,
+}
+
+impl Default for SP1FriOpts {
+    fn default() -> Self {
+        let dev_mode =
+            env::var("SP1_DEV").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        Self {
+            core: FriParams::from_env(1, 100),
+            compress: FriParams::from_env(1, 100),
+            shrink: FriParams::from_env(2, 50),
+            wrap: if dev_mode { FriParams::new(4, 1) } else { FriParams::from_env(4, 25) },
+            allow_insecure: dev_mode,
+        }
+    }
+}
+
+impl SP1FriOpts {
+    /// The parameters of each stage, labeled with the stage name.
+    #[must_use]
+    pub fn stages(&self) -> [(&'static str, FriParams); 4] {
+        [
+            ("core", self.core),
+            ("compress", self.compress),
+            ("shrink", self.shrink),
+            ("wrap", self.wrap),
+        ]
+    }
+
+    /// The conjectured security of the whole proof, in bits, which is that of its weakest stage.
+    #[must_use]
+    pub fn conjectured_security_bits(&self) -> usize {
+        self.stages().iter().map(|(_, params)| params.conjectured_security_bits()).min().unwrap()
+    }
+
+    /// These parameters with [`INSECURE_NUM_QUERIES`] queries in every stage, for staging
+    /// environments that want realistic proofs quickly but do not rely on their security.
+    ///
+    /// The blowups are kept, so programs have the same verifying keys as with these parameters.
+    #[must_use]
+    pub fn insecure(&self) -> Self {
+        let insecure =
+            |params: FriParams| FriParams { num_queries: INSECURE_NUM_QUERIES, ..params };
+        Self {
+            core: insecure(self.core),
+            compress: insecure(self.compress),
+            shrink: insecure(self.shrink),
+            wrap: insecure(self.wrap),
+            allow_insecure: true,
+        }
+    }
+
+    /// Check that every stage reaches [`MIN_SECURITY_BITS`], unless insecure parameters are
+    /// allowed.
+    pub fn check_security(&self) -> Result<(), InsecureFriParams> {
+        for (stage, params) in self.stages() {
+            let security_bits = params.conjectured_security_bits();
+            if security_bits < MIN_SECURITY_BITS {
+                if !self.allow_insecure {
+                    return Err(InsecureFriParams { stage, security_bits });
+                }
+                tracing::warn!(
+                    "{} FRI parameters only provide {} bits of security",
+                    stage,
+                    security_bits
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when FRI parameters do not reach [`MIN_SECURITY_BITS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsecureFriParams {
+    /// The stage with insecure parameters.
+    pub stage: &'static str,
+    /// The conjectured security of the stage, in bits.
+    pub security_bits: usize,
+}
+
+impl std::fmt::Display for InsecureFriParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} FRI parameters provide {} bits of security, at least {} are required",
+            self.stage, self.security_bits, MIN_SECURITY_BITS
+        )
+    }
+}
+
+impl std::error::Error for InsecureFriParams {}
+
+/// The error returned when a stage is requested with FRI parameters other than those the prover
+/// was created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FriParamsMismatch {
+    /// The stage requested with other parameters.
+    pub stage: &'static str,
+    /// The parameters of the request.
+    pub requested: FriParams,
+    /// The parameters of the prover.
+    pub actual: FriParams,
+}
+
+impl FriParamsMismatch {
+    /// Check that the parameters requested for `stage` are those of the prover.
+    pub fn check(stage: &'static str, requested: FriParams, actual: FriParams) -> Result<(), Self> {
+        if requested != actual {
+            return Err(Self { stage, requested, actual });
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for FriParamsMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the {} FRI parameters {:?} differ from those of the prover, {:?}; create the prover \
+             with these parameters to use them",
+            self.stage, self.requested, self.actual
+        )
+    }
+}
+
+impl std::error::Error for FriParamsMismatch {}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::print_stdout)]
 
     use super::*;
 
+    #[test]
+    fn test_fri_security() {
+        assert_eq!(FriParams::new(1, 100).conjectured_security_bits(), 116);
+        assert_eq!(FriParams::new(4, 100).conjectured_security_bits(), CHALLENGE_FIELD_BITS);
+
+        let mut opts = SP1FriOpts {
+            core: FriParams::new(1, 100),
+            compress: FriParams::new(1, 100),
+            shrink: FriParams::new(2, 50),
+            wrap: FriParams::new(4, 1),
+            allow_insecure: false,
+        };
+        assert_eq!(opts.conjectured_security_bits(), 20);
+        assert_eq!(
+            opts.check_security(),
+            Err(InsecureFriParams { stage: "wrap", security_bits: 20 })
+        );
+        opts.allow_insecure = true;
+        assert!(opts.check_security().is_ok());
+    }
+
+    #[test]
+    fn test_insecure_fri_opts() {
+        let opts = SP1FriOpts { allow_insecure: false, ..SP1FriOpts::default() };
+        let insecure = opts.insecure();
+        for ((_, params), (_, insecure_params)) in opts.stages().iter().zip(insecure.stages()) {
+            assert_eq!(insecure_params.log_blowup, params.log_blowup);
+            assert_eq!(insecure_params.num_queries, INSECURE_NUM_QUERIES);
+        }
+        assert!(insecure.conjectured_security_bits() < MIN_SECURITY_BITS);
+        assert!(insecure.check_security().is_ok());
+    }
+
+    #[test]
+    fn test_channel_capacities() {
+        let mut opts = SP1CoreOpts {
+            shard_size: 1 << 20,
+            shard_batch_size: 1,
+            trace_gen_workers: 2,
+            checkpoints_channel_capacity: 128,
+            records_and_traces_channel_capacity: 1,
+            memory_budget: None,
+            min_phase_shard_size: None,
+            ..SP1CoreOpts::default()
+        };
+        assert_eq!(opts.channel_capacities(1 << 30), (128, 1));
+
+        // A small machine keeps a single batch of records buffered.
+        opts.memory_budget = Some(2 << 30);
+        assert_eq!(opts.channel_capacities(1 << 30), (16, 1));
+
+        // A large machine buffers more, up to the maximum capacities.
+        opts.memory_budget = Some(64 << 30);
+        assert_eq!(opts.channel_capacities(1 << 30), (512, 32));
+        assert_eq!(opts.channel_capacities(4 << 30), (512, 11));
+    }
+
+    #[test]
+    fn test_opts_presets() {
+        let auto = SP1ProverOpts::cpu(128);
+        let layer = |preset: SP1ProverOptsPreset, num_cpus| preset.layer_on(auto.clone(), num_cpus);
+        let latency = layer(SP1ProverOptsPreset::Latency, 64);
+        assert_eq!(latency.core_opts.shard_batch_size, 1);
+        assert_eq!(latency.core_opts.trace_gen_workers, 5);
+        assert_eq!(latency.core_opts.shard_size, auto.core_opts.shard_size);
+        assert_eq!(layer(SP1ProverOptsPreset::Latency, 4).core_opts.trace_gen_workers, 1);
+
+        let throughput = layer(SP1ProverOptsPreset::Throughput, 64);
+        assert_eq!(throughput.core_opts.shard_batch_size, auto.core_opts.shard_batch_size);
+        assert_eq!(throughput.core_opts.trace_gen_workers, 1);
+
+        let low_memory = layer(SP1ProverOptsPreset::LowMemory(16 << 30), 64);
+        assert_eq!(low_memory.core_opts.memory_budget, Some(16 << 30));
+        assert_eq!(low_memory.core_opts.shard_batch_size, 1);
+        assert_eq!(low_memory.core_opts.shard_size, SP1ProverOpts::cpu(16).core_opts.shard_size);
+
+        // Only the development preset changes the FRI parameters, to insecure ones.
+        for preset in [SP1ProverOptsPreset::Latency, SP1ProverOptsPreset::Throughput] {
+            assert_eq!(layer(preset, 64).fri_opts, auto.fri_opts);
+        }
+        let dev = layer(SP1ProverOptsPreset::DevInsecure, 64);
+        assert_eq!(dev.core_opts, auto.core_opts);
+        assert!(dev.fri_opts.allow_insecure);
+        for (_, params) in dev.fri_opts.stages() {
+            assert_eq!(params.num_queries, INSECURE_NUM_QUERIES);
+        }
+        assert!(dev.fri_opts.conjectured_security_bits() < MIN_SECURITY_BITS);
+    }
+
+    #[test]
+    fn test_fri_params_mismatch() {
+        let opts = SP1FriOpts::default();
+        assert!(FriParamsMismatch::check("core", opts.core, opts.core).is_ok());
+        let insecure = opts.insecure();
+        assert_eq!(
+            FriParamsMismatch::check("wrap", insecure.wrap, opts.wrap),
+            Err(FriParamsMismatch { stage: "wrap", requested: insecure.wrap, actual: opts.wrap })
+        );
+    }
+
     #[test]
     fn test_opts() {
         let opts = SP1ProverOpts::cpu(8);
@@ -0,0 +1,136 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+/// A milestone of the proving pipeline, see [`ProgressReporter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// A core shard was proven.
+    ShardProved {
+        /// The index of the shard.
+        shard: u32,
+        /// The number of shards proven so far, including this one.
+        num_proven: usize,
+        /// The time spent proving the shard.
+        elapsed: Duration,
+    },
+    /// Every core shard was proven.
+    CoreFinished {
+        /// The number of shards of the proof.
+        num_shards: usize,
+        /// The time spent proving the shards.
+        elapsed: Duration,
+    },
+    /// A layer of the recursion tree of compress was proven.
+    LayerCompleted {
+        /// The layer, the first layer being zero.
+        layer: usize,
+        /// The number of nodes of the layer.
+        num_proofs: usize,
+        /// The time since compress started.
+        elapsed: Duration,
+    },
+    /// The recursion tree was reduced to a single proof.
+    CompressFinished {
+        /// The number of proofs of the tree.
+        num_proofs: usize,
+        /// The time spent compressing.
+        elapsed: Duration,
+    },
+    /// Shrinking the compressed proof started.
+    ShrinkStarted,
+    /// The compressed proof was shrunk.
+    ShrinkFinished {
+        /// The time spent shrinking.
+        elapsed: Duration,
+    },
+    /// Wrapping the shrunk proof over BN254 started.
+    WrapStarted,
+    /// The shrunk proof was wrapped over BN254.
+    WrapFinished {
+        /// The time spent wrapping.
+        elapsed: Duration,
+    },
+}
+
+/// Receives the [`ProgressEvent`]s of the proving pipeline, e.g. to show a progress bar or to
+/// export metrics, instead of scraping the tracing logs.
+///
+/// Events are reported from the proving threads, so implementations should return quickly.
+pub trait ProgressReporter: Send + Sync {
+    /// Receive `event`.
+    fn report(&self, event: ProgressEvent);
+}
+
+/// An optional [`ProgressReporter`], set in [`SP1ProverOpts`](crate::SP1ProverOpts).
+///
+/// Progress options compare equal when they share the same reporter.
+#[derive(Clone, Default)]
+pub struct ProgressOpts(Option<Arc<dyn ProgressReporter>>);
+
+impl ProgressOpts {
+    /// Report the progress to `reporter`.
+    #[must_use]
+    pub fn new(reporter: Arc<dyn ProgressReporter>) -> Self {
+        Self(Some(reporter))
+    }
+
+    /// Whether a reporter is set.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Report `event` to the reporter, if any.
+    pub fn report(&self, event: ProgressEvent) {
+        if let Some(reporter) = &self.0 {
+            reporter.report(event);
+        }
+    }
+}
+
+impl fmt::Debug for ProgressOpts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ProgressOpts").field(&self.is_enabled()).finish()
+    }
+}
+
+impl PartialEq for ProgressOpts {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ProgressOpts {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl ProgressReporter for Counter {
+        fn report(&self, _: ProgressEvent) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_progress_opts() {
+        let counter = Arc::new(Counter::default());
+        let opts = ProgressOpts::new(counter.clone());
+        opts.clone().report(ProgressEvent::ShrinkStarted);
+        ProgressOpts::default().report(ProgressEvent::ShrinkStarted);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        assert_eq!(opts, opts.clone());
+        assert_ne!(opts, ProgressOpts::new(Arc::new(Counter::default())));
+        assert_ne!(opts, ProgressOpts::default());
+        assert_eq!(ProgressOpts::default(), ProgressOpts::default());
+    }
+}
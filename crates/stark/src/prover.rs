@@ -23,6 +23,7 @@ use crate::{
     air::MachineAir, lookup::InteractionBuilder, opts::SP1CoreOpts, record::MachineRecord,
     Challenger, DebugConstraintBuilder, MachineChip, MachineProof, PackedChallenge, PcsProverData,
     ProverConstraintFolder, ShardCommitment, ShardMainData, ShardProof, StarkVerifyingKey,
+    TracePool, TracePoolStats,
 };
 
 /// An algorithmic & hardware independent prover implementation for any [`MachineAir`].
@@ -163,6 +164,11 @@ pub trait MachineProver<SC: StarkGenericConfig, A: MachineAir<SC::Val>>:
     {
         self.machine().debug_constraints(pk, records, challenger);
     }
+
+    /// The reuse statistics of the trace buffers of the prover, if it pools them.
+    fn trace_pool_stats(&self) -> Option<TracePoolStats> {
+        None
+    }
 }
 
 /// A proving key for any [`MachineAir`] that is agnostic to hardware.
@@ -183,6 +189,7 @@ pub trait MachineProvingKey<SC: StarkGenericConfig>: Send + Sync {
 /// A prover implementation based on x86 and ARM CPUs.
 pub struct CpuProver<SC: StarkGenericConfig, A> {
     machine: StarkMachine<SC, A>,
+    trace_pool: TracePool<Val<SC>>,
 }
 
 /// An error that occurs during the execution of the [`CpuProver`].
@@ -210,7 +217,7 @@ where
     type Error = CpuProverError;
 
     fn new(machine: StarkMachine<SC, A>) -> Self {
-        Self { machine }
+        Self { machine, trace_pool: TracePool::from_env() }
     }
 
     fn machine(&self) -> &StarkMachine<SC, A> {
@@ -251,7 +258,7 @@ where
             .iter()
             .map(|(_, trace)| {
                 let domain = pcs.natural_domain_for_degree(trace.height());
-                (domain, trace.to_owned())
+                (domain, self.trace_pool.copy(trace))
             })
             .collect::<Vec<_>>();
 
@@ -366,6 +373,11 @@ where
             );
         }
 
+        // The main traces are committed to, so their buffers can be reused by the next shards.
+        for trace in traces {
+            self.trace_pool.recycle(trace);
+        }
+
         let domains_and_perm_traces =
             tracing::debug_span!("flatten permutation traces and collect domains").in_scope(|| {
                 permutation_traces
@@ -669,6 +681,10 @@ where
 
         Ok(MachineProof { shard_proofs })
     }
+
+    fn trace_pool_stats(&self) -> Option<TracePoolStats> {
+        Some(self.trace_pool.stats())
+    }
 }
 
 impl<SC> MachineProvingKey<SC> for StarkProvingKey<SC>
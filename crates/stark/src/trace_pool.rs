@@ -0,0 +1,159 @@
+use std::{env, sync::Mutex};
+
+use hashbrown::HashMap;
+use p3_matrix::{dense::RowMajorMatrix, Matrix};
+
+/// The default number of bytes of trace buffers retained by a [`TracePool`].
+const DEFAULT_TRACE_POOL_BYTES: usize = 1 << 30;
+
+/// The reuse statistics of a [`TracePool`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TracePoolStats {
+    /// The number of traces copied into a recycled buffer.
+    pub hits: u64,
+    /// The number of traces copied into a fresh buffer.
+    pub misses: u64,
+    /// The number of bytes of the buffers retained by the pool.
+    pub retained_bytes: usize,
+}
+
+impl TracePoolStats {
+    /// The fraction of the traces copied into a recycled buffer.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn reuse_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+
+    /// Add up the statistics of two pools.
+    #[must_use]
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            retained_bytes: self.retained_bytes + other.retained_bytes,
+        }
+    }
+}
+
+/// A pool of trace buffers recycled across shards and recursion nodes.
+///
+/// With fixed shapes, the dimensions of the traces of a chip repeat from one shard to the next.
+/// The pool keeps the buffers of the traces of proven shards, keyed by their width and height, and
+/// copies the traces of the next shards into them instead of allocating new ones.
+///
+/// At most `SP1_TRACE_POOL_BYTES` bytes of buffers are retained, 1 GiB by default. Setting it to
+/// zero disables the pool.
+#[derive(Debug)]
+pub struct TracePool<T> {
+    max_bytes: usize,
+    inner: Mutex<TracePoolInner<T>>,
+}
+
+#[derive(Debug)]
+struct TracePoolInner<T> {
+    buffers: HashMap<(usize, usize), Vec<Vec<T>>>,
+    stats: TracePoolStats,
+}
+
+impl<T: Clone + Send + Sync> TracePool<T> {
+    /// Create a pool retaining at most `max_bytes` bytes of buffers.
+    #[must_use]
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            inner: Mutex::new(TracePoolInner {
+                buffers: HashMap::new(),
+                stats: TracePoolStats::default(),
+            }),
+        }
+    }
+
+    /// Create a pool retaining at most `SP1_TRACE_POOL_BYTES` bytes of buffers.
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self::new(
+            env::var("SP1_TRACE_POOL_BYTES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_TRACE_POOL_BYTES),
+        )
+    }
+
+    /// Copy `trace`, into a recycled buffer of its dimensions if there is one.
+    pub fn copy(&self, trace: &RowMajorMatrix<T>) -> RowMajorMatrix<T> {
+        let key = (trace.width(), trace.height());
+        let recycled = {
+            let mut inner = self.inner.lock().unwrap();
+            let buffer = inner.buffers.get_mut(&key).and_then(Vec::pop);
+            match &buffer {
+                Some(buffer) => {
+                    inner.stats.hits += 1;
+                    inner.stats.retained_bytes -= buffer_bytes(buffer);
+                }
+                None => inner.stats.misses += 1,
+            }
+            buffer
+        };
+        match recycled {
+            Some(mut values) => {
+                values.clone_from_slice(&trace.values);
+                RowMajorMatrix::new(values, trace.width())
+            }
+            None => trace.clone(),
+        }
+    }
+
+    /// Give back the buffer of `trace`, once it is no longer needed, for the traces of the same
+    /// dimensions.
+    pub fn recycle(&self, trace: RowMajorMatrix<T>) {
+        let key = (trace.width(), trace.height());
+        let bytes = buffer_bytes(&trace.values);
+        let mut inner = self.inner.lock().unwrap();
+        if bytes > 0 && inner.stats.retained_bytes + bytes <= self.max_bytes {
+            inner.stats.retained_bytes += bytes;
+            inner.buffers.entry(key).or_default().push(trace.values);
+        }
+    }
+
+    /// The reuse statistics of the pool.
+    pub fn stats(&self) -> TracePoolStats {
+        self.inner.lock().unwrap().stats
+    }
+}
+
+fn buffer_bytes<T>(buffer: &[T]) -> usize {
+    std::mem::size_of_val(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_pool_reuse() {
+        let pool = TracePool::new(1 << 10);
+        let trace = RowMajorMatrix::new(vec![1u32; 8], 2);
+        assert_eq!(pool.copy(&trace), trace);
+        pool.recycle(trace.clone());
+        assert_eq!(pool.stats().retained_bytes, 32);
+
+        let other = RowMajorMatrix::new(vec![2u32; 8], 4);
+        assert_eq!(pool.copy(&other), other);
+        let trace = RowMajorMatrix::new(vec![3u32; 8], 2);
+        assert_eq!(pool.copy(&trace), trace);
+
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses, stats.retained_bytes), (1, 2, 0));
+        assert!((stats.reuse_rate() - 1.0 / 3.0).abs() < 1e-9);
+
+        let disabled = TracePool::new(0);
+        disabled.recycle(trace);
+        assert_eq!(disabled.stats().retained_bytes, 0);
+    }
+}
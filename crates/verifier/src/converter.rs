@@ -1,3 +1,5 @@
+//! Conversions between BN254 points and their encodings in gnark proofs and verifying keys.
+
 use core::cmp::Ordering;
 
 use bn::{AffineG1, AffineG2, Fq, Fq2};
@@ -39,7 +41,7 @@ pub(crate) fn deserialize_with_flags(buf: &[u8]) -> Result<(Fq, CompressedPointF
 /// Asserts that the compressed point is represented as a single fq element: the x coordinate
 /// of the point. The y coordinate is then computed from the x coordinate. The final point
 /// is not checked to be on the curve for efficiency.
-pub(crate) fn unchecked_compressed_x_to_g1_point(buf: &[u8]) -> Result<AffineG1, Error> {
+pub fn unchecked_compressed_x_to_g1_point(buf: &[u8]) -> Result<AffineG1, Error> {
     let (x, m_data) = deserialize_with_flags(buf)?;
     let (y, neg_y) = AffineG1::get_ys_from_x_unchecked(x).ok_or(Error::InvalidPoint)?;
 
@@ -58,7 +60,7 @@ pub(crate) fn unchecked_compressed_x_to_g1_point(buf: &[u8]) -> Result<AffineG1,
 /// Converts an uncompressed G1 point to an AffineG1 point.
 ///
 /// Asserts that the affine point is represented as two fq elements.
-pub(crate) fn uncompressed_bytes_to_g1_point(buf: &[u8]) -> Result<AffineG1, Error> {
+pub fn uncompressed_bytes_to_g1_point(buf: &[u8]) -> Result<AffineG1, Error> {
     if buf.len() != 64 {
         return Err(Error::InvalidXLength);
     };
@@ -76,7 +78,7 @@ pub(crate) fn uncompressed_bytes_to_g1_point(buf: &[u8]) -> Result<AffineG1, Err
 /// of the point.
 /// Then, gets the y coordinate from the x coordinate.
 /// For efficiency, this function does not check that the final point is on the curve.
-pub(crate) fn unchecked_compressed_x_to_g2_point(buf: &[u8]) -> Result<AffineG2, Error> {
+pub fn unchecked_compressed_x_to_g2_point(buf: &[u8]) -> Result<AffineG2, Error> {
     if buf.len() != 64 {
         return Err(Error::InvalidXLength);
     };
@@ -101,7 +103,7 @@ pub(crate) fn unchecked_compressed_x_to_g2_point(buf: &[u8]) -> Result<AffineG2,
 /// Converts an uncompressed G2 point to an AffineG2 point.
 ///
 /// Asserts that the affine point is represented as two fq2 elements.
-pub(crate) fn uncompressed_bytes_to_g2_point(buf: &[u8]) -> Result<AffineG2, Error> {
+pub fn uncompressed_bytes_to_g2_point(buf: &[u8]) -> Result<AffineG2, Error> {
     if buf.len() != 128 {
         return Err(Error::InvalidXLength);
     }
@@ -120,3 +122,24 @@ pub(crate) fn uncompressed_bytes_to_g2_point(buf: &[u8]) -> Result<AffineG2, Err
 
     AffineG2::new(x, y).map_err(Error::Group)
 }
+
+/// Converts an AffineG1 point to its uncompressed bytes, the inverse of
+/// [`uncompressed_bytes_to_g1_point`].
+pub fn g1_point_to_uncompressed_bytes(point: &AffineG1) -> Result<[u8; 64], Error> {
+    let mut bytes = [0u8; 64];
+    point.x().to_big_endian(&mut bytes[..32]).map_err(Error::Field)?;
+    point.y().to_big_endian(&mut bytes[32..]).map_err(Error::Field)?;
+    Ok(bytes)
+}
+
+/// Converts an AffineG2 point to its uncompressed bytes, the inverse of
+/// [`uncompressed_bytes_to_g2_point`].
+pub fn g2_point_to_uncompressed_bytes(point: &AffineG2) -> Result<[u8; 128], Error> {
+    let mut bytes = [0u8; 128];
+    let (x, y) = (point.x(), point.y());
+    x.imaginary().to_big_endian(&mut bytes[..32]).map_err(Error::Field)?;
+    x.real().to_big_endian(&mut bytes[32..64]).map_err(Error::Field)?;
+    y.imaginary().to_big_endian(&mut bytes[64..96]).map_err(Error::Field)?;
+    y.real().to_big_endian(&mut bytes[96..]).map_err(Error::Field)?;
+    Ok(bytes)
+}
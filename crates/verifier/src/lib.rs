@@ -17,8 +17,9 @@ lazy_static! {
 }
 
 mod constants;
-mod converter;
+pub mod converter;
 mod error;
+pub use error::Error;
 
 mod utils;
 pub use utils::*;
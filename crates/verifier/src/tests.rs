@@ -1,6 +1,8 @@
 use rstest::rstest;
 use serial_test::serial;
-use sp1_sdk::{install::try_install_circuit_artifacts, HashableKey, ProverClient, SP1Stdin};
+use sp1_sdk::{
+    install::try_install_circuit_artifacts, HashableKey, ProverClient, SP1Proof, SP1Stdin,
+};
 use test_artifacts::{
     FIBONACCI_BLAKE3_ELF, FIBONACCI_ELF, GROTH16_BLAKE3_ELF, GROTH16_ELF, PLONK_BLAKE3_ELF,
     PLONK_ELF,
@@ -123,6 +125,42 @@ fn test_groth16_verifier(#[case] elf: &[u8], #[case] groth16_elf: &[u8]) {
     let _ = client.execute(groth16_elf, &stdin).run().unwrap();
 }
 
+#[rstest]
+#[case(FIBONACCI_ELF)]
+#[case(FIBONACCI_BLAKE3_ELF)]
+#[serial]
+fn test_verify_rerandomized_groth16(#[case] elf: &[u8]) {
+    // Set up the pk and vk.
+    let client = ProverClient::from_env();
+    let (pk, vk) = client.setup(elf);
+
+    // Generate the Groth16 proof and re-randomize it.
+    let sp1_proof_with_public_values = client.prove(&pk, &SP1Stdin::new()).groth16().run().unwrap();
+    let SP1Proof::Groth16(groth16_proof) = &sp1_proof_with_public_values.proof else {
+        panic!("expected a Groth16 proof");
+    };
+    let mut rerandomized = sp1_proof_with_public_values.clone();
+    rerandomized.proof =
+        SP1Proof::Groth16(groth16_proof.rerandomize(&crate::GROTH16_VK_BYTES).unwrap());
+
+    // The re-randomized proof differs from the original one, but proves the same statement.
+    let proof = sp1_proof_with_public_values.bytes();
+    let rerandomized_proof = rerandomized.bytes();
+    assert_ne!(proof, rerandomized_proof);
+    assert_eq!(proof[..4], rerandomized_proof[..4]);
+
+    let public_inputs = rerandomized.public_values.to_vec();
+    let vkey_hash = vk.bytes32();
+    crate::Groth16Verifier::verify(
+        &rerandomized_proof,
+        &public_inputs,
+        &vkey_hash,
+        &crate::GROTH16_VK_BYTES,
+    )
+    .expect("re-randomized Groth16 proof is invalid");
+    client.verify(&rerandomized, &vk).expect("re-randomized proof is invalid");
+}
+
 #[rstest]
 #[case(FIBONACCI_ELF)]
 #[case(FIBONACCI_BLAKE3_ELF)]
@@ -244,3 +282,26 @@ fn test_vkeys() {
     let s3_vkey_bytes = std::fs::read(s3_vkey_path).unwrap();
     assert_eq!(s3_vkey_bytes, *crate::PLONK_VK_BYTES);
 }
+
+#[test]
+fn test_uncompressed_points_round_trip() {
+    use crate::converter::{
+        g1_point_to_uncompressed_bytes, g2_point_to_uncompressed_bytes,
+        uncompressed_bytes_to_g1_point, uncompressed_bytes_to_g2_point,
+    };
+    use bn::{AffineG1, AffineG2, Fr, Group, G1, G2};
+
+    let scalar = Fr::from_str("123456789").unwrap();
+    let g1 = AffineG1::from_jacobian(G1::one() * scalar).unwrap();
+    let g2 = AffineG2::from_jacobian(G2::one() * scalar).unwrap();
+
+    let g1_bytes = g1_point_to_uncompressed_bytes(&g1).unwrap();
+    let g2_bytes = g2_point_to_uncompressed_bytes(&g2).unwrap();
+    assert_eq!(uncompressed_bytes_to_g1_point(&g1_bytes).unwrap(), g1);
+    assert_eq!(uncompressed_bytes_to_g2_point(&g2_bytes).unwrap(), g2);
+
+    // The decoders reject a point off the curve.
+    let mut g1_bytes = g1_bytes;
+    g1_bytes[63] ^= 1;
+    assert!(uncompressed_bytes_to_g1_point(&g1_bytes).is_err());
+}
@@ -3,7 +3,10 @@ use crate::{read_vec_raw, syscall_write, ReadVecResult};
 use serde::{de::DeserializeOwned, Serialize};
 use std::io::{Result, Write};
 
-pub use sp1_primitives::consts::fd::*;
+pub use sp1_primitives::{
+    consts::fd::*,
+    input_chunks::{chunk_digest, InputChunksHeader},
+};
 
 /// A writer that writes to a file descriptor inside the zkVM.
 struct SyscallWriter {
@@ -87,6 +90,70 @@ pub fn read<T: DeserializeOwned>() -> T {
     bincode::deserialize(&vec).expect("deserialization failed")
 }
 
+/// Read an input written in chunks by the host, committing to its [`InputChunksHeader`].
+///
+/// The header is read and committed to the public values stream first, followed by the digests of
+/// the chunks, which are checked against it. The returned iterator then reads the chunks one at a
+/// time, so the program never holds the whole input, and panics on a chunk that does not match
+/// its digest before yielding it.
+///
+/// ### Examples
+/// ```ignore
+/// let mut total = 0u64;
+/// for chunk in sp1_zkvm::io::read_input_chunks() {
+///     total += chunk.iter().map(|&b| b as u64).sum::<u64>();
+/// }
+/// sp1_zkvm::io::commit(&total);
+/// ```
+pub fn read_input_chunks() -> InputChunks {
+    let header: InputChunksHeader = read();
+    commit(&header);
+    let chunk_digests: Vec<[u8; 32]> = read();
+    assert!(
+        header.matches_chunk_digests(&chunk_digests),
+        "input chunk digests do not match their header"
+    );
+    InputChunks { header, chunk_digests, next: 0, len: 0 }
+}
+
+/// The chunks of an input, read by [`read_input_chunks`].
+pub struct InputChunks {
+    header: InputChunksHeader,
+    chunk_digests: Vec<[u8; 32]>,
+    next: usize,
+    len: u64,
+}
+
+impl InputChunks {
+    /// The header the chunks are checked against.
+    pub fn header(&self) -> &InputChunksHeader {
+        &self.header
+    }
+}
+
+impl Iterator for InputChunks {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let expected = self.chunk_digests.get(self.next)?;
+        let chunk = read_vec();
+        assert!(
+            chunk_digest(&chunk) == *expected,
+            "input chunk {} does not match its digest",
+            self.next
+        );
+        self.next += 1;
+        self.len += chunk.len() as u64;
+        if self.next == self.chunk_digests.len() {
+            assert!(
+                self.len == self.header.len,
+                "input chunks do not match the length of their header"
+            );
+        }
+        Some(chunk)
+    }
+}
+
 /// Commit a serializable object to the public values stream.
 ///
 /// ### Examples
@@ -157,6 +224,39 @@ pub fn hint_slice(buf: &[u8]) {
     my_reader.write_all(buf).unwrap();
 }
 
+/// Mark the start of a program phase named `name`.
+///
+/// If the prover is configured with a minimum phase shard size, the executor ends the current
+/// shard at the marker once the shard is at least that long, so that the shards of a phase do not
+/// depend on the phases before it. The marker is ignored in unconstrained blocks.
+///
+/// ### Examples
+/// ```ignore
+/// sp1_zkvm::io::phase("parse");
+/// let block = parse(&input);
+/// sp1_zkvm::io::phase("execute");
+/// execute(&block);
+/// ```
+pub fn phase(name: &str) {
+    unsafe {
+        syscall_write(FD_PHASE, name.as_ptr(), name.len());
+    }
+}
+
+/// Write `buf` to the output stream of the program.
+///
+/// The host receives the output as the program executes, e.g. to follow the progress of a long
+/// running program, both when executing and when proving it. Unlike the public values, the output
+/// is not committed to by the proof.
+///
+/// ### Examples
+/// ```ignore
+/// sp1_zkvm::io::output(b"processed block 42\n");
+/// ```
+pub fn output(buf: &[u8]) {
+    SyscallWriter { fd: FD_OUTPUT }.write_all(buf).unwrap();
+}
+
 /// Write the data `buf` to the file descriptor `fd`.
 ///
 /// ### Examples